@@ -1,13 +1,13 @@
 use core::time::Duration;
 use nodo::{codelet::ScheduleBuilder, prelude::*};
-use nodo_runtime::Runtime;
+use nodo_runtime::{app_info, Runtime};
 use nodo_std::{Sink, Source};
 
 #[derive(Debug, Clone)]
 struct Ping;
 
 fn main() -> eyre::Result<()> {
-    let mut rt = Runtime::new();
+    let mut rt = Runtime::new().with_app_info(app_info!());
 
     rt.enable_inspector("tcp://localhost:54399")?;
 
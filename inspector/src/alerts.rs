@@ -0,0 +1,667 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::csv_export::escape;
+use eyre::Result;
+use nodo::{
+    codelet::{Transition, TransitionStatistics},
+    prelude::Severity,
+};
+use nodo_runtime::{InspectorCodeletReport, SourcedNodeletId, SourcedReport};
+use regex::Regex;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::Path,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Alert rules as stored in a TOML file
+///
+/// ```toml
+/// [[rule]]
+/// name = "mostly skipping"
+/// severity = "Warn"
+/// codelet = "^camera"
+/// when = { metric = "skip_percent", op = ">", value = 0.95 }
+///
+/// [[rule]]
+/// name = "failed"
+/// severity = "Error"
+/// when = { status_severity = "Error" }
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertConfig {
+    #[serde(default)]
+    pub rule: Vec<AlertRuleConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertRuleConfig {
+    pub name: String,
+
+    #[serde(default = "default_severity")]
+    pub severity: Severity,
+
+    /// Only codelets with a name or sequence matching this regex are checked
+    pub codelet: Option<String>,
+
+    pub when: Condition,
+}
+
+fn default_severity() -> Severity {
+    Severity::Warn
+}
+
+/// Condition under which a rule fires for a codelet
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum Condition {
+    Threshold {
+        metric: Metric,
+        op: Comparison,
+        value: f64,
+    },
+    StatusSeverity {
+        status_severity: Severity,
+    },
+    StatusLabel {
+        status_label: String,
+    },
+}
+
+/// Step statistics of a codelet which can be checked against a threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// Fraction of skipped steps in [0, 1]
+    SkipPercent,
+
+    /// Average step duration in milliseconds
+    StepAvgMs,
+
+    /// Maximum step duration in milliseconds. Percentiles are not reported by the runtime.
+    StepMaxMs,
+
+    /// Average step period in milliseconds
+    PeriodAvgMs,
+
+    /// RMS deviation of steps from the periodic grid in milliseconds
+    JitterRmsMs,
+
+    /// Number of steps which overran their deadline
+    DeadlineMisses,
+}
+
+impl Metric {
+    fn get(&self, report: &InspectorCodeletReport) -> Option<f64> {
+        let step: &TransitionStatistics = &report.statistics.transitions[Transition::Step];
+        match self {
+            Metric::SkipPercent => {
+                (step.skipped_count + step.duration.count() > 0).then(|| step.skip_percent() as f64)
+            }
+            Metric::StepAvgMs => step.duration.average_ms().map(f64::from),
+            Metric::StepMaxMs => step.duration.max_ms().map(f64::from),
+            Metric::PeriodAvgMs => step.period.average_ms().map(f64::from),
+            Metric::JitterRmsMs => step.jitter.rms().map(|x| x.as_secs_f64() * 1000.),
+            Metric::DeadlineMisses => Some(report.statistics.deadline_miss_count as f64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Comparison {
+    #[serde(rename = ">")]
+    Greater,
+    #[serde(rename = ">=")]
+    GreaterEqual,
+    #[serde(rename = "<")]
+    Less,
+    #[serde(rename = "<=")]
+    LessEqual,
+    #[serde(rename = "==")]
+    Equal,
+}
+
+impl Comparison {
+    fn holds(&self, lhs: f64, rhs: f64) -> bool {
+        match self {
+            Comparison::Greater => lhs > rhs,
+            Comparison::GreaterEqual => lhs >= rhs,
+            Comparison::Less => lhs < rhs,
+            Comparison::LessEqual => lhs <= rhs,
+            Comparison::Equal => lhs == rhs,
+        }
+    }
+}
+
+/// A rule with compiled codelet selector
+#[derive(Debug, Clone)]
+pub struct AlertRule {
+    pub name: String,
+    pub severity: Severity,
+    pub codelet: Option<Regex>,
+    pub when: Condition,
+}
+
+impl AlertRule {
+    pub fn compile(config: AlertRuleConfig) -> Result<Self> {
+        Ok(Self {
+            name: config.name,
+            severity: config.severity,
+            codelet: config.codelet.as_deref().map(Regex::new).transpose()?,
+            when: config.when,
+        })
+    }
+
+    fn selects(&self, report: &InspectorCodeletReport) -> bool {
+        self.codelet
+            .as_ref()
+            .is_none_or(|re| re.is_match(&report.name) || re.is_match(&report.sequence))
+    }
+
+    /// The observed value if the rule fires for the codelet
+    fn check(&self, report: &InspectorCodeletReport) -> Option<String> {
+        if !self.selects(report) {
+            return None;
+        }
+
+        match &self.when {
+            Condition::Threshold { metric, op, value } => {
+                let observed = metric.get(report)?;
+                op.holds(observed, *value).then(|| format!("{observed:.3}"))
+            }
+            Condition::StatusSeverity { status_severity } => {
+                let status = report.status.as_ref()?;
+                (status.severity == *status_severity).then(|| status.label.clone())
+            }
+            Condition::StatusLabel { status_label } => {
+                let status = report.status.as_ref()?;
+                status
+                    .label
+                    .eq_ignore_ascii_case(status_label)
+                    .then(|| status.label.clone())
+            }
+        }
+    }
+}
+
+/// Loads and compiles alert rules from a TOML file
+pub fn load_alert_rules<P: AsRef<Path>>(path: P) -> Result<Vec<AlertRule>> {
+    let config: AlertConfig = toml::from_str(&std::fs::read_to_string(path)?)?;
+    config.rule.into_iter().map(AlertRule::compile).collect()
+}
+
+/// Identifies an alert: a rule firing for a codelet
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AlertKey {
+    pub rule: String,
+    pub codelet: SourcedNodeletId,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub severity: Severity,
+    pub sequence: String,
+    pub name: String,
+
+    /// Value observed when the rule fired the last time
+    pub value: String,
+
+    pub first_seen: Instant,
+    pub last_seen: Instant,
+
+    /// Number of reports in which the rule fired
+    pub count: u64,
+
+    /// True if the rule fired for the most recent report of the source
+    pub is_active: bool,
+}
+
+/// All alerts seen so far including resolved ones
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AlertState {
+    alerts: BTreeMap<AlertKey, Alert>,
+}
+
+impl AlertState {
+    pub fn is_empty(&self) -> bool {
+        self.alerts.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.alerts.len()
+    }
+
+    /// Alerts for display: active before resolved, then by severity and most recent first
+    pub fn sorted(&self) -> Vec<(&AlertKey, &Alert)> {
+        let mut result: Vec<_> = self.alerts.iter().collect();
+        result.sort_by(|(_, a), (_, b)| {
+            (b.is_active, b.severity, b.last_seen).cmp(&(a.is_active, a.severity, a.last_seen))
+        });
+        result
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertEventKind {
+    Raised,
+    Resolved,
+}
+
+/// Change of an alert caused by a report
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertEvent {
+    pub kind: AlertEventKind,
+    pub key: AlertKey,
+    pub alert: Alert,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AlertUpdate {
+    pub state: AlertState,
+    pub events: Vec<AlertEvent>,
+}
+
+/// Evaluates all rules against a report and returns the new alert state
+///
+/// Alerts of other sources are left unchanged. Alerts of the same source which did not fire are
+/// resolved but kept with their history.
+pub fn evaluate(
+    rules: &[AlertRule],
+    report: &SourcedReport,
+    previous: &AlertState,
+    now: Instant,
+) -> AlertUpdate {
+    let mut state = previous.clone();
+    let mut events = Vec::new();
+
+    let mut fired = Vec::new();
+    for (id, codelet) in report.report.iter() {
+        for rule in rules {
+            let Some(value) = rule.check(codelet) else {
+                continue;
+            };
+
+            let key = AlertKey {
+                rule: rule.name.clone(),
+                codelet: SourcedNodeletId {
+                    source: report.source.clone(),
+                    id: *id,
+                },
+            };
+
+            let alert = state.alerts.entry(key.clone()).or_insert_with(|| Alert {
+                severity: rule.severity,
+                sequence: codelet.sequence.to_string(),
+                name: codelet.name.to_string(),
+                value: String::new(),
+                first_seen: now,
+                last_seen: now,
+                count: 0,
+                is_active: false,
+            });
+            alert.value = value;
+            alert.last_seen = now;
+            alert.count += 1;
+            if !alert.is_active {
+                alert.is_active = true;
+                events.push(AlertEvent {
+                    kind: AlertEventKind::Raised,
+                    key: key.clone(),
+                    alert: alert.clone(),
+                });
+            }
+
+            fired.push(key);
+        }
+    }
+
+    for (key, alert) in state.alerts.iter_mut() {
+        if alert.is_active && key.codelet.source == report.source && !fired.contains(key) {
+            alert.is_active = false;
+            events.push(AlertEvent {
+                kind: AlertEventKind::Resolved,
+                key: key.clone(),
+                alert: alert.clone(),
+            });
+        }
+    }
+
+    AlertUpdate { state, events }
+}
+
+pub const ALERT_LOG_HEADER: &str = "timestamp,event,severity,rule,source,sequence,name,value,count";
+
+/// Formats a single line of the alert log. Timestamp is wall-clock time in seconds since UNIX
+/// epoch.
+pub fn format_alert_event(timestamp: f64, event: &AlertEvent) -> String {
+    let kind = match event.kind {
+        AlertEventKind::Raised => "raised",
+        AlertEventKind::Resolved => "resolved",
+    };
+    format!(
+        "{timestamp:.3},{kind},{:?},{},{},{},{},{},{}",
+        event.alert.severity,
+        escape(&event.key.rule),
+        escape(&event.key.codelet.source),
+        escape(&event.alert.sequence),
+        escape(&event.alert.name),
+        escape(&event.alert.value),
+        event.alert.count
+    )
+}
+
+/// Appends raised and resolved alerts to a file for post-analysis
+pub struct AlertLog {
+    writer: BufWriter<File>,
+}
+
+impl AlertLog {
+    /// Opens the log file for appending. The header is written if the file is new.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let is_new = file.metadata()?.len() == 0;
+        let mut writer = BufWriter::new(file);
+        if is_new {
+            writeln!(writer, "{ALERT_LOG_HEADER}")?;
+        }
+        Ok(Self { writer })
+    }
+
+    pub fn push(&mut self, events: &[AlertEvent]) -> Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        for event in events {
+            writeln!(self.writer, "{}", format_alert_event(timestamp, event))?;
+        }
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::time::Duration;
+    use nodo::{
+        codelet::{NodeletId, Statistics, WorkerId},
+        prelude::DefaultStatus,
+    };
+    use nodo_runtime::{InspectorReport, RenderedStatus};
+
+    const RULES: &str = r#"
+        [[rule]]
+        name = "skipping"
+        codelet = "^cam"
+        when = { metric = "skip_percent", op = ">", value = 0.95 }
+
+        [[rule]]
+        name = "slow"
+        severity = "Error"
+        when = { metric = "step_max_ms", op = ">=", value = 20.0 }
+
+        [[rule]]
+        name = "failed"
+        severity = "Error"
+        when = { status_label = "FAILED" }
+
+        [[rule]]
+        name = "overrun"
+        when = { metric = "deadline_misses", op = ">", value = 0 }
+    "#;
+
+    fn rules() -> Vec<AlertRule> {
+        let config: AlertConfig = toml::from_str(RULES).unwrap();
+        config
+            .rule
+            .into_iter()
+            .map(|r| AlertRule::compile(r).unwrap())
+            .collect()
+    }
+
+    fn codelet(name: &str, steps: &[u64], skipped: u64) -> InspectorCodeletReport {
+        let mut statistics = Statistics::new();
+        let step = &mut statistics.transitions[Transition::Step];
+        for &ms in steps {
+            step.duration.push(Duration::from_millis(ms));
+        }
+        step.skipped_count = skipped;
+
+        InspectorCodeletReport {
+            sequence: "seq".into(),
+            name: name.into(),
+            typename: "Foo".into(),
+            status: None,
+            statistics,
+            is_warmup: false,
+            labels: Vec::new(),
+            suspected_inactive: false,
+            suspend_resume_count: 0,
+            deadline: None,
+            start_diagnostics: Vec::new(),
+            schedule: Default::default(),
+            progress: None,
+            unknown: Default::default(),
+        }
+    }
+
+    fn report(source: &str, codelets: Vec<InspectorCodeletReport>) -> SourcedReport {
+        let mut report = InspectorReport::default();
+        for (i, c) in codelets.into_iter().enumerate() {
+            report.push(NodeletId(WorkerId(0), i as u32), c);
+        }
+        SourcedReport {
+            source: source.into(),
+            report,
+        }
+    }
+
+    fn key(rule: &str, source: &str, id: u32) -> AlertKey {
+        AlertKey {
+            rule: rule.into(),
+            codelet: SourcedNodeletId {
+                source: source.into(),
+                id: NodeletId(WorkerId(0), id),
+            },
+        }
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let rules = rules();
+        assert_eq!(rules.len(), 4);
+        assert_eq!(rules[0].severity, Severity::Warn);
+        assert_eq!(
+            rules[1].when,
+            Condition::Threshold {
+                metric: Metric::StepMaxMs,
+                op: Comparison::GreaterEqual,
+                value: 20.
+            }
+        );
+        assert_eq!(
+            rules[2].when,
+            Condition::StatusLabel {
+                status_label: "FAILED".into()
+            }
+        );
+
+        // typos are rejected
+        assert!(toml::from_str::<AlertConfig>(
+            "[[rule]]\nname = \"x\"\nwhen = { metric = \"skip\", op = \">\", value = 1 }"
+        )
+        .is_err());
+        assert!(toml::from_str::<AlertConfig>(
+            "[[rule]]\nname = \"x\"\nseverity = \"Warn\"\ncodelt = \"a\"\nwhen = { status_label = \"a\" }"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_thresholds_and_selector() {
+        let rules = rules();
+        let t0 = Instant::now();
+
+        let update = evaluate(
+            &rules,
+            &report(
+                "a",
+                vec![
+                    codelet("camera", &[1], 99),
+                    codelet("lidar", &[1], 99),
+                    codelet("planner", &[5, 25], 0),
+                ],
+            ),
+            &AlertState::default(),
+            t0,
+        );
+
+        // only the camera matches the selector of the skip rule
+        assert_eq!(update.state.len(), 2);
+        let skipping = update.state.alerts.get(&key("skipping", "a", 0)).unwrap();
+        assert_eq!(skipping.value, "0.990");
+        assert_eq!(skipping.severity, Severity::Warn);
+        assert!(skipping.is_active);
+        let slow = update.state.alerts.get(&key("slow", "a", 2)).unwrap();
+        assert_eq!(
+            (slow.name.as_str(), slow.value.as_str()),
+            ("planner", "25.000")
+        );
+        assert_eq!(slow.severity, Severity::Error);
+
+        assert_eq!(update.events.len(), 2);
+        assert!(update
+            .events
+            .iter()
+            .all(|e| e.kind == AlertEventKind::Raised));
+
+        // sorted by severity
+        let sorted = update.state.sorted();
+        assert_eq!(sorted[0].0.rule, "slow");
+        assert_eq!(sorted[1].0.rule, "skipping");
+    }
+
+    #[test]
+    fn test_status_and_missing_values() {
+        let rules = rules();
+
+        let mut failed = codelet("camera", &[], 0);
+        failed.status = Some(RenderedStatus {
+            label: "Failed".into(),
+            status: DefaultStatus::Running,
+            code: 0,
+            severity: Severity::Error,
+            skip_reason: None,
+        });
+        let mut overrun = codelet("b", &[], 0);
+        overrun.statistics.deadline_miss_count = 3;
+
+        let update = evaluate(
+            &rules,
+            &report("a", vec![failed, overrun]),
+            &AlertState::default(),
+            Instant::now(),
+        );
+
+        // codelets which never stepped have no skip percent and no step duration
+        let mut keys: Vec<_> = update
+            .state
+            .sorted()
+            .into_iter()
+            .map(|(k, _)| k.clone())
+            .collect();
+        keys.sort();
+        assert_eq!(keys, [key("failed", "a", 0), key("overrun", "a", 1)]);
+        assert_eq!(
+            update
+                .state
+                .alerts
+                .get(&key("failed", "a", 0))
+                .unwrap()
+                .value,
+            "Failed"
+        );
+        assert_eq!(
+            update
+                .state
+                .alerts
+                .get(&key("overrun", "a", 1))
+                .unwrap()
+                .value,
+            "3.000"
+        );
+    }
+
+    #[test]
+    fn test_history() {
+        let rules = rules();
+        let t0 = Instant::now();
+        let ms = Duration::from_millis;
+
+        let slow = || report("a", vec![codelet("planner", &[30], 0)]);
+        let fast = || report("a", vec![codelet("planner", &[1], 0)]);
+
+        let s1 = evaluate(&rules, &slow(), &AlertState::default(), t0).state;
+        let s2 = evaluate(&rules, &slow(), &s1, t0 + ms(100)).state;
+
+        let alert = s2.alerts.get(&key("slow", "a", 0)).unwrap();
+        assert_eq!(alert.count, 2);
+        assert_eq!(alert.first_seen, t0);
+        assert_eq!(alert.last_seen, t0 + ms(100));
+
+        // the evaluation is pure
+        assert_eq!(s1.alerts.get(&key("slow", "a", 0)).unwrap().count, 1);
+
+        // reports of other sources do not resolve the alert
+        let other = evaluate(&rules, &report("b", vec![]), &s2, t0 + ms(150));
+        assert!(other.events.is_empty());
+        assert_eq!(other.state, s2);
+
+        // resolved alerts are kept
+        let u3 = evaluate(&rules, &fast(), &s2, t0 + ms(200));
+        assert_eq!(u3.events.len(), 1);
+        assert_eq!(u3.events[0].kind, AlertEventKind::Resolved);
+        let alert = u3.state.alerts.get(&key("slow", "a", 0)).unwrap();
+        assert!(!alert.is_active);
+        assert_eq!(alert.last_seen, t0 + ms(100));
+
+        // raised again with its history
+        let u4 = evaluate(&rules, &slow(), &u3.state, t0 + ms(300));
+        assert_eq!(u4.events.len(), 1);
+        assert_eq!(u4.events[0].kind, AlertEventKind::Raised);
+        let alert = u4.state.alerts.get(&key("slow", "a", 0)).unwrap();
+        assert!(alert.is_active);
+        assert_eq!(alert.count, 3);
+        assert_eq!(alert.first_seen, t0);
+
+        // a codelet disappearing resolves its alerts
+        let u5 = evaluate(&rules, &report("a", vec![]), &u4.state, t0 + ms(400));
+        assert_eq!(u5.events[0].kind, AlertEventKind::Resolved);
+    }
+
+    #[test]
+    fn test_format_alert_event() {
+        let rules = rules();
+        let update = evaluate(
+            &rules,
+            &report("tcp://x", vec![codelet("plan,ner", &[30], 0)]),
+            &AlertState::default(),
+            Instant::now(),
+        );
+        assert_eq!(
+            format_alert_event(12.5, &update.events[0]),
+            "12.500,raised,Error,slow,tcp://x,seq,\"plan,ner\",30.000,1"
+        );
+    }
+}
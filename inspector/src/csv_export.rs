@@ -0,0 +1,334 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use eyre::Result;
+use nodo::codelet::{NodeletId, Transition, TransitionStatistics};
+use nodo_runtime::{InspectorCodeletReport, SourcedNodeletId, SourcedReport};
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Change of step statistics of a codelet between two consecutive reports
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatisticsDelta {
+    /// Counters decreased, e.g. because the runtime was restarted. The current report is used as
+    /// the new baseline.
+    Reset,
+
+    Interval {
+        /// Number of executed (not skipped) steps
+        step_count: u64,
+
+        /// Average step duration in milliseconds
+        step_avg_ms: Option<f32>,
+
+        /// Maximum step duration in milliseconds. Only set if the all-time maximum increased
+        /// during the interval as individual durations are not reported.
+        step_max_ms: Option<f32>,
+
+        /// Average step period in milliseconds
+        period_avg_ms: Option<f32>,
+
+        /// Fraction of skipped steps
+        skip_percent: f32,
+    },
+}
+
+/// Computes [StatisticsDelta]s from consecutive reports
+#[derive(Default)]
+pub struct StatisticsDiffer {
+    previous: HashMap<SourcedNodeletId, TransitionStatistics>,
+}
+
+impl StatisticsDiffer {
+    /// Computes the change since the previous report of the codelet. Returns None if the codelet
+    /// was not part of the previous report of its source.
+    pub fn diff(
+        &mut self,
+        key: &SourcedNodeletId,
+        current: &TransitionStatistics,
+    ) -> Option<StatisticsDelta> {
+        let previous = self.previous.insert(key.clone(), current.clone())?;
+
+        let step_count = current
+            .duration
+            .count()
+            .checked_sub(previous.duration.count());
+        let step_total = current
+            .duration
+            .total()
+            .checked_sub(previous.duration.total());
+        let period_count = current.period.count().checked_sub(previous.period.count());
+        let period_total = current.period.total().checked_sub(previous.period.total());
+        let skipped_count = current.skipped_count.checked_sub(previous.skipped_count);
+
+        let (
+            Some(step_count),
+            Some(step_total),
+            Some(period_count),
+            Some(period_total),
+            Some(skipped_count),
+        ) = (
+            step_count,
+            step_total,
+            period_count,
+            period_total,
+            skipped_count,
+        )
+        else {
+            return Some(StatisticsDelta::Reset);
+        };
+
+        let total_count = step_count + skipped_count;
+
+        Some(StatisticsDelta::Interval {
+            step_count,
+            step_avg_ms: average_ms(step_total.as_secs_f32(), step_count),
+            step_max_ms: current
+                .duration
+                .max_ms()
+                .filter(|&max| previous.duration.max_ms().is_none_or(|prev| max > prev)),
+            period_avg_ms: average_ms(period_total.as_secs_f32(), period_count),
+            skip_percent: if total_count == 0 {
+                0.
+            } else {
+                skipped_count as f32 / total_count as f32
+            },
+        })
+    }
+
+    /// Forgets all codelets of the source which are not in the given list
+    pub fn retain(&mut self, source: &str, ids: &[NodeletId]) {
+        self.previous
+            .retain(|key, _| key.source != source || ids.contains(&key.id));
+    }
+}
+
+fn average_ms(total_secs: f32, count: u64) -> Option<f32> {
+    (count > 0).then(|| total_secs * 1000. / count as f32)
+}
+
+pub const CSV_HEADER: &str =
+    "timestamp,source,sequence,name,event,step_count,step_avg_ms,step_max_ms,period_avg_ms,skip_percent";
+
+/// Formats a single CSV row. Timestamp is wall-clock time in seconds since UNIX epoch.
+pub fn format_csv_row(
+    timestamp: f64,
+    source: &str,
+    report: &InspectorCodeletReport,
+    delta: &StatisticsDelta,
+) -> String {
+    let prefix = format!(
+        "{timestamp:.3},{},{},{}",
+        escape(source),
+        escape(&report.sequence),
+        escape(&report.name)
+    );
+
+    match delta {
+        StatisticsDelta::Reset => format!("{prefix},reset,,,,,"),
+        StatisticsDelta::Interval {
+            step_count,
+            step_avg_ms,
+            step_max_ms,
+            period_avg_ms,
+            skip_percent,
+        } => format!(
+            "{prefix},data,{step_count},{},{},{},{skip_percent:.3}",
+            format_opt(*step_avg_ms),
+            format_opt(*step_max_ms),
+            format_opt(*period_avg_ms),
+        ),
+    }
+}
+
+fn format_opt(value: Option<f32>) -> String {
+    value.map_or_else(String::new, |x| format!("{x:.3}"))
+}
+
+pub fn escape(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Appends step statistics of selected codelets to a CSV file for every received report
+pub struct CsvExporter {
+    writer: BufWriter<File>,
+    select: Regex,
+    differ: StatisticsDiffer,
+}
+
+impl CsvExporter {
+    /// Creates the CSV file. Codelets are selected if their name or sequence matches the regex.
+    pub fn create<P: AsRef<Path>>(path: P, select: Regex) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{CSV_HEADER}")?;
+        Ok(Self {
+            writer,
+            select,
+            differ: StatisticsDiffer::default(),
+        })
+    }
+
+    pub fn push(&mut self, sourced: &SourcedReport) -> Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+
+        let mut entries: Vec<_> = sourced.report.iter().collect();
+        entries.sort_by(|(_, a), (_, b)| (&a.sequence, &a.name).cmp(&(&b.sequence, &b.name)));
+
+        for (id, report) in entries.iter() {
+            if !self.select.is_match(&report.name) && !self.select.is_match(&report.sequence) {
+                continue;
+            }
+
+            let key = SourcedNodeletId {
+                source: sourced.source.clone(),
+                id: **id,
+            };
+            if let Some(delta) = self
+                .differ
+                .diff(&key, &report.statistics.transitions[Transition::Step])
+            {
+                writeln!(
+                    self.writer,
+                    "{}",
+                    format_csv_row(timestamp, &sourced.source, report, &delta)
+                )?;
+            }
+        }
+
+        let ids: Vec<NodeletId> = entries.iter().map(|(id, _)| **id).collect();
+        self.differ.retain(&sourced.source, &ids);
+
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::time::Duration;
+    use nodo::codelet::{Statistics, WorkerId};
+
+    fn key(id: u32) -> SourcedNodeletId {
+        SourcedNodeletId {
+            source: "tcp://localhost:54399".into(),
+            id: NodeletId(WorkerId(0), id),
+        }
+    }
+
+    fn stats(steps: &[u64], skipped: u64) -> TransitionStatistics {
+        let mut stats = TransitionStatistics::new();
+        for &ms in steps {
+            stats.duration.push(Duration::from_millis(ms));
+            stats.period.push(Duration::from_millis(10));
+        }
+        stats.skipped_count = skipped;
+        stats
+    }
+
+    #[test]
+    fn test_diff_interval() {
+        let mut differ = StatisticsDiffer::default();
+        assert_eq!(differ.diff(&key(0), &stats(&[2, 4], 0)), None);
+        assert_eq!(
+            differ.diff(&key(0), &stats(&[2, 4, 1, 3], 2)),
+            Some(StatisticsDelta::Interval {
+                step_count: 2,
+                step_avg_ms: Some(2.),
+                step_max_ms: None,
+                period_avg_ms: Some(10.),
+                skip_percent: 0.5,
+            })
+        );
+        assert_eq!(
+            differ.diff(&key(0), &stats(&[2, 4, 1, 3, 8], 2)),
+            Some(StatisticsDelta::Interval {
+                step_count: 1,
+                step_avg_ms: Some(8.),
+                step_max_ms: Some(8.),
+                period_avg_ms: Some(10.),
+                skip_percent: 0.,
+            })
+        );
+    }
+
+    #[test]
+    fn test_diff_reset() {
+        let mut differ = StatisticsDiffer::default();
+        differ.diff(&key(0), &stats(&[2, 4, 6], 0));
+        assert_eq!(
+            differ.diff(&key(0), &stats(&[1], 0)),
+            Some(StatisticsDelta::Reset)
+        );
+        assert!(matches!(
+            differ.diff(&key(0), &stats(&[1, 1], 0)),
+            Some(StatisticsDelta::Interval { step_count: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_diff_appear_disappear() {
+        let mut differ = StatisticsDiffer::default();
+        differ.diff(&key(0), &stats(&[1], 0));
+        differ.diff(&key(1), &stats(&[1], 0));
+
+        differ.retain("tcp://localhost:54399", &[key(1).id]);
+        differ.retain("other", &[]);
+
+        assert_eq!(differ.diff(&key(0), &stats(&[1, 1], 0)), None);
+        assert!(differ.diff(&key(1), &stats(&[1, 1], 0)).is_some());
+    }
+
+    #[test]
+    fn test_format_csv_row() {
+        let report = InspectorCodeletReport {
+            sequence: "seq".into(),
+            name: "foo,bar".into(),
+            typename: "Foo".into(),
+            status: None,
+            statistics: Statistics::new(),
+            is_warmup: false,
+            labels: Vec::new(),
+            suspected_inactive: false,
+            suspend_resume_count: 0,
+            deadline: None,
+            start_diagnostics: Vec::new(),
+            schedule: Default::default(),
+            progress: None,
+            unknown: Default::default(),
+        };
+
+        assert_eq!(
+            format_csv_row(
+                12.5,
+                "src",
+                &report,
+                &StatisticsDelta::Interval {
+                    step_count: 3,
+                    step_avg_ms: Some(1.25),
+                    step_max_ms: None,
+                    period_avg_ms: Some(10.),
+                    skip_percent: 0.25,
+                }
+            ),
+            "12.500,src,seq,\"foo,bar\",data,3,1.250,,10.000,0.250"
+        );
+        assert_eq!(
+            format_csv_row(12.5, "src", &report, &StatisticsDelta::Reset),
+            "12.500,src,seq,\"foo,bar\",reset,,,,,"
+        );
+    }
+}
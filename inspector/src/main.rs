@@ -1,11 +1,22 @@
+mod alerts;
+mod csv_export;
+mod sort;
+mod ui_state;
+
+use alerts::{evaluate, load_alert_rules, AlertLog, AlertState};
 use clap::Parser;
 use core::time::Duration;
+use csv_export::CsvExporter;
 use eyre::Result;
 use nodo::{
     codelet::{NodeletId, Transition, TransitionStatistics},
-    prelude::DefaultStatus,
+    prelude::Severity,
+};
+use nodo_core::{fmt_bytes, fmt_duration};
+use nodo_runtime::{
+    InspectorClient, InspectorCommand, InspectorCommandClient, MultiSourceEntry, MultiSourceReport,
+    RenderedStatus, ReportCodecKind, SourcedNodeletId, StepMode,
 };
-use nodo_runtime::{InspectorClient, InspectorCodeletReport, InspectorReport, RenderedStatus};
 use ratatui::{
     crossterm::event::{self, KeyCode},
     layout::{Constraint, Layout},
@@ -16,16 +27,94 @@ use ratatui::{
     Frame,
 };
 use regex::Regex;
-use std::{collections::HashMap, time::Instant};
+use sort::{
+    compute_sequence_duration_sum, sequence_key, sort_entries, SequenceKey, SortColumn, SortState,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    time::Instant,
+};
+use ui_state::{default_ui_state_path, UiState};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
+    /// Inspector address of a runtime. Can be given multiple times to inspect multiple runtimes.
     #[arg(long, default_value = "tcp://localhost:54399")]
-    address: String,
+    address: Vec<String>,
+
+    /// Time in seconds after which a runtime which stopped sending reports is shown as stale
+    #[arg(long, default_value_t = 3.0)]
+    stale_timeout: f64,
+
+    /// Report codec used by the runtimes: lz4, zstd[:level], delta[:keyframe interval] or strtab
+    #[arg(long, default_value = "lz4")]
+    codec: ReportCodecKind,
+
+    /// Only receive the reports of these schedules, e.g. `--schedules control,vision`
+    #[arg(long, value_delimiter = ',')]
+    schedules: Vec<String>,
 
     #[arg(long)]
     disable_tui: bool,
+
+    /// Appends step statistics of selected codelets to this CSV file for every received report
+    #[arg(long)]
+    csv_out: Option<String>,
+
+    /// Codelets with a name or sequence matching this regex are written to the CSV file
+    #[arg(long, default_value = ".*")]
+    csv_select: String,
+
+    /// TOML file with alert rules evaluated against every received report
+    #[arg(long)]
+    alerts: Option<PathBuf>,
+
+    /// Appends raised and resolved alerts to this file
+    #[arg(long)]
+    alert_log: Option<PathBuf>,
+
+    /// File in which sorting, collapsed sequences and filters are stored across restarts.
+    /// Defaults to `nodo/inspector.json` in the user's config directory.
+    #[arg(long)]
+    ui_state: Option<PathBuf>,
+
+    /// Command address of the runtime, see `Runtime::enable_inspector_commands`. Required for
+    /// step control.
+    #[arg(long)]
+    command_address: Option<String>,
+
+    /// Schedule controlled with step control: p toggles between manual and automatic steps and
+    /// n executes a single step in manual mode
+    #[arg(long, requires = "command_address")]
+    step_schedule: Option<String>,
+}
+
+/// Single-stepping of one schedule from the inspector
+struct StepControl {
+    client: InspectorCommandClient,
+    schedule: String,
+    mode: StepMode,
+}
+
+impl StepControl {
+    fn toggle_mode(&mut self) -> Result<()> {
+        self.mode = match self.mode {
+            StepMode::Auto => StepMode::Manual,
+            StepMode::Manual => StepMode::Auto,
+        };
+        self.client.send(&InspectorCommand::SetStepMode {
+            schedule: self.schedule.clone(),
+            mode: self.mode,
+        })
+    }
+
+    fn step_once(&self) -> Result<()> {
+        self.client.send(&InspectorCommand::StepOnce {
+            schedule: self.schedule.clone(),
+        })
+    }
 }
 
 fn main() -> Result<()> {
@@ -35,26 +124,68 @@ fn main() -> Result<()> {
 
     let mut terminal = (!cli.disable_tui).then(|| ratatui::init());
 
-    let mut inspector = InspectorClient::dial(&cli.address)?;
+    let mut inspector = InspectorClient::dial_many_with_codec(&cli.address, cli.codec)?;
+    if !cli.schedules.is_empty() {
+        inspector.subscribe_schedules(&cli.schedules)?;
+    }
 
-    let mut rvc = ReportViewController::new();
+    let ui_state_path = cli.ui_state.clone().or_else(default_ui_state_path);
+    let mut ui_state = ui_state_path
+        .as_ref()
+        .map(|path| UiState::load(path))
+        .unwrap_or_default();
+    ui_state.prune_sources(
+        &inspector
+            .sources()
+            .iter()
+            .map(|s| s.address())
+            .collect::<Vec<_>>(),
+    );
+    let mut rvc = ReportViewController::from_ui_state(ui_state);
+
+    let mut csv = match cli.csv_out.as_ref() {
+        Some(path) => Some(CsvExporter::create(path, Regex::new(&cli.csv_select)?)?),
+        None => None,
+    };
+
+    let alert_rules = match cli.alerts.as_ref() {
+        Some(path) => load_alert_rules(path)?,
+        None => Vec::new(),
+    };
+    let mut alert_log = match cli.alert_log.as_ref() {
+        Some(path) => Some(AlertLog::open(path)?),
+        None => None,
+    };
+    let mut alerts = AlertState::default();
+
+    let mut step_control = match (cli.command_address.as_ref(), cli.step_schedule.as_ref()) {
+        (Some(address), Some(schedule)) => Some(StepControl {
+            client: InspectorCommandClient::dial(address)?,
+            schedule: schedule.clone(),
+            mode: StepMode::Auto,
+        }),
+        _ => None,
+    };
 
     // Main loop to handle input events.
-    let mut latest_report = None;
+    let mut reports = MultiSourceReport::new(Duration::from_secs_f64(cli.stale_timeout));
     loop {
-        if let Some(next) = inspector.try_recv_report()? {
-            latest_report = Some(next);
+        for next in inspector.try_recv_reports()? {
+            if let Some(csv) = csv.as_mut() {
+                csv.push(&next)?;
+            }
+            if !alert_rules.is_empty() {
+                let update = evaluate(&alert_rules, &next, &alerts, Instant::now());
+                if let Some(log) = alert_log.as_mut() {
+                    log.push(&update.events)?;
+                }
+                alerts = update.state;
+            }
+            reports.update(next, Instant::now());
         }
 
         if let Some(terminal) = terminal.as_mut() {
-            terminal.draw(|f| {
-                rvc.draw_ui(
-                    f,
-                    inspector.datarate(),
-                    inspector.last_report_time(),
-                    latest_report.as_ref(),
-                )
-            })?;
+            terminal.draw(|f| rvc.draw_ui(f, &inspector, &reports, &alerts))?;
 
             // Exit on "q" key press.
             if event::poll(Duration::from_millis(250))? {
@@ -64,6 +195,19 @@ fn main() -> Result<()> {
                         KeyCode::Down => rvc.select_next(),
                         KeyCode::Up => rvc.select_previous(),
                         KeyCode::Enter => rvc.toggle_expand(),
+                        KeyCode::Char('s') => rvc.cycle_sort_column(),
+                        KeyCode::Char('r') => rvc.reverse_sort(),
+                        KeyCode::Char('f') => rvc.cycle_source_filter(&inspector),
+                        KeyCode::Char('p') => {
+                            if let Some(step_control) = step_control.as_mut() {
+                                step_control.toggle_mode()?;
+                            }
+                        }
+                        KeyCode::Char('n') => {
+                            if let Some(step_control) = step_control.as_ref() {
+                                step_control.step_once()?;
+                            }
+                        }
                         _ => {}
                     },
                     _ => {}
@@ -74,22 +218,77 @@ fn main() -> Result<()> {
 
     ratatui::restore();
 
+    if let Some(path) = ui_state_path.as_ref() {
+        rvc.ui_state().save(path);
+    }
+
     Ok(())
 }
 
 struct ReportViewController {
     table_state: TableState,
-    expanded_seq: HashMap<String, bool>,
-    maybe_selected_seq: Option<String>,
+    expanded_seq: HashMap<SequenceKey, bool>,
+    maybe_selected_seq: Option<SequenceKey>,
+    source_filter: Option<String>,
+    sort: SortState,
+    seen_sequences: HashSet<SequenceKey>,
 }
 
 impl ReportViewController {
-    pub fn new() -> Self {
+    pub fn from_ui_state(state: UiState) -> Self {
         Self {
-            table_state: TableState::new(),
-            expanded_seq: HashMap::new(),
+            table_state: TableState::new().with_selected(state.selected_row),
+            expanded_seq: state.collapsed.into_iter().map(|k| (k, false)).collect(),
             maybe_selected_seq: None,
+            source_filter: state.source_filter,
+            sort: state.sort,
+            seen_sequences: HashSet::new(),
+        }
+    }
+
+    /// Current UI state for persistence. Collapsed sequences which were not seen while running
+    /// are dropped unless no report was received at all.
+    pub fn ui_state(&self) -> UiState {
+        let mut collapsed: Vec<_> = self
+            .expanded_seq
+            .iter()
+            .filter(|(_, is_expanded)| !**is_expanded)
+            .map(|(key, _)| key.clone())
+            .collect();
+        collapsed.sort();
+
+        let mut state = UiState {
+            sort: self.sort,
+            collapsed,
+            selected_row: self.table_state.selected(),
+            source_filter: self.source_filter.clone(),
+        };
+        if !self.seen_sequences.is_empty() {
+            state.prune_sequences(&self.seen_sequences);
         }
+        state
+    }
+
+    pub fn cycle_sort_column(&mut self) {
+        self.sort.column = self.sort.column.next();
+    }
+
+    pub fn reverse_sort(&mut self) {
+        self.sort.descending = !self.sort.descending;
+    }
+
+    /// Cycles through showing all sources and showing only a single source
+    pub fn cycle_source_filter(&mut self, inspector: &InspectorClient) {
+        let sources = inspector.sources();
+        let next = match self.source_filter.as_ref() {
+            None => 0,
+            Some(current) => sources
+                .iter()
+                .position(|s| s.address() == current)
+                .map_or(0, |i| i + 1),
+        };
+        self.source_filter = sources.get(next).map(|s| s.address().to_string());
+        self.table_state.select(None);
     }
 
     pub fn select_next(&mut self) {
@@ -103,7 +302,7 @@ impl ReportViewController {
     pub fn toggle_expand(&mut self) {
         if let Some(selected_seq) = self.maybe_selected_seq.as_ref() {
             self.expanded_seq
-                .entry(selected_seq.into())
+                .entry(selected_seq.clone())
                 .and_modify(|v| *v = !*v)
                 .or_insert(false);
         }
@@ -113,20 +312,32 @@ impl ReportViewController {
     pub fn draw_ui(
         &mut self,
         frame: &mut Frame,
-        datarate: f64,
-        last_report_time: Option<Instant>,
-        report: Option<&InspectorReport>,
+        inspector: &InspectorClient,
+        reports: &MultiSourceReport,
+        alerts: &AlertState,
     ) {
+        let constraints = if alerts.is_empty() {
+            vec![Constraint::Percentage(100)]
+        } else {
+            // alert rows plus header and borders
+            let height = (alerts.len() as u16 + 3).min(ALERT_PANEL_MAX_HEIGHT);
+            vec![Constraint::Min(0), Constraint::Length(height)]
+        };
         let chunks = Layout::default()
-            .constraints([Constraint::Percentage(100)].as_ref())
+            .constraints(constraints)
             .split(frame.area());
 
-        let mut entries = report.map_or_else(|| Vec::new(), |report| report.clone().into_vec());
+        let now = Instant::now();
+
+        let mut entries = reports.entries(now);
+        if let Some(filter) = self.source_filter.as_ref() {
+            entries.retain(|e| &e.key.source == filter);
+        }
 
         // duration of all nodelets
         let overall_step_duration_total: f32 = entries
             .iter()
-            .map(|(_, u)| {
+            .map(|MultiSourceEntry { report: u, .. }| {
                 u.statistics.transitions[Transition::Step]
                     .duration
                     .total()
@@ -137,52 +348,41 @@ impl ReportViewController {
         // duration of each nodelet group
         let sequence_duration_sum = compute_sequence_duration_sum(&entries);
 
-        // Sort first by sequence total duration, second by total duration and thirdby name
-        entries.sort_by(|(_, a), (_, b)| {
-            let seq_a = sequence_duration_sum[&a.sequence];
-            let seq_b = sequence_duration_sum[&b.sequence];
-
-            let duration_a = a.statistics.transitions[Transition::Step]
-                .duration
-                .total()
-                .as_millis();
-            let duration_b = b.statistics.transitions[Transition::Step]
-                .duration
-                .total()
-                .as_millis();
-
-            seq_a
-                .partial_cmp(&seq_b)
-                .unwrap_or(std::cmp::Ordering::Equal)
-                .then_with(|| duration_a.cmp(&duration_b))
-                .then_with(|| a.name.cmp(&b.name))
-        });
+        sort_entries(&mut entries, &self.sort);
 
         // Create rows for the combined table.
         let mut combined_rows: Vec<_> = Vec::new();
         let mut prev_sequence = None;
         let mut sel_helper = Vec::new();
-        for (id, u) in entries.into_iter().rev() {
-            let seq_duration = sequence_duration_sum[&u.sequence];
+        for entry in entries.into_iter() {
+            let key = sequence_key(&entry);
+            self.seen_sequences.insert(key.clone());
+            let seq_duration = sequence_duration_sum[&key];
+            let MultiSourceEntry {
+                key: SourcedNodeletId { source, id },
+                report: u,
+                is_stale,
+            } = entry;
             let seq = if u.sequence == "" {
                 "(ungrouped)".into()
             } else {
                 u.sequence
             };
 
-            let is_expanded = *self.expanded_seq.entry(seq.clone()).or_insert(true);
+            let is_expanded = *self.expanded_seq.entry(key.clone()).or_insert(true);
 
             const BASE_LEN: usize = 70;
 
-            if Some(&seq) != prev_sequence.as_ref() {
-                prev_sequence = Some(seq.clone());
+            if Some(&key) != prev_sequence.as_ref() {
+                prev_sequence = Some(key.clone());
 
                 let head = Row::new(vec![
                     Cell::from(Line::from(vec![
                         Span::from(if is_expanded { "+ " } else { "- " }),
-                        Span::styled(seq.clone(), Color::White),
+                        Span::styled(seq.to_string(), Color::White),
                         Span::from(format!(" {}", "─".repeat(2 * BASE_LEN))),
                     ])),
+                    Cell::from(format_source(&source, is_stale)),
                     Cell::from("─".repeat(2 * BASE_LEN)),
                     Cell::from("─".repeat(10)),
                     Cell::from(align_right(
@@ -192,12 +392,17 @@ impl ReportViewController {
                     Cell::from("─".repeat(10)),
                     Cell::from("─".repeat(10)),
                     Cell::from("─".repeat(10)),
+                    Cell::from("─".repeat(10)),
                     Cell::from("─".repeat(5)),
                     Cell::from("─".repeat(4 * BASE_LEN)),
                 ]);
 
-                combined_rows.push(head);
-                sel_helper.push((true, seq.clone()));
+                combined_rows.push(if is_stale {
+                    head.style(Color::DarkGray)
+                } else {
+                    head
+                });
+                sel_helper.push((true, key.clone()));
             }
 
             if is_expanded {
@@ -208,7 +413,8 @@ impl ReportViewController {
                         Span::from("├──"),
                         Span::styled(format!(" {}", u.name), Color::White),
                     ])),
-                    Cell::from(format_status(&u.status)),
+                    Cell::from(format_source(&source, is_stale)),
+                    Cell::from(format_status(&u.status, u.is_warmup)),
                     Cell::from(align_right(format_skip_percent(transition))),
                     Cell::from(align_right(format_total_duration(
                         transition.duration.total().as_secs_f32(),
@@ -218,12 +424,69 @@ impl ReportViewController {
                     Cell::from(align_right(format_step_duration(transition))),
                     Cell::from(align_right(format_step_count(transition))),
                     Cell::from(align_right(format_period(transition))),
+                    Cell::from(align_right(format_jitter(transition))),
                     Cell::from(align_right(format_worker_id(id))),
                     Cell::from(Text::from(format_typename(&u.typename))),
                 ]);
 
-                combined_rows.push(row);
-                sel_helper.push((false, seq.clone()));
+                // Rows of sources which stopped sending reports are kept but greyed out while rows
+                // of codelets with an error status are highlighted. Codelets which never executed a
+                // step are dimmed.
+                let severity = u.status.as_ref().map(|s| s.severity);
+                combined_rows.push(if is_stale {
+                    row.style(Style::default().fg(Color::DarkGray))
+                } else if severity == Some(Severity::Error) {
+                    row.style(Style::default().fg(severity_color(Severity::Error)))
+                } else if u.suspected_inactive {
+                    row.style(Style::default().add_modifier(Modifier::DIM))
+                } else {
+                    row
+                });
+                sel_helper.push((false, key.clone()));
+
+                if let Some((fraction, detail)) = u.progress.as_ref() {
+                    let style = if is_stale {
+                        Style::default().fg(Color::DarkGray)
+                    } else {
+                        Style::default().fg(Color::Cyan)
+                    };
+                    combined_rows.push(
+                        Row::new(vec![
+                            Cell::from("│    progress"),
+                            Cell::from(""),
+                            Cell::from(format_progress(*fraction, detail)),
+                        ])
+                        .style(style),
+                    );
+                    sel_helper.push((false, key.clone()));
+                }
+
+                // start diagnostics are kept for the whole run
+                for msg in u.start_diagnostics.iter() {
+                    let style = if is_stale {
+                        Style::default().fg(Color::DarkGray)
+                    } else {
+                        Style::default()
+                            .fg(severity_color(Severity::Warn))
+                            .add_modifier(Modifier::ITALIC)
+                    };
+                    combined_rows.push(
+                        Row::new(vec![
+                            Cell::from("│    start"),
+                            Cell::from(""),
+                            Cell::from(msg.clone()),
+                        ])
+                        .style(style),
+                    );
+                    sel_helper.push((false, key.clone()));
+                }
+            }
+        }
+
+        // a restored selection might point past the end of the table
+        if let Some(idx) = self.table_state.selected() {
+            if !combined_rows.is_empty() && idx >= combined_rows.len() {
+                self.table_state.select(Some(combined_rows.len() - 1));
             }
         }
 
@@ -236,37 +499,90 @@ impl ReportViewController {
             }
         }
 
-        let connection_status =
-            if last_report_time.map_or(false, |last| (Instant::now() - last).as_secs_f32() < 1.0) {
-                Span::styled("●", Color::Green)
+        let mut title = vec![
+            Span::styled(
+                " NODO INSPECTOR",
+                Style::default()
+                    .fg(Color::White)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::from(" ──"),
+        ];
+        for source in inspector.sources() {
+            let connection_status = if source
+                .last_report_time()
+                .is_some_and(|last| (now - last).as_secs_f32() < 1.0)
+            {
+                Span::styled(" ●", Color::Green)
             } else {
-                Span::styled("■", Color::Red)
+                Span::styled(" ■", Color::Red)
             };
+            title.push(connection_status);
+            if inspector.sources().len() > 1 {
+                title.push(Span::styled(
+                    format!(" {}", source.address()),
+                    Style::default().fg(Color::White),
+                ));
+            }
+            if let Some(app_info) = source.app_info() {
+                title.push(Span::styled(
+                    format!(" {}", app_info.summary()),
+                    Style::default().fg(Color::White),
+                ));
+                if !app_info.capabilities.is_empty() {
+                    title.push(Span::styled(
+                        format!(" [{}]", app_info.capabilities_summary()),
+                        Style::default().fg(Color::Gray),
+                    ));
+                }
+            }
+            title.push(Span::styled(
+                format!(
+                    " [{}/s, {}/report]",
+                    fmt_bytes(source.datarate() as u64),
+                    fmt_bytes(source.last_report_size() as u64)
+                ),
+                Style::default().fg(Color::White),
+            ));
+        }
+        if let Some(filter) = self.source_filter.as_ref() {
+            title.push(Span::styled(
+                format!(" ── showing {filter}"),
+                Style::default().fg(Color::White),
+            ));
+        }
+        title.push(Span::from(
+            " ── Press s to sort, r to reverse, f to filter sources, q to quit ",
+        ));
 
         // Create the combined table.
         let combined_table = Table::new(
             combined_rows,
             &[
                 Constraint::Fill(2),    // Inspector name
+                Constraint::Fill(1),    // Source
                 Constraint::Fill(2),    // Status label
                 Constraint::Length(8),  // Skipped flag
                 Constraint::Length(10), // Total duration
                 Constraint::Length(10), // Step
                 Constraint::Length(10), // Count
                 Constraint::Length(10), // Period
+                Constraint::Length(10), // Jitter
                 Constraint::Length(5),  // WorkerId
                 Constraint::Fill(4),    // Type name
             ],
         )
         .header(
             Row::new(vec![
-                "Codelet".into(),
-                "Status".into(),
-                align_right("Skip%".into()),
-                align_right("Time".into()),
+                self.sort.header(SortColumn::Name).into(),
+                "Source".into(),
+                self.sort.header(SortColumn::Status).into(),
+                align_right(self.sort.header(SortColumn::SkipPercent).into()),
+                align_right(self.sort.header(SortColumn::Time).into()),
                 align_right("Step".into()),
-                align_right("Count".into()),
-                align_right("Period".into()),
+                align_right(self.sort.header(SortColumn::Count).into()),
+                align_right(self.sort.header(SortColumn::Period).into()),
+                align_right("Jitter".into()),
                 align_right("WID".into()),
                 "Type".into(),
             ])
@@ -279,70 +595,124 @@ impl ReportViewController {
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title(Line::from(vec![
-                    Span::styled(
-                        " NODO INSPECTOR",
-                        Style::default()
-                            .fg(Color::White)
-                            .add_modifier(Modifier::BOLD),
-                    ),
-                    Span::from(" ── "),
-                    connection_status,
-                    Span::styled(
-                        format!(" [{:.0} kB/s] ", datarate / (1024.0)),
-                        Style::default().fg(Color::White),
-                    ),
-                    Span::from(" ── Press q to quit "),
-                ])),
+                .title(Line::from(title)),
         )
         .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
         .style(Color::Yellow);
 
         // Render the combined table.
         frame.render_stateful_widget(combined_table, chunks[0], &mut self.table_state);
+
+        if !alerts.is_empty() {
+            frame.render_widget(alert_table(alerts, now), chunks[1]);
+        }
     }
 }
 
-fn compute_sequence_duration_sum(
-    reports: &[(NodeletId, InspectorCodeletReport)],
-) -> HashMap<String, f32> {
-    let mut sequence_duration_map = HashMap::new();
-
-    for (_, report) in reports {
-        // Access the Transition::Step for each report's statistics
-        let step_transition = &report.statistics.transitions[Transition::Step];
-
-        // Get the total duration in seconds
-        let duration_secs = step_transition.duration.total().as_secs_f32();
-
-        // Add the duration to the corresponding sequence
-        sequence_duration_map
-            .entry(report.sequence.clone())
-            .and_modify(|e| *e += duration_secs)
-            .or_insert(duration_secs);
-    }
+const ALERT_PANEL_MAX_HEIGHT: u16 = 12;
+
+/// Alerts with the most important ones first. Resolved alerts are greyed out.
+fn alert_table(alerts: &AlertState, now: Instant) -> Table<'static> {
+    let rows = alerts.sorted().into_iter().map(|(key, alert)| {
+        let row = Row::new(vec![
+            Cell::from(format!("{:?}", alert.severity)),
+            Cell::from(key.rule.clone()),
+            Cell::from(format!("{} / {}", alert.sequence, alert.name)),
+            Cell::from(key.codelet.source.clone()),
+            Cell::from(align_right(Span::from(alert.value.clone()))),
+            Cell::from(align_right(Span::from(format!("{}", alert.count)))),
+            Cell::from(align_right(Span::from(format_ago(now, alert.first_seen)))),
+            Cell::from(align_right(Span::from(format_ago(now, alert.last_seen)))),
+        ]);
+        if alert.is_active {
+            row.style(Style::default().fg(severity_color(alert.severity)))
+        } else {
+            row.style(Style::default().fg(Color::DarkGray))
+        }
+    });
+
+    let active = alerts.sorted().iter().filter(|(_, a)| a.is_active).count();
+
+    Table::new(
+        rows,
+        &[
+            Constraint::Length(6),  // Severity
+            Constraint::Fill(1),    // Rule
+            Constraint::Fill(2),    // Codelet
+            Constraint::Fill(1),    // Source
+            Constraint::Length(10), // Value
+            Constraint::Length(8),  // Count
+            Constraint::Length(10), // First seen
+            Constraint::Length(10), // Last seen
+        ],
+    )
+    .header(
+        Row::new(vec![
+            "Level".into(),
+            "Rule".into(),
+            "Codelet".into(),
+            "Source".into(),
+            align_right("Value".into()),
+            align_right("Count".into()),
+            align_right("First".into()),
+            align_right("Last".into()),
+        ])
+        .style(
+            Style::default()
+                .add_modifier(Modifier::BOLD)
+                .add_modifier(Modifier::REVERSED),
+        ),
+    )
+    .block(Block::default().borders(Borders::ALL).title(format!(
+        " ALERTS ── {active} active, {} total ",
+        alerts.len()
+    )))
+    .style(Color::Yellow)
+}
 
-    sequence_duration_map
+fn format_ago(now: Instant, time: Instant) -> String {
+    format!("{} ago", fmt_duration(now.saturating_duration_since(time)))
 }
 
 fn align_right(span: Span<'_>) -> Text<'_> {
     Text::from(span).alignment(Alignment::Right)
 }
 
-fn format_status(maybe_status: &Option<RenderedStatus>) -> Span<'static> {
-    if let Some(status) = maybe_status {
-        let status_style = if status.status == DefaultStatus::Skipped {
-            Style::default().fg(Color::Yellow)
-        } else {
-            Style::default().fg(Color::Green)
+fn format_status(maybe_status: &Option<RenderedStatus>, is_warmup: bool) -> Span<'static> {
+    if is_warmup {
+        let label = maybe_status.as_ref().map_or("None", |s| s.label.as_str());
+        Span::styled(format!("warming up ({label})"), Color::Cyan)
+    } else if let Some(status) = maybe_status {
+        let label = match status.skip_reason.as_deref() {
+            Some(reason) if reason != status.label => format!("{} ({reason})", status.label),
+            _ => status.label.clone(),
         };
-
-        Span::styled(status.label.clone(), status_style)
+        Span::styled(label, severity_color(status.severity))
     } else {
         Span::styled("None", Color::DarkGray)
     }
 }
 
+/// Progress bar with percentage and detail like `[####------]  42% indexing`
+fn format_progress(fraction: f32, detail: &str) -> String {
+    const WIDTH: usize = 10;
+    let filled = ((fraction * WIDTH as f32).round() as usize).min(WIDTH);
+    format!(
+        "[{}{}] {:>3.0}% {detail}",
+        "#".repeat(filled),
+        "-".repeat(WIDTH - filled),
+        fraction * 100.0
+    )
+}
+
+fn severity_color(severity: Severity) -> Color {
+    match severity {
+        Severity::Info => Color::Green,
+        Severity::Warn => Color::Yellow,
+        Severity::Error => Color::Red,
+    }
+}
+
 fn format_skip_percent(u: &TransitionStatistics) -> Span<'static> {
     if u.skipped_count == 0 {
         Span::styled(format!("{:>6}", "None"), Color::DarkGray)
@@ -368,12 +738,15 @@ fn format_total_duration(x: f32, overall_total: f32, threshold: f32) -> Span<'st
     } else {
         Color::White
     };
-    Span::styled(format!("{:>7.1}s", x), color)
+    Span::styled(
+        format!("{:>9}", fmt_duration(Duration::from_secs_f32(x))),
+        color,
+    )
 }
 
 fn format_step_duration(u: &TransitionStatistics) -> Span<'static> {
-    if let (Some(x), Some(period)) = (u.duration.average_ms(), u.period.average_ms()) {
-        let p = x / period;
+    if let (Some(x), Some(period)) = (u.duration.average(), u.period.average()) {
+        let p = x.as_secs_f32() / period.as_secs_f32();
         let color = if p > 0.5 {
             Color::LightRed
         } else if p > 0.20 {
@@ -381,7 +754,7 @@ fn format_step_duration(u: &TransitionStatistics) -> Span<'static> {
         } else {
             Color::White
         };
-        Span::styled(format!("{:>5.1} ms", x), color)
+        Span::styled(format!("{:>8}", fmt_duration(x)), color)
     } else {
         Span::styled(format!("{:>8}", "None"), Color::DarkGray)
     }
@@ -393,13 +766,37 @@ fn format_step_count(u: &TransitionStatistics) -> Span<'static> {
 }
 
 fn format_period(u: &TransitionStatistics) -> Span<'static> {
-    if let Some(x) = u.period.average_ms() {
-        Span::styled(format!("{:>6.1} ms", x), Color::White)
+    if let Some(x) = u.period.average() {
+        Span::styled(format!("{:>8}", fmt_duration(x)), Color::White)
     } else {
         Span::styled(format!("{:>8}", "Never"), Color::DarkGray)
     }
 }
 
+/// RMS deviation of steps from the ideal periodic grid, highlighted if it exceeds 10% of the
+/// period
+fn format_jitter(u: &TransitionStatistics) -> Span<'static> {
+    match (u.jitter.rms(), u.period.average()) {
+        (Some(rms), Some(period)) => Span::styled(
+            format!("{:>8}", fmt_duration(rms)),
+            if rms * 10 > period {
+                Color::LightRed
+            } else {
+                Color::White
+            },
+        ),
+        _ => Span::styled(format!("{:>8}", "-"), Color::DarkGray),
+    }
+}
+
+fn format_source(source: &str, is_stale: bool) -> Span<'static> {
+    if is_stale {
+        Span::styled(format!("{source} (stale)"), Color::DarkGray)
+    } else {
+        Span::styled(source.to_string(), Color::LightBlue)
+    }
+}
+
 fn format_worker_id(id: NodeletId) -> Span<'static> {
     Span::styled(format!("{:>3}", id.0 .0), Color::LightBlue)
 }
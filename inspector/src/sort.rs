@@ -0,0 +1,256 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use nodo::codelet::{Transition, TransitionStatistics};
+use nodo_core::Name;
+use nodo_runtime::MultiSourceEntry;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, collections::HashMap};
+
+/// Sequences are identified by source and sequence name
+pub type SequenceKey = (String, Name);
+
+pub fn sequence_key(entry: &MultiSourceEntry) -> SequenceKey {
+    (entry.key.source.clone(), entry.report.sequence.clone())
+}
+
+/// Column by which the codelet table is sorted
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortColumn {
+    Name,
+    Status,
+    SkipPercent,
+    #[default]
+    Time,
+    Count,
+    Period,
+}
+
+impl SortColumn {
+    pub const ALL: [SortColumn; 6] = [
+        SortColumn::Name,
+        SortColumn::Status,
+        SortColumn::SkipPercent,
+        SortColumn::Time,
+        SortColumn::Count,
+        SortColumn::Period,
+    ];
+
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|&c| c == self).unwrap();
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+
+    /// Title of the table header column
+    pub fn title(self) -> &'static str {
+        match self {
+            SortColumn::Name => "Codelet",
+            SortColumn::Status => "Status",
+            SortColumn::SkipPercent => "Skip%",
+            SortColumn::Time => "Time",
+            SortColumn::Count => "Count",
+            SortColumn::Period => "Period",
+        }
+    }
+}
+
+/// Active sort of the codelet table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SortState {
+    pub column: SortColumn,
+    pub descending: bool,
+}
+
+impl Default for SortState {
+    fn default() -> Self {
+        Self {
+            column: SortColumn::Time,
+            descending: true,
+        }
+    }
+}
+
+impl SortState {
+    /// Header title of the given column with an indicator if it is the active sort column
+    pub fn header(&self, column: SortColumn) -> String {
+        if column == self.column {
+            let arrow = if self.descending { "▼" } else { "▲" };
+            format!("{}{arrow}", column.title())
+        } else {
+            column.title().to_string()
+        }
+    }
+}
+
+/// Total step duration of all codelets in each sequence in seconds
+pub fn compute_sequence_duration_sum(entries: &[MultiSourceEntry]) -> HashMap<SequenceKey, f32> {
+    let mut sequence_duration_map = HashMap::new();
+
+    for entry in entries {
+        let duration_secs = step(entry).duration.total().as_secs_f32();
+        *sequence_duration_map
+            .entry(sequence_key(entry))
+            .or_insert(0.0) += duration_secs;
+    }
+
+    sequence_duration_map
+}
+
+/// Sorts entries by the given column while keeping codelets of the same sequence together.
+///
+/// Sequences are ordered by their total duration when sorting by time and by name otherwise.
+/// Codelets within a sequence are ordered by the sort column and then by name.
+pub fn sort_entries(entries: &mut [MultiSourceEntry], sort: &SortState) {
+    let sequence_duration_sum = compute_sequence_duration_sum(entries);
+
+    entries.sort_by(|a, b| {
+        let (seq_a, seq_b) = (sequence_key(a), sequence_key(b));
+
+        let by_sequence = if sort.column == SortColumn::Time {
+            total_cmp(sequence_duration_sum[&seq_a], sequence_duration_sum[&seq_b])
+        } else {
+            Ordering::Equal
+        };
+
+        let ordering = by_sequence
+            .then_with(|| seq_a.cmp(&seq_b))
+            .then_with(|| compare_by(a, b, sort.column))
+            .then_with(|| a.report.name.cmp(&b.report.name))
+            .then_with(|| a.key.cmp(&b.key));
+
+        if sort.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+}
+
+fn step(entry: &MultiSourceEntry) -> &TransitionStatistics {
+    &entry.report.statistics.transitions[Transition::Step]
+}
+
+fn total_cmp(a: f32, b: f32) -> Ordering {
+    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
+
+fn compare_by(a: &MultiSourceEntry, b: &MultiSourceEntry, column: SortColumn) -> Ordering {
+    match column {
+        SortColumn::Name => a.report.name.cmp(&b.report.name),
+        SortColumn::Status => {
+            let label = |e: &MultiSourceEntry| e.report.status.as_ref().map(|s| s.label.clone());
+            label(a).cmp(&label(b))
+        }
+        SortColumn::SkipPercent => total_cmp(step(a).skip_percent(), step(b).skip_percent()),
+        SortColumn::Time => step(a).duration.total().cmp(&step(b).duration.total()),
+        SortColumn::Count => step(a).duration.count().cmp(&step(b).duration.count()),
+        SortColumn::Period => {
+            let period = |e: &MultiSourceEntry| step(e).period.average_ms().unwrap_or(-1.0);
+            total_cmp(period(a), period(b))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::time::Duration;
+    use nodo::codelet::{NodeletId, Statistics, WorkerId};
+    use nodo_runtime::{InspectorCodeletReport, SourcedNodeletId};
+
+    fn entry(id: u32, sequence: &str, name: &str, step_ms: &[u64]) -> MultiSourceEntry {
+        let mut statistics = Statistics::new();
+        for &ms in step_ms {
+            statistics.transitions[Transition::Step]
+                .duration
+                .push(Duration::from_millis(ms));
+        }
+        MultiSourceEntry {
+            key: SourcedNodeletId {
+                source: "src".into(),
+                id: NodeletId(WorkerId(0), id),
+            },
+            report: InspectorCodeletReport {
+                sequence: sequence.into(),
+                name: name.into(),
+                typename: "Foo".into(),
+                status: None,
+                statistics,
+                is_warmup: false,
+                labels: Vec::new(),
+                suspected_inactive: false,
+                suspend_resume_count: 0,
+                deadline: None,
+                start_diagnostics: Vec::new(),
+                schedule: Default::default(),
+                progress: None,
+                unknown: Default::default(),
+            },
+            is_stale: false,
+        }
+    }
+
+    fn names(entries: &[MultiSourceEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.report.name.as_str()).collect()
+    }
+
+    fn entries() -> Vec<MultiSourceEntry> {
+        vec![
+            entry(0, "a", "a1", &[1]),
+            entry(1, "b", "b1", &[10, 10]),
+            entry(2, "a", "a2", &[3, 1, 1]),
+            entry(3, "b", "b2", &[1]),
+        ]
+    }
+
+    #[test]
+    fn test_sort_by_time() {
+        let mut entries = entries();
+        sort_entries(&mut entries, &SortState::default());
+        assert_eq!(names(&entries), ["b1", "b2", "a2", "a1"]);
+
+        sort_entries(
+            &mut entries,
+            &SortState {
+                column: SortColumn::Time,
+                descending: false,
+            },
+        );
+        assert_eq!(names(&entries), ["a1", "a2", "b2", "b1"]);
+    }
+
+    #[test]
+    fn test_sort_keeps_sequences_together() {
+        let mut entries = entries();
+        sort_entries(
+            &mut entries,
+            &SortState {
+                column: SortColumn::Count,
+                descending: false,
+            },
+        );
+        assert_eq!(names(&entries), ["a1", "a2", "b2", "b1"]);
+
+        sort_entries(
+            &mut entries,
+            &SortState {
+                column: SortColumn::Name,
+                descending: true,
+            },
+        );
+        assert_eq!(names(&entries), ["b2", "b1", "a2", "a1"]);
+    }
+
+    #[test]
+    fn test_sort_column_cycle_and_header() {
+        let mut column = SortColumn::Name;
+        for _ in 0..SortColumn::ALL.len() {
+            column = column.next();
+        }
+        assert_eq!(column, SortColumn::Name);
+        assert_eq!(SortColumn::Period.next(), SortColumn::Name);
+
+        let sort = SortState::default();
+        assert_eq!(sort.header(SortColumn::Time), "Time▼");
+        assert_eq!(sort.header(SortColumn::Count), "Count");
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::sort::{SequenceKey, SortState};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// UI state which is persisted across restarts of the inspector
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiState {
+    pub sort: SortState,
+
+    /// Sequences which are collapsed. All other sequences are expanded.
+    pub collapsed: Vec<SequenceKey>,
+
+    /// Index of the selected table row
+    pub selected_row: Option<usize>,
+
+    /// Address of the source if only a single source is shown
+    pub source_filter: Option<String>,
+}
+
+impl UiState {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("UI state is always serializable")
+    }
+
+    /// Parses a persisted UI state. Unknown fields are ignored and missing ones use defaults.
+    pub fn from_json(text: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(text)
+    }
+
+    /// Forgets collapsed sequences which do not exist anymore
+    pub fn prune_sequences(&mut self, sequences: &HashSet<SequenceKey>) {
+        self.collapsed.retain(|key| sequences.contains(key));
+    }
+
+    /// Resets the source filter if it refers to a source which is not inspected anymore
+    pub fn prune_sources(&mut self, sources: &[&str]) {
+        if self
+            .source_filter
+            .as_ref()
+            .is_some_and(|f| !sources.contains(&f.as_str()))
+        {
+            self.source_filter = None;
+            self.selected_row = None;
+        }
+    }
+
+    /// Loads the UI state from a file. Failures are logged and result in the default state.
+    pub fn load(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::from_json(&text).unwrap_or_else(|err| {
+                log::warn!("ignoring invalid UI state file {path:?}: {err}");
+                Self::default()
+            }),
+            Err(err) => {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    log::warn!("could not read UI state file {path:?}: {err}");
+                }
+                Self::default()
+            }
+        }
+    }
+
+    /// Writes the UI state to a file. Failures are logged and otherwise ignored.
+    pub fn save(&self, path: &Path) {
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, self.to_json()));
+        if let Err(err) = result {
+            log::warn!("could not write UI state file {path:?}: {err}");
+        }
+    }
+}
+
+/// Default location of the UI state file in the user's config directory
+pub fn default_ui_state_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("nodo").join("inspector.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sort::SortColumn;
+
+    fn key(source: &str, sequence: &str) -> SequenceKey {
+        (source.into(), sequence.into())
+    }
+
+    fn state() -> UiState {
+        UiState {
+            sort: SortState {
+                column: SortColumn::Count,
+                descending: false,
+            },
+            collapsed: vec![key("a", "seq1"), key("a", "seq2")],
+            selected_row: Some(3),
+            source_filter: Some("a".into()),
+        }
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let state = state();
+        assert_eq!(UiState::from_json(&state.to_json()).unwrap(), state);
+    }
+
+    #[test]
+    fn test_json_partial_and_invalid() {
+        let state = UiState::from_json(r#"{"selected_row": 2, "unknown": 1}"#).unwrap();
+        assert_eq!(state.selected_row, Some(2));
+        assert_eq!(state.sort, SortState::default());
+
+        assert!(UiState::from_json("not json").is_err());
+        assert!(UiState::from_json(r#"{"sort": {"column": "Bogus"}}"#).is_err());
+    }
+
+    #[test]
+    fn test_prune_stale_entries() {
+        let mut state = state();
+        state.prune_sequences(&HashSet::from([key("a", "seq2")]));
+        assert_eq!(state.collapsed, vec![key("a", "seq2")]);
+
+        state.prune_sources(&["a"]);
+        assert_eq!(state.source_filter.as_deref(), Some("a"));
+        assert_eq!(state.selected_row, Some(3));
+
+        state.prune_sources(&["b"]);
+        assert_eq!(state.source_filter, None);
+        assert_eq!(state.selected_row, None);
+    }
+
+    #[test]
+    fn test_load_save_failures_fall_back_to_default() {
+        let dir = std::env::temp_dir().join(format!("nodo_ui_state_{}", std::process::id()));
+        let path = dir.join("inspector.json");
+
+        assert_eq!(UiState::load(&path), UiState::default());
+
+        state().save(&path);
+        assert_eq!(UiState::load(&path), state());
+
+        std::fs::write(&path, "garbage").unwrap();
+        assert_eq!(UiState::load(&path), UiState::default());
+
+        // writing to a directory fails but must not panic
+        state().save(&dir);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
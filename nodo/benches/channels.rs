@@ -0,0 +1,198 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+//! Benchmarks for the channel hot path
+//!
+//! Run with `cargo bench -p nodo --bench channels`. To compare two versions save a baseline on
+//! the first and compare against it on the second:
+//!
+//! ```text
+//! cargo bench -p nodo --bench channels -- --save-baseline before
+//! cargo bench -p nodo --bench channels -- --baseline before
+//! ```
+//!
+//! `cargo test` runs every benchmark body once as a smoke test.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use nodo::{
+    channels::{
+        FlushResult, OverflowPolicy, RetentionPolicy, Rx, RxBundle, SyncResult, Tx, TxBundle,
+    },
+    prelude::*,
+};
+use std::hint::black_box;
+
+/// Number of messages published per step
+const BATCH: usize = 8;
+
+/// Combinations of overflow and retention policies. Reject can not be combined with Keep.
+fn policy_matrix() -> Vec<(&'static str, OverflowPolicy, RetentionPolicy)> {
+    vec![
+        (
+            "reject_drop",
+            OverflowPolicy::Reject(BATCH),
+            RetentionPolicy::Drop,
+        ),
+        (
+            "forget_drop",
+            OverflowPolicy::Forget(BATCH),
+            RetentionPolicy::Drop,
+        ),
+        (
+            "forget_keep",
+            OverflowPolicy::Forget(BATCH),
+            RetentionPolicy::Keep,
+        ),
+        ("resize_drop", OverflowPolicy::Resize, RetentionPolicy::Drop),
+        ("resize_keep", OverflowPolicy::Resize, RetentionPolicy::Keep),
+    ]
+}
+
+/// Push, flush, sync and pop of a batch of messages
+fn bench_policies(c: &mut Criterion) {
+    let mut group = c.benchmark_group("push_flush_sync_pop");
+    for (name, overflow, retention) in policy_matrix() {
+        let mut tx = DoubleBufferTx::new(BATCH);
+        let mut rx = DoubleBufferRx::new(overflow, retention);
+        tx.connect(&mut rx).unwrap();
+
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for i in 0..BATCH as u64 {
+                    tx.push(i).unwrap();
+                }
+                tx.flush();
+                rx.sync();
+                black_box(rx.pop_all().sum::<u64>())
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Flush to multiple receivers which requires cloning every message
+fn bench_fan_out(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fan_out");
+    for receiver_count in [1, 4, 16] {
+        let mut tx = DoubleBufferTx::new(BATCH);
+        let mut rxs: Vec<DoubleBufferRx<Vec<u8>>> = (0..receiver_count)
+            .map(|_| DoubleBufferRx::new(OverflowPolicy::Reject(BATCH), RetentionPolicy::Drop))
+            .collect();
+        for rx in rxs.iter_mut() {
+            tx.connect(rx).unwrap();
+        }
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(receiver_count),
+            &receiver_count,
+            |b, _| {
+                b.iter(|| {
+                    for _ in 0..BATCH {
+                        tx.push(vec![0u8; 64]).unwrap();
+                    }
+                    tx.flush();
+                    for rx in rxs.iter_mut() {
+                        rx.sync();
+                        black_box(rx.pop_all().count());
+                    }
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+#[derive(RxBundleDerive)]
+struct SixRx {
+    a: DoubleBufferRx<u64>,
+    b: DoubleBufferRx<u64>,
+    c: DoubleBufferRx<u64>,
+    d: DoubleBufferRx<u64>,
+    e: DoubleBufferRx<u64>,
+    f: DoubleBufferRx<u64>,
+}
+
+#[derive(TxBundleDerive)]
+struct SixTx {
+    a: DoubleBufferTx<u64>,
+    b: DoubleBufferTx<u64>,
+    c: DoubleBufferTx<u64>,
+    d: DoubleBufferTx<u64>,
+    e: DoubleBufferTx<u64>,
+    f: DoubleBufferTx<u64>,
+}
+
+fn six_bundles() -> (SixTx, SixRx) {
+    let mut tx = SixTx {
+        a: DoubleBufferTx::new(1),
+        b: DoubleBufferTx::new(1),
+        c: DoubleBufferTx::new(1),
+        d: DoubleBufferTx::new(1),
+        e: DoubleBufferTx::new(1),
+        f: DoubleBufferTx::new(1),
+    };
+    let mut rx = SixRx {
+        a: DoubleBufferRx::new_latest(),
+        b: DoubleBufferRx::new_latest(),
+        c: DoubleBufferRx::new_latest(),
+        d: DoubleBufferRx::new_latest(),
+        e: DoubleBufferRx::new_latest(),
+        f: DoubleBufferRx::new_latest(),
+    };
+    tx.a.connect(&mut rx.a).unwrap();
+    tx.b.connect(&mut rx.b).unwrap();
+    tx.c.connect(&mut rx.c).unwrap();
+    tx.d.connect(&mut rx.d).unwrap();
+    tx.e.connect(&mut rx.e).unwrap();
+    tx.f.connect(&mut rx.f).unwrap();
+    (tx, rx)
+}
+
+fn push_six(tx: &mut SixTx, value: u64) {
+    for channel in [
+        &mut tx.a, &mut tx.b, &mut tx.c, &mut tx.d, &mut tx.e, &mut tx.f,
+    ] {
+        channel.push(value).unwrap();
+    }
+}
+
+/// Derive-generated `flush_all` and `sync_all` compared to calling each channel by hand
+fn bench_bundles(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bundle_6");
+
+    let (mut tx, mut rx) = six_bundles();
+    let mut flush_results = vec![FlushResult::ZERO; tx.len()];
+    let mut sync_results = vec![SyncResult::ZERO; rx.len()];
+    group.bench_function("derived", |b| {
+        b.iter(|| {
+            push_six(&mut tx, 1);
+            tx.flush_all(&mut flush_results);
+            rx.sync_all(&mut sync_results);
+            black_box(&sync_results);
+        })
+    });
+
+    let (mut tx, mut rx) = six_bundles();
+    group.bench_function("hand_written", |b| {
+        b.iter(|| {
+            push_six(&mut tx, 1);
+            flush_results[0] = tx.a.flush();
+            flush_results[1] = tx.b.flush();
+            flush_results[2] = tx.c.flush();
+            flush_results[3] = tx.d.flush();
+            flush_results[4] = tx.e.flush();
+            flush_results[5] = tx.f.flush();
+            sync_results[0] = rx.a.sync();
+            sync_results[1] = rx.b.sync();
+            sync_results[2] = rx.c.sync();
+            sync_results[3] = rx.d.sync();
+            sync_results[4] = rx.e.sync();
+            sync_results[5] = rx.f.sync();
+            black_box(&sync_results);
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_policies, bench_fan_out, bench_bundles);
+criterion_main!(benches);
@@ -0,0 +1,49 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+//! Benchmarks for the per-step framework overhead
+//!
+//! Run with `cargo bench -p nodo --bench codelet`. Use `--save-baseline <name>` and
+//! `--baseline <name>` to compare two versions, see `benches/channels.rs`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use nodo::{
+    codelet::{Clocks, Lifecycle, NodeletId, NodeletSetup, Transition, Vise, ViseTrait, WorkerId},
+    prelude::*,
+};
+
+/// A codelet which does nothing such that only the framework overhead is measured
+struct Noop;
+
+impl Codelet for Noop {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = DoubleBufferRx<u64>;
+    type Tx = DoubleBufferTx<u64>;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (DoubleBufferRx::new_auto_size(), DoubleBufferTx::new(1))
+    }
+
+    fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        SUCCESS
+    }
+}
+
+/// Steps a started no-op codelet: clock updates, statistics and sync/flush bookkeeping
+fn bench_empty_step(c: &mut Criterion) {
+    let mut vise = Vise::new(Noop.into_instance("noop", ()));
+    vise.setup(&mut NodeletSetup {
+        clocks: Clocks::new(),
+        nodelet_id_issue: NodeletId(WorkerId(0), 0),
+    });
+    vise.cycle(Transition::Start).unwrap();
+
+    c.bench_function("empty_step", |b| {
+        b.iter(|| vise.cycle(Transition::Step).unwrap())
+    });
+
+    vise.cycle(Transition::Stop).unwrap();
+}
+
+criterion_group!(benches, bench_empty_step);
+criterion_main!(benches);
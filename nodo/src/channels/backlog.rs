@@ -0,0 +1,50 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::{any::Any, fmt};
+use std::collections::VecDeque;
+
+/// Messages which were still queued in a receiver when its codelet was torn down
+///
+/// Dropping the backlog frees the messages. It can be sent to another thread to keep the cost of
+/// deallocation off the worker thread.
+pub struct Backlog {
+    len: usize,
+    bytes: usize,
+    _items: Box<dyn Any + Send>,
+}
+
+impl Backlog {
+    pub fn new<T: Send + 'static>(items: VecDeque<T>) -> Self {
+        Self {
+            len: items.len(),
+            bytes: items.len() * core::mem::size_of::<T>(),
+            _items: Box::new(items),
+        }
+    }
+
+    /// Number of messages
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Estimated size of the messages in bytes
+    ///
+    /// Only the inline size of messages is counted. Memory owned by messages, for example the
+    /// buffer of a `Vec`, is not included.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+impl fmt::Debug for Backlog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Backlog")
+            .field("len", &self.len)
+            .field("bytes", &self.bytes)
+            .finish()
+    }
+}
@@ -1,7 +1,11 @@
 // Copyright 2023 by David Weikersdorfer. All rights reserved.
 
-use crate::channels::{FlushResult, SyncResult, MAX_RECEIVER_COUNT};
+use crate::channels::{
+    Backlog, ChannelContract, FlushResult, SyncResult, TapPoint, TxConnectError,
+};
+use core::any::Any;
 use paste::paste;
+use std::{borrow::Cow, sync::Arc};
 
 /// An endpoint receiving data
 pub trait Rx: Send {
@@ -10,6 +14,45 @@ pub trait Rx: Send {
 
     /// Returns true if the channel is connected
     fn is_connected(&self) -> bool;
+
+    /// Contract expected from the transmitter
+    fn contract(&self) -> Option<ChannelContract> {
+        None
+    }
+
+    /// Hash of the message type, see [super::message_type_hash]
+    fn message_type_hash(&self) -> Option<u64> {
+        None
+    }
+
+    /// Type-erased access to the endpoint used to connect channels dynamically
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any>
+    where
+        Self: 'static,
+    {
+        None
+    }
+
+    /// Number of messages available to the consumer, if known
+    fn queue_len(&self) -> Option<usize> {
+        None
+    }
+
+    /// Removes all queued messages such that they can be freed elsewhere
+    fn take_backlog(&mut self) -> Option<Backlog>
+    where
+        Self: 'static,
+    {
+        None
+    }
+
+    /// Access to the back stage to observe incoming messages, see [TapPoint]
+    fn tap_point(&self) -> Option<Arc<dyn TapPoint>>
+    where
+        Self: 'static,
+    {
+        None
+    }
 }
 
 /// An endpoint publishing data
@@ -19,6 +62,40 @@ pub trait Tx: Send {
 
     /// Returns true if the channel is connected
     fn is_connected(&self) -> bool;
+
+    /// Contract promised to receivers
+    fn contract(&self) -> Option<ChannelContract> {
+        None
+    }
+
+    /// Hash of the message type, see [super::message_type_hash]
+    fn message_type_hash(&self) -> Option<u64> {
+        None
+    }
+
+    /// Connects to a type-erased receiver as returned by `Rx::as_any_mut`
+    fn connect_dyn(&mut self, _rx: &mut dyn Any) -> Result<(), DynConnectError>
+    where
+        Self: 'static,
+    {
+        Err(DynConnectError::NotSupported)
+    }
+}
+
+/// Error returned when connecting type-erased channels
+#[derive(Debug, thiserror::Error)]
+pub enum DynConnectError {
+    #[error("endpoint does not support dynamic connection")]
+    NotSupported,
+
+    #[error("invalid endpoint index {0}")]
+    InvalidIndex(usize),
+
+    #[error("message type mismatch: TX sends `{0}`")]
+    TypeMismatch(&'static str),
+
+    #[error(transparent)]
+    Connect(#[from] TxConnectError),
 }
 
 /// A collection of receiving endpoints. Synchronizing the bundle will synchronize all endpoints it
@@ -28,13 +105,61 @@ pub trait RxBundle: Send {
     fn len(&self) -> usize;
 
     /// Name of the i-th endpoint
-    fn name(&self, index: usize) -> String;
+    ///
+    /// Names are queried for warnings and reports and should not allocate. Bundles with names
+    /// known at compile time return `Cow::Borrowed`; only dynamic bundles return `Cow::Owned`.
+    /// Implementations which returned a `String` can wrap it with `.into()`.
+    fn name(&self, index: usize) -> Cow<'static, str>;
 
     /// Synchronizes all endpoints
     fn sync_all(&mut self, result: &mut [SyncResult]);
 
     /// Connection status of all endpoints in the budle
     fn check_connection(&self) -> ConnectionCheck;
+
+    /// Contract expected by the i-th endpoint
+    fn contract(&self, _index: usize) -> Option<ChannelContract> {
+        None
+    }
+
+    /// Hash of the message type of the i-th endpoint, see [super::message_type_hash]
+    fn message_type_hash(&self, _index: usize) -> Option<u64> {
+        None
+    }
+
+    /// Type-erased access to the i-th endpoint used to connect channels dynamically
+    fn endpoint_mut(&mut self, _index: usize) -> Option<&mut dyn Any>
+    where
+        Self: 'static,
+    {
+        None
+    }
+
+    /// Number of messages available in the i-th endpoint, see [Rx::queue_len]
+    fn queue_len(&self, _index: usize) -> Option<usize> {
+        None
+    }
+
+    /// Removes all messages queued in the i-th endpoint, see [Rx::take_backlog]
+    fn take_backlog(&mut self, _index: usize) -> Option<Backlog>
+    where
+        Self: 'static,
+    {
+        None
+    }
+
+    /// Access to the back stage of the i-th endpoint, see [Rx::tap_point]
+    fn tap_point(&self, _index: usize) -> Option<Arc<dyn TapPoint>>
+    where
+        Self: 'static,
+    {
+        None
+    }
+
+    /// Index of the endpoint with given name
+    fn index_of(&self, name: &str) -> Option<usize> {
+        (0..self.len()).find(|&i| self.name(i) == name)
+    }
 }
 
 /// A collection of transmitting endpoints. Flushing the bundle will flush all endpoints it
@@ -44,15 +169,46 @@ pub trait TxBundle: Send {
     fn len(&self) -> usize;
 
     /// Name of the i-th endpoint
-    fn name(&self, index: usize) -> String;
+    ///
+    /// Names are queried for warnings and reports and should not allocate. Bundles with names
+    /// known at compile time return `Cow::Borrowed`; only dynamic bundles return `Cow::Owned`.
+    /// Implementations which returned a `String` can wrap it with `.into()`.
+    fn name(&self, index: usize) -> Cow<'static, str>;
 
     /// Flushes all endpoints
     fn flush_all(&mut self, results: &mut [FlushResult]);
 
     /// Connection status of all endpoints in the budle
     fn check_connection(&self) -> ConnectionCheck;
+
+    /// Contract promised by the i-th endpoint
+    fn contract(&self, _index: usize) -> Option<ChannelContract> {
+        None
+    }
+
+    /// Hash of the message type of the i-th endpoint, see [super::message_type_hash]
+    fn message_type_hash(&self, _index: usize) -> Option<u64> {
+        None
+    }
+
+    /// Connects the i-th endpoint to a type-erased receiver as returned by
+    /// `RxBundle::endpoint_mut`
+    fn connect_dyn(&mut self, index: usize, _rx: &mut dyn Any) -> Result<(), DynConnectError>
+    where
+        Self: 'static,
+    {
+        Err(DynConnectError::InvalidIndex(index))
+    }
+
+    /// Index of the endpoint with given name
+    fn index_of(&self, name: &str) -> Option<usize> {
+        (0..self.len()).find(|&i| self.name(i) == name)
+    }
 }
 
+/// Names of tuple bundle endpoints
+const TUPLE_NAMES: [&str; 8] = ["0", "1", "2", "3", "4", "5", "6", "7"];
+
 macro_rules! count {
     () => (0usize);
     ($x:tt $($xs:tt)*) => (1usize + count!($($xs)*));
@@ -63,7 +219,7 @@ impl RxBundle for () {
         0
     }
 
-    fn name(&self, _index: usize) -> String {
+    fn name(&self, _index: usize) -> Cow<'static, str> {
         panic!("empty bundle")
     }
 
@@ -81,10 +237,10 @@ macro_rules! impl_rx_bundle_tuple {
                 count!($($ty)*)
             }
 
-            fn name(&self, index: usize) -> String {
+            fn name(&self, index: usize) -> Cow<'static, str> {
                 let len = count!($($ty)*);
                 assert!(index < len);
-                format!("{index}")
+                Cow::Borrowed(TUPLE_NAMES[index])
             }
 
             fn sync_all(&mut self, results: &mut [SyncResult]) {
@@ -97,6 +253,57 @@ macro_rules! impl_rx_bundle_tuple {
                 $(cc.mark($i, paste!{self.$i}.is_connected());)*
                 cc
             }
+
+            fn contract(&self, index: usize) -> Option<ChannelContract> {
+                match index {
+                    $($i => paste!{self.$i}.contract(),)*
+                    _ => None,
+                }
+            }
+
+            fn message_type_hash(&self, index: usize) -> Option<u64> {
+                match index {
+                    $($i => paste!{self.$i}.message_type_hash(),)*
+                    _ => None,
+                }
+            }
+
+            fn endpoint_mut(&mut self, index: usize) -> Option<&mut dyn Any>
+            where
+                Self: 'static,
+            {
+                match index {
+                    $($i => paste!{self.$i}.as_any_mut(),)*
+                    _ => None,
+                }
+            }
+
+            fn queue_len(&self, index: usize) -> Option<usize> {
+                match index {
+                    $($i => paste!{self.$i}.queue_len(),)*
+                    _ => None,
+                }
+            }
+
+            fn take_backlog(&mut self, index: usize) -> Option<Backlog>
+            where
+                Self: 'static,
+            {
+                match index {
+                    $($i => paste!{self.$i}.take_backlog(),)*
+                    _ => None,
+                }
+            }
+
+            fn tap_point(&self, index: usize) -> Option<Arc<dyn TapPoint>>
+            where
+                Self: 'static,
+            {
+                match index {
+                    $($i => paste!{self.$i}.tap_point(),)*
+                    _ => None,
+                }
+            }
         }
     };
 }
@@ -115,7 +322,7 @@ impl TxBundle for () {
         0
     }
 
-    fn name(&self, _index: usize) -> String {
+    fn name(&self, _index: usize) -> Cow<'static, str> {
         panic!("empty bundle")
     }
 
@@ -133,10 +340,10 @@ macro_rules! impl_tx_bundle_tuple {
                 count!($($ty)*)
             }
 
-            fn name(&self, index: usize) -> String {
+            fn name(&self, index: usize) -> Cow<'static, str> {
                 let len = count!($($ty)*);
                 assert!(index < len);
-                format!("{index}")
+                Cow::Borrowed(TUPLE_NAMES[index])
             }
 
             fn flush_all(&mut self, results: &mut [FlushResult]) {
@@ -149,6 +356,30 @@ macro_rules! impl_tx_bundle_tuple {
                 $(cc.mark($i, paste!{self.$i}.is_connected());)*
                 cc
             }
+
+            fn contract(&self, index: usize) -> Option<ChannelContract> {
+                match index {
+                    $($i => paste!{self.$i}.contract(),)*
+                    _ => None,
+                }
+            }
+
+            fn message_type_hash(&self, index: usize) -> Option<u64> {
+                match index {
+                    $($i => paste!{self.$i}.message_type_hash(),)*
+                    _ => None,
+                }
+            }
+
+            fn connect_dyn(&mut self, index: usize, rx: &mut dyn Any) -> Result<(), DynConnectError>
+            where
+                Self: 'static,
+            {
+                match index {
+                    $($i => paste!{self.$i}.connect_dyn(rx),)*
+                    _ => Err(DynConnectError::InvalidIndex(index)),
+                }
+            }
         }
     };
 }
@@ -163,54 +394,70 @@ impl_tx_bundle_tuple!(A, 0, B, 1, C, 2, D, 3, E, 4, F, 5, G, 6);
 impl_tx_bundle_tuple!(A, 0, B, 1, C, 2, D, 3, E, 4, F, 5, G, 6, H, 7);
 
 /// A collection of boolean flags indicating if an endpoint is connected.
-#[derive(Debug)]
-pub struct ConnectionCheck(u8, u64);
-
-impl Default for ConnectionCheck {
-    fn default() -> Self {
-        Self(0, 0)
-    }
+///
+/// Flags of the first 64 endpoints are stored inline such that checking common bundles does not
+/// allocate.
+#[derive(Debug, Default)]
+pub struct ConnectionCheck {
+    len: usize,
+    bits: u64,
+    more_bits: Vec<u64>,
 }
 
 impl ConnectionCheck {
     pub fn new(len: usize) -> Self {
-        assert!(len <= MAX_RECEIVER_COUNT, "too many connections: len={len}");
-
-        Self(len as u8, 0)
+        Self {
+            len,
+            bits: 0,
+            more_bits: vec![0; len.saturating_sub(64).div_ceil(64)],
+        }
     }
 
-    /// Sets the connections status of a channel
-    pub fn mark(&mut self, index: usize, is_connected: bool) {
+    fn word_mut(&mut self, index: usize) -> (&mut u64, u64) {
         assert!(
-            index < self.0.into(),
+            index < self.len,
             "invalid channel index: len={}, index={}",
-            self.0,
+            self.len,
             index
         );
 
+        let word = match index / 64 {
+            0 => &mut self.bits,
+            i => &mut self.more_bits[i - 1],
+        };
+        (word, 1 << (index % 64))
+    }
+
+    /// Sets the connections status of a channel
+    pub fn mark(&mut self, index: usize, is_connected: bool) {
+        let (word, mask) = self.word_mut(index);
         if is_connected {
-            self.1 |= 1 << index
+            *word |= mask
         } else {
-            self.1 &= !(1 << index)
+            *word &= !mask
         }
     }
 
     /// Returns true if the channel with given index is connected
     pub fn is_connected(&self, index: usize) -> bool {
         assert!(
-            index < self.0.into(),
+            index < self.len,
             "invalid channel index: len={}, index={}",
-            self.0,
+            self.len,
             index
         );
 
-        self.1 & (1 << index) != 0
+        let word = match index / 64 {
+            0 => self.bits,
+            i => self.more_bits[i - 1],
+        };
+        word & (1 << (index % 64)) != 0
     }
 
     /// Returns true if all endpoints are connected
     pub fn is_fully_connected(&self) -> bool {
         // FIXME I will never know how to safely create a mask with first N bits set...
-        for i in 0..self.0 as usize {
+        for i in 0..self.len {
             if !self.is_connected(i) {
                 return false;
             }
@@ -220,8 +467,6 @@ impl ConnectionCheck {
 
     /// Gets the indices of all unconnected endpoints
     pub fn list_unconnected(&self) -> Vec<usize> {
-        (0..self.0 as usize)
-            .filter(|&i| !self.is_connected(i))
-            .collect()
+        (0..self.len).filter(|&i| !self.is_connected(i)).collect()
     }
 }
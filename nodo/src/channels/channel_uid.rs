@@ -0,0 +1,102 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::fmt;
+use serde::{Deserialize, Serialize};
+
+/// Stable identifier of a channel endpoint
+///
+/// The identifier is a hash of the schedule, sequence, codelet and endpoint names. It is computed
+/// when instances are registered in the manifold and only depends on names, thus it does not
+/// change between runs or builds as long as the names stay the same. It is used to correlate
+/// channels in the graph export and in the inspector.
+///
+/// The message type is not part of the identifier as Rust has no type identifier which is stable
+/// across compiler versions, see [message_type_hash].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ChannelUid(pub u64);
+
+impl ChannelUid {
+    /// Computes the identifier of an endpoint. The channel is given as `rx.<name>` or
+    /// `tx.<name>`. Codelets which are not part of a named sequence use an empty sequence name.
+    pub fn new(schedule: &str, sequence: &str, codelet: &str, channel: &str) -> Self {
+        let mut hash = Fnv1a::new();
+        for part in [schedule, sequence, codelet, channel] {
+            hash.write(part.as_bytes());
+            hash.write(&[0]);
+        }
+        Self(hash.finish())
+    }
+}
+
+/// Formats as 16 hexadecimal digits
+impl fmt::Display for ChannelUid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// Hash of a message type based on its type name
+///
+/// The hash is only meaningful within one build, e.g. to check if two endpoints carry the same
+/// type. `core::any::type_name` is not guaranteed to be stable, so the hash may change with the
+/// compiler version and must not be persisted.
+pub fn message_type_hash<T: ?Sized>() -> u64 {
+    let mut hash = Fnv1a::new();
+    hash.write(core::any::type_name::<T>().as_bytes());
+    hash.finish()
+}
+
+/// 64-bit FNV-1a. The standard hasher is not guaranteed to be stable across Rust releases.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    fn new() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::channels::ChannelUid;
+
+    #[test]
+    fn test_channel_uid() {
+        let uid = ChannelUid::new("main", "sensors", "camera", "tx.out");
+
+        // the uid does not depend on the process
+        assert_eq!(uid, ChannelUid::new("main", "sensors", "camera", "tx.out"));
+        assert_eq!(uid.to_string().len(), 16);
+
+        // every part contributes
+        for other in [
+            ChannelUid::new("aux", "sensors", "camera", "tx.out"),
+            ChannelUid::new("main", "", "camera", "tx.out"),
+            ChannelUid::new("main", "sensors", "lidar", "tx.out"),
+            ChannelUid::new("main", "sensors", "camera", "rx.out"),
+            ChannelUid::new("mai", "nsensors", "camera", "tx.out"),
+            ChannelUid::new("main", "sensorscamera", "", "tx.out"),
+        ] {
+            assert_ne!(uid, other);
+        }
+    }
+
+    #[test]
+    fn test_fnv1a() {
+        let mut hash = super::Fnv1a::new();
+        hash.write(b"a");
+        assert_eq!(hash.finish(), 0xaf63_dc4c_8601_ec8c);
+    }
+}
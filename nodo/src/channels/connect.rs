@@ -1,5 +1,5 @@
 use crate::{
-    channels::TxConnectError,
+    channels::{SpscRx, SpscTx, TxConnectError},
     prelude::{DoubleBufferRx, DoubleBufferTx},
 };
 
@@ -17,13 +17,13 @@ pub trait Connect {
     fn connect(self) -> Result<(), TxConnectError>;
 }
 
-impl<T: Send + Sync> Connect for (&mut DoubleBufferTx<T>, &mut DoubleBufferRx<T>) {
+impl<T: Send + Sync + Clone> Connect for (&mut DoubleBufferTx<T>, &mut DoubleBufferRx<T>) {
     fn connect(self) -> Result<(), TxConnectError> {
         self.0.connect(self.1)
     }
 }
 
-impl<T: Send + Sync> Connect for (Option<&mut DoubleBufferTx<T>>, &mut DoubleBufferRx<T>) {
+impl<T: Send + Sync + Clone> Connect for (Option<&mut DoubleBufferTx<T>>, &mut DoubleBufferRx<T>) {
     fn connect(self) -> Result<(), TxConnectError> {
         if let Some(tx) = self.0 {
             tx.connect(self.1)
@@ -33,7 +33,7 @@ impl<T: Send + Sync> Connect for (Option<&mut DoubleBufferTx<T>>, &mut DoubleBuf
     }
 }
 
-impl<T: Send + Sync> Connect for (&mut DoubleBufferTx<T>, Option<&mut DoubleBufferRx<T>>) {
+impl<T: Send + Sync + Clone> Connect for (&mut DoubleBufferTx<T>, Option<&mut DoubleBufferRx<T>>) {
     fn connect(self) -> Result<(), TxConnectError> {
         if let Some(rx) = self.1 {
             self.0.connect(rx)
@@ -43,7 +43,7 @@ impl<T: Send + Sync> Connect for (&mut DoubleBufferTx<T>, Option<&mut DoubleBuff
     }
 }
 
-impl<T: Send + Sync> Connect
+impl<T: Send + Sync + Clone> Connect
     for (
         Option<&mut DoubleBufferTx<T>>,
         Option<&mut DoubleBufferRx<T>>,
@@ -57,3 +57,9 @@ impl<T: Send + Sync> Connect
         }
     }
 }
+
+impl<T> Connect for (&mut SpscTx<T>, &mut SpscRx<T>) {
+    fn connect(self) -> Result<(), TxConnectError> {
+        self.0.connect(self.1)
+    }
+}
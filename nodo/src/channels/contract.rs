@@ -0,0 +1,168 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::fmt;
+use nodo_core::TimestampKind;
+use serde::{Deserialize, Serialize};
+
+/// Properties of a message stream promised by a transmitter or expected by a receiver
+///
+/// When a transmitter is connected to a receiver with an expected contract the transmitter must
+/// promise at least what the receiver expects. Fields which are not set are not checked.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelContract {
+    /// Messages are published in non-decreasing order of this timestamp
+    pub ordered_by: Option<TimestampKind>,
+
+    /// Messages are never dropped by the transmitter
+    pub lossless: bool,
+
+    /// Upper bound on the rate at which messages are published
+    pub max_rate_hz: Option<f64>,
+}
+
+/// A field of a [ChannelContract] for which the promise does not satisfy the expectation
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContractMismatch {
+    OrderedBy {
+        promised: Option<TimestampKind>,
+        expected: TimestampKind,
+    },
+    Lossless,
+    MaxRateHz {
+        promised: Option<f64>,
+        expected: f64,
+    },
+}
+
+impl ChannelContract {
+    /// Lists all fields for which this contract does not satisfy the expected contract
+    pub fn mismatches(&self, expected: &ChannelContract) -> Vec<ContractMismatch> {
+        let mut result = Vec::new();
+
+        if let Some(expected) = expected.ordered_by {
+            if self.ordered_by != Some(expected) {
+                result.push(ContractMismatch::OrderedBy {
+                    promised: self.ordered_by,
+                    expected,
+                });
+            }
+        }
+
+        if expected.lossless && !self.lossless {
+            result.push(ContractMismatch::Lossless);
+        }
+
+        if let Some(expected) = expected.max_rate_hz {
+            if self.max_rate_hz.is_none_or(|promised| promised > expected) {
+                result.push(ContractMismatch::MaxRateHz {
+                    promised: self.max_rate_hz,
+                    expected,
+                });
+            }
+        }
+
+        result
+    }
+}
+
+impl fmt::Display for ContractMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContractMismatch::OrderedBy { promised, expected } => write!(
+                f,
+                "ordered_by: expected {expected:?}, promised {promised:?}"
+            ),
+            ContractMismatch::Lossless => write!(f, "lossless: expected true, promised false"),
+            ContractMismatch::MaxRateHz { promised, expected } => write!(
+                f,
+                "max_rate_hz: expected at most {expected}, promised {promised:?}"
+            ),
+        }
+    }
+}
+
+/// List of contract mismatches used in connection errors
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractMismatches(pub Vec<ContractMismatch>);
+
+impl fmt::Display for ContractMismatches {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, mismatch) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{mismatch}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::channels::{ChannelContract, ContractMismatch};
+    use nodo_core::TimestampKind;
+
+    fn promise() -> ChannelContract {
+        ChannelContract {
+            ordered_by: Some(TimestampKind::Acq),
+            lossless: true,
+            max_rate_hz: Some(50.0),
+        }
+    }
+
+    #[test]
+    fn test_compatible() {
+        assert!(promise().mismatches(&promise()).is_empty());
+        assert!(promise()
+            .mismatches(&ChannelContract {
+                max_rate_hz: Some(100.0),
+                ..promise()
+            })
+            .is_empty());
+    }
+
+    #[test]
+    fn test_partially_specified() {
+        // nothing expected
+        assert!(ChannelContract::default()
+            .mismatches(&ChannelContract::default())
+            .is_empty());
+
+        // only ordering expected
+        let expected = ChannelContract {
+            ordered_by: Some(TimestampKind::Acq),
+            ..Default::default()
+        };
+        assert!(promise().mismatches(&expected).is_empty());
+        assert_eq!(
+            ChannelContract::default().mismatches(&expected),
+            [ContractMismatch::OrderedBy {
+                promised: None,
+                expected: TimestampKind::Acq
+            }]
+        );
+    }
+
+    #[test]
+    fn test_incompatible() {
+        let promised = ChannelContract {
+            ordered_by: Some(TimestampKind::Pub),
+            lossless: false,
+            max_rate_hz: Some(200.0),
+        };
+        assert_eq!(
+            promised.mismatches(&promise()),
+            [
+                ContractMismatch::OrderedBy {
+                    promised: Some(TimestampKind::Pub),
+                    expected: TimestampKind::Acq
+                },
+                ContractMismatch::Lossless,
+                ContractMismatch::MaxRateHz {
+                    promised: Some(200.0),
+                    expected: 50.0
+                },
+            ]
+        );
+    }
+}
@@ -2,17 +2,24 @@
 
 use crate::{
     channels::{
-        BackStage, ConnectionCheck, FlushResult, FrontStage, OverflowPolicy, Rx, RxBundle,
-        RxChannelTimeseries, SyncResult, Tx, TxBundle,
+        message_type_hash, BackStage, Backlog, ChannelContract, ConnectionCheck,
+        ContractMismatches, DynConnectError, FlushResult, FrontStage, LatencyClock, OverflowPolicy,
+        Rx, RxBundle, RxChannelTimeseries, SyncResult, TapPoint, Tx, TxBundle,
     },
+    codelet::CountTotal,
     prelude::RetentionPolicy,
 };
-use core::ops;
-use nodo_core::{Message, TimestampKind};
+use core::{any::Any, ops, time::Duration};
+use nodo_core::{Clock, Message, Pubtime, PubtimeMarker, TimestampKind};
+use paste::paste;
 use std::{
-    collections::vec_deque,
+    borrow::Cow,
+    collections::{vec_deque, VecDeque},
     fmt,
-    sync::{Arc, RwLock},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, RwLock,
+    },
 };
 
 /// The maximum number of receivers which can be connected to a single transmitter. This is a
@@ -26,7 +33,19 @@ pub const MAX_RECEIVER_COUNT: usize = 64;
 /// large data blocks should use memory sharing like `Rc` to avoid costly memory copies.
 pub struct DoubleBufferTx<T> {
     outbox: BackStage<T>,
-    connections: Vec<SharedBackStage<T>>,
+    connections: Vec<Connection<T>>,
+    is_closed: bool,
+    batch: Vec<T>,
+    replay: VecDeque<T>,
+    replay_capacity: usize,
+    contract: Option<ChannelContract>,
+    delivery: Option<DeliveryStats>,
+
+    /// Number of pushes since the last flush which failed because the outbox was full
+    queue_full: usize,
+
+    /// Set by receivers which were rebound to another transmitter
+    has_detached: Arc<AtomicBool>,
 }
 
 /// The receiving side of a double-buffered SP-MC channel
@@ -42,10 +61,30 @@ pub struct DoubleBufferRx<T> {
     back: SharedBackStage<T>,
     front: FrontStage<T>,
     is_connected: bool,
+    expected_contract: Option<ChannelContract>,
+    delivery: Option<RxDeliveryStats>,
+    arrival_stamps: VecDeque<u64>,
+
+    /// Flag of the connected transmitter which is set when the receiver is rebound
+    tx_has_detached: Option<Arc<AtomicBool>>,
 }
 
 type SharedBackStage<T> = Arc<RwLock<BackStage<T>>>;
 
+/// Predicate deciding which messages are published to a connection
+pub type TxFilter<T> = Arc<dyn Fn(&T) -> bool + Send + Sync>;
+
+struct Connection<T> {
+    stage: SharedBackStage<T>,
+    filter: Option<TxFilter<T>>,
+}
+
+impl<T> Connection<T> {
+    fn accepts(&self, value: &T) -> bool {
+        self.filter.as_ref().is_none_or(|f| f(value))
+    }
+}
+
 impl<T> DoubleBufferTx<T> {
     /// Creates a new TX channel with fixed capacity
     /// TODO rename to `new_fixed`
@@ -53,6 +92,14 @@ impl<T> DoubleBufferTx<T> {
         Self {
             outbox: BackStage::new(OverflowPolicy::Reject(capacity), RetentionPolicy::Drop),
             connections: Vec::new(),
+            is_closed: false,
+            batch: Vec::new(),
+            replay: VecDeque::new(),
+            replay_capacity: 0,
+            contract: None,
+            delivery: None,
+            queue_full: 0,
+            has_detached: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -64,12 +111,69 @@ impl<T> DoubleBufferTx<T> {
         Self {
             outbox: BackStage::new(OverflowPolicy::Resize, RetentionPolicy::Drop),
             connections: Vec::new(),
+            is_closed: false,
+            batch: Vec::new(),
+            replay: VecDeque::new(),
+            replay_capacity: 0,
+            contract: None,
+            delivery: None,
+            queue_full: 0,
+            has_detached: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// Keeps the last `count` published messages and replays them to receivers which are connected
+    /// later (builder style)
+    ///
+    /// Replayed messages are cloned into the back stage of the new receiver oldest-first such that
+    /// its next sync sees them. Messages are replayed as they are, i.e. with their original
+    /// sequence number and timestamps. Keeping the history costs one additional clone per
+    /// published message. A receiver with a `Reject` policy only receives the newest messages
+    /// which fit into its queue.
+    #[must_use]
+    pub fn with_replay(mut self, count: usize) -> Self {
+        self.replay_capacity = count;
+        self.replay = VecDeque::with_capacity(count);
+        self
+    }
+
+    /// Declares properties of the published message stream (builder style)
+    ///
+    /// Connecting to a receiver fails if this contract does not satisfy the contract expected by
+    /// the receiver.
+    #[must_use]
+    pub fn with_contract(mut self, contract: ChannelContract) -> Self {
+        self.contract = Some(contract);
+        self
+    }
+
+    pub fn contract(&self) -> Option<&ChannelContract> {
+        self.contract.as_ref()
+    }
+
+    /// Enables per-connection delivery counters for debugging message loss
+    ///
+    /// Flush counts messages delivered to each connection and keeps a history of the most recent
+    /// deliveries. The counter of the last delivered message is also passed to the receiver, see
+    /// `DoubleBufferRx::delivery_stats`. Messages themselves are not modified.
+    pub fn enable_delivery_tracking(&mut self) {
+        self.delivery.get_or_insert_with(DeliveryStats::default);
+    }
+
+    /// Delivery counters if delivery tracking is enabled
+    pub fn delivery_stats(&self) -> Option<&DeliveryStats> {
+        self.delivery.as_ref()
+    }
+
     /// Puts a message in the outbox
     pub fn push(&mut self, value: T) -> Result<(), TxSendError> {
-        self.outbox.push(value).map_err(|_| TxSendError::QueueFull)
+        if self.is_closed {
+            return Err(TxSendError::Closed);
+        }
+        self.outbox.push(value).map_err(|_| {
+            self.queue_full += 1;
+            TxSendError::QueueFull
+        })
     }
 
     /// Puts multiple messages in the outbox
@@ -80,6 +184,42 @@ impl<T> DoubleBufferTx<T> {
         Ok(())
     }
 
+    /// Puts clones of as many messages as the outbox accepts and returns their count
+    ///
+    /// With a fixed capacity messages are accepted starting with the first one until the outbox
+    /// is full. With the `Forget` and `Resize` policies all messages are accepted.
+    pub fn push_slice(&mut self, values: &[T]) -> Result<usize, TxSendError>
+    where
+        T: Clone,
+    {
+        if self.is_closed {
+            return Err(TxSendError::Closed);
+        }
+        Ok(self.outbox.push_slice(values))
+    }
+
+    /// Puts clones of all messages in the outbox or none of them if they do not fit
+    pub fn push_slice_all(&mut self, values: &[T]) -> Result<(), TxSendError>
+    where
+        T: Clone,
+    {
+        if self.is_closed {
+            return Err(TxSendError::Closed);
+        }
+        self.outbox.push_all(values.iter().cloned()).map_err(|_| {
+            self.queue_full += 1;
+            TxSendError::QueueFull
+        })
+    }
+
+    /// Starts a batch of messages which are only put in the outbox when the batch is committed
+    ///
+    /// Dropping the guard without calling `commit` discards all messages of the batch.
+    pub fn begin_batch(&mut self) -> BatchGuard<'_, T> {
+        self.batch.clear();
+        BatchGuard { tx: self }
+    }
+
     /// Connects a receiver to this transmitter
     ///
     /// Receivers must be connected to at most one transmitter. There is also a technical connection
@@ -88,16 +228,93 @@ impl<T> DoubleBufferTx<T> {
     /// with the "Resize" policy as this will lead to failed message passing.
     pub fn connect(&mut self, rx: &mut DoubleBufferRx<T>) -> Result<(), TxConnectError>
     where
-        T: Send + Sync,
+        T: Send + Sync + Clone,
+    {
+        self.connect_impl(rx, None)
+    }
+
+    /// Connects a receiver which only receives messages for which the predicate returns true
+    ///
+    /// The predicate is evaluated during flush before messages are cloned. Messages which are
+    /// filtered out are counted in `FlushResult::filtered`.
+    pub fn connect_filtered(
+        &mut self,
+        rx: &mut DoubleBufferRx<T>,
+        predicate: TxFilter<T>,
+    ) -> Result<(), TxConnectError>
+    where
+        T: Send + Sync + Clone,
+    {
+        self.connect_impl(rx, Some(predicate))
+    }
+
+    /// Connects a receiver without replaying the history configured with `with_replay`
+    ///
+    /// The receiver only gets messages which are flushed after it was connected.
+    pub fn connect_live(&mut self, rx: &mut DoubleBufferRx<T>) -> Result<(), TxConnectError>
+    where
+        T: Send + Sync + Clone,
+    {
+        if rx.is_connected() {
+            return Err(TxConnectError::ReceiverAlreadyConnected);
+        }
+
+        self.check_connect(rx)?;
+        self.attach(rx, None, false);
+
+        Ok(())
+    }
+
+    /// Number of connected receivers
+    ///
+    /// Receivers which were rebound are only removed on the next flush. Receivers which were
+    /// dropped stay connected, see `disconnect_dropped`.
+    pub fn connection_count(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// Removes connections of receivers which were dropped
+    pub fn disconnect_dropped(&mut self) {
+        self.remove_connections(|stage| Arc::strong_count(stage) == 1);
+    }
+
+    fn connect_impl(
+        &mut self,
+        rx: &mut DoubleBufferRx<T>,
+        filter: Option<TxFilter<T>>,
+    ) -> Result<(), TxConnectError>
+    where
+        T: Send + Sync + Clone,
     {
         if rx.is_connected() {
             return Err(TxConnectError::ReceiverAlreadyConnected);
         }
 
+        self.check_connect(rx)?;
+        self.attach(rx, filter, true);
+
+        Ok(())
+    }
+
+    /// Checks if a receiver could be connected ignoring whether it is already connected
+    fn check_connect(&self, rx: &DoubleBufferRx<T>) -> Result<(), TxConnectError> {
         if self.connections.len() >= MAX_RECEIVER_COUNT {
             return Err(TxConnectError::MaxConnectionCountExceeded);
         }
 
+        if let Some(expected) = rx.expected_contract.as_ref() {
+            let mismatches = self
+                .contract
+                .clone()
+                .unwrap_or_default()
+                .mismatches(expected);
+            if !mismatches.is_empty() {
+                return Err(TxConnectError::ContractMismatch(ContractMismatches(
+                    mismatches,
+                )));
+            }
+        }
+
         if matches!(self.outbox.overflow_policy(), OverflowPolicy::Resize)
             && matches!(
                 rx.back.read().unwrap().overflow_policy(),
@@ -107,11 +324,238 @@ impl<T> DoubleBufferTx<T> {
             return Err(TxConnectError::PolicyMismatch);
         }
 
-        self.connections.push(rx.back.clone());
+        Ok(())
+    }
+
+    fn attach(&mut self, rx: &mut DoubleBufferRx<T>, filter: Option<TxFilter<T>>, replay: bool)
+    where
+        T: Clone,
+    {
+        if replay && !self.replay.is_empty() {
+            let mut back = rx.back.write().unwrap();
+            let room = match back.overflow_policy() {
+                OverflowPolicy::Reject(n) => n.saturating_sub(back.len()),
+                OverflowPolicy::Forget(_) | OverflowPolicy::Resize => usize::MAX,
+            };
+            let history = self
+                .replay
+                .iter()
+                .filter(|v| filter.as_ref().is_none_or(|f| f(v)))
+                .collect::<Vec<_>>();
+            for v in history.iter().skip(history.len().saturating_sub(room)) {
+                // cannot fail as only as many messages as fit are pushed
+                back.push((*v).clone()).ok();
+            }
+        }
+
+        if self.is_closed {
+            rx.back.write().unwrap().close();
+        }
+
+        self.connections.push(Connection {
+            stage: rx.back.clone(),
+            filter,
+        });
         rx.is_connected = true;
+        rx.tx_has_detached = Some(self.has_detached.clone());
+    }
+
+    /// Replaces the outbox with one using a new overflow policy
+    ///
+    /// Connections are preserved and messages pending in the outbox are moved to the new outbox
+    /// in order. With the `Forget` policy the oldest pending messages are dropped if they do not
+    /// fit. Messages flushed before the swap are already in the back stages of receivers and are
+    /// not affected. On error the outbox is left unchanged.
+    pub fn replace_and_migrate(
+        &mut self,
+        overflow_policy: OverflowPolicy,
+    ) -> Result<(), TxReplaceError> {
+        if let OverflowPolicy::Reject(n) = overflow_policy {
+            if self.outbox.len() > n {
+                return Err(TxReplaceError::PendingOverflow {
+                    pending: self.outbox.len(),
+                    capacity: n,
+                });
+            }
+        }
+
+        if matches!(overflow_policy, OverflowPolicy::Resize)
+            && self.connections.iter().any(|c| {
+                matches!(
+                    c.stage.read().unwrap().overflow_policy(),
+                    OverflowPolicy::Reject(_)
+                )
+            })
+        {
+            return Err(TxReplaceError::PolicyMismatch);
+        }
+
+        let mut outbox = BackStage::new(overflow_policy, RetentionPolicy::Drop);
+        for v in self.outbox.drain_all() {
+            // cannot fail as capacity was checked above
+            outbox.push(v).ok();
+        }
+        self.outbox = outbox;
+
+        Ok(())
+    }
+
+    /// Removes connections of receivers which were rebound to another transmitter
+    fn prune_detached(&mut self) {
+        if self.has_detached.swap(false, Ordering::AcqRel) {
+            self.remove_connections(|stage| stage.read().unwrap().is_detached());
+        }
+    }
+
+    fn remove_connections(&mut self, pred: impl Fn(&SharedBackStage<T>) -> bool) {
+        let mut i = 0;
+        while i < self.connections.len() {
+            if pred(&self.connections[i].stage) {
+                self.connections.remove(i);
+                if let Some(delivery) = self.delivery.as_mut() {
+                    if i < delivery.delivered.len() {
+                        delivery.delivered.remove(i);
+                    }
+                }
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// Closes the stream
+    ///
+    /// Messages already in the outbox are still sent on the next flush together with the close
+    /// marker. Receivers will observe the end of the stream after they received all messages.
+    /// Pushing messages after the channel was closed fails.
+    pub fn close(&mut self) {
+        self.is_closed = true;
+    }
 
+    /// Returns true if the stream was closed
+    pub fn is_closed(&self) -> bool {
+        self.is_closed
+    }
+}
+
+/// Collects messages for a transactional push, see `DoubleBufferTx::begin_batch`
+pub struct BatchGuard<'a, T> {
+    tx: &'a mut DoubleBufferTx<T>,
+}
+
+impl<T> BatchGuard<'_, T> {
+    /// Adds a message to the batch
+    pub fn push(&mut self, value: T) -> Result<(), TxSendError> {
+        if self.tx.is_closed {
+            return Err(TxSendError::Closed);
+        }
+        self.tx.batch.push(value);
         Ok(())
     }
+
+    /// Number of messages in the batch
+    pub fn len(&self) -> usize {
+        self.tx.batch.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tx.batch.is_empty()
+    }
+
+    /// Moves all messages of the batch into the outbox
+    ///
+    /// Either all messages are added or none. If the outbox does not have enough room for all
+    /// messages the batch is discarded and `QueueFull` is returned.
+    pub fn commit(self) -> Result<(), TxSendError> {
+        let tx = &mut *self.tx;
+        if tx.is_closed {
+            return Err(TxSendError::Closed);
+        }
+        tx.outbox.push_all(tx.batch.drain(..)).map_err(|_| {
+            tx.queue_full += 1;
+            TxSendError::QueueFull
+        })
+    }
+}
+
+impl<T> Drop for BatchGuard<'_, T> {
+    fn drop(&mut self) {
+        // keeps the allocation for the next batch
+        self.tx.batch.clear();
+    }
+}
+
+/// Number of recent deliveries kept by a transmitter with delivery tracking enabled
+pub const DELIVERY_HISTORY_LEN: usize = 32;
+
+/// A message delivered to a connection. The counter is the number of messages delivered to that
+/// connection including this one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Delivery {
+    pub connection: usize,
+    pub counter: u64,
+}
+
+/// Delivery counters of a transmitter, see `DoubleBufferTx::enable_delivery_tracking`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DeliveryStats {
+    /// Number of messages delivered to each connection
+    pub delivered: Vec<u64>,
+
+    /// The most recent deliveries, oldest first
+    pub recent: VecDeque<Delivery>,
+}
+
+impl DeliveryStats {
+    fn record<T>(&mut self, connection: usize, count: usize, stage: &mut BackStage<T>) {
+        if count == 0 {
+            return;
+        }
+
+        self.delivered[connection] += count as u64;
+        let last = self.delivered[connection];
+        stage.set_delivery_counter(last);
+
+        // only the newest deliveries can end up in the history
+        let first =
+            (last + 1 - count as u64).max((last + 1).saturating_sub(DELIVERY_HISTORY_LEN as u64));
+        for counter in first..=last {
+            if self.recent.len() == DELIVERY_HISTORY_LEN {
+                self.recent.pop_front();
+            }
+            self.recent.push_back(Delivery {
+                connection,
+                counter,
+            });
+        }
+    }
+}
+
+/// Delivery counters observed by a receiver
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RxDeliveryStats {
+    /// Delivery counter of the last message received during sync
+    pub last_counter: u64,
+
+    /// Number of messages received during sync since delivery tracking was enabled
+    pub received: u64,
+}
+
+impl RxDeliveryStats {
+    /// Number of messages which were delivered to the receiver but which it did not receive, for
+    /// example because they were forgotten due to the overflow policy
+    pub fn missed(&self) -> u64 {
+        self.last_counter.saturating_sub(self.received)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TxReplaceError {
+    #[error("{pending} pending messages do not fit into the new outbox with capacity {capacity}")]
+    PendingOverflow { pending: usize, capacity: usize },
+
+    #[error("Cannot use the TX policy `Resize` with a connected RX with policy `Reject`")]
+    PolicyMismatch,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -127,17 +571,45 @@ pub enum TxConnectError {
              Either change the TX policy to `Reject` or the RX policy to `Resize` or `Forget`."
     )]
     PolicyMismatch,
+
+    #[error("TX does not satisfy the contract expected by RX: {0}")]
+    ContractMismatch(ContractMismatches),
 }
 
 impl<T: Send + Sync + Clone> Tx for DoubleBufferTx<T> {
     fn flush(&mut self) -> FlushResult {
+        self.prune_detached();
+
         let mut result = FlushResult::default();
         result.available = self.outbox.len();
+        result.queue_full = core::mem::take(&mut self.queue_full);
+
+        if self.connections.iter().any(|c| c.filter.is_some()) {
+            result.filtered = vec![0; self.connections.len()];
+        }
+
+        if self.replay_capacity > 0 {
+            for v in self.outbox.iter() {
+                if self.replay.len() == self.replay_capacity {
+                    self.replay.pop_front();
+                }
+                self.replay.push_back(v.clone());
+            }
+        }
+
+        if let Some(delivery) = self.delivery.as_mut() {
+            delivery.delivered.resize(self.connections.len(), 0);
+        }
 
         // clone messages for connections 2..N
         for (i, rx) in self.connections.iter().enumerate().skip(1) {
-            let mut q = rx.write().unwrap();
+            let mut q = rx.stage.write().unwrap();
+            let published_before = result.published;
             for v in self.outbox.iter() {
+                if !rx.accepts(v) {
+                    result.filtered[i] += 1;
+                    continue;
+                }
                 if matches!(q.push((*v).clone()), Err(_)) {
                     result.error_indicator.mark(i);
                     break;
@@ -145,29 +617,71 @@ impl<T: Send + Sync + Clone> Tx for DoubleBufferTx<T> {
                 result.cloned += 1;
                 result.published += 1;
             }
+            if let Some(delivery) = self.delivery.as_mut() {
+                delivery.record(i, result.published - published_before, &mut q);
+            }
         }
 
         // move messages for connection 1
         if let Some(first_rx) = self.connections.get(0) {
-            let mut q = first_rx.write().unwrap();
+            let mut q = first_rx.stage.write().unwrap();
+            let published_before = result.published;
             for v in self.outbox.drain_all() {
+                if !first_rx.accepts(&v) {
+                    result.filtered[0] += 1;
+                    continue;
+                }
                 if matches!(q.push(v), Err(_)) {
                     result.error_indicator.mark(0);
                     break;
                 }
                 result.published += 1;
             }
+            if let Some(delivery) = self.delivery.as_mut() {
+                delivery.record(0, result.published - published_before, &mut q);
+            }
         } else {
             // still clear outbox if there is no connection
             self.outbox.clear();
         }
 
+        // the close marker is sent after all remaining messages
+        if self.is_closed {
+            for rx in self.connections.iter() {
+                rx.stage.write().unwrap().close();
+            }
+        }
+
         result
     }
 
     fn is_connected(&self) -> bool {
         !self.connections.is_empty()
     }
+
+    fn message_type_hash(&self) -> Option<u64> {
+        Some(message_type_hash::<T>())
+    }
+
+    fn contract(&self) -> Option<ChannelContract> {
+        self.contract.clone()
+    }
+
+    fn connect_dyn(&mut self, rx: &mut dyn Any) -> Result<(), DynConnectError>
+    where
+        Self: 'static,
+    {
+        if let Some(rx) = rx.downcast_mut::<DoubleBufferRx<T>>() {
+            Ok(self.connect(rx)?)
+        } else if let Some(maybe_rx) = rx.downcast_mut::<Option<DoubleBufferRx<T>>>() {
+            match maybe_rx {
+                Some(rx) => Ok(self.connect(rx)?),
+                None => Ok(()),
+            }
+        } else {
+            Err(DynConnectError::TypeMismatch(std::any::type_name::<T>()))
+        }
+    }
 }
 
 impl<T: Send + Sync + Clone> Tx for Option<DoubleBufferTx<T>> {
@@ -182,6 +696,24 @@ impl<T: Send + Sync + Clone> Tx for Option<DoubleBufferTx<T>> {
     fn is_connected(&self) -> bool {
         self.as_ref().map_or(false, |tx| tx.is_connected())
     }
+
+    fn message_type_hash(&self) -> Option<u64> {
+        Some(message_type_hash::<T>())
+    }
+
+    fn contract(&self) -> Option<ChannelContract> {
+        self.as_ref().and_then(|tx| tx.contract.clone())
+    }
+
+    fn connect_dyn(&mut self, rx: &mut dyn Any) -> Result<(), DynConnectError>
+    where
+        Self: 'static,
+    {
+        match self.as_mut() {
+            Some(tx) => Tx::connect_dyn(tx, rx),
+            None => Ok(()),
+        }
+    }
 }
 
 impl<T: Send + Sync + Clone> TxBundle for DoubleBufferTx<T> {
@@ -189,9 +721,9 @@ impl<T: Send + Sync + Clone> TxBundle for DoubleBufferTx<T> {
         1
     }
 
-    fn name(&self, index: usize) -> String {
+    fn name(&self, index: usize) -> Cow<'static, str> {
         assert_eq!(index, 0);
-        String::from("out")
+        Cow::Borrowed("out")
     }
 
     fn flush_all(&mut self, result: &mut [FlushResult]) {
@@ -203,6 +735,26 @@ impl<T: Send + Sync + Clone> TxBundle for DoubleBufferTx<T> {
         cc.mark(0, self.is_connected());
         cc
     }
+
+    fn message_type_hash(&self, index: usize) -> Option<u64> {
+        assert_eq!(index, 0);
+        Some(message_type_hash::<T>())
+    }
+
+    fn contract(&self, index: usize) -> Option<ChannelContract> {
+        assert_eq!(index, 0);
+        Tx::contract(self)
+    }
+
+    fn connect_dyn(&mut self, index: usize, rx: &mut dyn Any) -> Result<(), DynConnectError>
+    where
+        Self: 'static,
+    {
+        match index {
+            0 => Tx::connect_dyn(self, rx),
+            _ => Err(DynConnectError::InvalidIndex(index)),
+        }
+    }
 }
 
 impl<T: Send + Sync + Clone> TxBundle for Option<DoubleBufferTx<T>> {
@@ -210,9 +762,9 @@ impl<T: Send + Sync + Clone> TxBundle for Option<DoubleBufferTx<T>> {
         1
     }
 
-    fn name(&self, index: usize) -> String {
+    fn name(&self, index: usize) -> Cow<'static, str> {
         assert_eq!(index, 0);
-        String::from("out")
+        Cow::Borrowed("out")
     }
 
     fn flush_all(&mut self, result: &mut [FlushResult]) {
@@ -224,18 +776,49 @@ impl<T: Send + Sync + Clone> TxBundle for Option<DoubleBufferTx<T>> {
         cc.mark(0, self.as_ref().map_or(false, |tx| tx.is_connected()));
         cc
     }
+
+    fn message_type_hash(&self, index: usize) -> Option<u64> {
+        assert_eq!(index, 0);
+        Some(message_type_hash::<T>())
+    }
+
+    fn contract(&self, index: usize) -> Option<ChannelContract> {
+        assert_eq!(index, 0);
+        Tx::contract(self)
+    }
+
+    fn connect_dyn(&mut self, index: usize, rx: &mut dyn Any) -> Result<(), DynConnectError>
+    where
+        Self: 'static,
+    {
+        match index {
+            0 => Tx::connect_dyn(self, rx),
+            _ => Err(DynConnectError::InvalidIndex(index)),
+        }
+    }
 }
 
 impl<T> DoubleBufferRx<T> {
     /// Creates a new RX channel
     /// TODO deprecate in favor of `new_auto_size`, `new_fixed`, and `new_forget`
     pub fn new(overflow_policy: OverflowPolicy, retention_policy: RetentionPolicy) -> Self {
-        let back = BackStage::new(overflow_policy, retention_policy);
+        assert!(
+            !matches!(retention_policy, RetentionPolicy::KeepDecimated { .. }),
+            "Retention policy 'KeepDecimated' requires `DoubleBufferRx::new_timeseries`"
+        );
+        Self::from_back_stage(BackStage::new(overflow_policy, retention_policy))
+    }
+
+    fn from_back_stage(back: BackStage<T>) -> Self {
         let capacity = back.capacity();
         Self {
             back: Arc::new(RwLock::new(back)),
             front: FrontStage::new(capacity),
             is_connected: false,
+            expected_contract: None,
+            delivery: None,
+            arrival_stamps: VecDeque::new(),
+            tx_has_detached: None,
         }
     }
 
@@ -252,16 +835,137 @@ impl<T> DoubleBufferRx<T> {
         Self::new(OverflowPolicy::Resize, RetentionPolicy::Drop)
     }
 
+    /// Detaches from the current transmitter (if any) and connects to the given one
+    ///
+    /// Messages already in the back stage are kept and are received before any message of the
+    /// new transmitter, including messages replayed by it. Messages flushed by the old transmitter
+    /// before the rebind are thus still delivered while later flushes of the old transmitter no
+    /// longer reach this receiver. The old transmitter drops the connection on its next flush. A
+    /// close marker of the old transmitter is discarded. On error the receiver stays connected to
+    /// the old transmitter.
+    pub fn rebind(&mut self, from: &mut DoubleBufferTx<T>) -> Result<(), TxConnectError>
+    where
+        T: Send + Sync + Clone,
+    {
+        from.check_connect(self)?;
+
+        if self.is_connected {
+            let stage = self.back.write().unwrap().detach();
+            self.back = Arc::new(RwLock::new(stage));
+            self.front.reopen();
+            self.is_connected = false;
+            if let Some(flag) = self.tx_has_detached.take() {
+                flag.store(true, Ordering::Release);
+            }
+        }
+
+        from.attach(self, None, true);
+
+        Ok(())
+    }
+
+    /// Declares properties the transmitter must promise when connecting (builder style)
+    #[must_use]
+    pub fn expect_contract(mut self, contract: ChannelContract) -> Self {
+        self.expected_contract = Some(contract);
+        self
+    }
+
+    pub fn expected_contract(&self) -> Option<&ChannelContract> {
+        self.expected_contract.as_ref()
+    }
+
+    /// Delivery counters if the transmitter has delivery tracking enabled, see
+    /// `DoubleBufferTx::enable_delivery_tracking`
+    pub fn delivery_stats(&self) -> Option<&RxDeliveryStats> {
+        self.delivery.as_ref()
+    }
+
+    /// Enables tracking how long messages wait in the back stage before they are synced
+    ///
+    /// Every message pushed by the transmitter is timestamped with the given clock. Sync reports
+    /// the residency of received messages in `SyncResult::residency` and accumulates it in
+    /// `residency_stats`. Use the application monotonic clock of the runtime in production.
+    pub fn enable_latency_tracking<C>(&mut self, clock: C)
+    where
+        C: Clock<PubtimeMarker> + Send + Sync + 'static,
+    {
+        self.back
+            .write()
+            .unwrap()
+            .enable_latency_tracking(Box::new(clock) as LatencyClock);
+    }
+
+    /// Stamps every message pushed by the transmitter with the next value of the counter
+    ///
+    /// Receivers sharing a counter can merge their messages in the order in which they arrived,
+    /// see `arrival_stamps`. Messages already in the back stage are stamped now.
+    pub fn enable_arrival_stamps(&mut self, counter: Arc<AtomicU64>) {
+        self.back.write().unwrap().enable_arrival_stamps(counter);
+    }
+
+    /// Arrival stamps of the messages received by the last sync in the order of the messages.
+    /// Empty if arrival stamps are not enabled, see `enable_arrival_stamps`.
+    pub fn arrival_stamps(&self) -> &VecDeque<u64> {
+        &self.arrival_stamps
+    }
+
+    /// Residency of all messages received since latency tracking was enabled, see
+    /// `enable_latency_tracking`
+    pub fn residency_stats(&self) -> Option<CountTotal> {
+        self.back.read().unwrap().residency_stats().cloned()
+    }
+
     pub fn pop_all(&mut self) -> std::collections::vec_deque::Drain<'_, T> {
         self.front.drain(..)
     }
 
+    /// Moves up to `max` messages in order to the end of `buf` and returns their count
+    pub fn pop_into(&mut self, buf: &mut Vec<T>, max: usize) -> usize {
+        let count = max.min(self.front.len());
+        buf.extend(self.front.drain(..count));
+        count
+    }
+
     /// Number of messages currently visible. Additional messages might be stored in the stage
     /// buffer.
     pub fn len(&self) -> usize {
         self.front.len()
     }
 
+    /// Removes and returns the newest message and discards all older ones
+    ///
+    /// Pairs with [DoubleBufferRx::new_latest] but also works with larger queues in which case
+    /// the backlog is skipped. Discarded messages are counted as dropped at the next sync.
+    pub fn take_latest(&mut self) -> Option<T> {
+        self.front.take_latest()
+    }
+
+    /// Same as [DoubleBufferRx::take_latest], named for symmetry with [Pop::try_pop]
+    pub fn try_pop_latest(&mut self) -> Option<T> {
+        self.take_latest()
+    }
+
+    /// Removes and returns the newest message and discards all older ones, see
+    /// [DoubleBufferRx::take_latest]. Fails like [Pop::pop] if there is no message.
+    pub fn pop_latest(&mut self) -> Result<T, RxRecvError> {
+        self.front.take_latest().ok_or_else(|| {
+            if self.front.is_closed() {
+                RxRecvError::Closed
+            } else {
+                RxRecvError::QueueEmtpy
+            }
+        })
+    }
+
+    /// Removes and returns the newest `n` messages in order and discards all older ones
+    ///
+    /// Discarded messages are counted as dropped at the next sync.
+    pub fn split_last_n(&mut self, n: usize) -> vec_deque::Drain<'_, T> {
+        self.front.retain_last(n);
+        self.front.drain(..)
+    }
+
     /// Access the latest element in the queue (or None)
     pub fn latest(&self) -> Option<&T> {
         let n = self.front.len();
@@ -286,6 +990,11 @@ impl<T> DoubleBufferRx<T> {
         self.front.clear();
     }
 
+    /// Returns true if the transmitter closed the stream and all messages have been consumed
+    pub fn is_closed(&self) -> bool {
+        self.front.is_closed() && self.front.is_empty()
+    }
+
     pub fn drain<R>(&mut self, range: R) -> vec_deque::Drain<'_, T>
     where
         R: ops::RangeBounds<usize>,
@@ -295,6 +1004,17 @@ impl<T> DoubleBufferRx<T> {
 }
 
 impl<T> DoubleBufferRx<Message<T>> {
+    /// Creates a channel for timestamped messages. In addition to the policies supported by `new`
+    /// this supports the `KeepDecimated` retention policy which decimates by acquisition time.
+    pub fn new_timeseries(
+        overflow_policy: OverflowPolicy,
+        retention_policy: RetentionPolicy,
+    ) -> Self {
+        let mut back = BackStage::new(overflow_policy, retention_policy);
+        back.set_sample_time(|msg: &Message<T>| *msg.stamp.acqtime);
+        Self::from_back_stage(back)
+    }
+
     pub fn as_acq_time_series<'a>(&'a self) -> RxChannelTimeseries<'a, T> {
         RxChannelTimeseries {
             channel: self,
@@ -308,6 +1028,27 @@ impl<T> DoubleBufferRx<Message<T>> {
             kind: TimestampKind::Pub,
         }
     }
+
+    /// Age of the first queued message based on its publish time. Returns None if the queue is
+    /// empty. Timestamps in the future count as age zero.
+    pub fn oldest_age(&self, now: Pubtime) -> Option<Duration> {
+        self.oldest_age_by(TimestampKind::Pub, *now)
+    }
+
+    /// Age of the last queued message based on its publish time
+    pub fn latest_age(&self, now: Pubtime) -> Option<Duration> {
+        self.latest_age_by(TimestampKind::Pub, *now)
+    }
+
+    /// Like `oldest_age` but uses the given timestamp of messages
+    pub fn oldest_age_by(&self, kind: TimestampKind, now: Duration) -> Option<Duration> {
+        (!self.front.is_empty()).then(|| now.saturating_sub(self.front[0].stamp[kind]))
+    }
+
+    /// Like `latest_age` but uses the given timestamp of messages
+    pub fn latest_age_by(&self, kind: TimestampKind, now: Duration) -> Option<Duration> {
+        self.latest().map(|msg| now.saturating_sub(msg.stamp[kind]))
+    }
 }
 
 pub trait Pop {
@@ -316,6 +1057,11 @@ pub trait Pop {
     /// Returns true if the inbox is empty.
     fn is_empty(&self) -> bool;
 
+    /// Returns true if the stream was closed and no more messages will arrive
+    fn is_closed(&self) -> bool {
+        false
+    }
+
     /// Removes the next message from the inbox
     fn pop(&mut self) -> Result<Self::Output, RxRecvError>;
 
@@ -342,51 +1088,57 @@ impl<T> Pop for DoubleBufferRx<T> {
         self.front.is_empty()
     }
 
-    fn pop(&mut self) -> Result<T, RxRecvError> {
-        self.front.pop().ok_or(RxRecvError::QueueEmtpy)
-    }
-}
-
-impl<'a, T1: Pop, T2: Pop> Pop for (&'a mut T1, &'a mut T2) {
-    type Output = (<T1 as Pop>::Output, <T2 as Pop>::Output);
-
-    fn is_empty(&self) -> bool {
-        self.0.is_empty() || self.1.is_empty()
+    fn is_closed(&self) -> bool {
+        DoubleBufferRx::is_closed(self)
     }
 
-    fn pop(&mut self) -> Result<Self::Output, RxRecvError> {
-        if self.is_empty() {
-            Err(RxRecvError::QueueEmtpy)
-        } else {
-            Ok((self.0.pop().unwrap(), self.1.pop().unwrap()))
-        }
+    fn pop(&mut self) -> Result<T, RxRecvError> {
+        self.front.pop().ok_or_else(|| {
+            if self.front.is_closed() {
+                RxRecvError::Closed
+            } else {
+                RxRecvError::QueueEmtpy
+            }
+        })
     }
 }
 
-impl<'a, T1: Pop, T2: Pop, T3: Pop> Pop for (&'a mut T1, &'a mut T2, &'a mut T3) {
-    type Output = (
-        <T1 as Pop>::Output,
-        <T2 as Pop>::Output,
-        <T3 as Pop>::Output,
-    );
+macro_rules! impl_pop_tuple {
+    ( $( $ty: ident, $i: literal ),* ) => {
+        impl<'a, $($ty: Pop),*> Pop for ($(&'a mut $ty,)*) {
+            type Output = ($(<$ty as Pop>::Output,)*);
 
-    fn is_empty(&self) -> bool {
-        self.0.is_empty() || self.1.is_empty()
-    }
+            /// Returns true if any of the inboxes is empty
+            fn is_empty(&self) -> bool {
+                false $(|| paste!{self.$i}.is_empty())*
+            }
 
-    fn pop(&mut self) -> Result<Self::Output, RxRecvError> {
-        if self.is_empty() {
-            Err(RxRecvError::QueueEmtpy)
-        } else {
-            Ok((
-                self.0.pop().unwrap(),
-                self.1.pop().unwrap(),
-                self.2.pop().unwrap(),
-            ))
+            fn is_closed(&self) -> bool {
+                false $(|| paste!{self.$i}.is_closed())*
+            }
+
+            /// Pops one message from every inbox if none of them is empty
+            fn pop(&mut self) -> Result<Self::Output, RxRecvError> {
+                if self.is_closed() {
+                    Err(RxRecvError::Closed)
+                } else if self.is_empty() {
+                    Err(RxRecvError::QueueEmtpy)
+                } else {
+                    Ok(($(paste!{self.$i}.pop().unwrap(),)*))
+                }
+            }
         }
-    }
+    };
 }
 
+impl_pop_tuple!(A, 0, B, 1);
+impl_pop_tuple!(A, 0, B, 1, C, 2);
+impl_pop_tuple!(A, 0, B, 1, C, 2, D, 3);
+impl_pop_tuple!(A, 0, B, 1, C, 2, D, 3, E, 4);
+impl_pop_tuple!(A, 0, B, 1, C, 2, D, 3, E, 4, F, 5);
+impl_pop_tuple!(A, 0, B, 1, C, 2, D, 3, E, 4, F, 5, G, 6);
+impl_pop_tuple!(A, 0, B, 1, C, 2, D, 3, E, 4, F, 5, G, 6, H, 7);
+
 impl<T> ops::Index<usize> for DoubleBufferRx<T> {
     type Output = T;
 
@@ -406,8 +1158,53 @@ impl<T: Send + Sync> Rx for DoubleBufferRx<T> {
         self.is_connected
     }
 
+    fn message_type_hash(&self) -> Option<u64> {
+        Some(message_type_hash::<T>())
+    }
+
     fn sync(&mut self) -> SyncResult {
-        self.back.write().unwrap().sync(&mut self.front)
+        let mut back = self.back.write().unwrap();
+        if let Some(stamps) = back.take_arrival_stamps() {
+            self.arrival_stamps = stamps;
+        }
+        let result = back.sync(&mut self.front);
+        if let Some(counter) = back.delivery_counter() {
+            let delivery = self.delivery.get_or_insert_with(RxDeliveryStats::default);
+            delivery.last_counter = counter;
+            delivery.received += result.received as u64;
+        }
+        result
+    }
+
+    fn contract(&self) -> Option<ChannelContract> {
+        self.expected_contract.clone()
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any>
+    where
+        Self: 'static,
+    {
+        Some(self)
+    }
+
+    fn queue_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+
+    fn take_backlog(&mut self) -> Option<Backlog>
+    where
+        Self: 'static,
+    {
+        let mut items = self.front.take_all();
+        items.append(&mut self.back.write().unwrap().take_all());
+        (!items.is_empty()).then(|| Backlog::new(items))
+    }
+
+    fn tap_point(&self) -> Option<Arc<dyn TapPoint>>
+    where
+        Self: 'static,
+    {
+        Some(self.back.clone())
     }
 }
 
@@ -416,9 +1213,42 @@ impl<T: Send + Sync> Rx for Option<DoubleBufferRx<T>> {
         self.as_ref().map_or(false, |rx| rx.is_connected)
     }
 
+    fn message_type_hash(&self) -> Option<u64> {
+        Some(message_type_hash::<T>())
+    }
+
     fn sync(&mut self) -> SyncResult {
         self.as_mut().map_or(SyncResult::ZERO, |rx| rx.sync())
     }
+
+    fn contract(&self) -> Option<ChannelContract> {
+        self.as_ref().and_then(|rx| rx.expected_contract.clone())
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any>
+    where
+        Self: 'static,
+    {
+        Some(self)
+    }
+
+    fn queue_len(&self) -> Option<usize> {
+        self.as_ref().map(DoubleBufferRx::len)
+    }
+
+    fn take_backlog(&mut self) -> Option<Backlog>
+    where
+        Self: 'static,
+    {
+        self.as_mut().and_then(Rx::take_backlog)
+    }
+
+    fn tap_point(&self) -> Option<Arc<dyn TapPoint>>
+    where
+        Self: 'static,
+    {
+        self.as_ref().and_then(Rx::tap_point)
+    }
 }
 
 impl<T: Send + Sync> RxBundle for DoubleBufferRx<T> {
@@ -426,9 +1256,9 @@ impl<T: Send + Sync> RxBundle for DoubleBufferRx<T> {
         1
     }
 
-    fn name(&self, index: usize) -> String {
+    fn name(&self, index: usize) -> Cow<'static, str> {
         assert_eq!(index, 0);
-        String::from("in")
+        Cow::Borrowed("in")
     }
 
     fn sync_all(&mut self, results: &mut [SyncResult]) {
@@ -440,6 +1270,44 @@ impl<T: Send + Sync> RxBundle for DoubleBufferRx<T> {
         cc.mark(0, self.is_connected());
         cc
     }
+
+    fn message_type_hash(&self, index: usize) -> Option<u64> {
+        assert_eq!(index, 0);
+        Some(message_type_hash::<T>())
+    }
+
+    fn contract(&self, index: usize) -> Option<ChannelContract> {
+        assert_eq!(index, 0);
+        Rx::contract(self)
+    }
+
+    fn endpoint_mut(&mut self, index: usize) -> Option<&mut dyn Any>
+    where
+        Self: 'static,
+    {
+        (index == 0).then_some(self as &mut dyn Any)
+    }
+
+    fn queue_len(&self, index: usize) -> Option<usize> {
+        assert_eq!(index, 0);
+        Rx::queue_len(self)
+    }
+
+    fn take_backlog(&mut self, index: usize) -> Option<Backlog>
+    where
+        Self: 'static,
+    {
+        assert_eq!(index, 0);
+        Rx::take_backlog(self)
+    }
+
+    fn tap_point(&self, index: usize) -> Option<Arc<dyn TapPoint>>
+    where
+        Self: 'static,
+    {
+        assert_eq!(index, 0);
+        Rx::tap_point(self)
+    }
 }
 
 impl<T: Send + Sync> RxBundle for Option<DoubleBufferRx<T>> {
@@ -447,9 +1315,9 @@ impl<T: Send + Sync> RxBundle for Option<DoubleBufferRx<T>> {
         1
     }
 
-    fn name(&self, index: usize) -> String {
+    fn name(&self, index: usize) -> Cow<'static, str> {
         assert_eq!(index, 0);
-        String::from("in")
+        Cow::Borrowed("in")
     }
 
     fn sync_all(&mut self, results: &mut [SyncResult]) {
@@ -461,17 +1329,57 @@ impl<T: Send + Sync> RxBundle for Option<DoubleBufferRx<T>> {
         cc.mark(0, self.as_ref().map_or(false, |rx| rx.is_connected()));
         cc
     }
+
+    fn message_type_hash(&self, index: usize) -> Option<u64> {
+        assert_eq!(index, 0);
+        Some(message_type_hash::<T>())
+    }
+
+    fn contract(&self, index: usize) -> Option<ChannelContract> {
+        assert_eq!(index, 0);
+        Rx::contract(self)
+    }
+
+    fn endpoint_mut(&mut self, index: usize) -> Option<&mut dyn Any>
+    where
+        Self: 'static,
+    {
+        (index == 0).then_some(self as &mut dyn Any)
+    }
+
+    fn queue_len(&self, index: usize) -> Option<usize> {
+        assert_eq!(index, 0);
+        Rx::queue_len(self)
+    }
+
+    fn take_backlog(&mut self, index: usize) -> Option<Backlog>
+    where
+        Self: 'static,
+    {
+        assert_eq!(index, 0);
+        Rx::take_backlog(self)
+    }
+
+    fn tap_point(&self, index: usize) -> Option<Arc<dyn TapPoint>>
+    where
+        Self: 'static,
+    {
+        assert_eq!(index, 0);
+        Rx::tap_point(self)
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum TxSendError {
     QueueFull,
+    Closed,
 }
 
 impl fmt::Display for TxSendError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         match self {
             TxSendError::QueueFull => write!(fmt, "QueueFull"),
+            TxSendError::Closed => write!(fmt, "Closed"),
         }
     }
 }
@@ -481,12 +1389,14 @@ impl std::error::Error for TxSendError {}
 #[derive(Debug)]
 pub enum RxRecvError {
     QueueEmtpy,
+    Closed,
 }
 
 impl fmt::Display for RxRecvError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         match self {
             RxRecvError::QueueEmtpy => write!(fmt, "QueueEmtpy"),
+            RxRecvError::Closed => write!(fmt, "Closed"),
         }
     }
 }
@@ -496,10 +1406,15 @@ impl std::error::Error for RxRecvError {}
 #[cfg(test)]
 mod tests {
     use crate::{
-        channels::{FlushResult, SyncResult},
+        channels::{
+            BackStage, ChannelContract, Delivery, FlushResult, RxRecvError, SyncResult,
+            TxConnectError, TxReplaceError, TxSendError, DELIVERY_HISTORY_LEN,
+        },
         prelude::*,
     };
-    use std::sync::mpsc;
+    use core::time::Duration;
+    use nodo_core::TimestampKind;
+    use std::sync::{atomic::AtomicU64, mpsc, Arc};
 
     fn fixed_channel<T: Clone + Send + Sync>(
         size: usize,
@@ -532,6 +1447,8 @@ mod tests {
                     rx.sync(),
                     SyncResult {
                         received: NUM_MESSAGES,
+                        queue_len: NUM_MESSAGES,
+                        capacity: Some(NUM_MESSAGES),
                         ..Default::default()
                     }
                 );
@@ -570,4 +1487,930 @@ mod tests {
         t1.join().unwrap();
         t2.join().unwrap();
     }
+
+    #[test]
+    fn test_close() {
+        const NUM_MESSAGES: usize = 10;
+
+        let (mut tx, mut rx) = fixed_channel(NUM_MESSAGES);
+
+        for i in 0..NUM_MESSAGES {
+            tx.push(i).unwrap();
+        }
+        tx.close();
+        assert!(matches!(tx.push(NUM_MESSAGES), Err(TxSendError::Closed)));
+        tx.flush();
+
+        assert!(!rx.is_closed());
+        assert_eq!(
+            rx.sync(),
+            SyncResult {
+                received: NUM_MESSAGES,
+                queue_len: NUM_MESSAGES,
+                capacity: Some(NUM_MESSAGES),
+                closed: true,
+                ..Default::default()
+            }
+        );
+
+        for i in 0..NUM_MESSAGES {
+            assert!(!rx.is_closed());
+            assert_eq!(rx.pop().unwrap(), i);
+        }
+        assert!(rx.is_closed());
+        assert!(matches!(rx.pop(), Err(RxRecvError::Closed)));
+
+        tx.flush();
+        assert!(rx.sync().closed);
+        assert!(matches!(rx.pop(), Err(RxRecvError::Closed)));
+    }
+
+    #[test]
+    fn test_batch_commit() {
+        let (mut tx, mut rx) = fixed_channel(4);
+
+        tx.push(0).unwrap();
+        let mut batch = tx.begin_batch();
+        batch.push(1).unwrap();
+        batch.push(2).unwrap();
+        assert_eq!(batch.len(), 2);
+        batch.commit().unwrap();
+        tx.push(3).unwrap();
+
+        tx.flush();
+        rx.sync();
+        for i in 0..4 {
+            assert_eq!(rx.pop().unwrap(), i);
+        }
+    }
+
+    #[test]
+    fn test_batch_rollback_on_drop() {
+        let (mut tx, mut rx) = fixed_channel(4);
+
+        {
+            let mut batch = tx.begin_batch();
+            batch.push(1).unwrap();
+            batch.push(2).unwrap();
+        }
+        tx.push(3).unwrap();
+
+        let batch = tx.begin_batch();
+        assert!(batch.is_empty());
+        drop(batch);
+
+        tx.flush();
+        rx.sync();
+        assert_eq!(rx.pop().unwrap(), 3);
+        assert!(matches!(rx.pop(), Err(RxRecvError::QueueEmtpy)));
+    }
+
+    #[test]
+    fn test_batch_all_or_nothing() {
+        let (mut tx, mut rx) = fixed_channel(3);
+
+        tx.push(0).unwrap();
+        let mut batch = tx.begin_batch();
+        for i in 1..4 {
+            batch.push(i).unwrap();
+        }
+        assert!(matches!(batch.commit(), Err(TxSendError::QueueFull)));
+
+        // a batch which fits exactly is accepted
+        let mut batch = tx.begin_batch();
+        batch.push(1).unwrap();
+        batch.push(2).unwrap();
+        batch.commit().unwrap();
+
+        tx.flush();
+        rx.sync();
+        for i in 0..3 {
+            assert_eq!(rx.pop().unwrap(), i);
+        }
+        assert!(matches!(rx.pop(), Err(RxRecvError::QueueEmtpy)));
+    }
+
+    #[test]
+    fn test_empty_is_not_closed() {
+        let (mut tx, mut rx) = fixed_channel::<u32>(1);
+        tx.flush();
+        rx.sync();
+        assert!(!rx.is_closed());
+        assert!(matches!(rx.pop(), Err(RxRecvError::QueueEmtpy)));
+    }
+
+    #[test]
+    fn test_tuple_pop_requires_all() {
+        let (mut tx1, mut rx1) = fixed_channel::<u32>(2);
+        let (mut tx2, mut rx2) = fixed_channel::<u32>(2);
+        let (mut tx3, mut rx3) = fixed_channel::<u32>(2);
+        let (mut tx4, mut rx4) = fixed_channel::<u32>(2);
+        for tx in [&mut tx1, &mut tx2, &mut tx3] {
+            tx.push(1).unwrap();
+            tx.flush();
+        }
+        for rx in [&mut rx1, &mut rx2, &mut rx3, &mut rx4] {
+            rx.sync();
+        }
+
+        // the last channel is empty
+        assert!((&mut rx1, &mut rx4).is_empty());
+        assert!((&mut rx1, &mut rx2, &mut rx4).is_empty());
+        assert!((&mut rx1, &mut rx2, &mut rx3, &mut rx4).is_empty());
+        assert!(matches!(
+            (&mut rx1, &mut rx2, &mut rx4).pop(),
+            Err(RxRecvError::QueueEmtpy)
+        ));
+        assert_eq!((rx1.len(), rx2.len()), (1, 1));
+
+        tx4.push(1).unwrap();
+        tx4.flush();
+        rx4.sync();
+        let mut all = (&mut rx1, &mut rx2, &mut rx3, &mut rx4);
+        assert!(!all.is_empty());
+        assert_eq!(all.pop().unwrap(), (1, 1, 1, 1));
+        assert!(all.is_empty());
+    }
+
+    #[test]
+    fn test_connect_filtered() {
+        let mut tx = DoubleBufferTx::new_auto_size();
+        let mut rx_all = DoubleBufferRx::new_auto_size();
+        let mut rx_tenth = DoubleBufferRx::new_auto_size();
+        let mut rx_odd = DoubleBufferRx::new_auto_size();
+        tx.connect_filtered(&mut rx_tenth, Arc::new(|x: &usize| x % 10 == 0))
+            .unwrap();
+        tx.connect(&mut rx_all).unwrap();
+        tx.connect_filtered(&mut rx_odd, Arc::new(|x: &usize| x % 2 == 1))
+            .unwrap();
+
+        tx.push_many(0..30).unwrap();
+        assert_eq!(
+            tx.flush(),
+            FlushResult {
+                available: 30,
+                cloned: 30 + 15,
+                published: 3 + 30 + 15,
+                filtered: vec![27, 0, 15],
+                ..Default::default()
+            }
+        );
+
+        for rx in [&mut rx_all, &mut rx_tenth, &mut rx_odd] {
+            rx.sync();
+        }
+        assert_eq!(rx_tenth.pop_all().collect::<Vec<_>>(), [0, 10, 20]);
+        assert_eq!(
+            rx_all.pop_all().collect::<Vec<_>>(),
+            (0..30).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            rx_odd.pop_all().collect::<Vec<_>>(),
+            (1..30).step_by(2).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_flush_without_filters() {
+        let (mut tx, mut rx) = fixed_channel(2);
+        tx.push(1).unwrap();
+        assert!(tx.flush().filtered.is_empty());
+        rx.sync();
+        assert_eq!(rx.pop().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_replay_to_late_receivers() {
+        let mut tx = DoubleBufferTx::new_auto_size().with_replay(3);
+        let mut rx_early = DoubleBufferRx::new_auto_size();
+        tx.connect(&mut rx_early).unwrap();
+
+        tx.push_many(0..2).unwrap();
+        tx.flush();
+        tx.push_many(2..4).unwrap();
+        tx.flush();
+
+        // history is limited to the last three messages
+        let mut rx_late = DoubleBufferRx::new_auto_size();
+        tx.connect(&mut rx_late).unwrap();
+        rx_late.sync();
+        assert_eq!(rx_late.pop_all().collect::<Vec<_>>(), [1, 2, 3]);
+
+        // a receiver with capacity 1 only gets the newest message
+        let mut rx_latest = DoubleBufferRx::new_latest();
+        tx.connect(&mut rx_latest).unwrap();
+        rx_latest.sync();
+        assert_eq!(rx_latest.pop_all().collect::<Vec<_>>(), [3]);
+
+        // receivers which were connected before do not get the history again
+        rx_early.sync();
+        assert_eq!(rx_early.pop_all().collect::<Vec<_>>(), [0, 1, 2, 3]);
+
+        // new messages are delivered after the history
+        tx.push(4).unwrap();
+        tx.flush();
+        rx_late.sync();
+        assert_eq!(rx_late.pop_all().collect::<Vec<_>>(), [4]);
+    }
+
+    #[test]
+    fn test_replay_without_receivers() {
+        let mut tx = DoubleBufferTx::new(4).with_replay(2);
+        tx.push_many(0..3).unwrap();
+        tx.flush();
+
+        let mut rx = DoubleBufferRx::new(OverflowPolicy::Reject(4), RetentionPolicy::Drop);
+        tx.connect(&mut rx).unwrap();
+        rx.sync();
+        assert_eq!(rx.pop_all().collect::<Vec<_>>(), [1, 2]);
+    }
+
+    #[test]
+    fn test_connect_contract() {
+        let sorted = ChannelContract {
+            ordered_by: Some(TimestampKind::Acq),
+            lossless: true,
+            max_rate_hz: Some(10.0),
+        };
+        let expect_sorted = ChannelContract {
+            ordered_by: Some(TimestampKind::Acq),
+            ..Default::default()
+        };
+
+        // unannotated
+        let mut tx = DoubleBufferTx::<u32>::new(1);
+        tx.connect(&mut DoubleBufferRx::new_auto_size()).unwrap();
+
+        // only TX annotated
+        let mut tx = DoubleBufferTx::<u32>::new(1).with_contract(sorted.clone());
+        tx.connect(&mut DoubleBufferRx::new_auto_size()).unwrap();
+
+        // compatible
+        let mut rx = DoubleBufferRx::new_auto_size().expect_contract(expect_sorted.clone());
+        tx.connect(&mut rx).unwrap();
+        assert!(rx.is_connected());
+
+        // incompatible: TX unannotated
+        let mut tx = DoubleBufferTx::<u32>::new(1);
+        let mut rx = DoubleBufferRx::new_auto_size().expect_contract(expect_sorted);
+        let err = tx.connect(&mut rx).unwrap_err();
+        assert!(err.to_string().contains("ordered_by"));
+        assert!(!rx.is_connected());
+
+        // incompatible: rate too high
+        let mut tx = DoubleBufferTx::<u32>::new(1).with_contract(sorted);
+        let mut rx = DoubleBufferRx::new_auto_size().expect_contract(ChannelContract {
+            max_rate_hz: Some(5.0),
+            ..Default::default()
+        });
+        let err = tx.connect(&mut rx).unwrap_err();
+        assert!(matches!(err, TxConnectError::ContractMismatch(_)));
+        assert!(err.to_string().contains("max_rate_hz"));
+        assert!(!err.to_string().contains("ordered_by"));
+    }
+
+    #[test]
+    fn test_message_age() {
+        let ms = Duration::from_millis;
+        let message = |acq: u64, pubt: u64| Message {
+            seq: acq,
+            stamp: Stamp {
+                acqtime: ms(acq).into(),
+                pubtime: ms(pubt).into(),
+            },
+            value: (),
+        };
+
+        let (mut tx, mut rx) = fixed_channel(4);
+        assert_eq!(rx.oldest_age(ms(100).into()), None);
+        assert_eq!(rx.latest_age(ms(100).into()), None);
+
+        tx.push(message(10, 50)).unwrap();
+        tx.push(message(20, 60)).unwrap();
+        tx.push(message(30, 80)).unwrap();
+        tx.flush();
+        rx.sync();
+
+        assert_eq!(rx.oldest_age(ms(100).into()), Some(ms(50)));
+        assert_eq!(rx.latest_age(ms(100).into()), Some(ms(20)));
+        assert_eq!(rx.oldest_age_by(TimestampKind::Acq, ms(100)), Some(ms(90)));
+        assert_eq!(rx.latest_age_by(TimestampKind::Acq, ms(100)), Some(ms(70)));
+
+        // messages from the future have age zero
+        assert_eq!(rx.latest_age(ms(70).into()), Some(Duration::ZERO));
+
+        // queries neither consume nor reorder messages
+        assert_eq!(rx.len(), 3);
+        for seq in [10, 20, 30] {
+            assert_eq!(rx.pop().unwrap().seq, seq);
+        }
+        assert_eq!(rx.oldest_age(ms(100).into()), None);
+    }
+
+    #[test]
+    fn test_keep_decimated() {
+        let ms = Duration::from_millis;
+
+        let mut tx = DoubleBufferTx::new_auto_size();
+        let mut rx = DoubleBufferRx::new_timeseries(
+            OverflowPolicy::Forget(2000),
+            RetentionPolicy::KeepDecimated {
+                recent: ms(100),
+                recent_resolution: ms(2),
+                old_resolution: ms(50),
+            },
+        );
+        tx.connect(&mut rx).unwrap();
+
+        // uniform stream with one message per millisecond, synced in batches
+        for batch in 0..10 {
+            let end = if batch == 9 { 1001 } else { (batch + 1) * 100 };
+            for t in (batch * 100)..end {
+                tx.push(Message {
+                    seq: t,
+                    stamp: Stamp {
+                        acqtime: ms(t).into(),
+                        pubtime: ms(t).into(),
+                    },
+                    value: t as f64,
+                })
+                .unwrap();
+            }
+            tx.flush();
+            rx.sync();
+
+            // the oldest and the newest sample are always kept
+            assert_eq!(rx.front[0].seq, 0);
+            assert_eq!(rx.latest().unwrap().seq, end - 1);
+        }
+
+        let times = rx
+            .as_acq_time_series()
+            .iter()
+            .map(|(t, _)| t)
+            .collect::<Vec<_>>();
+        let expected = (0..900)
+            .step_by(50)
+            .chain((900..=1000).step_by(2))
+            .map(ms)
+            .collect::<Vec<_>>();
+        assert_eq!(times, expected);
+
+        // interpolation works across the boundary between old and recent samples
+        let lerp = |p: f64, a: &&f64, b: &&f64| Some(**a + p * (**b - **a));
+        let series = rx.as_acq_time_series();
+        assert_eq!(series.interpolate(ms(875), lerp).ok(), Some(875.0));
+        assert_eq!(series.interpolate(ms(901), lerp).ok(), Some(901.0));
+    }
+
+    #[test]
+    fn test_bulk_interleaved_with_single() {
+        let mut tx = DoubleBufferTx::new(6);
+        let mut rx = DoubleBufferRx::new(OverflowPolicy::Forget(8), RetentionPolicy::Keep);
+        tx.connect(&mut rx).unwrap();
+
+        tx.push(0).unwrap();
+        assert_eq!(tx.push_slice(&[1, 2, 3]), Ok(3));
+        tx.push(4).unwrap();
+        // only one more message fits
+        assert_eq!(tx.push_slice(&[5, 6, 7]), Ok(1));
+        assert_eq!(tx.push_slice_all(&[6]), Err(TxSendError::QueueFull));
+        assert_eq!(tx.push(6), Err(TxSendError::QueueFull));
+        assert_eq!(tx.flush().published, 6);
+        rx.sync();
+
+        let mut buf = vec![-1];
+        assert_eq!(rx.pop_into(&mut buf, 2), 2);
+        assert_eq!(rx.try_pop(), Some(2));
+        assert_eq!(rx.pop_into(&mut buf, 100), 3);
+        assert_eq!(buf, [-1, 0, 1, 3, 4, 5]);
+        assert_eq!(rx.pop_into(&mut buf, 100), 0);
+
+        // the RX forgets the oldest messages
+        assert_eq!(tx.push_slice_all(&[10, 11, 12, 13, 14]), Ok(()));
+        tx.flush();
+        assert_eq!(tx.push_slice(&[15, 16, 17, 18, 19, 20]), Ok(6));
+        tx.flush();
+        rx.sync();
+        buf.clear();
+        rx.pop_into(&mut buf, usize::MAX);
+        assert_eq!(buf, [13, 14, 15, 16, 17, 18, 19, 20]);
+
+        tx.close();
+        assert_eq!(tx.push_slice(&[1]), Err(TxSendError::Closed));
+        assert_eq!(tx.push_slice_all(&[1]), Err(TxSendError::Closed));
+    }
+
+    #[test]
+    fn test_push_slice_forget() {
+        let mut tx = DoubleBufferTx::new_auto_size();
+        tx.outbox = BackStage::new(OverflowPolicy::Forget(3), RetentionPolicy::Drop);
+        tx.push(0).unwrap();
+        assert_eq!(tx.push_slice(&[1, 2, 3, 4, 5]), Ok(5));
+        assert_eq!(tx.outbox.iter().copied().collect::<Vec<_>>(), [3, 4, 5]);
+    }
+
+    /// Moves a 50 kHz stream of samples in 1 ms steps with bulk and with single operations
+    #[test]
+    fn test_bulk_throughput() {
+        const SAMPLES_PER_STEP: usize = 50;
+        const STEPS: usize = 1000;
+
+        fn run(bulk: bool) -> usize {
+            let mut tx = DoubleBufferTx::new(SAMPLES_PER_STEP);
+            let mut rx = DoubleBufferRx::new(
+                OverflowPolicy::Reject(SAMPLES_PER_STEP),
+                RetentionPolicy::EnforceEmpty,
+            );
+            tx.connect(&mut rx).unwrap();
+
+            let samples: Vec<f32> = (0..SAMPLES_PER_STEP).map(|i| i as f32).collect();
+            let mut buf = Vec::with_capacity(SAMPLES_PER_STEP);
+            let mut moved = 0;
+
+            for _ in 0..STEPS {
+                if bulk {
+                    assert_eq!(tx.push_slice(&samples), Ok(SAMPLES_PER_STEP));
+                } else {
+                    for &x in samples.iter() {
+                        tx.push(x).unwrap();
+                    }
+                }
+                tx.flush();
+                rx.sync();
+
+                buf.clear();
+                if bulk {
+                    moved += rx.pop_into(&mut buf, usize::MAX);
+                } else {
+                    while let Ok(x) = rx.pop() {
+                        buf.push(x);
+                        moved += 1;
+                    }
+                }
+                assert_eq!(buf, samples);
+            }
+            moved
+        }
+
+        let bulk_count = run(true);
+        let single_count = run(false);
+
+        assert_eq!(bulk_count, SAMPLES_PER_STEP * STEPS);
+        assert_eq!(bulk_count, single_count);
+    }
+
+    #[test]
+    fn test_delivery_tracking() {
+        let mut tx = DoubleBufferTx::new(5);
+        let mut rx_all = DoubleBufferRx::new(OverflowPolicy::Reject(5), RetentionPolicy::Drop);
+        let mut rx_latest = DoubleBufferRx::new(OverflowPolicy::Forget(1), RetentionPolicy::Drop);
+        tx.connect(&mut rx_all).unwrap();
+        tx.connect(&mut rx_latest).unwrap();
+
+        // disabled by default
+        tx.push(0).unwrap();
+        tx.flush();
+        rx_all.sync();
+        assert!(tx.delivery_stats().is_none());
+        assert!(rx_all.delivery_stats().is_none());
+
+        tx.enable_delivery_tracking();
+        for step in 0..4 {
+            for i in 0..5 {
+                tx.push(step * 5 + i).unwrap();
+            }
+            tx.flush();
+            rx_all.sync();
+            rx_latest.sync();
+        }
+
+        let stats = tx.delivery_stats().unwrap();
+        assert_eq!(stats.delivered, [20, 20]);
+        assert_eq!(stats.recent.len(), DELIVERY_HISTORY_LEN);
+        let d = |connection, counter| Delivery {
+            connection,
+            counter,
+        };
+        assert_eq!(
+            stats
+                .recent
+                .iter()
+                .rev()
+                .take(6)
+                .copied()
+                .collect::<Vec<_>>(),
+            [d(0, 20), d(0, 19), d(0, 18), d(0, 17), d(0, 16), d(1, 20)]
+        );
+
+        // the receiver which forgets messages only received one message per step
+        let all = rx_all.delivery_stats().unwrap();
+        assert_eq!((all.last_counter, all.received, all.missed()), (20, 20, 0));
+        let latest = rx_latest.delivery_stats().unwrap();
+        assert_eq!(
+            (latest.last_counter, latest.received, latest.missed()),
+            (20, 4, 16)
+        );
+    }
+
+    #[derive(Clone, Default)]
+    struct ManualClock(Arc<std::sync::Mutex<Duration>>);
+
+    impl ManualClock {
+        fn set(&self, ms: u64) {
+            *self.0.lock().unwrap() = Duration::from_millis(ms);
+        }
+    }
+
+    impl nodo_core::Clock<nodo_core::PubtimeMarker> for ManualClock {
+        fn now(&self) -> Pubtime {
+            Pubtime::new(*self.0.lock().unwrap())
+        }
+    }
+
+    #[test]
+    fn test_latency_tracking() {
+        let ms = Duration::from_millis;
+        let clock = ManualClock::default();
+
+        let mut tx = DoubleBufferTx::new(5);
+        let mut rx_all = DoubleBufferRx::new(OverflowPolicy::Reject(5), RetentionPolicy::Drop);
+        let mut rx_latest = DoubleBufferRx::new(OverflowPolicy::Forget(1), RetentionPolicy::Drop);
+        tx.connect(&mut rx_all).unwrap();
+        tx.connect(&mut rx_latest).unwrap();
+
+        // disabled by default
+        tx.push(0).unwrap();
+        tx.flush();
+        assert_eq!(rx_all.sync().residency, None);
+        assert!(rx_all.residency_stats().is_none());
+        rx_latest.sync();
+
+        rx_all.enable_latency_tracking(clock.clone());
+        rx_latest.enable_latency_tracking(clock.clone());
+
+        // messages flushed at different times are synced together
+        clock.set(0);
+        tx.push(1).unwrap();
+        tx.push(2).unwrap();
+        tx.flush();
+        clock.set(10);
+        tx.push(3).unwrap();
+        tx.flush();
+        clock.set(30);
+        let residency = rx_all.sync().residency.unwrap();
+        assert_eq!(residency.min, ms(20));
+        assert_eq!(residency.average, ms(80) / 3);
+        assert_eq!(residency.max, ms(30));
+
+        // only the newest message is kept by the forgetting receiver
+        let residency = rx_latest.sync().residency.unwrap();
+        assert_eq!((residency.min, residency.max), (ms(20), ms(20)));
+
+        clock.set(40);
+        tx.push(4).unwrap();
+        tx.flush();
+        clock.set(45);
+        let residency = rx_all.sync().residency.unwrap();
+        assert_eq!((residency.min, residency.average), (ms(5), ms(5)));
+        rx_latest.sync();
+
+        // nothing received
+        clock.set(50);
+        assert_eq!(rx_all.sync().residency, None);
+
+        let stats = rx_all.residency_stats().unwrap();
+        assert_eq!(stats.count(), 4);
+        assert_eq!(stats.total(), ms(85));
+        assert_eq!(stats.min_ms(), Some(5.0));
+        assert_eq!(stats.max_ms(), Some(30.0));
+
+        let stats = rx_latest.residency_stats().unwrap();
+        assert_eq!(stats.count(), 2);
+        assert_eq!(stats.total(), ms(25));
+    }
+
+    #[test]
+    fn test_arrival_stamps() {
+        let counter = Arc::new(AtomicU64::new(0));
+        let mut tx_a = DoubleBufferTx::new_auto_size();
+        let mut tx_b = DoubleBufferTx::new_auto_size();
+        let mut rx_a = DoubleBufferRx::new(OverflowPolicy::Forget(2), RetentionPolicy::Drop);
+        let mut rx_b = DoubleBufferRx::new_auto_size();
+        tx_a.connect(&mut rx_a).unwrap();
+        tx_b.connect(&mut rx_b).unwrap();
+
+        // a message in the back stage is stamped when stamps are enabled
+        tx_a.push(0).unwrap();
+        tx_a.flush();
+        rx_a.enable_arrival_stamps(counter.clone());
+        rx_b.enable_arrival_stamps(counter.clone());
+
+        tx_b.push(1).unwrap();
+        tx_b.flush();
+        tx_a.push_many([2, 3]).unwrap();
+        tx_a.flush();
+        tx_b.push(4).unwrap();
+        tx_b.flush();
+
+        // the oldest message was forgotten together with its stamp
+        rx_a.sync();
+        rx_b.sync();
+        assert_eq!(rx_a.pop_all().collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(rx_a.arrival_stamps(), &[2, 3]);
+        assert_eq!(rx_b.arrival_stamps(), &[1, 4]);
+
+        // stamps only describe the messages received by the last sync
+        rx_b.sync();
+        assert!(rx_b.arrival_stamps().is_empty());
+    }
+
+    #[test]
+    fn test_replace_and_migrate() {
+        let mut tx = DoubleBufferTx::new(2);
+        let mut rx = DoubleBufferRx::new_auto_size();
+        tx.connect(&mut rx).unwrap();
+
+        tx.push(0).unwrap();
+        tx.push(1).unwrap();
+        tx.flush();
+        tx.push(2).unwrap();
+        tx.push(3).unwrap();
+        assert_eq!(tx.push(4), Err(TxSendError::QueueFull));
+
+        // pending messages must fit into the new outbox
+        assert!(matches!(
+            tx.replace_and_migrate(OverflowPolicy::Reject(1)),
+            Err(TxReplaceError::PendingOverflow {
+                pending: 2,
+                capacity: 1
+            })
+        ));
+
+        // flushed and pending messages are delivered after the swap
+        tx.replace_and_migrate(OverflowPolicy::Reject(8)).unwrap();
+        for i in 4..10 {
+            tx.push(i).unwrap();
+        }
+        assert_eq!(tx.push(10), Err(TxSendError::QueueFull));
+        assert_eq!(tx.flush().published, 8);
+        rx.sync();
+        assert_eq!(
+            rx.pop_all().collect::<Vec<_>>(),
+            (0..10).collect::<Vec<_>>()
+        );
+
+        // continued delivery on the new configuration
+        tx.push(10).unwrap();
+        tx.flush();
+        rx.sync();
+        assert_eq!(rx.pop_all().collect::<Vec<_>>(), [10]);
+
+        // the connection policies are still enforced
+        let (mut tx, _rx) = fixed_channel::<u32>(2);
+        assert!(matches!(
+            tx.replace_and_migrate(OverflowPolicy::Resize),
+            Err(TxReplaceError::PolicyMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_rebind() {
+        let mut tx_old = DoubleBufferTx::new(4);
+        let mut tx_new = DoubleBufferTx::new(4);
+        let mut rx = DoubleBufferRx::new(OverflowPolicy::Reject(8), RetentionPolicy::Drop);
+        let mut rx_other = DoubleBufferRx::new_auto_size();
+        tx_old.connect(&mut rx).unwrap();
+        tx_old.connect(&mut rx_other).unwrap();
+
+        tx_old.push_many([0, 1]).unwrap();
+        tx_old.flush();
+        rx.sync();
+        assert_eq!(rx.pop_all().collect::<Vec<_>>(), [0, 1]);
+
+        // messages flushed before the swap are in the back stage but not yet synced
+        tx_old.push_many([2, 3]).unwrap();
+        tx_old.flush();
+        tx_old.close();
+        rx.rebind(&mut tx_new).unwrap();
+
+        // later flushes of the old transmitter only reach the remaining receiver
+        assert_eq!(tx_old.connection_count(), 2);
+        assert_eq!(tx_old.flush().published, 0);
+        assert_eq!(tx_old.connection_count(), 1);
+        assert!(tx_old.is_connected());
+        rx_other.sync();
+        assert_eq!(rx_other.pop_all().collect::<Vec<_>>(), [0, 1, 2, 3]);
+        assert!(rx_other.is_closed());
+
+        tx_new.push_many([4, 5]).unwrap();
+        assert_eq!(tx_new.flush().published, 2);
+        rx.sync();
+        assert_eq!(rx.pop_all().collect::<Vec<_>>(), [2, 3, 4, 5]);
+        assert!(!rx.is_closed());
+
+        // a failed rebind keeps the current connection
+        let mut tx_resize = DoubleBufferTx::new_auto_size();
+        assert!(matches!(
+            rx.rebind(&mut tx_resize),
+            Err(TxConnectError::PolicyMismatch)
+        ));
+        tx_new.push(6).unwrap();
+        tx_new.flush();
+        rx.sync();
+        assert_eq!(rx.pop_all().collect::<Vec<_>>(), [6]);
+
+        // an unconnected receiver is simply connected
+        let mut rx_fresh = DoubleBufferRx::new_auto_size();
+        rx_fresh.rebind(&mut tx_new).unwrap();
+        assert!(rx_fresh.is_connected());
+        assert!(matches!(
+            tx_new.connect(&mut rx_fresh),
+            Err(TxConnectError::ReceiverAlreadyConnected)
+        ));
+    }
+
+    #[test]
+    fn test_disconnect_dropped() {
+        let mut tx = DoubleBufferTx::new(4);
+        let mut rx = DoubleBufferRx::new_auto_size();
+        let mut rx_dropped = DoubleBufferRx::new_auto_size();
+        tx.connect(&mut rx).unwrap();
+        tx.connect(&mut rx_dropped).unwrap();
+        drop(rx_dropped);
+
+        // dropped receivers stay connected until they are removed explicitly
+        tx.push(0).unwrap();
+        assert_eq!(tx.flush().published, 2);
+        assert_eq!(tx.connection_count(), 2);
+
+        tx.disconnect_dropped();
+        assert_eq!(tx.connection_count(), 1);
+        tx.push(1).unwrap();
+        assert_eq!(tx.flush().published, 1);
+        rx.sync();
+        assert_eq!(rx.pop_all().collect::<Vec<_>>(), [0, 1]);
+    }
+
+    #[test]
+    fn test_rebind_mid_stream() {
+        const NUM_ROUNDS: u32 = 20;
+
+        let mut txs = [DoubleBufferTx::new(3), DoubleBufferTx::new(3)];
+        let mut rx = DoubleBufferRx::new(OverflowPolicy::Reject(16), RetentionPolicy::Drop);
+        txs[0].connect(&mut rx).unwrap();
+
+        // alternate between transmitters with unsynced messages in the back stage at every swap
+        let mut next = 0;
+        let mut received = Vec::new();
+        for round in 0..NUM_ROUNDS {
+            let tx = &mut txs[round as usize % 2];
+            for _ in 0..3 {
+                tx.push(next).unwrap();
+                next += 1;
+            }
+            tx.flush();
+            rx.rebind(&mut txs[(round as usize + 1) % 2]).unwrap();
+            if round % 3 == 0 {
+                rx.sync();
+                received.extend(rx.pop_all());
+            }
+        }
+        rx.sync();
+        received.extend(rx.pop_all());
+
+        assert_eq!(received, (0..next).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_take_latest() {
+        let mut tx = DoubleBufferTx::new(8);
+        let mut rx = DoubleBufferRx::new_auto_size();
+        tx.connect(&mut rx).unwrap();
+
+        assert_eq!(rx.take_latest(), None);
+
+        tx.push_many([1, 2, 3, 4]).unwrap();
+        tx.flush();
+        rx.sync();
+        assert_eq!(rx.take_latest(), Some(4));
+        assert!(rx.is_empty());
+        assert_eq!(rx.take_latest(), None);
+
+        // discarded messages are counted as dropped at the next sync
+        tx.push(5).unwrap();
+        tx.flush();
+        let result = rx.sync();
+        assert_eq!((result.received, result.dropped), (1, 3));
+        assert_eq!(rx.take_latest(), Some(5));
+        assert_eq!(rx.sync().dropped, 0);
+    }
+
+    #[test]
+    fn test_pop_latest() {
+        let mut tx = DoubleBufferTx::new(8);
+        let mut rx = DoubleBufferRx::new(OverflowPolicy::Forget(4), RetentionPolicy::Drop);
+        tx.connect(&mut rx).unwrap();
+
+        // empty channel
+        assert_eq!(rx.try_pop_latest(), None);
+        assert!(matches!(rx.pop_latest(), Err(RxRecvError::QueueEmtpy)));
+
+        // single message
+        tx.push(1).unwrap();
+        tx.flush();
+        rx.sync();
+        assert_eq!(rx.pop_latest().unwrap(), 1);
+        assert_eq!(rx.try_pop_latest(), None);
+        assert_eq!(rx.sync().dropped, 0);
+
+        // multiple messages: older ones are counted as dropped at the next sync
+        tx.push_many([2, 3, 4]).unwrap();
+        tx.flush();
+        assert_eq!(rx.sync().received, 3);
+        assert_eq!(rx.try_pop_latest(), Some(4));
+        assert!(rx.is_empty());
+
+        tx.push_many([5, 6, 7, 8]).unwrap();
+        tx.flush();
+        let result = rx.sync();
+        assert_eq!((result.received, result.dropped), (4, 2));
+        assert_eq!(rx.pop_latest().unwrap(), 8);
+        assert_eq!(rx.sync().dropped, 3);
+
+        // a closed stream is reported once all messages are consumed
+        tx.push(10).unwrap();
+        tx.close();
+        tx.flush();
+        rx.sync();
+        assert_eq!(rx.pop_latest().unwrap(), 10);
+        assert!(matches!(rx.pop_latest(), Err(RxRecvError::Closed)));
+    }
+
+    #[test]
+    fn test_take_latest_keep() {
+        let mut tx = DoubleBufferTx::new(8);
+        let mut rx = DoubleBufferRx::new(OverflowPolicy::Forget(4), RetentionPolicy::Keep);
+        tx.connect(&mut rx).unwrap();
+
+        tx.push_many([1, 2, 3]).unwrap();
+        tx.flush();
+        rx.sync();
+
+        // messages which are not consumed are kept across syncs
+        tx.push(4).unwrap();
+        tx.flush();
+        rx.sync();
+        assert_eq!(rx.len(), 4);
+        assert_eq!(rx.take_latest(), Some(4));
+
+        // the backlog does not reappear after the next sync
+        tx.push_many([5, 6]).unwrap();
+        tx.flush();
+        let result = rx.sync();
+        assert_eq!((result.received, result.dropped), (2, 3));
+        assert_eq!(rx.pop_all().collect::<Vec<_>>(), [5, 6]);
+
+        // works with a single message channel
+        let mut rx = DoubleBufferRx::new_latest();
+        tx.connect(&mut rx).unwrap();
+        tx.push_many([7, 8]).unwrap();
+        tx.flush();
+        rx.sync();
+        assert_eq!(rx.take_latest(), Some(8));
+        rx.sync();
+        assert_eq!(rx.take_latest(), None);
+    }
+
+    #[test]
+    fn test_split_last_n() {
+        let mut tx = DoubleBufferTx::new(8);
+        let mut rx = DoubleBufferRx::new(OverflowPolicy::Forget(8), RetentionPolicy::Keep);
+        tx.connect(&mut rx).unwrap();
+
+        tx.push_many([1, 2, 3, 4, 5]).unwrap();
+        tx.flush();
+        rx.sync();
+        assert_eq!(rx.split_last_n(3).collect::<Vec<_>>(), [3, 4, 5]);
+        assert!(rx.is_empty());
+
+        // fewer messages than requested
+        tx.push(6).unwrap();
+        tx.flush();
+        let result = rx.sync();
+        assert_eq!((result.received, result.dropped), (1, 2));
+        assert_eq!(rx.split_last_n(3).collect::<Vec<_>>(), [6]);
+        assert_eq!(rx.split_last_n(3).count(), 0);
+
+        // an empty window discards all messages
+        tx.push_many([7, 8, 9]).unwrap();
+        tx.flush();
+        assert_eq!(rx.sync().dropped, 0);
+        assert_eq!(rx.split_last_n(0).count(), 0);
+        tx.push(10).unwrap();
+        tx.flush();
+        let result = rx.sync();
+        assert_eq!((result.dropped, result.queue_len), (3, 1));
+        assert_eq!(rx.pop_all().collect::<Vec<_>>(), [10]);
+    }
 }
@@ -0,0 +1,23 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::channels::{TxBundle, TxSendError};
+
+/// An enum whose variants can be routed to separate channels
+///
+/// Implement it with `#[derive(EnumSplitDerive)]` which also generates the TX bundle with one
+/// `DoubleBufferTx` per variant. Fields of the bundle are named after the variants in snake case.
+/// Unit variants are sent as `()`, single-field variants as the field and variants with multiple
+/// fields as a tuple.
+pub trait EnumSplitMessage: Sized {
+    /// TX bundle with one channel per variant in declaration order
+    type Tx: TxBundle + Default;
+
+    /// Names of the variants in declaration order
+    const VARIANTS: &'static [&'static str];
+
+    /// Index of the variant of this value in [Self::VARIANTS]
+    fn variant_index(&self) -> usize;
+
+    /// Pushes the payload of this value to the channel of its variant
+    fn route(self, tx: &mut Self::Tx) -> Result<(), TxSendError>;
+}
@@ -1,17 +1,33 @@
 // Copyright 2023 by David Weikersdorfer. All rights reserved.
-use core::fmt;
+use core::{fmt, time::Duration};
 
+mod backlog;
 mod bundle;
+mod channel_uid;
 mod connect;
+mod contract;
 mod double_buffer_channel;
+mod enum_split;
+mod pop_synced;
+mod snoop;
+mod spsc_channel;
 mod stage_queue;
 mod timeseries;
+mod timeseries_stats;
 
+pub use backlog::*;
 pub use bundle::*;
+pub use channel_uid::*;
 pub use connect::*;
+pub use contract::*;
 pub use double_buffer_channel::*;
+pub use enum_split::*;
+pub use pop_synced::*;
+pub use snoop::*;
+pub use spsc_channel::*;
 pub use stage_queue::*;
 pub use timeseries::*;
+pub use timeseries_stats::*;
 
 /// Statistics about a channel sync operation
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
@@ -22,11 +38,28 @@ pub struct SyncResult {
     /// Number of messges which were forgotten by the receiver to store incoming messages
     pub forgotten: usize,
 
-    /// Number of messages which where dropped by the receiver
+    /// Number of messages which where dropped by the receiver. Includes messages discarded by
+    /// the consumer since the last sync, e.g. with `DoubleBufferRx::take_latest`.
     pub dropped: usize,
 
+    /// Number of messages which were rejected because the receiver queue was full
+    pub rejected: usize,
+
+    /// Number of messages in the receiver queue after the sync
+    pub queue_len: usize,
+
+    /// Capacity of the receiver queue or None if the queue grows as needed
+    pub capacity: Option<usize>,
+
     /// Retention policy "EnforceEmpty" in use but the receiver queue was not empty.
     pub enforce_empty_violation: bool,
+
+    /// The transmitter closed the stream and no further messages will arrive.
+    pub closed: bool,
+
+    /// Time the received messages spent in the back stage. Only set if latency tracking is
+    /// enabled on the receiver and messages were received.
+    pub residency: Option<Residency>,
 }
 
 impl SyncResult {
@@ -34,10 +67,26 @@ impl SyncResult {
         received: 0,
         forgotten: 0,
         dropped: 0,
+        rejected: 0,
+        queue_len: 0,
+        capacity: None,
         enforce_empty_violation: false,
+        closed: false,
+        residency: None,
     };
 }
 
+/// Minimum, average and maximum time messages spent in the back stage before they were synced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Residency {
+    /// Number of messages which were measured
+    pub count: usize,
+
+    pub min: Duration,
+    pub average: Duration,
+    pub max: Duration,
+}
+
 /// Result of a channel flush operation. This type combines statistics and potential errors.
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct FlushResult {
@@ -55,6 +104,13 @@ pub struct FlushResult {
     /// RX in certain conditions, for example if the receiving channel is full while using a
     /// reject policy.
     pub error_indicator: FlushErrorIndicator,
+
+    /// Number of messages rejected by the filter of each connection. Empty if none of the
+    /// connections has a filter.
+    pub filtered: Vec<usize>,
+
+    /// Number of pushes since the last flush which failed because the outbox was full
+    pub queue_full: usize,
 }
 
 impl FlushResult {
@@ -63,6 +119,8 @@ impl FlushResult {
         published: 0,
         cloned: 0,
         error_indicator: FlushErrorIndicator::NO_ERROR,
+        filtered: Vec::new(),
+        queue_full: 0,
     };
 }
 
@@ -0,0 +1,169 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::channels::DoubleBufferRx;
+use core::time::Duration;
+use nodo_core::{Message, TimestampKind};
+use paste::paste;
+
+/// Pops one message from each of several channels such that their timestamps agree
+///
+/// Implemented for tuples of up to eight `&mut DoubleBufferRx<Message<T>>`. This is useful for
+/// fusion codelets which should only do work once all inputs have matching data.
+pub trait PopSyncedByTime {
+    type Output;
+
+    /// Pops the newest set of messages, one per channel, whose timestamps of the given kind are
+    /// all within `tolerance` of each other. Older messages in each channel are discarded while
+    /// newer messages are kept. Returns None and leaves all channels untouched if there is no
+    /// such set.
+    fn pop_synced_by_time(
+        &mut self,
+        kind: TimestampKind,
+        tolerance: Duration,
+    ) -> Option<Self::Output>;
+}
+
+/// Finds the index of one message per channel such that all timestamps are within the tolerance
+/// and the oldest of them is as new as possible. Within that window the newest message of each
+/// channel is picked.
+fn find_synced(
+    lens: &[usize],
+    stamp: impl Fn(usize, usize) -> Duration,
+    tolerance: Duration,
+) -> Option<Vec<usize>> {
+    let mut best: Option<(Duration, Vec<usize>)> = None;
+
+    'anchors: for (channel, &len) in lens.iter().enumerate() {
+        for index in 0..len {
+            let anchor = stamp(channel, index);
+            if best.as_ref().is_some_and(|(t, _)| anchor <= *t) {
+                continue;
+            }
+
+            let mut indices = Vec::with_capacity(lens.len());
+            for (other, &other_len) in lens.iter().enumerate() {
+                let window = anchor..=anchor + tolerance;
+                match (0..other_len)
+                    .rev()
+                    .find(|&j| window.contains(&stamp(other, j)))
+                {
+                    Some(j) => indices.push(j),
+                    None => continue 'anchors,
+                }
+            }
+
+            best = Some((anchor, indices));
+        }
+    }
+
+    best.map(|(_, indices)| indices)
+}
+
+macro_rules! impl_pop_synced_by_time_tuple {
+    ( $( $ty: ident, $i: literal ),* ) => {
+        impl<'a, $($ty),*> PopSyncedByTime for ($(&'a mut DoubleBufferRx<Message<$ty>>,)*) {
+            type Output = ($(Message<$ty>,)*);
+
+            fn pop_synced_by_time(
+                &mut self,
+                kind: TimestampKind,
+                tolerance: Duration,
+            ) -> Option<Self::Output> {
+                let indices = find_synced(
+                    &[$(paste!{self.$i}.len()),*],
+                    |channel, index| match channel {
+                        $($i => paste!{self.$i}[index].stamp[kind],)*
+                        _ => unreachable!(),
+                    },
+                    tolerance,
+                )?;
+
+                Some(($(paste!{self.$i}.drain(..=indices[$i]).last().unwrap(),)*))
+            }
+        }
+    };
+}
+
+impl_pop_synced_by_time_tuple!(A, 0, B, 1);
+impl_pop_synced_by_time_tuple!(A, 0, B, 1, C, 2);
+impl_pop_synced_by_time_tuple!(A, 0, B, 1, C, 2, D, 3);
+impl_pop_synced_by_time_tuple!(A, 0, B, 1, C, 2, D, 3, E, 4);
+impl_pop_synced_by_time_tuple!(A, 0, B, 1, C, 2, D, 3, E, 4, F, 5);
+impl_pop_synced_by_time_tuple!(A, 0, B, 1, C, 2, D, 3, E, 4, F, 5, G, 6);
+impl_pop_synced_by_time_tuple!(A, 0, B, 1, C, 2, D, 3, E, 4, F, 5, G, 6, H, 7);
+
+#[cfg(test)]
+mod tests {
+    use crate::{channels::PopSyncedByTime, prelude::*};
+    use core::time::Duration;
+    use nodo_core::TimestampKind;
+
+    fn stream(
+        offset_ms: u64,
+        count: u64,
+    ) -> (DoubleBufferTx<Message<u64>>, DoubleBufferRx<Message<u64>>) {
+        let mut tx = DoubleBufferTx::new_auto_size();
+        let mut rx = DoubleBufferRx::new_auto_size();
+        tx.connect(&mut rx).unwrap();
+        for i in 0..count {
+            let t = Duration::from_millis(offset_ms + 10 * i);
+            tx.push(Message {
+                seq: i,
+                stamp: Stamp {
+                    acqtime: t.into(),
+                    pubtime: Duration::ZERO.into(),
+                },
+                value: offset_ms + 10 * i,
+            })
+            .unwrap();
+        }
+        tx.flush();
+        rx.sync();
+        (tx, rx)
+    }
+
+    #[test]
+    fn test_pop_synced_by_time() {
+        let ms = Duration::from_millis;
+
+        // a: 0, 10, 20, 30  b: 3, 13, 23
+        let (_tx_a, mut a) = stream(0, 4);
+        let (_tx_b, mut b) = stream(3, 3);
+
+        // timestamps never agree within 1 ms
+        assert!((&mut a, &mut b)
+            .pop_synced_by_time(TimestampKind::Acq, ms(1))
+            .is_none());
+        assert_eq!((a.len(), b.len()), (4, 3));
+
+        // 30 has no partner, thus 20 and 23 is the newest set
+        let (x, y) = (&mut a, &mut b)
+            .pop_synced_by_time(TimestampKind::Acq, ms(5))
+            .unwrap();
+        assert_eq!((x.value, y.value), (20, 23));
+        assert_eq!(a.pop_all().map(|m| m.value).collect::<Vec<_>>(), [30]);
+        assert!(b.is_empty());
+    }
+
+    #[test]
+    fn test_pop_synced_by_time_three_streams() {
+        let ms = Duration::from_millis;
+
+        // a: 0, 10, 20, 30, 40  b: 4, 14, 24  c: 7, 17, 27, 37
+        let (_tx_a, mut a) = stream(0, 5);
+        let (_tx_b, mut b) = stream(4, 3);
+        let (_tx_c, mut c) = stream(7, 4);
+
+        let (x, y, z) = (&mut a, &mut b, &mut c)
+            .pop_synced_by_time(TimestampKind::Acq, ms(8))
+            .unwrap();
+        assert_eq!((x.value, y.value, z.value), (30, 24, 27));
+        assert_eq!((a.len(), b.len(), c.len()), (1, 0, 1));
+
+        // b is exhausted thus no further set exists
+        assert!((&mut a, &mut b, &mut c)
+            .pop_synced_by_time(TimestampKind::Acq, ms(8))
+            .is_none());
+        assert_eq!((a.len(), c.len()), (1, 1));
+    }
+}
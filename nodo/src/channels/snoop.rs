@@ -0,0 +1,337 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::channels::BackStage;
+use core::{
+    any::{type_name, Any, TypeId},
+    time::Duration,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{mpsc::SyncSender, Arc, RwLock},
+    time::Instant,
+};
+
+/// Bounds of a snoop such that observing a channel cannot destabilize the pipeline
+///
+/// Messages are counted in windows of one second. Messages beyond the sample limit are skipped.
+/// Samples whose content would exceed the byte budget are delivered without content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnoopLimits {
+    /// Maximum number of samples per second
+    pub max_samples_per_sec: u32,
+
+    /// Maximum number of content bytes per second
+    pub max_bytes_per_sec: usize,
+}
+
+impl Default for SnoopLimits {
+    fn default() -> Self {
+        Self {
+            max_samples_per_sec: 20,
+            max_bytes_per_sec: 64 * 1024,
+        }
+    }
+}
+
+/// Content of a snooped message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SnoopContent {
+    /// Message serialized as JSON
+    Json(String),
+
+    /// Message formatted with `Debug`
+    Debug(String),
+}
+
+impl SnoopContent {
+    /// Size of the content in bytes
+    pub fn len(&self) -> usize {
+        match self {
+            SnoopContent::Json(text) | SnoopContent::Debug(text) => text.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A message observed by a snoop
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnoopSample {
+    /// Number of messages which passed the channel since the snoop was attached, including this
+    /// one
+    pub count: u64,
+
+    /// Size of the content or the inline size of the message if it has no content
+    pub size: usize,
+
+    /// None if the message type has no snoop format or the byte budget was exhausted
+    pub content: Option<SnoopContent>,
+}
+
+/// Formats messages for snoops
+pub type SnoopFormat<T> = Arc<dyn Fn(&T) -> SnoopContent + Send + Sync>;
+
+/// Counts samples and bytes in windows of one second
+#[derive(Debug, Clone)]
+pub struct SnoopLimiter {
+    limits: SnoopLimits,
+    window_begin: Option<Instant>,
+    samples: u32,
+    bytes: usize,
+}
+
+impl SnoopLimiter {
+    const WINDOW: Duration = Duration::from_secs(1);
+
+    pub fn new(limits: SnoopLimits) -> Self {
+        Self {
+            limits,
+            window_begin: None,
+            samples: 0,
+            bytes: 0,
+        }
+    }
+
+    /// Returns true if a sample may be taken at the given time
+    pub fn admit(&mut self, now: Instant) -> bool {
+        if self
+            .window_begin
+            .is_none_or(|begin| now.saturating_duration_since(begin) >= Self::WINDOW)
+        {
+            self.window_begin = Some(now);
+            self.samples = 0;
+            self.bytes = 0;
+        }
+
+        if self.samples >= self.limits.max_samples_per_sec {
+            return false;
+        }
+        self.samples += 1;
+        true
+    }
+
+    /// Returns true if content of the given size fits into the byte budget of the current window
+    pub fn admit_bytes(&mut self, size: usize) -> bool {
+        if self.bytes + size > self.limits.max_bytes_per_sec {
+            return false;
+        }
+        self.bytes += size;
+        true
+    }
+
+    /// True if content of at least the given size would fit into the byte budget of the current
+    /// window
+    pub fn has_bytes(&self, size: usize) -> bool {
+        self.bytes + size <= self.limits.max_bytes_per_sec
+    }
+}
+
+/// Observer installed on the back stage of a receiver
+pub(crate) struct StageTap<T> {
+    format: Option<SnoopFormat<T>>,
+    limiter: SnoopLimiter,
+    tx: SyncSender<SnoopSample>,
+    count: u64,
+}
+
+impl<T> StageTap<T> {
+    /// Called for every message pushed into the back stage
+    pub fn observe(&mut self, value: &T) {
+        self.count += 1;
+        if !self.limiter.admit(Instant::now()) {
+            return;
+        }
+
+        // The inline size is a lower bound of the content size. It is checked before formatting
+        // such that messages are not formatted only to be dropped.
+        let content = self
+            .format
+            .as_ref()
+            .filter(|_| self.limiter.has_bytes(core::mem::size_of::<T>().max(1)))
+            .map(|format| format(value))
+            .filter(|content| self.limiter.admit_bytes(content.len()));
+        let size = content
+            .as_ref()
+            .map_or(core::mem::size_of::<T>(), SnoopContent::len);
+
+        // never blocks the worker; samples are skipped if the observer falls behind
+        self.tx
+            .try_send(SnoopSample {
+                count: self.count,
+                size,
+                content,
+            })
+            .ok();
+    }
+}
+
+/// Type-erased access to the back stage of a receiver to attach a snoop
+///
+/// The tap observes every message pushed into the back stage during the flush of the transmitter
+/// without consuming it.
+pub trait TapPoint: Send + Sync {
+    /// Name of the message type
+    fn message_type_name(&self) -> &'static str;
+
+    fn message_type_id(&self) -> TypeId;
+
+    /// Installs a tap which sends samples to `tx`. `format` is used if it is a [SnoopFormat] of
+    /// the message type. Replaces a previously attached tap.
+    fn attach(
+        &self,
+        format: Option<&(dyn Any + Send + Sync)>,
+        limits: SnoopLimits,
+        tx: SyncSender<SnoopSample>,
+    );
+
+    /// Removes the tap
+    fn detach(&self);
+
+    /// True if a tap is attached
+    fn is_attached(&self) -> bool;
+}
+
+impl<T: Send + Sync + 'static> TapPoint for RwLock<BackStage<T>> {
+    fn message_type_name(&self) -> &'static str {
+        type_name::<T>()
+    }
+
+    fn message_type_id(&self) -> TypeId {
+        TypeId::of::<T>()
+    }
+
+    fn attach(
+        &self,
+        format: Option<&(dyn Any + Send + Sync)>,
+        limits: SnoopLimits,
+        tx: SyncSender<SnoopSample>,
+    ) {
+        let format = format.and_then(|f| f.downcast_ref::<SnoopFormat<T>>().cloned());
+        self.write().unwrap().set_tap(Some(StageTap {
+            format,
+            limiter: SnoopLimiter::new(limits),
+            tx,
+            count: 0,
+        }));
+    }
+
+    fn detach(&self) {
+        self.write().unwrap().set_tap(None);
+    }
+
+    fn is_attached(&self) -> bool {
+        self.read().unwrap().has_tap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::channels::{
+        DoubleBufferRx, DoubleBufferTx, OverflowPolicy, RetentionPolicy, Rx, SnoopContent,
+        SnoopFormat, SnoopLimiter, SnoopLimits, Tx,
+    };
+    use core::time::Duration;
+    use std::{
+        sync::{mpsc::sync_channel, Arc},
+        time::Instant,
+    };
+
+    #[test]
+    fn test_limiter() {
+        let mut limiter = SnoopLimiter::new(SnoopLimits {
+            max_samples_per_sec: 3,
+            max_bytes_per_sec: 10,
+        });
+        let t0 = Instant::now();
+
+        assert!((0..3).all(|_| limiter.admit(t0)));
+        assert!(!limiter.admit(t0 + Duration::from_millis(999)));
+
+        assert!(limiter.admit_bytes(6));
+        assert!(!limiter.admit_bytes(6));
+        assert!(limiter.has_bytes(4));
+        assert!(!limiter.has_bytes(5));
+        assert!(limiter.admit_bytes(4));
+        assert!(!limiter.has_bytes(1));
+
+        // a new window starts after one second
+        assert!(limiter.admit(t0 + Duration::from_secs(1)));
+        assert!(limiter.admit_bytes(10));
+    }
+
+    #[test]
+    fn test_tap() {
+        let mut tx = DoubleBufferTx::<u32>::new_auto_size();
+        let mut rx = DoubleBufferRx::new_auto_size();
+        tx.connect(&mut rx).unwrap();
+
+        let (sample_tx, sample_rx) = sync_channel(16);
+        let format: SnoopFormat<u32> = Arc::new(|v| SnoopContent::Debug(format!("{v}")));
+        let tap = Rx::tap_point(&rx).unwrap();
+        assert_eq!(tap.message_type_name(), "u32");
+        tap.attach(
+            Some(&format),
+            SnoopLimits {
+                max_samples_per_sec: 2,
+                max_bytes_per_sec: 1024,
+            },
+            sample_tx,
+        );
+        assert!(tap.is_attached());
+
+        tx.push_many([10, 11, 12]).unwrap();
+        tx.flush();
+
+        // the receiver gets all messages
+        rx.sync();
+        assert_eq!(rx.pop_all().collect::<Vec<_>>(), [10, 11, 12]);
+
+        let samples = sample_rx.try_iter().collect::<Vec<_>>();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[1].count, 2);
+        assert_eq!(samples[1].content, Some(SnoopContent::Debug("11".into())));
+
+        tap.detach();
+        assert!(!tap.is_attached());
+        tx.push(13).unwrap();
+        tx.flush();
+        assert_eq!(sample_rx.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn test_tap_rejected() {
+        let mut tx = DoubleBufferTx::<u32>::new(4);
+        let mut rx = DoubleBufferRx::new(OverflowPolicy::Reject(2), RetentionPolicy::Drop);
+        tx.connect(&mut rx).unwrap();
+
+        let (sample_tx, sample_rx) = sync_channel(16);
+        let format: SnoopFormat<u32> = Arc::new(|v| SnoopContent::Debug(format!("{v}")));
+        Rx::tap_point(&rx).unwrap().attach(
+            Some(&format),
+            SnoopLimits {
+                max_samples_per_sec: 100,
+                max_bytes_per_sec: 1024,
+            },
+            sample_tx,
+        );
+
+        // the third message is rejected by the receiver and thus not reported
+        tx.push_many([10, 11, 12]).unwrap();
+        assert_eq!(tx.flush().published, 2);
+
+        let samples = sample_rx.try_iter().collect::<Vec<_>>();
+        assert_eq!(
+            samples
+                .iter()
+                .map(|s| s.content.clone())
+                .collect::<Vec<_>>(),
+            [
+                Some(SnoopContent::Debug("10".into())),
+                Some(SnoopContent::Debug("11".into()))
+            ]
+        );
+        assert_eq!(samples[1].count, 2);
+    }
+}
@@ -0,0 +1,726 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::channels::{
+    message_type_hash, ConnectionCheck, DynConnectError, FlushResult, OverflowPolicy, Pop, Rx,
+    RxBundle, RxRecvError, SyncResult, Tx, TxBundle, TxConnectError, TxSendError,
+};
+use core::{
+    any::Any,
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    ops,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+use std::{borrow::Cow, sync::Arc};
+
+/// The transmitting side of a single-producer single-consumer channel
+///
+/// A leaner alternative to `DoubleBufferTx` for a hot path between exactly two codelets. Messages
+/// are written directly into a fixed-size lock-free ring owned by the receiver. Flush publishes
+/// them with a single atomic store. The transmitter can be connected to exactly one receiver.
+///
+/// Pushing messages while the transmitter is not connected succeeds but the messages are
+/// discarded, the same as for an unconnected `DoubleBufferTx`.
+pub struct SpscTx<T> {
+    ring: Option<Arc<Ring<T>>>,
+    policy: OverflowPolicy,
+
+    /// Index of the next slot to write
+    tail: usize,
+
+    /// Index up to which messages were published to the receiver
+    flushed: usize,
+
+    /// Last known read index of the receiver. The receiver only moves it forward thus it is safe
+    /// to use a stale value.
+    head_cache: usize,
+
+    is_closed: bool,
+}
+
+/// The receiving side of a single-producer single-consumer channel
+///
+/// Supports the `Reject` and `Forget` overflow policies with fixed capacity. Sync makes all
+/// messages flushed by the transmitter visible and popping a message releases its slot right away.
+/// Messages which were not popped stay in the queue, i.e. the retention policy is always `Keep`.
+///
+/// With the `Forget` policy the transmitter forgets the oldest message it did not flush yet to make
+/// room for a new message. Flushed messages are owned by the receiver and are never taken back, thus
+/// if all queued messages were flushed the new message is forgotten instead.
+pub struct SpscRx<T> {
+    ring: Arc<Ring<T>>,
+    policy: OverflowPolicy,
+
+    /// Index of the next slot to read
+    head: usize,
+
+    /// Index up to which messages are visible after the last sync
+    visible: usize,
+
+    is_closed: bool,
+    is_connected: bool,
+}
+
+struct Ring<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+
+    /// Read index published by the receiver when messages are popped
+    head: CachePadded<AtomicUsize>,
+
+    /// Write index published by the transmitter on flush
+    tail: CachePadded<AtomicUsize>,
+
+    /// Number of messages forgotten by the transmitter since the last sync
+    forgotten: AtomicUsize,
+
+    closed: AtomicBool,
+}
+
+// SAFETY: Slots in [head, tail) are only accessed by the receiver and all other slots only by the
+// transmitter. Ownership of slots is passed on with release/acquire updates of head and tail.
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+/// Keeps the indices written by transmitter and receiver on separate cache lines
+#[repr(align(64))]
+struct CachePadded<T>(T);
+
+impl<T> ops::Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> ops::DerefMut for CachePadded<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> Ring<T> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity)
+                .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+                .collect(),
+            head: CachePadded(AtomicUsize::new(0)),
+            tail: CachePadded(AtomicUsize::new(0)),
+            forgotten: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn slot(&self, index: usize) -> *mut MaybeUninit<T> {
+        self.slots[index % self.slots.len()].get()
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        let head = *self.head.get_mut();
+        let tail = *self.tail.get_mut();
+        for i in head..tail {
+            // SAFETY: slots in [head, tail) contain flushed messages which were not popped
+            unsafe { (*self.slot(i)).assume_init_drop() };
+        }
+    }
+}
+
+impl<T> SpscTx<T> {
+    pub fn new() -> Self {
+        Self {
+            ring: None,
+            policy: OverflowPolicy::Reject(0),
+            tail: 0,
+            flushed: 0,
+            head_cache: 0,
+            is_closed: false,
+        }
+    }
+
+    /// Puts a message in the outbox
+    pub fn push(&mut self, value: T) -> Result<(), TxSendError> {
+        if self.is_closed {
+            return Err(TxSendError::Closed);
+        }
+
+        let Some(ring) = self.ring.as_ref() else {
+            return Ok(());
+        };
+
+        let capacity = ring.capacity();
+        if self.tail - self.head_cache == capacity {
+            self.head_cache = ring.head.load(Ordering::Acquire);
+            if self.tail - self.head_cache == capacity {
+                return match self.policy {
+                    OverflowPolicy::Forget(_) => {
+                        self.forget_oldest_pending(value);
+                        Ok(())
+                    }
+                    _ => Err(TxSendError::QueueFull),
+                };
+            }
+        }
+
+        // SAFETY: the slot is outside of [head, tail) and thus owned by the transmitter
+        unsafe { (*ring.slot(self.tail)).write(value) };
+        self.tail += 1;
+        Ok(())
+    }
+
+    /// Drops the oldest message which was not flushed yet and appends the new message. If all
+    /// queued messages were flushed the new message is dropped instead.
+    fn forget_oldest_pending(&mut self, value: T) {
+        let ring = self.ring.as_ref().unwrap();
+        ring.forgotten.fetch_add(1, Ordering::Relaxed);
+
+        if self.flushed == self.tail {
+            return;
+        }
+
+        // SAFETY: slots in [flushed, tail) are initialized and owned by the transmitter
+        unsafe {
+            (*ring.slot(self.flushed)).assume_init_drop();
+            for i in self.flushed + 1..self.tail {
+                let v = (*ring.slot(i)).assume_init_read();
+                (*ring.slot(i - 1)).write(v);
+            }
+            (*ring.slot(self.tail - 1)).write(value);
+        }
+    }
+
+    /// Connects the receiver to this transmitter. Fails if either endpoint is already connected.
+    pub fn connect(&mut self, rx: &mut SpscRx<T>) -> Result<(), TxConnectError> {
+        if self.ring.is_some() {
+            return Err(TxConnectError::MaxConnectionCountExceeded);
+        }
+        if rx.is_connected {
+            return Err(TxConnectError::ReceiverAlreadyConnected);
+        }
+
+        if self.is_closed {
+            rx.ring.closed.store(true, Ordering::Release);
+        }
+
+        self.ring = Some(rx.ring.clone());
+        self.policy = rx.policy;
+        rx.is_connected = true;
+
+        Ok(())
+    }
+
+    /// Closes the stream. The receiver observes the end of the stream after the next flush.
+    pub fn close(&mut self) {
+        self.is_closed = true;
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.is_closed
+    }
+}
+
+impl<T> Default for SpscTx<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for SpscTx<T> {
+    fn drop(&mut self) {
+        if let Some(ring) = self.ring.as_ref() {
+            for i in self.flushed..self.tail {
+                // SAFETY: messages which were not flushed are owned by the transmitter
+                unsafe { (*ring.slot(i)).assume_init_drop() };
+            }
+        }
+    }
+}
+
+impl<T: Send> Tx for SpscTx<T> {
+    fn flush(&mut self) -> FlushResult {
+        let Some(ring) = self.ring.as_ref() else {
+            return FlushResult::ZERO;
+        };
+
+        let available = self.tail - self.flushed;
+        ring.tail.store(self.tail, Ordering::Release);
+        self.flushed = self.tail;
+
+        if self.is_closed {
+            ring.closed.store(true, Ordering::Release);
+        }
+
+        FlushResult {
+            available,
+            published: available,
+            ..FlushResult::ZERO
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        self.ring.is_some()
+    }
+
+    fn message_type_hash(&self) -> Option<u64> {
+        Some(message_type_hash::<T>())
+    }
+
+    fn connect_dyn(&mut self, rx: &mut dyn Any) -> Result<(), DynConnectError>
+    where
+        Self: 'static,
+    {
+        match rx.downcast_mut::<SpscRx<T>>() {
+            Some(rx) => Ok(self.connect(rx)?),
+            None => Err(DynConnectError::TypeMismatch(std::any::type_name::<T>())),
+        }
+    }
+}
+
+impl<T: Send> TxBundle for SpscTx<T> {
+    fn len(&self) -> usize {
+        1
+    }
+
+    fn name(&self, index: usize) -> Cow<'static, str> {
+        assert_eq!(index, 0);
+        Cow::Borrowed("out")
+    }
+
+    fn flush_all(&mut self, results: &mut [FlushResult]) {
+        results[0] = self.flush();
+    }
+
+    fn check_connection(&self) -> ConnectionCheck {
+        let mut cc = ConnectionCheck::new(1);
+        cc.mark(0, self.is_connected());
+        cc
+    }
+
+    fn message_type_hash(&self, index: usize) -> Option<u64> {
+        assert_eq!(index, 0);
+        Some(message_type_hash::<T>())
+    }
+
+    fn connect_dyn(&mut self, index: usize, rx: &mut dyn Any) -> Result<(), DynConnectError>
+    where
+        Self: 'static,
+    {
+        match index {
+            0 => Tx::connect_dyn(self, rx),
+            _ => Err(DynConnectError::InvalidIndex(index)),
+        }
+    }
+}
+
+impl<T> SpscRx<T> {
+    /// Creates a new receiver with a ring of fixed capacity
+    ///
+    /// Panics if the policy is `Resize` or the capacity is zero.
+    pub fn new(policy: OverflowPolicy) -> Self {
+        let capacity = match policy {
+            OverflowPolicy::Reject(n) | OverflowPolicy::Forget(n) => n,
+            OverflowPolicy::Resize => panic!("SPSC channels require a fixed capacity"),
+        };
+        assert!(capacity > 0, "SPSC channels require a non-zero capacity");
+
+        Self {
+            ring: Arc::new(Ring::new(capacity)),
+            policy,
+            head: 0,
+            visible: 0,
+            is_closed: false,
+            is_connected: false,
+        }
+    }
+
+    /// Number of messages currently visible
+    pub fn len(&self) -> usize {
+        self.visible - self.head
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head == self.visible
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.ring.capacity()
+    }
+
+    /// Access the latest visible message (or None)
+    pub fn latest(&self) -> Option<&T> {
+        // SAFETY: visible slots are initialized and owned by the receiver
+        (self.visible > self.head)
+            .then(|| unsafe { (*self.ring.slot(self.visible - 1)).assume_init_ref() })
+    }
+
+    /// Drops all visible messages
+    pub fn clear(&mut self) {
+        while self.head < self.visible {
+            // SAFETY: visible slots are initialized and owned by the receiver
+            unsafe { (*self.ring.slot(self.head)).assume_init_drop() };
+            self.head += 1;
+        }
+        self.ring.head.store(self.head, Ordering::Release);
+    }
+
+    /// Returns true if the transmitter closed the stream and all messages have been consumed
+    pub fn is_closed(&self) -> bool {
+        self.is_closed && self.head == self.visible
+    }
+}
+
+impl<T> Pop for SpscRx<T> {
+    type Output = T;
+
+    fn is_empty(&self) -> bool {
+        SpscRx::is_empty(self)
+    }
+
+    fn is_closed(&self) -> bool {
+        SpscRx::is_closed(self)
+    }
+
+    fn pop(&mut self) -> Result<T, RxRecvError> {
+        if self.head == self.visible {
+            return Err(if self.is_closed {
+                RxRecvError::Closed
+            } else {
+                RxRecvError::QueueEmtpy
+            });
+        }
+
+        // SAFETY: visible slots are initialized and owned by the receiver. The head is advanced
+        // right away so the message is not read twice.
+        let value = unsafe { (*self.ring.slot(self.head)).assume_init_read() };
+        self.head += 1;
+        self.ring.head.store(self.head, Ordering::Release);
+        Ok(value)
+    }
+}
+
+impl<T: Send> Rx for SpscRx<T> {
+    fn sync(&mut self) -> SyncResult {
+        // the close marker is stored after the tail thus it must be loaded first
+        self.is_closed = self.ring.closed.load(Ordering::Acquire);
+        let tail = self.ring.tail.load(Ordering::Acquire);
+        let received = tail - self.visible;
+        self.visible = tail;
+
+        SyncResult {
+            received,
+            forgotten: self.ring.forgotten.swap(0, Ordering::Relaxed),
+            queue_len: self.len(),
+            capacity: Some(self.capacity()),
+            closed: self.is_closed,
+            ..SyncResult::ZERO
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        self.is_connected
+    }
+
+    fn message_type_hash(&self) -> Option<u64> {
+        Some(message_type_hash::<T>())
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any>
+    where
+        Self: 'static,
+    {
+        Some(self)
+    }
+}
+
+impl<T: Send> RxBundle for SpscRx<T> {
+    fn len(&self) -> usize {
+        1
+    }
+
+    fn name(&self, index: usize) -> Cow<'static, str> {
+        assert_eq!(index, 0);
+        Cow::Borrowed("in")
+    }
+
+    fn sync_all(&mut self, results: &mut [SyncResult]) {
+        results[0] = self.sync();
+    }
+
+    fn check_connection(&self) -> ConnectionCheck {
+        let mut cc = ConnectionCheck::new(1);
+        cc.mark(0, self.is_connected());
+        cc
+    }
+
+    fn message_type_hash(&self, index: usize) -> Option<u64> {
+        assert_eq!(index, 0);
+        Some(message_type_hash::<T>())
+    }
+
+    fn endpoint_mut(&mut self, index: usize) -> Option<&mut dyn Any>
+    where
+        Self: 'static,
+    {
+        (index == 0).then_some(self as &mut dyn Any)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        channels::{RxRecvError, SpscRx, SpscTx, SyncResult, TxConnectError, TxSendError},
+        prelude::*,
+    };
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    fn pop_all<T>(rx: &mut SpscRx<T>) -> Vec<T> {
+        let mut out = Vec::new();
+        while let Some(x) = rx.try_pop() {
+            out.push(x);
+        }
+        out
+    }
+
+    #[test]
+    fn test_push_flush_sync_pop() {
+        let mut tx = SpscTx::new();
+        let mut rx = SpscRx::new(OverflowPolicy::Reject(4));
+        connect(&mut tx, &mut rx).unwrap();
+
+        tx.push(1).unwrap();
+        tx.push(2).unwrap();
+        assert_eq!(rx.sync().received, 0);
+        assert!(rx.is_empty());
+
+        assert_eq!(tx.flush().published, 2);
+        assert_eq!(rx.sync().received, 2);
+        assert_eq!(rx.latest(), Some(&2));
+        assert_eq!(rx.try_pop(), Some(1));
+
+        // messages which were not popped are kept
+        tx.push(3).unwrap();
+        tx.flush();
+        rx.sync();
+        assert_eq!(pop_all(&mut rx), [2, 3]);
+    }
+
+    #[test]
+    fn test_reject() {
+        let mut tx = SpscTx::new();
+        let mut rx = SpscRx::new(OverflowPolicy::Reject(2));
+        connect(&mut tx, &mut rx).unwrap();
+
+        tx.push(1).unwrap();
+        tx.push(2).unwrap();
+        assert_eq!(tx.push(3), Err(TxSendError::QueueFull));
+        tx.flush();
+
+        // popping a message releases its slot
+        rx.sync();
+        assert_eq!(rx.try_pop(), Some(1));
+        tx.push(3).unwrap();
+        assert_eq!(tx.push(4), Err(TxSendError::QueueFull));
+        tx.flush();
+        rx.sync();
+        assert_eq!(pop_all(&mut rx), [2, 3]);
+    }
+
+    #[test]
+    fn test_forget() {
+        let mut tx = SpscTx::new();
+        let mut rx = SpscRx::new(OverflowPolicy::Forget(3));
+        connect(&mut tx, &mut rx).unwrap();
+
+        tx.push(1).unwrap();
+        tx.flush();
+        for i in 2..=5 {
+            tx.push(i).unwrap();
+        }
+        tx.flush();
+        assert_eq!(
+            rx.sync(),
+            SyncResult {
+                received: 3,
+                forgotten: 2,
+                queue_len: 3,
+                capacity: Some(3),
+                ..SyncResult::ZERO
+            }
+        );
+        assert_eq!(pop_all(&mut rx), [1, 4, 5]);
+
+        // all queued messages were flushed: the new message is forgotten
+        for i in 6..=9 {
+            tx.push(i).unwrap();
+        }
+        tx.flush();
+        tx.push(10).unwrap();
+        tx.flush();
+        assert_eq!(rx.sync().forgotten, 2);
+        assert_eq!(pop_all(&mut rx), [7, 8, 9]);
+    }
+
+    #[test]
+    fn test_single_connection() {
+        let mut tx = SpscTx::<u32>::new();
+        let mut rx1 = SpscRx::new(OverflowPolicy::Reject(1));
+        let mut rx2 = SpscRx::new(OverflowPolicy::Reject(1));
+        tx.connect(&mut rx1).unwrap();
+        assert!(matches!(
+            tx.connect(&mut rx2),
+            Err(TxConnectError::MaxConnectionCountExceeded)
+        ));
+
+        let mut tx2 = SpscTx::<u32>::new();
+        assert!(matches!(
+            tx2.connect(&mut rx1),
+            Err(TxConnectError::ReceiverAlreadyConnected)
+        ));
+    }
+
+    #[test]
+    fn test_close() {
+        let mut tx = SpscTx::new();
+        let mut rx = SpscRx::new(OverflowPolicy::Reject(2));
+        connect(&mut tx, &mut rx).unwrap();
+
+        tx.push(1).unwrap();
+        tx.close();
+        assert_eq!(tx.push(2), Err(TxSendError::Closed));
+        tx.flush();
+
+        assert!(rx.sync().closed);
+        assert!(!rx.is_closed());
+        assert_eq!(rx.try_pop(), Some(1));
+        assert!(rx.is_closed());
+        assert!(matches!(rx.pop(), Err(RxRecvError::Closed)));
+    }
+
+    #[test]
+    fn test_drop_releases_all_messages() {
+        struct Counted(Arc<AtomicUsize>);
+
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        {
+            let mut tx = SpscTx::new();
+            let mut rx = SpscRx::new(OverflowPolicy::Forget(8));
+            connect(&mut tx, &mut rx).unwrap();
+
+            for _ in 0..3 {
+                tx.push(Counted(drops.clone())).unwrap();
+            }
+            tx.flush();
+            rx.sync();
+            drop(rx.try_pop());
+            assert_eq!(drops.load(Ordering::Relaxed), 1);
+
+            // flushed but not synced, and not flushed at all
+            tx.push(Counted(drops.clone())).unwrap();
+            tx.flush();
+            tx.push(Counted(drops.clone())).unwrap();
+            tx.push(Counted(drops.clone())).unwrap();
+            assert_eq!(drops.load(Ordering::Relaxed), 1);
+        }
+        assert_eq!(drops.load(Ordering::Relaxed), 6);
+    }
+
+    /// Producer and consumer run on separate threads and hammer the channel
+    #[test]
+    fn test_two_threads_no_loss_or_duplication() {
+        const COUNT: usize = 200_000;
+
+        let mut tx = SpscTx::new();
+        let mut rx = SpscRx::new(OverflowPolicy::Reject(16));
+        connect(&mut tx, &mut rx).unwrap();
+
+        let producer = std::thread::spawn(move || {
+            let mut next = 0;
+            while next < COUNT {
+                while next < COUNT && tx.push(next).is_ok() {
+                    next += 1;
+                }
+                if tx.flush().published == 0 {
+                    std::thread::yield_now();
+                }
+            }
+            tx.close();
+            tx.flush();
+        });
+
+        let mut expected = 0;
+        while !rx.is_closed() {
+            if rx.sync().received == 0 {
+                std::thread::yield_now();
+            }
+            while let Some(x) = rx.try_pop() {
+                assert_eq!(x, expected);
+                expected += 1;
+            }
+        }
+        producer.join().unwrap();
+        assert_eq!(expected, COUNT);
+    }
+
+    /// Compares SPSC and double-buffered channels for a single producer and consumer
+    #[test]
+    fn test_spsc_throughput() {
+        const MESSAGES_PER_STEP: usize = 8;
+        const STEPS: usize = 20_000;
+
+        fn run<X: Tx, R: Rx + Pop<Output = usize>>(
+            mut tx: X,
+            mut rx: R,
+            push: impl Fn(&mut X, usize),
+        ) -> (usize, core::time::Duration) {
+            let mut sum = 0;
+            let time = std::time::Instant::now();
+            for step in 0..STEPS {
+                for i in 0..MESSAGES_PER_STEP {
+                    push(&mut tx, step + i);
+                }
+                tx.flush();
+                rx.sync();
+                while let Some(x) = rx.try_pop() {
+                    sum += x;
+                }
+            }
+            (sum, time.elapsed())
+        }
+
+        let mut spsc_tx = SpscTx::new();
+        let mut spsc_rx = SpscRx::new(OverflowPolicy::Reject(MESSAGES_PER_STEP));
+        connect(&mut spsc_tx, &mut spsc_rx).unwrap();
+        let (spsc_sum, spsc_time) = run(spsc_tx, spsc_rx, |tx, x| tx.push(x).unwrap());
+
+        let mut db_tx = DoubleBufferTx::new(MESSAGES_PER_STEP);
+        let mut db_rx = DoubleBufferRx::new(
+            OverflowPolicy::Reject(MESSAGES_PER_STEP),
+            RetentionPolicy::Drop,
+        );
+        connect(&mut db_tx, &mut db_rx).unwrap();
+        let (db_sum, db_time) = run(db_tx, db_rx, |tx, x| tx.push(x).unwrap());
+
+        assert_eq!(spsc_sum, db_sum);
+
+        // The SPSC channel avoids the double buffer swap and should never be notably slower. The
+        // bound is generous to keep the test stable on loaded machines.
+        assert!(
+            spsc_time < 2 * db_time,
+            "spsc: {spsc_time:?}, double buffer: {db_time:?}"
+        );
+    }
+}
@@ -1,13 +1,27 @@
 // Copyright 2023 by David Weikersdorfer. All rights reserved.
 
-use crate::channels::SyncResult;
-use core::ops;
-use std::collections::{vec_deque, VecDeque};
+use crate::{
+    channels::{Residency, StageTap, SyncResult},
+    codelet::CountTotal,
+};
+use core::{ops, time::Duration};
+use nodo_core::{Clock, PubtimeMarker};
+use std::{
+    collections::{vec_deque, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
 /// The front stage of StageQueue
 pub struct FrontStage<T> {
     items: VecDeque<T>,
     capacity: usize,
+    is_closed: bool,
+
+    /// Number of items discarded by the consumer since the last sync
+    discarded: usize,
 }
 
 /// The back stage of StageQueue
@@ -15,6 +29,85 @@ pub struct BackStage<T> {
     items: VecDeque<T>,
     overflow_policy: OverflowPolicy,
     retention_policy: RetentionPolicy,
+    sample_time: Option<fn(&T) -> Duration>,
+    is_closed: bool,
+    is_detached: bool,
+    delivery_counter: Option<u64>,
+    latency: Option<LatencyTracking>,
+    arrival: Option<ArrivalTracking>,
+    tap: Option<StageTap<T>>,
+
+    /// Number of items forgotten or rejected since the last sync
+    forgotten: usize,
+    rejected: usize,
+}
+
+/// Clock used to timestamp items for latency tracking
+pub type LatencyClock = Box<dyn Clock<PubtimeMarker> + Send + Sync>;
+
+/// Enqueue times of items in the back stage kept parallel to the items
+struct LatencyTracking {
+    clock: LatencyClock,
+    enqueue_times: VecDeque<Duration>,
+    residency: CountTotal,
+}
+
+impl LatencyTracking {
+    fn record(&mut self, count: usize) {
+        let now = *self.clock.now();
+        self.enqueue_times.extend(core::iter::repeat_n(now, count));
+    }
+
+    fn forget(&mut self, count: usize) {
+        self.enqueue_times.drain(..count);
+    }
+
+    /// Computes the residency of all items and clears their enqueue times
+    fn take_residency(&mut self) -> Option<Residency> {
+        if self.enqueue_times.is_empty() {
+            return None;
+        }
+
+        let now = *self.clock.now();
+        let count = self.enqueue_times.len();
+        let mut min = Duration::MAX;
+        let mut max = Duration::ZERO;
+        let mut total = Duration::ZERO;
+        for time in self.enqueue_times.drain(..) {
+            let dt = now.saturating_sub(time);
+            min = min.min(dt);
+            max = max.max(dt);
+            total += dt;
+            self.residency.push(dt);
+        }
+
+        Some(Residency {
+            count,
+            min,
+            average: total / count as u32,
+            max,
+        })
+    }
+}
+
+/// Arrival stamps of items in the back stage kept parallel to the items
+///
+/// Stamps are drawn from a counter which can be shared by multiple channels such that items
+/// received on different channels can be ordered by their arrival.
+struct ArrivalTracking {
+    counter: Arc<AtomicU64>,
+    stamps: VecDeque<u64>,
+}
+
+impl ArrivalTracking {
+    fn record(&mut self, count: usize) {
+        let first = self.counter.fetch_add(count as u64, Ordering::Relaxed);
+        self.stamps.extend(first..first + count as u64);
+    }
+
+    fn forget(&mut self, count: usize) {
+        self.stamps.drain(..count);
+    }
 }
 
 /// Push policy in case the back stage is at capacity when an item is pushed.
@@ -44,6 +137,25 @@ pub enum RetentionPolicy {
 
     /// The dev must drain all items out of the queue before the frame ends.
     EnforceEmpty,
+
+    /// Like `Keep` but thins out leftover items by their timestamp. One item per interval of
+    /// `recent_resolution` is kept for items less than `recent` older than the newest item, and
+    /// one item per interval of `old_resolution` for older items. The oldest and the newest item
+    /// are always kept. `old_resolution` should be a multiple of `recent_resolution` so that
+    /// items remain on the same grid as they age. Only available for channels of timestamped
+    /// messages, see `DoubleBufferRx::new_timeseries`.
+    KeepDecimated {
+        recent: Duration,
+        recent_resolution: Duration,
+        old_resolution: Duration,
+    },
+}
+
+impl RetentionPolicy {
+    /// True for policies which keep leftover items
+    pub fn is_keep(&self) -> bool {
+        matches!(self, Self::Keep | Self::KeepDecimated { .. })
+    }
 }
 
 impl<T> FrontStage<T> {
@@ -51,6 +163,8 @@ impl<T> FrontStage<T> {
         Self {
             items: VecDeque::with_capacity(capacity),
             capacity,
+            is_closed: false,
+            discarded: 0,
         }
     }
 
@@ -66,20 +180,93 @@ impl<T> FrontStage<T> {
         self.len() == 0
     }
 
+    /// True if the close marker was received. There might still be items left in the queue.
+    pub fn is_closed(&self) -> bool {
+        self.is_closed
+    }
+
     pub fn clear(&mut self) {
         self.items.clear()
     }
 
+    /// Forgets a previously received close marker
+    pub(crate) fn reopen(&mut self) {
+        self.is_closed = false;
+    }
+
     pub fn pop(&mut self) -> Option<T> {
         self.items.pop_front()
     }
 
+    /// Removes the newest item and discards all others
+    pub fn take_latest(&mut self) -> Option<T> {
+        let latest = self.items.pop_back()?;
+        self.discard(self.items.len());
+        Some(latest)
+    }
+
+    /// Discards the oldest items such that at most `n` items remain
+    pub fn retain_last(&mut self, n: usize) {
+        self.discard(self.items.len().saturating_sub(n));
+    }
+
+    /// Removes the `count` oldest items and counts them as dropped at the next sync
+    fn discard(&mut self, count: usize) {
+        self.items.drain(..count);
+        self.discarded += count;
+    }
+
     pub fn drain<R>(&mut self, range: R) -> vec_deque::Drain<'_, T>
     where
         R: ops::RangeBounds<usize>,
     {
         self.items.drain(range)
     }
+
+    /// Removes all items without moving them
+    pub fn take_all(&mut self) -> VecDeque<T> {
+        core::mem::take(&mut self.items)
+    }
+
+    /// Keeps only the first item per time interval of the resolution for their age. Returns the
+    /// number of removed items.
+    fn decimate(
+        &mut self,
+        sample_time: fn(&T) -> Duration,
+        recent: Duration,
+        recent_resolution: Duration,
+        old_resolution: Duration,
+    ) -> usize {
+        let Some(newest) = self.items.back().map(sample_time) else {
+            return 0;
+        };
+        let boundary = newest.saturating_sub(recent);
+
+        let count = self.items.len();
+        let mut index = 0;
+        let mut last_kept: Option<Duration> = None;
+        self.items.retain(|item| {
+            let time = sample_time(item);
+            let resolution = if time >= boundary {
+                recent_resolution
+            } else {
+                old_resolution
+            };
+            let keep = index + 1 == count
+                || resolution.is_zero()
+                || last_kept.is_none_or(|last| {
+                    time.as_nanos() / resolution.as_nanos()
+                        != last.as_nanos() / resolution.as_nanos()
+                });
+            index += 1;
+            if keep {
+                last_kept = Some(time);
+            }
+            keep
+        });
+
+        count - self.items.len()
+    }
 }
 
 impl<T> ops::Index<usize> for FrontStage<T> {
@@ -99,8 +286,7 @@ impl<T> ops::IndexMut<usize> for FrontStage<T> {
 impl<T> BackStage<T> {
     pub fn new(overflow_policy: OverflowPolicy, retention_policy: RetentionPolicy) -> Self {
         assert!(
-            retention_policy != RetentionPolicy::Keep
-                || !matches!(overflow_policy, OverflowPolicy::Reject(_)),
+            !retention_policy.is_keep() || !matches!(overflow_policy, OverflowPolicy::Reject(_)),
             "Retention policy 'Keep' not allowed with overflow policy 'Reject'"
         );
 
@@ -113,9 +299,108 @@ impl<T> BackStage<T> {
             items,
             overflow_policy,
             retention_policy,
+            sample_time: None,
+            is_closed: false,
+            is_detached: false,
+            delivery_counter: None,
+            latency: None,
+            arrival: None,
+            tap: None,
+            forgotten: 0,
+            rejected: 0,
         }
     }
 
+    /// Installs or removes the snoop observing pushed items
+    pub(crate) fn set_tap(&mut self, tap: Option<StageTap<T>>) {
+        self.tap = tap;
+    }
+
+    pub(crate) fn has_tap(&self) -> bool {
+        self.tap.is_some()
+    }
+
+    /// Sets the function used to get the timestamp of items for the `KeepDecimated` policy
+    pub(crate) fn set_sample_time(&mut self, sample_time: fn(&T) -> Duration) {
+        self.sample_time = Some(sample_time);
+    }
+
+    /// Side slot for the delivery counter of the last item pushed by a transmitter with delivery
+    /// tracking enabled
+    pub(crate) fn set_delivery_counter(&mut self, counter: u64) {
+        self.delivery_counter = Some(counter);
+    }
+
+    pub(crate) fn delivery_counter(&self) -> Option<u64> {
+        self.delivery_counter
+    }
+
+    /// Records the time when items are pushed such that sync can report how long they waited in
+    /// the back stage. Items already in the back stage are timestamped now.
+    pub(crate) fn enable_latency_tracking(&mut self, clock: LatencyClock) {
+        let mut latency = LatencyTracking {
+            clock,
+            enqueue_times: VecDeque::with_capacity(self.items.capacity()),
+            residency: CountTotal::default(),
+        };
+        latency.record(self.items.len());
+        self.latency = Some(latency);
+    }
+
+    /// Stamps pushed items with the next value of the counter. Items already in the back stage
+    /// are stamped now.
+    pub(crate) fn enable_arrival_stamps(&mut self, counter: Arc<AtomicU64>) {
+        let mut arrival = ArrivalTracking {
+            counter,
+            stamps: VecDeque::with_capacity(self.items.capacity()),
+        };
+        arrival.record(self.items.len());
+        self.arrival = Some(arrival);
+    }
+
+    /// Removes the arrival stamps of all items in the back stage. None if arrival stamps are not
+    /// enabled.
+    pub(crate) fn take_arrival_stamps(&mut self) -> Option<VecDeque<u64>> {
+        self.arrival
+            .as_mut()
+            .map(|arrival| core::mem::take(&mut arrival.stamps))
+    }
+
+    /// Records timestamps of pushed items for latency tracking and arrival stamps
+    fn record_pushed(&mut self, count: usize) {
+        if let Some(latency) = self.latency.as_mut() {
+            latency.record(count);
+        }
+        if let Some(arrival) = self.arrival.as_mut() {
+            arrival.record(count);
+        }
+    }
+
+    /// Removes timestamps of the oldest items which were forgotten
+    fn forget_oldest(&mut self, count: usize) {
+        if let Some(latency) = self.latency.as_mut() {
+            latency.forget(count);
+        }
+        if let Some(arrival) = self.arrival.as_mut() {
+            arrival.forget(count);
+        }
+    }
+
+    /// Removes timestamps of all items
+    fn clear_stamps(&mut self) {
+        if let Some(latency) = self.latency.as_mut() {
+            latency.enqueue_times.clear();
+        }
+        if let Some(arrival) = self.arrival.as_mut() {
+            arrival.stamps.clear();
+        }
+    }
+
+    /// Residency of all items synced since latency tracking was enabled
+    pub(crate) fn residency_stats(&self) -> Option<&CountTotal> {
+        self.latency.as_ref().map(|l| &l.residency)
+    }
+
     pub fn overflow_policy(&self) -> &OverflowPolicy {
         &self.overflow_policy
     }
@@ -128,74 +413,166 @@ impl<T> BackStage<T> {
         self.items.len()
     }
 
+    /// Marks the stream as closed. The close marker is passed to the front stage on the next sync.
+    pub fn close(&mut self) {
+        self.is_closed = true;
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.is_closed
+    }
+
+    /// Moves all items and settings into a new stage and leaves behind an empty stage which is
+    /// marked as detached. A close marker is not moved.
+    pub(crate) fn detach(&mut self) -> BackStage<T> {
+        let empty = BackStage::new(self.overflow_policy, self.retention_policy);
+        let mut stage = core::mem::replace(self, empty);
+        stage.is_closed = false;
+        self.is_detached = true;
+        stage
+    }
+
+    /// True if the receiver which owned this stage moved on to another transmitter
+    pub(crate) fn is_detached(&self) -> bool {
+        self.is_detached
+    }
+
     pub fn push(&mut self, value: T) -> Result<(), PushError> {
         match self.overflow_policy {
             OverflowPolicy::Reject(n) => {
                 if self.items.len() == n {
+                    self.rejected += 1;
                     return Err(PushError::Rejected);
                 }
             }
             OverflowPolicy::Forget(n) => {
                 if self.items.len() == n {
                     self.items.pop_front();
+                    self.forgotten += 1;
+                    self.forget_oldest(1);
                 }
             }
             OverflowPolicy::Resize => {}
         }
 
+        if let Some(tap) = self.tap.as_mut() {
+            tap.observe(&value);
+        }
+
         self.items.push_back(value);
+        self.record_pushed(1);
 
         Ok(())
     }
 
+    /// Pushes all items or none of them if they do not fit with the `Reject` policy
+    pub fn push_all<I>(&mut self, values: I) -> Result<(), PushError>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let values = values.into_iter();
+        if let OverflowPolicy::Reject(n) = self.overflow_policy {
+            if self.items.len() + values.len() > n {
+                self.rejected += values.len();
+                return Err(PushError::Rejected);
+            }
+        }
+
+        for value in values {
+            self.push(value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Pushes clones of the given items according to the overflow policy. Returns the number of
+    /// accepted items. With the `Reject` policy only as many items as fit are accepted starting
+    /// with the first one.
+    pub fn push_slice(&mut self, values: &[T]) -> usize
+    where
+        T: Clone,
+    {
+        let (accepted, forgotten, pushed) = match self.overflow_policy {
+            OverflowPolicy::Reject(n) => {
+                let count = values.len().min(n.saturating_sub(self.items.len()));
+                self.items.extend(values[..count].iter().cloned());
+                self.rejected += values.len() - count;
+                (count, 0, count)
+            }
+            OverflowPolicy::Forget(n) => {
+                // only the newest items which fit are cloned
+                let kept = &values[values.len().saturating_sub(n)..];
+                let excess = (self.items.len() + kept.len()).saturating_sub(n);
+                self.items.drain(..excess);
+                self.items.extend(kept.iter().cloned());
+                self.forgotten += excess + values.len() - kept.len();
+                (values.len(), excess, kept.len())
+            }
+            OverflowPolicy::Resize => {
+                self.items.extend(values.iter().cloned());
+                (values.len(), 0, values.len())
+            }
+        };
+
+        if let Some(tap) = self.tap.as_mut() {
+            values[..accepted]
+                .iter()
+                .for_each(|value| tap.observe(value));
+        }
+
+        self.forget_oldest(forgotten);
+        self.record_pushed(pushed);
+
+        accepted
+    }
+
     /// Clears the front stage and moves all items from the backstage to the front stage
     pub fn sync(&mut self, target: &mut FrontStage<T>) -> SyncResult {
+        // all items leave the back stage
+        let residency = self
+            .latency
+            .as_mut()
+            .and_then(LatencyTracking::take_residency);
+        if let Some(arrival) = self.arrival.as_mut() {
+            arrival.stamps.clear();
+        }
+
+        let mut result = self.sync_items(target);
+        result.residency = residency;
+        result.dropped += core::mem::take(&mut target.discarded);
+        result.forgotten += core::mem::take(&mut self.forgotten);
+        result.rejected = core::mem::take(&mut self.rejected);
+        result.queue_len = target.len();
+        result.capacity = match self.overflow_policy {
+            OverflowPolicy::Reject(n) | OverflowPolicy::Forget(n) => Some(n),
+            OverflowPolicy::Resize => None,
+        };
+
+        // The close marker is passed on together with the last items
+        if self.is_closed {
+            target.is_closed = true;
+        }
+        result.closed = target.is_closed;
+
+        result
+    }
+
+    fn sync_items(&mut self, target: &mut FrontStage<T>) -> SyncResult {
         match self.retention_policy {
-            RetentionPolicy::Keep => {
-                match self.overflow_policy {
-                    OverflowPolicy::Forget(n) => {
-                        let incoming_count = self.items.len();
-                        assert!(incoming_count <= n);
-                        let current_count = target.items.len();
-                        assert!(current_count <= n);
-
-                        let available_count = n - target.len();
-                        let forgotten = if available_count < incoming_count {
-                            let delta = incoming_count - available_count;
-                            target.drain(0..delta);
-                            delta
-                        } else {
-                            0
-                        };
-
-                        target.items.append(&mut self.items);
-
-                        assert_eq!(target.items.len(), (current_count + incoming_count).min(n));
-                        assert_eq!(target.items.capacity(), n);
-                        assert_eq!(self.items.len(), 0);
-                        assert_eq!(self.items.capacity(), n);
-
-                        SyncResult {
-                            received: incoming_count,
-                            forgotten,
-                            ..Default::default()
-                        }
-                    }
-                    OverflowPolicy::Reject(_) => {
-                        // SAFETY: This is checked in the constructor.
-                        unreachable!();
-                    }
-                    OverflowPolicy::Resize => {
-                        let result = SyncResult {
-                            received: self.items.len(),
-                            ..Default::default()
-                        };
-
-                        target.items.append(&mut self.items);
-
-                        result
-                    }
-                }
+            RetentionPolicy::Keep => self.sync_keep(target),
+            RetentionPolicy::KeepDecimated {
+                recent,
+                recent_resolution,
+                old_resolution,
+            } => {
+                let sample_time = self
+                    .sample_time
+                    .expect("Retention policy 'KeepDecimated' requires timestamped items");
+                let mut result = self.sync_keep(target);
+                result.dropped +=
+                    target.decimate(sample_time, recent, recent_resolution, old_resolution);
+                result
             }
             RetentionPolicy::Drop | RetentionPolicy::EnforceEmpty => {
                 let result = SyncResult {
@@ -215,15 +592,71 @@ impl<T> BackStage<T> {
         }
     }
 
+    /// Appends incoming items to the leftover items in the front stage
+    fn sync_keep(&mut self, target: &mut FrontStage<T>) -> SyncResult {
+        match self.overflow_policy {
+            OverflowPolicy::Forget(n) => {
+                let incoming_count = self.items.len();
+                assert!(incoming_count <= n);
+                let current_count = target.items.len();
+                assert!(current_count <= n);
+
+                let available_count = n - target.len();
+                let forgotten = if available_count < incoming_count {
+                    let delta = incoming_count - available_count;
+                    target.drain(0..delta);
+                    delta
+                } else {
+                    0
+                };
+
+                target.items.append(&mut self.items);
+
+                assert_eq!(target.items.len(), (current_count + incoming_count).min(n));
+                assert_eq!(target.items.capacity(), n);
+                assert_eq!(self.items.len(), 0);
+                assert_eq!(self.items.capacity(), n);
+
+                SyncResult {
+                    received: incoming_count,
+                    forgotten,
+                    ..Default::default()
+                }
+            }
+            OverflowPolicy::Reject(_) => {
+                // SAFETY: This is checked in the constructor.
+                unreachable!();
+            }
+            OverflowPolicy::Resize => {
+                let result = SyncResult {
+                    received: self.items.len(),
+                    ..Default::default()
+                };
+
+                target.items.append(&mut self.items);
+
+                result
+            }
+        }
+    }
+
     pub fn iter(&self) -> std::collections::vec_deque::Iter<'_, T> {
         self.items.iter()
     }
 
     pub fn drain_all(&mut self) -> std::collections::vec_deque::Drain<'_, T> {
+        self.clear_stamps();
         self.items.drain(..)
     }
 
+    /// Removes all items without moving them
+    pub fn take_all(&mut self) -> VecDeque<T> {
+        self.clear_stamps();
+        core::mem::take(&mut self.items)
+    }
+
     pub fn clear(&mut self) {
+        self.clear_stamps();
         self.items.clear()
     }
 }
@@ -292,6 +725,7 @@ mod tests {
             sq.sync(),
             SyncResult {
                 received: 2,
+                queue_len: 2,
                 ..Default::default()
             }
         );
@@ -307,6 +741,7 @@ mod tests {
             sq.sync(),
             SyncResult {
                 received: 3,
+                queue_len: 3,
                 ..Default::default()
             }
         );
@@ -326,6 +761,9 @@ mod tests {
             sq.sync(),
             SyncResult {
                 received: 1,
+                rejected: 1,
+                queue_len: 1,
+                capacity: Some(1),
                 ..Default::default()
             }
         );
@@ -350,6 +788,9 @@ mod tests {
             sq.sync(),
             SyncResult {
                 received: 1,
+                forgotten: 1,
+                queue_len: 1,
+                capacity: Some(1),
                 ..Default::default()
             }
         );
@@ -359,4 +800,36 @@ mod tests {
         assert_eq!(sq.push(53), Ok(()));
         assert_eq!(sq.capacity(), 1);
     }
+
+    #[test]
+    fn test_close() {
+        let mut sq = StageQueue::new(2, OverflowPolicy::Reject(2));
+
+        assert_eq!(sq.push(31), Ok(()));
+        sq.back.close();
+        assert!(!sq.front.is_closed());
+
+        assert_eq!(
+            sq.sync(),
+            SyncResult {
+                received: 1,
+                queue_len: 1,
+                capacity: Some(2),
+                closed: true,
+                ..Default::default()
+            }
+        );
+        assert!(sq.front.is_closed());
+        assert_eq!(sq.pop(), Some(31));
+        assert_eq!(sq.pop(), None);
+
+        assert_eq!(
+            sq.sync(),
+            SyncResult {
+                capacity: Some(2),
+                closed: true,
+                ..Default::default()
+            }
+        );
+    }
 }
@@ -0,0 +1,218 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::channels::Timeseries;
+use core::time::Duration;
+
+/// Values which can be converted to f64 for computing statistics
+pub trait StatsValue: Copy {
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_stats_value {
+    ($($ty:ty),*) => {
+        $(
+            impl StatsValue for $ty {
+                fn to_f64(self) -> f64 {
+                    self.into()
+                }
+            }
+
+            impl StatsValue for &$ty {
+                fn to_f64(self) -> f64 {
+                    (*self).into()
+                }
+            }
+        )*
+    };
+}
+
+impl_stats_value!(f64, f32, i8, i16, i32, u8, u16, u32);
+
+/// Running mean and variance using Welford's algorithm
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Welford {
+    count: usize,
+    mean: f64,
+    m2: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Welford {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a sample
+    pub fn push(&mut self, x: f64) {
+        self.count += 1;
+        if self.count == 1 {
+            self.min = x;
+            self.max = x;
+        } else {
+            self.min = self.min.min(x);
+            self.max = self.max.max(x);
+        }
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Statistics of all samples or None if no samples were added
+    pub fn stats(&self) -> Option<WindowStats> {
+        (self.count > 0).then(|| WindowStats {
+            count: self.count,
+            mean: self.mean,
+            var: self.m2 / self.count as f64,
+            min: self.min,
+            max: self.max,
+        })
+    }
+}
+
+/// Statistics over the values in a time window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowStats {
+    /// Number of samples in the window
+    pub count: usize,
+
+    /// Mean of all samples
+    pub mean: f64,
+
+    /// Population variance of all samples
+    pub var: f64,
+
+    /// Smallest sample
+    pub min: f64,
+
+    /// Largest sample
+    pub max: f64,
+}
+
+/// Sliding-window statistics for timeseries with numeric values
+///
+/// The window ends at the latest sample and includes all samples with time in
+/// `[latest - window, latest]`. All functions return None if the window is empty.
+pub trait TimeseriesStatsExt<T>: Timeseries<T> {
+    /// Statistics over all samples in the window
+    fn stats_over(&self, window: Duration) -> Option<WindowStats>;
+
+    /// Mean over the window
+    fn mean_over(&self, window: Duration) -> Option<f64> {
+        self.stats_over(window).map(|s| s.mean)
+    }
+
+    /// Smallest and largest value in the window
+    fn min_max_over(&self, window: Duration) -> Option<(f64, f64)> {
+        self.stats_over(window).map(|s| (s.min, s.max))
+    }
+
+    /// Population variance over the window
+    fn variance_over(&self, window: Duration) -> Option<f64> {
+        self.stats_over(window).map(|s| s.var)
+    }
+
+    /// Sample rate in samples per second computed from the time between the first and the last
+    /// sample in the window. Returns None if there are less than two samples or if all samples
+    /// have the same time.
+    fn rate_over(&self, window: Duration) -> Option<f64>;
+}
+
+impl<T: StatsValue, S: Timeseries<T>> TimeseriesStatsExt<T> for S {
+    fn stats_over(&self, window: Duration) -> Option<WindowStats> {
+        let start = self.latest_time()?.saturating_sub(window);
+        let mut acc = Welford::new();
+        for (_, value) in self.iter().filter(|(t, _)| *t >= start) {
+            acc.push(value.to_f64());
+        }
+        acc.stats()
+    }
+
+    fn rate_over(&self, window: Duration) -> Option<f64> {
+        let latest = self.latest_time()?;
+        let start = latest.saturating_sub(window);
+        let mut samples = self.iter().map(|(t, _)| t).filter(|t| *t >= start);
+        let first = samples.next()?;
+        let count = 1 + samples.count();
+        let span = (latest - first).as_secs_f64();
+        (count >= 2 && span > 0.0).then(|| (count - 1) as f64 / span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::channels::{TimeseriesStatsExt, Welford, WindowStats};
+    use core::time::Duration;
+
+    fn ms(x: u64) -> Duration {
+        Duration::from_millis(x)
+    }
+
+    #[test]
+    fn test_welford() {
+        let mut acc = Welford::new();
+        assert_eq!(acc.stats(), None);
+
+        for x in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            acc.push(x);
+        }
+        assert_eq!(
+            acc.stats(),
+            Some(WindowStats {
+                count: 8,
+                mean: 5.0,
+                var: 4.0,
+                min: 2.0,
+                max: 9.0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_welford_large_offset() {
+        // naive sum-of-squares loses all precision here
+        let mut acc = Welford::new();
+        for x in [4.0, 7.0, 13.0, 16.0] {
+            acc.push(1e9 + x);
+        }
+        let stats = acc.stats().unwrap();
+        assert_eq!(stats.mean, 1e9 + 10.0);
+        assert!((stats.var - 22.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_stats_over() {
+        let data: &[(Duration, f32)] =
+            &[(ms(10), 1.0), (ms(20), 3.0), (ms(30), 8.0), (ms(40), 4.0)];
+
+        // window covers the last three samples: 3, 8, 4
+        assert_eq!(data.mean_over(ms(20)), Some(5.0));
+        assert_eq!(data.min_max_over(ms(20)), Some((3.0, 8.0)));
+        assert_eq!(data.variance_over(ms(20)), Some(14.0 / 3.0));
+        assert_eq!(data.rate_over(ms(20)), Some(100.0));
+
+        // window covers all samples
+        assert_eq!(data.mean_over(ms(1000)), Some(4.0));
+        assert_eq!(data.variance_over(ms(1000)), Some(6.5));
+        assert_eq!(data.rate_over(ms(1000)), Some(100.0));
+
+        // window only covers the latest sample
+        assert_eq!(data.mean_over(ms(0)), Some(4.0));
+        assert_eq!(data.variance_over(ms(0)), Some(0.0));
+        assert_eq!(data.rate_over(ms(0)), None);
+    }
+
+    #[test]
+    fn test_stats_over_empty() {
+        let data: &[(Duration, f64)] = &[];
+        assert_eq!(data.stats_over(ms(100)), None);
+        assert_eq!(data.mean_over(ms(100)), None);
+        assert_eq!(data.min_max_over(ms(100)), None);
+        assert_eq!(data.variance_over(ms(100)), None);
+        assert_eq!(data.rate_over(ms(100)), None);
+    }
+}
@@ -0,0 +1,313 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::codelet::{
+    Codelet, CodeletInstance, DisabledRxPolicy, EnableCondition, Persist, Persistence,
+};
+use core::time::Duration;
+use std::sync::{atomic::AtomicBool, Arc};
+
+/// Error returned by [CodeletBuilder::build] if options are missing or conflict
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CodeletBuilderError {
+    #[error("codelet '{name}': no config was given")]
+    MissingConfig { name: String },
+
+    #[error("codelet '{name}': more than one enable condition was given")]
+    ConflictingEnable { name: String },
+
+    #[error("codelet '{name}': label '{key}' was set to '{first}' and to '{second}'")]
+    ConflictingLabel {
+        name: String,
+        key: String,
+        first: String,
+        second: String,
+    },
+
+    #[error("codelet '{name}': deadline must not be zero")]
+    ZeroDeadline { name: String },
+
+    #[error("codelet '{name}': deadline {deadline:?} is longer than the period hint {period:?}")]
+    DeadlineExceedsPeriod {
+        name: String,
+        deadline: Duration,
+        period: Duration,
+    },
+}
+
+/// Builds a [CodeletInstance] with all its options, see `Instantiate::builder` and
+/// `IntoInstance::into_builder`
+///
+/// ```
+/// use core::time::Duration;
+/// use nodo::prelude::*;
+///
+/// #[derive(Default)]
+/// struct Camera;
+///
+/// impl Codelet for Camera {
+///   type Status = DefaultStatus;
+///   type Config = u32;
+///   type Rx = ();
+///   type Tx = ();
+///   fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) { ((),()) }
+/// }
+///
+/// let camera = Camera::builder("front_camera")
+///     .config(30)
+///     .label("camera", "front")
+///     .deadline(Duration::from_millis(5))
+///     .start_after("driver")
+///     .build()
+///     .unwrap();
+/// ```
+#[must_use]
+pub struct CodeletBuilder<C: Codelet> {
+    name: String,
+    state: C,
+    config: Option<C::Config>,
+    labels: Vec<(String, Option<String>)>,
+    deadline: Option<Duration>,
+    start_after: Vec<String>,
+    period_hint: Option<Duration>,
+    enable: Vec<EnableCondition>,
+    disabled_rx_policy: DisabledRxPolicy,
+    persistence: Option<Persistence<C>>,
+}
+
+impl<C: Codelet> CodeletBuilder<C> {
+    pub fn new<S: Into<String>>(name: S, state: C) -> Self {
+        Self {
+            name: name.into(),
+            state,
+            config: None,
+            labels: Vec::new(),
+            deadline: None,
+            start_after: Vec::new(),
+            period_hint: None,
+            enable: Vec::new(),
+            disabled_rx_policy: DisabledRxPolicy::default(),
+            persistence: None,
+        }
+    }
+
+    /// Configuration of the codelet. Required.
+    pub fn config(mut self, config: C::Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Adds a `key=value` label which is shown in reports
+    pub fn label<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.labels.push((key.into(), Some(value.into())));
+        self
+    }
+
+    /// Adds a plain label which is shown in reports, e.g. [crate::codelet::RARELY_ACTIVE_LABEL]
+    pub fn tag<S: Into<String>>(mut self, label: S) -> Self {
+        self.labels.push((label.into(), None));
+        self
+    }
+
+    /// See `CodeletInstance::with_deadline`
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// See `CodeletInstance::with_start_after`
+    pub fn start_after<S: Into<String>>(mut self, schedule: S) -> Self {
+        self.start_after.push(schedule.into());
+        self
+    }
+
+    /// See `CodeletInstance::with_period_hint`
+    pub fn period_hint(mut self, period: Duration) -> Self {
+        self.period_hint = Some(period);
+        self
+    }
+
+    /// See `CodeletInstance::with_enable`
+    pub fn enable(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.enable.push(EnableCondition::Flag(flag));
+        self
+    }
+
+    /// See `CodeletInstance::with_enable_fn`
+    pub fn enable_fn<F>(mut self, f: F) -> Self
+    where
+        F: FnMut() -> bool + Send + 'static,
+    {
+        self.enable.push(EnableCondition::Fn(Box::new(f)));
+        self
+    }
+
+    /// See `CodeletInstance::with_disabled_rx_policy`
+    pub fn disabled_rx_policy(mut self, policy: DisabledRxPolicy) -> Self {
+        self.disabled_rx_policy = policy;
+        self
+    }
+
+    /// See `CodeletInstance::with_persistence`
+    pub fn persistence<S: Into<String>>(mut self, key: S) -> Self
+    where
+        C: Persist,
+    {
+        self.persistence = Some(Persistence::new(key.into()));
+        self
+    }
+
+    /// Validates all options and creates the instance
+    pub fn build(mut self) -> Result<CodeletInstance<C>, CodeletBuilderError> {
+        let name = self.name;
+
+        let Some(config) = self.config else {
+            return Err(CodeletBuilderError::MissingConfig { name });
+        };
+
+        if self.enable.len() > 1 {
+            return Err(CodeletBuilderError::ConflictingEnable { name });
+        }
+
+        for (i, (key, value)) in self.labels.iter().enumerate() {
+            let (Some(first), Some((_, Some(second)))) = (
+                value,
+                self.labels[i + 1..].iter().find(|(other, other_value)| {
+                    other == key && other_value.is_some() && other_value != value
+                }),
+            ) else {
+                continue;
+            };
+            return Err(CodeletBuilderError::ConflictingLabel {
+                name,
+                key: key.clone(),
+                first: first.clone(),
+                second: second.clone(),
+            });
+        }
+
+        if let Some(deadline) = self.deadline {
+            if deadline.is_zero() {
+                return Err(CodeletBuilderError::ZeroDeadline { name });
+            }
+            if let Some(period) = self.period_hint.filter(|&period| deadline > period) {
+                return Err(CodeletBuilderError::DeadlineExceedsPeriod {
+                    name,
+                    deadline,
+                    period,
+                });
+            }
+        }
+
+        let mut instance = CodeletInstance::new(name, self.state, config);
+        for label in self.labels.into_iter().map(|(key, value)| match value {
+            Some(value) => format!("{key}={value}"),
+            None => key,
+        }) {
+            if !instance.labels.contains(&label) {
+                instance.labels.push(label);
+            }
+        }
+        instance.deadline = self.deadline;
+        instance.start_after = self.start_after;
+        instance.period_hint = self.period_hint;
+        instance.enable = self.enable.pop();
+        instance.disabled_rx_policy = self.disabled_rx_policy;
+        instance.persistence = self.persistence;
+        Ok(instance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        codelet::{CodeletBuilderError, Instantiate},
+        prelude::*,
+    };
+    use core::time::Duration;
+    use std::sync::{atomic::AtomicBool, Arc};
+
+    #[derive(Default)]
+    struct Noop;
+
+    impl Codelet for Noop {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = ();
+        type Tx = ();
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            ((), ())
+        }
+    }
+
+    fn build_err(builder: crate::codelet::CodeletBuilder<Noop>) -> CodeletBuilderError {
+        match builder.build() {
+            Ok(mut instance) => {
+                instance.mark_unscheduled_ok();
+                panic!("expected an error")
+            }
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn test_builder_conflicts() {
+        let ms = Duration::from_millis;
+
+        assert_eq!(
+            build_err(Noop::builder("a")),
+            CodeletBuilderError::MissingConfig { name: "a".into() }
+        );
+        assert_eq!(
+            build_err(
+                Noop::builder("a")
+                    .config(())
+                    .enable(Arc::new(AtomicBool::new(true)))
+                    .enable_fn(|| true)
+            ),
+            CodeletBuilderError::ConflictingEnable { name: "a".into() }
+        );
+        assert_eq!(
+            build_err(
+                Noop::builder("a")
+                    .config(())
+                    .label("camera", "front")
+                    .label("camera", "rear")
+            ),
+            CodeletBuilderError::ConflictingLabel {
+                name: "a".into(),
+                key: "camera".into(),
+                first: "front".into(),
+                second: "rear".into()
+            }
+        );
+        assert_eq!(
+            build_err(Noop::builder("a").config(()).deadline(Duration::ZERO)),
+            CodeletBuilderError::ZeroDeadline { name: "a".into() }
+        );
+        assert_eq!(
+            build_err(
+                Noop::builder("a")
+                    .config(())
+                    .deadline(ms(20))
+                    .period_hint(ms(10))
+            ),
+            CodeletBuilderError::DeadlineExceedsPeriod {
+                name: "a".into(),
+                deadline: ms(20),
+                period: ms(10)
+            }
+        );
+
+        // a deadline without a period hint and repeated labels are fine
+        let mut instance = Noop::builder("a")
+            .config(())
+            .label("camera", "front")
+            .label("camera", "front")
+            .deadline(ms(20))
+            .build()
+            .unwrap();
+        instance.mark_unscheduled_ok();
+        assert_eq!(instance.labels(), ["camera=front"]);
+    }
+}
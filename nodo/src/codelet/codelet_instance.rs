@@ -1,15 +1,33 @@
 // Copyright 2023 by David Weikersdorfer. All rights reserved.
 
+#[cfg(feature = "scratch")]
+use crate::codelet::ScratchArena;
+#[cfg(any(debug_assertions, feature = "step-lints"))]
+use crate::codelet::{is_enabled_by_env, StepLints};
 use crate::{
-    channels::{FlushResult, RxBundle, SyncResult, TxBundle},
-    codelet::{Codelet, CodeletStatus, Context, Lifecycle, TaskClocks, Transition},
+    channels::{
+        Backlog, ChannelContract, ChannelUid, FlushResult, RxBundle, SyncResult, TapPoint, TxBundle,
+    },
+    codelet::{
+        Codelet, CodeletStatus, Context, FailurePlan, Lifecycle, Persist, PersistedState,
+        Persistence, ResourceRegistry, ScopedWorkers, StepLint, TaskClocks, Transition,
+        DEFAULT_SCOPED_WORKER_JOIN_TIMEOUT,
+    },
 };
-use eyre::Result;
+use core::time::Duration;
+use eyre::{eyre, Result};
 use nodo_core::*;
 use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
 
 /// Unique identifier of a worker (i.e. thread)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct WorkerId(pub u32);
 
 impl WorkerId {
@@ -17,18 +35,79 @@ impl WorkerId {
 }
 
 /// Unique identifier of a nodelet running in a worker
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct NodeletId(pub WorkerId, pub u32);
 
 impl NodeletId {
     pub const INVALID: Self = NodeletId(WorkerId::INVALID, u32::MAX);
 }
 
+/// Name and connection status of a channel endpoint of a codelet instance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EndpointInfo {
+    pub name: Cow<'static, str>,
+    pub is_connected: bool,
+
+    /// Contract promised by a TX or expected by an RX endpoint
+    pub contract: Option<ChannelContract>,
+
+    /// Hash of the message type if known by the endpoint, see `message_type_hash`
+    #[serde(default)]
+    pub type_hash: Option<u64>,
+
+    /// Stable identifier assigned when the instance is registered in the manifold
+    #[serde(default)]
+    pub uid: Option<ChannelUid>,
+}
+
+/// Condition evaluated before every step to decide if a codelet is executed
+pub enum EnableCondition {
+    /// The codelet is enabled while the flag is set
+    Flag(Arc<AtomicBool>),
+
+    /// The codelet is enabled while the function returns true. It is evaluated on the worker
+    /// thread.
+    Fn(Box<dyn FnMut() -> bool + Send>),
+}
+
+impl EnableCondition {
+    fn eval(&mut self) -> bool {
+        match self {
+            EnableCondition::Flag(flag) => flag.load(Ordering::Relaxed),
+            EnableCondition::Fn(f) => f(),
+        }
+    }
+}
+
+/// How RX channels of a disabled codelet are handled
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DisabledRxPolicy {
+    /// RX channels are synced every step as if the codelet was running. Messages are discarded
+    /// according to the retention policy of each channel.
+    #[default]
+    DropWhileDisabled,
+
+    /// RX channels are not synced. Messages accumulate in the channel according to its overflow
+    /// policy and are received once the codelet is enabled again.
+    HoldWhileDisabled,
+}
+
+/// Label for codelets which are legitimately skipped most of the time, e.g. because they only
+/// react to rare events. They are not reported as suspected inactive by the runtime.
+pub const RARELY_ACTIVE_LABEL: &str = "rarely_active";
+
+/// Maximum number of start diagnostics kept per codelet, see
+/// [CodeletInstance::start_diagnostics]
+pub const MAX_START_DIAGNOSTICS: usize = 16;
+
+/// Start diagnostics longer than this many characters are truncated
+pub const MAX_START_DIAGNOSTIC_LEN: usize = 256;
+
 /// Named instance of a codelet with configuration and channel bundels
 pub struct CodeletInstance<C: Codelet> {
     pub id: NodeletId,
 
-    pub name: String,
+    pub name: Name,
     pub state: C,
     pub config: C::Config,
     pub rx: C::Rx,
@@ -36,18 +115,63 @@ pub struct CodeletInstance<C: Codelet> {
 
     pub(crate) clocks: Option<TaskClocks>,
     pub(crate) is_scheduled: bool,
+    pub(crate) is_unscheduled_ok: bool,
+    pub(crate) is_warmup: bool,
+    pub(crate) is_dry_run: bool,
+    pub(crate) enable: Option<EnableCondition>,
+    pub(crate) disabled_rx_policy: DisabledRxPolicy,
+    pub(crate) is_disabled: bool,
     pub(crate) rx_sync_results: Vec<SyncResult>,
     pub(crate) tx_flush_results: Vec<FlushResult>,
     pub(crate) status: Option<C::Status>,
+    pub(crate) skip_reason: Option<SkipReason>,
+    pub(crate) persistence: Option<Persistence<C>>,
+    pub(crate) labels: Vec<String>,
+    pub(crate) deadline: Option<Duration>,
+    pub(crate) failure_plan: Option<FailurePlan>,
+    pub(crate) start_after: Vec<String>,
+    pub(crate) period_hint: Option<Duration>,
+    pub(crate) start_diagnostics: Vec<String>,
+    pub(crate) start_diagnostics_omitted: usize,
+    pub(crate) progress: Option<(f32, String)>,
+    pub(crate) scoped_workers: ScopedWorkers,
+    pub(crate) scoped_worker_join_timeout: Duration,
+    pub(crate) resources: Arc<ResourceRegistry>,
+    pub(crate) has_acquired_resources: bool,
+    #[cfg(feature = "scratch")]
+    pub(crate) scratch: ScratchArena,
+    #[cfg(any(debug_assertions, feature = "step-lints"))]
+    pub(crate) step_lints: Option<StepLints>,
 }
 
 impl<C: Codelet> Drop for CodeletInstance<C> {
     fn drop(&mut self) {
-        if !self.is_scheduled {
-            log::warn!(
-                "Codelet instance `{}` was created and destroyed without every being scheduled",
-                self.name
-            );
+        if let Some((level, msg)) = self.unscheduled_drop_diagnostic() {
+            log::log!(level, "{msg}");
+        }
+
+        // Workers are joined when the codelet stops. Workers still running at teardown would
+        // outlive the codelet.
+        if self.scoped_workers.len() > 0 {
+            let running = self.scoped_workers.running_count();
+            let error = match self
+                .scoped_workers
+                .stop_and_join(&self.name, self.scoped_worker_join_timeout)
+            {
+                Err(err) => Some(err.to_string()),
+                Ok(()) if running > 0 => Some(format!(
+                    "codelet '{}': {running} scoped workers were still running",
+                    self.name
+                )),
+                Ok(()) => None,
+            };
+            if let Some(err) = error {
+                if cfg!(debug_assertions) && !std::thread::panicking() {
+                    panic!("scoped workers failed at teardown: {err}");
+                } else {
+                    log::error!("scoped workers failed at teardown: {err}");
+                }
+            }
         }
     }
 }
@@ -60,16 +184,40 @@ impl<C: Codelet> CodeletInstance<C> {
         let tx_count = tx.len();
         Self {
             id: NodeletId::INVALID,
-            name: name.into(),
+            name: Name::from(name.into()),
             state,
             config,
             rx,
             tx,
             clocks: None,
             is_scheduled: false,
+            is_unscheduled_ok: false,
+            is_warmup: false,
+            is_dry_run: false,
+            enable: None,
+            disabled_rx_policy: DisabledRxPolicy::default(),
+            is_disabled: false,
             rx_sync_results: vec![SyncResult::ZERO; rx_count],
             tx_flush_results: vec![FlushResult::ZERO; tx_count],
             status: None,
+            skip_reason: None,
+            persistence: None,
+            labels: Vec::new(),
+            deadline: None,
+            failure_plan: None,
+            start_after: Vec::new(),
+            period_hint: None,
+            start_diagnostics: Vec::new(),
+            start_diagnostics_omitted: 0,
+            progress: None,
+            scoped_workers: ScopedWorkers::default(),
+            scoped_worker_join_timeout: DEFAULT_SCOPED_WORKER_JOIN_TIMEOUT,
+            resources: Arc::default(),
+            has_acquired_resources: false,
+            #[cfg(feature = "scratch")]
+            scratch: ScratchArena::with_capacity(0),
+            #[cfg(any(debug_assertions, feature = "step-lints"))]
+            step_lints: is_enabled_by_env().then(StepLints::default),
         }
     }
 
@@ -85,52 +233,433 @@ impl<C: Codelet> CodeletInstance<C> {
         self
     }
 
+    /// Executes the codelet only while the given flag is set. Start and stop are not affected.
+    #[must_use]
+    pub fn with_enable(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.enable = Some(EnableCondition::Flag(flag));
+        self
+    }
+
+    /// Executes the codelet only while the given function returns true. The function is evaluated
+    /// on the worker thread before every step. Start and stop are not affected.
+    #[must_use]
+    pub fn with_enable_fn<F>(mut self, f: F) -> Self
+    where
+        F: FnMut() -> bool + Send + 'static,
+    {
+        self.enable = Some(EnableCondition::Fn(Box::new(f)));
+        self
+    }
+
+    /// Sets how RX channels are handled while the codelet is disabled
+    #[must_use]
+    pub fn with_disabled_rx_policy(mut self, policy: DisabledRxPolicy) -> Self {
+        self.disabled_rx_policy = policy;
+        self
+    }
+
+    /// Annotates the instance with a label which is shown in reports, e.g. [RARELY_ACTIVE_LABEL]
+    #[must_use]
+    pub fn with_label<S: Into<String>>(mut self, label: S) -> Self {
+        self.labels.push(label.into());
+        self
+    }
+
+    /// Labels added with `with_label`
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    /// Steps which take longer than the deadline are counted as deadline misses in statistics
+    #[must_use]
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Deadline set with `with_deadline`
+    pub fn deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+
+    /// Injects failures into start, step and stop for chaos testing, see [FailurePlan]
+    ///
+    /// While a plan is armed the codelet is labeled with [super::FAILURE_INJECTION_LABEL].
+    #[must_use]
+    pub fn with_failure_injection(mut self, plan: FailurePlan) -> Self {
+        self.failure_plan = Some(plan);
+        self
+    }
+
+    /// Maximum time to wait for scoped workers to finish after the codelet stopped, see
+    /// `Context::spawn_scoped`
+    #[must_use]
+    pub fn with_scoped_worker_join_timeout(mut self, timeout: Duration) -> Self {
+        self.scoped_worker_join_timeout = timeout;
+        self
+    }
+
+    /// Pre-allocates the scratch arena with the given number of bytes, see `Context::scratch`.
+    /// Use the peak usage reported in the statistics to size it.
+    #[cfg(feature = "scratch")]
+    #[must_use]
+    pub fn with_scratch_capacity(mut self, bytes: usize) -> Self {
+        self.scratch = ScratchArena::with_capacity(bytes);
+        self
+    }
+
+    /// Maximum number of bytes allocated in the scratch arena during a single transition
+    #[cfg(feature = "scratch")]
+    pub fn scratch_peak_bytes(&self) -> usize {
+        self.scratch.peak_bytes()
+    }
+
+    /// Number of scoped workers which were spawned but not joined yet
+    pub fn scoped_worker_count(&self) -> usize {
+        self.scoped_workers.len()
+    }
+
+    /// Enables checks for common mistakes in `step` (builder style), see [StepLint]
+    ///
+    /// Each lint is reported at most once with a warning. Lints are only available in debug
+    /// builds or with the `step-lints` feature and this call has no effect otherwise. Setting the
+    /// environment variable [super::STEP_LINTS_ENV] to "1" enables lints for all codelets.
+    #[must_use]
+    pub fn with_step_lints(mut self) -> Self {
+        #[cfg(any(debug_assertions, feature = "step-lints"))]
+        self.step_lints.get_or_insert_with(StepLints::default);
+        self
+    }
+
+    /// Lints which fired so far
+    pub fn step_lints(&self) -> &[StepLint] {
+        #[cfg(any(debug_assertions, feature = "step-lints"))]
+        if let Some(lints) = self.step_lints.as_ref() {
+            return lints.fired();
+        }
+        &[]
+    }
+
+    /// Sets the schedule period against which step durations are checked by step lints
+    pub(crate) fn set_step_lint_period(&mut self, _period: Option<Duration>) {
+        #[cfg(any(debug_assertions, feature = "step-lints"))]
+        if let Some(lints) = self.step_lints.as_mut() {
+            lints.set_period(_period);
+        }
+    }
+
+    /// Problems detected during the last start, e.g. unconnected channels, a failed start or
+    /// warnings given with `Context::warn`. Kept until the next start.
+    pub fn start_diagnostics(&self) -> &[String] {
+        &self.start_diagnostics
+    }
+
+    /// Adds a start diagnostic while keeping their number and length bounded
+    fn push_start_diagnostic(&mut self, mut msg: String) {
+        if let Some((end, _)) = msg.char_indices().nth(MAX_START_DIAGNOSTIC_LEN) {
+            msg.truncate(end);
+            msg.push_str("..");
+        }
+
+        if self.start_diagnostics.len() < MAX_START_DIAGNOSTICS {
+            self.start_diagnostics.push(msg);
+        } else {
+            // the last entry is replaced by a note on how many were omitted
+            self.start_diagnostics_omitted += 1;
+            *self.start_diagnostics.last_mut().unwrap() = format!(
+                "{} more diagnostics omitted",
+                self.start_diagnostics_omitted + 1
+            );
+        }
+    }
+
+    /// Progress reported with `Context::set_progress` as fraction and detail
+    pub fn progress(&self) -> Option<(f32, &str)> {
+        self.progress
+            .as_ref()
+            .map(|(fraction, detail)| (*fraction, detail.as_str()))
+    }
+
+    /// Keeps progress reported during a transition. Completed progress is cleared by the next
+    /// step which does not report progress.
+    fn take_progress(&mut self, progress: Option<(f32, Option<String>)>) {
+        match progress {
+            Some((fraction, detail)) => {
+                self.progress = Some((fraction, detail.unwrap_or_default()));
+            }
+            None => {
+                if self.progress.as_ref().is_some_and(|(f, _)| *f >= 1.0) {
+                    self.progress = None;
+                }
+            }
+        }
+    }
+
+    /// Logs warnings given by the codelet with `Context::warn` and keeps those given during start
+    fn take_warnings(&mut self, transition: Transition, warnings: Vec<String>) {
+        for warning in warnings {
+            log::warn!("codelet '{}': {warning}", self.name);
+            if transition == Transition::Start {
+                self.push_start_diagnostic(format!("warning: {warning}"));
+            }
+        }
+    }
+
+    /// Schedule hint: the schedule of this instance depends on the schedule with the given name,
+    /// see `ScheduleBuilder::with_start_after`
+    #[must_use]
+    pub fn with_start_after<S: Into<String>>(mut self, schedule: S) -> Self {
+        self.start_after.push(schedule.into());
+        self
+    }
+
+    /// Schedule hint: the period at which the codelet expects to be stepped. Used as period of
+    /// the schedule if the schedule does not specify one.
+    #[must_use]
+    pub fn with_period_hint(mut self, period: Duration) -> Self {
+        self.period_hint = Some(period);
+        self
+    }
+
+    /// Saves the codelet state under the given key when the application stops and restores it
+    /// before the codelet is started the next time. Keys must be unique within the application.
+    #[must_use]
+    pub fn with_persistence<S: Into<String>>(mut self, key: S) -> Self
+    where
+        C: Persist,
+    {
+        self.persistence = Some(Persistence::new(key.into()));
+        self
+    }
+
+    /// Key under which the codelet state is persisted, see `with_persistence`
+    pub fn persistence_key(&self) -> Option<&str> {
+        self.persistence.as_ref().map(|p| p.key.as_str())
+    }
+
+    /// Saves the codelet state if persistence is enabled
+    pub fn save_state(&self) -> Result<Option<PersistedState>> {
+        self.persistence
+            .as_ref()
+            .map(|p| p.save(&self.state))
+            .transpose()
+    }
+
+    /// Restores a saved codelet state. Does nothing if persistence is not enabled.
+    pub fn restore_state(&mut self, state: &PersistedState) -> Result<()> {
+        match self.persistence.as_ref() {
+            Some(p) => p.restore(&mut self.state, state),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns true if the codelet was disabled for the most recent step
+    pub fn is_disabled(&self) -> bool {
+        self.is_disabled
+    }
+
+    /// Disables the diagnostic which is logged when the instance is dropped without ever being
+    /// scheduled. Use this for instances which are discarded deliberately.
+    pub fn mark_unscheduled_ok(&mut self) {
+        self.is_unscheduled_ok = true;
+    }
+
+    /// Names of all connected channels like `rx.in` or `tx.out`
+    pub fn connected_channel_names(&self) -> Vec<String> {
+        let rx_cc = self.rx.check_connection();
+        let tx_cc = self.tx.check_connection();
+        (0..self.rx.len())
+            .filter(|&i| rx_cc.is_connected(i))
+            .map(|i| format!("rx.{}", self.rx.name(i)))
+            .chain(
+                (0..self.tx.len())
+                    .filter(|&i| tx_cc.is_connected(i))
+                    .map(|i| format!("tx.{}", self.tx.name(i))),
+            )
+            .collect()
+    }
+
+    /// Removes the messages still queued in RX channels together with the channel names
+    pub fn take_rx_backlogs(&mut self) -> Vec<(Cow<'static, str>, Backlog)>
+    where
+        C::Rx: 'static,
+    {
+        (0..self.rx.len())
+            .filter_map(|i| Some((self.rx.name(i), self.rx.take_backlog(i)?)))
+            .collect()
+    }
+
+    /// Back stages of all RX channels to attach snoops, see [TapPoint]
+    pub fn rx_tap_points(&self) -> Vec<Option<Arc<dyn TapPoint>>>
+    where
+        C::Rx: 'static,
+    {
+        (0..self.rx.len()).map(|i| self.rx.tap_point(i)).collect()
+    }
+
+    /// Names and connection status of all RX channels
+    pub fn rx_endpoints(&self) -> Vec<EndpointInfo> {
+        let cc = self.rx.check_connection();
+        (0..self.rx.len())
+            .map(|i| EndpointInfo {
+                name: self.rx.name(i),
+                is_connected: cc.is_connected(i),
+                contract: self.rx.contract(i),
+                type_hash: self.rx.message_type_hash(i),
+                uid: None,
+            })
+            .collect()
+    }
+
+    /// Names and connection status of all TX channels
+    pub fn tx_endpoints(&self) -> Vec<EndpointInfo> {
+        let cc = self.tx.check_connection();
+        (0..self.tx.len())
+            .map(|i| EndpointInfo {
+                name: self.tx.name(i),
+                is_connected: cc.is_connected(i),
+                contract: self.tx.contract(i),
+                type_hash: self.tx.message_type_hash(i),
+                uid: None,
+            })
+            .collect()
+    }
+
+    /// Identifier of the i-th RX channel once the instance is added to the given schedule and
+    /// sequence. It is the same as the one assigned in the manifold.
+    pub fn rx_channel_uid(&self, schedule: &str, sequence: &str, index: usize) -> ChannelUid {
+        ChannelUid::new(
+            schedule,
+            sequence,
+            &self.name,
+            &format!("rx.{}", self.rx.name(index)),
+        )
+    }
+
+    /// Identifier of the i-th TX channel once the instance is added to the given schedule and
+    /// sequence. It is the same as the one assigned in the manifold.
+    pub fn tx_channel_uid(&self, schedule: &str, sequence: &str, index: usize) -> ChannelUid {
+        ChannelUid::new(
+            schedule,
+            sequence,
+            &self.name,
+            &format!("tx.{}", self.tx.name(index)),
+        )
+    }
+
+    /// Message logged when an unscheduled instance is dropped
+    ///
+    /// Dropping an instance with connected channels is an error as the rest of the graph will
+    /// silently miss its messages.
+    fn unscheduled_drop_diagnostic(&self) -> Option<(log::Level, String)> {
+        if self.is_scheduled || self.is_unscheduled_ok {
+            return None;
+        }
+
+        let connected = self.connected_channel_names();
+        if connected.is_empty() {
+            Some((
+                log::Level::Warn,
+                format!(
+                    "Codelet instance `{}` was created and destroyed without every being scheduled",
+                    self.name
+                ),
+            ))
+        } else {
+            Some((
+                log::Level::Error,
+                format!(
+                    "Codelet instance `{}` was connected but destroyed without every being \
+                     scheduled. Connected channels: {}",
+                    self.name,
+                    connected.join(", ")
+                ),
+            ))
+        }
+    }
+
     pub fn start(&mut self) -> Result<C::Status> {
         profiling::scope!(&format!("{}_start", self.name));
+        #[cfg(feature = "tracing")]
+        let _span = self.span(Transition::Start).entered();
 
         log::trace!("'{}' start begin", self.name);
 
+        self.start_diagnostics.clear();
+        self.start_diagnostics_omitted = 0;
+
         let cc = self.rx.check_connection();
         if !cc.is_fully_connected() {
+            let channels = cc
+                .list_unconnected()
+                .iter()
+                .map(|&i| format!("[{i}] {}", self.rx.name(i)))
+                .collect::<Vec<String>>()
+                .join(", ");
             log::warn!(
-                "codelet '{}' (type={}) has unconnected RX channels: {}",
+                "codelet '{}' (type={}) has unconnected RX channels: {channels}",
                 self.name,
                 self.type_name(),
-                cc.list_unconnected()
-                    .iter()
-                    .map(|&i| format!("[{i}] {}", self.rx.name(i)))
-                    .collect::<Vec<String>>()
-                    .join(", ")
             );
+            self.push_start_diagnostic(format!("unconnected RX channels: {channels}"));
         }
 
         let cc = self.tx.check_connection();
         if !cc.is_fully_connected() {
+            let channels = cc
+                .list_unconnected()
+                .iter()
+                .map(|&i| format!("[{i}] {}", self.tx.name(i)))
+                .collect::<Vec<String>>()
+                .join(", ");
             log::warn!(
-                "codelet '{}' (type={}) has unconnected TX channels: {}",
+                "codelet '{}' (type={}) has unconnected TX channels: {channels}",
                 self.name,
                 self.type_name(),
-                cc.list_unconnected()
-                    .iter()
-                    .map(|&i| format!("[{i}] {}", self.tx.name(i)))
-                    .collect::<Vec<String>>()
-                    .join(", ")
             );
+            self.push_start_diagnostic(format!("unconnected TX channels: {channels}"));
+        }
+
+        let result = self.start_impl();
+        if let Err(err) = result.as_ref() {
+            self.push_start_diagnostic(format!("start failed: {err}"));
+        }
+        result
+    }
+
+    fn start_impl(&mut self) -> Result<C::Status> {
+        if !self.has_acquired_resources {
+            self.state
+                .acquire_resources(&self.resources)
+                .map_err(|err| {
+                    eyre!("codelet '{}' failed to acquire resources: {err}", self.name)
+                })?;
+            self.has_acquired_resources = true;
         }
 
         self.sync()?;
 
         self.clocks.as_mut().unwrap().on_codelet_start();
 
-        let status = self.state.start(
-            &Context {
-                clock: &self.clocks.as_ref().unwrap().deprecated_task_clock,
-                clocks: &self.clocks.as_ref().unwrap(),
-                config: &self.config,
-            },
-            &mut self.rx,
-            &mut self.tx,
-        )?;
+        let cx = Context {
+            clock: &self.clocks.as_ref().unwrap().deprecated_task_clock,
+            clocks: self.clocks.as_ref().unwrap(),
+            config: &self.config,
+            is_warmup: self.is_warmup,
+            is_dry_run: self.is_dry_run,
+            reports: Mutex::default(),
+            scoped_workers: &self.scoped_workers,
+            #[cfg(feature = "scratch")]
+            scratch: &self.scratch,
+        };
+        let result = self.state.start(&cx, &mut self.rx, &mut self.tx);
+        let reports = cx.into_reports();
+        self.skip_reason = reports.skip_reason;
+        self.take_warnings(Transition::Start, reports.warnings);
+        self.take_progress(reports.progress);
+        #[cfg(feature = "scratch")]
+        self.scratch.reset();
+        let status = result?;
 
         self.flush()?;
 
@@ -140,21 +669,41 @@ impl<C: Codelet> CodeletInstance<C> {
 
     pub fn stop(&mut self) -> Result<C::Status> {
         profiling::scope!(&format!("{}_stop", self.name));
+        #[cfg(feature = "tracing")]
+        let _span = self.span(Transition::Stop).entered();
         log::trace!("'{}' stop begin", self.name);
 
         self.sync()?;
 
         self.clocks.as_mut().unwrap().on_codelet_stop();
 
-        let status = self.state.stop(
-            &Context {
-                clock: &self.clocks.as_ref().unwrap().deprecated_task_clock,
-                clocks: &self.clocks.as_ref().unwrap(),
-                config: &self.config,
-            },
-            &mut self.rx,
-            &mut self.tx,
-        )?;
+        self.scoped_workers.request_stop();
+
+        let cx = Context {
+            clock: &self.clocks.as_ref().unwrap().deprecated_task_clock,
+            clocks: self.clocks.as_ref().unwrap(),
+            config: &self.config,
+            is_warmup: self.is_warmup,
+            is_dry_run: self.is_dry_run,
+            reports: Mutex::default(),
+            scoped_workers: &self.scoped_workers,
+            #[cfg(feature = "scratch")]
+            scratch: &self.scratch,
+        };
+        let result = self.state.stop(&cx, &mut self.rx, &mut self.tx);
+        let reports = cx.into_reports();
+        self.skip_reason = reports.skip_reason;
+        self.take_warnings(Transition::Stop, reports.warnings);
+        self.take_progress(reports.progress);
+        #[cfg(feature = "scratch")]
+        self.scratch.reset();
+
+        // workers are joined even if stop failed
+        let joined = self
+            .scoped_workers
+            .stop_and_join(&self.name, self.scoped_worker_join_timeout);
+        let status = result?;
+        joined?;
 
         self.flush()?;
 
@@ -164,28 +713,86 @@ impl<C: Codelet> CodeletInstance<C> {
 
     pub fn step(&mut self) -> Result<C::Status> {
         profiling::scope!(&format!("{}_step", self.name));
+        #[cfg(feature = "tracing")]
+        let _span = self.span(Transition::Step).entered();
         log::trace!("'{}' step begin", self.name);
 
         self.sync()?;
 
         self.clocks.as_mut().unwrap().on_codelet_step();
 
-        let status = self.state.step(
-            &Context {
-                clock: &self.clocks.as_ref().unwrap().deprecated_task_clock,
-                clocks: &self.clocks.as_ref().unwrap(),
-                config: &self.config,
-            },
-            &mut self.rx,
-            &mut self.tx,
-        )?;
+        let cx = Context {
+            clock: &self.clocks.as_ref().unwrap().deprecated_task_clock,
+            clocks: self.clocks.as_ref().unwrap(),
+            config: &self.config,
+            is_warmup: self.is_warmup,
+            is_dry_run: self.is_dry_run,
+            reports: Mutex::default(),
+            scoped_workers: &self.scoped_workers,
+            #[cfg(feature = "scratch")]
+            scratch: &self.scratch,
+        };
+        #[cfg(any(debug_assertions, feature = "step-lints"))]
+        let step_begin = self.step_lints.as_mut().map(|lints| {
+            lints.before_step(&self.rx);
+            std::time::Instant::now()
+        });
+        let result = self.state.step(&cx, &mut self.rx, &mut self.tx);
+        #[cfg(any(debug_assertions, feature = "step-lints"))]
+        let step_duration = step_begin.map(|begin| begin.elapsed());
+        let reports = cx.into_reports();
+        self.skip_reason = reports.skip_reason;
+        self.take_warnings(Transition::Step, reports.warnings);
+        self.take_progress(reports.progress);
+        #[cfg(feature = "scratch")]
+        self.scratch.reset();
+        let status = result?;
 
         self.flush()?;
 
+        #[cfg(any(debug_assertions, feature = "step-lints"))]
+        if let (Some(lints), Some(duration)) = (self.step_lints.as_mut(), step_duration) {
+            lints.after_step(
+                &self.name,
+                &self.rx,
+                &self.tx,
+                &self.tx_flush_results,
+                duration,
+            );
+        }
+
         log::trace!("'{}' step end ({})", self.name, status.label());
         Ok(status)
     }
 
+    /// Span covering a single transition of the codelet
+    #[cfg(feature = "tracing")]
+    fn span(&self, transition: Transition) -> tracing::Span {
+        tracing::debug_span!(
+            "codelet",
+            codelet = %self.name,
+            type_name = self.type_name(),
+            transition = ?transition,
+            id = ?self.id,
+            cycle = self.clocks.as_ref().map_or(0, |clocks| clocks.cycle_index()),
+        )
+    }
+
+    /// Evaluates the enable condition and handles RX channels if the codelet is disabled. Returns
+    /// true if the codelet shall be stepped.
+    pub(crate) fn check_enabled(&mut self) -> Result<bool> {
+        self.is_disabled = !self.enable.as_mut().is_none_or(|cond| cond.eval());
+
+        if self.is_disabled {
+            log::trace!("'{}' disabled", self.name);
+            if self.disabled_rx_policy == DisabledRxPolicy::DropWhileDisabled {
+                self.sync()?;
+            }
+        }
+
+        Ok(!self.is_disabled)
+    }
+
     pub fn pause(&mut self) -> Result<C::Status> {
         self.state.pause()
     }
@@ -194,12 +801,34 @@ impl<C: Codelet> CodeletInstance<C> {
         self.state.resume()
     }
 
+    /// Returns true if the codelet has RX channels and all of them were closed as of the last sync
+    /// and no messages are left for the codelet to consume
+    pub fn is_rx_closed(&self) -> bool {
+        !self.rx_sync_results.is_empty()
+            && self.rx_sync_results.iter().all(|r| r.closed)
+            && (0..self.rx.len()).all(|i| self.rx.queue_len(i).unwrap_or(0) == 0)
+    }
+
     fn sync(&mut self) -> Result<()> {
         // For some codelets the TX channel count might change dynamically
         self.rx_sync_results.resize(self.rx.len(), SyncResult::ZERO);
 
         self.rx.sync_all(self.rx_sync_results.as_mut_slice());
 
+        #[cfg(feature = "tracing")]
+        for (i, result) in self.rx_sync_results.iter().enumerate() {
+            tracing::trace!(
+                target: "nodo::channels",
+                channel = %self.rx.name(i),
+                received = result.received,
+                forgotten = result.forgotten,
+                dropped = result.dropped,
+                closed = result.closed,
+                residency = ?result.residency,
+                "rx sync"
+            );
+        }
+
         for result in self.rx_sync_results.iter() {
             if result.enforce_empty_violation {
                 return Err(eyre!("'{}': sync error (EnforceEmpty violated)", self.name,));
@@ -216,6 +845,19 @@ impl<C: Codelet> CodeletInstance<C> {
 
         self.tx.flush_all(self.tx_flush_results.as_mut_slice());
 
+        #[cfg(feature = "tracing")]
+        for (i, result) in self.tx_flush_results.iter().enumerate() {
+            tracing::trace!(
+                target: "nodo::channels",
+                channel = %self.tx.name(i),
+                available = result.available,
+                cloned = result.cloned,
+                published = result.published,
+                error = %result.error_indicator,
+                "tx flush"
+            );
+        }
+
         for result in self.tx_flush_results.iter() {
             if result.error_indicator.is_err() {
                 return Err(eyre!(
@@ -232,6 +874,7 @@ impl<C: Codelet> CodeletInstance<C> {
 
 impl<C: Codelet> Lifecycle for CodeletInstance<C> {
     fn cycle(&mut self, transition: Transition) -> Result<DefaultStatus> {
+        self.skip_reason = None;
         let status = match transition {
             Transition::Start => self.start(),
             Transition::Step => self.step(),
@@ -240,7 +883,278 @@ impl<C: Codelet> Lifecycle for CodeletInstance<C> {
             Transition::Resume => self.resume(),
         }?;
         let simplified_status = status.as_default_status();
+        if simplified_status != DefaultStatus::Skipped {
+            self.skip_reason = None;
+        }
         self.status = Some(status);
         Ok(simplified_status)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    struct Relay;
+
+    impl Codelet for Relay {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = DoubleBufferRx<u32>;
+        type Tx = DoubleBufferTx<u32>;
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            (DoubleBufferRx::new_auto_size(), DoubleBufferTx::new(1))
+        }
+    }
+
+    #[test]
+    fn test_unscheduled_drop_diagnostic() {
+        let mut a = Relay.into_instance("a", ());
+        let mut b = Relay.into_instance("b", ());
+        let mut c = Relay.into_instance("c", ());
+
+        a.tx.connect(&mut b.rx).unwrap();
+
+        let (level, msg) = a.unscheduled_drop_diagnostic().unwrap();
+        assert_eq!(level, log::Level::Error);
+        assert!(msg.contains("tx.out"));
+        assert_eq!(b.connected_channel_names(), vec!["rx.in".to_string()]);
+
+        let (level, _) = c.unscheduled_drop_diagnostic().unwrap();
+        assert_eq!(level, log::Level::Warn);
+
+        for x in [&mut a, &mut b, &mut c] {
+            x.mark_unscheduled_ok();
+            assert_eq!(x.unscheduled_drop_diagnostic(), None);
+        }
+    }
+
+    /// Reports warnings and skips from worker threads which share the context
+    #[cfg(not(feature = "scratch"))]
+    struct Parallel;
+
+    #[cfg(not(feature = "scratch"))]
+    impl Codelet for Parallel {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = ();
+        type Tx = ();
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            ((), ())
+        }
+
+        fn start(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            std::thread::scope(|s| {
+                for i in 0..4 {
+                    s.spawn(move || cx.warn(format!("worker {i}")));
+                }
+            });
+            SUCCESS
+        }
+
+        fn step(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            std::thread::scope(|s| {
+                s.spawn(|| cx.skip_because(SkipReason::Throttled))
+                    .join()
+                    .unwrap()
+            })
+        }
+    }
+
+    #[cfg(not(feature = "scratch"))]
+    #[test]
+    fn test_context_shared_with_threads() {
+        use crate::codelet::{Clocks, TaskClocks};
+
+        let mut instance = Parallel.into_instance("parallel", ());
+        instance.clocks = Some(TaskClocks::from(Clocks::new()));
+
+        instance.start().unwrap();
+        let mut diagnostics = instance.start_diagnostics().to_vec();
+        diagnostics.sort();
+        assert_eq!(
+            diagnostics,
+            (0..4)
+                .map(|i| format!("warning: worker {i}"))
+                .collect::<Vec<_>>()
+        );
+
+        instance.step().unwrap();
+        assert_eq!(instance.skip_reason, Some(SkipReason::Throttled));
+    }
+
+    /// Reports the next scripted progress value every step
+    struct Progressing {
+        script: Vec<Option<f32>>,
+    }
+
+    impl Codelet for Progressing {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = ();
+        type Tx = ();
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            ((), ())
+        }
+
+        fn step(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            if let Some(fraction) = self.script.remove(0) {
+                cx.set_progress(fraction, Some(format!("at {fraction}")));
+            }
+            SUCCESS
+        }
+    }
+
+    #[test]
+    fn test_progress() {
+        use crate::codelet::{Clocks, TaskClocks};
+
+        let mut instance = Progressing {
+            script: vec![Some(0.5), None, Some(-1.0), Some(f32::NAN), Some(1.5), None],
+        }
+        .into_instance("progressing", ());
+        instance.clocks = Some(TaskClocks::from(Clocks::new()));
+
+        instance.start().unwrap();
+        assert_eq!(instance.progress(), None);
+
+        instance.step().unwrap();
+        assert_eq!(instance.progress(), Some((0.5, "at 0.5")));
+
+        // incomplete progress is kept when a step does not report progress
+        instance.step().unwrap();
+        assert_eq!(instance.progress(), Some((0.5, "at 0.5")));
+
+        // values outside of [0, 1] are clamped
+        instance.step().unwrap();
+        assert_eq!(instance.progress(), Some((0.0, "at -1")));
+        instance.step().unwrap();
+        assert_eq!(instance.progress(), Some((0.0, "at NaN")));
+        instance.step().unwrap();
+        assert_eq!(instance.progress(), Some((1.0, "at 1.5")));
+
+        // completed progress is cleared by the next step
+        instance.step().unwrap();
+        assert_eq!(instance.progress(), None);
+    }
+
+    #[cfg(feature = "tracing")]
+    mod tracing_capture {
+        use std::{
+            fmt,
+            sync::{Arc, Mutex},
+        };
+        use tracing::{
+            field::{Field, Visit},
+            span, Event, Metadata, Subscriber,
+        };
+
+        /// Name and formatted fields of a span or event
+        pub type Record = (String, Vec<(String, String)>);
+
+        /// Subscriber which records all spans and events
+        #[derive(Default, Clone)]
+        pub struct Capture {
+            pub spans: Arc<Mutex<Vec<Record>>>,
+            pub events: Arc<Mutex<Vec<Record>>>,
+        }
+
+        #[derive(Default)]
+        struct Fields(Vec<(String, String)>);
+
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                self.0.push((field.name().into(), format!("{value:?}")));
+            }
+        }
+
+        impl Subscriber for Capture {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+                let mut fields = Fields::default();
+                span.record(&mut fields);
+                let mut spans = self.spans.lock().unwrap();
+                spans.push((span.metadata().name().into(), fields.0));
+                span::Id::from_u64(spans.len() as u64)
+            }
+
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = Fields::default();
+                event.record(&mut fields);
+                let message = fields
+                    .0
+                    .iter()
+                    .find(|(k, _)| k == "message")
+                    .map(|(_, v)| v.clone())
+                    .unwrap_or_default();
+                self.events.lock().unwrap().push((message, fields.0));
+            }
+
+            fn enter(&self, _: &span::Id) {}
+
+            fn exit(&self, _: &span::Id) {}
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing_span() {
+        use crate::codelet::{
+            Clocks, Lifecycle, NodeletId, NodeletSetup, Transition, Vise, ViseTrait, WorkerId,
+        };
+
+        let field = |record: &tracing_capture::Record, name: &str| {
+            record
+                .1
+                .iter()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.clone())
+        };
+
+        let mut tx = DoubleBufferTx::new(1);
+        let mut relay = Relay.into_instance("relay", ());
+        tx.connect(&mut relay.rx).unwrap();
+
+        let mut vise = Vise::new(relay);
+        vise.setup(&mut NodeletSetup {
+            clocks: Clocks::new(),
+            nodelet_id_issue: NodeletId(WorkerId(0), 3),
+        });
+        vise.cycle(Transition::Start).unwrap();
+
+        tx.push(7).unwrap();
+        tx.flush();
+
+        let capture = tracing_capture::Capture::default();
+        tracing::subscriber::with_default(capture.clone(), || {
+            vise.cycle(Transition::Step).unwrap();
+        });
+
+        let spans = capture.spans.lock().unwrap();
+        assert_eq!(spans.len(), 1);
+        let span = &spans[0];
+        assert_eq!(span.0, "codelet");
+        assert_eq!(field(span, "codelet").unwrap(), "relay");
+        assert!(field(span, "type_name").unwrap().contains("Relay"));
+        assert_eq!(field(span, "transition").unwrap(), "Step");
+        assert_eq!(field(span, "id").unwrap(), "NodeletId(WorkerId(0), 3)");
+        assert_eq!(field(span, "cycle").unwrap(), "0");
+
+        let events = capture.events.lock().unwrap();
+        let sync = events.iter().find(|e| e.0 == "rx sync").unwrap();
+        assert_eq!(field(sync, "channel").unwrap(), "in");
+        assert_eq!(field(sync, "received").unwrap(), "1");
+        let flush = events.iter().find(|e| e.0 == "tx flush").unwrap();
+        assert_eq!(field(flush, "channel").unwrap(), "out");
+    }
+}
@@ -0,0 +1,69 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use serde::{Deserialize, Serialize};
+
+/// Codelet configurations which can be validated and described
+///
+/// This trait is usually implemented with `#[derive(NodoConfig)]`:
+///
+/// ```
+/// use nodo::prelude::*;
+///
+/// #[derive(NodoConfig)]
+/// struct MyConfig {
+///     #[config(default = 10, min = 1, doc = "queue size")]
+///     queue_size: usize,
+///
+///     #[config(default = 0.5, min = 0.0, max = 1.0)]
+///     alpha: f32,
+/// }
+///
+/// let cfg = MyConfig::default();
+/// assert_eq!(cfg.queue_size, 10);
+/// assert!(cfg.validate().is_ok());
+/// assert_eq!(MyConfig::describe().len(), 2);
+/// ```
+pub trait NodoConfig {
+    /// Checks that all fields are within their allowed ranges
+    fn validate(&self) -> Result<(), ConfigError>;
+
+    /// Describes all fields of the configuration
+    fn describe() -> Vec<FieldDescriptor>
+    where
+        Self: Sized;
+}
+
+/// Error returned when a configuration is invalid
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ConfigError {
+    #[error("config field `{field}` is out of range: value={value}, min={}, max={}",
+        min.as_deref().unwrap_or("-"), max.as_deref().unwrap_or("-"))]
+    OutOfRange {
+        field: &'static str,
+        value: String,
+        min: Option<String>,
+        max: Option<String>,
+    },
+}
+
+/// Description of a single field of a configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldDescriptor {
+    /// Name of the field
+    pub name: String,
+
+    /// Rust type name of the field
+    pub type_name: String,
+
+    /// Default value as written in the config attribute
+    pub default: Option<String>,
+
+    /// Minimal allowed value (inclusive)
+    pub min: Option<String>,
+
+    /// Maximal allowed value (inclusive)
+    pub max: Option<String>,
+
+    /// Human-readable description of the field
+    pub doc: Option<String>,
+}
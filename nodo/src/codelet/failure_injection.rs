@@ -0,0 +1,176 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::codelet::Transition;
+use core::time::Duration;
+
+/// Label shown in reports for codelets with an armed failure plan. Errors and panics caused by
+/// injection start with this marker as well.
+pub const FAILURE_INJECTION_LABEL: &str = "[injected]";
+
+/// Failures injected into the lifecycle of a codelet for chaos testing
+///
+/// Failures are injected around the real codelet calls such that any codelet can be targeted
+/// without modification, see `CodeletInstance::with_failure_injection`. Plans can be changed at
+/// runtime for live drills with `Runtime::set_failure_injection`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FailurePlan {
+    fail_step: Option<u64>,
+    fail_start: Option<(f64, u64)>,
+    panic_on_stop: bool,
+    step_delay: Option<Duration>,
+}
+
+impl FailurePlan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fails the n-th step after the plan was armed. Counting starts at 1.
+    #[must_use]
+    pub fn with_fail_step(mut self, n: u64) -> Self {
+        self.fail_step = Some(n);
+        self
+    }
+
+    /// Fails every start with the given probability. Outcomes are drawn from a random generator
+    /// with the given seed such that runs are reproducible.
+    #[must_use]
+    pub fn with_fail_start(mut self, probability: f64, seed: u64) -> Self {
+        self.fail_start = Some((probability, seed));
+        self
+    }
+
+    /// Panics instead of stopping the codelet
+    #[must_use]
+    pub fn with_panic_on_stop(mut self) -> Self {
+        self.panic_on_stop = true;
+        self
+    }
+
+    /// Sleeps for the given duration before every step
+    #[must_use]
+    pub fn with_step_delay(mut self, delay: Duration) -> Self {
+        self.step_delay = Some(delay);
+        self
+    }
+}
+
+/// A failure caused by a [FailurePlan]
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum InjectedFailure {
+    #[error("{FAILURE_INJECTION_LABEL} start of codelet '{0}' failed")]
+    Start(String),
+
+    #[error("{FAILURE_INJECTION_LABEL} step {1} of codelet '{0}' failed")]
+    Step(String, u64),
+}
+
+/// Executes a [FailurePlan] before the transitions of a codelet
+#[derive(Debug, Clone)]
+pub(crate) struct FailureInjector {
+    plan: FailurePlan,
+    step_count: u64,
+    rng: u64,
+}
+
+impl FailureInjector {
+    pub fn new(plan: FailurePlan) -> Self {
+        let rng = plan.fail_start.map_or(0, |(_, seed)| seed);
+        Self {
+            plan,
+            step_count: 0,
+            rng,
+        }
+    }
+
+    /// Called before the transition is executed. Fails or panics if the plan says so.
+    pub fn inject(&mut self, codelet: &str, transition: Transition) -> Result<(), InjectedFailure> {
+        let failure = match transition {
+            Transition::Start => match self.plan.fail_start {
+                Some((probability, _)) => {
+                    (self.next_f64() < probability).then(|| InjectedFailure::Start(codelet.into()))
+                }
+                None => None,
+            },
+            Transition::Step => {
+                self.step_count += 1;
+                if let Some(delay) = self.plan.step_delay {
+                    std::thread::sleep(delay);
+                }
+                (self.plan.fail_step == Some(self.step_count))
+                    .then(|| InjectedFailure::Step(codelet.into(), self.step_count))
+            }
+            Transition::Stop if self.plan.panic_on_stop => {
+                log::warn!("{FAILURE_INJECTION_LABEL} panic on stop of codelet '{codelet}'");
+                panic!("{FAILURE_INJECTION_LABEL} panic on stop of codelet '{codelet}'");
+            }
+            Transition::Stop | Transition::Pause | Transition::Resume => None,
+        };
+
+        match failure {
+            Some(failure) => {
+                log::warn!("{failure}");
+                Err(failure)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Uniform random number in [0, 1) using splitmix64
+    fn next_f64(&mut self) -> f64 {
+        self.rng = self.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codelet::{FailureInjector, FailurePlan, InjectedFailure, Transition};
+
+    fn start_outcomes(probability: f64, seed: u64) -> Vec<bool> {
+        let mut injector =
+            FailureInjector::new(FailurePlan::new().with_fail_start(probability, seed));
+        (0..64)
+            .map(|_| injector.inject("c", Transition::Start).is_err())
+            .collect()
+    }
+
+    #[test]
+    fn test_fail_step() {
+        let mut injector = FailureInjector::new(FailurePlan::new().with_fail_step(3));
+        assert!(injector.inject("c", Transition::Start).is_ok());
+        assert!(injector.inject("c", Transition::Step).is_ok());
+        assert!(injector.inject("c", Transition::Step).is_ok());
+
+        let err = injector.inject("c", Transition::Step).unwrap_err();
+        assert_eq!(err, InjectedFailure::Step("c".into(), 3));
+        assert_eq!(err.to_string(), "[injected] step 3 of codelet 'c' failed");
+
+        assert!(injector.inject("c", Transition::Step).is_ok());
+        assert!(injector.inject("c", Transition::Stop).is_ok());
+    }
+
+    #[test]
+    fn test_fail_start_seeded() {
+        assert!(start_outcomes(0.0, 7).iter().all(|&failed| !failed));
+        assert!(start_outcomes(1.0, 7).iter().all(|&failed| failed));
+
+        // the same seed gives the same outcomes
+        let outcomes = start_outcomes(0.5, 42);
+        assert_eq!(outcomes, start_outcomes(0.5, 42));
+        assert_ne!(outcomes, start_outcomes(0.5, 43));
+        let failures = outcomes.iter().filter(|&&failed| failed).count();
+        assert!((16..48).contains(&failures), "{failures}");
+    }
+
+    #[test]
+    #[should_panic(expected = "[injected] panic on stop of codelet 'c'")]
+    fn test_panic_on_stop() {
+        let mut injector = FailureInjector::new(FailurePlan::new().with_panic_on_stop());
+        injector.inject("c", Transition::Stop).ok();
+    }
+}
@@ -1,26 +1,45 @@
 // Copyright 2023 by David Weikersdorfer. All rights reserved.
 
+mod builder;
 mod codelet_instance;
+mod config;
+mod failure_injection;
 mod lifecycle;
+mod persist;
+mod resources;
 mod schedule;
+mod scoped_worker;
+#[cfg(feature = "scratch")]
+mod scratch;
 mod sequence;
 mod statistics;
+mod step_lints;
 mod task_clock;
 mod transition;
 mod vise;
 
+pub use builder::*;
 pub use codelet_instance::*;
+pub use config::*;
+pub use failure_injection::*;
 pub use lifecycle::*;
+pub use persist::*;
+pub use resources::*;
 pub use schedule::*;
+pub use scoped_worker::*;
+#[cfg(feature = "scratch")]
+pub use scratch::*;
 pub use sequence::*;
 pub use statistics::*;
+pub use step_lints::*;
 pub use task_clock::*;
 pub use transition::*;
 pub use vise::*;
 
 use crate::channels::{RxBundle, TxBundle};
 use eyre::Result;
-use nodo_core::DefaultStatus;
+use nodo_core::{DefaultStatus, Outcome, Severity, SkipReason, SKIPPED};
+use std::sync::{Mutex, MutexGuard, PoisonError};
 
 /// Codelets can be implemented by the user to execute work.
 pub trait Codelet: Send {
@@ -39,6 +58,12 @@ pub trait Codelet: Send {
     /// Constructs channel bundles
     fn build_bundles(cfg: &Self::Config) -> (Self::Rx, Self::Tx);
 
+    /// Obtains shared resources from the registry of the runtime. Called once on the worker
+    /// thread before the first start. Returning an error fails the start of the codelet.
+    fn acquire_resources(&mut self, _resources: &ResourceRegistry) -> Result<()> {
+        Ok(())
+    }
+
     /// Start is guaranteed to be called first. Start may be called again after stop was called.
     fn start(
         &mut self,
@@ -92,6 +117,16 @@ pub trait CodeletStatus: 'static + Send + Sync {
 
     /// A textual rendering of the status code
     fn label(&self) -> &str;
+
+    /// A numeric code identifying the status
+    fn code(&self) -> u32 {
+        0
+    }
+
+    /// Severity of the status used by tools to highlight problems
+    fn severity(&self) -> Severity {
+        Severity::from_default_status(self.as_default_status())
+    }
 }
 
 impl CodeletStatus for DefaultStatus {
@@ -109,9 +144,16 @@ impl CodeletStatus for DefaultStatus {
             DefaultStatus::Running => "running",
         }
     }
+
+    fn code(&self) -> u32 {
+        *self as u32
+    }
 }
 
 /// Context argument used for `Codelet` start, step and stop functions
+///
+/// The context can be shared with threads spawned during the transition, e.g. with
+/// `std::thread::scope`. This does not hold with the `scratch` feature as the arena is not `Sync`.
 pub struct Context<'a, C>
 where
     C: Codelet + ?Sized,
@@ -125,10 +167,108 @@ where
 
     /// The configuration used for this instance
     pub config: &'a C::Config,
+
+    pub(crate) is_warmup: bool,
+    pub(crate) is_dry_run: bool,
+    pub(crate) reports: Mutex<ContextReports>,
+    pub(crate) scoped_workers: &'a ScopedWorkers,
+    #[cfg(feature = "scratch")]
+    pub(crate) scratch: &'a ScratchArena,
+}
+
+/// Values reported by a codelet through its [Context] during a single transition
+///
+/// They are kept behind a mutex such that the context can be shared with threads, for example
+/// when a codelet processes its inputs in parallel.
+#[derive(Default)]
+pub(crate) struct ContextReports {
+    pub skip_reason: Option<SkipReason>,
+    pub warnings: Vec<String>,
+    pub progress: Option<(f32, Option<String>)>,
+}
+
+impl<C> Context<'_, C>
+where
+    C: Codelet + ?Sized,
+{
+    fn reports(&self) -> MutexGuard<'_, ContextReports> {
+        self.reports.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Takes the values reported during the transition
+    pub(crate) fn into_reports(self) -> ContextReports {
+        self.reports
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// True while the schedule is in its warm-up phase, see `ScheduleBuilder::with_warmup_steps`
+    pub fn is_warmup(&self) -> bool {
+        self.is_warmup
+    }
+
+    /// True if the codelet is executed as part of a dry run. Codelets which access hardware or
+    /// the network should substitute mocks or skip the access.
+    pub fn is_dry_run(&self) -> bool {
+        self.is_dry_run
+    }
+
+    /// Skips the current transition and records why. The reason is shown by tools next to the
+    /// status and skips are counted per reason in statistics.
+    pub fn skip_because(&self, reason: SkipReason) -> Outcome {
+        self.reports().skip_reason = Some(reason);
+        SKIPPED
+    }
+
+    /// Logs a warning. Warnings given during start are kept as start diagnostics which are shown
+    /// by the inspector for the whole run.
+    pub fn warn<S: Into<String>>(&self, msg: S) {
+        self.reports().warnings.push(msg.into());
+    }
+
+    /// Reports progress of long-running work as a fraction in [0, 1] with an optional detail
+    /// shown by the inspector
+    ///
+    /// Progress is kept until it is set again. After progress 1.0 was reported it is cleared by
+    /// the first step which does not set progress. Values outside of [0, 1] are clamped.
+    pub fn set_progress(&self, fraction: f32, detail: Option<String>) {
+        let clamped = if fraction.is_nan() {
+            0.0
+        } else {
+            fraction.clamp(0.0, 1.0)
+        };
+        if clamped != fraction {
+            log::debug!("progress {fraction} clamped to {clamped}");
+        }
+        self.reports().progress = Some((clamped, detail));
+    }
+
+    /// Arena for temporary allocations which is reset after the transition, see [ScratchArena]
+    #[cfg(feature = "scratch")]
+    pub fn scratch(&self) -> &ScratchArena {
+        self.scratch
+    }
+
+    /// Spawns a background thread tied to the lifecycle of the codelet
+    ///
+    /// The stop flag of the worker is set when the codelet receives stop and the thread is joined
+    /// after `Codelet::stop` returned. Workers which do not finish within the join timeout fail
+    /// the stop transition, see `CodeletInstance::with_scoped_worker_join_timeout`. Values posted
+    /// by the worker are received via the returned handle.
+    pub fn spawn_scoped<T, F, S>(&self, name: S, f: F) -> Result<ScopedWorker<T>>
+    where
+        T: Send + 'static,
+        F: FnOnce(WorkerScope<T>) + Send + 'static,
+        S: Into<String>,
+    {
+        self.scoped_workers.spawn(name.into(), f)
+    }
 }
 
 /// All instances of codelets can be converted into a CodeletInstance with into_instance
 ///
+/// Prefer `into_builder` to configure the instance with further options.
+///
 /// ```
 /// use nodo::prelude::*;
 ///
@@ -147,6 +287,9 @@ where
 pub trait IntoInstance: Codelet + Sized {
     fn into_instance<S: Into<String>>(self, name: S, config: Self::Config)
         -> CodeletInstance<Self>;
+
+    /// Starts building an instance with the given name, see [CodeletBuilder]
+    fn into_builder<S: Into<String>>(self, name: S) -> CodeletBuilder<Self>;
 }
 
 impl<C> IntoInstance for C
@@ -160,10 +303,16 @@ where
     ) -> CodeletInstance<Self> {
         CodeletInstance::new(name, self, config)
     }
+
+    fn into_builder<S: Into<String>>(self, name: S) -> CodeletBuilder<Self> {
+        CodeletBuilder::new(name, self)
+    }
 }
 
 /// Default-constructible codelets can be instantiated directly
 ///
+/// Prefer `builder` to configure the instance with further options.
+///
 /// ```
 /// use nodo::prelude::*;
 ///
@@ -182,6 +331,9 @@ where
 /// ```
 pub trait Instantiate: Codelet + Sized {
     fn instantiate<S: Into<String>>(name: S, config: Self::Config) -> CodeletInstance<Self>;
+
+    /// Starts building an instance with the given name, see [CodeletBuilder]
+    fn builder<S: Into<String>>(name: S) -> CodeletBuilder<Self>;
 }
 
 impl<C> Instantiate for C
@@ -191,4 +343,8 @@ where
     fn instantiate<S: Into<String>>(name: S, config: Self::Config) -> CodeletInstance<Self> {
         CodeletInstance::new(name, C::default(), config)
     }
+
+    fn builder<S: Into<String>>(name: S) -> CodeletBuilder<Self> {
+        CodeletBuilder::new(name, C::default())
+    }
 }
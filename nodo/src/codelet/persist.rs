@@ -0,0 +1,66 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use eyre::{bail, Result};
+use nodo_core::EyreResult;
+
+/// Codelets which can save their state and restore it after an application restart
+///
+/// Persistence is enabled per instance with `CodeletInstance::with_persistence`. The runtime
+/// restores the state before the codelet is started and saves it after it was stopped.
+pub trait Persist {
+    /// Version of the saved format. Saved states with a different version are not restored.
+    const VERSION: u8 = 0;
+
+    /// Serializes the state of the codelet
+    fn save(&self) -> EyreResult<Vec<u8>>;
+
+    /// Restores a state created by `save`. The codelet is started with its initial state if this
+    /// fails, thus it should not be modified in case of an error.
+    fn restore(&mut self, data: &[u8]) -> EyreResult<()>;
+}
+
+/// Saved state of a codelet tagged with the version of its format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PersistedState {
+    pub version: u8,
+    pub data: Vec<u8>,
+}
+
+/// Type-erased access to the `Persist` implementation of a codelet
+pub(crate) struct Persistence<C> {
+    pub(crate) key: String,
+    version: u8,
+    save: fn(&C) -> EyreResult<Vec<u8>>,
+    restore: fn(&mut C, &[u8]) -> EyreResult<()>,
+}
+
+impl<C: Persist> Persistence<C> {
+    pub(crate) fn new(key: String) -> Self {
+        Self {
+            key,
+            version: C::VERSION,
+            save: C::save,
+            restore: C::restore,
+        }
+    }
+}
+
+impl<C> Persistence<C> {
+    pub(crate) fn save(&self, codelet: &C) -> Result<PersistedState> {
+        Ok(PersistedState {
+            version: self.version,
+            data: (self.save)(codelet)?,
+        })
+    }
+
+    pub(crate) fn restore(&self, codelet: &mut C, state: &PersistedState) -> Result<()> {
+        if state.version != self.version {
+            bail!(
+                "saved state has version {} but version {} is required",
+                state.version,
+                self.version
+            );
+        }
+        (self.restore)(codelet, &state.data)
+    }
+}
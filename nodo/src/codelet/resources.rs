@@ -0,0 +1,124 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::any::{Any, TypeId};
+use std::{collections::HashMap, sync::Arc};
+
+/// Shared resources like a GPU context or a connection pool by type
+///
+/// Resources are inserted before schedules are added to the runtime and are read-only afterwards.
+/// Codelets obtain them in [super::Codelet::acquire_resources] which is called on the worker
+/// thread before the codelet starts. At most one resource per type is stored.
+#[derive(Default)]
+pub struct ResourceRegistry {
+    items: HashMap<TypeId, Entry>,
+}
+
+struct Entry {
+    type_name: &'static str,
+    value: Arc<dyn Any + Send + Sync>,
+}
+
+/// A codelet requested a resource which was not inserted into the [ResourceRegistry]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("missing resource of type `{type_name}`")]
+pub struct MissingResourceError {
+    pub type_name: &'static str,
+}
+
+impl ResourceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a resource. Replaces and returns a previous resource of the same type.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<Arc<T>> {
+        self.insert_arc(Arc::new(value))
+    }
+
+    /// Adds a resource which is already shared
+    pub fn insert_arc<T: Send + Sync + 'static>(&mut self, value: Arc<T>) -> Option<Arc<T>> {
+        self.items
+            .insert(
+                TypeId::of::<T>(),
+                Entry {
+                    type_name: core::any::type_name::<T>(),
+                    value,
+                },
+            )
+            .and_then(|entry| entry.value.downcast().ok())
+    }
+
+    /// Gets the resource of the given type
+    pub fn get<T: Send + Sync + 'static>(&self) -> Result<Arc<T>, MissingResourceError> {
+        self.items
+            .get(&TypeId::of::<T>())
+            .and_then(|entry| entry.value.clone().downcast().ok())
+            .ok_or(MissingResourceError {
+                type_name: core::any::type_name::<T>(),
+            })
+    }
+
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.items.contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Type names of all resources sorted alphabetically
+    pub fn type_names(&self) -> Vec<&'static str> {
+        let mut names: Vec<_> = self.items.values().map(|entry| entry.type_name).collect();
+        names.sort();
+        names
+    }
+}
+
+impl core::fmt::Debug for ResourceRegistry {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_set().entries(self.type_names()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codelet::{MissingResourceError, ResourceRegistry};
+    use std::sync::{atomic::AtomicU32, Arc};
+
+    #[test]
+    fn test_registry() {
+        let mut registry = ResourceRegistry::new();
+        assert!(registry.is_empty());
+
+        assert!(registry.insert(AtomicU32::new(3)).is_none());
+        registry.insert(String::from("pool"));
+        assert_eq!(registry.len(), 2);
+
+        // all users get the same instance
+        let a = registry.get::<AtomicU32>().unwrap();
+        let b = registry.get::<AtomicU32>().unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_eq!(*registry.get::<String>().unwrap(), "pool");
+
+        assert_eq!(
+            registry.get::<u64>().unwrap_err(),
+            MissingResourceError { type_name: "u64" }
+        );
+        assert_eq!(
+            registry.get::<u64>().unwrap_err().to_string(),
+            "missing resource of type `u64`"
+        );
+
+        let previous = registry.insert(String::from("other")).unwrap();
+        assert_eq!(*previous, "pool");
+        assert_eq!(registry.len(), 2);
+        assert_eq!(
+            registry.type_names(),
+            ["alloc::string::String", "core::sync::atomic::AtomicU32"]
+        );
+    }
+}
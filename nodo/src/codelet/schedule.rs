@@ -13,6 +13,84 @@ pub struct ScheduleBuilder {
     pub sequences: Vec<Sequence>,
     pub max_step_count: Option<usize>,
     pub period: Option<Duration>,
+    pub warmup: Option<Warmup>,
+    pub exclude_warmup_statistics: bool,
+    pub catch_up: CatchUpPolicy,
+    pub max_dt: Option<Duration>,
+    pub start_after: Vec<String>,
+    pub load_shedding: Option<LoadShedding>,
+    pub sleep: SleepStrategy,
+}
+
+/// Length of the warm-up phase of a schedule
+///
+/// During warm-up the schedule steps normally but codelets can query `Context::is_warmup` to
+/// suppress outputs which are not reliable yet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Warmup {
+    /// The first N steps are warm-up steps
+    Steps(usize),
+
+    /// All steps within the given duration after the first step are warm-up steps
+    Duration(Duration),
+}
+
+/// How a periodic schedule reacts if it fell far behind its period, e.g. after the system was
+/// suspended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatchUpPolicy {
+    /// The next step is executed immediately regardless of how late it is
+    CatchUp,
+
+    /// If a step begins later than the given number of periods after it was scheduled the missed
+    /// time is treated as a gap and the next step is executed one period after the late step.
+    /// Time spent in steps which overrun the period does not count as being late.
+    SkipAhead(u32),
+}
+
+impl Default for CatchUpPolicy {
+    fn default() -> Self {
+        CatchUpPolicy::SkipAhead(10)
+    }
+}
+
+/// Pauses low-priority sequences while a periodic schedule overruns its period
+///
+/// A step overruns if it takes longer than the period. After `trigger_after_overruns` consecutive
+/// overruns all sequences with the lowest priority are shed, i.e. paused and no longer stepped.
+/// If the schedule keeps overrunning the next priority level is shed. Sequences with priority 0
+/// are never shed. After `recover_after_clean` consecutive steps without overrun the most
+/// recently shed level is resumed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadShedding {
+    pub trigger_after_overruns: usize,
+    pub recover_after_clean: usize,
+}
+
+/// How the worker of a periodic schedule waits for the next step
+///
+/// The OS wakes up a sleeping thread late by an amount which depends on the system load. Spinning
+/// instead of sleeping for the final part of the wait removes this overshoot but keeps a CPU core
+/// busy, which costs power and heat. The default strategy spins up to 15 ms per period. A
+/// smaller slack reduces the time spent spinning, pure spinning occupies one core completely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SleepStrategy {
+    /// Sleeps with the OS until `slack` before the next step and spins for the rest. If
+    /// `adaptive` is set the slack is tuned by the worker from the measured overshoot of the OS
+    /// sleep, starting at `slack`.
+    Hybrid { slack: Duration, adaptive: bool },
+
+    /// Spins for the whole wait. Only use this for the most timing-critical schedule.
+    Spin,
+}
+
+impl Default for SleepStrategy {
+    fn default() -> Self {
+        SleepStrategy::Hybrid {
+            slack: Duration::from_millis(15),
+            adaptive: false,
+        }
+    }
 }
 
 impl ScheduleBuilder {
@@ -24,6 +102,13 @@ impl ScheduleBuilder {
             sequences: Vec::new(),
             max_step_count: None,
             period: None,
+            warmup: None,
+            exclude_warmup_statistics: false,
+            catch_up: CatchUpPolicy::default(),
+            max_dt: None,
+            start_after: Vec::new(),
+            load_shedding: None,
+            sleep: SleepStrategy::default(),
         }
     }
 
@@ -45,6 +130,66 @@ impl ScheduleBuilder {
         self
     }
 
+    /// The first `count` steps of the schedule are warm-up steps
+    #[must_use]
+    pub fn with_warmup_steps(mut self, count: usize) -> Self {
+        self.warmup = Some(Warmup::Steps(count));
+        self
+    }
+
+    /// All steps within `duration` after the first step are warm-up steps
+    #[must_use]
+    pub fn with_warmup_duration(mut self, duration: Duration) -> Self {
+        self.warmup = Some(Warmup::Duration(duration));
+        self
+    }
+
+    /// If enabled warm-up steps are not included in step statistics
+    #[must_use]
+    pub fn with_exclude_warmup_statistics(mut self, exclude: bool) -> Self {
+        self.exclude_warmup_statistics = exclude;
+        self
+    }
+
+    /// Sets how the schedule reacts if it fell far behind its period
+    #[must_use]
+    pub fn with_catch_up_policy(mut self, policy: CatchUpPolicy) -> Self {
+        self.catch_up = policy;
+        self
+    }
+
+    /// Clamps the time step seen by codelets via `cx.clocks.codelet.dt_secs_f32()` such that
+    /// codelets integrating over time do not take a huge step after a gap
+    #[must_use]
+    pub fn with_max_dt(mut self, max_dt: Duration) -> Self {
+        self.max_dt = Some(max_dt);
+        self
+    }
+
+    /// Declares that this schedule depends on the schedule with the given name
+    ///
+    /// On shutdown the runtime stops this schedule before its dependencies, e.g. such that a
+    /// recorder schedule still receives the final messages of the schedules feeding it.
+    #[must_use]
+    pub fn with_start_after<S: Into<String>>(mut self, schedule: S) -> Self {
+        self.start_after.push(schedule.into());
+        self
+    }
+
+    /// Enables load shedding of sequences with a non-zero priority, see [LoadShedding]
+    #[must_use]
+    pub fn with_load_shedding(mut self, policy: LoadShedding) -> Self {
+        self.load_shedding = Some(policy);
+        self
+    }
+
+    /// Sets how the worker waits for the next step, see [SleepStrategy]
+    #[must_use]
+    pub fn with_sleep_strategy(mut self, strategy: SleepStrategy) -> Self {
+        self.sleep = strategy;
+        self
+    }
+
     #[deprecated]
     #[must_use]
     pub fn with_max_step_count(mut self, max_step_count: usize) -> Self {
@@ -71,11 +216,29 @@ pub trait Schedulable {
 }
 
 impl<C: Codelet + 'static> Schedulable for CodeletInstance<C> {
-    fn schedule(self, sched: &mut ScheduleBuilder) {
+    fn schedule(mut self, sched: &mut ScheduleBuilder) {
+        for schedule in self.start_after.drain(..) {
+            if !sched.start_after.contains(&schedule) {
+                sched.start_after.push(schedule);
+            }
+        }
+        match (sched.period, self.period_hint) {
+            (None, Some(hint)) => sched.period = Some(hint),
+            (Some(period), Some(hint)) if period != hint => log::warn!(
+                "codelet '{}' expects a period of {hint:?} but schedule '{}' has a period of \
+                 {period:?}",
+                self.name,
+                sched.name
+            ),
+            _ => {}
+        }
+
         sched.sequences.push(Sequence {
             name: "".into(),
             vises: vec![DynamicVise::new(self)],
             period: None,
+            auto_stop_on_closed: false,
+            priority: 0,
         });
     }
 }
@@ -0,0 +1,447 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use eyre::{eyre, Result};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread::JoinHandle,
+    time::Instant,
+};
+
+/// Default time to wait for scoped workers to finish after the codelet stopped
+pub const DEFAULT_SCOPED_WORKER_JOIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Flag which is set when a scoped worker shall finish
+#[derive(Debug, Clone, Default)]
+pub struct StopFlag(Arc<AtomicBool>);
+
+impl StopFlag {
+    /// True once the codelet received stop
+    pub fn is_set(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    fn set(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+/// Argument passed to the function running on a scoped worker thread
+pub struct WorkerScope<T> {
+    stop: StopFlag,
+    mailbox: mpsc::Sender<T>,
+}
+
+impl<T> WorkerScope<T> {
+    /// True once the codelet received stop. The worker function should return soon after.
+    pub fn is_stop_requested(&self) -> bool {
+        self.stop.is_set()
+    }
+
+    /// The stop flag, e.g. to hand it to blocking I/O helpers
+    pub fn stop_flag(&self) -> &StopFlag {
+        &self.stop
+    }
+
+    /// Posts a value to the mailbox of the codelet. Never blocks. Values posted after the
+    /// codelet dropped its [ScopedWorker] handle are discarded.
+    pub fn post(&self, value: T) {
+        self.mailbox.send(value).ok();
+    }
+}
+
+/// Handle of a background thread spawned with `Context::spawn_scoped`
+///
+/// The thread itself is owned by the codelet instance: its stop flag is set when the codelet
+/// receives stop and it is joined after `Codelet::stop` returned. The handle gives access to
+/// values posted by the worker.
+pub struct ScopedWorker<T> {
+    name: String,
+    stop: StopFlag,
+    mailbox: mpsc::Receiver<T>,
+}
+
+impl<T> ScopedWorker<T> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// True once the codelet received stop
+    pub fn is_stop_requested(&self) -> bool {
+        self.stop.is_set()
+    }
+
+    /// Takes the oldest value posted by the worker without blocking
+    pub fn try_recv(&self) -> Option<T> {
+        self.mailbox.try_recv().ok()
+    }
+
+    /// Takes all values posted by the worker so far without blocking
+    pub fn drain(&self) -> impl Iterator<Item = T> + '_ {
+        self.mailbox.try_iter()
+    }
+}
+
+/// A thread spawned by a codelet which was not joined yet
+struct ScopedWorkerEntry {
+    name: String,
+    stop: StopFlag,
+
+    /// Disconnects when the thread function returned or panicked
+    done: mpsc::Receiver<()>,
+
+    thread: Option<JoinHandle<()>>,
+}
+
+/// All scoped workers of a codelet instance
+#[derive(Default)]
+pub(crate) struct ScopedWorkers {
+    entries: Mutex<Vec<ScopedWorkerEntry>>,
+}
+
+impl ScopedWorkers {
+    fn entries(&self) -> MutexGuard<'_, Vec<ScopedWorkerEntry>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn spawn<T, F>(&self, name: String, f: F) -> Result<ScopedWorker<T>>
+    where
+        T: Send + 'static,
+        F: FnOnce(WorkerScope<T>) + Send + 'static,
+    {
+        let stop = StopFlag::default();
+        let (mailbox_tx, mailbox_rx) = mpsc::channel();
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+
+        let scope = WorkerScope {
+            stop: stop.clone(),
+            mailbox: mailbox_tx,
+        };
+        let thread = std::thread::Builder::new()
+            .name(name.clone())
+            .spawn(move || {
+                let _done = done_tx;
+                f(scope)
+            })
+            .map_err(|err| eyre!("failed to spawn scoped worker '{name}': {err}"))?;
+
+        self.entries().push(ScopedWorkerEntry {
+            name: name.clone(),
+            stop: stop.clone(),
+            done: done_rx,
+            thread: Some(thread),
+        });
+
+        Ok(ScopedWorker {
+            name,
+            stop,
+            mailbox: mailbox_rx,
+        })
+    }
+
+    /// Number of workers which were not joined yet
+    pub(crate) fn len(&self) -> usize {
+        self.entries().len()
+    }
+
+    /// Number of workers whose thread function did not return yet
+    pub(crate) fn running_count(&self) -> usize {
+        self.entries()
+            .iter()
+            .filter(|entry| matches!(entry.done.try_recv(), Err(mpsc::TryRecvError::Empty)))
+            .count()
+    }
+
+    /// Requests all workers to stop
+    pub(crate) fn request_stop(&self) {
+        for entry in self.entries().iter() {
+            entry.stop.set();
+        }
+    }
+
+    /// Requests all workers to stop and joins them. Workers which do not finish within the
+    /// timeout are kept and reported as error. The timeout applies to all workers together.
+    pub(crate) fn stop_and_join(&self, codelet: &str, timeout: Duration) -> Result<()> {
+        self.request_stop();
+
+        let deadline = Instant::now() + timeout;
+        let mut errors = Vec::new();
+        self.entries().retain_mut(|entry| {
+            match entry
+                .done
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            {
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    errors.push(format!(
+                        "codelet '{codelet}': scoped worker '{}' did not finish within {timeout:?}",
+                        entry.name
+                    ));
+                    true
+                }
+                _ => {
+                    if entry.thread.take().unwrap().join().is_err() {
+                        errors.push(format!(
+                            "codelet '{codelet}': scoped worker '{}' panicked",
+                            entry.name
+                        ));
+                    }
+                    false
+                }
+            }
+        });
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(eyre!(errors.join("; ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        codelet::{
+            Clocks, Lifecycle, NodeletId, NodeletSetup, ScopedWorker, TaskClocks, Transition, Vise,
+            ViseTrait, WorkerId,
+        },
+        prelude::*,
+    };
+    use core::time::Duration;
+    use std::sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[derive(Default)]
+    struct Counters {
+        count: AtomicUsize,
+        started: AtomicUsize,
+        finished: AtomicUsize,
+    }
+
+    /// Spawns a worker on start which counts until it is stopped and posts its count
+    struct Counting {
+        counters: Arc<Counters>,
+        worker: Option<ScopedWorker<usize>>,
+        posted: Vec<usize>,
+    }
+
+    impl Codelet for Counting {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = ();
+        type Tx = ();
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            ((), ())
+        }
+
+        fn start(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            let counters = self.counters.clone();
+            self.worker = Some(cx.spawn_scoped("counter", move |scope| {
+                counters.started.fetch_add(1, Ordering::SeqCst);
+                while !scope.is_stop_requested() {
+                    let count = counters.count.fetch_add(1, Ordering::SeqCst) + 1;
+                    scope.post(count);
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+                counters.finished.fetch_add(1, Ordering::SeqCst);
+            })?);
+            SUCCESS
+        }
+
+        fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            self.posted.extend(self.worker.as_ref().unwrap().drain());
+            SUCCESS
+        }
+
+        fn stop(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            // the stop flag is set before stop is called
+            assert!(self.worker.as_ref().unwrap().is_stop_requested());
+            SUCCESS
+        }
+    }
+
+    fn setup<C: Codelet>(vise: &mut Vise<C>) {
+        vise.setup(&mut NodeletSetup {
+            clocks: Clocks::new(),
+            nodelet_id_issue: NodeletId(WorkerId(0), 0),
+        });
+    }
+
+    #[test]
+    fn test_start_stop_cycles() {
+        let counters = Arc::new(Counters::default());
+        let mut vise = Vise::new(
+            Counting {
+                counters: counters.clone(),
+                worker: None,
+                posted: Vec::new(),
+            }
+            .into_instance("counting", ()),
+        );
+        setup(&mut vise);
+
+        for cycle in 1..=3 {
+            vise.cycle(Transition::Start).unwrap();
+            while counters.count.load(Ordering::SeqCst) == 0 {
+                std::thread::yield_now();
+            }
+            vise.cycle(Transition::Step).unwrap();
+            vise.cycle(Transition::Stop).unwrap();
+
+            // the worker was stopped and joined exactly once per cycle
+            assert_eq!(counters.started.load(Ordering::SeqCst), cycle);
+            assert_eq!(counters.finished.load(Ordering::SeqCst), cycle);
+
+            // the thread no longer runs
+            let count = counters.count.load(Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(5));
+            assert_eq!(counters.count.load(Ordering::SeqCst), count);
+        }
+    }
+
+    #[test]
+    fn test_mailbox() {
+        let counters = Arc::new(Counters::default());
+        let mut counting = Counting {
+            counters: counters.clone(),
+            worker: None,
+            posted: Vec::new(),
+        }
+        .into_instance("counting", ());
+        counting.clocks = Some(TaskClocks::from(Clocks::new()));
+
+        counting.start().unwrap();
+        while counters.count.load(Ordering::SeqCst) < 3 {
+            std::thread::yield_now();
+        }
+        counting.step().unwrap();
+        assert_eq!(counting.scoped_worker_count(), 1);
+        counting.stop().unwrap();
+        assert_eq!(counting.scoped_worker_count(), 0);
+
+        // values arrive in the order they were posted
+        let posted = &counting.state.posted;
+        assert!(posted.len() >= 3);
+        assert!(posted.iter().enumerate().all(|(i, &v)| v == i + 1));
+    }
+
+    /// Spawns a worker which ignores the stop flag until released
+    struct Stubborn {
+        release: Arc<AtomicBool>,
+    }
+
+    impl Codelet for Stubborn {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = ();
+        type Tx = ();
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            ((), ())
+        }
+
+        fn start(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            let release = self.release.clone();
+            cx.spawn_scoped::<(), _, _>("serial-reader", move |_| {
+                while !release.load(Ordering::SeqCst) {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            })?;
+            SUCCESS
+        }
+
+        fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            SUCCESS
+        }
+    }
+
+    #[test]
+    fn test_join_timeout() {
+        let release = Arc::new(AtomicBool::new(false));
+        let mut vise = Vise::new(
+            Stubborn {
+                release: release.clone(),
+            }
+            .into_instance("stubborn", ())
+            .with_scoped_worker_join_timeout(Duration::from_millis(10)),
+        );
+        setup(&mut vise);
+
+        vise.cycle(Transition::Start).unwrap();
+        let err = vise.cycle(Transition::Stop).unwrap_err().to_string();
+        assert!(err.contains("codelet 'stubborn'"), "{err}");
+        assert!(err.contains("scoped worker 'serial-reader'"), "{err}");
+
+        // a worker which is still running at teardown is a hard error in debug builds
+        let teardown = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(vise)));
+        assert_eq!(teardown.is_err(), cfg!(debug_assertions));
+
+        release.store(true, Ordering::SeqCst);
+    }
+
+    /// Spawns workers which need the given time to finish after stop was requested
+    struct Slow {
+        delays: Vec<Duration>,
+    }
+
+    impl Codelet for Slow {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = ();
+        type Tx = ();
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            ((), ())
+        }
+
+        fn start(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            for (i, &delay) in self.delays.iter().enumerate() {
+                cx.spawn_scoped::<(), _, _>(format!("slow-{i}"), move |scope| {
+                    while !scope.is_stop_requested() {
+                        std::thread::sleep(Duration::from_millis(1));
+                    }
+                    std::thread::sleep(delay);
+                })?;
+            }
+            SUCCESS
+        }
+
+        fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            SUCCESS
+        }
+    }
+
+    #[test]
+    fn test_join_deadline_is_shared() {
+        let mut vise = Vise::new(
+            Slow {
+                delays: vec![Duration::from_millis(250), Duration::from_secs(1)],
+            }
+            .into_instance("slow", ())
+            .with_scoped_worker_join_timeout(Duration::from_millis(400)),
+        );
+        setup(&mut vise);
+
+        vise.cycle(Transition::Start).unwrap();
+        let time = std::time::Instant::now();
+        let err = vise.cycle(Transition::Stop).unwrap_err().to_string();
+
+        // the second worker only gets the time left after joining the first
+        assert!(
+            time.elapsed() < Duration::from_millis(600),
+            "{:?}",
+            time.elapsed()
+        );
+        assert!(!err.contains("'slow-0'"), "{err}");
+        assert!(err.contains("scoped worker 'slow-1'"), "{err}");
+
+        // let the slow worker finish before teardown
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
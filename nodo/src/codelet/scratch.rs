@@ -0,0 +1,118 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use bumpalo::Bump;
+use core::cell::Cell;
+
+/// Vector allocated in a [ScratchArena]
+pub type ScratchVec<'a, T> = bumpalo::collections::Vec<'a, T>;
+
+/// Bump allocator for temporary allocations of a codelet
+///
+/// The arena is reset after every transition, thus memory is reused by the next step instead of
+/// being returned to the global allocator. Values allocated in the arena borrow the context and
+/// can not escape the transition. Drop is not run for values allocated with
+/// [ScratchArena::alloc].
+///
+/// Usage is sampled after every allocation made with the functions of the arena and at the end
+/// of every transition. Growth of a [ScratchVec] is accounted at the next sample.
+pub struct ScratchArena {
+    bump: Bump,
+    peak_bytes: Cell<usize>,
+}
+
+impl ScratchArena {
+    /// Creates an arena which pre-allocates `capacity` bytes. The arena grows as needed.
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            bump: Bump::with_capacity(capacity),
+            peak_bytes: Cell::new(0),
+        }
+    }
+
+    /// Creates an empty vector in the arena
+    pub fn vec<T>(&self) -> ScratchVec<'_, T> {
+        ScratchVec::new_in(&self.bump)
+    }
+
+    /// Creates an empty vector with the given capacity in the arena
+    pub fn vec_with_capacity<T>(&self, capacity: usize) -> ScratchVec<'_, T> {
+        let vec = ScratchVec::with_capacity_in(capacity, &self.bump);
+        self.sample();
+        vec
+    }
+
+    /// Moves a value into the arena
+    pub fn alloc<T>(&self, value: T) -> &mut T {
+        let value = self.bump.alloc(value);
+        self.sample();
+        value
+    }
+
+    /// Copies a slice into the arena
+    pub fn alloc_slice_copy<T: Copy>(&self, src: &[T]) -> &mut [T] {
+        let slice = self.bump.alloc_slice_copy(src);
+        self.sample();
+        slice
+    }
+
+    /// Maximum number of bytes used during a single transition so far
+    pub fn peak_bytes(&self) -> usize {
+        self.peak_bytes.get()
+    }
+
+    /// Bytes reserved by the arena from the global allocator
+    pub fn capacity_bytes(&self) -> usize {
+        self.bump.allocated_bytes()
+    }
+
+    /// Frees all allocations and keeps the largest chunk of memory for the next transition
+    pub(crate) fn reset(&mut self) {
+        self.sample();
+        self.bump.reset();
+    }
+
+    /// Updates the peak usage. Unused space at the end of full chunks counts as used.
+    fn sample(&self) {
+        let used = self.bump.allocated_bytes() - self.bump.chunk_capacity();
+        self.peak_bytes.set(self.peak_bytes.get().max(used));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codelet::ScratchArena;
+
+    #[test]
+    fn test_reset() {
+        let mut arena = ScratchArena::with_capacity(1024);
+
+        let mut pointers = Vec::new();
+        for _ in 0..3 {
+            {
+                let mut v = arena.vec_with_capacity::<f32>(16);
+                v.extend((0..16).map(|i| i as f32));
+                pointers.push(v.as_ptr() as usize);
+            }
+            arena.reset();
+        }
+
+        // memory is reused after reset
+        assert!(pointers.iter().all(|&p| p == pointers[0]));
+        assert_eq!(arena.peak_bytes(), 64);
+    }
+
+    #[test]
+    fn test_peak_bytes() {
+        let mut arena = ScratchArena::with_capacity(0);
+
+        arena.alloc_slice_copy(&[0u8; 100]);
+        arena.reset();
+        arena.alloc_slice_copy(&[0u8; 300]);
+        arena.reset();
+        arena.alloc(0u64);
+        arena.reset();
+
+        assert_eq!(arena.peak_bytes(), 300);
+        assert!(arena.capacity_bytes() >= 300);
+    }
+}
@@ -8,10 +8,21 @@ use std::time::Duration;
 
 /// A sequences of nodos (codelet instances) which are executed one after another in the given
 /// order.
+///
+/// Each codelet syncs its RX channels right before its step and flushes its TX channels right
+/// after. A consumer ordered after its producer thus receives messages in the same cycle, while a
+/// consumer ordered before its producer receives them one cycle later.
 pub struct Sequence {
     pub name: String,
     pub period: Option<Duration>,
     pub vises: Vec<DynamicVise>,
+
+    /// If enabled codelets are stopped automatically once all their RX channels are closed
+    pub auto_stop_on_closed: bool,
+
+    /// Importance of the sequence for load shedding. Lower numbers are more important and
+    /// sequences with priority 0 are never shed.
+    pub priority: u8,
 }
 
 impl Sequence {
@@ -22,6 +33,8 @@ impl Sequence {
             name: String::new(),
             period: None,
             vises: Vec::new(),
+            auto_stop_on_closed: false,
+            priority: 0,
         }
     }
 
@@ -32,6 +45,26 @@ impl Sequence {
         self
     }
 
+    /// Automatically stop codelets after all their RX channels were closed (builder style)
+    ///
+    /// The codelet is stepped one more time after the close marker arrived so that it can process
+    /// the last messages. Codelets without RX channels are never stopped automatically.
+    #[must_use]
+    pub fn with_auto_stop_on_closed(mut self, enabled: bool) -> Self {
+        self.auto_stop_on_closed = enabled;
+        self
+    }
+
+    /// Sets the priority used for load shedding (builder style)
+    ///
+    /// If the schedule enables load shedding the sequences with the highest number are paused
+    /// first when the schedule overruns its period. The default priority 0 is never shed.
+    #[must_use]
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+
     // TODO implement
     // #[must_use]
     // pub fn with_period(mut self, period: Duration) -> Self {
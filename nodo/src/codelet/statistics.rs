@@ -1,13 +1,90 @@
 // Copyright 2024 by David Weikersdorfer. All rights reserved.
 
-use crate::codelet::TransitionMap;
+use crate::{
+    channels::{ChannelUid, SyncResult},
+    codelet::{Transition, TransitionMap},
+};
 use core::time::Duration;
+use nodo_core::{Name, SkipReason, UnknownFields};
 use serde::{Deserialize, Serialize};
-use std::time::Instant;
+use std::{collections::BTreeMap, time::Instant};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Statistics {
     pub transitions: TransitionMap<TransitionStatistics>,
+
+    /// Statistics of every RX channel accumulated over all syncs
+    #[serde(default)]
+    pub rx_channels: Vec<RxChannelStatistics>,
+
+    /// Number of steps which took longer than the deadline of the codelet, see
+    /// `CodeletInstance::with_deadline`
+    #[serde(default)]
+    pub deadline_miss_count: u64,
+
+    /// Maximum number of bytes allocated in the scratch arena of the codelet during a single
+    /// transition. Zero unless the `scratch` feature is enabled.
+    #[serde(default)]
+    pub scratch_peak_bytes: usize,
+
+    /// Fields written by a newer version which are kept when the statistics are forwarded
+    #[serde(flatten)]
+    pub unknown: UnknownFields,
+}
+
+/// Occupancy and loss of a receiving channel
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RxChannelStatistics {
+    /// Name of the endpoint
+    pub name: Name,
+
+    /// Stable identifier of the endpoint, see [ChannelUid]
+    #[serde(default)]
+    pub uid: Option<ChannelUid>,
+
+    /// Capacity of the receiver queue or None if the queue grows as needed
+    pub capacity: Option<usize>,
+
+    /// Maximum number of messages in the receiver queue after a sync
+    pub high_water_mark: usize,
+
+    pub received: u64,
+    pub forgotten: u64,
+    pub dropped: u64,
+    pub rejected: u64,
+
+    /// Time received messages waited in the back stage before they were synced. Only measured
+    /// if latency tracking is enabled on the receiver, see
+    /// `DoubleBufferRx::enable_latency_tracking`.
+    #[serde(default)]
+    pub residency: CountTotal,
+}
+
+impl RxChannelStatistics {
+    pub fn new<S: Into<Name>>(name: S) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Accumulates the result of a sync
+    pub fn push(&mut self, result: &SyncResult) {
+        self.capacity = result.capacity;
+        self.high_water_mark = self.high_water_mark.max(result.queue_len);
+        self.received += result.received as u64;
+        self.forgotten += result.forgotten as u64;
+        self.dropped += result.dropped as u64;
+        self.rejected += result.rejected as u64;
+        if let Some(residency) = result.residency.as_ref() {
+            self.residency.push_summary(
+                residency.count as u64,
+                residency.average,
+                residency.min,
+                residency.max,
+            );
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -16,11 +93,112 @@ pub struct TransitionStatistics {
     pub period: CountTotal,
     pub skipped_count: u64,
 
+    /// Number of skipped executions since the last execution which was not skipped
+    #[serde(default)]
+    pub consecutive_skipped_count: u64,
+
+    /// Total time spent in skipped executions. This is pure overhead from syncing and flushing.
+    #[serde(default)]
+    pub skipped_duration: Duration,
+
+    /// Number of skipped executions because the codelet was disabled. These are also counted in
+    /// `skipped_count`.
+    pub disabled_count: u64,
+
+    /// Number of skipped executions per skip reason, see `Context::skip_because`. Skips without
+    /// a reason are counted as unspecified.
+    #[serde(default)]
+    pub skipped_by_reason: BTreeMap<String, u64>,
+
+    /// Number of times the codelet was paused and resumed
+    pub pause_count: u64,
+    pub resume_count: u64,
+
+    /// Time between the last execution before and the first execution after the most recent
+    /// pause. This interval is not included in `period`.
+    pub resume_gap: Option<Duration>,
+
+    /// Deviation of executions from the ideal periodic grid of the schedule. Only measured for
+    /// steps of periodic schedules.
+    #[serde(default)]
+    pub jitter: JitterStatistics,
+
+    /// Fields written by a newer version which are kept when the statistics are forwarded
+    #[serde(flatten)]
+    pub unknown: UnknownFields,
+
+    #[serde(skip)]
+    nominal_period: Option<Duration>,
+
     #[serde(skip)]
     last_exec_begin: Option<Instant>,
+
+    #[serde(skip)]
+    is_paused: bool,
+
+    #[serde(skip)]
+    is_gap: bool,
 }
 
+/// Deviation of execution times from an ideal periodic grid
+///
+/// The grid is anchored at the first execution and every later execution is compared against the
+/// closest grid point. Thus an execution which is late by more than half a period counts as early
+/// for the next grid point. The anchor must be reset with [JitterStatistics::reset_anchor] when
+/// the execution is interrupted, e.g. after a pause or a clock gap.
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct JitterStatistics {
+    count: u64,
+    sum_squared: f64,
+    max: Duration,
+
+    #[serde(skip)]
+    anchor: Option<Instant>,
+}
+
+impl JitterStatistics {
+    /// Records an execution at the given time for a grid with the given period
+    pub fn push(&mut self, now: Instant, period: Duration) {
+        let Some(anchor) = self.anchor else {
+            self.anchor = Some(now);
+            return;
+        };
+        if period.is_zero() {
+            return;
+        }
+
+        let elapsed = now.saturating_duration_since(anchor).as_secs_f64();
+        let period = period.as_secs_f64();
+        let deviation = (elapsed - (elapsed / period).round() * period).abs();
+
+        self.count += 1;
+        self.sum_squared += deviation * deviation;
+        self.max = self.max.max(Duration::from_secs_f64(deviation));
+    }
+
+    /// The next execution starts a new grid
+    pub fn reset_anchor(&mut self) {
+        self.anchor = None;
+    }
+
+    /// Number of executions which were compared against the grid
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Root mean square of the deviation from the grid
+    pub fn rms(&self) -> Option<Duration> {
+        (self.count > 0)
+            .then(|| Duration::from_secs_f64((self.sum_squared / self.count as f64).sqrt()))
+    }
+
+    /// Largest deviation from the grid
+    pub fn max(&self) -> Option<Duration> {
+        (self.count > 0).then_some(self.max)
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CountTotal {
     count: u64,
     total: Duration,
@@ -31,8 +209,41 @@ impl Statistics {
     pub fn new() -> Self {
         Self {
             transitions: TransitionMap::default(),
+            rx_channels: Vec::new(),
+            deadline_miss_count: 0,
+            scratch_peak_bytes: 0,
+            unknown: UnknownFields::default(),
         }
     }
+
+    /// Percentage of step transitions which were skipped
+    pub fn skip_percent(&self) -> f32 {
+        self.transitions[Transition::Step].skip_percent()
+    }
+
+    /// Marks that the codelet was paused. The next step interval is recorded as resume gap
+    /// instead of as a period.
+    pub fn on_pause(&mut self) {
+        let step = &mut self.transitions[Transition::Step];
+        step.pause_count += 1;
+        step.is_paused = true;
+    }
+
+    /// Marks that the codelet was resumed
+    pub fn on_resume(&mut self) {
+        self.transitions[Transition::Step].resume_count += 1;
+    }
+
+    /// Marks that the schedule detected a gap in time, e.g. after the system was suspended. The
+    /// next step interval is not recorded as a period.
+    pub fn on_clock_gap(&mut self) {
+        self.transitions[Transition::Step].is_gap = true;
+    }
+
+    /// Sets the period of the schedule against which the jitter of steps is measured
+    pub fn set_nominal_period(&mut self, period: Option<Duration>) {
+        self.transitions[Transition::Step].nominal_period = period;
+    }
 }
 
 impl TransitionStatistics {
@@ -41,7 +252,19 @@ impl TransitionStatistics {
             duration: CountTotal::default(),
             period: CountTotal::default(),
             skipped_count: 0,
+            consecutive_skipped_count: 0,
+            skipped_duration: Duration::ZERO,
+            disabled_count: 0,
+            skipped_by_reason: BTreeMap::new(),
+            pause_count: 0,
+            resume_count: 0,
+            resume_gap: None,
+            jitter: JitterStatistics::default(),
+            unknown: UnknownFields::default(),
+            nominal_period: None,
             last_exec_begin: None,
+            is_paused: false,
+            is_gap: false,
         }
     }
 
@@ -56,26 +279,61 @@ impl TransitionStatistics {
     }
 
     pub fn begin(&mut self) {
-        let now = Instant::now();
+        self.begin_at(Instant::now());
+    }
+
+    fn begin_at(&mut self, now: Instant) {
+        if let Some(period) = self.nominal_period {
+            if self.is_paused || self.is_gap {
+                self.jitter.reset_anchor();
+            }
+            self.jitter.push(now, period);
+        }
 
         if let Some(last_exec) = self.last_exec_begin {
-            self.period.push(now - last_exec);
+            if self.is_paused {
+                self.resume_gap = Some(now - last_exec);
+            } else if !self.is_gap {
+                self.period.push(now - last_exec);
+            }
         }
+        self.is_paused = false;
+        self.is_gap = false;
 
         self.last_exec_begin = Some(now);
     }
 
-    pub fn end(&mut self, skipped: bool) {
+    /// Total time spent in executions including skipped ones
+    pub fn total_duration(&self) -> Duration {
+        self.duration.total() + self.skipped_duration
+    }
+
+    /// Counts a skipped execution for the given reason
+    pub fn count_skip_reason(&mut self, reason: Option<SkipReason>) {
+        let label = reason.map_or(SkipReason::UNSPECIFIED_LABEL, |r| r.label());
+        match self.skipped_by_reason.get_mut(label) {
+            Some(count) => *count += 1,
+            None => {
+                self.skipped_by_reason.insert(label.to_string(), 1);
+            }
+        }
+    }
+
+    /// Ends an execution and returns its duration
+    pub fn end(&mut self, skipped: bool) -> Duration {
+        let dt = Instant::now()
+            - self
+                .last_exec_begin
+                .expect("end() must be called after begin()");
         if skipped {
             self.skipped_count += 1;
+            self.consecutive_skipped_count += 1;
+            self.skipped_duration += dt;
         } else {
-            self.duration.push(
-                Instant::now()
-                    - self
-                        .last_exec_begin
-                        .expect("end() must be called after begin()"),
-            );
+            self.consecutive_skipped_count = 0;
+            self.duration.push(dt);
         }
+        dt
     }
 }
 
@@ -90,6 +348,20 @@ impl CountTotal {
         };
     }
 
+    /// Accumulates `count` samples of which only the average and the limits are known
+    pub fn push_summary(&mut self, count: u64, average: Duration, min: Duration, max: Duration) {
+        if count == 0 {
+            return;
+        }
+        self.limits = if self.count == 0 {
+            (min, max)
+        } else {
+            (self.limits.0.min(min), self.limits.1.max(max))
+        };
+        self.count += count;
+        self.total += average * count as u32;
+    }
+
     pub fn count(&self) -> u64 {
         self.count
     }
@@ -121,4 +393,151 @@ impl CountTotal {
             Some(self.limits.1.as_secs_f32() * 1000.0)
         }
     }
+
+    pub fn average(&self) -> Option<Duration> {
+        (self.count > 0).then(|| self.total.div_f64(self.count as f64))
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        (self.count > 0).then_some(self.limits.0)
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        (self.count > 0).then_some(self.limits.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codelet::{JitterStatistics, Statistics, Transition};
+    use core::time::Duration;
+    use std::time::Instant;
+
+    fn ms(x: f64) -> Duration {
+        Duration::from_secs_f64(x / 1000.0)
+    }
+
+    fn assert_close(actual: Option<Duration>, expected: Duration) {
+        let actual = actual.unwrap();
+        assert!(
+            actual.abs_diff(expected) < Duration::from_nanos(10),
+            "{actual:?} vs {expected:?}"
+        );
+    }
+
+    #[test]
+    fn test_jitter_grid() {
+        let t0 = Instant::now();
+        let period = ms(10.0);
+
+        // alternating 9ms and 11ms steps have a perfect average period but deviate from the grid
+        let mut jitter = JitterStatistics::default();
+        for t in [0.0, 9.0, 20.0, 29.0, 40.0, 49.0] {
+            jitter.push(t0 + ms(t), period);
+        }
+        assert_eq!(jitter.count(), 5);
+        assert_close(jitter.rms(), ms((3.0f64 / 5.0).sqrt()));
+        assert_close(jitter.max(), ms(1.0));
+
+        // a missed period is compared against the closest grid point
+        let mut jitter = JitterStatistics::default();
+        for t in [0.0, 10.0, 32.0, 38.0, 50.0] {
+            jitter.push(t0 + ms(t), period);
+        }
+        assert_close(jitter.rms(), ms((8.0f64 / 4.0).sqrt()));
+        assert_close(jitter.max(), ms(2.0));
+
+        // a new anchor starts a new grid
+        jitter.reset_anchor();
+        jitter.push(t0 + ms(1005.0), period);
+        jitter.push(t0 + ms(1015.0), period);
+        assert_eq!(jitter.count(), 5);
+        assert_close(jitter.max(), ms(2.0));
+    }
+
+    #[test]
+    fn test_jitter_anchor_reset_after_pause_and_gap() {
+        let t0 = Instant::now();
+        let mut stats = Statistics::new();
+        stats.set_nominal_period(Some(ms(10.0)));
+
+        let step = |stats: &mut Statistics, t: f64| {
+            stats.transitions[Transition::Step].begin_at(t0 + ms(t));
+        };
+
+        step(&mut stats, 0.0);
+        step(&mut stats, 10.0);
+        stats.on_pause();
+        stats.on_resume();
+        step(&mut stats, 55.0);
+        step(&mut stats, 66.0);
+        stats.on_clock_gap();
+        step(&mut stats, 1003.0);
+        step(&mut stats, 1013.0);
+
+        // only 10ms, 66ms and 1013ms are measured
+        let jitter = &stats.transitions[Transition::Step].jitter;
+        assert_eq!(jitter.count(), 3);
+        assert_close(jitter.max(), ms(1.0));
+        assert_close(jitter.rms(), ms((1.0f64 / 3.0).sqrt()));
+    }
+
+    fn step(stats: &mut Statistics, skipped: bool) {
+        let step = &mut stats.transitions[Transition::Step];
+        step.begin();
+        step.end(skipped);
+    }
+
+    #[test]
+    fn test_pause_resume_excluded_from_period() {
+        let mut stats = Statistics::new();
+        for i in 0..3 {
+            step(&mut stats, i == 1);
+        }
+
+        stats.on_pause();
+        std::thread::sleep(Duration::from_millis(50));
+        stats.on_resume();
+
+        step(&mut stats, false);
+        step(&mut stats, false);
+
+        let step = &stats.transitions[Transition::Step];
+        assert_eq!(step.period.count(), 3);
+        assert!(step.period.max_ms().unwrap() < 50.0);
+        assert!(step.resume_gap.unwrap() >= Duration::from_millis(50));
+        assert_eq!((step.pause_count, step.resume_count), (1, 1));
+        assert_eq!(stats.skip_percent(), 0.2);
+    }
+
+    #[test]
+    fn test_consecutive_skipped() {
+        let mut stats = Statistics::new();
+        for skipped in [true, true, false, true, true, true] {
+            step(&mut stats, skipped);
+        }
+
+        let step = &stats.transitions[Transition::Step];
+        assert_eq!(step.skipped_count, 5);
+        assert_eq!(step.consecutive_skipped_count, 3);
+        assert_eq!(
+            step.total_duration(),
+            step.duration.total() + step.skipped_duration
+        );
+    }
+
+    #[test]
+    fn test_clock_gap_excluded_from_period() {
+        let mut stats = Statistics::new();
+        step(&mut stats, false);
+        stats.on_clock_gap();
+        std::thread::sleep(Duration::from_millis(50));
+        step(&mut stats, false);
+        step(&mut stats, false);
+
+        let step = &stats.transitions[Transition::Step];
+        assert_eq!(step.period.count(), 1);
+        assert!(step.period.max_ms().unwrap() < 50.0);
+        assert_eq!(step.resume_gap, None);
+    }
 }
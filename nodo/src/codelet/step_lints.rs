@@ -0,0 +1,299 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+#[cfg(any(debug_assertions, feature = "step-lints"))]
+use crate::channels::{FlushResult, RxBundle, TxBundle};
+use core::{fmt, time::Duration};
+#[cfg(any(debug_assertions, feature = "step-lints"))]
+use std::sync::OnceLock;
+
+/// Number of consecutive steps without pops after which a non-empty RX channel is reported
+pub const UNTOUCHED_RX_STEPS: usize = 5;
+
+/// Steps which take longer than this multiple of the schedule period are reported
+pub const SLOW_STEP_FACTOR: u32 = 10;
+
+/// Environment variable which enables step lints for all codelets if set to "1"
+pub const STEP_LINTS_ENV: &str = "NODO_STEP_LINTS";
+
+/// A common mistake detected while a codelet steps, see `CodeletInstance::with_step_lints`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepLint {
+    /// An RX channel had messages but none were popped for [UNTOUCHED_RX_STEPS] steps
+    UntouchedRx { channel: String },
+
+    /// Pushing to a TX channel failed more than once in a single step because its queue was full
+    TxQueueFull { channel: String, failures: usize },
+
+    /// A step took longer than [SLOW_STEP_FACTOR] times the schedule period
+    SlowStep {
+        duration: Duration,
+        period: Duration,
+    },
+}
+
+impl StepLint {
+    #[cfg(any(debug_assertions, feature = "step-lints"))]
+    fn kind(&self) -> usize {
+        match self {
+            StepLint::UntouchedRx { .. } => 0,
+            StepLint::TxQueueFull { .. } => 1,
+            StepLint::SlowStep { .. } => 2,
+        }
+    }
+}
+
+impl fmt::Display for StepLint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepLint::UntouchedRx { channel } => write!(
+                f,
+                "RX channel '{channel}' had messages but none were popped for \
+                 {UNTOUCHED_RX_STEPS} steps. The queue grows or messages are dropped silently."
+            ),
+            StepLint::TxQueueFull { channel, failures } => write!(
+                f,
+                "{failures} pushes to TX channel '{channel}' failed in one step because the \
+                 queue was full. Increase the capacity or use a forgetting overflow policy."
+            ),
+            StepLint::SlowStep { duration, period } => write!(
+                f,
+                "step took {duration:?} which is more than {SLOW_STEP_FACTOR}x the schedule \
+                 period of {period:?}. Use a step budget or move blocking work to a scoped worker."
+            ),
+        }
+    }
+}
+
+/// True if step lints are enabled for all codelets with [STEP_LINTS_ENV]
+#[cfg(any(debug_assertions, feature = "step-lints"))]
+pub(crate) fn is_enabled_by_env() -> bool {
+    static ENABLED: OnceLock<bool> = OnceLock::new();
+    *ENABLED.get_or_init(|| std::env::var(STEP_LINTS_ENV).is_ok_and(|value| value == "1"))
+}
+
+/// Checks each step of a codelet for common mistakes. Every lint fires at most once.
+#[cfg(any(debug_assertions, feature = "step-lints"))]
+#[derive(Default)]
+pub(crate) struct StepLints {
+    period: Option<Duration>,
+    rx_len_before: Vec<Option<usize>>,
+    untouched_steps: Vec<usize>,
+    fired: Vec<StepLint>,
+}
+
+#[cfg(any(debug_assertions, feature = "step-lints"))]
+impl StepLints {
+    pub fn set_period(&mut self, period: Option<Duration>) {
+        self.period = period;
+    }
+
+    pub fn fired(&self) -> &[StepLint] {
+        &self.fired
+    }
+
+    /// Records the RX queue lengths after sync and before the step
+    pub fn before_step<R: RxBundle>(&mut self, rx: &R) {
+        self.rx_len_before.clear();
+        self.rx_len_before
+            .extend((0..rx.len()).map(|i| rx.queue_len(i)));
+    }
+
+    /// Checks the step of a codelet after it returned and its TX channels were flushed
+    pub fn after_step<R: RxBundle, T: TxBundle>(
+        &mut self,
+        codelet: &str,
+        rx: &R,
+        tx: &T,
+        flush_results: &[FlushResult],
+        duration: Duration,
+    ) {
+        self.untouched_steps.resize(self.rx_len_before.len(), 0);
+        for i in 0..self.rx_len_before.len() {
+            let is_untouched = matches!(
+                self.rx_len_before[i],
+                Some(len) if len > 0 && rx.queue_len(i) == Some(len)
+            );
+            if is_untouched {
+                self.untouched_steps[i] += 1;
+            } else {
+                self.untouched_steps[i] = 0;
+            }
+            if self.untouched_steps[i] >= UNTOUCHED_RX_STEPS {
+                let channel = rx.name(i).into_owned();
+                self.fire(codelet, StepLint::UntouchedRx { channel });
+            }
+        }
+
+        for (i, result) in flush_results.iter().enumerate() {
+            if result.queue_full > 1 {
+                let channel = tx.name(i).into_owned();
+                let failures = result.queue_full;
+                self.fire(codelet, StepLint::TxQueueFull { channel, failures });
+            }
+        }
+
+        if let Some(period) = self.period {
+            if duration > period * SLOW_STEP_FACTOR {
+                self.fire(codelet, StepLint::SlowStep { duration, period });
+            }
+        }
+    }
+
+    fn fire(&mut self, codelet: &str, lint: StepLint) {
+        if self.fired.iter().any(|fired| fired.kind() == lint.kind()) {
+            return;
+        }
+        log::warn!("codelet '{codelet}': {lint}");
+        self.fired.push(lint);
+    }
+}
+
+#[cfg(all(test, any(debug_assertions, feature = "step-lints")))]
+mod tests {
+    use crate::{
+        codelet::{Clocks, CodeletInstance, StepLint, TaskClocks},
+        prelude::*,
+    };
+    use core::time::Duration;
+
+    /// Makes the mistakes detected by step lints on demand
+    #[derive(Default)]
+    struct Sloppy {
+        pop: bool,
+        pushes: usize,
+        sleep: Duration,
+    }
+
+    impl Codelet for Sloppy {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = DoubleBufferRx<u32>;
+        type Tx = DoubleBufferTx<u32>;
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            (DoubleBufferRx::new_auto_size(), DoubleBufferTx::new(1))
+        }
+
+        fn step(&mut self, _: &Context<Self>, rx: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+            if self.pop {
+                rx.pop_all().count();
+            }
+            for i in 0..self.pushes {
+                tx.push(i as u32).ok();
+            }
+            std::thread::sleep(self.sleep);
+            SUCCESS
+        }
+    }
+
+    fn instance(state: Sloppy) -> CodeletInstance<Sloppy> {
+        let mut instance = state.into_instance("sloppy", ()).with_step_lints();
+        instance.clocks = Some(TaskClocks::from(Clocks::new()));
+        instance.start().unwrap();
+        instance
+    }
+
+    #[test]
+    fn test_untouched_rx() {
+        let mut instance = instance(Sloppy::default());
+        let mut source = DoubleBufferTx::new_auto_size();
+        source.connect(&mut instance.rx).unwrap();
+
+        let step = |source: &mut DoubleBufferTx<u32>, instance: &mut CodeletInstance<Sloppy>| {
+            source.push(1).unwrap();
+            source.flush();
+            instance.step().unwrap();
+        };
+
+        for _ in 0..4 {
+            step(&mut source, &mut instance);
+        }
+        assert!(instance.step_lints().is_empty());
+
+        step(&mut source, &mut instance);
+        let expected = [StepLint::UntouchedRx {
+            channel: "in".into(),
+        }];
+        assert_eq!(instance.step_lints(), expected);
+
+        // the lint fires only once
+        for _ in 0..10 {
+            step(&mut source, &mut instance);
+        }
+        assert_eq!(instance.step_lints(), expected);
+
+        // popping does not fire
+        let mut instance = self::instance(Sloppy {
+            pop: true,
+            ..Default::default()
+        });
+        source.connect(&mut instance.rx).unwrap();
+        for _ in 0..10 {
+            step(&mut source, &mut instance);
+        }
+        assert!(instance.step_lints().is_empty());
+    }
+
+    #[test]
+    fn test_tx_queue_full() {
+        // a single failed push is accepted
+        let mut instance = instance(Sloppy {
+            pushes: 2,
+            ..Default::default()
+        });
+        for _ in 0..5 {
+            instance.step().unwrap();
+        }
+        assert!(instance.step_lints().is_empty());
+
+        instance.state.pushes = 3;
+        for _ in 0..5 {
+            instance.step().unwrap();
+        }
+        assert_eq!(
+            instance.step_lints(),
+            [StepLint::TxQueueFull {
+                channel: "out".into(),
+                failures: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn test_slow_step() {
+        let mut instance = instance(Sloppy {
+            sleep: Duration::from_millis(15),
+            ..Default::default()
+        });
+
+        // without a period steps are never too slow
+        instance.step().unwrap();
+        assert!(instance.step_lints().is_empty());
+
+        instance.set_step_lint_period(Some(Duration::from_millis(1)));
+        for _ in 0..3 {
+            instance.step().unwrap();
+        }
+        assert_eq!(instance.step_lints().len(), 1);
+        let StepLint::SlowStep { duration, period } = instance.step_lints()[0] else {
+            panic!("unexpected lint {:?}", instance.step_lints()[0]);
+        };
+        assert!(duration >= Duration::from_millis(15));
+        assert_eq!(period, Duration::from_millis(1));
+    }
+
+    #[test]
+    fn test_step_lints_disabled() {
+        let mut instance = Sloppy {
+            pushes: 3,
+            ..Default::default()
+        }
+        .into_instance("sloppy", ());
+        instance.clocks = Some(TaskClocks::from(Clocks::new()));
+        instance.start().unwrap();
+        for _ in 0..5 {
+            instance.step().unwrap();
+        }
+        assert!(instance.step_lints().is_empty());
+    }
+}
@@ -1,7 +1,13 @@
 // Copyright 2023 by David Weikersdorfer. All rights reserved.
 
+use core::{fmt, time::Duration};
 use nodo_core::{
-    AcqtimeMarker, AppMonotonicClock, Clock, Pubtime, PubtimeMarker, SysMonotonicClock,
+    AcqtimeMarker, AppMonotonicClock, Clock, Pubtime, PubtimeMarker, SysMonotonicClock, TimeAnchor,
+    TimeStandardConfig,
+};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
 };
 
 /// Task clocks used internally
@@ -12,15 +18,101 @@ pub struct Clocks {
 
     /// System-wide monotonic clock (probably) starting when the system boots
     pub sys_mono: SysMonotonicClock<AcqtimeMarker>,
+
+    /// Cycle counter of the schedule. Replaced by each schedule during setup.
+    pub schedule: ScheduleCycle,
+
+    /// Relation of the monotonic clocks to wall-clock time captured when the clocks are created
+    pub anchor: TimeAnchor,
 }
 
 impl Clocks {
     pub fn new() -> Self {
+        Self::from_app_mono(AppMonotonicClock::new())
+    }
+
+    /// Clocks with a manual app clock, see [AppMonotonicClock::new_manual]
+    pub fn new_manual() -> Self {
+        Self::from_app_mono(AppMonotonicClock::new_manual())
+    }
+
+    fn from_app_mono(app_mono: AppMonotonicClock<PubtimeMarker>) -> Self {
+        let sys_mono = SysMonotonicClock::new();
         Self {
-            app_mono: AppMonotonicClock::new(),
-            sys_mono: SysMonotonicClock::new(),
+            schedule: ScheduleCycle::new(app_mono.clone()),
+            anchor: TimeAnchor::capture(app_mono.now(), sys_mono.now()),
+            app_mono,
+            sys_mono,
         }
     }
+
+    /// Enables conversions to TAI and GPS time with the time anchor
+    #[must_use]
+    pub fn with_time_standards(mut self, config: TimeStandardConfig) -> Self {
+        self.anchor = self.anchor.with_time_standards(config);
+        self
+    }
+}
+
+/// Counts the cycles of a schedule and is shared by all codelets in the schedule
+///
+/// The schedule executor advances the counter once before every step of the schedule, thus all
+/// codelets observe the same value during one cycle. Start and stop observe the current value.
+#[derive(Clone)]
+pub struct ScheduleCycle {
+    clock: AppMonotonicClock<PubtimeMarker>,
+    shared: Arc<ScheduleCycleShared>,
+}
+
+#[derive(Default)]
+struct ScheduleCycleShared {
+    index: AtomicU64,
+    epoch_nanos: AtomicU64,
+}
+
+impl ScheduleCycle {
+    pub fn new(clock: AppMonotonicClock<PubtimeMarker>) -> Self {
+        Self {
+            clock,
+            shared: Arc::new(ScheduleCycleShared::default()),
+        }
+    }
+
+    /// Sets the schedule epoch to the current time. Called when the schedule starts.
+    pub fn begin(&self) {
+        self.shared
+            .epoch_nanos
+            .store(self.clock.now().as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Advances the counter. Called once per schedule step.
+    pub fn advance(&self) {
+        self.shared.index.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Index of the current cycle: 0 during start and 1 during the first step
+    pub fn index(&self) -> u64 {
+        self.shared.index.load(Ordering::Relaxed)
+    }
+
+    /// Time at which the schedule started
+    pub fn epoch(&self) -> Pubtime {
+        Duration::from_nanos(self.shared.epoch_nanos.load(Ordering::Relaxed)).into()
+    }
+
+    /// The app clock against which the epoch is measured
+    pub fn app_mono(&self) -> &AppMonotonicClock<PubtimeMarker> {
+        &self.clock
+    }
+}
+
+impl fmt::Debug for ScheduleCycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScheduleCycle")
+            .field("index", &self.index())
+            .field("epoch", &self.epoch())
+            .finish()
+    }
 }
 
 /// Clocks interface exposed to codelet
@@ -35,6 +127,11 @@ pub struct TaskClocks {
     /// Codelet-specific timings
     pub codelet: CodeletClock,
 
+    /// Converts timestamps to wall-clock time, see [TimeAnchor]
+    pub anchor: TimeAnchor,
+
+    schedule: ScheduleCycle,
+
     pub(crate) deprecated_task_clock: TaskClock,
 }
 
@@ -44,10 +141,23 @@ impl TaskClocks {
             app_mono: clocks.app_mono.clone(),
             sys_mono: clocks.sys_mono.clone(),
             codelet: CodeletClock::new(clocks.app_mono.now()),
+            anchor: clocks.anchor,
+            schedule: clocks.schedule,
             deprecated_task_clock: TaskClock::from(clocks.app_mono.clone()),
         }
     }
 
+    /// Index of the current schedule cycle which is the same for all codelets in the schedule,
+    /// see [ScheduleCycle]
+    pub fn cycle_index(&self) -> u64 {
+        self.schedule.index()
+    }
+
+    /// Time at which the schedule started
+    pub fn schedule_epoch(&self) -> Pubtime {
+        self.schedule.epoch()
+    }
+
     pub(crate) fn on_codelet_start(&mut self) {
         let now = self.app_mono.now();
         self.codelet.last = now;
@@ -56,6 +166,12 @@ impl TaskClocks {
 
     pub(crate) fn on_codelet_stop(&mut self) {}
 
+    /// Clamps the time step reported to the codelet
+    pub(crate) fn set_max_dt(&mut self, max_dt: Option<Duration>) {
+        self.codelet.max_dt = max_dt;
+        self.deprecated_task_clock.max_dt = max_dt;
+    }
+
     pub(crate) fn on_codelet_step(&mut self) {
         let now = self.app_mono.now();
         self.codelet.update_dt(now);
@@ -67,15 +183,20 @@ impl TaskClocks {
 pub struct CodeletClock {
     last: Pubtime,
     dt: f32,
+    max_dt: Option<Duration>,
 }
 
 impl CodeletClock {
     pub fn new(now: Pubtime) -> Self {
-        Self { last: now, dt: 0.0 }
+        Self {
+            last: now,
+            dt: 0.0,
+            max_dt: None,
+        }
     }
 
     pub fn update_dt(&mut self, now: Pubtime) {
-        let dt = self.last.abs_diff(now).as_secs_f32();
+        let dt = clamp_dt(self.last.abs_diff(now), self.max_dt);
         self.last = now;
         self.dt = dt;
     }
@@ -98,6 +219,7 @@ pub struct TaskClock {
     clock: AppMonotonicClock<PubtimeMarker>,
     last: Pubtime,
     dt: f32,
+    max_dt: Option<Duration>,
 }
 
 impl TaskClock {
@@ -107,6 +229,7 @@ impl TaskClock {
             clock,
             last,
             dt: 0.0,
+            max_dt: None,
         }
     }
 
@@ -115,7 +238,7 @@ impl TaskClock {
     }
 
     pub(crate) fn step(&mut self, now: Pubtime) {
-        let dt = self.last.abs_diff(now).as_secs_f32();
+        let dt = clamp_dt(self.last.abs_diff(now), self.max_dt);
         self.last = now;
         self.dt = dt;
     }
@@ -142,3 +265,28 @@ impl TaskClock {
         self.dt
     }
 }
+
+fn clamp_dt(dt: Duration, max_dt: Option<Duration>) -> f32 {
+    max_dt.map_or(dt, |max_dt| dt.min(max_dt)).as_secs_f32()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codelet::CodeletClock;
+    use core::time::Duration;
+
+    #[test]
+    fn test_max_dt() {
+        let t = |ms| Duration::from_millis(ms).into();
+
+        let mut clock = CodeletClock::new(t(0));
+        clock.max_dt = Some(Duration::from_millis(50));
+        clock.update_dt(t(20));
+        assert_eq!(clock.dt_secs_f32(), 0.020);
+
+        // a gap of one second is clamped
+        clock.update_dt(t(1020));
+        assert_eq!(clock.dt_secs_f32(), 0.050);
+        assert_eq!(*clock.step_time(), Duration::from_millis(1020));
+    }
+}
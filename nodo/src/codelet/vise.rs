@@ -1,41 +1,125 @@
 // Copyright 2023 by David Weikersdorfer. All rights reserved.
 
-use crate::codelet::{
-    Clocks, Codelet, CodeletInstance, CodeletStatus, Lifecycle, NodeletId, Statistics, TaskClocks,
-    Transition,
+use crate::{
+    channels::{Backlog, ChannelUid, RxBundle, TapPoint},
+    codelet::{
+        Clocks, Codelet, CodeletInstance, CodeletStatus, EndpointInfo, FailureInjector,
+        FailurePlan, Lifecycle, NodeletId, PersistedState, ResourceRegistry, RxChannelStatistics,
+        Statistics, TaskClocks, Transition, FAILURE_INJECTION_LABEL,
+    },
 };
+use core::time::Duration;
 use eyre::Result;
-use nodo_core::{DefaultStatus, OutcomeKind};
+use nodo_core::{DefaultStatus, Name, OutcomeKind, Severity, SkipReason};
+use std::{borrow::Cow, sync::Arc};
 
 /// Wrapper around a codelet with additional information
 pub struct Vise<C: Codelet> {
     instance: CodeletInstance<C>,
     statistics: Statistics,
+    exclude_warmup_statistics: bool,
+    rx_channel_uids: Vec<ChannelUid>,
+    failure_injector: Option<FailureInjector>,
+    type_name: Name,
 }
 
 impl<C: Codelet> Vise<C> {
     pub fn new(mut instance: CodeletInstance<C>) -> Self {
         instance.is_scheduled = true; // TODO is this the right location?
-        Self {
+        let failure_plan = instance.failure_plan.take();
+        let type_name = Name::new(instance.type_name());
+        let mut vise = Self {
             instance,
             statistics: Statistics::new(),
+            exclude_warmup_statistics: false,
+            rx_channel_uids: Vec::new(),
+            failure_injector: None,
+            type_name,
+        };
+        if failure_plan.is_some() {
+            vise.set_failure_plan(failure_plan);
         }
+        vise
     }
 
     pub fn statistics(&self) -> &Statistics {
         &self.statistics
     }
+
+    /// Executes the failure plan before the transition of the codelet
+    fn inject_failure(&mut self, transition: Transition) -> Result<()> {
+        if let Some(injector) = self.failure_injector.as_mut() {
+            injector.inject(&self.instance.name, transition)?;
+        }
+        Ok(())
+    }
+
+    /// Accumulates the results of the last RX sync into the channel statistics
+    fn record_rx_sync(&mut self) {
+        let results = &self.instance.rx_sync_results;
+        let channels = &mut self.statistics.rx_channels;
+        while channels.len() < results.len() {
+            let mut channel =
+                RxChannelStatistics::new(self.instance.rx.name(channels.len()).into_owned());
+            channel.uid = self.rx_channel_uids.get(channels.len()).copied();
+            channels.push(channel);
+        }
+        for (channel, result) in channels.iter_mut().zip(results.iter()) {
+            channel.push(result);
+        }
+    }
 }
 
 impl<C: Codelet> Lifecycle for Vise<C> {
     fn cycle(&mut self, transition: Transition) -> Result<OutcomeKind> {
-        let stats = &mut self.statistics.transitions[transition];
-        stats.begin();
+        if transition == Transition::Step && !self.instance.check_enabled()? {
+            let stats = &mut self.statistics.transitions[transition];
+            stats.begin();
+            stats.end(true);
+            stats.disabled_count += 1;
+            stats.count_skip_reason(Some(SkipReason::Disabled));
+            return Ok(OutcomeKind::Skipped);
+        }
+
+        if self.instance.is_warmup
+            && self.exclude_warmup_statistics
+            && transition == Transition::Step
+        {
+            self.inject_failure(transition)?;
+            return self.instance.cycle(transition);
+        }
 
+        self.statistics.transitions[transition].begin();
+
+        // injected delays are measured like a slow codelet
+        self.inject_failure(transition)?;
+
+        let stats = &mut self.statistics.transitions[transition];
         let outcome = self.instance.cycle(transition)?;
 
         let skipped = outcome == OutcomeKind::Skipped;
-        stats.end(skipped);
+        let duration = stats.end(skipped);
+        if skipped {
+            stats.count_skip_reason(self.instance.skip_reason);
+        } else if transition == Transition::Step
+            && self
+                .instance
+                .deadline
+                .is_some_and(|deadline| duration > deadline)
+        {
+            self.statistics.deadline_miss_count += 1;
+        }
+
+        #[cfg(feature = "scratch")]
+        {
+            self.statistics.scratch_peak_bytes = self.instance.scratch_peak_bytes();
+        }
+
+        match transition {
+            Transition::Pause => self.statistics.on_pause(),
+            Transition::Resume => self.statistics.on_resume(),
+            Transition::Start | Transition::Step | Transition::Stop => self.record_rx_sync(),
+        }
 
         Ok(outcome)
     }
@@ -55,24 +139,118 @@ impl NodeletSetup {
     }
 }
 
+/// Type-erased snapshot of a codelet status
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusInfo {
+    pub label: String,
+    pub status: DefaultStatus,
+    pub code: u32,
+    pub severity: Severity,
+
+    /// Why the codelet skipped, if it skipped and gave a reason
+    pub skip_reason: Option<SkipReason>,
+}
+
+impl StatusInfo {
+    pub fn from_status<S: CodeletStatus>(status: &S) -> Self {
+        Self {
+            label: status.label().to_string(),
+            status: status.as_default_status(),
+            code: status.code(),
+            severity: status.severity(),
+            skip_reason: None,
+        }
+    }
+}
+
 pub trait ViseTrait: Send + Lifecycle {
     /// Unique nodelet ID assigned by the runtime
     fn id(&self) -> NodeletId;
 
     /// Nodelet name assignd by the user
-    fn name(&self) -> &str;
+    fn name(&self) -> &Name;
 
     /// The type name of the codelet as given by Rust compiler
-    fn type_name(&self) -> &str;
+    fn type_name(&self) -> &Name;
 
-    /// Gets the status as a string and the corresponding simplified status
-    fn status(&self) -> Option<(String, DefaultStatus)>;
+    /// Gets the current status of the codelet in a type-erased form
+    fn status(&self) -> Option<StatusInfo>;
 
     /// Called once at the beginning to setup the clock
     fn setup(&mut self, setup: &mut NodeletSetup);
 
     /// Get instantce statistics
     fn statistics(&self) -> &Statistics;
+
+    /// Returns true if all RX channels of the codelet were closed by their transmitters and all
+    /// queued messages were consumed
+    fn is_rx_closed(&self) -> bool;
+
+    /// Labels of the codelet instance
+    fn labels(&self) -> &[String];
+
+    /// Deadline of the codelet instance for a step
+    fn deadline(&self) -> Option<Duration>;
+
+    /// Problems detected during the last start, see `CodeletInstance::start_diagnostics`
+    fn start_diagnostics(&self) -> &[String];
+
+    /// Progress reported by the codelet, see `Context::set_progress`
+    fn progress(&self) -> Option<(f32, &str)>;
+
+    /// Sets whether the schedule is in its warm-up phase and if warm-up steps are excluded from
+    /// statistics
+    fn set_warmup(&mut self, is_warmup: bool, exclude_statistics: bool);
+
+    /// Returns true if the codelet is in the warm-up phase of its schedule
+    fn is_warmup(&self) -> bool;
+
+    /// Clamps the time step seen by the codelet, see `ScheduleBuilder::with_max_dt`
+    fn set_max_dt(&mut self, max_dt: Option<Duration>);
+
+    /// Notifies the codelet that its schedule detected a gap in time, e.g. after a suspend
+    fn on_clock_gap(&mut self);
+
+    /// Sets the period of the schedule against which the step jitter is measured
+    fn set_nominal_period(&mut self, period: Option<Duration>);
+
+    /// Sets whether the codelet is executed as part of a dry run, see `Context::is_dry_run`
+    fn set_dry_run(&mut self, is_dry_run: bool);
+
+    /// Sets the registry from which the codelet acquires resources before it starts
+    fn set_resources(&mut self, resources: Arc<ResourceRegistry>);
+
+    /// Names and connection status of all RX channels
+    fn rx_endpoints(&self) -> Vec<EndpointInfo>;
+
+    /// Names and connection status of all TX channels
+    fn tx_endpoints(&self) -> Vec<EndpointInfo>;
+
+    /// Sets the identifiers of RX channels reported in the channel statistics
+    fn set_rx_channel_uids(&mut self, uids: Vec<ChannelUid>);
+
+    /// Removes the messages still queued in RX channels, see [Backlog]
+    fn take_rx_backlogs(&mut self) -> Vec<(Cow<'static, str>, Backlog)>
+    where
+        Self: 'static;
+
+    /// Back stages of all RX channels to attach snoops, see [TapPoint]
+    fn rx_tap_points(&self) -> Vec<Option<Arc<dyn TapPoint>>>
+    where
+        Self: 'static;
+
+    /// Key under which the codelet state is persisted, if persistence is enabled
+    fn persistence_key(&self) -> Option<&str>;
+
+    /// Saves the codelet state if persistence is enabled
+    fn save_state(&self) -> Result<Option<PersistedState>>;
+
+    /// Restores a saved codelet state
+    fn restore_state(&mut self, state: &PersistedState) -> Result<()>;
+
+    /// Arms a plan of injected failures or disarms it with None. Step counting of the plan starts
+    /// with the next step.
+    fn set_failure_plan(&mut self, plan: Option<FailurePlan>);
 }
 
 impl<C: Codelet> ViseTrait for Vise<C> {
@@ -80,19 +258,27 @@ impl<C: Codelet> ViseTrait for Vise<C> {
         self.instance.id
     }
 
-    fn name(&self) -> &str {
+    fn name(&self) -> &Name {
         &self.instance.name
     }
 
-    fn type_name(&self) -> &str {
-        self.instance.type_name()
+    fn type_name(&self) -> &Name {
+        &self.type_name
     }
 
-    fn status(&self) -> Option<(String, DefaultStatus)> {
-        self.instance
-            .status
-            .as_ref()
-            .map(|s| (s.label().to_string(), s.as_default_status()))
+    fn status(&self) -> Option<StatusInfo> {
+        if self.instance.is_disabled {
+            return Some(StatusInfo {
+                label: "disabled".into(),
+                skip_reason: Some(SkipReason::Disabled),
+                ..StatusInfo::from_status(&DefaultStatus::Skipped)
+            });
+        }
+
+        self.instance.status.as_ref().map(|status| StatusInfo {
+            skip_reason: self.instance.skip_reason,
+            ..StatusInfo::from_status(status)
+        })
     }
 
     fn setup(&mut self, setup: &mut NodeletSetup) {
@@ -103,6 +289,118 @@ impl<C: Codelet> ViseTrait for Vise<C> {
     fn statistics(&self) -> &Statistics {
         &self.statistics
     }
+
+    fn is_rx_closed(&self) -> bool {
+        self.instance.is_rx_closed()
+    }
+
+    fn labels(&self) -> &[String] {
+        self.instance.labels()
+    }
+
+    fn deadline(&self) -> Option<Duration> {
+        self.instance.deadline()
+    }
+
+    fn start_diagnostics(&self) -> &[String] {
+        self.instance.start_diagnostics()
+    }
+
+    fn progress(&self) -> Option<(f32, &str)> {
+        self.instance.progress()
+    }
+
+    fn set_warmup(&mut self, is_warmup: bool, exclude_statistics: bool) {
+        self.instance.is_warmup = is_warmup;
+        self.exclude_warmup_statistics = exclude_statistics;
+    }
+
+    fn is_warmup(&self) -> bool {
+        self.instance.is_warmup
+    }
+
+    fn set_max_dt(&mut self, max_dt: Option<Duration>) {
+        if let Some(clocks) = self.instance.clocks.as_mut() {
+            clocks.set_max_dt(max_dt);
+        }
+    }
+
+    fn on_clock_gap(&mut self) {
+        self.statistics.on_clock_gap();
+    }
+
+    fn set_nominal_period(&mut self, period: Option<Duration>) {
+        self.statistics.set_nominal_period(period);
+        self.instance.set_step_lint_period(period);
+    }
+
+    fn set_dry_run(&mut self, is_dry_run: bool) {
+        self.instance.is_dry_run = is_dry_run;
+    }
+
+    fn set_resources(&mut self, resources: Arc<ResourceRegistry>) {
+        self.instance.resources = resources;
+    }
+
+    fn rx_endpoints(&self) -> Vec<EndpointInfo> {
+        self.instance.rx_endpoints()
+    }
+
+    fn tx_endpoints(&self) -> Vec<EndpointInfo> {
+        self.instance.tx_endpoints()
+    }
+
+    fn set_rx_channel_uids(&mut self, uids: Vec<ChannelUid>) {
+        for (channel, uid) in self.statistics.rx_channels.iter_mut().zip(uids.iter()) {
+            channel.uid = Some(*uid);
+        }
+        self.rx_channel_uids = uids;
+    }
+
+    fn take_rx_backlogs(&mut self) -> Vec<(Cow<'static, str>, Backlog)>
+    where
+        Self: 'static,
+    {
+        self.instance.take_rx_backlogs()
+    }
+
+    fn rx_tap_points(&self) -> Vec<Option<Arc<dyn TapPoint>>>
+    where
+        Self: 'static,
+    {
+        self.instance.rx_tap_points()
+    }
+
+    fn persistence_key(&self) -> Option<&str> {
+        self.instance.persistence_key()
+    }
+
+    fn save_state(&self) -> Result<Option<PersistedState>> {
+        self.instance.save_state()
+    }
+
+    fn restore_state(&mut self, state: &PersistedState) -> Result<()> {
+        self.instance.restore_state(state)
+    }
+
+    fn set_failure_plan(&mut self, plan: Option<FailurePlan>) {
+        let labels = &mut self.instance.labels;
+        labels.retain(|label| label != FAILURE_INJECTION_LABEL);
+        match &plan {
+            Some(plan) => {
+                log::warn!(
+                    "{FAILURE_INJECTION_LABEL} armed failure plan for codelet '{}': {plan:?}",
+                    self.instance.name
+                );
+                labels.push(FAILURE_INJECTION_LABEL.into());
+            }
+            None => log::info!(
+                "{FAILURE_INJECTION_LABEL} disarmed failure plan for codelet '{}'",
+                self.instance.name
+            ),
+        }
+        self.failure_injector = plan.map(FailureInjector::new);
+    }
 }
 
 pub struct DynamicVise(pub(crate) Box<dyn ViseTrait>);
@@ -118,15 +416,15 @@ impl ViseTrait for DynamicVise {
         self.0.id()
     }
 
-    fn name(&self) -> &str {
+    fn name(&self) -> &Name {
         self.0.name()
     }
 
-    fn type_name(&self) -> &str {
+    fn type_name(&self) -> &Name {
         self.0.type_name()
     }
 
-    fn status(&self) -> Option<(String, DefaultStatus)> {
+    fn status(&self) -> Option<StatusInfo> {
         self.0.status()
     }
 
@@ -137,6 +435,90 @@ impl ViseTrait for DynamicVise {
     fn statistics(&self) -> &Statistics {
         self.0.statistics()
     }
+
+    fn is_rx_closed(&self) -> bool {
+        self.0.is_rx_closed()
+    }
+
+    fn labels(&self) -> &[String] {
+        self.0.labels()
+    }
+
+    fn deadline(&self) -> Option<Duration> {
+        self.0.deadline()
+    }
+
+    fn start_diagnostics(&self) -> &[String] {
+        self.0.start_diagnostics()
+    }
+
+    fn progress(&self) -> Option<(f32, &str)> {
+        self.0.progress()
+    }
+
+    fn set_warmup(&mut self, is_warmup: bool, exclude_statistics: bool) {
+        self.0.set_warmup(is_warmup, exclude_statistics);
+    }
+
+    fn is_warmup(&self) -> bool {
+        self.0.is_warmup()
+    }
+
+    fn set_max_dt(&mut self, max_dt: Option<Duration>) {
+        self.0.set_max_dt(max_dt);
+    }
+
+    fn on_clock_gap(&mut self) {
+        self.0.on_clock_gap();
+    }
+
+    fn set_nominal_period(&mut self, period: Option<Duration>) {
+        self.0.set_nominal_period(period);
+    }
+
+    fn set_dry_run(&mut self, is_dry_run: bool) {
+        self.0.set_dry_run(is_dry_run);
+    }
+
+    fn set_resources(&mut self, resources: Arc<ResourceRegistry>) {
+        self.0.set_resources(resources);
+    }
+
+    fn rx_endpoints(&self) -> Vec<EndpointInfo> {
+        self.0.rx_endpoints()
+    }
+
+    fn tx_endpoints(&self) -> Vec<EndpointInfo> {
+        self.0.tx_endpoints()
+    }
+
+    fn set_rx_channel_uids(&mut self, uids: Vec<ChannelUid>) {
+        self.0.set_rx_channel_uids(uids);
+    }
+
+    fn take_rx_backlogs(&mut self) -> Vec<(Cow<'static, str>, Backlog)> {
+        self.0.take_rx_backlogs()
+    }
+
+    fn rx_tap_points(&self) -> Vec<Option<Arc<dyn TapPoint>>> {
+        self.0.rx_tap_points()
+    }
+
+    fn persistence_key(&self) -> Option<&str> {
+        self.0.persistence_key()
+    }
+
+    fn save_state(&self) -> Result<Option<PersistedState>> {
+        self.0.save_state()
+    }
+
+    fn restore_state(&mut self, state: &PersistedState) -> Result<()> {
+        self.0.restore_state(state)
+    }
+
+    fn set_failure_plan(&mut self, plan: Option<FailurePlan>) {
+        self.0.set_failure_plan(plan);
+    }
 }
 
 impl Lifecycle for DynamicVise {
@@ -144,3 +526,179 @@ impl Lifecycle for DynamicVise {
         self.0.cycle(transition)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        codelet::{
+            Clocks, DisabledRxPolicy, Lifecycle, NodeletId, NodeletSetup, Transition, Vise,
+            ViseTrait, WorkerId,
+        },
+        prelude::*,
+    };
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    };
+
+    /// Records all received messages
+    struct Recorder {
+        received: Arc<Mutex<Vec<u32>>>,
+    }
+
+    impl Codelet for Recorder {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = DoubleBufferRx<u32>;
+        type Tx = ();
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            (DoubleBufferRx::new_auto_size(), ())
+        }
+
+        fn step(&mut self, _: &Context<Self>, rx: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            self.received.lock().unwrap().extend(rx.pop_all());
+            SUCCESS
+        }
+    }
+
+    fn publish_and_step(
+        tx: &mut DoubleBufferTx<u32>,
+        vise: &mut Vise<Recorder>,
+        values: &[u32],
+    ) -> OutcomeKind {
+        for &v in values {
+            tx.push(v).unwrap();
+        }
+        tx.flush();
+        vise.cycle(Transition::Step).unwrap()
+    }
+
+    fn run_toggle(policy: DisabledRxPolicy) -> Vec<u32> {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let enabled = Arc::new(AtomicBool::new(true));
+
+        let mut tx = DoubleBufferTx::new(4);
+        let mut recorder = Recorder {
+            received: received.clone(),
+        }
+        .into_instance("recorder", ())
+        .with_enable(enabled.clone())
+        .with_disabled_rx_policy(policy);
+        tx.connect(&mut recorder.rx).unwrap();
+
+        let mut vise = Vise::new(recorder);
+        vise.setup(&mut NodeletSetup {
+            clocks: Clocks::new(),
+            nodelet_id_issue: NodeletId(WorkerId(0), 0),
+        });
+
+        vise.cycle(Transition::Start).unwrap();
+        assert_eq!(
+            publish_and_step(&mut tx, &mut vise, &[1]),
+            OutcomeKind::Running
+        );
+
+        enabled.store(false, Ordering::Relaxed);
+        assert_eq!(
+            publish_and_step(&mut tx, &mut vise, &[2, 3]),
+            OutcomeKind::Skipped
+        );
+        assert_eq!(
+            publish_and_step(&mut tx, &mut vise, &[4]),
+            OutcomeKind::Skipped
+        );
+        assert_eq!(vise.status().unwrap().label, "disabled");
+
+        enabled.store(true, Ordering::Relaxed);
+        assert_eq!(
+            publish_and_step(&mut tx, &mut vise, &[5]),
+            OutcomeKind::Running
+        );
+        assert_ne!(vise.status().unwrap().label, "disabled");
+        vise.cycle(Transition::Stop).unwrap();
+
+        let stats = &vise.statistics().transitions[Transition::Step];
+        assert_eq!(stats.duration.count(), 2);
+        assert_eq!(stats.skipped_count, 2);
+        assert_eq!(stats.disabled_count, 2);
+
+        let result = received.lock().unwrap().clone();
+        result
+    }
+
+    #[test]
+    fn test_disabled_drop() {
+        assert_eq!(run_toggle(DisabledRxPolicy::DropWhileDisabled), [1, 5]);
+    }
+
+    #[test]
+    fn test_disabled_hold() {
+        assert_eq!(
+            run_toggle(DisabledRxPolicy::HoldWhileDisabled),
+            [1, 2, 3, 4, 5]
+        );
+    }
+
+    /// Skips with a different reason depending on the step
+    struct Picky {
+        step: usize,
+    }
+
+    impl Codelet for Picky {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = ();
+        type Tx = ();
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            ((), ())
+        }
+
+        fn step(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            self.step += 1;
+            match self.step % 4 {
+                0 => SUCCESS,
+                1 => cx.skip_because(SkipReason::NoInput),
+                2 => cx.skip_because(SkipReason::Custom("calibrating")),
+                _ => SKIPPED,
+            }
+        }
+    }
+
+    #[test]
+    fn test_skip_reason() {
+        let mut vise = Vise::new(Picky { step: 0 }.into_instance("picky", ()));
+        vise.setup(&mut NodeletSetup {
+            clocks: Clocks::new(),
+            nodelet_id_issue: NodeletId(WorkerId(0), 0),
+        });
+
+        vise.cycle(Transition::Start).unwrap();
+        let mut reasons = Vec::new();
+        for _ in 0..8 {
+            vise.cycle(Transition::Step).unwrap();
+            reasons.push(vise.status().unwrap().skip_reason);
+        }
+        assert_eq!(
+            reasons[..4],
+            [
+                Some(SkipReason::NoInput),
+                Some(SkipReason::Custom("calibrating")),
+                None,
+                None
+            ]
+        );
+        assert_eq!(vise.status().unwrap().status, DefaultStatus::Running);
+
+        let stats = &vise.statistics().transitions[Transition::Step];
+        assert_eq!(stats.skipped_count, 6);
+        assert_eq!(
+            stats.skipped_by_reason,
+            [("calibrating", 2), ("no input", 2), ("unspecified", 2)]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect()
+        );
+    }
+}
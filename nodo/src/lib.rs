@@ -7,18 +7,21 @@ pub mod runtime_control;
 pub mod prelude {
     pub use crate::{
         channels::{
-            connect, Connect, DoubleBufferRx, DoubleBufferTx, OverflowPolicy, Pop, RetentionPolicy,
-            Rx, Timeseries, Tx,
+            connect, Connect, DoubleBufferRx, DoubleBufferTx, OverflowPolicy, Pop, PopSyncedByTime,
+            RetentionPolicy, Rx, SpscRx, SpscTx, Timeseries, TimeseriesStatsExt, Tx,
         },
         codelet::{
-            Codelet, CodeletStatus, Context, Instantiate, IntoInstance, Schedulable, Sequence,
-            Sequenceable,
+            Codelet, CodeletStatus, ConfigError, Context, Instantiate, IntoInstance, NodoConfig,
+            Schedulable, Sequence, Sequenceable,
         },
-        runtime_control::RuntimeControl,
+        runtime_control::{ControlHandle, RuntimeControl},
     };
     pub use nodo_core::{
-        Acqtime, Clock, DefaultStatus, Message, Outcome, OutcomeKind, Pubtime, Stamp, WithAcqtime,
-        RUNNING, SKIPPED, SUCCESS,
+        Acqtime, Clock, DefaultStatus, Message, Outcome, OutcomeKind, Pubtime, SelfDescribing,
+        Severity, SkipReason, Stamp, WithAcqtime, RUNNING, SKIPPED, SUCCESS,
+    };
+    pub use nodo_derive::{
+        nodo_graph, EnumSplitDerive, NodoConfig, RxBundleDerive, SelfDescribing, Status,
+        TxBundleDerive,
     };
-    pub use nodo_derive::{RxBundleDerive, Status, TxBundleDerive};
 }
@@ -1,6 +1,105 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{SyncSender, TrySendError},
+    Arc,
+};
+
 #[derive(Debug, Clone, Copy)]
 pub enum RuntimeControl {
     /// Request the runtime to stop. It may take a while for the runtime to shut down as codelets
     /// will finish stepping and stop will be called for all active codelets.
     RequestStop,
 }
+
+/// Error returned by [ControlHandle] if the runtime does not exist anymore
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("runtime control channel disconnected")]
+pub struct ControlDisconnectedError;
+
+/// Cheap cloneable handle to control the runtime from codelets or other threads
+///
+/// Requests are idempotent and never block: only the first stop request is forwarded to the
+/// runtime and later requests are no-ops.
+#[derive(Debug, Clone)]
+pub struct ControlHandle {
+    tx: SyncSender<RuntimeControl>,
+    stop_requested: Arc<AtomicBool>,
+}
+
+impl ControlHandle {
+    pub fn new(tx: SyncSender<RuntimeControl>) -> Self {
+        Self {
+            tx,
+            stop_requested: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Requests the runtime to stop, see [RuntimeControl::RequestStop]
+    pub fn request_stop(&self) -> Result<(), ControlDisconnectedError> {
+        if self.stop_requested.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+
+        match self.tx.try_send(RuntimeControl::RequestStop) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                // The runtime also polls the flag, thus the request is only delayed.
+                log::warn!("runtime control channel is full: stop request is delayed");
+                Ok(())
+            }
+            Err(TrySendError::Disconnected(_)) => Err(ControlDisconnectedError),
+        }
+    }
+
+    /// True if a stop was requested through any clone of this handle
+    pub fn stop_requested(&self) -> bool {
+        self.stop_requested.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime_control::{ControlDisconnectedError, ControlHandle};
+    use std::sync::mpsc::sync_channel;
+
+    #[test]
+    fn test_request_stop_from_threads() {
+        let (tx, rx) = sync_channel(16);
+        let handle = ControlHandle::new(tx);
+        assert!(!handle.stop_requested());
+
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let handle = handle.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        assert_eq!(handle.request_stop(), Ok(()));
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert!(handle.stop_requested());
+        assert_eq!(rx.try_iter().count(), 1);
+    }
+
+    #[test]
+    fn test_request_stop_full_or_disconnected() {
+        // a full channel does not block and the request is still observable via the flag
+        let (tx, rx) = sync_channel(0);
+        let handle = ControlHandle::new(tx);
+        assert_eq!(handle.request_stop(), Ok(()));
+        assert!(handle.stop_requested());
+        drop(rx);
+
+        let (tx, rx) = sync_channel(1);
+        drop(rx);
+        assert_eq!(
+            ControlHandle::new(tx).request_stop(),
+            Err(ControlDisconnectedError)
+        );
+    }
+}
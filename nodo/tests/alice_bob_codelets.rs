@@ -2,7 +2,7 @@
 
 use nodo::{codelet::ScheduleBuilder, prelude::*};
 use nodo_runtime::{Runtime, ScheduleExecutor};
-use nodo_std::Terminator;
+use nodo_std::{Merge, MergeConfig, Terminator};
 use std::time::Duration;
 
 mod common;
@@ -85,6 +85,42 @@ impl Codelet for Bob {
     }
 }
 
+/// Like Bob but receives the pings of two Alices every step
+struct MergedBob {
+    num_recv: usize,
+}
+
+impl Codelet for MergedBob {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = BobRx;
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (
+            BobRx {
+                ping: DoubleBufferRx::new_auto_size(),
+            },
+            (),
+        )
+    }
+
+    fn step(&mut self, _: &Context<Self>, rx: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        assert_eq!(rx.ping.len(), 2);
+        let expected = format!("hello_{}", self.num_recv / 2);
+        for _ in 0..2 {
+            assert_eq!(rx.ping.pop()?.0, expected);
+            self.num_recv += 1;
+        }
+        SUCCESS
+    }
+
+    fn stop(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        assert_eq!(self.num_recv, 2 * NUM_MESSAGES);
+        SUCCESS
+    }
+}
+
 use std::sync::Once;
 
 static INIT: Once = Once::new();
@@ -106,19 +142,18 @@ fn test_schedule(schedule: ScheduleExecutor) {
 fn alice_bob_codelets() {
     init_reporting();
 
-    let mut alice = Alice { num_sent: 0 }.into_instance("alice", ());
-    let mut bob = Bob { num_recv: 0 }.into_instance("bob", ());
-
-    alice.tx.ping.connect(&mut bob.rx.ping).unwrap();
+    let schedule = nodo_graph! {
+        alice: Alice = Alice { num_sent: 0 };
+        bob: Bob = Bob { num_recv: 0 };
+        alice.tx.ping -> bob.rx.ping;
+        schedule {
+            period: Duration::from_millis(2),
+            max_step_count: NUM_MESSAGES,
+        }
+    }
+    .unwrap();
 
-    test_schedule(
-        ScheduleBuilder::new()
-            .with_period(Duration::from_millis(2))
-            .with_max_step_count(NUM_MESSAGES)
-            .with(alice)
-            .with(bob)
-            .into(),
-    );
+    test_schedule(schedule.into());
 }
 
 #[test]
@@ -127,7 +162,8 @@ fn alice_bob_codelets_with_terminator() {
 
     let mut rt = Runtime::new();
 
-    let term = Terminator::new(NUM_MESSAGES - 1, rt.tx_control()).into_instance("terminator", ());
+    let term =
+        Terminator::new(NUM_MESSAGES - 1, rt.control_handle()).into_instance("terminator", ());
     let mut alice = Alice { num_sent: 0 }.into_instance("alice", ());
     let mut bob = Bob { num_recv: 0 }.into_instance("bob", ());
 
@@ -191,3 +227,41 @@ fn alice_many_bobs_codelets() {
 
     test_schedule(schedule.into());
 }
+
+#[test]
+fn double_alice_merge_bob_codelets() {
+    init_reporting();
+
+    let mut rt = Runtime::new();
+
+    let term =
+        Terminator::new(NUM_MESSAGES - 1, rt.control_handle()).into_instance("terminator", ());
+    let mut alice_1 = Alice { num_sent: 0 }.into_instance("alice 1", ());
+    let mut alice_2 = Alice { num_sent: 0 }.into_instance("alice 2", ());
+    let mut merge = Merge::default().into_instance("merge", MergeConfig::default());
+    let mut bob = MergedBob { num_recv: 0 }.into_instance("bob", ());
+
+    alice_1.tx.ping.connect(merge.rx.new_channel_mut()).unwrap();
+    alice_2.tx.ping.connect(merge.rx.new_channel_mut()).unwrap();
+    merge.tx.connect(&mut bob.rx.ping).unwrap();
+
+    // bob can only be connected to one transmitter
+    assert!(alice_1.tx.ping.connect(&mut bob.rx.ping).is_err());
+
+    // an unconnected input does not stop the merge
+    merge.rx.new_channel_mut();
+    assert_eq!(merge.rx.connected_count(), 2);
+
+    rt.add_codelet_schedule(
+        ScheduleBuilder::new()
+            .with_period(Duration::from_millis(2))
+            .with(term)
+            .with(alice_1)
+            .with(alice_2)
+            .with(merge)
+            .with(bob)
+            .into(),
+    );
+
+    rt.spin();
+}
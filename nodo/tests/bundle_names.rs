@@ -0,0 +1,170 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use nodo::{
+    channels::{ConnectionCheck, RxBundle, SyncResult, TxBundle},
+    prelude::*,
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    borrow::Cow,
+    cell::Cell,
+    sync::OnceLock,
+};
+
+/// Counts allocations made by the current thread
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Number of allocations made by the current thread while running `f`
+fn count_allocations<R>(f: impl FnOnce() -> R) -> (usize, R) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (ALLOCATIONS.with(Cell::get) - before, result)
+}
+
+#[derive(RxBundleDerive)]
+struct PairRx {
+    left: DoubleBufferRx<u32>,
+    right: DoubleBufferRx<u32>,
+}
+
+#[derive(TxBundleDerive)]
+struct PairTx {
+    out: DoubleBufferTx<u32>,
+}
+
+#[test]
+fn test_derived_names_are_static() {
+    let rx = PairRx {
+        left: DoubleBufferRx::new_auto_size(),
+        right: DoubleBufferRx::new_auto_size(),
+    };
+    let tx = PairTx {
+        out: DoubleBufferTx::new_auto_size(),
+    };
+
+    let (count, names) = count_allocations(|| [rx.name(0), rx.name(1), tx.name(0)]);
+    assert_eq!(count, 0);
+
+    let names: Vec<&'static str> = names
+        .into_iter()
+        .map(|name| match name {
+            Cow::Borrowed(name) => name,
+            Cow::Owned(name) => panic!("derived name '{name}' was allocated"),
+        })
+        .collect();
+    assert_eq!(names, ["left", "right", "out"]);
+}
+
+/// Number of channels of the synthetic bundle
+const WIDE_CHANNEL_COUNT: usize = 100;
+
+/// A synthetic bundle with many channels which either has static or allocated names
+struct WideRx {
+    channels: Vec<DoubleBufferRx<u32>>,
+    has_static_names: bool,
+}
+
+fn wide_name(index: usize) -> String {
+    format!("channel_{index}")
+}
+
+fn static_wide_names() -> &'static [&'static str] {
+    static NAMES: OnceLock<Vec<&'static str>> = OnceLock::new();
+    NAMES.get_or_init(|| {
+        (0..WIDE_CHANNEL_COUNT)
+            .map(|i| &*Box::leak(wide_name(i).into_boxed_str()))
+            .collect()
+    })
+}
+
+impl RxBundle for WideRx {
+    fn len(&self) -> usize {
+        self.channels.len()
+    }
+
+    fn name(&self, index: usize) -> Cow<'static, str> {
+        if self.has_static_names {
+            Cow::Borrowed(static_wide_names()[index])
+        } else {
+            Cow::Owned(wide_name(index))
+        }
+    }
+
+    fn sync_all(&mut self, results: &mut [SyncResult]) {
+        for (result, channel) in results.iter_mut().zip(self.channels.iter_mut()) {
+            *result = channel.sync();
+        }
+    }
+
+    fn check_connection(&self) -> ConnectionCheck {
+        let mut cc = ConnectionCheck::new(self.channels.len());
+        for (i, channel) in self.channels.iter().enumerate() {
+            cc.mark(i, channel.is_connected());
+        }
+        cc
+    }
+}
+
+struct Wide;
+
+impl Codelet for Wide {
+    type Status = DefaultStatus;
+    type Config = bool;
+    type Rx = WideRx;
+    type Tx = ();
+
+    fn build_bundles(has_static_names: &bool) -> (Self::Rx, Self::Tx) {
+        (
+            WideRx {
+                channels: (0..WIDE_CHANNEL_COUNT)
+                    .map(|_| DoubleBufferRx::new_auto_size())
+                    .collect(),
+                has_static_names: *has_static_names,
+            },
+            (),
+        )
+    }
+
+    fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        SUCCESS
+    }
+}
+
+#[test]
+fn test_endpoint_report_allocations() {
+    // initialize the static names outside of the measurement
+    static_wide_names();
+
+    let owned = Wide.into_instance("owned", false);
+    let borrowed = Wide.into_instance("borrowed", true);
+
+    let (owned_count, owned_endpoints) = count_allocations(|| owned.rx_endpoints());
+    let (borrowed_count, borrowed_endpoints) = count_allocations(|| borrowed.rx_endpoints());
+
+    // every allocated name costs one allocation per report in addition to the endpoint list and
+    // the connection flags of channels beyond the first 64
+    assert_eq!(owned_count, WIDE_CHANNEL_COUNT + 2);
+    assert_eq!(borrowed_count, 2);
+
+    assert_eq!(owned_endpoints.len(), WIDE_CHANNEL_COUNT);
+    assert_eq!(borrowed_endpoints[42].name, "channel_42");
+    assert_eq!(owned_endpoints[42].name, borrowed_endpoints[42].name);
+}
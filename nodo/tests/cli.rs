@@ -0,0 +1,74 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use clap::Parser;
+use nodo::{codelet::ScheduleBuilder, prelude::*};
+use nodo_runtime::{DryRunError, LaunchMode, NodoArgs, Runtime};
+use nodo_std::{Sink, Source};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+#[test]
+fn test_list_codelets() {
+    let args = NodoArgs::try_parse_from(["app", "--list-codelets"]).unwrap();
+    let mut rt = Runtime::new();
+    args.apply(&mut rt).unwrap();
+    assert_eq!(rt.launch_mode(), LaunchMode::ListCodelets);
+
+    let steps = Arc::new(AtomicUsize::new(0));
+
+    let mut source = Source::new({
+        let steps = steps.clone();
+        move || steps.fetch_add(1, Ordering::Relaxed)
+    })
+    .into_instance("source", ());
+    let mut sink = Sink::new(|_: usize| SUCCESS).into_instance("sink", ());
+    let idle = Sink::new(|_: usize| SUCCESS).into_instance("idle", ());
+    source.tx.connect(&mut sink.rx).unwrap();
+
+    rt.add_codelet_schedule(
+        ScheduleBuilder::new()
+            .with_name("main")
+            .with(source)
+            .with(sink)
+            .with(idle)
+            .into(),
+    );
+
+    // spin returns immediately without running any codelet
+    rt.spin();
+    assert_eq!(steps.load(Ordering::Relaxed), 0);
+
+    assert_eq!(
+        rt.manifold().to_string(),
+        "main/source (Source) rx: [] tx: [out]\n\
+         main/sink (Sink) rx: [in] tx: []\n\
+         main/idle (Sink) rx: [in?] tx: []\n"
+    );
+}
+
+#[test]
+fn test_dry_run_failure() {
+    let args = NodoArgs::try_parse_from(["app", "--dry-run"]).unwrap();
+    let mut rt = Runtime::new();
+    args.apply(&mut rt).unwrap();
+
+    let mut source = Source::new(|| -> usize { 0 }).into_instance("source", ());
+    let mut broken = Sink::new(|_: usize| eyre::bail!("misconfigured")).into_instance("broken", ());
+    source.tx.connect(&mut broken.rx).unwrap();
+
+    rt.add_codelet_schedule(
+        ScheduleBuilder::new()
+            .with_name("main")
+            .with(source)
+            .with(broken)
+            .into(),
+    );
+
+    let err = rt.dry_run_result().unwrap_err();
+    let err = err.downcast_ref::<DryRunError>().unwrap();
+    let failures = err.reports[0].failures().collect::<Vec<_>>();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].name, "broken");
+}
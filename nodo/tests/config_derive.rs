@@ -0,0 +1,109 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use nodo::prelude::*;
+
+#[derive(NodoConfig)]
+struct FilterConfig {
+    /// Number of samples to keep
+    #[config(default = 10, min = 1, max = 100)]
+    window: usize,
+
+    #[config(default = 0.5, min = 0.0, max = 1.0, doc = "smoothing factor")]
+    alpha: f32,
+
+    #[config(default = -5, min = -10)]
+    offset: i32,
+
+    #[config(default = "camera")]
+    topic: String,
+
+    enabled: bool,
+}
+
+#[test]
+fn config_derive_default() {
+    let cfg = FilterConfig::default();
+    assert_eq!(cfg.window, 10);
+    assert_eq!(cfg.alpha, 0.5);
+    assert_eq!(cfg.offset, -5);
+    assert_eq!(cfg.topic, "camera");
+    assert!(!cfg.enabled);
+    assert_eq!(cfg.validate(), Ok(()));
+}
+
+#[test]
+fn config_derive_validate_boundaries() {
+    let mut cfg = FilterConfig {
+        window: 1,
+        ..Default::default()
+    };
+    assert!(cfg.validate().is_ok());
+    cfg.window = 100;
+    assert!(cfg.validate().is_ok());
+    cfg.window = 0;
+    assert_eq!(
+        cfg.validate(),
+        Err(ConfigError::OutOfRange {
+            field: "window",
+            value: "0".into(),
+            min: Some("1".into()),
+            max: Some("100".into()),
+        })
+    );
+    cfg.window = 101;
+    assert!(cfg.validate().is_err());
+    cfg.window = 10;
+
+    cfg.alpha = 0.0;
+    assert!(cfg.validate().is_ok());
+    cfg.alpha = 1.0;
+    assert!(cfg.validate().is_ok());
+    cfg.alpha = 1.01;
+    assert!(cfg.validate().is_err());
+    cfg.alpha = -0.01;
+    assert!(cfg.validate().is_err());
+    cfg.alpha = 0.5;
+
+    cfg.offset = -10;
+    assert!(cfg.validate().is_ok());
+    cfg.offset = i32::MAX;
+    assert!(cfg.validate().is_ok());
+    cfg.offset = -11;
+    assert_eq!(
+        cfg.validate(),
+        Err(ConfigError::OutOfRange {
+            field: "offset",
+            value: "-11".into(),
+            min: Some("-10".into()),
+            max: None,
+        })
+    );
+}
+
+#[test]
+fn config_derive_describe() {
+    let fields = FilterConfig::describe();
+    assert_eq!(fields.len(), 5);
+
+    assert_eq!(fields[0].name, "window");
+    assert_eq!(fields[0].type_name, "usize");
+    assert_eq!(fields[0].default.as_deref(), Some("10"));
+    assert_eq!(fields[0].min.as_deref(), Some("1"));
+    assert_eq!(fields[0].max.as_deref(), Some("100"));
+    assert_eq!(fields[0].doc.as_deref(), Some("Number of samples to keep"));
+
+    assert_eq!(fields[1].doc.as_deref(), Some("smoothing factor"));
+    assert_eq!(fields[2].default.as_deref(), Some("-5"));
+    assert_eq!(fields[3].default.as_deref(), Some("camera"));
+
+    assert_eq!(fields[4].name, "enabled");
+    assert_eq!(fields[4].default, None);
+    assert_eq!(fields[4].min, None);
+    assert_eq!(fields[4].doc, None);
+}
+
+#[test]
+fn config_derive_ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/config_*.rs");
+}
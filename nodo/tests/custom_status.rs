@@ -21,6 +21,10 @@ enum PingerStatus {
 
     #[label = "ping"]
     Pinging(usize),
+
+    #[code = 42]
+    #[severity = "error"]
+    Jammed,
 }
 
 #[derive(TxBundleDerive)]
@@ -59,7 +63,7 @@ impl Codelet for Pinger {
 fn test_custom_status() {
     let mut rt = Runtime::new();
 
-    let term = Terminator::new(100, rt.tx_control()).into_instance("terminator", ());
+    let term = Terminator::new(100, rt.control_handle()).into_instance("terminator", ());
 
     let alice = Pinger { num_sent: 0 }.into_instance("alice", ());
 
@@ -73,3 +77,29 @@ fn test_custom_status() {
 
     rt.spin();
 }
+
+#[test]
+fn test_status_code_and_severity() {
+    assert_eq!(PingerStatus::Idle.code(), 0);
+    assert_eq!(PingerStatus::Pinging(3).code(), 1);
+    assert_eq!(PingerStatus::Jammed.code(), 42);
+
+    assert_eq!(PingerStatus::Idle.severity(), Severity::Warn);
+    assert_eq!(PingerStatus::Pinging(3).severity(), Severity::Info);
+    assert_eq!(PingerStatus::Jammed.severity(), Severity::Error);
+    assert_eq!(
+        PingerStatus::Jammed.as_default_status(),
+        DefaultStatus::Running
+    );
+
+    assert_eq!(DefaultStatus::Skipped.code(), 0);
+    assert_eq!(DefaultStatus::Running.code(), 1);
+    assert_eq!(DefaultStatus::Skipped.severity(), Severity::Warn);
+    assert_eq!(DefaultStatus::Running.severity(), Severity::Info);
+}
+
+#[test]
+fn test_status_derive_ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/status_*.rs");
+}
@@ -0,0 +1,91 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use nodo::{
+    codelet::{Clocks, NodeletId, NodeletSetup, ScheduleBuilder, WorkerId},
+    prelude::*,
+};
+use nodo_runtime::ScheduleExecutor;
+use std::sync::{Arc, Mutex};
+
+const NUM_CYCLES: u64 = 5;
+
+/// Records the cycle index observed in every transition
+struct CycleProbe {
+    log: Arc<Mutex<Vec<(&'static str, u64)>>>,
+}
+
+impl CycleProbe {
+    fn record(&self, cx: &Context<Self>, transition: &'static str) {
+        assert!(*cx.clocks.schedule_epoch() <= *cx.clocks.app_mono.now());
+        self.log
+            .lock()
+            .unwrap()
+            .push((transition, cx.clocks.cycle_index()));
+    }
+}
+
+impl Codelet for CycleProbe {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), ())
+    }
+
+    fn start(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.record(cx, "start");
+        SUCCESS
+    }
+
+    fn step(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.record(cx, "step");
+        SUCCESS
+    }
+
+    fn stop(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.record(cx, "stop");
+        SUCCESS
+    }
+}
+
+#[test]
+fn test_codelets_observe_same_cycle_index() {
+    let log_a = Arc::new(Mutex::new(Vec::new()));
+    let log_b = Arc::new(Mutex::new(Vec::new()));
+
+    let a = CycleProbe { log: log_a.clone() }.into_instance("a", ());
+    let b = CycleProbe { log: log_b.clone() }.into_instance("b", ());
+
+    let mut exec: ScheduleExecutor = ScheduleBuilder::new()
+        .with(Sequence::new().with((a, b)))
+        .into();
+    exec.setup(NodeletSetup {
+        clocks: Clocks::new(),
+        nodelet_id_issue: NodeletId(WorkerId(0), 0),
+    });
+
+    // start and steps
+    for _ in 0..=NUM_CYCLES {
+        exec.spin();
+    }
+    assert_eq!(exec.cycle_index(), NUM_CYCLES);
+    exec.finalize();
+    drop(exec);
+
+    let log_a = log_a.lock().unwrap().clone();
+    let log_b = log_b.lock().unwrap().clone();
+    assert_eq!(log_a, log_b);
+
+    let steps = log_a
+        .iter()
+        .filter(|(transition, _)| *transition == "step")
+        .map(|(_, index)| *index)
+        .collect::<Vec<_>>();
+    assert_eq!(steps, (1..=NUM_CYCLES).collect::<Vec<_>>());
+
+    // start and stop do not advance the cycle
+    assert_eq!(log_a.first(), Some(&("start", 0)));
+    assert_eq!(log_a.last(), Some(&("stop", NUM_CYCLES)));
+}
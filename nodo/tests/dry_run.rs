@@ -0,0 +1,155 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use eyre::{bail, Result};
+use nodo::{
+    codelet::{ScheduleBuilder, Transition},
+    prelude::*,
+};
+use nodo_runtime::{DryRunError, Runtime};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Pretends to talk to hardware which is not available in a dry run
+struct Sensor {
+    used_mock: Arc<AtomicBool>,
+}
+
+impl Codelet for Sensor {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), ())
+    }
+
+    fn step(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        if !cx.is_dry_run() {
+            bail!("sensor hardware not available");
+        }
+        self.used_mock.store(true, Ordering::Relaxed);
+        SUCCESS
+    }
+}
+
+struct Broken {
+    stopped: Arc<AtomicBool>,
+}
+
+impl Codelet for Broken {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), ())
+    }
+
+    fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        bail!("misconfigured")
+    }
+
+    fn stop(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.stopped.store(true, Ordering::Relaxed);
+        SUCCESS
+    }
+}
+
+#[test]
+fn test_dry_run_all() -> Result<()> {
+    let used_mock = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::new(AtomicBool::new(false));
+
+    let mut rt = Runtime::new();
+    let err = rt
+        .dry_run_all([
+            ScheduleBuilder::new()
+                .with_name("sensing")
+                .with(
+                    Sensor {
+                        used_mock: used_mock.clone(),
+                    }
+                    .into_instance("sensor", ()),
+                )
+                .with(
+                    Broken {
+                        stopped: stopped.clone(),
+                    }
+                    .into_instance("broken", ()),
+                )
+                .into(),
+            ScheduleBuilder::new()
+                .with_name("healthy")
+                .with(
+                    Sensor {
+                        used_mock: used_mock.clone(),
+                    }
+                    .into_instance("other_sensor", ()),
+                )
+                .into(),
+        ])
+        .unwrap_err();
+
+    assert!(used_mock.load(Ordering::Relaxed));
+    assert_eq!(rt.manifold().len(), 3);
+
+    let err = err.downcast_ref::<DryRunError>().unwrap();
+    assert_eq!(err.reports.len(), 2);
+
+    let sensing = &err.reports[0];
+    assert_eq!(sensing.schedule, "sensing");
+    assert_eq!(sensing.codelets.len(), 2);
+
+    let sensor = &sensing.codelets[0];
+    assert!(sensor.is_ok());
+    assert_eq!(
+        sensor
+            .transitions
+            .iter()
+            .map(|t| t.transition)
+            .collect::<Vec<_>>(),
+        [Transition::Start, Transition::Step, Transition::Stop]
+    );
+
+    // the broken codelet is still stopped after its step failed
+    let broken = &sensing.codelets[1];
+    assert_eq!(broken.transitions.len(), 3);
+    assert_eq!(broken.transitions[2].transition, Transition::Stop);
+    assert!(broken.transitions[2].result.is_ok());
+    assert_eq!(broken.error(), Some((Transition::Step, "misconfigured")));
+    assert!(stopped.load(Ordering::Relaxed));
+
+    assert!(err.reports[1].is_ok());
+    assert_eq!(
+        sensing
+            .failures()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>(),
+        ["broken"]
+    );
+    assert!(err
+        .to_string()
+        .contains("codelet 'broken' (schedule 'sensing', sequence '') failed during Step"));
+
+    Ok(())
+}
+
+#[test]
+fn test_dry_run_success() -> Result<()> {
+    let mut rt = Runtime::new();
+    let reports = rt.dry_run_all([ScheduleBuilder::new()
+        .with(
+            Sensor {
+                used_mock: Arc::default(),
+            }
+            .into_instance("sensor", ()),
+        )
+        .into()])?;
+    assert_eq!(reports.len(), 1);
+    assert!(reports[0].is_ok());
+    Ok(())
+}
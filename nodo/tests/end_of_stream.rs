@@ -0,0 +1,172 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use nodo::{
+    channels::RxRecvError,
+    codelet::{Clocks, NodeletId, NodeletSetup, ScheduleBuilder, WorkerId},
+    prelude::*,
+};
+use nodo_runtime::ScheduleExecutor;
+use std::sync::{Arc, Mutex};
+
+const NUM_MESSAGES: usize = 17;
+
+/// Sends a fixed number of messages and closes the channel afterwards
+struct Producer {
+    num_sent: usize,
+}
+
+#[derive(TxBundleDerive)]
+struct ProducerTx {
+    out: DoubleBufferTx<usize>,
+}
+
+impl Codelet for Producer {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = ProducerTx;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (
+            (),
+            ProducerTx {
+                out: DoubleBufferTx::new(4),
+            },
+        )
+    }
+
+    fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        if self.num_sent == NUM_MESSAGES {
+            return SKIPPED;
+        }
+
+        // send a couple of messages per step and close with the last batch
+        for _ in 0..3 {
+            tx.out.push(self.num_sent)?;
+            self.num_sent += 1;
+            if self.num_sent == NUM_MESSAGES {
+                tx.out.close();
+                break;
+            }
+        }
+        SUCCESS
+    }
+}
+
+#[derive(Default, Debug)]
+struct ConsumerLog {
+    received: Vec<usize>,
+    closed_observed: bool,
+    steps_after_close: usize,
+    stopped: bool,
+}
+
+/// Receives messages until the stream is closed
+struct Consumer {
+    log: Arc<Mutex<ConsumerLog>>,
+    max_pop_per_step: usize,
+}
+
+#[derive(RxBundleDerive)]
+struct ConsumerRx {
+    input: DoubleBufferRx<usize>,
+}
+
+impl Codelet for Consumer {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ConsumerRx;
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (
+            ConsumerRx {
+                input: DoubleBufferRx::new(OverflowPolicy::Resize, RetentionPolicy::Keep),
+            },
+            (),
+        )
+    }
+
+    fn step(&mut self, _: &Context<Self>, rx: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        let mut log = self.log.lock().unwrap();
+        if log.closed_observed {
+            log.steps_after_close += 1;
+        }
+        for _ in 0..self.max_pop_per_step {
+            match rx.input.pop() {
+                Ok(x) => log.received.push(x),
+                Err(RxRecvError::QueueEmtpy) => break,
+                Err(RxRecvError::Closed) => {
+                    assert!(rx.input.is_closed());
+                    log.closed_observed = true;
+                    break;
+                }
+            }
+        }
+        SUCCESS
+    }
+
+    fn stop(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.log.lock().unwrap().stopped = true;
+        SUCCESS
+    }
+}
+
+fn run(auto_stop: bool, max_pop_per_step: usize) -> ConsumerLog {
+    let log = Arc::new(Mutex::new(ConsumerLog::default()));
+
+    let mut producer = Producer { num_sent: 0 }.into_instance("producer", ());
+    let mut consumer = Consumer {
+        log: log.clone(),
+        max_pop_per_step,
+    }
+    .into_instance("consumer", ());
+    producer.tx.out.connect(&mut consumer.rx.input).unwrap();
+
+    let mut exec: ScheduleExecutor = ScheduleBuilder::new()
+        .with(
+            Sequence::new()
+                .with((producer, consumer))
+                .with_auto_stop_on_closed(auto_stop),
+        )
+        .into();
+    exec.setup(NodeletSetup {
+        clocks: Clocks::new(),
+        nodelet_id_issue: NodeletId(WorkerId(0), 0),
+    });
+
+    for _ in 0..2 * NUM_MESSAGES {
+        exec.spin();
+    }
+    exec.finalize();
+    drop(exec);
+
+    Arc::try_unwrap(log).unwrap().into_inner().unwrap()
+}
+
+#[test]
+fn end_of_stream_no_tail_lost() {
+    let log = run(false, usize::MAX);
+    assert_eq!(log.received, (0..NUM_MESSAGES).collect::<Vec<_>>());
+    assert!(log.closed_observed);
+    assert!(log.steps_after_close > 0);
+    assert!(log.stopped);
+}
+
+#[test]
+fn end_of_stream_auto_stop() {
+    let log = run(true, usize::MAX);
+    assert_eq!(log.received, (0..NUM_MESSAGES).collect::<Vec<_>>());
+    assert!(log.closed_observed);
+    assert_eq!(log.steps_after_close, 0);
+    assert!(log.stopped);
+}
+
+#[test]
+fn end_of_stream_auto_stop_slow_consumer() {
+    // the consumer pops one message per step and thus still has messages queued when the
+    // channel is closed
+    let log = run(true, 1);
+    assert_eq!(log.received, (0..NUM_MESSAGES).collect::<Vec<_>>());
+    assert!(log.stopped);
+}
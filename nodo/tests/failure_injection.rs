@@ -0,0 +1,242 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use nodo::{
+    codelet::{FailurePlan, ScheduleBuilder, FAILURE_INJECTION_LABEL},
+    prelude::*,
+};
+use nodo_runtime::{InspectorCodeletReport, InspectorReport, Runtime};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// Counts how often each transition was executed
+#[derive(Default)]
+struct Counts {
+    starts: AtomicUsize,
+    steps: AtomicUsize,
+    stops: AtomicUsize,
+}
+
+/// A codelet which knows nothing about failure injection
+struct Probe {
+    counts: Arc<Counts>,
+}
+
+impl Codelet for Probe {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), ())
+    }
+
+    fn start(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.counts.starts.fetch_add(1, Ordering::SeqCst);
+        SUCCESS
+    }
+
+    fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.counts.steps.fetch_add(1, Ordering::SeqCst);
+        SUCCESS
+    }
+
+    fn stop(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.counts.stops.fetch_add(1, Ordering::SeqCst);
+        SUCCESS
+    }
+}
+
+/// Creates a runtime with one schedule per probe. Schedules and probes are named `schedule_{i}`
+/// and `probe_{i}`.
+fn runtime(start_barrier: bool, probes: Vec<(Arc<Counts>, Option<FailurePlan>)>) -> Runtime {
+    let mut rt = Runtime::new();
+    rt.set_start_barrier(start_barrier);
+    for (i, (counts, plan)) in probes.into_iter().enumerate() {
+        let mut probe = Probe { counts }.into_instance(format!("probe_{i}"), ());
+        if let Some(plan) = plan {
+            probe = probe.with_failure_injection(plan);
+        }
+        rt.add_codelet_schedule(
+            ScheduleBuilder::new()
+                .with_name(format!("schedule_{i}"))
+                .with_period(Duration::from_millis(1))
+                .with(probe)
+                .into(),
+        );
+    }
+    rt
+}
+
+fn entry<'a>(report: &'a InspectorReport, name: &str) -> &'a InspectorCodeletReport {
+    report
+        .iter()
+        .map(|(_, entry)| entry)
+        .find(|entry| entry.name == name)
+        .unwrap()
+}
+
+fn is_labeled(report: &InspectorReport, name: &str) -> bool {
+    report
+        .iter()
+        .map(|(_, entry)| entry)
+        .filter(|entry| entry.name == name)
+        .any(|entry| entry.labels.iter().any(|l| l == FAILURE_INJECTION_LABEL))
+}
+
+#[test]
+fn test_failed_step_stops_schedule() {
+    let failing = Arc::new(Counts::default());
+    let healthy = Arc::new(Counts::default());
+    let mut rt = runtime(
+        false,
+        vec![
+            (failing.clone(), Some(FailurePlan::new().with_fail_step(5))),
+            (healthy.clone(), None),
+        ],
+    );
+
+    rt.spin_until(
+        |_| healthy.steps.load(Ordering::SeqCst) >= 100,
+        Duration::from_secs(10),
+    )
+    .unwrap();
+
+    // the failed codelet is not stopped while the other schedule keeps running
+    assert_eq!(failing.starts.load(Ordering::SeqCst), 1);
+    assert_eq!(failing.steps.load(Ordering::SeqCst), 4);
+    assert_eq!(failing.stops.load(Ordering::SeqCst), 0);
+    assert_eq!(healthy.stops.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_failed_start_aborts_start_barrier() {
+    let healthy = Arc::new(Counts::default());
+    let failing = Arc::new(Counts::default());
+    let mut rt = runtime(
+        true,
+        vec![
+            (healthy.clone(), None),
+            (
+                failing.clone(),
+                Some(FailurePlan::new().with_fail_start(1.0, 7)),
+            ),
+        ],
+    );
+
+    let err = rt.spin_for(Duration::from_millis(100)).unwrap_err();
+    let err = format!("{err:?}");
+    assert!(err.contains("schedule_1"), "{err}");
+    assert!(
+        err.contains("[injected] start of codelet 'probe_1' failed"),
+        "{err}"
+    );
+
+    // no codelet stepped and the real start of the failing codelet was never called
+    assert_eq!(healthy.starts.load(Ordering::SeqCst), 1);
+    assert_eq!(failing.starts.load(Ordering::SeqCst), 0);
+    assert_eq!(healthy.steps.load(Ordering::SeqCst), 0);
+    assert_eq!(failing.steps.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn test_panic_on_stop_is_reported() {
+    let counts = Arc::new(Counts::default());
+    let mut rt = runtime(
+        false,
+        vec![(
+            counts.clone(),
+            Some(FailurePlan::new().with_panic_on_stop()),
+        )],
+    );
+
+    let err = rt.spin_for(Duration::from_millis(50)).unwrap_err();
+    let err = format!("{err:#}");
+    assert!(err.contains("in codelet 'probe_0'"), "{err}");
+    assert!(err.contains("[injected] panic on stop"), "{err}");
+    assert!(counts.steps.load(Ordering::SeqCst) > 0);
+    assert_eq!(counts.stops.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn test_step_delay_misses_deadline() {
+    let counts = Arc::new(Counts::default());
+    let mut rt = Runtime::new();
+    rt.add_codelet_schedule(
+        ScheduleBuilder::new()
+            .with_name("main")
+            .with_period(Duration::from_millis(1))
+            .with(
+                Probe {
+                    counts: counts.clone(),
+                }
+                .into_instance("probe", ())
+                .with_deadline(Duration::from_millis(1))
+                .with_failure_injection(
+                    FailurePlan::new().with_step_delay(Duration::from_millis(3)),
+                ),
+            )
+            .into(),
+    );
+
+    rt.spin_until(
+        |report| {
+            is_labeled(report, "probe")
+                && entry(report, "probe").statistics.deadline_miss_count >= 5
+        },
+        Duration::from_secs(10),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_live_drill() {
+    let target = Arc::new(Counts::default());
+    let bystander = Arc::new(Counts::default());
+    let mut rt = runtime(
+        false,
+        vec![(target.clone(), None), (bystander.clone(), None)],
+    );
+
+    assert!(rt
+        .set_failure_injection("missing", "probe_0", None)
+        .is_err());
+
+    let mut armed_at = None;
+    rt.spin_with(
+        |rt| match armed_at {
+            None if target.steps.load(Ordering::SeqCst) >= 20 => {
+                rt.set_failure_injection(
+                    "schedule_0",
+                    "probe_0",
+                    Some(FailurePlan::new().with_fail_step(3)),
+                )
+                .unwrap();
+                armed_at = Some((
+                    target.steps.load(Ordering::SeqCst),
+                    bystander.steps.load(Ordering::SeqCst),
+                ));
+                false
+            }
+            Some((_, bystander_steps)) => {
+                bystander.steps.load(Ordering::SeqCst) >= bystander_steps + 100
+            }
+            None => false,
+        },
+        Duration::from_secs(10),
+    )
+    .unwrap();
+
+    // the drill only failed the targeted codelet
+    let (target_steps, _) = armed_at.unwrap();
+    let steps = target.steps.load(Ordering::SeqCst);
+    assert!(
+        (target_steps + 2..target_steps + 50).contains(&steps),
+        "{steps}"
+    );
+    assert_eq!(target.stops.load(Ordering::SeqCst), 0);
+    assert_eq!(bystander.stops.load(Ordering::SeqCst), 1);
+}
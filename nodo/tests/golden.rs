@@ -0,0 +1,139 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use eyre::Result;
+use nodo::{codelet::ScheduleBuilder, prelude::*};
+use nodo_runtime::{
+    actual_path, assert_matches_golden, assert_matches_golden_with, GoldenRecorder,
+    GoldenRecorderConfig, GoldenTolerances, Runtime,
+};
+use nodo_std::{Pipe, PipeConfig, Sink};
+use serde::Serialize;
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+const MESSAGE_COUNT: usize = 20;
+
+#[derive(Debug, Clone, Serialize)]
+struct Pose {
+    x: f64,
+    y: f64,
+    heading: f64,
+}
+
+/// Publishes a fixed number of poses on a circle
+struct Generator {
+    next: usize,
+}
+
+impl Codelet for Generator {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = DoubleBufferTx<Message<Pose>>;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), DoubleBufferTx::new_auto_size())
+    }
+
+    fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        if self.next >= MESSAGE_COUNT {
+            return SKIPPED;
+        }
+        let angle = self.next as f64 * 0.1;
+        tx.push(Message {
+            seq: self.next as u64,
+            stamp: Stamp {
+                acqtime: Duration::from_millis(self.next as u64).into(),
+                pubtime: Duration::from_millis(self.next as u64).into(),
+            },
+            value: Pose {
+                x: angle.cos(),
+                y: angle.sin(),
+                heading: angle,
+            },
+        })?;
+        self.next += 1;
+        SUCCESS
+    }
+}
+
+fn golden(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/goldens")
+        .join(name)
+}
+
+/// Runs the pipeline generator -> offset -> golden recorder until all poses were recorded
+fn run_pipeline(golden: PathBuf, offset: f64) -> Result<()> {
+    let received = Arc::new(AtomicUsize::new(0));
+
+    let mut generator = Generator { next: 0 }.into_instance("generator", ());
+
+    let mut offset = Pipe::new(move |mut msg: Message<Pose>| {
+        msg.value.x += offset;
+        msg
+    })
+    .into_instance("offset", PipeConfig::Dynamic);
+
+    let mut recorder =
+        GoldenRecorder::new(golden).into_instance("golden", GoldenRecorderConfig::default());
+
+    let mut count = Sink::new({
+        let received = received.clone();
+        move |_: Message<Pose>| {
+            received.fetch_add(1, Ordering::Relaxed);
+            SUCCESS
+        }
+    })
+    .into_instance("count", ());
+
+    generator.tx.connect(&mut offset.rx)?;
+    offset.tx.connect(&mut recorder.rx)?;
+    offset.tx.connect(&mut count.rx)?;
+
+    let mut rt = Runtime::new();
+    rt.add_codelet_schedule(
+        ScheduleBuilder::new()
+            .with_period(Duration::from_millis(1))
+            .with(generator)
+            .with(offset)
+            .with(recorder)
+            .with(count)
+            .into(),
+    );
+    rt.spin_until(
+        |_| received.load(Ordering::Relaxed) >= MESSAGE_COUNT,
+        Duration::from_secs(10),
+    )
+}
+
+#[test]
+fn test_golden_pipeline() -> Result<()> {
+    run_pipeline(golden("poses.jsonl"), 0.0)?;
+    assert_matches_golden(golden("poses.jsonl"));
+    Ok(())
+}
+
+#[test]
+fn test_golden_tolerance() -> Result<()> {
+    // the same golden is used with a recorder output in a temporary directory
+    let dir = std::env::temp_dir().join(format!("nodo-golden-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("poses.jsonl");
+    std::fs::copy(golden("poses.jsonl"), &path)?;
+
+    run_pipeline(path.clone(), 1e-4)?;
+    assert_matches_golden_with(&path, &GoldenTolerances::new().with("x", 1e-3, 0.0));
+
+    // the output is removed after a successful comparison
+    assert!(!actual_path(&path).exists());
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
@@ -0,0 +1,97 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use eyre::Result;
+use nodo::prelude::*;
+use nodo_runtime::Runtime;
+use nodo_std::{Sink, Source};
+use std::sync::{Arc, Mutex};
+
+type Received = Arc<Mutex<Vec<u64>>>;
+
+fn counter() -> Source<u64, impl FnMut() -> u64 + Send> {
+    let mut count = 0;
+    Source::new(move || {
+        count += 1;
+        count
+    })
+}
+
+fn recorder(received: &Received) -> Sink<u64, impl FnMut(u64) -> Outcome + Send> {
+    let received = received.clone();
+    Sink::new(move |value| {
+        received.lock().unwrap().push(value);
+        SUCCESS
+    })
+}
+
+/// Runs a counter into a sink and optionally into a second sink
+fn run(with_logger: bool) -> Result<(Vec<String>, Vec<u64>, Vec<u64>)> {
+    let received: Received = Default::default();
+    let logged: Received = Default::default();
+
+    let schedule = nodo_graph! {
+        source: Source<u64, _> = counter(), name: "counter";
+        if with_logger {
+            logger: Sink<u64, _> = recorder(&logged);
+            source.tx -> logger.rx;
+        }
+        sink: Sink<u64, _> = recorder(&received), config: ();
+        source.tx -> sink.rx;
+        schedule {
+            name: "graph",
+            period: Duration::from_millis(1),
+        }
+    }?;
+
+    let mut rt = Runtime::new();
+    rt.add_codelet_schedule(schedule.into());
+    let names = rt.manifold().entries().iter().map(|e| e.path()).collect();
+    rt.spin_until(
+        |_| received.lock().unwrap().len() >= 20,
+        Duration::from_secs(10),
+    )?;
+
+    let received = received.lock().unwrap().clone();
+    let logged = logged.lock().unwrap().clone();
+    Ok((names, received, logged))
+}
+
+#[test]
+fn test_graph() -> Result<()> {
+    let (names, received, logged) = run(false)?;
+    assert_eq!(names, ["graph/counter", "graph/sink"]);
+    assert_eq!(received, (1..=received.len() as u64).collect::<Vec<_>>());
+    assert!(logged.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_graph_optional_instance() -> Result<()> {
+    let (names, received, logged) = run(true)?;
+    assert_eq!(names, ["graph/counter", "graph/logger", "graph/sink"]);
+    assert_eq!(received, (1..=received.len() as u64).collect::<Vec<_>>());
+    assert_eq!(logged, received);
+    Ok(())
+}
+
+#[test]
+fn test_graph_connect_error() {
+    let mut source = counter().into_instance("source", ());
+    let mut sink = recorder(&Default::default()).into_instance("sink", ());
+    source.tx.connect(&mut sink.rx).unwrap();
+
+    // a receiver can only be connected once
+    let result = nodo_graph! {
+        other: Source<u64, _> = counter();
+        other.tx -> sink.rx;
+        schedule {}
+    };
+    assert!(result.is_err());
+}
+
+#[test]
+fn graph_ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/graph_*.rs");
+}
@@ -0,0 +1,124 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use eyre::Result as EyreResult;
+use nodo::{
+    codelet::{
+        Clocks, Instantiate, NodeletId, NodeletSetup, Persist, ScheduleBuilder, Transition,
+        WorkerId,
+    },
+    prelude::*,
+};
+use nodo_runtime::ScheduleExecutor;
+use std::sync::{atomic::AtomicBool, Arc};
+
+/// Sleeps longer than its deadline every step
+#[derive(Default)]
+struct Slow {
+    steps: u8,
+}
+
+impl Codelet for Slow {
+    type Status = DefaultStatus;
+    type Config = Duration;
+    type Rx = ();
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), ())
+    }
+
+    fn step(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.steps += 1;
+        std::thread::sleep(*cx.config);
+        SUCCESS
+    }
+}
+
+impl Persist for Slow {
+    fn save(&self) -> EyreResult<Vec<u8>> {
+        Ok(vec![self.steps])
+    }
+
+    fn restore(&mut self, data: &[u8]) -> EyreResult<()> {
+        self.steps = data[0];
+        Ok(())
+    }
+}
+
+#[test]
+fn test_builder_options_in_report() {
+    let ms = Duration::from_millis;
+
+    let slow = Slow::builder("slow")
+        .config(ms(2))
+        .label("camera", "front")
+        .tag(nodo::codelet::RARELY_ACTIVE_LABEL)
+        .deadline(ms(1))
+        .period_hint(ms(5))
+        .start_after("driver")
+        .persistence("slow_state")
+        .build()
+        .unwrap();
+    assert_eq!(slow.persistence_key(), Some("slow_state"));
+
+    let disabled = Slow::default()
+        .into_builder("disabled")
+        .config(ms(0))
+        .enable(Arc::new(AtomicBool::new(false)))
+        .build()
+        .unwrap();
+
+    let mut schedule = ScheduleExecutor::from(
+        ScheduleBuilder::new()
+            .with_name("builder")
+            .with(slow)
+            .with(disabled),
+    );
+
+    // schedule hints are applied to the schedule
+    assert_eq!(schedule.start_after(), ["driver"]);
+    assert_eq!(schedule.period(), Some(ms(5)));
+
+    schedule.setup(NodeletSetup {
+        clocks: Clocks::new(),
+        nodelet_id_issue: NodeletId(WorkerId(0), 0),
+    });
+    for _ in 0..4 {
+        schedule.spin();
+    }
+
+    let report = schedule.report();
+    let entry = |name: &str| {
+        report
+            .iter()
+            .map(|(_, entry)| entry)
+            .find(|entry| entry.name == name)
+            .unwrap()
+            .clone()
+    };
+
+    let slow = entry("slow");
+    assert_eq!(
+        slow.labels,
+        ["camera=front", nodo::codelet::RARELY_ACTIVE_LABEL]
+    );
+    assert_eq!(slow.deadline, Some(ms(1)));
+    let step = &slow.statistics.transitions[Transition::Step];
+    assert_eq!(step.duration.count(), 3);
+    assert_eq!(slow.statistics.deadline_miss_count, 3);
+
+    let disabled = entry("disabled");
+    assert!(disabled.labels.is_empty());
+    assert_eq!(disabled.deadline, None);
+    assert_eq!(
+        disabled.statistics.transitions[Transition::Step].disabled_count,
+        3
+    );
+}
+
+#[test]
+fn test_builder_ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/builder_*.rs");
+}
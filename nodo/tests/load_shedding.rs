@@ -0,0 +1,176 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use nodo::{
+    codelet::{
+        Clocks, LoadShedding, NodeletId, NodeletSetup, ScheduleBuilder, Transition, WorkerId,
+    },
+    prelude::*,
+};
+use nodo_core::Name;
+use nodo_runtime::ScheduleExecutor;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+const PERIOD: Duration = Duration::from_millis(5);
+
+/// Counts its steps and sleeps for longer than the period while the slow flag is set
+struct Worker {
+    steps: Arc<AtomicUsize>,
+    slow: Arc<AtomicBool>,
+}
+
+impl Codelet for Worker {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), ())
+    }
+
+    fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.steps.fetch_add(1, Ordering::SeqCst);
+        if self.slow.load(Ordering::SeqCst) {
+            std::thread::sleep(2 * PERIOD);
+        }
+        SUCCESS
+    }
+}
+
+fn worker(name: &str, steps: &Arc<AtomicUsize>, slow: &Arc<AtomicBool>) -> impl Sequenceable {
+    Worker {
+        steps: steps.clone(),
+        slow: slow.clone(),
+    }
+    .into_instance(name, ())
+}
+
+/// Spins once and returns true if the step finished within the period
+fn spin(exec: &mut ScheduleExecutor) -> bool {
+    let time_begin = Instant::now();
+    exec.spin();
+    time_begin.elapsed() <= PERIOD
+}
+
+fn shed_sequences(exec: &ScheduleExecutor) -> Vec<Name> {
+    exec.report().schedules()[&Name::new("main")]
+        .shed_sequences
+        .clone()
+}
+
+#[test]
+fn test_load_shedding() {
+    let never = Arc::new(AtomicBool::new(false));
+    let slow = Arc::new(AtomicBool::new(true));
+    let control_steps = Arc::new(AtomicUsize::new(0));
+    let viz_steps = Arc::new(AtomicUsize::new(0));
+
+    let mut exec: ScheduleExecutor = ScheduleBuilder::new()
+        .with_name("main")
+        .with_period(PERIOD)
+        .with_load_shedding(LoadShedding {
+            trigger_after_overruns: 3,
+            recover_after_clean: 20,
+        })
+        .with(Sequence::new().with_name("control").with(worker(
+            "controller",
+            &control_steps,
+            &never,
+        )))
+        .with(
+            Sequence::new()
+                .with_name("viz")
+                .with_priority(5)
+                .with(worker("renderer", &viz_steps, &slow)),
+        )
+        .into();
+    exec.setup(NodeletSetup {
+        clocks: Clocks::new(),
+        nodelet_id_issue: NodeletId(WorkerId(0), 0),
+    });
+    exec.start().unwrap();
+
+    // the slow sequence is shed after three overruns
+    for _ in 0..3 {
+        assert!(shed_sequences(&exec).is_empty());
+        assert!(!spin(&mut exec));
+    }
+    assert_eq!(shed_sequences(&exec), ["viz"]);
+    assert_eq!(viz_steps.load(Ordering::SeqCst), 3);
+
+    // the schedule keeps its period while the control sequence is still stepped
+    for _ in 0..10 {
+        assert!(spin(&mut exec));
+    }
+    assert_eq!(control_steps.load(Ordering::SeqCst), 13);
+    assert_eq!(viz_steps.load(Ordering::SeqCst), 3);
+    assert_eq!(shed_sequences(&exec), ["viz"]);
+
+    // the sequence is restored after 20 steps without overrun once the load is removed
+    slow.store(false, Ordering::SeqCst);
+    for _ in 0..10 {
+        assert_eq!(shed_sequences(&exec), ["viz"]);
+        spin(&mut exec);
+    }
+    assert!(shed_sequences(&exec).is_empty());
+
+    spin(&mut exec);
+    assert_eq!(viz_steps.load(Ordering::SeqCst), 4);
+
+    let report = exec.report();
+    let shedding = &report.schedules()[&Name::new("main")].shedding;
+    assert_eq!(shedding.len(), 1);
+    assert_eq!(shedding[&Name::new("viz")].shed_count, 1);
+    assert_eq!(shedding[&Name::new("viz")].restore_count, 1);
+
+    // codelets of shed sequences are paused
+    let renderer = report
+        .iter()
+        .find(|(_, codelet)| codelet.name == "renderer")
+        .unwrap()
+        .1;
+    let step = &renderer.statistics.transitions[Transition::Step];
+    assert_eq!((step.pause_count, step.resume_count), (1, 1));
+
+    exec.finalize();
+}
+
+#[test]
+fn test_priority_zero_is_never_shed() {
+    let slow = Arc::new(AtomicBool::new(true));
+    let steps = Arc::new(AtomicUsize::new(0));
+
+    let mut exec: ScheduleExecutor = ScheduleBuilder::new()
+        .with_name("main")
+        .with_period(PERIOD)
+        .with_load_shedding(LoadShedding {
+            trigger_after_overruns: 1,
+            recover_after_clean: 1,
+        })
+        .with(
+            Sequence::new()
+                .with_name("control")
+                .with(worker("controller", &steps, &slow)),
+        )
+        .into();
+    exec.setup(NodeletSetup {
+        clocks: Clocks::new(),
+        nodelet_id_issue: NodeletId(WorkerId(0), 0),
+    });
+    exec.start().unwrap();
+
+    for _ in 0..3 {
+        spin(&mut exec);
+        assert!(shed_sequences(&exec).is_empty());
+    }
+    assert_eq!(steps.load(Ordering::SeqCst), 3);
+
+    exec.finalize();
+}
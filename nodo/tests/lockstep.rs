@@ -0,0 +1,89 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use nodo::{
+    codelet::{Clocks, NodeletId, NodeletSetup, ScheduleBuilder, WorkerId},
+    prelude::*,
+};
+use nodo_runtime::ScheduleExecutor;
+use nodo_std::{LockstepConfig, LockstepHandle, Pipe, PipeConfig};
+use std::sync::{Arc, Mutex};
+
+const TICK_COUNT: u64 = 100;
+const DT: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Event {
+    Submit(u64),
+    Step(u64),
+    Receive(u64),
+}
+
+#[test]
+fn test_lockstep_with_simulator() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+
+    let handle = LockstepHandle::<f64, Message<f64>>::new(LockstepConfig {
+        dt: DT,
+        timeout: Duration::from_secs(5),
+    });
+
+    let mut gate = handle.gate().into_instance("gate", ());
+    let mut controller = Pipe::new({
+        let events = events.clone();
+        move |msg: Message<f64>| {
+            events.lock().unwrap().push(Event::Step(msg.seq));
+            msg.map(|x| 2.0 * x)
+        }
+    })
+    .into_instance("controller", PipeConfig::Dynamic);
+    let mut sink = handle.sink().into_instance("sink", ());
+    gate.tx.connect(&mut controller.rx).unwrap();
+    controller.tx.connect(&mut sink.rx).unwrap();
+
+    let mut exec: ScheduleExecutor = ScheduleBuilder::new()
+        .with(Sequence::new().with((gate, controller, sink)))
+        .into();
+    exec.setup(NodeletSetup {
+        clocks: Clocks::new_manual(),
+        nodelet_id_issue: NodeletId(WorkerId(0), 0),
+    });
+
+    let simulator = std::thread::spawn({
+        let events = events.clone();
+        move || {
+            for tick in 0..TICK_COUNT {
+                events.lock().unwrap().push(Event::Submit(tick));
+                handle.submit_inputs(tick, vec![tick as f64]).unwrap();
+
+                let outputs = handle.wait_outputs(tick, Duration::from_secs(5)).unwrap();
+                events.lock().unwrap().push(Event::Receive(tick));
+
+                // commands match the tick and app time advanced by dt per tick
+                assert_eq!(outputs.len(), 1);
+                assert_eq!(outputs[0].seq, tick);
+                assert_eq!(outputs[0].value, 2.0 * tick as f64);
+                assert_eq!(
+                    Duration::from(outputs[0].stamp.pubtime),
+                    DT * (tick as u32 + 1)
+                );
+            }
+            handle.tick_count()
+        }
+    });
+
+    exec.start().unwrap();
+    for _ in 0..TICK_COUNT {
+        exec.spin();
+    }
+    let tick_count = simulator.join().unwrap();
+    exec.finalize();
+
+    assert_eq!(tick_count, TICK_COUNT);
+
+    // simulator and graph strictly alternate
+    let expected: Vec<Event> = (0..TICK_COUNT)
+        .flat_map(|k| [Event::Submit(k), Event::Step(k), Event::Receive(k)])
+        .collect();
+    assert_eq!(*events.lock().unwrap(), expected);
+}
@@ -0,0 +1,99 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use nodo::{
+    codelet::{Persist, ScheduleBuilder},
+    prelude::*,
+};
+use nodo_core::{eyre, EyreResult};
+use nodo_runtime::{Runtime, SnapshotConfig};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+const STEPS_PER_RUN: usize = 5;
+
+/// Counts its steps and reports the count when stopped
+struct Counter {
+    count: u64,
+    result: Arc<Mutex<Option<u64>>>,
+}
+
+impl Codelet for Counter {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), ())
+    }
+
+    fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.count += 1;
+        SUCCESS
+    }
+
+    fn stop(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        *self.result.lock().unwrap() = Some(self.count);
+        SUCCESS
+    }
+}
+
+impl Persist for Counter {
+    const VERSION: u8 = 1;
+
+    fn save(&self) -> EyreResult<Vec<u8>> {
+        Ok(self.count.to_le_bytes().to_vec())
+    }
+
+    fn restore(&mut self, data: &[u8]) -> EyreResult<()> {
+        let bytes = data.try_into().map_err(|_| eyre!("invalid length"))?;
+        self.count = u64::from_le_bytes(bytes);
+        Ok(())
+    }
+}
+
+/// Runs a runtime with a persisted counter until its schedule finished and returns the final count
+#[allow(deprecated)]
+fn run(path: &Path) -> u64 {
+    let result = Arc::new(Mutex::new(None));
+
+    let counter = Counter {
+        count: 0,
+        result: result.clone(),
+    }
+    .into_instance("counter", ())
+    .with_persistence("counter");
+
+    let mut rt = Runtime::new().with_snapshot(SnapshotConfig::new(path));
+    rt.add_codelet_schedule(
+        ScheduleBuilder::new()
+            .with_name("persist")
+            .with_max_step_count(STEPS_PER_RUN)
+            .with(counter)
+            .into(),
+    );
+    rt.spin();
+
+    let count = result.lock().unwrap().unwrap();
+    count
+}
+
+#[test]
+fn test_persist_across_runtimes() {
+    let path = std::env::temp_dir().join(format!("nodo_persist_{}.snap", std::process::id()));
+    std::fs::remove_file(&path).ok();
+
+    // cold start without snapshot
+    assert_eq!(run(&path), STEPS_PER_RUN as u64);
+
+    // warm start resumes from the saved count
+    assert_eq!(run(&path), 2 * STEPS_PER_RUN as u64);
+
+    // a corrupt snapshot results in a cold start
+    std::fs::write(&path, b"NODOSNAP garbage").unwrap();
+    assert_eq!(run(&path), STEPS_PER_RUN as u64);
+
+    std::fs::remove_file(&path).ok();
+}
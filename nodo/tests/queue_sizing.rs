@@ -0,0 +1,120 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use nodo::{
+    channels::{OverflowPolicy, RetentionPolicy},
+    codelet::{ScheduleBuilder, Transition},
+    prelude::*,
+};
+use nodo_runtime::{QueueSizeAdvice, Runtime};
+
+const STEP_COUNT: u64 = 50;
+
+/// Publishes a burst of messages every step
+struct Producer;
+
+impl Codelet for Producer {
+    type Status = DefaultStatus;
+    type Config = usize;
+    type Rx = ();
+    type Tx = DoubleBufferTx<u64>;
+
+    fn build_bundles(burst: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), DoubleBufferTx::new(*burst))
+    }
+
+    fn step(&mut self, cx: &Context<Self>, _: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        for i in 0..*cx.config {
+            tx.push(i as u64)?;
+        }
+        SUCCESS
+    }
+}
+
+/// Consumes all messages with a receiver using the given overflow policy
+struct Consumer;
+
+impl Codelet for Consumer {
+    type Status = DefaultStatus;
+    type Config = OverflowPolicy;
+    type Rx = DoubleBufferRx<u64>;
+    type Tx = ();
+
+    fn build_bundles(policy: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (DoubleBufferRx::new(*policy, RetentionPolicy::Drop), ())
+    }
+
+    fn step(&mut self, _: &Context<Self>, rx: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        rx.pop_all().for_each(drop);
+        SUCCESS
+    }
+}
+
+#[test]
+fn test_queue_sizing_report() {
+    let mut small_burst = Producer.into_instance("small_burst", 1);
+    let mut oversized = Consumer.into_instance("oversized", OverflowPolicy::Reject(24));
+    small_burst.tx.connect(&mut oversized.rx).unwrap();
+
+    let mut large_burst = Producer.into_instance("large_burst", 8);
+    let mut undersized = Consumer.into_instance("undersized", OverflowPolicy::Forget(4));
+    large_burst.tx.connect(&mut undersized.rx).unwrap();
+
+    let mut rt = Runtime::new().with_queue_sizing_report(true);
+    rt.add_codelet_schedule(
+        ScheduleBuilder::new()
+            .with_period(Duration::from_millis(1))
+            .with(small_burst)
+            .with(oversized)
+            .with(large_burst)
+            .with(undersized)
+            .into(),
+    );
+    rt.spin_until(
+        |report| {
+            report.iter().all(|(_, entry)| {
+                entry.statistics.transitions[Transition::Step]
+                    .duration
+                    .count()
+                    >= STEP_COUNT
+            })
+        },
+        Duration::from_secs(10),
+    )
+    .unwrap();
+
+    let report = rt.queue_sizing_report();
+    let advice = report
+        .entries
+        .iter()
+        .map(|entry| {
+            (
+                entry.codelet.as_str(),
+                entry.recommendation.advice,
+                entry.recommendation.suggested_capacity,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        advice,
+        [
+            ("oversized", QueueSizeAdvice::Oversized, 1),
+            ("undersized", QueueSizeAdvice::Undersized, 8)
+        ]
+    );
+
+    let messages = report
+        .entries
+        .iter()
+        .map(|entry| entry.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages[0],
+        "channel oversized/in: capacity 24, observed max 1, never forgot - consider 1"
+    );
+    assert_eq!(
+        messages[1],
+        "channel undersized/in: capacity 4, forgot 50% of messages - consider 8 or a faster \
+         consumer"
+    );
+}
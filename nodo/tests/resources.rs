@@ -0,0 +1,150 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use eyre::Result;
+use nodo::{
+    codelet::{Clocks, NodeletId, NodeletSetup, ResourceRegistry, ScheduleBuilder, WorkerId},
+    prelude::*,
+};
+use nodo_runtime::{Runtime, ScheduleExecutor};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+/// Resource shared by all counters
+#[derive(Default)]
+struct SharedCounter(AtomicUsize);
+
+/// Instance acquired by a counter and the name of the thread which acquired it
+type Acquired = Arc<Mutex<Vec<(Arc<SharedCounter>, Option<String>)>>>;
+
+/// Increments the shared counter every step
+#[derive(Default)]
+struct Counter {
+    counter: Option<Arc<SharedCounter>>,
+
+    acquired: Acquired,
+}
+
+impl Codelet for Counter {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), ())
+    }
+
+    fn acquire_resources(&mut self, resources: &ResourceRegistry) -> Result<()> {
+        let counter = resources.get::<SharedCounter>()?;
+        self.acquired.lock().unwrap().push((
+            counter.clone(),
+            std::thread::current().name().map(String::from),
+        ));
+        self.counter = Some(counter);
+        Ok(())
+    }
+
+    fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.counter
+            .as_ref()
+            .unwrap()
+            .0
+            .fetch_add(1, Ordering::SeqCst);
+        SUCCESS
+    }
+}
+
+/// Does not use any resources
+struct Idle;
+
+impl Codelet for Idle {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), ())
+    }
+}
+
+#[test]
+fn test_shared_resource() -> Result<()> {
+    let acquired = Arc::new(Mutex::new(Vec::new()));
+
+    let mut rt = Runtime::new();
+    rt.resources()?.insert(SharedCounter::default());
+    for name in ["first", "second"] {
+        rt.add_codelet_schedule(
+            ScheduleBuilder::new()
+                .with_name(name)
+                .with_period(Duration::from_millis(1))
+                .with(
+                    Counter {
+                        acquired: acquired.clone(),
+                        ..Default::default()
+                    }
+                    .into_instance("counter", ()),
+                )
+                .into(),
+        );
+    }
+
+    let counter = acquired.clone();
+    rt.spin_until(
+        |_| {
+            let acquired = counter.lock().unwrap();
+            acquired.len() == 2 && acquired[0].0 .0.load(Ordering::SeqCst) >= 20
+        },
+        Duration::from_secs(10),
+    )?;
+
+    // both codelets observe the same instance and acquired it on their worker thread
+    let mut acquired = acquired.lock().unwrap().clone();
+    assert_eq!(acquired.len(), 2);
+    assert!(Arc::ptr_eq(&acquired[0].0, &acquired[1].0));
+    acquired.sort_by(|a, b| a.1.cmp(&b.1));
+    assert_eq!(acquired[0].1.as_deref(), Some("first"));
+    assert_eq!(acquired[1].1.as_deref(), Some("second"));
+
+    Ok(())
+}
+
+#[test]
+fn test_missing_resource_fails_start() {
+    let mut exec: ScheduleExecutor = ScheduleBuilder::new()
+        .with(Counter::default().into_instance("counter", ()))
+        .into();
+    exec.setup(NodeletSetup {
+        clocks: Clocks::new(),
+        nodelet_id_issue: NodeletId(WorkerId(0), 0),
+    });
+    exec.set_resources(Arc::new(ResourceRegistry::new()));
+
+    let err = format!("{:?}", exec.start().unwrap_err());
+    assert!(
+        err.contains("codelet 'counter' failed to acquire resources: missing resource of type")
+            && err.contains("SharedCounter"),
+        "{err}"
+    );
+    exec.finalize();
+}
+
+#[test]
+fn test_resources_are_read_only_after_schedules_were_added() {
+    let mut rt = Runtime::new();
+    rt.add_codelet_schedule(
+        ScheduleBuilder::new()
+            .with_period(Duration::from_millis(1))
+            .with(Idle.into_instance("idle", ()))
+            .into(),
+    );
+    let err = rt.resources().err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "resources must be inserted before schedules are added"
+    );
+}
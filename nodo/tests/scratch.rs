@@ -0,0 +1,85 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+#![cfg(feature = "scratch")]
+
+use nodo::{
+    codelet::{Clocks, Lifecycle, NodeletId, NodeletSetup, Transition, Vise, ViseTrait, WorkerId},
+    prelude::*,
+};
+use std::sync::{Arc, Mutex};
+
+/// Builds a temporary buffer with the configured number of samples every step
+struct Smoother {
+    pointers: Arc<Mutex<Vec<usize>>>,
+}
+
+impl Codelet for Smoother {
+    type Status = DefaultStatus;
+    type Config = usize;
+    type Rx = ();
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), ())
+    }
+
+    fn step(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        let mut samples = cx.scratch().vec_with_capacity::<f32>(*cx.config);
+        samples.extend((0..*cx.config).map(|i| i as f32));
+        self.pointers
+            .lock()
+            .unwrap()
+            .push(samples.as_ptr() as usize);
+        SUCCESS
+    }
+}
+
+fn vise(samples: usize, pointers: Arc<Mutex<Vec<usize>>>) -> Vise<Smoother> {
+    let mut vise = Vise::new(
+        Smoother { pointers }
+            .into_instance("smoother", samples)
+            .with_scratch_capacity(4096),
+    );
+    vise.setup(&mut NodeletSetup {
+        clocks: Clocks::new(),
+        nodelet_id_issue: NodeletId(WorkerId(0), 0),
+    });
+    vise
+}
+
+#[test]
+fn test_scratch_reset_between_steps() {
+    let pointers = Arc::new(Mutex::new(Vec::new()));
+    let mut vise = vise(100, pointers.clone());
+    vise.cycle(Transition::Start).unwrap();
+    for _ in 0..5 {
+        vise.cycle(Transition::Step).unwrap();
+    }
+    vise.cycle(Transition::Stop).unwrap();
+
+    // every step reuses the memory of the previous step
+    let pointers = pointers.lock().unwrap();
+    assert_eq!(pointers.len(), 5);
+    assert!(pointers.iter().all(|&p| p == pointers[0]));
+}
+
+#[test]
+fn test_scratch_peak_usage() {
+    let mut vise = vise(1000, Default::default());
+    vise.cycle(Transition::Start).unwrap();
+    assert_eq!(vise.statistics().scratch_peak_bytes, 0);
+
+    vise.cycle(Transition::Step).unwrap();
+    let peak = vise.statistics().scratch_peak_bytes;
+    assert!(peak >= 4000, "{peak}");
+
+    // the peak is per transition and not accumulated over steps
+    vise.cycle(Transition::Step).unwrap();
+    assert_eq!(vise.statistics().scratch_peak_bytes, peak);
+}
+
+#[test]
+fn test_scratch_ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/scratch_*.rs");
+}
@@ -0,0 +1,122 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use eyre::Result;
+use nodo::{codelet::ScheduleBuilder, prelude::*};
+use nodo_runtime::Runtime;
+use nodo_std::{Join, JoinConfig, Sink, Source};
+use std::sync::{Arc, Mutex};
+
+const MESSAGE_COUNT: usize = 200;
+
+/// Sequence numbers received after the join tagged with the index of the producer
+type Received = Arc<Mutex<Vec<(usize, u64)>>>;
+
+/// Runs two producers which allocate from the same domain and joins their streams
+fn run(same_schedule: bool) -> Result<Vec<(usize, u64)>> {
+    let mut rt = Runtime::new();
+    let received: Received = Default::default();
+
+    let mut join = Join::default().into_instance("join", JoinConfig { input_count: 2 });
+    let mut sink = Sink::new({
+        let received = received.clone();
+        move |msg: Message<usize>| {
+            received.lock().unwrap().push((msg.value, msg.seq));
+            SUCCESS
+        }
+    })
+    .into_instance("sink", ());
+    join.tx.connect(&mut sink.rx)?;
+
+    let mut producers = Vec::new();
+    for index in 0..2 {
+        let seq = rt.seq_domain("detections");
+        let mut producer = Source::new(move || {
+            seq.message(
+                Stamp {
+                    acqtime: Duration::ZERO.into(),
+                    pubtime: Duration::ZERO.into(),
+                },
+                index,
+            )
+        })
+        .into_instance(format!("producer_{index}"), ());
+        producer.tx.connect(join.rx.channel_mut(index))?;
+        producers.push(producer);
+    }
+
+    if same_schedule {
+        let mut schedule = ScheduleBuilder::new()
+            .with_name("main")
+            .with_period(Duration::from_millis(1));
+        for producer in producers {
+            schedule = schedule.with(producer);
+        }
+        rt.add_codelet_schedule(schedule.with(join).with(sink).into());
+    } else {
+        for (index, producer) in producers.into_iter().enumerate() {
+            rt.add_codelet_schedule(
+                ScheduleBuilder::new()
+                    .with_name(format!("producer_{index}"))
+                    .with_period(Duration::from_micros(500))
+                    .with(producer)
+                    .into(),
+            );
+        }
+        rt.add_codelet_schedule(
+            ScheduleBuilder::new()
+                .with_name("main")
+                .with_period(Duration::from_millis(1))
+                .with(join)
+                .with(sink)
+                .into(),
+        );
+    }
+
+    rt.spin_until(
+        |_| received.lock().unwrap().len() >= MESSAGE_COUNT,
+        Duration::from_secs(10),
+    )?;
+
+    let received = received.lock().unwrap().clone();
+    Ok(received)
+}
+
+fn assert_unique(received: &[(usize, u64)]) {
+    let mut seqs: Vec<_> = received.iter().map(|(_, seq)| *seq).collect();
+    seqs.sort();
+    assert!(seqs.windows(2).all(|w| w[0] < w[1]), "{seqs:?}");
+}
+
+fn assert_monotonic_per_producer(received: &[(usize, u64)]) {
+    for index in 0..2 {
+        let seqs: Vec<_> = received
+            .iter()
+            .filter(|(producer, _)| *producer == index)
+            .map(|(_, seq)| *seq)
+            .collect();
+        assert!(!seqs.is_empty());
+        assert!(seqs.windows(2).all(|w| w[0] < w[1]), "{seqs:?}");
+    }
+}
+
+#[test]
+fn test_seq_domain_same_schedule() -> Result<()> {
+    let received = run(true)?;
+
+    // producers step before the join thus the merged stream is strictly increasing
+    assert!(received.windows(2).all(|w| w[0].1 < w[1].1), "{received:?}");
+    assert_monotonic_per_producer(&received);
+    Ok(())
+}
+
+#[test]
+fn test_seq_domain_concurrent_producers() -> Result<()> {
+    let received = run(false)?;
+
+    // producers run on different threads and the join does not order its inputs, but sequence
+    // numbers never collide
+    assert_unique(&received);
+    assert_monotonic_per_producer(&received);
+    Ok(())
+}
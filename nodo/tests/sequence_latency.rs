@@ -0,0 +1,115 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use nodo::{
+    codelet::{Clocks, NodeletId, NodeletSetup, ScheduleBuilder, WorkerId},
+    prelude::*,
+};
+use nodo_runtime::ScheduleExecutor;
+use std::sync::{Arc, Mutex};
+
+const NUM_CYCLES: usize = 5;
+
+/// Pairs of (cycle, value received in that cycle)
+type ProbeLog = Vec<(usize, Option<usize>)>;
+
+/// Publishes the index of the current cycle
+struct Counter {
+    cycle: usize,
+}
+
+impl Codelet for Counter {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = DoubleBufferTx<usize>;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), DoubleBufferTx::new(1))
+    }
+
+    fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        tx.push(self.cycle)?;
+        self.cycle += 1;
+        SUCCESS
+    }
+}
+
+/// Records (cycle, received value) for every step
+struct Probe {
+    cycle: usize,
+    log: Arc<Mutex<ProbeLog>>,
+}
+
+impl Codelet for Probe {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = DoubleBufferRx<usize>;
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (DoubleBufferRx::new_latest(), ())
+    }
+
+    fn step(&mut self, _: &Context<Self>, rx: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.log.lock().unwrap().push((self.cycle, rx.try_pop()));
+        self.cycle += 1;
+        SUCCESS
+    }
+}
+
+fn run(producer_first: bool) -> ProbeLog {
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let mut counter = Counter { cycle: 0 }.into_instance("counter", ());
+    let mut probe = Probe {
+        cycle: 0,
+        log: log.clone(),
+    }
+    .into_instance("probe", ());
+    counter.tx.connect(&mut probe.rx).unwrap();
+
+    let sequence = if producer_first {
+        Sequence::new().with((counter, probe))
+    } else {
+        Sequence::new().with((probe, counter))
+    };
+
+    let mut exec: ScheduleExecutor = ScheduleBuilder::new().with(sequence).into();
+    exec.setup(NodeletSetup {
+        clocks: Clocks::new(),
+        nodelet_id_issue: NodeletId(WorkerId(0), 0),
+    });
+
+    // start and steps
+    for _ in 0..=NUM_CYCLES {
+        exec.spin();
+    }
+    exec.finalize();
+    drop(exec);
+
+    Arc::try_unwrap(log).unwrap().into_inner().unwrap()
+}
+
+/// RX channels are synced at the beginning of each step and TX channels are flushed at the end.
+/// Thus a consumer ordered after its producer in the same sequence sees messages within the same
+/// cycle without any special connection mode.
+#[test]
+fn producer_before_consumer_same_cycle() {
+    let log = run(true);
+    assert_eq!(
+        log,
+        (0..NUM_CYCLES).map(|i| (i, Some(i))).collect::<Vec<_>>()
+    );
+}
+
+/// A consumer ordered before its producer sees messages one cycle later.
+#[test]
+fn consumer_before_producer_one_cycle_delay() {
+    let log = run(false);
+    assert_eq!(
+        log,
+        (0..NUM_CYCLES)
+            .map(|i| (i, i.checked_sub(1)))
+            .collect::<Vec<_>>()
+    );
+}
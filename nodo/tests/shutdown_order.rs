@@ -0,0 +1,105 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use nodo::{codelet::ScheduleBuilder, prelude::*};
+use nodo_runtime::Runtime;
+use std::sync::{Arc, Mutex};
+
+/// Sentinel published by the producer when it stops
+const FINAL: u64 = u64::MAX;
+
+/// Publishes a counter every step and a final message when stopped
+struct Producer {
+    count: u64,
+}
+
+impl Codelet for Producer {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = DoubleBufferTx<u64>;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), DoubleBufferTx::new_auto_size())
+    }
+
+    fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        self.count += 1;
+        tx.push(self.count)?;
+        SUCCESS
+    }
+
+    fn stop(&mut self, _: &Context<Self>, _: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        tx.push(FINAL)?;
+        SUCCESS
+    }
+}
+
+/// Records all received messages including those still queued when it stops
+struct Recorder {
+    recorded: Arc<Mutex<Vec<u64>>>,
+}
+
+impl Codelet for Recorder {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = DoubleBufferRx<u64>;
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (DoubleBufferRx::new_auto_size(), ())
+    }
+
+    fn step(&mut self, _: &Context<Self>, rx: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.recorded.lock().unwrap().extend(rx.pop_all());
+        SUCCESS
+    }
+
+    fn stop(&mut self, _: &Context<Self>, rx: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.recorded.lock().unwrap().extend(rx.pop_all());
+        SUCCESS
+    }
+}
+
+#[test]
+fn test_recorder_stops_after_producer() {
+    let recorded = Arc::new(Mutex::new(Vec::new()));
+
+    let mut producer = Producer { count: 0 }.into_instance("producer", ());
+    let mut recorder = Recorder {
+        recorded: recorded.clone(),
+    }
+    .into_instance("recorder", ());
+    producer.tx.connect(&mut recorder.rx).unwrap();
+
+    // the producer is added first thus without the dependency the recorder would stop first
+    let mut rt = Runtime::new();
+    rt.add_codelet_schedule(
+        ScheduleBuilder::new()
+            .with_name("producer")
+            .with_period(Duration::from_millis(1))
+            .with_start_after("recorder")
+            .with(producer)
+            .into(),
+    );
+    rt.add_codelet_schedule(
+        ScheduleBuilder::new()
+            .with_name("recorder")
+            .with_period(Duration::from_millis(50))
+            .with(recorder)
+            .into(),
+    );
+
+    rt.spin_until(
+        |_| recorded.lock().unwrap().len() >= 10,
+        Duration::from_secs(10),
+    )
+    .unwrap();
+
+    let recorded = recorded.lock().unwrap();
+    assert_eq!(recorded.last(), Some(&FINAL));
+    assert_eq!(recorded.iter().filter(|&&x| x == FINAL).count(), 1);
+    assert!(recorded[..recorded.len() - 1]
+        .windows(2)
+        .all(|w| w[1] == w[0] + 1));
+}
@@ -0,0 +1,185 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use eyre::Result;
+use nodo::{
+    channels::{SnoopContent, SnoopLimits, SnoopSample},
+    codelet::ScheduleBuilder,
+    prelude::*,
+};
+use nodo_runtime::{Runtime, SnoopError, SnoopSink};
+use nodo_std::{Sink, Source};
+use std::sync::{Arc, Mutex};
+
+const CHANNEL: &str = "main/sink/rx.in";
+
+type Shared<T> = Arc<Mutex<Vec<T>>>;
+
+/// Counts up with 1 kHz into a sink which records all values
+fn runtime(received: &Shared<u64>) -> Result<Runtime> {
+    let mut rt = Runtime::new();
+
+    let mut counter = 0;
+    let mut source = Source::new(move || {
+        counter += 1;
+        counter
+    })
+    .into_instance("source", ());
+    let mut sink = Sink::new({
+        let received = received.clone();
+        move |value: u64| {
+            received.lock().unwrap().push(value);
+            SUCCESS
+        }
+    })
+    .into_instance("sink", ());
+    source.tx.connect(&mut sink.rx)?;
+
+    rt.add_codelet_schedule(
+        ScheduleBuilder::new()
+            .with_name("main")
+            .with_period(Duration::from_millis(1))
+            .with(source)
+            .with(sink)
+            .into(),
+    );
+    Ok(rt)
+}
+
+fn collect(samples: &Shared<SnoopSample>) -> SnoopSink {
+    let samples = samples.clone();
+    SnoopSink::callback(move |sample| samples.lock().unwrap().push(sample.clone()))
+}
+
+fn assert_contiguous(received: &[u64]) {
+    assert!(!received.is_empty());
+    assert!(
+        received.iter().zip(1..).all(|(&value, i)| value == i),
+        "{received:?}"
+    );
+}
+
+#[test]
+fn test_snoop_mid_run() -> Result<()> {
+    let received: Shared<u64> = Default::default();
+    let samples: Shared<SnoopSample> = Default::default();
+
+    let mut rt = runtime(&received)?;
+    rt.snoop_formats_mut().register_debug::<u64>();
+    assert!(rt.channel_taps().iter().any(|tap| tap.path == CHANNEL));
+
+    let limits = SnoopLimits {
+        max_samples_per_sec: 10,
+        max_bytes_per_sec: 1024,
+    };
+    let mut attached_at = None;
+    let mut detached = None;
+    rt.spin_with(
+        |rt| {
+            let count = received.lock().unwrap().len();
+            match (attached_at, detached) {
+                (None, _) if count >= 20 => {
+                    rt.snoop_with_limits(CHANNEL, collect(&samples), limits)
+                        .unwrap();
+                    assert!(matches!(
+                        rt.snoop(CHANNEL, SnoopSink::Inspector),
+                        Err(SnoopError::AlreadySnooped(_))
+                    ));
+                    attached_at = Some(count);
+                }
+                (Some(at), None) if count >= at + 200 => {
+                    rt.unsnoop(CHANNEL).unwrap();
+                    detached = Some((count, samples.lock().unwrap().len()));
+                }
+                (Some(_), Some((at, _))) => return count >= at + 50,
+                _ => {}
+            }
+            false
+        },
+        Duration::from_secs(10),
+    )?;
+
+    // the consumer received every message
+    assert_contiguous(&received.lock().unwrap());
+
+    // samples were captured within the limits and only while attached
+    let samples = samples.lock().unwrap();
+    let (_, sample_count) = detached.unwrap();
+    assert_eq!(samples.len(), sample_count);
+    assert!(!samples.is_empty());
+    assert!(samples.len() <= limits.max_samples_per_sec as usize);
+    assert!(samples.windows(2).all(|w| w[0].count < w[1].count));
+    for sample in samples.iter() {
+        let Some(SnoopContent::Debug(text)) = &sample.content else {
+            panic!("sample without content: {sample:?}");
+        };
+        assert_eq!(sample.size, text.len());
+        assert!(text.parse::<u64>()? > attached_at.unwrap() as u64);
+    }
+
+    assert!(matches!(
+        rt.unsnoop(CHANNEL),
+        Err(SnoopError::NotSnooped(_))
+    ));
+    assert!(matches!(
+        rt.snoop("main/sink/rx.missing", SnoopSink::Inspector),
+        Err(SnoopError::UnknownChannel(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_snoop_byte_budget() -> Result<()> {
+    let received: Shared<u64> = Default::default();
+    let samples: Shared<SnoopSample> = Default::default();
+
+    let mut rt = runtime(&received)?;
+    rt.snoop_formats_mut().register_debug::<u64>();
+    rt.snoop_with_limits(
+        CHANNEL,
+        collect(&samples),
+        SnoopLimits {
+            max_samples_per_sec: 100,
+            max_bytes_per_sec: 8,
+        },
+    )?;
+    rt.spin_until(
+        |_| received.lock().unwrap().len() >= 100,
+        Duration::from_secs(10),
+    )?;
+
+    assert_contiguous(&received.lock().unwrap());
+
+    // the first samples use up the byte budget, the rest only have a size
+    let samples = samples.lock().unwrap();
+    let content_size: usize = samples
+        .iter()
+        .filter_map(|s| s.content.as_ref())
+        .map(SnoopContent::len)
+        .sum();
+    assert!(samples[0].content.is_some());
+    assert!(content_size <= 8);
+    assert!(samples
+        .iter()
+        .any(|s| s.content.is_none() && s.size == size_of::<u64>()));
+    Ok(())
+}
+
+#[test]
+fn test_snoop_without_format() -> Result<()> {
+    let received: Shared<u64> = Default::default();
+    let samples: Shared<SnoopSample> = Default::default();
+
+    let mut rt = runtime(&received)?;
+    rt.snoop(CHANNEL, collect(&samples))?;
+    rt.spin_until(
+        |_| received.lock().unwrap().len() >= 50,
+        Duration::from_secs(10),
+    )?;
+
+    // messages without a snoop format are counted but have no content
+    let samples = samples.lock().unwrap();
+    assert!(!samples.is_empty());
+    assert!(samples.iter().all(|s| s.content.is_none()));
+    Ok(())
+}
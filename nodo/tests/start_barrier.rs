@@ -0,0 +1,101 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use eyre::eyre;
+use nodo::{codelet::ScheduleBuilder, prelude::*};
+use nodo_runtime::Runtime;
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+
+/// Flags shared by all probes
+#[derive(Default)]
+struct Flags {
+    started: AtomicUsize,
+    steps: AtomicUsize,
+    early_step: AtomicBool,
+}
+
+/// Takes a while to start and records whether it stepped before all probes started
+struct StartProbe {
+    flags: Arc<Flags>,
+    count: usize,
+    start_delay: Duration,
+    fail_start: bool,
+}
+
+impl Codelet for StartProbe {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), ())
+    }
+
+    fn start(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        std::thread::sleep(self.start_delay);
+        if self.fail_start {
+            return Err(eyre!("start failed"));
+        }
+        self.flags.started.fetch_add(1, Ordering::SeqCst);
+        SUCCESS
+    }
+
+    fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        if self.flags.started.load(Ordering::SeqCst) < self.count {
+            self.flags.early_step.store(true, Ordering::SeqCst);
+        }
+        self.flags.steps.fetch_add(1, Ordering::SeqCst);
+        SUCCESS
+    }
+}
+
+fn runtime(flags: &Arc<Flags>, delays: [u64; 2], fail_start: bool) -> Runtime {
+    let mut rt = Runtime::new();
+    rt.set_start_barrier(true);
+    for (i, delay) in delays.into_iter().enumerate() {
+        let probe = StartProbe {
+            flags: flags.clone(),
+            count: delays.len(),
+            start_delay: Duration::from_millis(delay),
+            fail_start: fail_start && i == 1,
+        }
+        .into_instance(format!("probe_{i}"), ());
+        rt.add_codelet_schedule(
+            ScheduleBuilder::new()
+                .with_name(format!("schedule_{i}"))
+                .with_period(Duration::from_millis(1))
+                .with(probe)
+                .into(),
+        );
+    }
+    rt
+}
+
+#[test]
+fn test_no_step_before_all_started() {
+    let flags = Arc::new(Flags::default());
+
+    // the second schedule takes much longer to start than the first
+    let mut rt = runtime(&flags, [0, 100], false);
+    rt.spin_for(Duration::from_millis(200)).unwrap();
+
+    assert_eq!(flags.started.load(Ordering::SeqCst), 2);
+    assert!(flags.steps.load(Ordering::SeqCst) > 0);
+    assert!(!flags.early_step.load(Ordering::SeqCst));
+}
+
+#[test]
+fn test_start_failure_aborts_barrier() {
+    let flags = Arc::new(Flags::default());
+
+    let mut rt = runtime(&flags, [0, 20], true);
+    let err = rt.spin_for(Duration::from_millis(100)).unwrap_err();
+    assert!(err.to_string().contains("schedule_1"), "{err}");
+
+    assert_eq!(flags.started.load(Ordering::SeqCst), 1);
+    assert_eq!(flags.steps.load(Ordering::SeqCst), 0);
+}
@@ -0,0 +1,93 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use nodo::{
+    codelet::{Clocks, NodeletId, NodeletSetup, ScheduleBuilder, WorkerId, MAX_START_DIAGNOSTICS},
+    prelude::*,
+};
+use nodo_runtime::{
+    decode_report_frame, encode_report_frame, InspectorReport, ReportCodecKind, ScheduleExecutor,
+};
+
+/// Has an RX channel which is never connected and warns during start
+struct Lonely {
+    warning_count: usize,
+}
+
+impl Codelet for Lonely {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = DoubleBufferRx<u32>;
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (DoubleBufferRx::new_auto_size(), ())
+    }
+
+    fn start(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        for i in 0..self.warning_count {
+            cx.warn(format!("calibration {i} missing"));
+        }
+        SUCCESS
+    }
+
+    fn step(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        // warnings during step are only logged
+        cx.warn("step warning");
+        SUCCESS
+    }
+}
+
+fn round_trip(report: &InspectorReport) -> InspectorReport {
+    let mut server = ReportCodecKind::default().build();
+    let mut client = ReportCodecKind::default().build();
+    let frame = encode_report_frame(server.as_mut(), report).unwrap();
+    decode_report_frame(client.as_mut(), &frame)
+        .unwrap()
+        .unwrap()
+}
+
+#[test]
+fn test_start_diagnostics_in_report() {
+    let a = Lonely { warning_count: 1 }.into_instance("a", ());
+    let b = Lonely { warning_count: 20 }.into_instance("b", ());
+
+    let mut exec: ScheduleExecutor = ScheduleBuilder::new()
+        .with(Sequence::new().with((a, b)))
+        .into();
+    exec.setup(NodeletSetup {
+        clocks: Clocks::new(),
+        nodelet_id_issue: NodeletId(WorkerId(0), 0),
+    });
+
+    // start and a few steps: diagnostics persist after start
+    for _ in 0..3 {
+        exec.spin();
+    }
+    let report = round_trip(&exec.report());
+    exec.finalize();
+
+    let diagnostics = |name: &str| {
+        report
+            .clone()
+            .into_vec()
+            .into_iter()
+            .find(|(_, c)| c.name == name)
+            .unwrap()
+            .1
+            .start_diagnostics
+    };
+
+    assert_eq!(
+        diagnostics("a"),
+        [
+            "unconnected RX channels: [0] in",
+            "warning: calibration 0 missing"
+        ]
+    );
+
+    // diagnostics are bounded
+    let b = diagnostics("b");
+    assert_eq!(b.len(), MAX_START_DIAGNOSTICS);
+    assert_eq!(b[1], "warning: calibration 0 missing");
+    assert_eq!(b.last().unwrap(), "6 more diagnostics omitted");
+}
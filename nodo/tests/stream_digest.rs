@@ -0,0 +1,137 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use eyre::Result;
+use nodo::{codelet::ScheduleBuilder, prelude::*};
+use nodo_core::WithTopic;
+use nodo_runtime::Runtime;
+use nodo_std::{
+    compare_digests, Pipe, PipeConfig, Sink, StreamDigest, StreamDigestConfig, TopicDigest,
+    TopicMessage,
+};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+const MESSAGE_COUNT: u64 = 3000;
+const BATCH_SIZE: u64 = 100;
+
+/// Publishes a deterministic sequence of messages on two topics
+struct Generator {
+    next: u64,
+
+    /// Modifies the payload of this message on topic "b"
+    perturb: Option<u64>,
+}
+
+impl Codelet for Generator {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = DoubleBufferTx<TopicMessage>;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), DoubleBufferTx::new_auto_size())
+    }
+
+    fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        if self.next >= MESSAGE_COUNT {
+            return SKIPPED;
+        }
+        for _ in 0..BATCH_SIZE {
+            let seq = self.next;
+            for topic in ["a", "b"] {
+                let mut payload = seq.to_le_bytes().to_vec();
+                if topic == "b" && self.perturb == Some(seq) {
+                    payload[0] ^= 1;
+                }
+                tx.push(Message {
+                    seq,
+                    stamp: Stamp {
+                        acqtime: Duration::from_millis(seq).into(),
+                        pubtime: Duration::from_millis(seq).into(),
+                    },
+                    value: WithTopic {
+                        topic: topic.into(),
+                        value: payload,
+                    },
+                })?;
+            }
+            self.next += 1;
+        }
+        SUCCESS
+    }
+}
+
+fn run_pipeline(perturb: Option<u64>) -> Result<Vec<TopicDigest>> {
+    let digests = Arc::new(Mutex::new(Vec::new()));
+    let received = Arc::new(AtomicUsize::new(0));
+
+    let mut generator = Generator { next: 0, perturb }.into_instance("generator", ());
+
+    let mut reverse = Pipe::new(|mut msg: TopicMessage| {
+        msg.value.value.reverse();
+        msg
+    })
+    .into_instance("reverse", PipeConfig::Dynamic);
+
+    let mut digest = StreamDigest::new({
+        let digests = digests.clone();
+        move |d| {
+            *digests.lock().unwrap() = d;
+            Ok(())
+        }
+    })
+    .into_instance("digest", StreamDigestConfig::default());
+
+    let mut count = Sink::new({
+        let received = received.clone();
+        move |_: TopicMessage| {
+            received.fetch_add(1, Ordering::Relaxed);
+            SUCCESS
+        }
+    })
+    .into_instance("count", ());
+
+    generator.tx.connect(&mut reverse.rx)?;
+    reverse.tx.connect(&mut digest.rx)?;
+    reverse.tx.connect(&mut count.rx)?;
+
+    let mut rt = Runtime::new();
+    rt.add_codelet_schedule(
+        ScheduleBuilder::new()
+            .with_period(Duration::from_millis(1))
+            .with(generator)
+            .with(reverse)
+            .with(digest)
+            .with(count)
+            .into(),
+    );
+    rt.spin_until(
+        |_| received.load(Ordering::Relaxed) >= 2 * MESSAGE_COUNT as usize,
+        Duration::from_secs(30),
+    )?;
+
+    let digests = digests.lock().unwrap().clone();
+    Ok(digests)
+}
+
+#[test]
+fn test_stream_digest_replay_equivalence() -> Result<()> {
+    let first = run_pipeline(None)?;
+    let second = run_pipeline(None)?;
+
+    assert_eq!(first.len(), 2);
+    assert_eq!(first[0].message_count, MESSAGE_COUNT as usize);
+    assert_eq!(first[0].checkpoints.len(), 2);
+    assert!(compare_digests(&first, &second).is_empty());
+
+    let perturbed = run_pipeline(Some(2500))?;
+    let mismatches = compare_digests(&first, &perturbed);
+    assert_eq!(mismatches.len(), 1);
+    assert_eq!(mismatches[0].topic, "b");
+    assert_eq!(mismatches[0].first_divergence, 2048);
+
+    Ok(())
+}
@@ -0,0 +1,186 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use nodo::{codelet::ScheduleBuilder, prelude::*};
+use nodo_core::Name;
+use nodo_runtime::{BacklogTeardown, Executor, TeardownConfig};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+const BACKLOG_LEN: usize = 2_000;
+
+/// A message which is slow to free
+#[derive(Clone)]
+struct Heavy {
+    drop_time: Duration,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl Drop for Heavy {
+    fn drop(&mut self) {
+        std::thread::sleep(self.drop_time);
+        self.dropped.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Publishes the whole backlog when it starts
+struct Producer {
+    drop_time: Duration,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl Codelet for Producer {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = DoubleBufferTx<Heavy>;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), DoubleBufferTx::new_auto_size())
+    }
+
+    fn start(&mut self, _: &Context<Self>, _: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        tx.push_many((0..BACKLOG_LEN).map(|_| Heavy {
+            drop_time: self.drop_time,
+            dropped: self.dropped.clone(),
+        }))?;
+        SUCCESS
+    }
+}
+
+/// Keeps all received messages without ever consuming them
+struct Hoarder {
+    queued: Arc<AtomicUsize>,
+}
+
+impl Codelet for Hoarder {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = DoubleBufferRx<Heavy>;
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (
+            DoubleBufferRx::new(OverflowPolicy::Resize, RetentionPolicy::Keep),
+            (),
+        )
+    }
+
+    fn step(&mut self, _: &Context<Self>, rx: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.queued.store(rx.len(), Ordering::SeqCst);
+        SUCCESS
+    }
+}
+
+/// Result of a run: time to stop and join all workers, teardown report and number of freed
+/// messages
+type RunResult = (Duration, Vec<BacklogTeardown>, Arc<AtomicUsize>);
+
+/// Builds a backlog and stops
+fn run(config: TeardownConfig, drop_time: Duration) -> RunResult {
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let queued = Arc::new(AtomicUsize::new(0));
+
+    let mut producer = Producer {
+        drop_time,
+        dropped: dropped.clone(),
+    }
+    .into_instance("producer", ());
+    let mut hoarder = Hoarder {
+        queued: queued.clone(),
+    }
+    .into_instance("hoarder", ());
+    producer.tx.connect(&mut hoarder.rx).unwrap();
+
+    let mut exec = Executor::new();
+    exec.set_teardown(config);
+    exec.push(
+        ScheduleBuilder::new()
+            .with_name("main")
+            .with_period(Duration::from_millis(1))
+            .with(producer)
+            .with(hoarder)
+            .into(),
+    );
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while queued.load(Ordering::SeqCst) < BACKLOG_LEN {
+        assert!(Instant::now() < deadline, "backlog was not built");
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    let time_begin = Instant::now();
+    exec.request_stop();
+    exec.join().unwrap();
+    let elapsed = time_begin.elapsed();
+
+    let teardown = exec.report().schedules()[&Name::new("main")]
+        .teardown
+        .clone();
+    (elapsed, teardown, dropped)
+}
+
+#[test]
+fn test_teardown_warning() {
+    let (_, teardown, dropped) = run(
+        TeardownConfig {
+            warn_len: 1_000,
+            ..Default::default()
+        },
+        Duration::ZERO,
+    );
+
+    assert_eq!(teardown.len(), 1);
+    assert_eq!(teardown[0].codelet, "hoarder");
+    assert_eq!(teardown[0].channel, "in");
+    assert_eq!(teardown[0].len, BACKLOG_LEN);
+    assert_eq!(
+        teardown[0].bytes,
+        BACKLOG_LEN * core::mem::size_of::<Heavy>()
+    );
+    assert!(!teardown[0].deferred);
+
+    // all messages were freed on the worker thread
+    assert_eq!(dropped.load(Ordering::SeqCst), BACKLOG_LEN);
+}
+
+#[test]
+fn test_teardown_below_threshold() {
+    let (_, teardown, dropped) = run(TeardownConfig::default(), Duration::ZERO);
+    assert!(teardown.is_empty());
+    assert_eq!(dropped.load(Ordering::SeqCst), BACKLOG_LEN);
+}
+
+#[test]
+fn test_teardown_deferred_drop() {
+    // freeing the backlog on the worker thread would take at least 2 seconds
+    let drop_time = Duration::from_millis(1);
+    let (elapsed, teardown, dropped) = run(
+        TeardownConfig {
+            warn_len: 1_000,
+            deferred_drop: true,
+            ..Default::default()
+        },
+        drop_time,
+    );
+
+    assert_eq!(teardown.len(), 1);
+    assert!(teardown[0].deferred);
+    assert!(
+        elapsed < drop_time * BACKLOG_LEN as u32 / 4,
+        "stop took {elapsed:?}"
+    );
+    assert!(dropped.load(Ordering::SeqCst) < BACKLOG_LEN);
+
+    // the reaper frees all messages eventually
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while dropped.load(Ordering::SeqCst) < BACKLOG_LEN {
+        assert!(Instant::now() < deadline, "reaper did not free the backlog");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,22 @@
+use nodo::prelude::*;
+
+#[derive(Default)]
+struct Stateless;
+
+impl Codelet for Stateless {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), ())
+    }
+}
+
+fn main() {
+    let _ = Stateless::builder("stateless")
+        .config(())
+        .persistence("key")
+        .build();
+}
@@ -0,0 +1,9 @@
+use nodo::prelude::*;
+
+#[derive(NodoConfig)]
+struct Config {
+    #[config(min = 1, min = 2)]
+    value: u32,
+}
+
+fn main() {}
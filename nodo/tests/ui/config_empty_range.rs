@@ -0,0 +1,9 @@
+use nodo::prelude::*;
+
+#[derive(NodoConfig)]
+struct Config {
+    #[config(min = 1.0, max = 0.0)]
+    value: f64,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use nodo::prelude::*;
+
+#[derive(NodoConfig)]
+struct Config {
+    #[config(max = "ten")]
+    value: u32,
+}
+
+fn main() {}
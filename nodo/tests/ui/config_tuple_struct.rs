@@ -0,0 +1,6 @@
+use nodo::prelude::*;
+
+#[derive(NodoConfig)]
+struct Config(u32);
+
+fn main() {}
@@ -0,0 +1,9 @@
+use nodo::prelude::*;
+
+#[derive(NodoConfig)]
+struct Config {
+    #[config(default = 1, maximum = 2)]
+    value: u32,
+}
+
+fn main() {}
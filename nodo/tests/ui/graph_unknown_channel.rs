@@ -0,0 +1,11 @@
+use nodo::prelude::*;
+use nodo_std::{Sink, Source};
+
+fn main() {
+    let _ = nodo_graph! {
+        source: Source<u32, _> = Source::new(|| 1u32);
+        sink: Sink<u32, _> = Sink::new(|_: u32| SUCCESS);
+        source.tx.out -> sink.rx;
+        schedule {}
+    };
+}
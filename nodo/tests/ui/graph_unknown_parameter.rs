@@ -0,0 +1,8 @@
+use nodo::prelude::*;
+
+fn main() {
+    let _ = nodo_graph! {
+        source: nodo_std::Source<u32, _> = nodo_std::Source::new(|| 1u32), period: 10;
+        schedule {}
+    };
+}
@@ -0,0 +1,12 @@
+use nodo::prelude::*;
+use nodo_std::Source;
+
+fn main() {
+    let _ = nodo_graph! {
+        source: Source<u32, _> = Source::new(|| 1u32);
+        schedule {
+            name: "main",
+            frequency: 100,
+        }
+    };
+}
@@ -0,0 +1,23 @@
+use nodo::prelude::*;
+
+struct Leaky {
+    samples: Option<&'static mut [f32]>,
+}
+
+impl Codelet for Leaky {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), ())
+    }
+
+    fn step(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.samples = Some(cx.scratch().alloc_slice_copy(&[1.0, 2.0]));
+        SUCCESS
+    }
+}
+
+fn main() {}
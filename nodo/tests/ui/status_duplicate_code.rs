@@ -0,0 +1,16 @@
+use nodo::prelude::*;
+
+#[derive(Status)]
+enum MyStatus {
+    #[default]
+    #[skipped]
+    Idle,
+
+    #[code = 7]
+    Running,
+
+    #[code = 7]
+    Busy,
+}
+
+fn main() {}
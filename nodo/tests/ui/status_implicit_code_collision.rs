@@ -0,0 +1,13 @@
+use nodo::prelude::*;
+
+#[derive(Status)]
+enum MyStatus {
+    #[default]
+    #[skipped]
+    Idle,
+
+    #[code = 0]
+    Running,
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use nodo::prelude::*;
+
+#[derive(Status)]
+enum MyStatus {
+    #[default]
+    #[skipped]
+    Idle,
+
+    #[severity = "fatal"]
+    Running,
+}
+
+fn main() {}
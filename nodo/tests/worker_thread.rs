@@ -0,0 +1,78 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+#![cfg(target_os = "linux")]
+
+use core::time::Duration;
+use eyre::Result;
+use nodo::{codelet::ScheduleBuilder, prelude::*};
+use nodo_core::Name;
+use nodo_runtime::Runtime;
+
+struct Idle;
+
+impl Codelet for Idle {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), ())
+    }
+
+    fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        SUCCESS
+    }
+}
+
+#[test]
+fn test_worker_thread_report() -> Result<()> {
+    let mut rt = Runtime::new();
+    for name in ["a", "b"] {
+        rt.add_codelet_schedule(
+            ScheduleBuilder::new()
+                .with_name(name)
+                .with_period(Duration::from_micros(100))
+                .with(Idle.into_instance("idle", ()))
+                .into(),
+        );
+    }
+
+    let mut migrations = 0;
+    let mut last_report = None;
+    rt.spin_until(
+        |report| {
+            last_report = Some(report.clone());
+            let Some(thread) = report
+                .schedules()
+                .get(&Name::new("a"))
+                .and_then(|schedule| schedule.thread.as_ref())
+            else {
+                return false;
+            };
+            assert!(thread.os_thread_id.is_some());
+            assert!(thread.initial_cpu.is_some());
+            assert!(thread.last_cpu.is_some());
+
+            // the migration counter never decreases
+            assert!(thread.migration_count >= migrations);
+            migrations = thread.migration_count;
+
+            thread.sample_count >= 4
+        },
+        Duration::from_secs(10),
+    )?;
+
+    let report = last_report.unwrap();
+    let a = report.schedules()[&Name::new("a")].thread.clone().unwrap();
+    let b = report.schedules()[&Name::new("b")].thread.clone().unwrap();
+
+    // every worker runs on its own thread which is not the main thread
+    assert_ne!(a.os_thread_id, b.os_thread_id);
+    assert_ne!(a.os_thread_id, Some(std::process::id()));
+
+    assert!(a.sample_count >= 4);
+    assert!(a.migration_count < a.sample_count);
+
+    Ok(())
+}
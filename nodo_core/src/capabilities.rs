@@ -0,0 +1,128 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+//! Registry of optional integrations available in this binary
+//!
+//! Integration crates register a capability when they are first used, for example
+//! `capabilities::register("nng", env!("CARGO_PKG_VERSION"))`. Applications can use
+//! [has] to decide what to wire without resorting to `cfg!` and the full list is included in the
+//! inspector report.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    sync::RwLock,
+};
+
+static REGISTRY: RwLock<BTreeMap<String, String>> = RwLock::new(BTreeMap::new());
+
+/// An integration registered with [register]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Capability {
+    pub name: String,
+    pub version: String,
+}
+
+/// Registers a capability with its version
+///
+/// Each name is only registered once. Returns false if the name was already registered in which
+/// case the version of the first registration is kept.
+pub fn register<S1: Into<String>, S2: Into<String>>(name: S1, version: S2) -> bool {
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    match registry.entry(name.into()) {
+        Entry::Occupied(_) => false,
+        Entry::Vacant(entry) => {
+            entry.insert(version.into());
+            true
+        }
+    }
+}
+
+/// True if a capability with this name was registered
+pub fn has(name: &str) -> bool {
+    REGISTRY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .contains_key(name)
+}
+
+/// Version of a registered capability
+pub fn version(name: &str) -> Option<String> {
+    REGISTRY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(name)
+        .cloned()
+}
+
+/// All registered capabilities sorted by name
+pub fn list() -> Vec<Capability> {
+    REGISTRY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|(name, version)| Capability {
+            name: name.clone(),
+            version: version.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::capabilities;
+    use std::sync::{Arc, Barrier};
+
+    #[test]
+    fn test_register() {
+        assert!(!capabilities::has("test_register"));
+        assert!(capabilities::register("test_register", "1.0.0"));
+        assert!(capabilities::has("test_register"));
+
+        // duplicates are rejected and keep the first version
+        assert!(!capabilities::register("test_register", "2.0.0"));
+        assert_eq!(
+            capabilities::version("test_register").as_deref(),
+            Some("1.0.0")
+        );
+        assert_eq!(
+            capabilities::list()
+                .iter()
+                .filter(|c| c.name == "test_register")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_register_from_threads() {
+        const THREAD_COUNT: usize = 8;
+
+        let barrier = Arc::new(Barrier::new(THREAD_COUNT));
+        let handles: Vec<_> = (0..THREAD_COUNT)
+            .map(|i| {
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    let shared = capabilities::register("test_threads_shared", format!("{i}"));
+                    let own = capabilities::register(format!("test_threads_{i}"), "0.1.0");
+                    (shared, own)
+                })
+            })
+            .collect();
+
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        // exactly one thread won the shared registration
+        assert_eq!(results.iter().filter(|(shared, _)| *shared).count(), 1);
+        assert!(results.iter().all(|(_, own)| *own));
+
+        let list = capabilities::list();
+        assert_eq!(
+            list.iter()
+                .filter(|c| c.name.starts_with("test_threads_"))
+                .count(),
+            THREAD_COUNT + 1
+        );
+        assert!(list.windows(2).all(|w| w[0].name < w[1].name));
+    }
+}
@@ -1,8 +1,14 @@
 // Copyright 2023 by David Weikersdorfer. All rights reserved.
 
 use crate::Timestamp;
-use core::marker::PhantomData;
-use std::time::Instant;
+use core::{marker::PhantomData, time::Duration};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
 
 const DEFAULT_CLOCK_ID: u64 = 0;
 
@@ -32,25 +38,64 @@ pub trait Clock<M> {
 }
 
 /// A monotonic clock which starts when the application starts
+///
+/// A manual clock created with [AppMonotonicClock::new_manual] starts at zero and only advances
+/// when [AppMonotonicClock::advance] is called. Clones share the same time.
 #[derive(Clone)]
 pub struct AppMonotonicClock<M> {
-    reference: Instant,
+    source: AppClockSource,
     _marker: PhantomData<M>,
 }
 
+#[derive(Clone)]
+enum AppClockSource {
+    Instant(Instant),
+    Manual(Arc<AtomicU64>),
+}
+
 impl<M> Clock<M> for AppMonotonicClock<M> {
     fn now(&self) -> Timestamp<M> {
-        Timestamp::new(self.reference.elapsed())
+        match &self.source {
+            AppClockSource::Instant(reference) => Timestamp::new(reference.elapsed()),
+            AppClockSource::Manual(nanos) => {
+                Timestamp::new(Duration::from_nanos(nanos.load(Ordering::Acquire)))
+            }
+        }
     }
 }
 
 impl<M> AppMonotonicClock<M> {
     pub fn new() -> Self {
         Self {
-            reference: Instant::now(),
+            source: AppClockSource::Instant(Instant::now()),
             _marker: PhantomData,
         }
     }
+
+    /// Creates a clock which is advanced manually, e.g. in lockstep with a simulator
+    pub fn new_manual() -> Self {
+        Self {
+            source: AppClockSource::Manual(Arc::new(AtomicU64::new(0))),
+            _marker: PhantomData,
+        }
+    }
+
+    /// True if the clock was created with [AppMonotonicClock::new_manual]
+    pub fn is_manual(&self) -> bool {
+        matches!(self.source, AppClockSource::Manual(_))
+    }
+
+    /// Advances a manual clock by the given duration
+    ///
+    /// Panics if the clock is not manual.
+    pub fn advance(&self, dt: Duration) {
+        match &self.source {
+            AppClockSource::Instant(_) => panic!("only manual clocks can be advanced"),
+            AppClockSource::Manual(nanos) => {
+                nanos.fetch_add(dt.as_nanos() as u64, Ordering::AcqRel);
+            }
+        }
+    }
 }
 
 impl<M> Default for AppMonotonicClock<M> {
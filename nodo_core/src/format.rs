@@ -0,0 +1,125 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+//! Human-friendly formatting of durations, rates and sizes used by statistics and tools
+//!
+//! All functions pick the largest unit in which the value is at least one and print three
+//! significant digits. The output does not depend on the locale.
+
+use core::time::Duration;
+
+const DURATION_UNITS: &[(f64, &str)] = &[
+    (1.0, "ns"),
+    (1e3, "µs"),
+    (1e6, "ms"),
+    (1e9, "s"),
+    (60e9, "min"),
+    (3600e9, "h"),
+];
+
+const RATE_UNITS: &[(f64, &str)] = &[(1.0, "Hz"), (1e3, "kHz"), (1e6, "MHz")];
+
+const BYTE_UNITS: &[(f64, &str)] = &[
+    (1.0, "B"),
+    (1024.0, "KiB"),
+    (1048576.0, "MiB"),
+    (1073741824.0, "GiB"),
+    (1099511627776.0, "TiB"),
+];
+
+/// Formats a duration like `950 ns`, `12.3 µs`, `1.00 ms`, `59.9 s`, `1.50 min` or `2.00 h`
+pub fn fmt_duration(duration: Duration) -> String {
+    fmt_scaled(duration.as_nanos() as f64, DURATION_UNITS, true)
+}
+
+/// Formats a rate like `0.500 Hz`, `100 Hz` or `1.20 kHz`
+pub fn fmt_rate(hz: f64) -> String {
+    fmt_scaled(hz, RATE_UNITS, false)
+}
+
+/// Formats a size like `512 B`, `1.50 KiB` or `3.00 MiB`
+pub fn fmt_bytes(bytes: u64) -> String {
+    fmt_scaled(bytes as f64, BYTE_UNITS, true)
+}
+
+/// Rounds to three significant digits and returns the rounded value and its decimal places
+fn round_significant(value: f64) -> (f64, usize) {
+    if value == 0.0 {
+        return (0.0, 0);
+    }
+    let decimals = |x: f64| 2 - x.abs().log10().floor() as i32;
+    let scale = 10f64.powi(decimals(value));
+    let rounded = (value * scale).round() / scale;
+    // rounding might have increased the magnitude, e.g. 9.996 to 10.0
+    (rounded, decimals(rounded).max(0) as usize)
+}
+
+fn fmt_scaled(value: f64, units: &[(f64, &str)], is_base_integer: bool) -> String {
+    if !value.is_finite() {
+        return format!("{value} {}", units[0].1);
+    }
+
+    // pick the largest unit in which the rounded value is at least one
+    let (index, (rounded, decimals)) = units
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(i, (scale, _))| (i, round_significant(value / scale)))
+        .find(|(i, (rounded, _))| *i == 0 || rounded.abs() >= 1.0)
+        .unwrap();
+
+    if index == 0 && is_base_integer {
+        format!("{value:.0} {}", units[0].1)
+    } else {
+        format!("{rounded:.decimals$} {}", units[index].1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fmt_bytes, fmt_duration, fmt_rate};
+    use core::time::Duration;
+
+    #[test]
+    fn test_fmt_duration() {
+        let f = |ns: u64| fmt_duration(Duration::from_nanos(ns));
+        assert_eq!(f(0), "0 ns");
+        assert_eq!(f(7), "7 ns");
+        assert_eq!(f(999), "999 ns");
+        assert_eq!(f(1_000), "1.00 µs");
+        assert_eq!(f(12_345), "12.3 µs");
+        assert_eq!(f(999_000), "999 µs");
+        assert_eq!(f(999_600), "1.00 ms");
+        assert_eq!(f(1_000_000), "1.00 ms");
+        assert_eq!(f(7_126_000_000), "7.13 s");
+        assert_eq!(f(59_900_000_000), "59.9 s");
+        assert_eq!(f(59_990_000_000), "1.00 min");
+        assert_eq!(f(60_000_000_000), "1.00 min");
+        assert_eq!(f(90_000_000_000), "1.50 min");
+        assert_eq!(f(3_500_000_000_000), "58.3 min");
+        assert_eq!(f(3_599_000_000_000), "1.00 h");
+        assert_eq!(f(3_600_000_000_000), "1.00 h");
+        assert_eq!(f(100 * 3_600_000_000_000), "100 h");
+        assert_eq!(f(2000 * 3_600_000_000_000), "2000 h");
+    }
+
+    #[test]
+    fn test_fmt_rate() {
+        assert_eq!(fmt_rate(0.0), "0 Hz");
+        assert_eq!(fmt_rate(0.5), "0.500 Hz");
+        assert_eq!(fmt_rate(100.0), "100 Hz");
+        assert_eq!(fmt_rate(999.4), "999 Hz");
+        assert_eq!(fmt_rate(999.6), "1.00 kHz");
+        assert_eq!(fmt_rate(1200.0), "1.20 kHz");
+        assert_eq!(fmt_rate(2.5e6), "2.50 MHz");
+    }
+
+    #[test]
+    fn test_fmt_bytes() {
+        assert_eq!(fmt_bytes(0), "0 B");
+        assert_eq!(fmt_bytes(1023), "1023 B");
+        assert_eq!(fmt_bytes(1024), "1.00 KiB");
+        assert_eq!(fmt_bytes(1536), "1.50 KiB");
+        assert_eq!(fmt_bytes(1024 * 1023), "1020 KiB");
+        assert_eq!(fmt_bytes(3 * 1024 * 1024), "3.00 MiB");
+    }
+}
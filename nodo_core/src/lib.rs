@@ -1,16 +1,32 @@
 // Copyright 2023 by David Weikersdorfer. All rights reserved.
 
+pub mod capabilities;
 mod clock;
+mod format;
 #[macro_use]
 mod outcome;
 mod message;
+mod name;
+mod retry;
+mod self_describing;
+mod seq_allocator;
 mod serializable;
 mod stamped;
+mod time_anchor;
 mod timestamp;
+mod unknown_fields;
 
+pub use capabilities::Capability;
 pub use clock::*;
+pub use format::*;
 pub use message::*;
+pub use name::*;
 pub use outcome::*;
+pub use retry::*;
+pub use self_describing::*;
+pub use seq_allocator::*;
 pub use serializable::*;
 pub use stamped::*;
+pub use time_anchor::*;
 pub use timestamp::*;
+pub use unknown_fields::*;
@@ -4,6 +4,9 @@ use crate::{Acqtime, Stamp, WithAcqtime};
 #[derive(Debug, Clone)]
 pub struct Message<T> {
     /// Sequence number as issued by transmitter
+    ///
+    /// Producers number their messages on their own. Use a shared [crate::SeqAllocator] if
+    /// sequence numbers must be unique across producers.
     pub seq: u64,
 
     /// Timestamps
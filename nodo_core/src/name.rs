@@ -0,0 +1,434 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+};
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{hash_map::DefaultHasher, HashMap},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+/// An interned string used for names of codelets, sequences, schedules, channels and types
+///
+/// Equal names share the same allocation, thus cloning is cheap and comparing is usually a
+/// pointer comparison. The hash is computed once when the name is interned. Names are serialized
+/// as plain strings, or as indices while a [NameTable] is active.
+#[derive(Clone)]
+pub struct Name {
+    text: Arc<str>,
+    hash: u64,
+}
+
+impl Name {
+    /// Interns the given string
+    pub fn new(text: &str) -> Self {
+        interner().lock().unwrap().intern(text)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// True if both names share the same allocation
+    pub fn ptr_eq(a: &Name, b: &Name) -> bool {
+        Arc::ptr_eq(&a.text, &b.text)
+    }
+
+    /// Number of strings currently held by the interner
+    pub fn interned_count() -> usize {
+        interner().lock().unwrap().len()
+    }
+}
+
+fn hash_str(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Minimal number of interned strings before unused strings are released
+const MIN_PURGE_THRESHOLD: usize = 1024;
+
+/// Interned strings by hash
+///
+/// Strings which are not used by any name anymore are released when the number of strings
+/// doubled since the last purge. Memory is thus bounded by twice the number of live names.
+struct Interner {
+    buckets: HashMap<u64, Vec<Arc<str>>>,
+    len: usize,
+    purge_threshold: usize,
+}
+
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(|| {
+        Mutex::new(Interner {
+            buckets: HashMap::new(),
+            len: 0,
+            purge_threshold: MIN_PURGE_THRESHOLD,
+        })
+    })
+}
+
+impl Interner {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn intern(&mut self, text: &str) -> Name {
+        let hash = hash_str(text);
+        if let Some(existing) = self
+            .buckets
+            .get(&hash)
+            .and_then(|bucket| bucket.iter().find(|s| ***s == *text))
+        {
+            return Name {
+                text: existing.clone(),
+                hash,
+            };
+        }
+
+        if self.len >= self.purge_threshold {
+            self.purge();
+        }
+
+        let text: Arc<str> = Arc::from(text);
+        self.buckets.entry(hash).or_default().push(text.clone());
+        self.len += 1;
+        Name { text, hash }
+    }
+
+    /// Releases strings which are only held by the interner
+    fn purge(&mut self) {
+        self.buckets.retain(|_, bucket| {
+            bucket.retain(|s| Arc::strong_count(s) > 1);
+            !bucket.is_empty()
+        });
+        self.len = self.buckets.values().map(Vec::len).sum();
+        self.purge_threshold = (2 * self.len).max(MIN_PURGE_THRESHOLD);
+    }
+}
+
+impl Default for Name {
+    fn default() -> Self {
+        Name::new("")
+    }
+}
+
+impl Deref for Name {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.text
+    }
+}
+
+impl AsRef<str> for Name {
+    fn as_ref(&self) -> &str {
+        &self.text
+    }
+}
+
+impl PartialEq for Name {
+    fn eq(&self, other: &Self) -> bool {
+        Name::ptr_eq(self, other) || (self.hash == other.hash && self.text == other.text)
+    }
+}
+
+impl Eq for Name {}
+
+impl PartialEq<str> for Name {
+    fn eq(&self, other: &str) -> bool {
+        &*self.text == other
+    }
+}
+
+impl PartialEq<&str> for Name {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.text == *other
+    }
+}
+
+impl PartialEq<String> for Name {
+    fn eq(&self, other: &String) -> bool {
+        *self.text == **other
+    }
+}
+
+impl PartialEq<Name> for str {
+    fn eq(&self, other: &Name) -> bool {
+        self == &*other.text
+    }
+}
+
+impl PartialEq<Name> for &str {
+    fn eq(&self, other: &Name) -> bool {
+        *self == &*other.text
+    }
+}
+
+impl PartialEq<Name> for String {
+    fn eq(&self, other: &Name) -> bool {
+        **self == *other.text
+    }
+}
+
+impl PartialOrd for Name {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Name {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.text.cmp(&other.text)
+    }
+}
+
+impl Hash for Name {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+impl fmt::Debug for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.text, f)
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.text, f)
+    }
+}
+
+impl From<&str> for Name {
+    fn from(text: &str) -> Self {
+        Name::new(text)
+    }
+}
+
+impl From<String> for Name {
+    fn from(text: String) -> Self {
+        Name::new(&text)
+    }
+}
+
+impl From<&String> for Name {
+    fn from(text: &String) -> Self {
+        Name::new(text)
+    }
+}
+
+impl From<Cow<'_, str>> for Name {
+    fn from(text: Cow<'_, str>) -> Self {
+        Name::new(&text)
+    }
+}
+
+impl From<&Name> for Name {
+    fn from(name: &Name) -> Self {
+        name.clone()
+    }
+}
+
+impl From<Name> for String {
+    fn from(name: Name) -> Self {
+        name.text.to_string()
+    }
+}
+
+thread_local! {
+    static ACTIVE_TABLE: RefCell<Option<NameTable>> = const { RefCell::new(None) };
+}
+
+/// Table of names which replaces repeated names by indices during serialization
+///
+/// While a table is active on the current thread with [NameTable::encode] or [NameTable::decode]
+/// names are serialized as indices into the table instead of strings. Deserialization accepts
+/// both strings and indices. This shrinks encodings which contain the same names many times, for
+/// example inspector reports. The table has to be transmitted alongside the encoding.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NameTable {
+    names: Vec<Name>,
+    indices: HashMap<Name, u32>,
+}
+
+impl NameTable {
+    /// Creates a table for decoding from names collected by [NameTable::encode]
+    pub fn from_names(names: Vec<Name>) -> Self {
+        Self {
+            names,
+            indices: HashMap::new(),
+        }
+    }
+
+    /// Names in the order of their indices
+    pub fn names(&self) -> &[Name] {
+        &self.names
+    }
+
+    /// Serializes names as indices while `f` runs and returns the collected table
+    pub fn encode<R>(f: impl FnOnce() -> R) -> (R, NameTable) {
+        Self::with_active(NameTable::default(), f)
+    }
+
+    /// Deserializes name indices with this table while `f` runs
+    pub fn decode<R>(self, f: impl FnOnce() -> R) -> R {
+        Self::with_active(self, f).0
+    }
+
+    /// True while a table is active on the current thread
+    pub(crate) fn is_active() -> bool {
+        ACTIVE_TABLE.with(|active| active.borrow().is_some())
+    }
+
+    fn with_active<R>(table: NameTable, f: impl FnOnce() -> R) -> (R, NameTable) {
+        let previous = ACTIVE_TABLE.with(|active| active.borrow_mut().replace(table));
+        let result = f();
+        let table =
+            ACTIVE_TABLE.with(|active| core::mem::replace(&mut *active.borrow_mut(), previous));
+        (result, table.unwrap_or_default())
+    }
+
+    fn index(&mut self, name: &Name) -> u32 {
+        if let Some(&index) = self.indices.get(name) {
+            return index;
+        }
+        let index = self.names.len() as u32;
+        self.names.push(name.clone());
+        self.indices.insert(name.clone(), index);
+        index
+    }
+}
+
+impl Serialize for Name {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let index =
+            ACTIVE_TABLE.with(|active| active.borrow_mut().as_mut().map(|table| table.index(self)));
+        match index {
+            Some(index) => serializer.serialize_u32(index),
+            None => serializer.serialize_str(&self.text),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Name {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if NameTable::is_active() {
+            deserializer.deserialize_any(NameVisitor)
+        } else {
+            deserializer.deserialize_str(NameVisitor)
+        }
+    }
+}
+
+struct NameVisitor;
+
+impl Visitor<'_> for NameVisitor {
+    type Value = Name;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a name or an index into the name table")
+    }
+
+    fn visit_str<E: de::Error>(self, text: &str) -> Result<Name, E> {
+        Ok(Name::new(text))
+    }
+
+    fn visit_u64<E: de::Error>(self, index: u64) -> Result<Name, E> {
+        ACTIVE_TABLE
+            .with(|active| {
+                active
+                    .borrow()
+                    .as_ref()
+                    .and_then(|table| table.names.get(index as usize).cloned())
+            })
+            .ok_or_else(|| E::custom(format!("name index {index} not in name table")))
+    }
+
+    fn visit_i64<E: de::Error>(self, index: i64) -> Result<Name, E> {
+        let index =
+            u64::try_from(index).map_err(|_| E::custom(format!("invalid name index {index}")))?;
+        self.visit_u64(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Name, NameTable};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Entry {
+        name: Name,
+        path: Vec<Name>,
+    }
+
+    #[test]
+    fn test_interning() {
+        let a = Name::new("schedule/sequence/codelet");
+        let b = Name::from(String::from("schedule/sequence/codelet"));
+        let c = Name::from("schedule/sequence/other");
+        assert!(Name::ptr_eq(&a, &b));
+        assert!(!Name::ptr_eq(&a, &c));
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a, "schedule/sequence/codelet");
+        assert!(a < c);
+        assert_eq!(a.len(), 25);
+        assert_eq!(format!("{a:?}"), "\"schedule/sequence/codelet\"");
+    }
+
+    #[test]
+    fn test_unused_names_are_released() {
+        let keep = Name::new("test_unused_names_are_released");
+        for i in 0..10_000 {
+            Name::new(&format!("test_unused_names_are_released/{i}"));
+        }
+        assert!(Name::interned_count() < 4_000, "{}", Name::interned_count());
+        assert!(Name::ptr_eq(
+            &keep,
+            &Name::new("test_unused_names_are_released")
+        ));
+    }
+
+    #[test]
+    fn test_serde_plain_string() {
+        let entry = Entry {
+            name: Name::new("a"),
+            path: vec![Name::new("s"), Name::new("a")],
+        };
+        let json = serde_json::to_string(&entry).unwrap();
+        assert_eq!(json, r#"{"name":"a","path":["s","a"]}"#);
+        assert_eq!(serde_json::from_str::<Entry>(&json).unwrap(), entry);
+    }
+
+    #[test]
+    fn test_name_table() {
+        let entry = Entry {
+            name: Name::new("a"),
+            path: vec![Name::new("s"), Name::new("a")],
+        };
+        let (json, table) = NameTable::encode(|| serde_json::to_string(&entry).unwrap());
+        assert_eq!(json, r#"{"name":0,"path":[1,0]}"#);
+        assert_eq!(table.names(), [Name::new("a"), Name::new("s")]);
+
+        let table = NameTable::from_names(table.names().to_vec());
+        let decoded = table.decode(|| serde_json::from_str::<Entry>(&json).unwrap());
+        assert_eq!(decoded, entry);
+
+        // indices are rejected without a table
+        assert!(serde_json::from_str::<Entry>(&json).is_err());
+    }
+}
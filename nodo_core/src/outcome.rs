@@ -19,6 +19,61 @@ pub enum DefaultStatus {
     Running,
 }
 
+/// Reason why a codelet skipped a step, see `Context::skip_because`
+///
+/// Tools show the reason next to the status and statistics count skips per reason.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SkipReason {
+    /// No new input arrived
+    NoInput,
+
+    /// The codelet limits how often it does work
+    Throttled,
+
+    /// The codelet is disabled
+    Disabled,
+
+    /// The codelet waits for the warm-up phase to end
+    WarmUp,
+
+    /// Any other reason
+    Custom(&'static str),
+}
+
+impl SkipReason {
+    /// Label used for skips which do not specify a reason
+    pub const UNSPECIFIED_LABEL: &'static str = "unspecified";
+
+    /// Human-readable name of the reason
+    pub fn label(&self) -> &'static str {
+        match self {
+            SkipReason::NoInput => "no input",
+            SkipReason::Throttled => "throttled",
+            SkipReason::Disabled => "disabled",
+            SkipReason::WarmUp => "warm-up",
+            SkipReason::Custom(label) => label,
+        }
+    }
+}
+
+/// Severity of a codelet status used by tools to highlight problems
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Severity {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Severity {
+    /// Default severity for a status: running is informational while skipping is a warning
+    pub fn from_default_status(status: DefaultStatus) -> Self {
+        match status {
+            DefaultStatus::Skipped => Severity::Warn,
+            DefaultStatus::Running => Severity::Info,
+        }
+    }
+}
+
 pub const SKIPPED: Outcome = Ok(DefaultStatus::Skipped);
 
 // TODO to be enabled #[deprecated(note = "use RUNNING instead")]
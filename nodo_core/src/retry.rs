@@ -0,0 +1,263 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::EyreResult;
+use core::time::Duration;
+
+/// Configuration of a [Retrier]
+#[derive(Debug, Clone)]
+pub struct RetryConfig {
+    /// Maximum number of attempts of an operation including the first one
+    pub max_attempts: u32,
+
+    /// Backoff after the first failed attempt. It doubles with every further failure.
+    pub initial_backoff: Duration,
+
+    /// Upper limit for the backoff
+    pub max_backoff: Duration,
+
+    /// Fraction in [0, 1] by which the backoff is randomly shortened to spread out retries
+    pub jitter: f64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+            jitter: 0.2,
+        }
+    }
+}
+
+/// Result of an attempt made with a [Retrier]
+#[derive(Debug)]
+pub enum RetryOutcome<T> {
+    /// The operation succeeded
+    Done(T),
+
+    /// The operation failed or was not attempted because the backoff did not pass yet. It shall
+    /// be attempted again not before the given time.
+    Retry {
+        not_before: Duration,
+        error: Option<eyre::Report>,
+    },
+
+    /// The operation failed for the last allowed time
+    Exhausted(eyre::Report),
+}
+
+/// Retries a fallible operation with exponential backoff without blocking
+///
+/// The retrier never sleeps. Instead it tells the caller when to retry. A codelet can store it and
+/// attempt the operation again in a later step. Times are given by the caller, e.g. from
+/// `cx.clocks.app_mono`.
+#[derive(Debug, Clone)]
+pub struct Retrier {
+    config: RetryConfig,
+    failed_attempts: u32,
+    not_before: Option<Duration>,
+    retry_count: u64,
+    exhaustion_count: u64,
+    rng: u64,
+}
+
+impl Retrier {
+    pub fn new(config: RetryConfig) -> Self {
+        Self::with_seed(config, 0x9E37_79B9_7F4A_7C15)
+    }
+
+    /// Creates a retrier with a seed for the jitter
+    pub fn with_seed(config: RetryConfig, seed: u64) -> Self {
+        assert!(config.max_attempts > 0, "max_attempts must be at least 1");
+        assert!(
+            (0.0..=1.0).contains(&config.jitter),
+            "jitter must be in [0, 1]"
+        );
+
+        Self {
+            config,
+            failed_attempts: 0,
+            not_before: None,
+            retry_count: 0,
+            exhaustion_count: 0,
+            rng: seed,
+        }
+    }
+
+    /// Backoff after the given number of failed attempts without jitter
+    pub fn backoff(&self, failed_attempts: u32) -> Duration {
+        let factor = 1u32 << failed_attempts.saturating_sub(1).min(31);
+        self.config
+            .initial_backoff
+            .saturating_mul(factor)
+            .min(self.config.max_backoff)
+    }
+
+    /// Time before which the operation shall not be attempted again while it is in backoff
+    pub fn not_before(&self) -> Option<Duration> {
+        self.not_before
+    }
+
+    /// True if the operation may be attempted at the given time
+    pub fn is_ready(&self, now: Duration) -> bool {
+        self.not_before.is_none_or(|t| now >= t)
+    }
+
+    /// True if the previous attempt failed and the operation is waiting to be retried
+    pub fn is_retrying(&self) -> bool {
+        self.failed_attempts > 0
+    }
+
+    /// Total number of failed attempts which were followed by a retry
+    pub fn retry_count(&self) -> u64 {
+        self.retry_count
+    }
+
+    /// Total number of operations which failed for the last allowed time
+    pub fn exhaustion_count(&self) -> u64 {
+        self.exhaustion_count
+    }
+
+    /// Forgets failed attempts of the current operation
+    pub fn reset(&mut self) {
+        self.failed_attempts = 0;
+        self.not_before = None;
+    }
+
+    /// Attempts the operation unless it is still in backoff
+    pub fn attempt<T, F>(&mut self, now: Duration, op: F) -> RetryOutcome<T>
+    where
+        F: FnOnce() -> EyreResult<T>,
+    {
+        if let Some(not_before) = self.not_before.filter(|&t| now < t) {
+            return RetryOutcome::Retry {
+                not_before,
+                error: None,
+            };
+        }
+
+        match op() {
+            Ok(value) => {
+                self.reset();
+                RetryOutcome::Done(value)
+            }
+            Err(error) => {
+                self.failed_attempts += 1;
+                if self.failed_attempts >= self.config.max_attempts {
+                    self.exhaustion_count += 1;
+                    self.reset();
+                    RetryOutcome::Exhausted(error)
+                } else {
+                    self.retry_count += 1;
+                    let not_before = now + self.jittered(self.backoff(self.failed_attempts));
+                    self.not_before = Some(not_before);
+                    RetryOutcome::Retry {
+                        not_before,
+                        error: Some(error),
+                    }
+                }
+            }
+        }
+    }
+
+    fn jittered(&mut self, backoff: Duration) -> Duration {
+        if self.config.jitter == 0.0 {
+            return backoff;
+        }
+
+        // xorshift64
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        let unit = (self.rng >> 11) as f64 / (1u64 << 53) as f64;
+
+        backoff.mul_f64(1.0 - self.config.jitter * unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{eyre, EyreResult, Retrier, RetryConfig, RetryOutcome};
+    use core::time::Duration;
+
+    fn ms(x: u64) -> Duration {
+        Duration::from_millis(x)
+    }
+
+    fn config(jitter: f64) -> RetryConfig {
+        RetryConfig {
+            max_attempts: 6,
+            initial_backoff: ms(10),
+            max_backoff: ms(50),
+            jitter,
+        }
+    }
+
+    #[test]
+    fn test_backoff_schedule() {
+        let mut retrier = Retrier::new(config(0.0));
+        assert_eq!(
+            (1..=6).map(|i| retrier.backoff(i)).collect::<Vec<_>>(),
+            [ms(10), ms(20), ms(40), ms(50), ms(50), ms(50)]
+        );
+
+        // a failing operation is retried after the backoff until it is exhausted
+        let mut now = ms(0);
+        let mut attempts = Vec::new();
+        loop {
+            match retrier.attempt(now, || -> EyreResult<()> { Err(eyre!("fail")) }) {
+                RetryOutcome::Retry {
+                    not_before,
+                    error: Some(_),
+                } => {
+                    attempts.push(now);
+
+                    // attempts before the backoff passed are not executed
+                    let outcome = retrier.attempt(not_before - ms(1), || -> EyreResult<()> {
+                        panic!("attempted during backoff")
+                    });
+                    assert!(matches!(outcome, RetryOutcome::Retry { error: None, .. }));
+
+                    now = not_before;
+                }
+                RetryOutcome::Exhausted(_) => {
+                    attempts.push(now);
+                    break;
+                }
+                outcome => panic!("unexpected outcome {outcome:?}"),
+            }
+        }
+        assert_eq!(attempts, [ms(0), ms(10), ms(30), ms(70), ms(120), ms(170)]);
+        assert_eq!(retrier.retry_count(), 5);
+        assert_eq!(retrier.exhaustion_count(), 1);
+        assert!(retrier.is_ready(now));
+
+        // success resets the backoff
+        let _ = retrier.attempt(now, || -> EyreResult<()> { Err(eyre!("fail")) });
+        assert!(retrier.is_retrying());
+        let outcome = retrier.attempt(now + ms(10), || Ok(3));
+        assert!(matches!(outcome, RetryOutcome::Done(3)));
+        assert!(!retrier.is_retrying());
+        assert_eq!(retrier.not_before(), None);
+    }
+
+    #[test]
+    fn test_jitter() {
+        let mut retrier = Retrier::with_seed(config(0.5), 7);
+        for _ in 0..100 {
+            let RetryOutcome::Retry {
+                not_before,
+                error: Some(_),
+            } = retrier.attempt(ms(0), || -> EyreResult<()> { Err(eyre!("fail")) })
+            else {
+                panic!("expected retry");
+            };
+            assert!(
+                ms(5) <= not_before && not_before <= ms(10),
+                "{not_before:?}"
+            );
+            retrier.reset();
+        }
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+
+/// Structure of a serialized type, e.g. to decode bincode messages without the Rust definitions
+///
+/// Fields are listed in declaration order which is also the order in which serde serializes them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TypeSchema {
+    /// A primitive type like `u32`, `f64`, `bool` or `string`
+    Primitive { name: String },
+
+    /// A variable length sequence like `Vec<T>`
+    Seq { item: Box<TypeSchema> },
+
+    /// A fixed size array like `[T; N]`
+    Array { item: Box<TypeSchema>, len: usize },
+
+    /// An optional value
+    Option { item: Box<TypeSchema> },
+
+    /// A tuple. The unit type is an empty tuple.
+    Tuple { items: Vec<TypeSchema> },
+
+    /// A struct. Fields of tuple structs are named by their index.
+    Struct {
+        name: String,
+        fields: Vec<FieldSchema>,
+    },
+
+    /// An enum. Variants are listed in declaration order which is their bincode index.
+    Enum {
+        name: String,
+        variants: Vec<VariantSchema>,
+    },
+}
+
+/// A named field of a struct or enum variant
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldSchema {
+    pub name: String,
+
+    #[serde(rename = "type")]
+    pub ty: TypeSchema,
+}
+
+/// A variant of an enum. Unit variants have no fields.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VariantSchema {
+    pub name: String,
+    pub fields: Vec<FieldSchema>,
+}
+
+impl FieldSchema {
+    pub fn new<T: SelfDescribing + ?Sized>(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ty: T::describe(),
+        }
+    }
+}
+
+impl TypeSchema {
+    pub fn primitive(name: &str) -> Self {
+        TypeSchema::Primitive {
+            name: name.to_string(),
+        }
+    }
+}
+
+/// Types which can describe their own serialized structure
+///
+/// Can be derived with `#[derive(SelfDescribing)]` for structs and enums whose fields are
+/// self-describing. Serde attributes like `rename` or `skip` are not taken into account.
+pub trait SelfDescribing {
+    fn describe() -> TypeSchema;
+}
+
+macro_rules! impl_self_describing_primitive {
+    ( $( $ty: ty => $name: literal ),* ) => {
+        $(
+            impl SelfDescribing for $ty {
+                fn describe() -> TypeSchema {
+                    TypeSchema::primitive($name)
+                }
+            }
+        )*
+    };
+}
+
+impl_self_describing_primitive!(
+    bool => "bool",
+    u8 => "u8", u16 => "u16", u32 => "u32", u64 => "u64", u128 => "u128", usize => "u64",
+    i8 => "i8", i16 => "i16", i32 => "i32", i64 => "i64", i128 => "i128", isize => "i64",
+    f32 => "f32", f64 => "f64",
+    char => "char", String => "string", str => "string"
+);
+
+impl<T: SelfDescribing> SelfDescribing for Vec<T> {
+    fn describe() -> TypeSchema {
+        TypeSchema::Seq {
+            item: Box::new(T::describe()),
+        }
+    }
+}
+
+impl<T: SelfDescribing> SelfDescribing for Option<T> {
+    fn describe() -> TypeSchema {
+        TypeSchema::Option {
+            item: Box::new(T::describe()),
+        }
+    }
+}
+
+impl<T: SelfDescribing, const N: usize> SelfDescribing for [T; N] {
+    fn describe() -> TypeSchema {
+        TypeSchema::Array {
+            item: Box::new(T::describe()),
+            len: N,
+        }
+    }
+}
+
+impl<T: SelfDescribing + ?Sized> SelfDescribing for Box<T> {
+    fn describe() -> TypeSchema {
+        T::describe()
+    }
+}
+
+impl SelfDescribing for Duration {
+    fn describe() -> TypeSchema {
+        TypeSchema::Struct {
+            name: "Duration".into(),
+            fields: vec![
+                FieldSchema::new::<u64>("secs"),
+                FieldSchema::new::<u32>("nanos"),
+            ],
+        }
+    }
+}
+
+macro_rules! impl_self_describing_tuple {
+    ( $( $ty: ident ),* ) => {
+        impl<$($ty: SelfDescribing),*> SelfDescribing for ($($ty,)*) {
+            fn describe() -> TypeSchema {
+                TypeSchema::Tuple {
+                    items: vec![$($ty::describe()),*],
+                }
+            }
+        }
+    };
+}
+
+impl_self_describing_tuple!();
+impl_self_describing_tuple!(A);
+impl_self_describing_tuple!(A, B);
+impl_self_describing_tuple!(A, B, C);
+impl_self_describing_tuple!(A, B, C, D);
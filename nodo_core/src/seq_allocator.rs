@@ -0,0 +1,136 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::{Message, Stamp};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// Issues sequence numbers which are unique across all producers sharing the same domain
+///
+/// By default every producer numbers its messages on its own and sequence numbers of different
+/// producers collide when their streams are merged, for example by a join. Producers which clone
+/// the same allocator instead get globally unique and monotonically increasing numbers.
+#[derive(Debug, Clone)]
+pub struct SeqAllocator {
+    domain: Arc<str>,
+    next: Arc<AtomicU64>,
+}
+
+impl SeqAllocator {
+    /// Creates a new domain which starts at 0
+    pub fn new(domain: &str) -> Self {
+        Self {
+            domain: domain.into(),
+            next: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Name of the domain
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// Allocates the next sequence number
+    pub fn next(&self) -> u64 {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Number of sequence numbers allocated so far
+    pub fn allocated(&self) -> u64 {
+        self.next.load(Ordering::Relaxed)
+    }
+
+    /// Creates a message with the next sequence number
+    pub fn message<T>(&self, stamp: Stamp, value: T) -> Message<T> {
+        Message {
+            seq: self.next(),
+            stamp,
+            value,
+        }
+    }
+
+    /// True if both handles allocate from the same domain instance
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.next, &other.next)
+    }
+}
+
+/// Sequence number domains by name
+#[derive(Debug, Default)]
+pub struct SeqDomains {
+    domains: Mutex<HashMap<String, SeqAllocator>>,
+}
+
+impl SeqDomains {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the allocator for the given domain and creates it on first use
+    pub fn get(&self, domain: &str) -> SeqAllocator {
+        self.domains
+            .lock()
+            .unwrap()
+            .entry(domain.to_string())
+            .or_insert_with(|| SeqAllocator::new(domain))
+            .clone()
+    }
+
+    /// Names of all domains sorted alphabetically
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.domains.lock().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SeqAllocator, SeqDomains};
+
+    #[test]
+    fn test_domains() {
+        let domains = SeqDomains::new();
+        let a = domains.get("detections");
+        let b = domains.get("detections");
+        let c = domains.get("tracks");
+        assert!(a.ptr_eq(&b));
+        assert!(!a.ptr_eq(&c));
+        assert_eq!(a.domain(), "detections");
+        assert_eq!(domains.names(), ["detections", "tracks"]);
+
+        assert_eq!(a.next(), 0);
+        assert_eq!(b.next(), 1);
+        assert_eq!(c.next(), 0);
+        assert_eq!(a.allocated(), 2);
+    }
+
+    #[test]
+    fn test_concurrent_allocation() {
+        const COUNT: usize = 10_000;
+
+        let alloc = SeqAllocator::new("test");
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let alloc = alloc.clone();
+                std::thread::spawn(move || (0..COUNT).map(|_| alloc.next()).collect::<Vec<_>>())
+            })
+            .collect();
+
+        let mut all = Vec::new();
+        for thread in threads {
+            let seqs = thread.join().unwrap();
+            // every thread observes strictly increasing numbers
+            assert!(seqs.windows(2).all(|w| w[0] < w[1]));
+            all.extend(seqs);
+        }
+
+        // all numbers are unique and none are skipped
+        all.sort();
+        assert!(all.iter().copied().eq(0..4 * COUNT as u64));
+    }
+}
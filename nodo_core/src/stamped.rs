@@ -33,7 +33,7 @@ pub struct PubtimeMarker;
 
 pub type Pubtime = Timestamp<PubtimeMarker>;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TimestampKind {
     Acq,
     Pub,
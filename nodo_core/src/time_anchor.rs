@@ -0,0 +1,244 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::{Acqtime, AcqtimeMarker, Pubtime, PubtimeMarker, Timestamp};
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Offset between TAI and UTC in seconds as of 2017-01-01. It changes with every leap second.
+pub const TAI_UTC_OFFSET_SECS_2017: u32 = 37;
+
+/// Offset between TAI and GPS time in seconds. GPS time does not observe leap seconds.
+pub const TAI_GPS_OFFSET_SECS: u32 = 19;
+
+/// Unix time of the GPS epoch 1980-01-06T00:00:00Z in seconds
+pub const GPS_EPOCH_UNIX_SECS: u64 = 315_964_800;
+
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+
+/// Configuration of conversions to time standards which depend on leap seconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeStandardConfig {
+    /// Number of seconds TAI is ahead of UTC, e.g. [TAI_UTC_OFFSET_SECS_2017]. TAI and GPS
+    /// conversions are only available if set. The value is not updated automatically when a
+    /// leap second is announced.
+    pub tai_utc_offset_secs: Option<u32>,
+}
+
+/// Clock markers whose timestamps can be converted to wall-clock time with a [TimeAnchor]
+pub trait AnchoredMarker {
+    /// Reading of the clock for this marker at the time the anchor was captured
+    fn anchor_reading(anchor: &TimeAnchor) -> Duration;
+}
+
+impl AnchoredMarker for PubtimeMarker {
+    fn anchor_reading(anchor: &TimeAnchor) -> Duration {
+        *anchor.app_mono
+    }
+}
+
+impl AnchoredMarker for AcqtimeMarker {
+    fn anchor_reading(anchor: &TimeAnchor) -> Duration {
+        *anchor.sys_mono
+    }
+}
+
+/// Pairs readings of the app and system monotonic clocks with wall-clock time
+///
+/// Timestamps of nodo messages are durations on monotonic clocks. The anchor is captured once
+/// when the runtime starts and converts them to Unix time, and optionally to TAI or GPS time.
+///
+/// Monotonic clocks and the wall clock drift apart over time: the wall clock is disciplined by
+/// NTP and may be stepped while monotonic clocks are only slewed or not corrected at all. The
+/// anchor is never updated, thus converted times of very long runs can be off by the accumulated
+/// drift, typically in the order of milliseconds per day.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimeAnchor {
+    app_mono: Pubtime,
+    sys_mono: Acqtime,
+    unix: Duration,
+    standards: TimeStandardConfig,
+}
+
+impl TimeAnchor {
+    /// Creates an anchor from clock readings taken at the same time
+    pub fn new(app_mono: Pubtime, sys_mono: Acqtime, wall_clock: SystemTime) -> Self {
+        Self {
+            app_mono,
+            sys_mono,
+            unix: wall_clock.duration_since(UNIX_EPOCH).unwrap_or_default(),
+            standards: TimeStandardConfig::default(),
+        }
+    }
+
+    /// Pairs the given clock readings with the current wall-clock time
+    pub fn capture(app_mono: Pubtime, sys_mono: Acqtime) -> Self {
+        Self::new(app_mono, sys_mono, SystemTime::now())
+    }
+
+    /// Enables conversions to TAI and GPS time
+    #[must_use]
+    pub fn with_time_standards(mut self, config: TimeStandardConfig) -> Self {
+        self.standards = config;
+        self
+    }
+
+    pub fn time_standards(&self) -> &TimeStandardConfig {
+        &self.standards
+    }
+
+    /// Unix time at which the anchor was captured
+    pub fn unix_time(&self) -> Duration {
+        self.unix
+    }
+
+    /// Converts a timestamp to nanoseconds since the Unix epoch. Saturates at zero.
+    pub fn to_unix_nanos<M: AnchoredMarker>(&self, time: Timestamp<M>) -> u64 {
+        saturate(self.unix_nanos(time))
+    }
+
+    /// Converts nanoseconds since the Unix epoch to a timestamp. Saturates at zero.
+    pub fn from_unix_nanos<M: AnchoredMarker>(&self, nanos: u64) -> Timestamp<M> {
+        self.timestamp_from_offset_nanos(nanos as i128, 0)
+    }
+
+    /// Converts a timestamp to TAI as nanoseconds since 1970-01-01 TAI, i.e. the convention of
+    /// `CLOCK_TAI`. None if no TAI-UTC offset is configured.
+    pub fn to_tai_nanos<M: AnchoredMarker>(&self, time: Timestamp<M>) -> Option<u64> {
+        Some(saturate(self.unix_nanos(time) + self.tai_offset_nanos()?))
+    }
+
+    /// Inverse of [TimeAnchor::to_tai_nanos]
+    pub fn from_tai_nanos<M: AnchoredMarker>(&self, nanos: u64) -> Option<Timestamp<M>> {
+        Some(self.timestamp_from_offset_nanos(nanos as i128, self.tai_offset_nanos()?))
+    }
+
+    /// Converts a timestamp to GPS time as nanoseconds since the GPS epoch. None if no TAI-UTC
+    /// offset is configured.
+    pub fn to_gps_nanos<M: AnchoredMarker>(&self, time: Timestamp<M>) -> Option<u64> {
+        Some(saturate(self.unix_nanos(time) + self.gps_offset_nanos()?))
+    }
+
+    /// Inverse of [TimeAnchor::to_gps_nanos]
+    pub fn from_gps_nanos<M: AnchoredMarker>(&self, nanos: u64) -> Option<Timestamp<M>> {
+        Some(self.timestamp_from_offset_nanos(nanos as i128, self.gps_offset_nanos()?))
+    }
+
+    fn unix_nanos<M: AnchoredMarker>(&self, time: Timestamp<M>) -> i128 {
+        self.unix.as_nanos() as i128 + time.as_nanos() as i128
+            - M::anchor_reading(self).as_nanos() as i128
+    }
+
+    /// Converts nanoseconds in a time standard which is `offset` nanoseconds ahead of Unix time
+    fn timestamp_from_offset_nanos<M: AnchoredMarker>(
+        &self,
+        nanos: i128,
+        offset: i128,
+    ) -> Timestamp<M> {
+        let nanos = nanos - offset - self.unix.as_nanos() as i128
+            + M::anchor_reading(self).as_nanos() as i128;
+        Duration::from_nanos(saturate(nanos)).into()
+    }
+
+    fn tai_offset_nanos(&self) -> Option<i128> {
+        self.standards
+            .tai_utc_offset_secs
+            .map(|secs| secs as i128 * NANOS_PER_SEC)
+    }
+
+    /// GPS time counts the leap seconds inserted since the GPS epoch but starts 1980-01-06
+    fn gps_offset_nanos(&self) -> Option<i128> {
+        self.tai_offset_nanos().map(|tai| {
+            tai - TAI_GPS_OFFSET_SECS as i128 * NANOS_PER_SEC
+                - GPS_EPOCH_UNIX_SECS as i128 * NANOS_PER_SEC
+        })
+    }
+}
+
+fn saturate(nanos: i128) -> u64 {
+    nanos.clamp(0, u64::MAX as i128) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Acqtime, AcqtimeMarker, Pubtime, PubtimeMarker, TimeAnchor, TimeStandardConfig,
+        GPS_EPOCH_UNIX_SECS, TAI_UTC_OFFSET_SECS_2017,
+    };
+    use core::time::Duration;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    const SEC: u64 = 1_000_000_000;
+
+    /// 2024-01-01T00:00:00Z
+    const UNIX_2024: u64 = 1_704_067_200;
+
+    fn anchor() -> TimeAnchor {
+        TimeAnchor::new(
+            Duration::from_secs(2).into(),
+            Duration::from_secs(1000).into(),
+            UNIX_EPOCH + Duration::from_secs(UNIX_2024),
+        )
+    }
+
+    #[test]
+    fn test_unix_round_trip() {
+        let anchor = anchor();
+
+        let pubtime: Pubtime = Duration::from_millis(2500).into();
+        let acqtime: Acqtime = Duration::from_millis(1_000_500).into();
+        assert_eq!(anchor.to_unix_nanos(pubtime), UNIX_2024 * SEC + SEC / 2);
+        assert_eq!(anchor.to_unix_nanos(acqtime), UNIX_2024 * SEC + SEC / 2);
+
+        assert_eq!(
+            anchor.from_unix_nanos::<PubtimeMarker>(anchor.to_unix_nanos(pubtime)),
+            pubtime
+        );
+        assert_eq!(
+            anchor.from_unix_nanos::<AcqtimeMarker>(anchor.to_unix_nanos(acqtime)),
+            acqtime
+        );
+
+        // times before the anchor are supported
+        let early: Pubtime = Duration::ZERO.into();
+        assert_eq!(anchor.to_unix_nanos(early), (UNIX_2024 - 2) * SEC);
+
+        // unix times before the clock started saturate
+        assert_eq!(
+            anchor.from_unix_nanos::<PubtimeMarker>(0),
+            Duration::ZERO.into()
+        );
+    }
+
+    #[test]
+    fn test_time_standards() {
+        let pubtime: Pubtime = Duration::from_secs(2).into();
+
+        assert_eq!(anchor().to_tai_nanos(pubtime), None);
+        assert_eq!(anchor().to_gps_nanos(pubtime), None);
+
+        let anchor = anchor().with_time_standards(TimeStandardConfig {
+            tai_utc_offset_secs: Some(TAI_UTC_OFFSET_SECS_2017),
+        });
+        assert_eq!(anchor.to_tai_nanos(pubtime), Some((UNIX_2024 + 37) * SEC));
+
+        // GPS is 18 seconds ahead of UTC since 2017
+        assert_eq!(
+            anchor.to_gps_nanos(pubtime),
+            Some((UNIX_2024 - GPS_EPOCH_UNIX_SECS + 18) * SEC)
+        );
+
+        let tai = anchor.to_tai_nanos(pubtime).unwrap();
+        assert_eq!(anchor.from_tai_nanos(tai), Some(pubtime));
+        let gps = anchor.to_gps_nanos(pubtime).unwrap();
+        assert_eq!(anchor.from_gps_nanos(gps), Some(pubtime));
+    }
+
+    #[test]
+    fn test_capture() {
+        let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let anchor = TimeAnchor::capture(Duration::ZERO.into(), Duration::ZERO.into());
+        let after = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        assert!(before <= anchor.unix_time() && anchor.unix_time() <= after);
+    }
+}
@@ -0,0 +1,176 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::NameTable;
+use core::fmt;
+use serde::{
+    de::{MapAccess, SeqAccess, Visitor},
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::collections::BTreeMap;
+
+/// Fields of a serialized struct which are not known to this version of the struct
+///
+/// Add it to a struct with `#[serde(flatten)]` to keep fields written by a newer version when the
+/// struct is deserialized and serialized again. This only works with self-describing formats
+/// which encode structs as maps with named fields, for example JSON or MessagePack.
+///
+/// Unknown fields are dropped when decoding with a [NameTable]: names inside them are encoded as
+/// indices into the table and can not be told apart from plain numbers.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct UnknownFields(BTreeMap<String, UnknownValue>);
+
+impl<'de> Deserialize<'de> for UnknownFields {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields = BTreeMap::deserialize(deserializer)?;
+        if NameTable::is_active() {
+            Ok(Self::default())
+        } else {
+            Ok(Self(fields))
+        }
+    }
+}
+
+impl UnknownFields {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&UnknownValue> {
+        self.0.get(name)
+    }
+
+    pub fn insert<S: Into<String>>(&mut self, name: S, value: UnknownValue) {
+        self.0.insert(name.into(), value);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &UnknownValue)> {
+        self.0.iter()
+    }
+}
+
+/// A value of any type decoded from a self-describing format, see [UnknownFields]
+#[derive(Debug, Clone, PartialEq)]
+pub enum UnknownValue {
+    Unit,
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    Seq(Vec<UnknownValue>),
+
+    /// Entries in the order in which they were decoded
+    Map(Vec<(UnknownValue, UnknownValue)>),
+}
+
+impl Serialize for UnknownValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            UnknownValue::Unit => serializer.serialize_unit(),
+            UnknownValue::Bool(value) => serializer.serialize_bool(*value),
+            UnknownValue::I64(value) => serializer.serialize_i64(*value),
+            UnknownValue::U64(value) => serializer.serialize_u64(*value),
+            UnknownValue::F64(value) => serializer.serialize_f64(*value),
+            UnknownValue::String(value) => serializer.serialize_str(value),
+            UnknownValue::Bytes(value) => serializer.serialize_bytes(value),
+            UnknownValue::Seq(items) => serializer.collect_seq(items),
+            UnknownValue::Map(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for UnknownValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(UnknownValueVisitor)
+    }
+}
+
+struct UnknownValueVisitor;
+
+impl<'de> Visitor<'de> for UnknownValueVisitor {
+    type Value = UnknownValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "any value")
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<UnknownValue, E> {
+        Ok(UnknownValue::Bool(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<UnknownValue, E> {
+        Ok(UnknownValue::I64(value))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<UnknownValue, E> {
+        Ok(UnknownValue::U64(value))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<UnknownValue, E> {
+        Ok(UnknownValue::F64(value))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<UnknownValue, E> {
+        Ok(UnknownValue::String(value.to_string()))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<UnknownValue, E> {
+        Ok(UnknownValue::String(value))
+    }
+
+    fn visit_bytes<E>(self, value: &[u8]) -> Result<UnknownValue, E> {
+        Ok(UnknownValue::Bytes(value.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(self, value: Vec<u8>) -> Result<UnknownValue, E> {
+        Ok(UnknownValue::Bytes(value))
+    }
+
+    fn visit_none<E>(self) -> Result<UnknownValue, E> {
+        Ok(UnknownValue::Unit)
+    }
+
+    fn visit_unit<E>(self) -> Result<UnknownValue, E> {
+        Ok(UnknownValue::Unit)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<UnknownValue, D::Error> {
+        UnknownValue::deserialize(deserializer)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<UnknownValue, D::Error> {
+        UnknownValue::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<UnknownValue, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(UnknownValue::Seq(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<UnknownValue, A::Error> {
+        let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(UnknownValue::Map(entries))
+    }
+}
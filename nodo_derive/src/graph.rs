@@ -0,0 +1,334 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, quote_spanned};
+use syn::{
+    braced,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    spanned::Spanned,
+    token, Expr, Ident, Member, Token, Type,
+};
+
+/// Graph description parsed by `nodo_graph!`
+pub struct Graph {
+    instances: Vec<Instance>,
+    guards: Vec<Guard>,
+    connections: Vec<Connection>,
+    schedule: Vec<ScheduleParam>,
+
+    /// All instances in the order of declaration which is the order of execution
+    order: Vec<Ident>,
+}
+
+/// `ident: Type = state, name: expr, config: expr;`
+struct Instance {
+    ident: Ident,
+    ty: Type,
+    state: Expr,
+    name: Option<Expr>,
+    config: Option<Expr>,
+}
+
+/// `if condition { instances and connections }`
+struct Guard {
+    condition: Expr,
+    instances: Vec<Instance>,
+}
+
+/// `instance.tx.channel -> instance.rx.channel;`
+struct Connection {
+    tx: Endpoint,
+    rx: Endpoint,
+    is_guarded: bool,
+}
+
+struct Endpoint {
+    instance: Ident,
+    members: Vec<Member>,
+}
+
+/// `key: expr` in the schedule block which calls `ScheduleBuilder::with_key(expr)`
+struct ScheduleParam {
+    key: Ident,
+    value: Expr,
+}
+
+impl Parse for Graph {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut graph = Graph {
+            instances: Vec::new(),
+            guards: Vec::new(),
+            connections: Vec::new(),
+            schedule: Vec::new(),
+            order: Vec::new(),
+        };
+        let mut has_schedule = false;
+
+        while !input.is_empty() {
+            if input.peek(Token![if]) {
+                input.parse::<Token![if]>()?;
+                let condition = Expr::parse_without_eager_brace(input)?;
+                let content;
+                braced!(content in input);
+                let mut guard = Guard {
+                    condition,
+                    instances: Vec::new(),
+                };
+                while !content.is_empty() {
+                    if content.peek(Token![if]) {
+                        return Err(content.error("nested `if` blocks are not supported"));
+                    } else if content.peek2(Token![:]) {
+                        let instance: Instance = content.parse()?;
+                        graph.order.push(instance.ident.clone());
+                        guard.instances.push(instance);
+                    } else {
+                        let mut connection: Connection = content.parse()?;
+                        connection.is_guarded = true;
+                        graph.connections.push(connection);
+                    }
+                }
+                graph.guards.push(guard);
+            } else if input.peek(Ident) && input.peek2(token::Brace) {
+                let keyword: Ident = input.parse()?;
+                if keyword != "schedule" {
+                    return Err(syn::Error::new(keyword.span(), "expected `schedule`"));
+                }
+                if has_schedule {
+                    return Err(syn::Error::new(
+                        keyword.span(),
+                        "only one schedule block is allowed",
+                    ));
+                }
+                has_schedule = true;
+                let content;
+                braced!(content in input);
+                let params = Punctuated::<ScheduleParam, Token![,]>::parse_terminated(&content)?;
+                graph.schedule.extend(params);
+            } else if input.peek2(Token![:]) {
+                let instance: Instance = input.parse()?;
+                graph.order.push(instance.ident.clone());
+                graph.instances.push(instance);
+            } else {
+                graph.connections.push(input.parse()?);
+            }
+        }
+
+        if !has_schedule {
+            return Err(input.error("missing `schedule { .. }` block"));
+        }
+
+        Ok(graph)
+    }
+}
+
+impl Parse for Instance {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let ty = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let state = input.parse()?;
+
+        let mut name = None;
+        let mut config = None;
+        while input.peek(Token![,]) {
+            input.parse::<Token![,]>()?;
+            let key: Ident = input.parse()?;
+            input.parse::<Token![:]>()?;
+            let value = input.parse()?;
+            let slot = match key.to_string().as_str() {
+                "name" => &mut name,
+                "config" => &mut config,
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "unknown instance parameter, expected `name` or `config`",
+                    ))
+                }
+            };
+            if slot.replace(value).is_some() {
+                return Err(syn::Error::new(key.span(), "duplicate instance parameter"));
+            }
+        }
+        input.parse::<Token![;]>()?;
+
+        Ok(Self {
+            ident,
+            ty,
+            state,
+            name,
+            config,
+        })
+    }
+}
+
+impl Parse for Connection {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let tx = input.parse()?;
+        input.parse::<Token![->]>()?;
+        let rx = input.parse()?;
+        input.parse::<Token![;]>()?;
+        Ok(Self {
+            tx,
+            rx,
+            is_guarded: false,
+        })
+    }
+}
+
+impl Parse for Endpoint {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let instance = input.parse()?;
+        let mut members = Vec::new();
+        while input.peek(Token![.]) {
+            input.parse::<Token![.]>()?;
+            members.push(input.parse()?);
+        }
+        if members.is_empty() {
+            return Err(input.error("expected a channel like `instance.tx.channel`"));
+        }
+        Ok(Self { instance, members })
+    }
+}
+
+impl Parse for ScheduleParam {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let key = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let value = input.parse()?;
+        Ok(Self { key, value })
+    }
+}
+
+impl Instance {
+    /// Expression which creates the codelet instance
+    fn instantiate(&self) -> TokenStream {
+        let Instance {
+            ident,
+            state,
+            name,
+            config,
+            ..
+        } = self;
+        let name = match name {
+            Some(name) => quote!(#name),
+            None => {
+                let name = ident.to_string();
+                quote!(#name)
+            }
+        };
+        let config = match config {
+            Some(config) => quote!(#config),
+            None => quote!(::core::default::Default::default()),
+        };
+        quote!(nodo::codelet::IntoInstance::into_instance(#state, #name, #config))
+    }
+}
+
+impl Endpoint {
+    fn tokens(&self) -> TokenStream {
+        let instance = &self.instance;
+        let members = &self.members;
+        quote!(#instance #(.#members)*)
+    }
+}
+
+impl Graph {
+    pub fn expand(&self) -> syn::Result<TokenStream> {
+        let idents = &self.order;
+        for (i, ident) in idents.iter().enumerate() {
+            if idents[..i].contains(ident) {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    format!("duplicate instance `{ident}`"),
+                ));
+            }
+        }
+        let optional: Vec<&Ident> = self
+            .guards
+            .iter()
+            .flat_map(|guard| guard.instances.iter().map(|instance| &instance.ident))
+            .collect();
+
+        let instances = self.instances.iter().map(|instance| {
+            let ident = &instance.ident;
+            let ty = &instance.ty;
+            let instantiate = instance.instantiate();
+            quote! {
+                #[allow(unused_mut)]
+                let mut #ident: nodo::codelet::CodeletInstance<#ty> = #instantiate;
+            }
+        });
+
+        let guards = self.guards.iter().map(|guard| {
+            let condition = &guard.condition;
+            let idents = guard.instances.iter().map(|instance| &instance.ident);
+            let types = guard.instances.iter().map(|instance| &instance.ty);
+            let instantiate = guard.instances.iter().map(Instance::instantiate);
+            let nones = guard.instances.iter().map(|_| quote!(None));
+            quote! {
+                #[allow(unused_mut)]
+                let (#(mut #idents,)*): (#(Option<nodo::codelet::CodeletInstance<#types>>,)*) =
+                    if #condition {
+                        (#(Some(#instantiate),)*)
+                    } else {
+                        (#(#nones,)*)
+                    };
+            }
+        });
+
+        let mut connections = Vec::new();
+        for connection in self.connections.iter() {
+            let mut guarded: Vec<&Ident> = Vec::new();
+            for endpoint in [&connection.tx, &connection.rx] {
+                if optional.contains(&&endpoint.instance) && !guarded.contains(&&endpoint.instance)
+                {
+                    guarded.push(&endpoint.instance);
+                }
+            }
+            if connection.is_guarded && guarded.is_empty() {
+                return Err(syn::Error::new(
+                    connection.tx.instance.span(),
+                    "connection inside an `if` block must involve an instance of that block",
+                ));
+            }
+
+            let tx = connection.tx.tokens();
+            let rx = connection.rx.tokens();
+            let connect = quote_spanned! {tx.span()=>
+                if let Err(err) = nodo::channels::connect(&mut #tx, &mut #rx) {
+                    break 'nodo_graph Err(err);
+                }
+            };
+            connections.push(if guarded.is_empty() {
+                connect
+            } else {
+                quote! {
+                    if let (#(Some(#guarded),)*) = (#(#guarded.as_mut(),)*) {
+                        #connect
+                    }
+                }
+            });
+        }
+
+        let params = self.schedule.iter().map(|param| {
+            let method = format_ident!("with_{}", param.key, span = param.key.span());
+            let value = &param.value;
+            quote!(.#method(#value))
+        });
+
+        Ok(quote! {
+            'nodo_graph: {
+                #(#instances)*
+                #(#guards)*
+                #(#connections)*
+                Ok::<_, nodo::channels::TxConnectError>(
+                    nodo::codelet::ScheduleBuilder::new()
+                        #(#params)*
+                        #(.with(#idents))*
+                )
+            }
+        })
+    }
+}
@@ -1,6 +1,13 @@
+mod graph;
+
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, Data, DataEnum, DataStruct, DeriveInput, Fields, Meta};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    Data, DataEnum, DataStruct, DeriveInput, Fields, Ident, Lit, Meta, Token,
+};
 
 /// Derive macro to implement the RxBundle trait for a custom struct with Rx fields
 #[proc_macro_derive(RxBundleDerive)]
@@ -36,9 +43,9 @@ fn impl_rx_bundle_derive(input: &syn::DeriveInput) -> TokenStream {
                 #fields_count
             }
 
-            fn name(&self, index: usize) -> String {
+            fn name(&self, index: usize) -> std::borrow::Cow<'static, str> {
                 match index {
-                    #(#field_index => (#field_name_str).to_string(),)*
+                    #(#field_index => std::borrow::Cow::Borrowed(#field_name_str),)*
                     _ => panic!("invalid rx bundle index {index} for `{}`", #name_str),
                 }
             }
@@ -56,6 +63,60 @@ fn impl_rx_bundle_derive(input: &syn::DeriveInput) -> TokenStream {
                 #(cc.mark(#field_index, self.#field_name.is_connected());)*
                 cc
             }
+
+            fn contract(&self, index: usize) -> Option<nodo::channels::ChannelContract> {
+                match index {
+                    #(#field_index => nodo::channels::Rx::contract(&self.#field_name),)*
+                    _ => None,
+                }
+            }
+
+            fn message_type_hash(&self, index: usize) -> Option<u64> {
+                match index {
+                    #(#field_index => nodo::channels::Rx::message_type_hash(&self.#field_name),)*
+                    _ => None,
+                }
+            }
+
+            fn endpoint_mut(&mut self, index: usize) -> Option<&mut dyn core::any::Any>
+            where
+                Self: 'static,
+            {
+                match index {
+                    #(#field_index => nodo::channels::Rx::as_any_mut(&mut self.#field_name),)*
+                    _ => None,
+                }
+            }
+
+            fn queue_len(&self, index: usize) -> Option<usize> {
+                match index {
+                    #(#field_index => nodo::channels::Rx::queue_len(&self.#field_name),)*
+                    _ => None,
+                }
+            }
+
+            fn take_backlog(&mut self, index: usize) -> Option<nodo::channels::Backlog>
+            where
+                Self: 'static,
+            {
+                match index {
+                    #(#field_index => nodo::channels::Rx::take_backlog(&mut self.#field_name),)*
+                    _ => None,
+                }
+            }
+
+            fn tap_point(
+                &self,
+                index: usize,
+            ) -> Option<std::sync::Arc<dyn nodo::channels::TapPoint>>
+            where
+                Self: 'static,
+            {
+                match index {
+                    #(#field_index => nodo::channels::Rx::tap_point(&self.#field_name),)*
+                    _ => None,
+                }
+            }
         }
     };
     gen.into()
@@ -81,50 +142,261 @@ fn impl_tx_bundle_derive(input: &syn::DeriveInput) -> TokenStream {
         _ => panic!("expected a struct with named fields"),
     };
 
-    let fields_count = fields.len();
-    let field_index = (0..fields.len()).collect::<Vec<_>>();
-    let field_name = fields.iter().map(|field| &field.ident).collect::<Vec<_>>();
-    let field_name_str = fields
+    let field_name = fields
         .iter()
-        .map(|f| f.ident.as_ref().unwrap().to_string())
+        .map(|field| field.ident.clone().unwrap())
         .collect::<Vec<_>>();
+    let items = tx_bundle_items(&name_str, &field_name);
 
     let gen = quote! {
         impl #impl_generics nodo::channels::TxBundle for #name #type_generics #where_clause {
-            fn len(&self) -> usize {
-                #fields_count
+            #items
+        }
+    };
+    gen.into()
+}
+
+/// Items of a `TxBundle` impl for a struct whose fields are TX channels
+///
+/// Shared by `TxBundleDerive` and the TX bundle generated by `EnumSplitDerive`.
+fn tx_bundle_items(bundle_name: &str, field_name: &[Ident]) -> proc_macro2::TokenStream {
+    let fields_count = field_name.len();
+    let field_index = (0..fields_count).collect::<Vec<_>>();
+    let field_name_str = field_name.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+
+    quote! {
+        fn len(&self) -> usize {
+            #fields_count
+        }
+
+        fn name(&self, index: usize) -> std::borrow::Cow<'static, str> {
+            match index {
+                #(#field_index => std::borrow::Cow::Borrowed(#field_name_str),)*
+                _ => panic!("invalid tx bundle index {index} for `{}`", #bundle_name),
             }
+        }
 
-            fn name(&self, index: usize) -> String {
-                match index {
-                    #(#field_index => (#field_name_str).to_string(),)*
-                    _ => panic!("invalid tx bundle index {index} for `{}`", #name_str),
+        fn flush_all(&mut self, results: &mut [nodo::channels::FlushResult]) {
+            use nodo::channels::Tx;
+
+            #(results[#field_index] = self.#field_name.flush();)*
+        }
+
+        fn check_connection(&self) -> nodo::channels::ConnectionCheck {
+            use nodo::channels::Tx;
+
+            let mut cc = nodo::channels::ConnectionCheck::new(#fields_count);
+            #(cc.mark(#field_index, self.#field_name.is_connected());)*
+            cc
+        }
+
+        fn contract(&self, index: usize) -> Option<nodo::channels::ChannelContract> {
+            match index {
+                #(#field_index => nodo::channels::Tx::contract(&self.#field_name),)*
+                _ => None,
+            }
+        }
+
+        fn message_type_hash(&self, index: usize) -> Option<u64> {
+            match index {
+                #(#field_index => nodo::channels::Tx::message_type_hash(&self.#field_name),)*
+                _ => None,
+            }
+        }
+
+        fn connect_dyn(
+            &mut self,
+            index: usize,
+            rx: &mut dyn core::any::Any,
+        ) -> Result<(), nodo::channels::DynConnectError>
+        where
+            Self: 'static,
+        {
+            match index {
+                #(#field_index => nodo::channels::Tx::connect_dyn(&mut self.#field_name, rx),)*
+                _ => Err(nodo::channels::DynConnectError::InvalidIndex(index)),
+            }
+        }
+    }
+}
+
+/// Derive macro to implement the EnumSplitMessage trait for an enum
+///
+/// Generates a TX bundle `<Enum>SplitTx` with one `DoubleBufferTx` per variant which is used by the
+/// `EnumSplit` codelet in nodo_std. Variants with named fields are not supported.
+#[proc_macro_derive(EnumSplitDerive)]
+pub fn enum_split_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match impl_enum_split_derive(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn impl_enum_split_derive(input: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let vis = &input.vis;
+    let tx_name = Ident::new(&format!("{name}SplitTx"), name.span());
+    let tx_name_str = tx_name.to_string();
+
+    let variants = match &input.data {
+        Data::Enum(DataEnum { variants, .. }) => variants,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "EnumSplitDerive can only be derived for enums",
+            ))
+        }
+    };
+    if variants.is_empty() {
+        return Err(syn::Error::new_spanned(
+            input,
+            "EnumSplitDerive requires at least one variant",
+        ));
+    }
+
+    let mut variant_name_str = Vec::new();
+    let mut field_name = Vec::new();
+    let mut payload_type = Vec::new();
+    let mut variant_pattern = Vec::new();
+    let mut route_arm = Vec::new();
+
+    for variant in variants.iter() {
+        let variant_name = &variant.ident;
+        let field = Ident::new(
+            &to_snake_case(&variant_name.to_string()),
+            variant_name.span(),
+        );
+
+        let (ty, arm) = match &variant.fields {
+            Fields::Unit => (
+                quote! { () },
+                quote! { #name::#variant_name => tx.#field.push(()), },
+            ),
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let ty = &fields.unnamed[0].ty;
+                (
+                    quote! { #ty },
+                    quote! { #name::#variant_name(value) => tx.#field.push(value), },
+                )
+            }
+            Fields::Unnamed(fields) => {
+                let tys = fields.unnamed.iter().map(|f| &f.ty);
+                let values = (0..fields.unnamed.len())
+                    .map(|i| Ident::new(&format!("value_{i}"), variant_name.span()))
+                    .collect::<Vec<_>>();
+                (
+                    quote! { (#(#tys),*) },
+                    quote! { #name::#variant_name(#(#values),*) => tx.#field.push((#(#values),*)), },
+                )
+            }
+            Fields::Named(_) => {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "EnumSplitDerive does not support variants with named fields",
+                ))
+            }
+        };
+
+        variant_pattern.push(match &variant.fields {
+            Fields::Unit => quote! { #name::#variant_name },
+            _ => quote! { #name::#variant_name(..) },
+        });
+        variant_name_str.push(variant_name.to_string());
+        field_name.push(field);
+        payload_type.push(ty);
+        route_arm.push(arm);
+    }
+
+    let field_index = (0..field_name.len()).collect::<Vec<_>>();
+    let tx_items = tx_bundle_items(&tx_name_str, &field_name);
+
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    // Channels can only be flushed if their payload can be cloned and sent across threads
+    let mut bounded_generics = input.generics.clone();
+    {
+        let where_clause = bounded_generics.make_where_clause();
+        for ty in payload_type.iter() {
+            where_clause
+                .predicates
+                .push(syn::parse_quote!(#ty: Send + Sync + Clone));
+        }
+    }
+    let (_, _, bounded_where_clause) = bounded_generics.split_for_impl();
+
+    Ok(quote! {
+        /// TX bundle with one channel per variant generated by `EnumSplitDerive`
+        #vis struct #tx_name #impl_generics #where_clause {
+            #(pub #field_name: nodo::channels::DoubleBufferTx<#payload_type>,)*
+        }
+
+        impl #impl_generics ::core::default::Default for #tx_name #type_generics #where_clause {
+            fn default() -> Self {
+                Self {
+                    #(#field_name: nodo::channels::DoubleBufferTx::new_auto_size(),)*
                 }
             }
+        }
+
+        impl #impl_generics nodo::channels::TxBundle for #tx_name #type_generics #bounded_where_clause {
+            #tx_items
+        }
+
+        impl #impl_generics nodo::channels::EnumSplitMessage for #name #type_generics #bounded_where_clause {
+            type Tx = #tx_name #type_generics;
 
-            fn flush_all(&mut self, results: &mut [nodo::channels::FlushResult]) {
-                use nodo::channels::Tx;
+            const VARIANTS: &'static [&'static str] = &[#(#variant_name_str),*];
 
-                #(results[#field_index] = self.#field_name.flush();)*
+            fn variant_index(&self) -> usize {
+                match self {
+                    #(#variant_pattern => #field_index,)*
+                }
             }
 
-            fn check_connection(&self) -> nodo::channels::ConnectionCheck {
-                use nodo::channels::Tx;
+            fn route(self, tx: &mut Self::Tx) -> Result<(), nodo::channels::TxSendError> {
+                match self {
+                    #(#route_arm)*
+                }
+            }
+        }
+    })
+}
 
-                let mut cc = nodo::channels::ConnectionCheck::new(#fields_count);
-                #(cc.mark(#field_index, self.#field_name.is_connected());;)*
-                cc
+/// Converts a CamelCase identifier to snake_case
+fn to_snake_case(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                out.push('_');
             }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
         }
-    };
-    gen.into()
+    }
+    out
 }
 
-#[proc_macro_derive(Status, attributes(label, default, skipped))]
+/// Derive macro to implement the CodeletStatus trait for an enum
+///
+/// Variants can be annotated with `#[default]`, `#[skipped]`, `#[label = "..."]`,
+/// `#[code = 42]` and `#[severity = "info|warn|error"]`. The code defaults to the index of the
+/// variant and the severity defaults to `warn` for skipped variants and `info` otherwise. Codes
+/// must be unique within the enum.
+#[proc_macro_derive(Status, attributes(label, default, skipped, code, severity))]
 pub fn derive_status(input: TokenStream) -> TokenStream {
     // Parse the input token stream (the enum)
     let input = parse_macro_input!(input as DeriveInput);
 
+    match impl_derive_status(input) {
+        Ok(expanded) => expanded.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn impl_derive_status(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     // Get the enum name
     let enum_name = input.ident.clone();
 
@@ -132,24 +404,30 @@ pub fn derive_status(input: TokenStream) -> TokenStream {
     let data = if let Data::Enum(DataEnum { variants, .. }) = input.data {
         variants
     } else {
-        return syn::Error::new_spanned(input, "Status can only be derived for enums")
-            .to_compile_error()
-            .into();
+        return Err(syn::Error::new_spanned(
+            input,
+            "Status can only be derived for enums",
+        ));
     };
 
     let mut default_variant = None;
     let mut match_arms_status = Vec::new();
     let mut match_arms_label = Vec::new();
+    let mut match_arms_code = Vec::new();
+    let mut match_arms_severity = Vec::new();
+    let mut codes = std::collections::HashMap::new();
 
     // Iterate over each variant
-    for variant in data {
+    for (index, variant) in data.into_iter().enumerate() {
         let variant_name = &variant.ident;
         let mut label = None;
         let mut is_default = false;
         let mut is_skipped = false;
+        let mut code = None;
+        let mut severity = None;
 
         // Parse the attributes on each variant
-        for attr in variant.attrs {
+        for attr in &variant.attrs {
             if attr.path.is_ident("label") {
                 if let Ok(Meta::NameValue(meta_name_value)) = attr.parse_meta() {
                     if let syn::Lit::Str(lit_str) = &meta_name_value.lit {
@@ -160,9 +438,55 @@ pub fn derive_status(input: TokenStream) -> TokenStream {
                 is_default = true;
             } else if attr.path.is_ident("skipped") {
                 is_skipped = true;
+            } else if attr.path.is_ident("code") {
+                match attr.parse_meta()? {
+                    Meta::NameValue(syn::MetaNameValue {
+                        lit: Lit::Int(lit), ..
+                    }) => code = Some((lit.base10_parse::<u32>()?, lit.span())),
+                    meta => {
+                        return Err(syn::Error::new_spanned(
+                            meta,
+                            "expected an integer code like `#[code = 42]`",
+                        ))
+                    }
+                }
+            } else if attr.path.is_ident("severity") {
+                let lit = match attr.parse_meta()? {
+                    Meta::NameValue(syn::MetaNameValue {
+                        lit: Lit::Str(lit), ..
+                    }) => lit,
+                    meta => {
+                        return Err(syn::Error::new_spanned(
+                            meta,
+                            "expected a severity like `#[severity = \"warn\"]`",
+                        ))
+                    }
+                };
+                severity = Some(match lit.value().as_str() {
+                    "info" => quote! { nodo::prelude::Severity::Info },
+                    "warn" => quote! { nodo::prelude::Severity::Warn },
+                    "error" => quote! { nodo::prelude::Severity::Error },
+                    other => {
+                        return Err(syn::Error::new_spanned(
+                            lit,
+                            format!(
+                                "unknown severity `{other}`, expected `info`, `warn` or `error`"
+                            ),
+                        ))
+                    }
+                });
             }
         }
 
+        // Codes default to the index of the variant and must be unique
+        let (code, code_span) = code.unwrap_or_else(|| (index as u32, variant_name.span()));
+        if let Some(other) = codes.insert(code, variant_name.clone()) {
+            return Err(syn::Error::new(
+                code_span,
+                format!("duplicate status code {code}, already used by variant `{other}`"),
+            ));
+        }
+
         // Handle different variant types (unit, tuple, and struct)
         let pattern = match &variant.fields {
             Fields::Unit => quote! { #enum_name::#variant_name },
@@ -186,6 +510,22 @@ pub fn derive_status(input: TokenStream) -> TokenStream {
             #pattern => #label,
         });
 
+        match_arms_code.push(quote! {
+            #pattern => #code,
+        });
+
+        // Severity defaults to warn for skipped variants and info otherwise
+        let severity = severity.unwrap_or_else(|| {
+            if is_skipped {
+                quote! { nodo::prelude::Severity::Warn }
+            } else {
+                quote! { nodo::prelude::Severity::Info }
+            }
+        });
+        match_arms_severity.push(quote! {
+            #pattern => #severity,
+        });
+
         // Set the default variant
         if is_default {
             default_variant = Some(quote! {
@@ -206,7 +546,7 @@ pub fn derive_status(input: TokenStream) -> TokenStream {
     });
 
     // Generate the final implementation
-    let expanded = quote! {
+    Ok(quote! {
         impl CodeletStatus for #enum_name {
             #default_implementation_status
 
@@ -221,9 +561,420 @@ pub fn derive_status(input: TokenStream) -> TokenStream {
                     #(#match_arms_label)*
                 }
             }
+
+            fn code(&self) -> u32 {
+                match self {
+                    #(#match_arms_code)*
+                }
+            }
+
+            fn severity(&self) -> nodo::prelude::Severity {
+                match self {
+                    #(#match_arms_severity)*
+                }
+            }
+        }
+    })
+}
+
+/// Derive macro to implement the NodoConfig trait and Default for a config struct
+///
+/// Fields can be annotated with `#[config(default = 10, min = 0, max = 100, doc = "...")]`. All
+/// keys are optional. Fields without a default use `Default::default()`. If no `doc` is given the
+/// doc comment of the field is used instead.
+#[proc_macro_derive(NodoConfig, attributes(config))]
+pub fn derive_nodo_config(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match impl_nodo_config_derive(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// A single `key = value` entry in a `#[config(..)]` attribute
+struct ConfigArg {
+    key: Ident,
+    value: ConfigValue,
+}
+
+/// A literal value with an optional leading minus sign
+struct ConfigValue {
+    is_negative: bool,
+    lit: Lit,
+}
+
+impl Parse for ConfigArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let key: Ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let is_negative = if input.peek(Token![-]) {
+            input.parse::<Token![-]>()?;
+            true
+        } else {
+            false
+        };
+        let lit: Lit = input.parse()?;
+        Ok(ConfigArg {
+            key,
+            value: ConfigValue { is_negative, lit },
+        })
+    }
+}
+
+impl ConfigValue {
+    fn to_tokens(&self) -> proc_macro2::TokenStream {
+        let lit = &self.lit;
+        match (&self.lit, self.is_negative) {
+            (Lit::Str(_), _) => quote! { ::core::convert::Into::into(#lit) },
+            (_, true) => quote! { -#lit },
+            (_, false) => quote! { #lit },
+        }
+    }
+
+    fn to_display_string(&self) -> String {
+        let text = match &self.lit {
+            Lit::Str(s) => s.value(),
+            Lit::Int(i) => i.base10_digits().to_string(),
+            Lit::Float(f) => f.base10_digits().to_string(),
+            Lit::Bool(b) => b.value.to_string(),
+            Lit::Char(c) => c.value().to_string(),
+            other => quote! { #other }.to_string(),
+        };
+        if self.is_negative {
+            format!("-{text}")
+        } else {
+            text
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        let value = match &self.lit {
+            Lit::Int(i) => i.base10_parse::<f64>().ok()?,
+            Lit::Float(f) => f.base10_parse::<f64>().ok()?,
+            _ => return None,
+        };
+        Some(if self.is_negative { -value } else { value })
+    }
+}
+
+#[derive(Default)]
+struct ConfigFieldAttrs {
+    default: Option<ConfigValue>,
+    min: Option<ConfigValue>,
+    max: Option<ConfigValue>,
+    doc: Option<String>,
+}
+
+fn parse_config_field_attrs(field: &syn::Field) -> syn::Result<ConfigFieldAttrs> {
+    let mut attrs = ConfigFieldAttrs::default();
+    let mut doc_comment = Vec::new();
+
+    for attr in field.attrs.iter() {
+        if attr.path.is_ident("doc") {
+            if let Ok(Meta::NameValue(meta)) = attr.parse_meta() {
+                if let Lit::Str(text) = meta.lit {
+                    doc_comment.push(text.value().trim().to_string());
+                }
+            }
+            continue;
+        }
+
+        if !attr.path.is_ident("config") {
+            continue;
+        }
+
+        let args = attr.parse_args_with(Punctuated::<ConfigArg, Token![,]>::parse_terminated)?;
+        for arg in args {
+            let slot = match arg.key.to_string().as_str() {
+                "default" => &mut attrs.default,
+                "min" => &mut attrs.min,
+                "max" => &mut attrs.max,
+                "doc" => {
+                    if attrs.doc.is_some() {
+                        return Err(syn::Error::new_spanned(&arg.key, "duplicate config key `doc`"));
+                    }
+                    match arg.value.lit {
+                        Lit::Str(text) => attrs.doc = Some(text.value()),
+                        other => {
+                            return Err(syn::Error::new_spanned(
+                                other,
+                                "config key `doc` expects a string literal",
+                            ))
+                        }
+                    }
+                    continue;
+                }
+                other => {
+                    return Err(syn::Error::new_spanned(
+                        &arg.key,
+                        format!(
+                            "unknown config key `{other}`; expected one of `default`, `min`, `max`, `doc`"
+                        ),
+                    ))
+                }
+            };
+            if slot.is_some() {
+                return Err(syn::Error::new_spanned(
+                    &arg.key,
+                    format!("duplicate config key `{}`", arg.key),
+                ));
+            }
+            if matches!(arg.key.to_string().as_str(), "min" | "max")
+                && matches!(arg.value.lit, Lit::Str(_))
+            {
+                return Err(syn::Error::new_spanned(
+                    &arg.value.lit,
+                    format!("config key `{}` expects a numeric literal", arg.key),
+                ));
+            }
+            *slot = Some(arg.value);
+        }
+    }
+
+    if let (Some(min), Some(max)) = (&attrs.min, &attrs.max) {
+        if let (Some(lo), Some(hi)) = (min.as_f64(), max.as_f64()) {
+            if lo > hi {
+                return Err(syn::Error::new_spanned(
+                    &max.lit,
+                    format!("config range is empty: min={lo} > max={hi}"),
+                ));
+            }
+        }
+    }
+
+    if attrs.doc.is_none() && !doc_comment.is_empty() {
+        attrs.doc = Some(doc_comment.join(" "));
+    }
+
+    Ok(attrs)
+}
+
+fn impl_nodo_config_derive(input: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match &input.data {
+        Data::Struct(DataStruct {
+            fields: Fields::Named(fields),
+            ..
+        }) => &fields.named,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "NodoConfig can only be derived for structs with named fields",
+            ))
+        }
+    };
+
+    let mut default_inits = Vec::new();
+    let mut checks = Vec::new();
+    let mut descriptors = Vec::new();
+
+    for field in fields.iter() {
+        let attrs = parse_config_field_attrs(field)?;
+        let ident = field.ident.as_ref().unwrap();
+        let ident_str = ident.to_string();
+        let ty = &field.ty;
+
+        let default_value = match &attrs.default {
+            Some(value) => value.to_tokens(),
+            None => quote! { ::core::default::Default::default() },
+        };
+        default_inits.push(quote! { #ident: #default_value, });
+
+        if attrs.min.is_some() || attrs.max.is_some() {
+            let below = attrs.min.as_ref().map(|v| {
+                let v = v.to_tokens();
+                quote! { self.#ident < #v }
+            });
+            let above = attrs.max.as_ref().map(|v| {
+                let v = v.to_tokens();
+                quote! { self.#ident > #v }
+            });
+            let condition = match (below, above) {
+                (Some(a), Some(b)) => quote! { #a || #b },
+                (Some(a), None) => a,
+                (None, Some(b)) => b,
+                (None, None) => unreachable!(),
+            };
+            let min_str = option_string_tokens(attrs.min.as_ref().map(|v| v.to_display_string()));
+            let max_str = option_string_tokens(attrs.max.as_ref().map(|v| v.to_display_string()));
+            checks.push(quote! {
+                if #condition {
+                    return Err(nodo::codelet::ConfigError::OutOfRange {
+                        field: #ident_str,
+                        value: format!("{:?}", self.#ident),
+                        min: #min_str,
+                        max: #max_str,
+                    });
+                }
+            });
+        }
+
+        let default_str =
+            option_string_tokens(attrs.default.as_ref().map(|v| v.to_display_string()));
+        let min_str = option_string_tokens(attrs.min.as_ref().map(|v| v.to_display_string()));
+        let max_str = option_string_tokens(attrs.max.as_ref().map(|v| v.to_display_string()));
+        let doc_str = option_string_tokens(attrs.doc.clone());
+        descriptors.push(quote! {
+            nodo::codelet::FieldDescriptor {
+                name: #ident_str.to_string(),
+                type_name: ::core::any::type_name::<#ty>().to_string(),
+                default: #default_str,
+                min: #min_str,
+                max: #max_str,
+                doc: #doc_str,
+            },
+        });
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::core::default::Default for #name #type_generics #where_clause {
+            fn default() -> Self {
+                Self {
+                    #(#default_inits)*
+                }
+            }
+        }
+
+        impl #impl_generics nodo::codelet::NodoConfig for #name #type_generics #where_clause {
+            fn validate(&self) -> ::core::result::Result<(), nodo::codelet::ConfigError> {
+                #(#checks)*
+                Ok(())
+            }
+
+            fn describe() -> Vec<nodo::codelet::FieldDescriptor> {
+                vec![
+                    #(#descriptors)*
+                ]
+            }
+        }
+    })
+}
+
+fn option_string_tokens(value: Option<String>) -> proc_macro2::TokenStream {
+    match value {
+        Some(text) => quote! { Some(#text.to_string()) },
+        None => quote! { None },
+    }
+}
+
+/// Derive macro to implement `nodo_core::SelfDescribing` for structs and enums
+#[proc_macro_derive(SelfDescribing)]
+pub fn derive_self_describing(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match impl_self_describing_derive(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn impl_self_describing_derive(input: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let name_str = name.to_string();
+
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        param
+            .bounds
+            .push(syn::parse_quote!(::nodo_core::SelfDescribing));
+    }
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+    let schema = match &input.data {
+        Data::Struct(DataStruct { fields, .. }) => {
+            let fields = field_schemas(fields);
+            quote! {
+                ::nodo_core::TypeSchema::Struct {
+                    name: #name_str.to_string(),
+                    fields: vec![#(#fields),*],
+                }
+            }
+        }
+        Data::Enum(DataEnum { variants, .. }) => {
+            let variants = variants.iter().map(|variant| {
+                let variant_str = variant.ident.to_string();
+                let fields = field_schemas(&variant.fields);
+                quote! {
+                    ::nodo_core::VariantSchema {
+                        name: #variant_str.to_string(),
+                        fields: vec![#(#fields),*],
+                    }
+                }
+            });
+            quote! {
+                ::nodo_core::TypeSchema::Enum {
+                    name: #name_str.to_string(),
+                    variants: vec![#(#variants),*],
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "SelfDescribing can only be derived for structs and enums",
+            ))
         }
     };
 
-    // Convert the generated code into a token stream
-    TokenStream::from(expanded)
+    Ok(quote! {
+        impl #impl_generics ::nodo_core::SelfDescribing for #name #type_generics #where_clause {
+            fn describe() -> ::nodo_core::TypeSchema {
+                #schema
+            }
+        }
+    })
+}
+
+/// Field schemas of a struct or enum variant. Unnamed fields are named by their index.
+fn field_schemas(fields: &Fields) -> Vec<proc_macro2::TokenStream> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let field_str = field
+                .ident
+                .as_ref()
+                .map_or_else(|| i.to_string(), |ident| ident.to_string());
+            let ty = &field.ty;
+            quote! {
+                ::nodo_core::FieldSchema::new::<#ty>(#field_str)
+            }
+        })
+        .collect()
+}
+
+/// Describes a small static graph and expands to the code which creates the instances, connects
+/// their channels and builds the schedule
+///
+/// Instances are declared with `ident: Type = state` followed by an optional `name` (defaults to
+/// the identifier) and `config` (defaults to `Default::default()`). Instances declared in an
+/// `if condition { .. }` block are optional: they are created only if the condition is true and
+/// connections involving them are skipped otherwise. The `schedule` block calls the corresponding
+/// `with_*` methods of `ScheduleBuilder` and instances are scheduled in the order of declaration.
+///
+/// The macro evaluates to `Result<ScheduleBuilder, TxConnectError>`.
+///
+/// ```ignore
+/// let schedule = nodo_graph! {
+///     camera: Camera = Camera::default(), config: CameraConfig { fps: 30 };
+///     detector: Detector = Detector::new(model);
+///     if cfg!(feature = "logging") {
+///         logger: Logger = Logger::default(), name: "detections";
+///         detector.tx.detections -> logger.rx.input;
+///     }
+///     camera.tx.image -> detector.rx.image;
+///     schedule {
+///         name: "vision",
+///         period: Duration::from_millis(33),
+///     }
+/// }?;
+/// ```
+#[proc_macro]
+pub fn nodo_graph(input: TokenStream) -> TokenStream {
+    let graph = parse_macro_input!(input as graph::Graph);
+    match graph.expand() {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
 }
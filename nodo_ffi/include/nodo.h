@@ -0,0 +1,66 @@
+/* Copyright 2024 by David Weikersdorfer. All rights reserved. */
+
+/*
+ * C interface to drive nodo graphs from a host application.
+ *
+ * Graphs are created by factories which are registered from Rust with
+ * `nodo_ffi::register_graph_factory`. The host owns the main loop and calls `nodo_graph_step`.
+ * The built-in graph "nodo/loopback" forwards messages from the input topic "in" to the output
+ * topic "out" and can be used to test the integration.
+ *
+ * Memory ownership:
+ * - Graph handles are owned by the host and must be released with `nodo_graph_destroy`.
+ * - Strings and buffers passed into nodo are only read during the call. Data is copied.
+ * - `nodo_poll_bytes` copies into a buffer owned by the host.
+ * - The string returned by `nodo_last_error` is owned by nodo and valid until the next nodo call
+ *   on the same thread.
+ *
+ * A graph handle must not be used by multiple threads at the same time. Rust panics are caught
+ * and reported as errors.
+ */
+
+#ifndef NODO_H
+#define NODO_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define NODO_OK 0
+#define NODO_MESSAGE 1
+#define NODO_ERROR -1
+#define NODO_BUFFER_TOO_SMALL -2
+
+typedef struct NodoGraphHandle NodoGraphHandle;
+
+/* Creates and starts a graph. Returns NULL on failure. */
+NodoGraphHandle* nodo_graph_create(const char* name);
+
+/* Stops and releases a graph. Passing NULL is a no-op. */
+void nodo_graph_destroy(NodoGraphHandle* handle);
+
+/* Steps all codelets once. Returns NODO_OK or NODO_ERROR. */
+int nodo_graph_step(NodoGraphHandle* handle);
+
+/* Publishes a copy of the bytes on an input topic. Returns NODO_OK or NODO_ERROR. */
+int nodo_push_bytes(NodoGraphHandle* handle, const char* topic, const uint8_t* data, size_t len,
+                    uint64_t acqtime_ns);
+
+/* Copies the oldest message of an output topic into buffer. Returns NODO_MESSAGE, NODO_OK if no
+ * message is available, NODO_BUFFER_TOO_SMALL with the required size in out_len, or NODO_ERROR.
+ * If buffer is NULL the size of the next message is queried without consuming it.
+ * out_acqtime_ns may be NULL. */
+int nodo_poll_bytes(NodoGraphHandle* handle, const char* topic, uint8_t* buffer, size_t capacity,
+                    size_t* out_len, uint64_t* out_acqtime_ns);
+
+/* Message of the last error on the calling thread */
+const char* nodo_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,213 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::NodoGraph;
+use core::time::Duration;
+use eyre::{eyre, Result};
+use nodo_core::SerializedMessage;
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{c_char, c_int, CStr, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+};
+
+/// The call succeeded
+pub const NODO_OK: c_int = 0;
+
+/// `nodo_poll_bytes` wrote a message into the buffer
+pub const NODO_MESSAGE: c_int = 1;
+
+/// The call failed. Use `nodo_last_error` to get the error message.
+pub const NODO_ERROR: c_int = -1;
+
+/// The buffer passed to `nodo_poll_bytes` is too small or null. The message is kept.
+pub const NODO_BUFFER_TOO_SMALL: c_int = -2;
+
+/// Opaque handle to a graph owned by the host
+pub struct NodoGraphHandle {
+    graph: NodoGraph,
+
+    /// Messages which did not fit into the buffer given to `nodo_poll_bytes`
+    pending: HashMap<String, SerializedMessage>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = message);
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic payload".into()
+    }
+}
+
+/// Runs `f` catching errors and panics. On failure the last error is set and `on_error` returned.
+fn guard<T>(on_error: T, f: impl FnOnce() -> Result<T>) -> T {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(err)) => {
+            set_last_error(format!("{err:#}"));
+            on_error
+        }
+        Err(payload) => {
+            set_last_error(format!("panic: {}", panic_message(&*payload)));
+            on_error
+        }
+    }
+}
+
+unsafe fn as_str<'a>(text: *const c_char, what: &str) -> Result<&'a str> {
+    if text.is_null() {
+        return Err(eyre!("{what} is null"));
+    }
+    Ok(CStr::from_ptr(text).to_str()?)
+}
+
+unsafe fn as_handle<'a>(handle: *mut NodoGraphHandle) -> Result<&'a mut NodoGraphHandle> {
+    handle.as_mut().ok_or_else(|| eyre!("graph handle is null"))
+}
+
+/// Creates and starts a graph with the factory registered under `name`
+///
+/// Returns null on failure. The handle must be released with `nodo_graph_destroy`.
+///
+/// # Safety
+///
+/// `name` must be a valid null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn nodo_graph_create(name: *const c_char) -> *mut NodoGraphHandle {
+    guard(core::ptr::null_mut(), || {
+        let graph = NodoGraph::create(as_str(name, "name")?)?;
+        Ok(Box::into_raw(Box::new(NodoGraphHandle {
+            graph,
+            pending: HashMap::new(),
+        })))
+    })
+}
+
+/// Stops all codelets and releases the graph. Passing null is a no-op.
+///
+/// # Safety
+///
+/// `handle` must be null or a handle returned by `nodo_graph_create` which was not destroyed yet.
+#[no_mangle]
+pub unsafe extern "C" fn nodo_graph_destroy(handle: *mut NodoGraphHandle) {
+    guard((), || {
+        if !handle.is_null() {
+            drop(Box::from_raw(handle));
+        }
+        Ok(())
+    })
+}
+
+/// Steps all codelets of the graph once
+///
+/// # Safety
+///
+/// `handle` must be a valid graph handle which is not used concurrently by another thread.
+#[no_mangle]
+pub unsafe extern "C" fn nodo_graph_step(handle: *mut NodoGraphHandle) -> c_int {
+    guard(NODO_ERROR, || {
+        as_handle(handle)?.graph.step()?;
+        Ok(NODO_OK)
+    })
+}
+
+/// Publishes a copy of `len` bytes at `data` on an input topic
+///
+/// # Safety
+///
+/// `handle` must be a valid graph handle, `topic` a valid null-terminated string and `data` must
+/// point to at least `len` readable bytes. `data` may be null if `len` is zero.
+#[no_mangle]
+pub unsafe extern "C" fn nodo_push_bytes(
+    handle: *mut NodoGraphHandle,
+    topic: *const c_char,
+    data: *const u8,
+    len: usize,
+    acqtime_ns: u64,
+) -> c_int {
+    guard(NODO_ERROR, || {
+        let handle = as_handle(handle)?;
+        let topic = as_str(topic, "topic")?;
+        let bytes = if len == 0 {
+            Vec::new()
+        } else if data.is_null() {
+            return Err(eyre!("data is null"));
+        } else {
+            core::slice::from_raw_parts(data, len).to_vec()
+        };
+        handle
+            .graph
+            .push(topic, bytes, Duration::from_nanos(acqtime_ns))?;
+        Ok(NODO_OK)
+    })
+}
+
+/// Copies the oldest message of an output topic into `buffer`
+///
+/// Returns `NODO_MESSAGE` if a message was copied and `NODO_OK` if no message is available. If
+/// the buffer is too small `NODO_BUFFER_TOO_SMALL` is returned, the required size is written to
+/// `out_len` and the message is returned again by the next call. A null `buffer` queries the
+/// size of the next message in the same way without consuming it.
+///
+/// # Safety
+///
+/// `handle` must be a valid graph handle, `topic` a valid null-terminated string and `buffer`
+/// must be null or point to at least `capacity` writable bytes. `out_len` must be valid for
+/// writes and `out_acqtime_ns` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn nodo_poll_bytes(
+    handle: *mut NodoGraphHandle,
+    topic: *const c_char,
+    buffer: *mut u8,
+    capacity: usize,
+    out_len: *mut usize,
+    out_acqtime_ns: *mut u64,
+) -> c_int {
+    guard(NODO_ERROR, || {
+        let handle = as_handle(handle)?;
+        let topic = as_str(topic, "topic")?;
+        if out_len.is_null() {
+            return Err(eyre!("out_len is null"));
+        }
+
+        let message = match handle.pending.remove(topic) {
+            Some(message) => message,
+            None => match handle.graph.poll(topic)? {
+                Some(message) => message,
+                None => return Ok(NODO_OK),
+            },
+        };
+
+        *out_len = message.value.len();
+        if buffer.is_null() || message.value.len() > capacity {
+            handle.pending.insert(topic.into(), message);
+            return Ok(NODO_BUFFER_TOO_SMALL);
+        }
+        core::ptr::copy_nonoverlapping(message.value.as_ptr(), buffer, message.value.len());
+        if let Some(acqtime) = out_acqtime_ns.as_mut() {
+            *acqtime = message.stamp.acqtime.as_nanos() as u64;
+        }
+        Ok(NODO_MESSAGE)
+    })
+}
+
+/// Message of the last error which occurred on the calling thread
+///
+/// The returned string is owned by the library and valid until the next nodo call on the same
+/// thread. It is empty if no error occurred.
+#[no_mangle]
+pub extern "C" fn nodo_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ptr())
+}
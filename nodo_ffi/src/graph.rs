@@ -0,0 +1,233 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use eyre::{bail, eyre, Result};
+use nodo::{
+    channels::{Rx, Tx},
+    codelet::{Clocks, NodeletId, NodeletSetup, Schedulable, ScheduleBuilder, WorkerId},
+    prelude::*,
+};
+use nodo_core::SerializedMessage;
+use nodo_runtime::ScheduleExecutor;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+type GraphFactory = Arc<dyn Fn(&mut GraphBuilder) -> Result<()> + Send + Sync>;
+
+static FACTORIES: Mutex<Option<HashMap<String, GraphFactory>>> = Mutex::new(None);
+
+/// Registers a function which builds a graph when `nodo_graph_create` is called with this name
+///
+/// A factory registered under an existing name replaces the previous one.
+pub fn register_graph_factory<F>(name: &str, factory: F)
+where
+    F: Fn(&mut GraphBuilder) -> Result<()> + Send + Sync + 'static,
+{
+    FACTORIES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(HashMap::new)
+        .insert(name.into(), Arc::new(factory));
+}
+
+/// Name of the built-in graph which forwards messages from the input topic "in" to the output
+/// topic "out" unchanged. Hosts can use it to test their integration.
+pub const LOOPBACK_GRAPH: &str = "nodo/loopback";
+
+fn find_graph_factory(name: &str) -> Option<GraphFactory> {
+    if let Some(factory) = FACTORIES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .and_then(|factories| factories.get(name).cloned())
+    {
+        return Some(factory);
+    }
+
+    (name == LOOPBACK_GRAPH).then(|| -> GraphFactory {
+        Arc::new(|builder: &mut GraphBuilder| {
+            let mut loopback = Loopback.into_instance("loopback", ());
+            builder.input("in", &mut loopback.rx)?;
+            builder.output("out", &mut loopback.tx)?;
+            builder.add(loopback);
+            Ok(())
+        })
+    })
+}
+
+/// Forwards all received messages unchanged
+struct Loopback;
+
+impl Codelet for Loopback {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = DoubleBufferRx<SerializedMessage>;
+    type Tx = DoubleBufferTx<SerializedMessage>;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (
+            DoubleBufferRx::new_auto_size(),
+            DoubleBufferTx::new_auto_size(),
+        )
+    }
+
+    fn step(&mut self, _: &Context<Self>, rx: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        let mut count = 0;
+        while let Some(message) = rx.try_pop() {
+            tx.push(message)?;
+            count += 1;
+        }
+        if count == 0 {
+            SKIPPED
+        } else {
+            SUCCESS
+        }
+    }
+}
+
+/// Passed to graph factories to add codelets and byte endpoints
+pub struct GraphBuilder {
+    schedule: ScheduleBuilder,
+    inputs: HashMap<String, DoubleBufferTx<SerializedMessage>>,
+    outputs: HashMap<String, DoubleBufferRx<SerializedMessage>>,
+}
+
+impl GraphBuilder {
+    fn new(name: &str) -> Self {
+        Self {
+            schedule: ScheduleBuilder::new().with_name(name),
+            inputs: HashMap::new(),
+            outputs: HashMap::new(),
+        }
+    }
+
+    /// Adds codelets to the graph. They are stepped in the order in which they are added.
+    pub fn add<A: Schedulable>(&mut self, x: A) {
+        self.schedule.append(x);
+    }
+
+    /// Connects an input topic to a receiver, for example the one of a `Deserializer`
+    pub fn input(&mut self, topic: &str, rx: &mut DoubleBufferRx<SerializedMessage>) -> Result<()> {
+        let tx = self
+            .inputs
+            .entry(topic.into())
+            .or_insert_with(DoubleBufferTx::new_auto_size);
+        tx.connect(rx)?;
+        Ok(())
+    }
+
+    /// Connects a transmitter, for example the one of a `Serializer`, to an output topic
+    pub fn output(
+        &mut self,
+        topic: &str,
+        tx: &mut DoubleBufferTx<SerializedMessage>,
+    ) -> Result<()> {
+        if self.outputs.contains_key(topic) {
+            bail!("output topic '{topic}' is already connected");
+        }
+        let mut rx = DoubleBufferRx::new_auto_size();
+        tx.connect(&mut rx)?;
+        self.outputs.insert(topic.into(), rx);
+        Ok(())
+    }
+}
+
+/// A graph driven manually by the host
+///
+/// Codelets are started when the graph is created and stopped when it is dropped. Schedule
+/// periods are ignored: every call to [NodoGraph::step] steps all codelets once.
+pub struct NodoGraph {
+    clocks: Clocks,
+    schedule: ScheduleExecutor,
+    inputs: HashMap<String, (DoubleBufferTx<SerializedMessage>, u64)>,
+    outputs: HashMap<String, DoubleBufferRx<SerializedMessage>>,
+}
+
+impl NodoGraph {
+    /// Creates a graph with the factory registered under the given name and starts it
+    pub fn create(name: &str) -> Result<Self> {
+        let factory =
+            find_graph_factory(name).ok_or_else(|| eyre!("no graph factory named '{name}'"))?;
+
+        let mut builder = GraphBuilder::new(name);
+        factory(&mut builder)?;
+
+        let clocks = Clocks::new();
+        let mut schedule = ScheduleExecutor::from(builder.schedule);
+        schedule.setup(NodeletSetup {
+            clocks: clocks.clone(),
+            nodelet_id_issue: NodeletId(WorkerId(0), 0),
+        });
+
+        // start
+        schedule.spin();
+        if schedule.is_terminated() {
+            bail!("graph '{name}' failed to start");
+        }
+
+        Ok(Self {
+            clocks,
+            schedule,
+            inputs: builder
+                .inputs
+                .into_iter()
+                .map(|(topic, tx)| (topic, (tx, 0)))
+                .collect(),
+            outputs: builder.outputs,
+        })
+    }
+
+    /// Steps all codelets once
+    pub fn step(&mut self) -> Result<()> {
+        if self.schedule.is_terminated() {
+            bail!("graph '{}' is terminated", self.schedule.name());
+        }
+        self.schedule.spin();
+        Ok(())
+    }
+
+    /// Publishes a message on an input topic. It is received by the graph in the next step.
+    pub fn push(&mut self, topic: &str, bytes: Vec<u8>, acqtime: Duration) -> Result<()> {
+        let (tx, seq) = self
+            .inputs
+            .get_mut(topic)
+            .ok_or_else(|| eyre!("unknown input topic '{topic}'"))?;
+
+        tx.push(Message {
+            seq: *seq,
+            stamp: Stamp {
+                acqtime: acqtime.into(),
+                pubtime: self.clocks.app_mono.now(),
+            },
+            value: bytes,
+        })?;
+        *seq += 1;
+
+        let result = tx.flush();
+        if result.error_indicator.is_err() {
+            bail!(
+                "input topic '{topic}': flush error {}",
+                result.error_indicator
+            );
+        }
+        Ok(())
+    }
+
+    /// Takes the oldest message published by the graph on an output topic
+    pub fn poll(&mut self, topic: &str) -> Result<Option<SerializedMessage>> {
+        let rx = self
+            .outputs
+            .get_mut(topic)
+            .ok_or_else(|| eyre!("unknown output topic '{topic}'"))?;
+        rx.sync();
+        Ok(rx.try_pop())
+    }
+}
+
+impl Drop for NodoGraph {
+    fn drop(&mut self) {
+        self.schedule.finalize();
+    }
+}
@@ -0,0 +1,16 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+//! C ABI to embed nodo graphs in host applications which own the main loop
+//!
+//! C can not build Rust graphs, thus graphs are created by factories which are registered from
+//! Rust with [register_graph_factory], for example from an init function exported by the cdylib
+//! which embeds nodo. The host then creates a graph by name and drives it with
+//! `nodo_graph_step`. Data enters and leaves the graph as bytes on named topics. The built-in
+//! [LOOPBACK_GRAPH] can be used to test the integration. See `include/nodo.h` for the C
+//! declarations and the memory ownership rules.
+
+mod capi;
+mod graph;
+
+pub use capi::*;
+pub use graph::*;
@@ -0,0 +1,54 @@
+/* Copyright 2024 by David Weikersdorfer. All rights reserved. */
+
+/*
+ * Drives the built-in loopback graph through the C interface of the nodo_ffi cdylib. Exits with
+ * a non-zero status and prints the failed check on error.
+ */
+
+#include "nodo.h"
+
+#include <stdio.h>
+#include <string.h>
+
+#define CHECK(cond)                                                                       \
+  do {                                                                                    \
+    if (!(cond)) {                                                                        \
+      fprintf(stderr, "%s:%d: check failed: %s (last error: '%s')\n", __FILE__, __LINE__, \
+              #cond, nodo_last_error());                                                  \
+      return 1;                                                                           \
+    }                                                                                     \
+  } while (0)
+
+int main(void) {
+  NodoGraphHandle* graph = nodo_graph_create("nodo/loopback");
+  CHECK(graph != NULL);
+
+  const uint8_t data[] = {7, 8, 9, 10};
+  CHECK(nodo_push_bytes(graph, "in", data, sizeof(data), 1234) == NODO_OK);
+  CHECK(nodo_graph_step(graph) == NODO_OK);
+
+  /* a null buffer queries the size without consuming the message */
+  size_t len = 0;
+  CHECK(nodo_poll_bytes(graph, "out", NULL, 0, &len, NULL) == NODO_BUFFER_TOO_SMALL);
+  CHECK(len == sizeof(data));
+
+  uint8_t buffer[16];
+  uint64_t acqtime_ns = 0;
+  CHECK(nodo_poll_bytes(graph, "out", buffer, sizeof(buffer), &len, &acqtime_ns) == NODO_MESSAGE);
+  CHECK(len == sizeof(data));
+  CHECK(memcmp(buffer, data, sizeof(data)) == 0);
+  CHECK(acqtime_ns == 1234);
+
+  CHECK(nodo_poll_bytes(graph, "out", buffer, sizeof(buffer), &len, &acqtime_ns) == NODO_OK);
+
+  /* errors are reported through nodo_last_error */
+  CHECK(nodo_push_bytes(graph, "unknown", data, sizeof(data), 0) == NODO_ERROR);
+  CHECK(strcmp(nodo_last_error(), "unknown input topic 'unknown'") == 0);
+
+  nodo_graph_destroy(graph);
+
+  CHECK(nodo_graph_create("missing") == NULL);
+
+  printf("ok\n");
+  return 0;
+}
@@ -0,0 +1,147 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use eyre::bail;
+use nodo::prelude::*;
+use nodo_core::SerializedMessage;
+use nodo_ffi::*;
+use nodo_std::{Pipe, PipeConfig};
+use std::ffi::{CStr, CString};
+
+fn last_error() -> String {
+    unsafe { CStr::from_ptr(nodo_last_error()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn register_factories() {
+    register_graph_factory("doubler", |builder| {
+        let mut doubler = Pipe::new(|msg: SerializedMessage| {
+            msg.map(|bytes| bytes.iter().map(|x| 2 * x).collect())
+        })
+        .into_instance("doubler", PipeConfig::Dynamic);
+
+        builder.input("numbers", &mut doubler.rx)?;
+        builder.output("doubled", &mut doubler.tx)?;
+        builder.add(doubler);
+        Ok(())
+    });
+
+    register_graph_factory("broken", |_| bail!("missing calibration"));
+
+    register_graph_factory("panicky", |_| panic!("factory panicked"));
+}
+
+#[test]
+fn test_capi_roundtrip() {
+    register_factories();
+
+    let name = CString::new("doubler").unwrap();
+    let numbers = CString::new("numbers").unwrap();
+    let doubled = CString::new("doubled").unwrap();
+
+    unsafe {
+        let graph = nodo_graph_create(name.as_ptr());
+        assert!(!graph.is_null(), "{}", last_error());
+
+        let data = [1u8, 2, 3];
+        assert_eq!(
+            nodo_push_bytes(graph, numbers.as_ptr(), data.as_ptr(), data.len(), 42_000),
+            NODO_OK
+        );
+
+        let mut buffer = [0u8; 8];
+        let mut len = 0;
+        let mut acqtime = 0;
+
+        // nothing is published before the graph was stepped
+        assert_eq!(
+            nodo_poll_bytes(
+                graph,
+                doubled.as_ptr(),
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut len,
+                &mut acqtime
+            ),
+            NODO_OK
+        );
+
+        assert_eq!(nodo_graph_step(graph), NODO_OK);
+
+        // a null buffer queries the size and keeps the message
+        len = 0;
+        assert_eq!(
+            nodo_poll_bytes(
+                graph,
+                doubled.as_ptr(),
+                core::ptr::null_mut(),
+                0,
+                &mut len,
+                &mut acqtime
+            ),
+            NODO_BUFFER_TOO_SMALL
+        );
+        assert_eq!(len, 3);
+
+        // too small buffers keep the message
+        assert_eq!(
+            nodo_poll_bytes(
+                graph,
+                doubled.as_ptr(),
+                buffer.as_mut_ptr(),
+                2,
+                &mut len,
+                &mut acqtime
+            ),
+            NODO_BUFFER_TOO_SMALL
+        );
+        assert_eq!(len, 3);
+
+        assert_eq!(
+            nodo_poll_bytes(
+                graph,
+                doubled.as_ptr(),
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut len,
+                &mut acqtime
+            ),
+            NODO_MESSAGE
+        );
+        assert_eq!(&buffer[..len], &[2, 4, 6]);
+        assert_eq!(acqtime, 42_000);
+
+        // unknown topics are reported as errors
+        let unknown = CString::new("unknown").unwrap();
+        assert_eq!(
+            nodo_push_bytes(graph, unknown.as_ptr(), data.as_ptr(), data.len(), 0),
+            NODO_ERROR
+        );
+        assert_eq!(last_error(), "unknown input topic 'unknown'");
+
+        nodo_graph_destroy(graph);
+    }
+}
+
+#[test]
+fn test_capi_create_errors() {
+    register_factories();
+
+    unsafe {
+        let name = CString::new("broken").unwrap();
+        assert!(nodo_graph_create(name.as_ptr()).is_null());
+        assert_eq!(last_error(), "missing calibration");
+
+        let name = CString::new("panicky").unwrap();
+        assert!(nodo_graph_create(name.as_ptr()).is_null());
+        assert_eq!(last_error(), "panic: factory panicked");
+
+        let name = CString::new("missing").unwrap();
+        assert!(nodo_graph_create(name.as_ptr()).is_null());
+        assert_eq!(last_error(), "no graph factory named 'missing'");
+
+        assert!(nodo_graph_create(core::ptr::null()).is_null());
+        assert_eq!(nodo_graph_step(core::ptr::null_mut()), NODO_ERROR);
+        nodo_graph_destroy(core::ptr::null_mut());
+    }
+}
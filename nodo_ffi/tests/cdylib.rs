@@ -0,0 +1,41 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+#![cfg(unix)]
+
+use std::{path::PathBuf, process::Command};
+
+/// Directory with the cdylib which cargo builds next to the `deps` folder of the test binary
+fn cdylib_dir() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    exe.parent().unwrap().parent().unwrap().to_path_buf()
+}
+
+/// Compiles `tests/c/smoke.c` with the system C compiler, links it against the cdylib and runs it
+#[test]
+fn test_c_smoke() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let lib_dir = cdylib_dir();
+    let exe = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("nodo_ffi_smoke");
+
+    let status = Command::new(std::env::var("CC").unwrap_or_else(|_| "cc".into()))
+        .arg(manifest_dir.join("tests/c/smoke.c"))
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lnodo_ffi")
+        .arg("-o")
+        .arg(&exe)
+        .status()
+        .expect("could not run the C compiler");
+    assert!(status.success(), "compiling tests/c/smoke.c failed");
+
+    let output = Command::new(&exe).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}
@@ -1,7 +1,7 @@
 // Copyright 2023 by David Weikersdorfer. All rights reserved.
 
-use nodo::codelet::{Codelet, CodeletInstance, Instantiate};
-use nodo_core::{EyreResult, WrapErr};
+use nodo::codelet::{Codelet, CodeletInstance, ConfigError, Instantiate, NodoConfig};
+use nodo_core::{eyre, EyreResult, WrapErr};
 use std::{fs::File, io::BufReader};
 
 /// Codelets which can be instantiated with configuration loaded from a JSON file
@@ -39,3 +39,27 @@ pub fn load_json<T: for<'a> serde::Deserialize<'a>, S: Into<String>>(filename: S
 
     Ok(value)
 }
+
+/// Loads a config from a JSON file and validates it
+///
+/// Validation errors are annotated with the field description generated by `NodoConfig`.
+pub fn load_validated_json<T, S>(filename: S) -> EyreResult<T>
+where
+    T: NodoConfig + for<'a> serde::Deserialize<'a>,
+    S: Into<String>,
+{
+    let filename = filename.into();
+    let value: T = load_json(filename.clone())?;
+
+    if let Err(err) = value.validate() {
+        let ConfigError::OutOfRange { field, .. } = &err;
+        let doc = T::describe()
+            .into_iter()
+            .find(|d| d.name == *field)
+            .and_then(|d| d.doc.map(|doc| format!(" ({doc})")))
+            .unwrap_or_default();
+        return Err(eyre!("invalid config file '{filename}': {err}{doc}"));
+    }
+
+    Ok(value)
+}
@@ -0,0 +1,24 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+//! Prints the schemas published by a `Publisher` with schema publication enabled
+//!
+//! Usage: cargo run -p nodo_nng --example dump_schemas -- tcp://127.0.0.1:7789
+
+use nng::{options::protocol::pubsub::Subscribe, options::Options, Protocol, Socket};
+use nodo_core::EyreResult;
+use nodo_nng::{SchemaSet, SCHEMAS_TOPIC};
+
+fn main() -> EyreResult<()> {
+    let address = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "tcp://127.0.0.1:7789".to_string());
+
+    let socket = Socket::new(Protocol::Sub0)?;
+    socket.dial(&address)?;
+    socket.set_opt::<Subscribe>(format!("{SCHEMAS_TOPIC}\0").into_bytes())?;
+
+    let schemas = SchemaSet::from_nng_message(&socket.recv()?)?;
+    println!("{}", serde_json::to_string_pretty(&schemas)?);
+
+    Ok(())
+}
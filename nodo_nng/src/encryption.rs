@@ -0,0 +1,152 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::EyreResult;
+
+/// Pre-shared key used to encrypt messages between [NngPub](crate::NngPub) and
+/// [NngSub](crate::NngSub)
+pub type NngEncryptionKey = [u8; 32];
+
+/// Size of the nonce sent in plain text in front of the ciphertext
+pub const NONCE_SIZE: usize = 24;
+
+/// Size of the authentication tag appended to the ciphertext
+pub const TAG_SIZE: usize = 16;
+
+/// Reasons for which [NngSub](crate::NngSub) drops a message when encryption is enabled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NngDecryptError {
+    /// The message was sent by a publisher without an encryption key
+    NotEncrypted,
+
+    /// The message is encrypted but the subscriber has no key
+    MissingKey,
+
+    /// Decryption failed, i.e. the publisher uses a different key or the message was modified
+    InvalidCiphertext,
+}
+
+impl core::fmt::Display for NngDecryptError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            NngDecryptError::NotEncrypted => write!(f, "message is not encrypted"),
+            NngDecryptError::MissingKey => write!(f, "message is encrypted but no key is set"),
+            NngDecryptError::InvalidCiphertext => write!(f, "message failed decryption"),
+        }
+    }
+}
+
+impl std::error::Error for NngDecryptError {}
+
+/// Encrypts messages with XChaCha20-Poly1305
+///
+/// Every message uses a random 24-byte nonce. Nonces are large enough that random nonces do not
+/// repeat, also across restarts and across publishers which share the same key. The sequence
+/// number of messages is not used as it is only unique per topic and restarts with the publisher.
+pub struct Encryptor {
+    #[cfg(feature = "encryption")]
+    cipher: chacha20poly1305::XChaCha20Poly1305,
+}
+
+/// Decrypts messages encrypted by an [Encryptor] with the same key
+pub struct Decryptor {
+    #[cfg(feature = "encryption")]
+    cipher: chacha20poly1305::XChaCha20Poly1305,
+}
+
+#[cfg(feature = "encryption")]
+mod imp {
+    use super::*;
+    use chacha20poly1305::{
+        aead::{rand_core::RngCore, Aead, KeyInit, OsRng, Payload},
+        XChaCha20Poly1305, XNonce,
+    };
+
+    impl Encryptor {
+        pub fn new(key: &NngEncryptionKey) -> EyreResult<Self> {
+            Ok(Self {
+                cipher: XChaCha20Poly1305::new(key.into()),
+            })
+        }
+
+        /// Encrypts the plaintext and returns the nonce and the ciphertext including the tag.
+        /// Additional data is authenticated but not encrypted.
+        pub fn seal(
+            &mut self,
+            aad: &[u8],
+            plaintext: &[u8],
+        ) -> EyreResult<([u8; NONCE_SIZE], Vec<u8>)> {
+            let mut nonce = [0; NONCE_SIZE];
+            OsRng
+                .try_fill_bytes(&mut nonce)
+                .map_err(|err| eyre::eyre!("could not generate a nonce: {err}"))?;
+
+            let ciphertext = self
+                .cipher
+                .encrypt(
+                    XNonce::from_slice(&nonce),
+                    Payload {
+                        msg: plaintext,
+                        aad,
+                    },
+                )
+                .map_err(|_| eyre::eyre!("encryption failed"))?;
+
+            Ok((nonce, ciphertext))
+        }
+    }
+
+    impl Decryptor {
+        pub fn new(key: &NngEncryptionKey) -> EyreResult<Self> {
+            Ok(Self {
+                cipher: XChaCha20Poly1305::new(key.into()),
+            })
+        }
+
+        pub fn open(
+            &self,
+            nonce: &[u8],
+            aad: &[u8],
+            ciphertext: &[u8],
+        ) -> Result<Vec<u8>, NngDecryptError> {
+            if nonce.len() != NONCE_SIZE || ciphertext.len() < TAG_SIZE {
+                return Err(NngDecryptError::InvalidCiphertext);
+            }
+            self.cipher
+                .decrypt(
+                    XNonce::from_slice(nonce),
+                    Payload {
+                        msg: ciphertext,
+                        aad,
+                    },
+                )
+                .map_err(|_| NngDecryptError::InvalidCiphertext)
+        }
+    }
+}
+
+#[cfg(not(feature = "encryption"))]
+mod imp {
+    use super::*;
+
+    const UNSUPPORTED: &str = "encryption requires the `encryption` feature of nodo_nng";
+
+    impl Encryptor {
+        pub fn new(_: &NngEncryptionKey) -> EyreResult<Self> {
+            Err(eyre::eyre!(UNSUPPORTED))
+        }
+
+        pub fn seal(&mut self, _: &[u8], _: &[u8]) -> EyreResult<([u8; NONCE_SIZE], Vec<u8>)> {
+            Err(eyre::eyre!(UNSUPPORTED))
+        }
+    }
+
+    impl Decryptor {
+        pub fn new(_: &NngEncryptionKey) -> EyreResult<Self> {
+            Err(eyre::eyre!(UNSUPPORTED))
+        }
+
+        pub fn open(&self, _: &[u8], _: &[u8], _: &[u8]) -> Result<Vec<u8>, NngDecryptError> {
+            Err(NngDecryptError::InvalidCiphertext)
+        }
+    }
+}
@@ -4,17 +4,26 @@ use nodo::{
     codelet::{CodeletInstance, ScheduleBuilder},
     prelude::*,
 };
-use nodo_core::EyreResult;
+use nodo_core::{
+    eyre, AppMonotonicClock, BinaryFormat, EyreResult, RetryConfig, Schema, SelfDescribing,
+};
 use nodo_std::{Serializer, SerializerConfig, TopicJoin, TopicJoinConfig};
 use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, panic::Location};
 
 mod bincode_format;
+mod encryption;
+mod local_address;
 mod r#pub;
+mod schema;
 mod snappy_bincode_format;
 mod sub;
 
 pub use bincode_format::*;
+pub use encryption::*;
+pub use local_address::*;
 pub use r#pub::*;
+pub use schema::*;
 pub use snappy_bincode_format::*;
 pub use sub::*;
 
@@ -28,30 +37,86 @@ pub struct NngPubSubHeader {
 
 impl NngPubSubHeader {
     pub const MAGIC: u64 = 0x90D0ABCDABCD90D0;
+
+    /// Magic of the authenticated header version. The header is followed by a MAC of
+    /// [NngPubSubHeader::MAC_SIZE] bytes before the payload.
+    pub const MAGIC_AUTH: u64 = 0x90D0ABCDABCD90D1;
+
+    /// Magic of encrypted messages. Only the topic and this magic are sent in plain text. They
+    /// are followed by a nonce of [NONCE_SIZE] bytes and the encrypted header and payload.
+    pub const MAGIC_ENCRYPTED: u64 = 0x90D0ABCDABCD90D2;
+
     pub const CRC: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_AUTOSAR);
     pub const BINCODE_SIZE: usize = 44;
+    pub const MAC_SIZE: usize = blake3::OUT_LEN;
+
+    /// Computes the MAC over topic, header and payload using a keyed blake3 hash
+    pub fn mac(key: &NngAuthKey, topic: &[u8], header: &[u8], payload: &[u8]) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new_keyed(key);
+        hasher.update(topic);
+        hasher.update(header);
+        hasher.update(payload);
+        hasher.finalize()
+    }
+}
+
+/// Pre-shared key used to authenticate messages between [NngPub] and [NngSub]
+pub type NngAuthKey = [u8; blake3::KEY_LEN];
+
+/// Reasons for which [NngSub] rejects a message when authentication is enabled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NngAuthError {
+    /// The message was sent by a publisher without a key
+    MissingMac,
+
+    /// The MAC does not match, i.e. the publisher uses a different key or the message was modified
+    InvalidMac,
+}
+
+impl core::fmt::Display for NngAuthError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            NngAuthError::MissingMac => write!(f, "message is not authenticated"),
+            NngAuthError::InvalidMac => write!(f, "message failed authentication"),
+        }
+    }
 }
 
+impl std::error::Error for NngAuthError {}
+
 /// Helper to simplify publishing serialized messages from multiple channels on the same socket
 pub struct Publisher {
     tag: String,
     join: CodeletInstance<TopicJoin<Vec<u8>>>,
     nng_pub: CodeletInstance<NngPub>,
     schedule_builder: ScheduleBuilder,
+    topics: Vec<(String, Schema)>,
+    registration_sites: HashMap<String, String>,
+    schema_registry: SchemaRegistry,
+    schema_announcer: Option<CodeletInstance<SchemaAnnouncer>>,
 }
 
 impl Publisher {
     pub fn new(tag: &str, address: &str) -> Self {
+        let schema_registry = SchemaRegistry::default();
+
         let mut join = TopicJoin::instantiate(format!("{tag}_join"), TopicJoinConfig::default());
-        let mut nng_pub = NngPub::instantiate(
+        let mut nng_pub = NngPub::with_subscriber_flag(schema_registry.dirty_flag()).into_instance(
             format!("{tag}_nng_pub"),
             NngPubConfig {
                 address: address.to_string(),
                 queue_size: 24,
                 enable_statistics: false,
+                auth_key: None,
+                encryption_key: None,
+                retry: RetryConfig::default(),
             },
         );
         join.tx.connect(&mut nng_pub.rx).unwrap(); // SAFETY errors guaranteed to not happen
+
+        // measures how long messages wait for the publisher after the vis schedule produced them
+        nng_pub.rx.enable_latency_tracking(AppMonotonicClock::new());
+
         Self {
             tag: tag.to_string(),
             join,
@@ -59,32 +124,159 @@ impl Publisher {
             schedule_builder: nodo::codelet::ScheduleBuilder::new()
                 .with_name("vis")
                 .with_period(Duration::from_millis(10)),
+            topics: Vec::new(),
+            registration_sites: HashMap::new(),
+            schema_registry,
+            schema_announcer: None,
         }
     }
 
+    /// Publishes the schemas of all topics published with [Publisher::publish_described] on
+    /// [SCHEMAS_TOPIC]
+    ///
+    /// Fails if [SCHEMAS_TOPIC] was already published, e.g. by calling this function twice.
+    #[track_caller]
+    pub fn with_schema_publication(mut self, config: SchemaAnnouncerConfig) -> EyreResult<Self> {
+        self.ensure_unpublished(SCHEMAS_TOPIC)?;
+        self.registration_sites
+            .insert(SCHEMAS_TOPIC.to_string(), Location::caller().to_string());
+
+        let mut announcer = SchemaAnnouncer::new(self.schema_registry.clone())
+            .into_instance(format!("{}_schemas", self.tag), config);
+        // SAFETY: errors guaranteed to not happen
+        announcer
+            .tx
+            .connect(self.join.rx.add(SCHEMAS_TOPIC.into()).unwrap())
+            .unwrap();
+        self.schema_announcer = Some(announcer);
+        Ok(self)
+    }
+
+    /// Schemas of all topics published with [Publisher::publish_described]
+    pub fn schema_registry(&self) -> &SchemaRegistry {
+        &self.schema_registry
+    }
+
     pub fn schedule_builder_mut(&mut self) -> &mut ScheduleBuilder {
         &mut self.schedule_builder
     }
 
+    /// Publishes messages of a channel on a topic. Fails if the topic was already published.
+    #[track_caller]
     pub fn publish<T>(&mut self, topic: &str, tx: &mut DoubleBufferTx<Message<T>>) -> EyreResult<()>
     where
         T: Clone + Send + Sync + Serialize + for<'a> Deserialize<'a> + 'static,
     {
-        let mut ser = Serializer::new(Bincode::default()).into_instance(
-            format!("{}_ser_{topic}", self.tag),
-            SerializerConfig::default(),
-        );
+        self.ensure_unpublished(topic)?;
+
+        let format = Bincode::<T>::default();
+        let schema = format.schema();
+        self.add_serializer(topic, format, tx)?;
+        self.registration_sites
+            .insert(topic.to_string(), Location::caller().to_string());
+
+        self.topics.push((topic.to_string(), schema));
+
+        Ok(())
+    }
+
+    /// Publishes messages of another channel on a topic which was already published with
+    /// [Publisher::publish]
+    ///
+    /// Messages of all sources are interleaved in no particular order and sequence numbers of
+    /// different sources are not coordinated. Subscribers can not tell the sources apart. All
+    /// sources must use the same message type.
+    pub fn publish_additional_source<T>(
+        &mut self,
+        topic: &str,
+        tx: &mut DoubleBufferTx<Message<T>>,
+    ) -> EyreResult<()>
+    where
+        T: Clone + Send + Sync + Serialize + for<'a> Deserialize<'a> + 'static,
+    {
+        let format = Bincode::<T>::default();
+        let schema = format.schema();
+        let Some((_, expected)) = self.topics.iter().find(|(key, _)| key == topic) else {
+            return Err(eyre!(
+                "topic '{topic}' of publisher '{}' must be published with `publish` before \
+                 adding sources",
+                self.tag
+            ));
+        };
+        if *expected != schema {
+            return Err(eyre!(
+                "additional source for topic '{topic}' of publisher '{}' has schema {schema:?} \
+                 but the topic was published with schema {expected:?}",
+                self.tag
+            ));
+        }
+
+        self.add_serializer(topic, format, tx)
+    }
+
+    /// Fails with the site where the topic was published first if it was already published
+    fn ensure_unpublished(&self, topic: &str) -> EyreResult<()> {
+        if let Some(first) = self.registration_sites.get(topic) {
+            return Err(eyre!(
+                "topic '{topic}' is already published by publisher '{}' (first registered at \
+                 {first}). Use `Publisher::publish_additional_source` if multiple sources shall \
+                 feed the same topic.",
+                self.tag
+            ));
+        }
+        Ok(())
+    }
+
+    /// Creates a serializer which feeds the channel into a new input of the topic join
+    fn add_serializer<T>(
+        &mut self,
+        topic: &str,
+        format: Bincode<T>,
+        tx: &mut DoubleBufferTx<Message<T>>,
+    ) -> EyreResult<()>
+    where
+        T: Clone + Send + Sync + Serialize + for<'a> Deserialize<'a> + 'static,
+    {
+        let name = match self.join.rx.source_count(&topic.into()) {
+            0 => format!("{}_ser_{topic}", self.tag),
+            n => format!("{}_ser_{topic}_{n}", self.tag),
+        };
+        let mut ser = Serializer::new(format).into_instance(name, SerializerConfig::default());
 
         tx.connect(&mut ser.rx)?;
-        ser.tx.connect(&mut self.join.rx.add(topic.into()))?;
+        ser.tx.connect(self.join.rx.add_source(topic.into()))?;
 
         self.schedule_builder.append(ser);
 
         Ok(())
     }
 
+    /// Like [Publisher::publish] but also registers the schema of the message type so that
+    /// subscribers without the Rust type definitions can decode it
+    #[track_caller]
+    pub fn publish_described<T>(
+        &mut self,
+        topic: &str,
+        tx: &mut DoubleBufferTx<Message<T>>,
+    ) -> EyreResult<()>
+    where
+        T: Clone + Send + Sync + Serialize + for<'a> Deserialize<'a> + SelfDescribing + 'static,
+    {
+        self.publish(topic, tx)?;
+        self.schema_registry.register(
+            topic,
+            &Bincode::<T>::default().schema().encoding,
+            T::describe(),
+        );
+        Ok(())
+    }
+
     pub fn into_sequence(self) -> Sequence {
-        Sequence::new().with(self.join).with(self.nng_pub)
+        let mut sequence = Sequence::new();
+        if let Some(announcer) = self.schema_announcer {
+            sequence = sequence.with(announcer);
+        }
+        sequence.with(self.join).with(self.nng_pub)
     }
 }
 
@@ -93,7 +285,7 @@ mod tests {
     use crate::{Bincode, NngPub, NngPubConfig, NngSub, NngSubConfig};
     use core::time::Duration;
     use nodo::prelude::*;
-    use nodo_core::WithTopic;
+    use nodo_core::{BinaryFormat, RetryConfig, WithTopic};
     use nodo_runtime::Runtime;
     use nodo_std::{
         Deserializer, DeserializerConfig, Log, Pipe, PipeConfig, Serializer, SerializerConfig,
@@ -111,9 +303,7 @@ mod tests {
             number: u32,
         }
 
-        // const ADDRESS: &str = "ipc://Danvil/nodo/nodo_nng/test_pub_sub";
-        // const ADDRESS: &str = "tcp://192.168.8.228:54327";
-        const ADDRESS: &str = "tcp://127.0.0.1:7789";
+        let address = crate::ephemeral_tcp_address().unwrap();
 
         const MESSAGE_COUNT: usize = 25;
 
@@ -150,17 +340,22 @@ mod tests {
         let mut alice = NngPub::instantiate(
             "alice",
             NngPubConfig {
-                address: ADDRESS.to_string(),
+                address: address.clone(),
                 queue_size: 10,
                 enable_statistics: false,
+                auth_key: None,
+                encryption_key: None,
+                retry: RetryConfig::default(),
             },
         );
 
         let mut bob = NngSub::instantiate(
             "bob",
             NngSubConfig {
-                address: ADDRESS.to_string(),
+                address: address.clone(),
                 queue_size: 10,
+                auth_key: None,
+                encryption_key: None,
             },
         );
 
@@ -168,21 +363,22 @@ mod tests {
             Pipe::new(|msg: Message<WithTopic<Vec<u8>>>| msg.map(|WithTopic { value, .. }| value))
                 .into_instance("add_topic", PipeConfig::Dynamic);
 
-        let mut de = Deserializer::<Foo, _>::new(Bincode::default())
-            .into_instance("de", DeserializerConfig { queue_size: 1 });
+        let mut de = Deserializer::<Foo, _>::new(Bincode::default()).into_instance(
+            "de",
+            DeserializerConfig {
+                queue_size: 1,
+                ..Default::default()
+            },
+        );
 
         let mut log = Log::instantiate("log", ());
 
         let rx_counter = Arc::new(RwLock::new(0));
         let mut check = {
             let rx_counter = rx_counter.clone();
-            let ctrl = rt.tx_control();
             Sink::new(move |foo: Message<Foo>| {
                 assert!(foo.value.number as usize > *rx_counter.read().unwrap());
                 *rx_counter.write().unwrap() += 1;
-                if *rx_counter.read().unwrap() == MESSAGE_COUNT {
-                    ctrl.send(RuntimeControl::RequestStop)?;
-                }
                 SUCCESS
             })
             .into_instance("check", ())
@@ -193,8 +389,8 @@ mod tests {
         add_topic.tx.connect(&mut alice.rx).unwrap();
         bob.tx.connect(&mut rmv_topic.rx).unwrap();
         rmv_topic.tx.connect(&mut de.rx).unwrap();
-        de.tx.connect(&mut log.rx).unwrap();
-        de.tx.connect(&mut check.rx).unwrap();
+        de.tx.output.connect(&mut log.rx).unwrap();
+        de.tx.output.connect(&mut check.rx).unwrap();
 
         rt.add_codelet_schedule(
             nodo::codelet::ScheduleBuilder::new()
@@ -211,8 +407,181 @@ mod tests {
                 .into(),
         );
 
-        rt.spin();
+        rt.spin_until(
+            |_| *rx_counter.read().unwrap() >= MESSAGE_COUNT,
+            Duration::from_secs(10),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_topic() {
+        let mut tx_a = DoubleBufferTx::<Message<u32>>::new_auto_size();
+        let mut tx_b = DoubleBufferTx::<Message<u32>>::new_auto_size();
+
+        let mut publisher = crate::Publisher::new("test", "inproc://test_duplicate_topic");
+        publisher.publish("pose", &mut tx_a).unwrap();
+        let line = line!() - 1;
+
+        let err = publisher
+            .publish("pose", &mut tx_b)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("topic 'pose'"), "{err}");
+        assert!(err.contains("publisher 'test'"), "{err}");
+        assert!(err.contains(&format!("{}:{line}:", file!())), "{err}");
+        assert!(err.contains("publish_additional_source"), "{err}");
+
+        // the rejected channel was not connected
+        assert_eq!(publisher.join.rx.source_count(&"pose".into()), 1);
+        assert!(!tx_b.is_connected());
+
+        // additional sources require a published topic of the same type
+        let mut tx_c = DoubleBufferTx::<Message<f64>>::new_auto_size();
+        assert!(publisher
+            .publish_additional_source("image", &mut tx_b)
+            .is_err());
+        assert!(publisher
+            .publish_additional_source("pose", &mut tx_c)
+            .is_err());
+    }
+
+    #[test]
+    fn test_additional_source() {
+        const OFFSET: u32 = 1000;
+
+        let mut rx = DoubleBufferRx::<Message<WithTopic<Vec<u8>>>>::new_auto_size();
+
+        let source = |offset: u32| {
+            let mut count = 0;
+            Source::new(move || {
+                count += 1;
+                Message {
+                    seq: count as u64,
+                    stamp: Stamp {
+                        acqtime: Duration::ZERO.into(),
+                        pubtime: Duration::ZERO.into(),
+                    },
+                    value: offset + count,
+                }
+            })
+        };
+        let mut first = source(0).into_instance("first", ());
+        let mut second = source(OFFSET).into_instance("second", ());
+
+        let mut publisher = crate::Publisher::new("test", "inproc://test_additional_source");
+        publisher.publish("pose", &mut first.tx).unwrap();
+        publisher
+            .publish_additional_source("pose", &mut second.tx)
+            .unwrap();
+        publisher.join.tx.connect(&mut rx).unwrap();
+
+        // the topic is registered once
+        assert_eq!(publisher.topics.len(), 1);
+
+        let schedule = core::mem::replace(
+            publisher.schedule_builder_mut(),
+            nodo::codelet::ScheduleBuilder::new(),
+        )
+        .with(first)
+        .with(second)
+        .with(publisher.into_sequence());
+
+        let mut rt = Runtime::new();
+        rt.add_codelet_schedule(schedule.into());
+        rt.spin_for(Duration::from_millis(100)).unwrap();
+
+        rx.sync();
+        let mut format = Bincode::<u32>::default();
+        let values: Vec<u32> = rx
+            .drain(..)
+            .map(|msg| {
+                assert_eq!(msg.value.topic, "pose".into());
+                format.deserialize(&msg.value.value).unwrap()
+            })
+            .collect();
+
+        // messages of both sources are interleaved on the same topic
+        assert!(values.iter().any(|&v| v < OFFSET));
+        assert!(values.iter().any(|&v| v > OFFSET));
+    }
+
+    #[test]
+    fn test_schema_publication_twice() {
+        let address = crate::local_ipc_address("test_schema_publication_twice");
+
+        let err = crate::Publisher::new("test", &address)
+            .with_schema_publication(crate::SchemaAnnouncerConfig::default())
+            .unwrap()
+            .with_schema_publication(crate::SchemaAnnouncerConfig::default())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains(crate::SCHEMAS_TOPIC), "{err}");
+    }
+
+    #[test]
+    fn test_schema_publication() {
+        let address = crate::local_ipc_address("test_schema_publication");
+
+        #[derive(Clone, Serialize, Deserialize, SelfDescribing)]
+        struct Pose {
+            x: f64,
+            y: f64,
+        }
+
+        let mut tx_pose = DoubleBufferTx::<Message<Pose>>::new_auto_size();
+        let mut tx_count = DoubleBufferTx::<Message<u32>>::new_auto_size();
+
+        let mut publisher = crate::Publisher::new("test", &address)
+            .with_schema_publication(crate::SchemaAnnouncerConfig::default())
+            .unwrap();
+        publisher.publish_described("pose", &mut tx_pose).unwrap();
+        publisher.publish("count", &mut tx_count).unwrap();
+
+        let mut sub = NngSub::instantiate(
+            "sub",
+            NngSubConfig {
+                address: address.clone(),
+                queue_size: 10,
+                auth_key: None,
+                encryption_key: None,
+            },
+        );
+
+        let schemas = Arc::new(RwLock::new(None));
+        let mut check = {
+            let schemas = schemas.clone();
+            Sink::new(move |msg: Message<WithTopic<Vec<u8>>>| {
+                if String::from(&msg.value.topic) == crate::SCHEMAS_TOPIC {
+                    *schemas.write().unwrap() =
+                        Some(crate::SchemaSet::from_json(&msg.value.value)?);
+                }
+                SUCCESS
+            })
+            .into_instance("check", ())
+        };
+        sub.tx.connect(&mut check.rx).unwrap();
+
+        let mut rt = Runtime::new();
+        rt.add_codelet_schedule(
+            nodo::codelet::ScheduleBuilder::new()
+                .with_period(Duration::from_millis(1))
+                .with(publisher.into_sequence())
+                .with(sub)
+                .with(check)
+                .into(),
+        );
+        rt.spin_until(
+            |_| schemas.read().unwrap().is_some(),
+            Duration::from_secs(10),
+        )
+        .unwrap();
 
-        assert_eq!(*rx_counter.read().unwrap(), MESSAGE_COUNT);
+        let schemas = schemas.read().unwrap().clone().unwrap();
+        assert_eq!(schemas.topics.len(), 1);
+        let pose = schemas.get("pose").unwrap();
+        assert_eq!(pose.encoding, "bincode");
+        assert_eq!(pose.schema, Pose::describe());
+        assert_eq!(pose.type_hash, crate::type_hash(&Pose::describe()));
     }
 }
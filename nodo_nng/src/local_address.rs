@@ -0,0 +1,155 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use nodo_core::EyreResult;
+use std::{
+    net::TcpListener,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+const SOCKET_EXTENSION: &str = "ipc";
+
+static NEXT_SOCKET_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+/// Creates a unique `ipc://` address for sockets which are only used on this machine
+///
+/// Socket files are placed in a per-user directory in the temporary directory and are named by
+/// process ID, tag and a counter such that every call returns a different address. Socket files
+/// left behind by processes which are no longer running are removed.
+pub fn local_ipc_address(tag: &str) -> String {
+    let dir = local_ipc_dir();
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        log::warn!("could not create IPC directory '{}': {err}", dir.display());
+    }
+    remove_stale_sockets(&dir);
+
+    let index = NEXT_SOCKET_INDEX.fetch_add(1, Ordering::Relaxed);
+    let path = dir.join(socket_file_name(std::process::id(), tag, index));
+    format!("ipc://{}", path.display())
+}
+
+/// Finds a free TCP port on the loopback interface and returns a `tcp://` address for it
+///
+/// The port is only guaranteed to be free at the time of the call.
+pub fn ephemeral_tcp_address() -> EyreResult<String> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+    Ok(format!("tcp://127.0.0.1:{port}"))
+}
+
+fn local_ipc_dir() -> PathBuf {
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".into());
+    std::env::temp_dir().join(format!("nodo-{}", sanitize(&user)))
+}
+
+fn socket_file_name(pid: u32, tag: &str, index: usize) -> String {
+    format!("{pid}-{}-{index}.{SOCKET_EXTENSION}", sanitize(tag))
+}
+
+fn sanitize(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Removes socket files in the directory which were created by processes which are not running
+pub(crate) fn remove_stale_sockets(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.extension().and_then(|ext| ext.to_str()) != Some(SOCKET_EXTENSION) {
+            continue;
+        }
+        let Some(pid) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.split('-').next())
+            .and_then(|pid| pid.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if !is_process_alive(pid) {
+            // another process might have removed it already
+            std::fs::remove_file(&path).ok();
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn is_process_alive(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+/// Without a portable liveness check socket files are never considered stale
+#[cfg(not(target_os = "linux"))]
+fn is_process_alive(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ephemeral_tcp_address, local_address::remove_stale_sockets,
+        local_address::socket_file_name, local_ipc_address,
+    };
+    use nng::{Protocol, Socket};
+
+    #[test]
+    fn test_unique_addresses() {
+        let ipc = std::thread::scope(|s| {
+            let a = s.spawn(|| local_ipc_address("test"));
+            let b = s.spawn(|| local_ipc_address("test"));
+            [a.join().unwrap(), b.join().unwrap()]
+        });
+        assert_ne!(ipc[0], ipc[1]);
+        assert!(ipc[0].starts_with("ipc://"));
+
+        let tcp = [
+            ephemeral_tcp_address().unwrap(),
+            ephemeral_tcp_address().unwrap(),
+        ];
+        assert!(tcp[0].starts_with("tcp://127.0.0.1:"));
+
+        // all addresses can be listened on at the same time
+        let sockets = ipc
+            .iter()
+            .chain(tcp.iter())
+            .map(|address| {
+                let socket = Socket::new(Protocol::Pub0).unwrap();
+                socket.listen(address).unwrap();
+                socket
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(sockets.len(), 4);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_remove_stale_sockets() {
+        let dir = std::env::temp_dir().join(format!("nodo-test-stale-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // the PID of a process which already terminated
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let dead_pid = child.id();
+        child.wait().unwrap();
+
+        let stale = dir.join(socket_file_name(dead_pid, "old", 0));
+        let alive = dir.join(socket_file_name(std::process::id(), "new", 0));
+        let unrelated = dir.join("notes.txt");
+        for path in [&stale, &alive, &unrelated] {
+            std::fs::write(path, b"").unwrap();
+        }
+
+        remove_stale_sockets(&dir);
+        assert!(!stale.exists());
+        assert!(alive.exists());
+        assert!(unrelated.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
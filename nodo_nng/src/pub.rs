@@ -1,28 +1,114 @@
 // Copyright 2023 by David Weikersdorfer. All rights reserved.
 
-use crate::NngPubSubHeader;
-use log::{error, info, trace};
-use nng::{Protocol, Socket};
-use nodo::prelude::*;
-use nodo_core::{Topic, WithTopic};
-use std::{collections::HashMap, time::Instant};
-
-/// Codelet which receives serialized messages and writes them to MCAP
+use crate::{Encryptor, EyreResult, NngAuthKey, NngEncryptionKey, NngPubSubHeader};
+use core::time::Duration;
+use log::{error, info, trace, warn};
+use nng::{PipeEvent, Protocol, Socket};
+use nodo::{codelet::CountTotal, prelude::*};
+use nodo_core::{
+    fmt_bytes, fmt_duration, fmt_rate, Retrier, RetryConfig, RetryOutcome, Topic, WithTopic,
+};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+/// Socket used by [NngPub] to send messages
+///
+/// Implemented by [nng::Socket]. Other implementations can be injected with
+/// [NngPub::with_socket], e.g. to simulate send failures.
+pub trait PubSocket: Send {
+    /// Sends a message. On failure the message is returned together with the error.
+    fn send(&mut self, message: nng::Message) -> Result<(), (nng::Message, nng::Error)>;
+
+    fn close(&mut self);
+}
+
+impl PubSocket for Socket {
+    fn send(&mut self, message: nng::Message) -> Result<(), (nng::Message, nng::Error)> {
+        Socket::send(self, message)
+    }
+
+    fn close(&mut self) {
+        Socket::close(self)
+    }
+}
+
+/// Codelet which publishes messages tagged with a topic on an NNG PUB socket
+///
+/// Messages which fail to send are retried in later steps with backoff as configured by
+/// `NngPubConfig::retry`. Newer messages wait in the receiver meanwhile, up to `queue_size`. A
+/// message is dropped once all attempts failed.
 pub struct NngPub {
-    socket: Option<Socket>,
+    socket: Option<Box<dyn PubSocket>>,
     statistics: Option<Statistics>,
+    subscriber_added: Option<Arc<AtomicBool>>,
+    retrier: Option<Retrier>,
+    pending: Option<nng::Message>,
+    sent_count: u64,
+    encryptor: Option<Encryptor>,
+}
+
+/// Status of [NngPub] which shows send retries in its label
+pub struct NngPubStatus {
+    label: String,
+    status: DefaultStatus,
+    severity: Severity,
+}
+
+impl CodeletStatus for NngPubStatus {
+    fn default_implementation_status() -> Self {
+        Self {
+            label: "idle".into(),
+            status: DefaultStatus::Skipped,
+            severity: Severity::Info,
+        }
+    }
+
+    fn as_default_status(&self) -> DefaultStatus {
+        self.status
+    }
+
+    fn label(&self) -> &str {
+        &self.label
+    }
+
+    fn severity(&self) -> Severity {
+        self.severity
+    }
 }
 
 pub struct NngPubConfig {
     pub address: String,
     pub queue_size: usize,
     pub enable_statistics: bool,
+
+    /// If set messages are authenticated with a MAC computed from this pre-shared key. Subscribers
+    /// must be configured with the same key.
+    pub auth_key: Option<NngAuthKey>,
+
+    /// If set header and payload of messages are encrypted with this pre-shared key. Only the
+    /// topic is sent in plain text such that subscribers can still filter by topic. Encryption
+    /// also authenticates messages, thus `auth_key` must not be set in addition. Requires the
+    /// `encryption` feature.
+    pub encryption_key: Option<NngEncryptionKey>,
+
+    /// Backoff and number of attempts for messages which fail to send
+    pub retry: RetryConfig,
 }
 
 #[derive(Default)]
 pub struct Statistics {
     items: HashMap<String, TopicStatistics>,
     last_sec: Option<Instant>,
+
+    /// Time messages waited in the receiver before they were published. Only available if
+    /// latency tracking is enabled on the receiver.
+    residency: Option<CountTotal>,
 }
 
 impl Statistics {
@@ -51,13 +137,24 @@ impl Statistics {
         }
     }
 
+    pub fn set_residency(&mut self, residency: Option<CountTotal>) {
+        self.residency = residency;
+    }
+
     pub fn print_report(&self) {
         println!("NngPub statistics:");
+        if let Some(residency) = self.residency.as_ref() {
+            println!(
+                "  residency: {} avg | {} max",
+                fmt_duration(residency.average().unwrap_or_default()),
+                fmt_duration(residency.max().unwrap_or_default())
+            );
+        }
         for (topic, item) in self.items.iter() {
             println!(
-                "  [{topic}] {} Hz | {:.1} kB/s",
-                item.last_sec_count,
-                item.last_sec_size as f64 / 1024.
+                "  [{topic}] {} | {}/s",
+                fmt_rate(item.last_sec_count as f64),
+                fmt_bytes(item.last_sec_size as u64)
             );
         }
     }
@@ -94,12 +191,95 @@ impl Default for NngPub {
         Self {
             socket: None,
             statistics: None,
+            subscriber_added: None,
+            retrier: None,
+            pending: None,
+            sent_count: 0,
+            encryptor: None,
+        }
+    }
+}
+
+impl NngPub {
+    /// Creates a publisher which sets the given flag whenever a new subscriber connects
+    pub fn with_subscriber_flag(flag: Arc<AtomicBool>) -> Self {
+        Self {
+            subscriber_added: Some(flag),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a publisher which sends on the given socket instead of opening one
+    pub fn with_socket(socket: Box<dyn PubSocket>) -> Self {
+        Self {
+            socket: Some(socket),
+            ..Default::default()
+        }
+    }
+
+    /// Sends a message or keeps it for a later retry. Returns false if the message is pending.
+    fn send(&mut self, now: Duration, outmsg: nng::Message) -> bool {
+        // SAFETY: guaranteed by start
+        let socket = self.socket.as_mut().unwrap();
+        let retrier = self.retrier.as_mut().unwrap();
+
+        let mut returned = None;
+        let outcome = retrier.attempt(now, || {
+            socket.send(outmsg).map_err(|(outmsg, err)| {
+                returned = Some(outmsg);
+                err.into()
+            })
+        });
+
+        match outcome {
+            RetryOutcome::Done(()) => {
+                self.sent_count += 1;
+                true
+            }
+            RetryOutcome::Retry { not_before, error } => {
+                if let Some(err) = error {
+                    warn!(
+                        "failed to send message: {err}. Retrying in {}.",
+                        fmt_duration(not_before.saturating_sub(now))
+                    );
+                }
+                self.pending = returned;
+                false
+            }
+            RetryOutcome::Exhausted(err) => {
+                error!("failed to send message: {err}. Giving up and dropping it.");
+                true
+            }
+        }
+    }
+
+    fn status(&self, count: usize) -> NngPubStatus {
+        // SAFETY: guaranteed by start
+        let retrier = self.retrier.as_ref().unwrap();
+
+        NngPubStatus {
+            label: format!(
+                "sent {} msgs, {} retries, {} exhausted",
+                self.sent_count,
+                retrier.retry_count(),
+                retrier.exhaustion_count()
+            ),
+            status: if count > 0 {
+                DefaultStatus::Running
+            } else {
+                DefaultStatus::Skipped
+            },
+            severity: if retrier.is_retrying() {
+                Severity::Warn
+            } else {
+                Severity::Info
+            },
         }
     }
 }
 
 impl Codelet for NngPub {
-    type Status = DefaultStatus;
+    type Status = NngPubStatus;
     type Config = NngPubConfig;
     type Rx = DoubleBufferRx<Message<WithTopic<Vec<u8>>>>;
     type Tx = ();
@@ -108,18 +288,53 @@ impl Codelet for NngPub {
         (
             DoubleBufferRx::new(
                 OverflowPolicy::Forget(cfg.queue_size),
-                RetentionPolicy::Drop,
+                // messages wait here while a failed send is retried
+                RetentionPolicy::Keep,
             ),
             (),
         )
     }
 
-    fn start(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+    fn start(
+        &mut self,
+        cx: &Context<Self>,
+        _: &mut Self::Rx,
+        _: &mut Self::Tx,
+    ) -> EyreResult<NngPubStatus> {
+        nodo_core::capabilities::register("nng", env!("CARGO_PKG_VERSION"));
+
+        self.retrier = Some(Retrier::new(cx.config.retry.clone()));
+        self.pending = None;
+
+        if cx.config.auth_key.is_some() && cx.config.encryption_key.is_some() {
+            return Err(eyre::eyre!(
+                "`auth_key` and `encryption_key` are exclusive as encryption also authenticates"
+            ));
+        }
+        self.encryptor = cx
+            .config
+            .encryption_key
+            .as_ref()
+            .map(Encryptor::new)
+            .transpose()?;
+
+        if cx.config.enable_statistics {
+            self.statistics = Some(Statistics::default());
+        }
+
+        if self.socket.is_some() {
+            return Ok(self.status(0));
+        }
+
         info!("Opening PUB socket at '{}'..", cx.config.address);
         let socket = Socket::new(Protocol::Pub0)?;
 
+        let subscriber_added = self.subscriber_added.clone();
         socket.pipe_notify(move |_, ev| {
             trace!("nng::socket::pipe_notify: {ev:?}");
+            if let (PipeEvent::AddPost, Some(flag)) = (ev, subscriber_added.as_ref()) {
+                flag.store(true, Ordering::Relaxed);
+            }
         })?;
 
         let res = socket.listen(&cx.config.address);
@@ -129,47 +344,63 @@ impl Codelet for NngPub {
             res?;
         }
 
-        self.socket = Some(socket);
+        self.socket = Some(Box::new(socket));
 
-        if cx.config.enable_statistics {
-            self.statistics = Some(Statistics::default());
-        }
-
-        SUCCESS
+        Ok(self.status(0))
     }
 
-    fn stop(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+    fn stop(
+        &mut self,
+        _: &Context<Self>,
+        _: &mut Self::Rx,
+        _: &mut Self::Tx,
+    ) -> EyreResult<NngPubStatus> {
+        if self.pending.take().is_some() {
+            warn!("dropping a message which failed to send");
+        }
+
         // SAFETY: guaranteed by start
-        let socket = self.socket.take().unwrap();
+        let mut socket = self.socket.take().unwrap();
 
         socket.close();
 
-        SUCCESS
+        Ok(self.status(0))
     }
 
-    fn step(&mut self, _: &Context<Self>, rx: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
-        // SAFETY: guaranteed by start
-        let socket = self.socket.as_mut().unwrap();
+    fn step(
+        &mut self,
+        cx: &Context<Self>,
+        rx: &mut Self::Rx,
+        _: &mut Self::Tx,
+    ) -> EyreResult<NngPubStatus> {
+        let now = *cx.clocks.app_mono.now();
 
         let mut count = 0;
-        while let Some(message) = rx.try_pop() {
-            let topic_buffer = serialize_topic(&message.value.topic);
 
-            let header = NngPubSubHeader {
-                magic: NngPubSubHeader::MAGIC,
-                seq: message.seq.try_into()?,
-                stamp: message.stamp,
-                payload_checksum: NngPubSubHeader::CRC.checksum(&message.value.value),
-            };
-            let header_buffer = bincode::serialize(&header)?;
-
-            let outmsg_size = topic_buffer.len() + header_buffer.len() + message.value.value.len();
-            let mut outmsg = nng::Message::with_capacity(outmsg_size);
-            outmsg.push_back(&topic_buffer);
-            outmsg.push_back(&header_buffer);
-            outmsg.push_back(&message.value.value);
+        // a message which failed to send earlier goes out before newer messages
+        if let Some(outmsg) = self.pending.take() {
+            // SAFETY: guaranteed by start
+            if !self.retrier.as_ref().unwrap().is_ready(now) {
+                self.pending = Some(outmsg);
+                return Ok(self.status(0));
+            }
+            if !self.send(now, outmsg) {
+                return Ok(self.status(0));
+            }
+            count += 1;
+        }
 
-            socket.send(outmsg).map_err(|(_, err)| err)?;
+        while let Some(message) = rx.try_pop() {
+            let outmsg = encode(
+                &message,
+                cx.config.auth_key.as_ref(),
+                self.encryptor.as_mut(),
+            )?;
+            let outmsg_size = outmsg.len();
+
+            if !self.send(now, outmsg) {
+                break;
+            }
 
             count += 1;
 
@@ -180,15 +411,66 @@ impl Codelet for NngPub {
         }
 
         if let Some(stats) = self.statistics.as_mut() {
+            stats.set_residency(rx.residency_stats());
             stats.step();
         }
 
-        if count > 0 {
-            SUCCESS
+        Ok(self.status(count))
+    }
+}
+
+/// Encodes a message as topic, header, optional MAC and payload
+///
+/// Encrypted messages are encoded as topic, magic, nonce and the encrypted header and payload.
+/// Topic and magic are authenticated as additional data.
+pub(crate) fn encode(
+    message: &Message<WithTopic<Vec<u8>>>,
+    auth_key: Option<&NngAuthKey>,
+    encryptor: Option<&mut Encryptor>,
+) -> EyreResult<nng::Message> {
+    let topic_buffer = serialize_topic(&message.value.topic);
+    let payload = &message.value.value;
+
+    let header = NngPubSubHeader {
+        magic: if auth_key.is_some() && encryptor.is_none() {
+            NngPubSubHeader::MAGIC_AUTH
         } else {
-            SKIPPED
-        }
+            NngPubSubHeader::MAGIC
+        },
+        seq: message.seq,
+        stamp: message.stamp.clone(),
+        payload_checksum: NngPubSubHeader::CRC.checksum(payload),
+    };
+    let header_buffer = bincode::serialize(&header)?;
+
+    if let Some(encryptor) = encryptor {
+        let magic_buffer = bincode::serialize(&NngPubSubHeader::MAGIC_ENCRYPTED)?;
+        let aad = [topic_buffer.as_slice(), magic_buffer.as_slice()].concat();
+        let plaintext = [header_buffer.as_slice(), payload.as_slice()].concat();
+        let (nonce, ciphertext) = encryptor.seal(&aad, &plaintext)?;
+
+        let mut outmsg = nng::Message::with_capacity(aad.len() + nonce.len() + ciphertext.len());
+        outmsg.push_back(&aad);
+        outmsg.push_back(&nonce);
+        outmsg.push_back(&ciphertext);
+        return Ok(outmsg);
     }
+
+    let mac = auth_key.map(|key| NngPubSubHeader::mac(key, &topic_buffer, &header_buffer, payload));
+
+    let outmsg_size = topic_buffer.len()
+        + header_buffer.len()
+        + mac.map_or(0, |_| NngPubSubHeader::MAC_SIZE)
+        + payload.len();
+    let mut outmsg = nng::Message::with_capacity(outmsg_size);
+    outmsg.push_back(&topic_buffer);
+    outmsg.push_back(&header_buffer);
+    if let Some(mac) = mac {
+        outmsg.push_back(mac.as_bytes());
+    }
+    outmsg.push_back(payload);
+
+    Ok(outmsg)
 }
 
 fn serialize_topic(topic: &Topic) -> Vec<u8> {
@@ -205,3 +487,126 @@ fn serialize_topic(topic: &Topic) -> Vec<u8> {
 
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{NngPub, NngPubConfig, PubSocket};
+    use core::time::Duration;
+    use nodo::{
+        codelet::{
+            Clocks, Lifecycle, NodeletId, NodeletSetup, Transition, Vise, ViseTrait, WorkerId,
+        },
+        prelude::*,
+    };
+    use nodo_core::{RetryConfig, Severity, WithTopic};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+
+    /// Socket which fails a given number of sends before it succeeds
+    struct FlakySocket {
+        failures: Arc<AtomicUsize>,
+        sent: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    impl PubSocket for FlakySocket {
+        fn send(&mut self, message: nng::Message) -> Result<(), (nng::Message, nng::Error)> {
+            if self.failures.load(Ordering::Relaxed) > 0 {
+                self.failures.fetch_sub(1, Ordering::Relaxed);
+                return Err((message, nng::Error::TryAgain));
+            }
+            self.sent.lock().unwrap().push(message.as_slice().to_vec());
+            Ok(())
+        }
+
+        fn close(&mut self) {}
+    }
+
+    #[test]
+    fn test_send_retry() {
+        let failures = Arc::new(AtomicUsize::new(2));
+        let sent = Arc::new(Mutex::new(Vec::new()));
+
+        let mut instance = NngPub::with_socket(Box::new(FlakySocket {
+            failures: failures.clone(),
+            sent: sent.clone(),
+        }))
+        .into_instance(
+            "pub",
+            NngPubConfig {
+                address: String::new(),
+                queue_size: 10,
+                enable_statistics: false,
+                auth_key: None,
+                encryption_key: None,
+                retry: RetryConfig {
+                    max_attempts: 3,
+                    initial_backoff: Duration::ZERO,
+                    max_backoff: Duration::ZERO,
+                    jitter: 0.0,
+                },
+            },
+        );
+        let mut tx = DoubleBufferTx::new_auto_size();
+        tx.connect(&mut instance.rx).unwrap();
+
+        let mut vise = Vise::new(instance);
+        vise.setup(&mut NodeletSetup {
+            clocks: Clocks::new(),
+            nodelet_id_issue: NodeletId(WorkerId(0), 0),
+        });
+        vise.cycle(Transition::Start).unwrap();
+
+        let mut publish = |values: &[u8]| {
+            for &value in values {
+                tx.push(Message {
+                    seq: value as u64,
+                    stamp: Stamp {
+                        acqtime: Duration::ZERO.into(),
+                        pubtime: Duration::ZERO.into(),
+                    },
+                    value: WithTopic {
+                        topic: "test".into(),
+                        value: vec![value],
+                    },
+                })
+                .unwrap();
+            }
+            tx.flush();
+        };
+        let payloads = |sent: &Mutex<Vec<Vec<u8>>>| {
+            sent.lock()
+                .unwrap()
+                .iter()
+                .map(|msg| *msg.last().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // the first message fails twice and then goes out before the others
+        publish(&[1, 2, 3]);
+        vise.cycle(Transition::Step).unwrap();
+        assert!(sent.lock().unwrap().is_empty());
+        assert_eq!(vise.status().unwrap().severity, Severity::Warn);
+        vise.cycle(Transition::Step).unwrap();
+        vise.cycle(Transition::Step).unwrap();
+        assert_eq!(payloads(&sent), [1, 2, 3]);
+        let status = vise.status().unwrap();
+        assert_eq!(status.label, "sent 3 msgs, 2 retries, 0 exhausted");
+        assert_eq!(status.severity, Severity::Info);
+
+        // a message which fails every attempt is dropped
+        failures.store(3, Ordering::Relaxed);
+        publish(&[4, 5]);
+        for _ in 0..3 {
+            vise.cycle(Transition::Step).unwrap();
+        }
+        assert_eq!(payloads(&sent), [1, 2, 3, 5]);
+        assert_eq!(
+            vise.status().unwrap().label,
+            "sent 4 msgs, 4 retries, 1 exhausted"
+        );
+
+        vise.cycle(Transition::Stop).unwrap();
+    }
+}
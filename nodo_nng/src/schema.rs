@@ -0,0 +1,360 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::NngSub;
+use core::time::Duration;
+use nodo::prelude::*;
+use nodo_core::{EyreResult, TypeSchema, WithTopic};
+use nodo_std::SchemaWatch;
+use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+/// Reserved topic on which a [crate::Publisher] publishes the [SchemaSet] of its topics
+pub const SCHEMAS_TOPIC: &str = "__schemas__";
+
+/// Version hash of a type schema: hex encoded blake3 hash of its JSON document
+///
+/// Decoders can compare it against the hash of the schema they were written for.
+pub fn type_hash(schema: &TypeSchema) -> String {
+    // Serializing a schema to JSON can not fail as all its keys are strings
+    blake3::hash(&serde_json::to_vec(schema).unwrap())
+        .to_hex()
+        .to_string()
+}
+
+/// Schema of the messages published on a topic
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopicSchema {
+    pub topic: String,
+
+    /// Encoding of the payload, e.g. "bincode"
+    pub encoding: String,
+
+    /// See [type_hash]
+    pub type_hash: String,
+
+    pub schema: TypeSchema,
+}
+
+/// Schemas of all topics of a publisher as published on [SCHEMAS_TOPIC]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaSet {
+    pub topics: Vec<TopicSchema>,
+}
+
+impl SchemaSet {
+    pub fn get(&self, topic: &str) -> Option<&TopicSchema> {
+        self.topics.iter().find(|entry| entry.topic == topic)
+    }
+
+    pub fn to_json(&self) -> EyreResult<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    pub fn from_json(buffer: &[u8]) -> EyreResult<Self> {
+        Ok(serde_json::from_slice(buffer)?)
+    }
+
+    /// Decodes a raw NNG message received on [SCHEMAS_TOPIC], e.g. to dump the schemas of a
+    /// running publisher. Authenticated and encrypted messages are not supported.
+    pub fn from_nng_message(data: &[u8]) -> EyreResult<Self> {
+        Self::from_json(&NngSub::parse(data, None, None)?.value.value)
+    }
+}
+
+/// Schemas of published topics shared between a [crate::Publisher] and its [SchemaAnnouncer]
+#[derive(Clone, Default)]
+pub struct SchemaRegistry {
+    schemas: Arc<Mutex<SchemaSet>>,
+    is_dirty: Arc<AtomicBool>,
+}
+
+impl SchemaRegistry {
+    /// Adds or replaces the schema of a topic and requests a publication
+    pub fn register(&self, topic: &str, encoding: &str, schema: TypeSchema) {
+        let entry = TopicSchema {
+            topic: topic.to_string(),
+            encoding: encoding.to_string(),
+            type_hash: type_hash(&schema),
+            schema,
+        };
+
+        let mut schemas = self.schemas.lock().unwrap();
+        schemas.topics.retain(|other| other.topic != topic);
+        schemas.topics.push(entry);
+
+        self.is_dirty.store(true, Ordering::Relaxed);
+    }
+
+    pub fn schemas(&self) -> SchemaSet {
+        self.schemas.lock().unwrap().clone()
+    }
+
+    /// Flag which requests a publication when set, e.g. when a new subscriber connects
+    pub fn dirty_flag(&self) -> Arc<AtomicBool> {
+        self.is_dirty.clone()
+    }
+
+    fn take_dirty(&self) -> bool {
+        self.is_dirty.swap(false, Ordering::Relaxed)
+    }
+}
+
+#[derive(Clone)]
+pub struct SchemaAnnouncerConfig {
+    /// Interval at which the schemas are repeated even if nothing changed
+    pub period: Duration,
+}
+
+impl Default for SchemaAnnouncerConfig {
+    fn default() -> Self {
+        Self {
+            period: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Publishes the schemas of a [SchemaRegistry] as JSON, see [SCHEMAS_TOPIC]
+///
+/// Schemas are published periodically and immediately when a topic was registered or a
+/// subscriber connected.
+pub struct SchemaAnnouncer {
+    registry: SchemaRegistry,
+    last_publish: Option<Duration>,
+}
+
+impl SchemaAnnouncer {
+    pub fn new(registry: SchemaRegistry) -> Self {
+        Self {
+            registry,
+            last_publish: None,
+        }
+    }
+}
+
+impl Codelet for SchemaAnnouncer {
+    type Status = DefaultStatus;
+    type Config = SchemaAnnouncerConfig;
+    type Rx = ();
+    type Tx = DoubleBufferTx<Message<Vec<u8>>>;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), DoubleBufferTx::new_auto_size())
+    }
+
+    fn step(&mut self, cx: &Context<Self>, _: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        let now = cx.clocks.sys_mono.now();
+        let is_due = self
+            .last_publish
+            .is_none_or(|last| *now >= last + cx.config.period);
+        if !self.registry.take_dirty() && !is_due {
+            return SKIPPED;
+        }
+
+        let schemas = self.registry.schemas();
+        if schemas.topics.is_empty() {
+            return SKIPPED;
+        }
+
+        self.last_publish = Some(*now);
+        tx.push(Message {
+            seq: 0,
+            stamp: Stamp {
+                acqtime: now,
+                pubtime: cx.clocks.app_mono.now(),
+            },
+            value: schemas.to_json()?,
+        })?;
+
+        SUCCESS
+    }
+}
+
+/// Receives the schemas announced on [SCHEMAS_TOPIC] and updates the [SchemaWatch] of every
+/// watched topic
+///
+/// Connect it to the output of the [NngSub] which feeds the deserializers of the watched topics
+/// such that they detect when the publisher restarted with a new schema, see
+/// `nodo_std::SchemaChangePolicy`.
+#[derive(Default)]
+pub struct SchemaWatcher {
+    watches: Vec<SchemaWatch>,
+}
+
+impl SchemaWatcher {
+    /// Watches the schema of a topic. Pass the watch to the deserializer of the topic.
+    pub fn watch(&mut self, topic: &str) -> SchemaWatch {
+        if let Some(watch) = self.watches.iter().find(|watch| watch.topic() == topic) {
+            return watch.clone();
+        }
+        let watch = SchemaWatch::new(topic);
+        self.watches.push(watch.clone());
+        watch
+    }
+
+    fn update(&self, schemas: &SchemaSet) {
+        for watch in self.watches.iter() {
+            if let Some(entry) = schemas.get(watch.topic()) {
+                watch.announce(&entry.type_hash);
+            }
+        }
+    }
+}
+
+impl Codelet for SchemaWatcher {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = DoubleBufferRx<Message<WithTopic<Vec<u8>>>>;
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (DoubleBufferRx::new_auto_size(), ())
+    }
+
+    fn step(&mut self, _: &Context<Self>, rx: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        let mut is_updated = false;
+        while let Some(msg) = rx.try_pop() {
+            if String::from(&msg.value.topic) != SCHEMAS_TOPIC {
+                continue;
+            }
+            match SchemaSet::from_json(&msg.value.value) {
+                Ok(schemas) => {
+                    self.update(&schemas);
+                    is_updated = true;
+                }
+                Err(err) => log::warn!("dropped malformed schema announcement: {err:?}"),
+            }
+        }
+
+        if is_updated {
+            SUCCESS
+        } else {
+            SKIPPED
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{type_hash, SchemaSet, SchemaWatcher, TopicSchema};
+    use nodo::prelude::*;
+    use nodo_core::{FieldSchema, TypeSchema, VariantSchema};
+
+    #[allow(dead_code)]
+    #[derive(SelfDescribing)]
+    struct Pose {
+        position: [f64; 3],
+        label: Option<String>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(SelfDescribing)]
+    enum Shape {
+        Point,
+        Circle(f32),
+        Polygon { vertices: Vec<(f32, f32)> },
+    }
+
+    #[allow(dead_code)]
+    #[derive(SelfDescribing)]
+    struct Scene {
+        poses: Vec<Pose>,
+        shape: Shape,
+    }
+
+    #[test]
+    fn test_derive() {
+        let pose = TypeSchema::Struct {
+            name: "Pose".into(),
+            fields: vec![
+                FieldSchema {
+                    name: "position".into(),
+                    ty: TypeSchema::Array {
+                        item: Box::new(TypeSchema::primitive("f64")),
+                        len: 3,
+                    },
+                },
+                FieldSchema {
+                    name: "label".into(),
+                    ty: TypeSchema::Option {
+                        item: Box::new(TypeSchema::primitive("string")),
+                    },
+                },
+            ],
+        };
+        assert_eq!(Pose::describe(), pose);
+
+        let TypeSchema::Enum { name, variants } = Shape::describe() else {
+            panic!("expected enum");
+        };
+        assert_eq!(name, "Shape");
+        assert_eq!(
+            variants[0],
+            VariantSchema {
+                name: "Point".into(),
+                fields: vec![]
+            }
+        );
+        assert_eq!(variants[1].fields, [FieldSchema::new::<f32>("0")]);
+        assert_eq!(
+            variants[2].fields,
+            [FieldSchema::new::<Vec<(f32, f32)>>("vertices")]
+        );
+    }
+
+    #[test]
+    fn test_schema_set_round_trip() {
+        let schema = Scene::describe();
+        let set = SchemaSet {
+            topics: vec![TopicSchema {
+                topic: "scene".into(),
+                encoding: "bincode".into(),
+                type_hash: type_hash(&schema),
+                schema: schema.clone(),
+            }],
+        };
+
+        let json = set.to_json().unwrap();
+        assert!(std::str::from_utf8(&json)
+            .unwrap()
+            .contains(r#"{"name":"position","type":{"kind":"array","item":{"kind":"primitive","name":"f64"},"len":3}}"#));
+
+        let actual = SchemaSet::from_json(&json).unwrap();
+        assert_eq!(actual, set);
+        assert_eq!(
+            type_hash(&actual.get("scene").unwrap().schema),
+            type_hash(&schema)
+        );
+        assert_ne!(type_hash(&schema), type_hash(&Pose::describe()));
+    }
+
+    #[test]
+    fn test_schema_watcher() {
+        let mut watcher = SchemaWatcher::default();
+        let pose = watcher.watch("pose");
+        let scene = watcher.watch("scene");
+        let pose_2 = watcher.watch("pose");
+        assert_eq!(watcher.watches.len(), 2);
+
+        let entry = |schema: TypeSchema| TopicSchema {
+            topic: "pose".into(),
+            encoding: "bincode".into(),
+            type_hash: type_hash(&schema),
+            schema,
+        };
+        watcher.update(&SchemaSet {
+            topics: vec![entry(Pose::describe())],
+        });
+        assert_eq!(pose.hash(), Some(type_hash(&Pose::describe())));
+        assert_eq!(pose_2.hash(), pose.hash());
+        assert_eq!(scene.hash(), None);
+
+        // a restarted publisher announces a new schema
+        watcher.update(&SchemaSet {
+            topics: vec![entry(Scene::describe())],
+        });
+        assert_eq!(pose.hash(), Some(type_hash(&Scene::describe())));
+    }
+}
@@ -1,6 +1,9 @@
 // Copyright 2023 by David Weikersdorfer. All rights reserved.
 
-use crate::{EyreResult, NngPubSubHeader};
+use crate::{
+    Decryptor, EyreResult, NngAuthError, NngAuthKey, NngDecryptError, NngEncryptionKey,
+    NngPubSubHeader, NONCE_SIZE,
+};
 use log::{error, info, trace};
 use nng::{
     options::{protocol::pubsub::Subscribe, Options},
@@ -8,16 +11,72 @@ use nng::{
 };
 use nodo::prelude::*;
 use nodo_core::{eyre, Topic, WithTopic};
+use std::time::{Duration, Instant};
+
+/// Minimum time between two warnings about messages which failed authentication or decryption
+const AUTH_WARNING_INTERVAL: Duration = Duration::from_secs(1);
 
 /// Codelet which receives serialized messages and writes them to MCAP
 pub struct NngSub {
     socket: Option<Socket>,
     message_count: usize,
+    auth_failures: AuthFailures,
+    decrypt_failures: AuthFailures,
+    decryptor: Option<Decryptor>,
+}
+
+/// Counts messages which failed authentication or decryption and reports them with a
+/// rate-limited warning
+struct AuthFailures {
+    reason: &'static str,
+    count: usize,
+    unreported: usize,
+    last_warning: Option<Instant>,
+}
+
+impl AuthFailures {
+    fn new(reason: &'static str) -> Self {
+        Self {
+            reason,
+            count: 0,
+            unreported: 0,
+            last_warning: None,
+        }
+    }
+
+    fn add(&mut self, err: &dyn core::fmt::Display, pipe: Option<nng::Pipe>) {
+        self.count += 1;
+        self.unreported += 1;
+
+        let now = Instant::now();
+        if self
+            .last_warning
+            .is_none_or(|last| now - last >= AUTH_WARNING_INTERVAL)
+        {
+            log::warn!(
+                "dropped {} message(s) which failed {} (total: {}). last: {err} (pipe: {})",
+                self.unreported,
+                self.reason,
+                self.count,
+                pipe.map_or_else(|| "unknown".into(), |pipe| format!("{pipe:?}"))
+            );
+            self.unreported = 0;
+            self.last_warning = Some(now);
+        }
+    }
 }
 
 pub struct NngSubConfig {
     pub address: String,
     pub queue_size: usize,
+
+    /// If set only messages authenticated with this pre-shared key are accepted. Messages which
+    /// fail authentication are dropped.
+    pub auth_key: Option<NngAuthKey>,
+
+    /// If set only messages encrypted with this pre-shared key are accepted. Messages which fail
+    /// decryption are dropped. Requires the `encryption` feature.
+    pub encryption_key: Option<NngEncryptionKey>,
 }
 
 impl Default for NngSub {
@@ -25,6 +84,9 @@ impl Default for NngSub {
         Self {
             socket: None,
             message_count: 0,
+            auth_failures: AuthFailures::new("authentication"),
+            decrypt_failures: AuthFailures::new("decryption"),
+            decryptor: None,
         }
     }
 }
@@ -41,6 +103,15 @@ impl Codelet for NngSub {
     }
 
     fn start(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        nodo_core::capabilities::register("nng", env!("CARGO_PKG_VERSION"));
+
+        self.decryptor = cx
+            .config
+            .encryption_key
+            .as_ref()
+            .map(Decryptor::new)
+            .transpose()?;
+
         info!("Opening SUB socket at '{}'..", cx.config.address);
 
         let socket = Socket::new(Protocol::Sub0)?;
@@ -88,14 +159,25 @@ impl Codelet for NngSub {
             }
 
             match socket.try_recv() {
-                Ok(buff) => match Self::parse(buff) {
+                Ok(mut buff) => match Self::parse(
+                    buff.as_slice(),
+                    cx.config.auth_key.as_ref(),
+                    self.decryptor.as_ref(),
+                ) {
                     Ok(msg) => {
                         tx.push(msg)?;
                         self.message_count += 1;
                         received_count += 1;
                     }
                     Err(err) => {
-                        log::error!("{err:?}");
+                        let pipe = buff.pipe();
+                        if let Some(auth_err) = err.downcast_ref::<NngAuthError>() {
+                            self.auth_failures.add(auth_err, pipe);
+                        } else if let Some(decrypt_err) = err.downcast_ref::<NngDecryptError>() {
+                            self.decrypt_failures.add(decrypt_err, pipe);
+                        } else {
+                            log::error!("{err:?}");
+                        }
                     }
                 },
                 Err(nng::Error::TryAgain) => {
@@ -114,23 +196,83 @@ impl Codelet for NngSub {
 }
 
 impl NngSub {
-    fn parse(msg: nng::Message) -> EyreResult<Message<WithTopic<Vec<u8>>>> {
-        // Message has three parts:
-        let data = msg.as_slice();
+    /// Number of messages which were dropped because they failed authentication
+    pub fn auth_failure_count(&self) -> usize {
+        self.auth_failures.count
+    }
+
+    /// Number of messages which were dropped because they failed decryption
+    pub fn decrypt_failure_count(&self) -> usize {
+        self.decrypt_failures.count
+    }
+
+    pub(crate) fn parse(
+        data: &[u8],
+        auth_key: Option<&NngAuthKey>,
+        decryptor: Option<&Decryptor>,
+    ) -> EyreResult<Message<WithTopic<Vec<u8>>>> {
+        // Message has three or four parts:
 
         // 1) topic: null-terminated string
-        let (cstr, data) = parse_cstr(data)?;
+        let (cstr, rest) = parse_cstr(data)?;
+        let topic_buffer = &data[..data.len() - rest.len()];
         let topic: Topic = cstr.into();
+        let data = rest;
+
+        // encrypted messages: magic, nonce, and encrypted header and payload
+        let is_encrypted = data.len() >= MAGIC_SIZE
+            && bincode::deserialize::<u64>(&data[..MAGIC_SIZE])?
+                == NngPubSubHeader::MAGIC_ENCRYPTED;
+        match (decryptor, is_encrypted) {
+            (Some(decryptor), true) => {
+                let (magic_nonce, ciphertext) = data
+                    .split_at_checked(MAGIC_SIZE + NONCE_SIZE)
+                    .ok_or(NngDecryptError::InvalidCiphertext)?;
+                let (magic, nonce) = magic_nonce.split_at(MAGIC_SIZE);
+                let aad = [topic_buffer, magic].concat();
+                let plaintext = decryptor.open(nonce, &aad, ciphertext)?;
+                Self::parse_header_and_payload(topic, topic_buffer, &plaintext, None)
+            }
+            (Some(_), false) => Err(NngDecryptError::NotEncrypted.into()),
+            (None, true) => Err(NngDecryptError::MissingKey.into()),
+            (None, false) => Self::parse_header_and_payload(topic, topic_buffer, data, auth_key),
+        }
+    }
 
+    fn parse_header_and_payload(
+        topic: Topic,
+        topic_buffer: &[u8],
+        data: &[u8],
+        auth_key: Option<&NngAuthKey>,
+    ) -> EyreResult<Message<WithTopic<Vec<u8>>>> {
         // 2) header: NngPubSubHeader
-        let header: NngPubSubHeader =
-            bincode::deserialize(&data[0..NngPubSubHeader::BINCODE_SIZE])?;
-        if header.magic != NngPubSubHeader::MAGIC {
-            return Err(eyre!("invalid header magic"));
+        if data.len() < NngPubSubHeader::BINCODE_SIZE {
+            return Err(eyre!("message too short for header"));
         }
+        let (header_buffer, data) = data.split_at(NngPubSubHeader::BINCODE_SIZE);
+        let header: NngPubSubHeader = bincode::deserialize(header_buffer)?;
 
-        // 3) value: [u8]
-        let value = data[NngPubSubHeader::BINCODE_SIZE..].to_vec();
+        // 3) mac: only present for authenticated messages
+        let data = match (auth_key, header.magic) {
+            (None, NngPubSubHeader::MAGIC) => data,
+            (Some(_), NngPubSubHeader::MAGIC) => return Err(NngAuthError::MissingMac.into()),
+            (Some(key), NngPubSubHeader::MAGIC_AUTH) => {
+                if data.len() < NngPubSubHeader::MAC_SIZE {
+                    return Err(NngAuthError::InvalidMac.into());
+                }
+                let (mac, payload) = data.split_at(NngPubSubHeader::MAC_SIZE);
+                let mac = blake3::Hash::from(<[u8; NngPubSubHeader::MAC_SIZE]>::try_from(mac)?);
+                // blake3::Hash uses constant-time comparison
+                if mac != NngPubSubHeader::mac(key, topic_buffer, header_buffer, payload) {
+                    return Err(NngAuthError::InvalidMac.into());
+                }
+                payload
+            }
+            _ => return Err(eyre!("invalid header magic")),
+        };
+
+        // 4) value: [u8]
+        let value = data.to_vec();
         let checksum = NngPubSubHeader::CRC.checksum(&value);
         if header.payload_checksum != checksum {
             return Err(eyre!(
@@ -148,6 +290,9 @@ impl NngSub {
     }
 }
 
+/// Size of the bincode-encoded header magic
+const MAGIC_SIZE: usize = 8;
+
 fn parse_cstr(utf8_src: &[u8]) -> EyreResult<(&str, &[u8])> {
     let end = utf8_src
         .iter()
@@ -156,3 +301,229 @@ fn parse_cstr(utf8_src: &[u8]) -> EyreResult<(&str, &[u8])> {
 
     Ok(::std::str::from_utf8(&utf8_src[0..end]).map(|x| (x, &utf8_src[end + 1..]))?)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{r#pub::encode, NngAuthError, NngAuthKey, NngSub};
+    use core::time::Duration;
+    use nodo::prelude::*;
+    use nodo_core::{EyreResult, WithTopic};
+
+    const KEY: NngAuthKey = [7; 32];
+
+    fn message() -> Message<WithTopic<Vec<u8>>> {
+        Message {
+            seq: 42,
+            stamp: Stamp {
+                acqtime: Duration::from_millis(1000).into(),
+                pubtime: Duration::from_millis(1001).into(),
+            },
+            value: WithTopic {
+                topic: "test".into(),
+                value: vec![1, 2, 3, 4, 5],
+            },
+        }
+    }
+
+    fn round_trip(
+        pub_key: Option<&NngAuthKey>,
+        sub_key: Option<&NngAuthKey>,
+    ) -> EyreResult<Message<WithTopic<Vec<u8>>>> {
+        let buff = encode(&message(), pub_key, None)?;
+        NngSub::parse(buff.as_slice(), sub_key, None)
+    }
+
+    fn auth_error(result: EyreResult<Message<WithTopic<Vec<u8>>>>) -> Option<NngAuthError> {
+        result
+            .err()
+            .unwrap()
+            .downcast_ref::<NngAuthError>()
+            .copied()
+    }
+
+    #[test]
+    fn test_keyless() {
+        let msg = round_trip(None, None).unwrap();
+        assert_eq!(msg.seq, 42);
+        assert_eq!(msg.value.value, message().value.value);
+    }
+
+    #[test]
+    fn test_matching_keys() {
+        let msg = round_trip(Some(&KEY), Some(&KEY)).unwrap();
+        assert_eq!(msg.seq, 42);
+        assert_eq!(msg.stamp.acqtime, message().stamp.acqtime);
+        assert_eq!(msg.stamp.pubtime, message().stamp.pubtime);
+        assert_eq!(msg.value.value, message().value.value);
+    }
+
+    #[test]
+    fn test_mismatched_keys() {
+        assert_eq!(
+            auth_error(round_trip(Some(&KEY), Some(&[8; 32]))),
+            Some(NngAuthError::InvalidMac)
+        );
+    }
+
+    #[test]
+    fn test_missing_key() {
+        assert_eq!(
+            auth_error(round_trip(None, Some(&KEY))),
+            Some(NngAuthError::MissingMac)
+        );
+
+        // keyless subscribers do not accept authenticated messages
+        let result = round_trip(Some(&KEY), None);
+        assert!(result.is_err());
+        assert_eq!(auth_error(result), None);
+    }
+
+    #[test]
+    fn test_tampered_payload() {
+        let mut buff = encode(&message(), Some(&KEY), None).unwrap();
+        let last = buff.len() - 1;
+        buff[last] ^= 0x01;
+        assert_eq!(
+            auth_error(NngSub::parse(buff.as_slice(), Some(&KEY), None)),
+            Some(NngAuthError::InvalidMac)
+        );
+    }
+
+    #[cfg(feature = "encryption")]
+    mod encryption {
+        use super::message;
+        use crate::{
+            r#pub::encode, Decryptor, Encryptor, NngDecryptError, NngEncryptionKey, NngSub,
+        };
+        use nodo::prelude::*;
+        use nodo_core::{EyreResult, WithTopic};
+
+        const KEY: NngEncryptionKey = [3; 32];
+
+        fn decrypt_error(
+            result: EyreResult<Message<WithTopic<Vec<u8>>>>,
+        ) -> Option<NngDecryptError> {
+            result
+                .err()
+                .unwrap()
+                .downcast_ref::<NngDecryptError>()
+                .copied()
+        }
+
+        fn encrypt() -> nng::Message {
+            let mut encryptor = Encryptor::new(&KEY).unwrap();
+            encode(&message(), None, Some(&mut encryptor)).unwrap()
+        }
+
+        #[test]
+        fn test_round_trip() {
+            let mut encryptor = Encryptor::new(&KEY).unwrap();
+            let decryptor = Decryptor::new(&KEY).unwrap();
+
+            let first = encode(&message(), None, Some(&mut encryptor)).unwrap();
+            let second = encode(&message(), None, Some(&mut encryptor)).unwrap();
+
+            // the same message is encrypted with a different nonce every time
+            assert_ne!(first.as_slice(), second.as_slice());
+
+            for buff in [first, second] {
+                // only the topic is in plain text
+                assert!(buff.as_slice().starts_with(b"test\0"));
+                assert!(!buff
+                    .as_slice()
+                    .windows(5)
+                    .any(|w| w == message().value.value.as_slice()));
+
+                let msg = NngSub::parse(buff.as_slice(), None, Some(&decryptor)).unwrap();
+                assert_eq!(msg.seq, 42);
+                assert_eq!(msg.stamp.acqtime, message().stamp.acqtime);
+                assert_eq!(msg.stamp.pubtime, message().stamp.pubtime);
+                assert_eq!(msg.value.topic, message().value.topic);
+                assert_eq!(msg.value.value, message().value.value);
+            }
+        }
+
+        #[test]
+        fn test_random_nonces() {
+            // nonces are random and thus differ between messages and between encryptors
+            let mut a = Encryptor::new(&KEY).unwrap();
+            let mut b = Encryptor::new(&KEY).unwrap();
+            let (nonce_a, _) = a.seal(b"", b"x").unwrap();
+            let (nonce_b, _) = b.seal(b"", b"x").unwrap();
+            let (next, _) = a.seal(b"", b"x").unwrap();
+            assert_eq!(nonce_a.len(), 24);
+            assert_ne!(nonce_a, nonce_b);
+            assert_ne!(next, nonce_a);
+        }
+
+        #[test]
+        fn test_tampered_ciphertext() {
+            let decryptor = Decryptor::new(&KEY).unwrap();
+            let len = encrypt().len();
+
+            // flipping any bit after the topic fails decryption, including the magic
+            for i in 5..len {
+                let mut buff = encrypt();
+                buff[i] ^= 0x01;
+                let result = NngSub::parse(buff.as_slice(), None, Some(&decryptor));
+                if i < 13 {
+                    // a modified magic is no longer recognized as encrypted
+                    assert_eq!(decrypt_error(result), Some(NngDecryptError::NotEncrypted));
+                } else {
+                    assert_eq!(
+                        decrypt_error(result),
+                        Some(NngDecryptError::InvalidCiphertext),
+                        "byte {i}"
+                    );
+                }
+            }
+
+            // the topic is authenticated
+            let mut buff = encrypt();
+            buff[0] = b'b';
+            assert_eq!(
+                decrypt_error(NngSub::parse(buff.as_slice(), None, Some(&decryptor))),
+                Some(NngDecryptError::InvalidCiphertext)
+            );
+
+            // truncated
+            let buff = encrypt();
+            assert_eq!(
+                decrypt_error(NngSub::parse(
+                    &buff.as_slice()[..20],
+                    None,
+                    Some(&decryptor)
+                )),
+                Some(NngDecryptError::InvalidCiphertext)
+            );
+        }
+
+        #[test]
+        fn test_key_mismatch() {
+            let other = Decryptor::new(&[4; 32]).unwrap();
+            assert_eq!(
+                decrypt_error(NngSub::parse(encrypt().as_slice(), None, Some(&other))),
+                Some(NngDecryptError::InvalidCiphertext)
+            );
+
+            // plain and encrypted messages are distinguished
+            let plain = encode(&message(), None, None).unwrap();
+            let decryptor = Decryptor::new(&KEY).unwrap();
+            assert_eq!(
+                decrypt_error(NngSub::parse(plain.as_slice(), None, Some(&decryptor))),
+                Some(NngDecryptError::NotEncrypted)
+            );
+            assert_eq!(
+                decrypt_error(NngSub::parse(encrypt().as_slice(), None, None)),
+                Some(NngDecryptError::MissingKey)
+            );
+        }
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    #[test]
+    fn test_encryption_requires_feature() {
+        assert!(crate::Encryptor::new(&[3; 32]).is_err());
+        assert!(crate::Decryptor::new(&[3; 32]).is_err());
+    }
+}
@@ -0,0 +1,66 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use nodo::channels::DynConnectError;
+
+/// Error returned when loading a pipeline
+///
+/// The path is a JSON pointer to the location in the document which caused the error, for
+/// example `/connections/2/rx`.
+#[derive(Debug, thiserror::Error)]
+#[error("pipeline error at `{path}`: {kind}")]
+pub struct PipelineError {
+    pub path: String,
+    pub kind: PipelineErrorKind,
+}
+
+impl PipelineError {
+    pub fn new<S: Into<String>>(path: S, kind: PipelineErrorKind) -> Self {
+        Self {
+            path: path.into(),
+            kind,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PipelineErrorKind {
+    #[error("could not read pipeline file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("expected an array")]
+    ExpectedArray,
+
+    #[error("unknown codelet type `{0}`")]
+    UnknownType(String),
+
+    #[error("invalid config for codelet type `{type_name}`: {message}")]
+    InvalidConfig { type_name: String, message: String },
+
+    #[error("duplicate codelet name `{0}`")]
+    DuplicateCodelet(String),
+
+    #[error("unknown codelet `{0}`")]
+    UnknownCodelet(String),
+
+    #[error("invalid channel path `{0}`: expected `codelet.channel`")]
+    InvalidChannelPath(String),
+
+    #[error("codelet `{codelet}` has no {direction} channel `{channel}`")]
+    UnknownChannel {
+        codelet: String,
+        channel: String,
+        direction: &'static str,
+    },
+
+    #[error("could not connect channels: {0}")]
+    Connect(#[from] DynConnectError),
+
+    #[error("codelet `{0}` is not part of any schedule")]
+    NotScheduled(String),
+
+    #[error("codelet `{0}` is scheduled more than once")]
+    ScheduledTwice(String),
+}
@@ -0,0 +1,33 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+//! Builds schedules from a declarative JSON pipeline description
+//!
+//! A pipeline document lists codelet instances, the connections between their channels and the
+//! schedules which execute them:
+//!
+//! ```json
+//! {
+//!   "codelets": [
+//!     { "type": "Alice", "name": "alice" },
+//!     { "type": "Bob", "name": "bob", "config": { "queue_size": 1 } }
+//!   ],
+//!   "connections": [
+//!     { "tx": "alice.ping", "rx": "bob.ping" }
+//!   ],
+//!   "schedules": [
+//!     { "name": "main", "thread_id": 0, "period": 0.002, "codelets": ["alice", "bob"] }
+//!   ]
+//! }
+//! ```
+//!
+//! Codelet types are looked up by name in a [CodeletRegistry].
+
+mod error;
+mod loader;
+mod registry;
+mod spec;
+
+pub use error::*;
+pub use loader::*;
+pub use registry::*;
+pub use spec::*;
@@ -0,0 +1,245 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::{
+    CodeletRegistry, CodeletSpec, ConnectionSpec, Node, PipelineError, PipelineErrorKind,
+    ScheduleSpec,
+};
+use core::time::Duration;
+use nodo::codelet::{ScheduleBuilder, Sequence};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::{collections::HashMap, path::Path};
+
+/// Loads a pipeline from a JSON file and builds its schedules
+pub fn load_pipeline_file<P: AsRef<Path>>(
+    registry: &CodeletRegistry,
+    path: P,
+) -> Result<Vec<ScheduleBuilder>, PipelineError> {
+    let text = std::fs::read_to_string(path).map_err(|err| PipelineError::new("", err.into()))?;
+    load_pipeline_str(registry, &text)
+}
+
+/// Loads a pipeline from a JSON string and builds its schedules
+pub fn load_pipeline_str(
+    registry: &CodeletRegistry,
+    text: &str,
+) -> Result<Vec<ScheduleBuilder>, PipelineError> {
+    let doc: Value =
+        serde_json::from_str(text).map_err(|err| PipelineError::new("", err.into()))?;
+    load_pipeline(registry, &doc)
+}
+
+/// Builds the schedules of a pipeline given as a JSON value
+///
+/// Every codelet must be part of exactly one schedule.
+pub fn load_pipeline(
+    registry: &CodeletRegistry,
+    doc: &Value,
+) -> Result<Vec<ScheduleBuilder>, PipelineError> {
+    let codelets: Vec<CodeletSpec> = parse_list(doc, "codelets")?;
+    let connections: Vec<ConnectionSpec> = parse_list(doc, "connections")?;
+    let schedules: Vec<ScheduleSpec> = parse_list(doc, "schedules")?;
+
+    // create codelet instances
+    let mut nodes: Vec<Option<Box<dyn Node>>> = Vec::with_capacity(codelets.len());
+    let mut lookup = HashMap::new();
+    for (i, spec) in codelets.iter().enumerate() {
+        if lookup.insert(spec.name.as_str(), i).is_some() {
+            return Err(PipelineError::new(
+                format!("/codelets/{i}/name"),
+                PipelineErrorKind::DuplicateCodelet(spec.name.clone()),
+            ));
+        }
+
+        let node = registry
+            .create(&spec.type_name, &spec.name, spec.config.clone())
+            .ok_or_else(|| {
+                PipelineError::new(
+                    format!("/codelets/{i}/type"),
+                    PipelineErrorKind::UnknownType(spec.type_name.clone()),
+                )
+            })?
+            .map_err(|message| {
+                PipelineError::new(
+                    format!("/codelets/{i}/config"),
+                    PipelineErrorKind::InvalidConfig {
+                        type_name: spec.type_name.clone(),
+                        message,
+                    },
+                )
+            })?;
+        nodes.push(Some(node));
+    }
+
+    // wire connections
+    for (i, conn) in connections.iter().enumerate() {
+        let tx_path = format!("/connections/{i}/tx");
+        let rx_path = format!("/connections/{i}/rx");
+
+        let (tx_node, tx_channel) = resolve_codelet(&lookup, &conn.tx, &tx_path)?;
+        let (rx_node, rx_channel) = resolve_codelet(&lookup, &conn.rx, &rx_path)?;
+
+        let tx_index = nodes[tx_node]
+            .as_ref()
+            .unwrap()
+            .tx_index(tx_channel)
+            .ok_or_else(|| unknown_channel(&tx_path, &codelets[tx_node], tx_channel, "TX"))?;
+        let rx_index = nodes[rx_node]
+            .as_ref()
+            .unwrap()
+            .rx_index(rx_channel)
+            .ok_or_else(|| unknown_channel(&rx_path, &codelets[rx_node], rx_channel, "RX"))?;
+
+        let result = if tx_node == rx_node {
+            nodes[tx_node]
+                .as_mut()
+                .unwrap()
+                .connect_self(tx_index, rx_index)
+        } else {
+            let (tx, rx) = pair_mut(&mut nodes, tx_node, rx_node);
+            let tx = tx.as_mut().unwrap();
+            match rx.as_mut().unwrap().rx_endpoint(rx_index) {
+                Some(endpoint) => tx.connect(tx_index, endpoint),
+                None => Err(nodo::channels::DynConnectError::NotSupported),
+            }
+        };
+        result.map_err(|err| PipelineError::new(format!("/connections/{i}"), err.into()))?;
+    }
+
+    // assign codelets to schedules
+    let mut builders = Vec::with_capacity(schedules.len());
+    for (i, spec) in schedules.iter().enumerate() {
+        let path = format!("/schedules/{i}");
+
+        let mut builder = ScheduleBuilder::new()
+            .with_name(spec.name.clone())
+            .with_thread_id(spec.thread_id);
+        if let Some(period) = spec.period {
+            builder = builder.with_period(Duration::from_secs_f64(period));
+        }
+        builder.max_step_count = spec.max_step_count;
+
+        for (j, name) in spec.codelets.iter().enumerate() {
+            let node = take_node(&mut nodes, &lookup, name, &format!("{path}/codelets/{j}"))?;
+            builder.sequences.push(Sequence {
+                name: String::new(),
+                period: None,
+                vises: vec![node.into_vise()],
+                auto_stop_on_closed: false,
+                priority: 0,
+            });
+        }
+
+        for (j, seq_spec) in spec.sequences.iter().enumerate() {
+            let mut seq = Sequence::new()
+                .with_name(seq_spec.name.clone())
+                .with_auto_stop_on_closed(seq_spec.auto_stop_on_closed);
+            for (k, name) in seq_spec.codelets.iter().enumerate() {
+                let node = take_node(
+                    &mut nodes,
+                    &lookup,
+                    name,
+                    &format!("{path}/sequences/{j}/codelets/{k}"),
+                )?;
+                seq.vises.push(node.into_vise());
+            }
+            builder.sequences.push(seq);
+        }
+
+        builders.push(builder);
+    }
+
+    if let Some(i) = nodes.iter().position(|n| n.is_some()) {
+        return Err(PipelineError::new(
+            format!("/codelets/{i}"),
+            PipelineErrorKind::NotScheduled(codelets[i].name.clone()),
+        ));
+    }
+
+    Ok(builders)
+}
+
+/// Parses an optional list of items stored under `key` in the document
+fn parse_list<T: DeserializeOwned>(doc: &Value, key: &str) -> Result<Vec<T>, PipelineError> {
+    match doc.get(key) {
+        None => Ok(Vec::new()),
+        Some(Value::Array(items)) => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                T::deserialize(item)
+                    .map_err(|err| PipelineError::new(format!("/{key}/{i}"), err.into()))
+            })
+            .collect(),
+        Some(_) => Err(PipelineError::new(
+            format!("/{key}"),
+            PipelineErrorKind::ExpectedArray,
+        )),
+    }
+}
+
+/// Splits a `codelet.channel` path and finds the codelet
+fn resolve_codelet<'a>(
+    lookup: &HashMap<&str, usize>,
+    channel_path: &'a str,
+    path: &str,
+) -> Result<(usize, &'a str), PipelineError> {
+    let (codelet, channel) = channel_path
+        .split_once('.')
+        .filter(|(a, b)| !a.is_empty() && !b.is_empty())
+        .ok_or_else(|| {
+            PipelineError::new(
+                path,
+                PipelineErrorKind::InvalidChannelPath(channel_path.into()),
+            )
+        })?;
+
+    let index = *lookup.get(codelet).ok_or_else(|| {
+        PipelineError::new(path, PipelineErrorKind::UnknownCodelet(codelet.into()))
+    })?;
+
+    Ok((index, channel))
+}
+
+fn unknown_channel(
+    path: &str,
+    spec: &CodeletSpec,
+    channel: &str,
+    direction: &'static str,
+) -> PipelineError {
+    PipelineError::new(
+        path,
+        PipelineErrorKind::UnknownChannel {
+            codelet: spec.name.clone(),
+            channel: channel.into(),
+            direction,
+        },
+    )
+}
+
+/// Mutable access to two different elements of a slice
+fn pair_mut<T>(items: &mut [T], a: usize, b: usize) -> (&mut T, &mut T) {
+    assert_ne!(a, b);
+    if a < b {
+        let (lo, hi) = items.split_at_mut(b);
+        (&mut lo[a], &mut hi[0])
+    } else {
+        let (lo, hi) = items.split_at_mut(a);
+        (&mut hi[0], &mut lo[b])
+    }
+}
+
+/// Removes a codelet from the list of unscheduled codelets
+fn take_node(
+    nodes: &mut [Option<Box<dyn Node>>],
+    lookup: &HashMap<&str, usize>,
+    name: &str,
+    path: &str,
+) -> Result<Box<dyn Node>, PipelineError> {
+    let index = *lookup
+        .get(name)
+        .ok_or_else(|| PipelineError::new(path, PipelineErrorKind::UnknownCodelet(name.into())))?;
+    nodes[index]
+        .take()
+        .ok_or_else(|| PipelineError::new(path, PipelineErrorKind::ScheduledTwice(name.into())))
+}
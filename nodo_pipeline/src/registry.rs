@@ -0,0 +1,107 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use nodo::{
+    channels::{DynConnectError, RxBundle, TxBundle},
+    codelet::{Codelet, CodeletInstance, DynamicVise, IntoInstance},
+};
+use serde::de::DeserializeOwned;
+use std::{any::Any, collections::HashMap};
+
+type Factory = Box<dyn Fn(&str, serde_json::Value) -> Result<Box<dyn Node>, String> + Send + Sync>;
+
+/// Maps codelet type names used in pipeline documents to factory functions
+#[derive(Default)]
+pub struct CodeletRegistry {
+    factories: HashMap<String, Factory>,
+}
+
+impl CodeletRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a default-constructible codelet under the given type name
+    pub fn register_codelet<C>(&mut self, type_name: &str)
+    where
+        C: Codelet + Default + 'static,
+        C::Config: DeserializeOwned,
+    {
+        self.register_codelet_with(type_name, C::default);
+    }
+
+    /// Registers a codelet which is created with the given function
+    pub fn register_codelet_with<C, F>(&mut self, type_name: &str, f: F)
+    where
+        C: Codelet + 'static,
+        C::Config: DeserializeOwned,
+        F: Fn() -> C + Send + Sync + 'static,
+    {
+        self.factories.insert(
+            type_name.into(),
+            Box::new(move |name, config| {
+                let config: C::Config =
+                    serde_json::from_value(config).map_err(|err| err.to_string())?;
+                Ok(Box::new(f().into_instance(name, config)))
+            }),
+        );
+    }
+
+    /// Returns true if a codelet was registered under the given type name
+    pub fn contains(&self, type_name: &str) -> bool {
+        self.factories.contains_key(type_name)
+    }
+
+    pub(crate) fn create(
+        &self,
+        type_name: &str,
+        name: &str,
+        config: serde_json::Value,
+    ) -> Option<Result<Box<dyn Node>, String>> {
+        self.factories.get(type_name).map(|f| f(name, config))
+    }
+}
+
+/// Type-erased codelet instance used while building a pipeline
+pub(crate) trait Node {
+    fn rx_index(&self, channel: &str) -> Option<usize>;
+
+    fn tx_index(&self, channel: &str) -> Option<usize>;
+
+    fn rx_endpoint(&mut self, index: usize) -> Option<&mut dyn Any>;
+
+    fn connect(&mut self, tx_index: usize, rx: &mut dyn Any) -> Result<(), DynConnectError>;
+
+    fn connect_self(&mut self, tx_index: usize, rx_index: usize) -> Result<(), DynConnectError>;
+
+    fn into_vise(self: Box<Self>) -> DynamicVise;
+}
+
+impl<C: Codelet + 'static> Node for CodeletInstance<C> {
+    fn rx_index(&self, channel: &str) -> Option<usize> {
+        self.rx.index_of(channel)
+    }
+
+    fn tx_index(&self, channel: &str) -> Option<usize> {
+        self.tx.index_of(channel)
+    }
+
+    fn rx_endpoint(&mut self, index: usize) -> Option<&mut dyn Any> {
+        self.rx.endpoint_mut(index)
+    }
+
+    fn connect(&mut self, tx_index: usize, rx: &mut dyn Any) -> Result<(), DynConnectError> {
+        self.tx.connect_dyn(tx_index, rx)
+    }
+
+    fn connect_self(&mut self, tx_index: usize, rx_index: usize) -> Result<(), DynConnectError> {
+        let rx = self
+            .rx
+            .endpoint_mut(rx_index)
+            .ok_or(DynConnectError::NotSupported)?;
+        self.tx.connect_dyn(tx_index, rx)
+    }
+
+    fn into_vise(self: Box<Self>) -> DynamicVise {
+        DynamicVise::new(*self)
+    }
+}
@@ -0,0 +1,63 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use serde::{Deserialize, Serialize};
+
+/// A codelet instance in a pipeline document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CodeletSpec {
+    /// Name under which the codelet type was registered
+    #[serde(rename = "type")]
+    pub type_name: String,
+
+    /// Unique name of the instance
+    pub name: String,
+
+    /// Codelet configuration. A missing config is deserialized from `null`.
+    #[serde(default)]
+    pub config: serde_json::Value,
+}
+
+/// A connection from a TX channel to an RX channel, both given as `codelet.channel`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConnectionSpec {
+    pub tx: String,
+    pub rx: String,
+}
+
+/// A schedule in a pipeline document
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleSpec {
+    #[serde(default)]
+    pub name: String,
+
+    #[serde(default)]
+    pub thread_id: usize,
+
+    /// Period of the schedule in seconds
+    pub period: Option<f64>,
+
+    pub max_step_count: Option<usize>,
+
+    /// Codelets which are added as individual sequences
+    #[serde(default)]
+    pub codelets: Vec<String>,
+
+    #[serde(default)]
+    pub sequences: Vec<SequenceSpec>,
+}
+
+/// A sequence of codelets in a pipeline document
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SequenceSpec {
+    #[serde(default)]
+    pub name: String,
+
+    #[serde(default)]
+    pub auto_stop_on_closed: bool,
+
+    pub codelets: Vec<String>,
+}
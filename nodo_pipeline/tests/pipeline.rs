@@ -0,0 +1,222 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use nodo::prelude::*;
+use nodo_pipeline::{load_pipeline_str, CodeletRegistry, PipelineErrorKind};
+use nodo_runtime::Runtime;
+use serde::Deserialize;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+#[derive(Clone)]
+pub struct Ping(String);
+
+const NUM_MESSAGES: usize = 85;
+
+struct Alice {
+    num_sent: Arc<AtomicUsize>,
+}
+
+#[derive(TxBundleDerive)]
+struct AliceTx {
+    ping: DoubleBufferTx<Ping>,
+}
+
+impl Codelet for Alice {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = AliceTx;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (
+            (),
+            AliceTx {
+                ping: DoubleBufferTx::new(1),
+            },
+        )
+    }
+
+    fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        let num_sent = self.num_sent.load(Ordering::SeqCst);
+        tx.ping.push(Ping(format!("hello_{num_sent}")))?;
+        self.num_sent.store(num_sent + 1, Ordering::SeqCst);
+        SUCCESS
+    }
+}
+
+struct Bob {
+    num_recv: Arc<AtomicUsize>,
+}
+
+#[derive(Deserialize)]
+struct BobConfig {
+    queue_size: usize,
+}
+
+#[derive(RxBundleDerive)]
+struct BobRx {
+    ping: DoubleBufferRx<Ping>,
+}
+
+impl Codelet for Bob {
+    type Status = DefaultStatus;
+    type Config = BobConfig;
+    type Rx = BobRx;
+    type Tx = ();
+
+    fn build_bundles(config: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (
+            BobRx {
+                ping: DoubleBufferRx::new(
+                    OverflowPolicy::Reject(config.queue_size),
+                    RetentionPolicy::Drop,
+                ),
+            },
+            (),
+        )
+    }
+
+    fn step(&mut self, _: &Context<Self>, rx: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        let ping = rx.ping.pop()?;
+        let num_recv = self.num_recv.load(Ordering::SeqCst);
+        if ping.0 == format!("hello_{num_recv}") {
+            self.num_recv.store(num_recv + 1, Ordering::SeqCst);
+        }
+        SUCCESS
+    }
+}
+
+struct Counters {
+    sent: Arc<AtomicUsize>,
+    recv: Arc<AtomicUsize>,
+}
+
+fn registry() -> (CodeletRegistry, Counters) {
+    let counters = Counters {
+        sent: Arc::new(AtomicUsize::new(0)),
+        recv: Arc::new(AtomicUsize::new(0)),
+    };
+
+    let mut registry = CodeletRegistry::new();
+    let sent = counters.sent.clone();
+    registry.register_codelet_with("Alice", move || Alice {
+        num_sent: sent.clone(),
+    });
+    let recv = counters.recv.clone();
+    registry.register_codelet_with("Bob", move || Bob {
+        num_recv: recv.clone(),
+    });
+
+    (registry, counters)
+}
+
+/// Same setup as the `alice_bob_codelets` integration test in nodo
+const ALICE_BOB: &str = r#"{
+    "codelets": [
+        { "type": "Alice", "name": "alice" },
+        { "type": "Bob", "name": "bob", "config": { "queue_size": 1 } }
+    ],
+    "connections": [
+        { "tx": "alice.ping", "rx": "bob.ping" }
+    ],
+    "schedules": [
+        { "period": 0.002, "max_step_count": 85, "codelets": ["alice", "bob"] }
+    ]
+}"#;
+
+#[test]
+fn alice_bob_pipeline() {
+    let (registry, counters) = registry();
+
+    let schedules = load_pipeline_str(&registry, ALICE_BOB).unwrap();
+    assert_eq!(schedules.len(), 1);
+    assert_eq!(schedules[0].sequences.len(), 2);
+
+    let mut rt = Runtime::new();
+    for schedule in schedules {
+        rt.add_codelet_schedule(schedule.into());
+    }
+    rt.spin();
+
+    assert_eq!(counters.sent.load(Ordering::SeqCst), NUM_MESSAGES);
+    assert_eq!(counters.recv.load(Ordering::SeqCst), NUM_MESSAGES);
+}
+
+fn load_error(json: &str) -> (String, PipelineErrorKind) {
+    let (registry, _) = registry();
+    let err = load_pipeline_str(&registry, json).err().unwrap();
+    (err.path, err.kind)
+}
+
+#[test]
+fn pipeline_errors() {
+    let (path, kind) = load_error(
+        r#"{ "codelets": [ { "type": "Alice", "name": "a" }, { "type": "Carol", "name": "c" } ] }"#,
+    );
+    assert_eq!(path, "/codelets/1/type");
+    assert!(matches!(kind, PipelineErrorKind::UnknownType(t) if t == "Carol"));
+
+    let (path, kind) = load_error(
+        r#"{ "codelets": [ { "type": "Bob", "name": "b", "config": { "size": 1 } } ] }"#,
+    );
+    assert_eq!(path, "/codelets/0/config");
+    assert!(matches!(kind, PipelineErrorKind::InvalidConfig { .. }));
+
+    let (path, kind) = load_error(
+        r#"{
+            "codelets": [ { "type": "Alice", "name": "a" }, { "type": "Alice", "name": "a" } ]
+        }"#,
+    );
+    assert_eq!(path, "/codelets/1/name");
+    assert!(matches!(kind, PipelineErrorKind::DuplicateCodelet(_)));
+
+    let (path, kind) = load_error(
+        r#"{
+            "codelets": [
+                { "type": "Alice", "name": "a" },
+                { "type": "Bob", "name": "b", "config": { "queue_size": 1 } }
+            ],
+            "connections": [ { "tx": "a.ping", "rx": "b.pong" } ]
+        }"#,
+    );
+    assert_eq!(path, "/connections/0/rx");
+    assert!(matches!(kind, PipelineErrorKind::UnknownChannel { channel, .. } if channel == "pong"));
+
+    let (path, kind) = load_error(
+        r#"{
+            "codelets": [ { "type": "Alice", "name": "a" } ],
+            "connections": [ { "tx": "a", "rx": "b.ping" } ]
+        }"#,
+    );
+    assert_eq!(path, "/connections/0/tx");
+    assert!(matches!(kind, PipelineErrorKind::InvalidChannelPath(_)));
+
+    let (path, kind) = load_error(
+        r#"{
+            "codelets": [ { "type": "Alice", "name": "a" } ],
+            "connections": [ { "tx": "a.ping", "rx": "b.ping" } ]
+        }"#,
+    );
+    assert_eq!(path, "/connections/0/rx");
+    assert!(matches!(kind, PipelineErrorKind::UnknownCodelet(_)));
+
+    let (path, kind) = load_error(
+        r#"{
+            "codelets": [ { "type": "Alice", "name": "a" }, { "type": "Alice", "name": "b" } ],
+            "schedules": [ { "codelets": ["a"] } ]
+        }"#,
+    );
+    assert_eq!(path, "/codelets/1");
+    assert!(matches!(kind, PipelineErrorKind::NotScheduled(_)));
+
+    let (path, kind) = load_error(
+        r#"{
+            "codelets": [ { "type": "Alice", "name": "a" } ],
+            "schedules": [ { "codelets": ["a"], "sequences": [ { "codelets": ["a"] } ] } ]
+        }"#,
+    );
+    assert_eq!(path, "/schedules/0/sequences/0/codelets/0");
+    assert!(matches!(kind, PipelineErrorKind::ScheduledTwice(_)));
+}
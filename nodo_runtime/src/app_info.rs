@@ -0,0 +1,185 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use nodo_core::Capability;
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+/// Build and version information of the application running the runtime
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppInfo {
+    pub name: String,
+    pub version: String,
+    pub git_hash: Option<String>,
+    pub build_profile: String,
+
+    /// Time at which the application was started
+    pub start_time: SystemTime,
+
+    /// Optional integrations registered in this binary, see `nodo_core::capabilities`
+    pub capabilities: Vec<Capability>,
+}
+
+/// Creates an [AppInfo] from the `CARGO_PKG_*` variables of the calling crate
+///
+/// The git hash is taken from the `GIT_HASH` environment variable at compile time if it is set,
+/// for example by a build script.
+#[macro_export]
+macro_rules! app_info {
+    () => {
+        $crate::AppInfo::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+            .with_git_hash(option_env!("GIT_HASH"))
+            .with_build_profile(if cfg!(debug_assertions) {
+                "debug"
+            } else {
+                "release"
+            })
+    };
+}
+
+impl AppInfo {
+    pub fn new<S1: Into<String>, S2: Into<String>>(name: S1, version: S2) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+            git_hash: None,
+            build_profile: String::from(if cfg!(debug_assertions) {
+                "debug"
+            } else {
+                "release"
+            }),
+            start_time: SystemTime::now(),
+            capabilities: Vec::new(),
+        }
+    }
+
+    /// Info used when the application did not provide any: the name of the executable and an
+    /// unknown version
+    pub fn from_process() -> Self {
+        let name = std::env::current_exe()
+            .ok()
+            .and_then(|p| p.file_stem().map(|s| s.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| String::from("unknown"));
+        Self::new(name, "unknown")
+    }
+
+    #[must_use]
+    pub fn with_git_hash<S: Into<String>>(mut self, git_hash: Option<S>) -> Self {
+        self.git_hash = git_hash.map(Into::into);
+        self
+    }
+
+    #[must_use]
+    pub fn with_build_profile<S: Into<String>>(mut self, build_profile: S) -> Self {
+        self.build_profile = build_profile.into();
+        self
+    }
+
+    #[must_use]
+    pub fn with_capabilities(mut self, capabilities: Vec<Capability>) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Time since the application was started
+    pub fn uptime(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(self.start_time)
+            .unwrap_or_default()
+    }
+
+    /// Single line summary like `my_app 0.1.0 (a1b2c3d, release) up 1h02m03s`
+    pub fn summary(&self) -> String {
+        let build = match self.git_hash.as_ref() {
+            Some(hash) => format!("{hash}, {}", self.build_profile),
+            None => self.build_profile.clone(),
+        };
+        format!(
+            "{} {} ({build}) up {}",
+            self.name,
+            self.version,
+            format_uptime(self.uptime())
+        )
+    }
+
+    /// Comma separated list of capabilities like `nng 0.1.0, mcap 0.1.0`
+    pub fn capabilities_summary(&self) -> String {
+        self.capabilities
+            .iter()
+            .map(|c| format!("{} {}", c.name, c.version))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Formats a duration with second resolution like `2d03h04m05s`
+pub fn format_uptime(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, mins, secs) = (
+        secs / 86400,
+        (secs / 3600) % 24,
+        (secs / 60) % 60,
+        secs % 60,
+    );
+    if days > 0 {
+        format!("{days}d{hours:02}h{mins:02}m{secs:02}s")
+    } else if hours > 0 {
+        format!("{hours}h{mins:02}m{secs:02}s")
+    } else if mins > 0 {
+        format!("{mins}m{secs:02}s")
+    } else {
+        format!("{secs}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{decode_report, encode_report, format_uptime, AppInfo, InspectorReport};
+    use core::time::Duration;
+    use nodo_core::Capability;
+
+    #[test]
+    fn test_app_info_macro() {
+        let info = crate::app_info!();
+        assert_eq!(info.name, "nodo_runtime");
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(info.uptime() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_app_info_from_process() {
+        let info = AppInfo::from_process();
+        assert!(!info.name.is_empty());
+        assert_eq!(info.version, "unknown");
+        assert_eq!(info.git_hash, None);
+    }
+
+    #[test]
+    fn test_app_info_serialization() {
+        let info = AppInfo::new("robot", "1.2.3")
+            .with_git_hash(Some("a1b2c3d"))
+            .with_capabilities(vec![Capability {
+                name: "nng".into(),
+                version: "0.1.0".into(),
+            }]);
+        assert_eq!(info.capabilities_summary(), "nng 0.1.0");
+
+        let mut report = InspectorReport::default();
+        report.set_app_info(info.clone());
+
+        let buffer = encode_report(&report).unwrap();
+        let actual = decode_report(&buffer).unwrap();
+        assert_eq!(actual.app_info(), Some(&info));
+    }
+
+    #[test]
+    fn test_format_uptime() {
+        assert_eq!(format_uptime(Duration::from_secs(5)), "5s");
+        assert_eq!(format_uptime(Duration::from_secs(65)), "1m05s");
+        assert_eq!(format_uptime(Duration::from_secs(3723)), "1h02m03s");
+        assert_eq!(
+            format_uptime(Duration::from_secs(2 * 86400 + 3723)),
+            "2d01h02m03s"
+        );
+    }
+}
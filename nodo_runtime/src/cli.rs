@@ -0,0 +1,138 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::{LaunchMode, Runtime};
+use clap::Parser;
+use eyre::Result;
+use std::path::PathBuf;
+
+/// Default address of the inspector
+pub const DEFAULT_INSPECTOR_ADDRESS: &str = "tcp://localhost:54399";
+
+/// Standard command-line flags of nodo applications
+///
+/// Applications embed them with `#[command(flatten)]` and call [NodoArgs::apply] before schedules
+/// are added to the runtime.
+#[derive(Debug, Clone, PartialEq, Parser)]
+pub struct NodoArgs {
+    /// Address on which the inspector publishes reports
+    #[arg(long, default_value = DEFAULT_INSPECTOR_ADDRESS)]
+    pub inspector_address: String,
+
+    /// Disables the inspector
+    #[arg(long)]
+    pub no_inspector: bool,
+
+    /// Writes the final statistics as JSON to this file when the runtime stops
+    #[arg(long, value_name = "PATH")]
+    pub stats_json: Option<PathBuf>,
+
+    /// Maximum log level: off, error, warn, info, debug or trace
+    #[arg(long, default_value = "info")]
+    pub log_level: log::LevelFilter,
+
+    /// Runs start and stop of every codelet once instead of running the application
+    #[arg(long, conflicts_with = "list_codelets")]
+    pub dry_run: bool,
+
+    /// Prints all codelet instances and their channels instead of running the application
+    #[arg(long)]
+    pub list_codelets: bool,
+}
+
+impl NodoArgs {
+    pub fn launch_mode(&self) -> LaunchMode {
+        if self.list_codelets {
+            LaunchMode::ListCodelets
+        } else if self.dry_run {
+            LaunchMode::DryRun
+        } else {
+            LaunchMode::Run
+        }
+    }
+
+    /// Configures the runtime. The inspector is only enabled when the application runs.
+    ///
+    /// The log level is applied when the runtime spins. In dry-run mode failures are returned by
+    /// `Runtime::dry_run_result`.
+    pub fn apply(&self, rt: &mut Runtime) -> Result<()> {
+        rt.set_log_level(Some(self.log_level));
+
+        let mode = self.launch_mode();
+        rt.set_launch_mode(mode);
+        rt.set_stats_json_path(self.stats_json.clone());
+
+        if mode == LaunchMode::Run && !self.no_inspector {
+            rt.enable_inspector(&self.inspector_address)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LaunchMode, NodoArgs, Runtime, DEFAULT_INSPECTOR_ADDRESS};
+    use clap::Parser;
+    use std::path::Path;
+
+    #[derive(Parser)]
+    struct AppArgs {
+        #[arg(long, default_value_t = 1)]
+        robots: u32,
+
+        #[command(flatten)]
+        nodo: NodoArgs,
+    }
+
+    fn apply(argv: &[&str]) -> Runtime {
+        let args = NodoArgs::try_parse_from(argv).unwrap();
+        let mut rt = Runtime::new();
+        args.apply(&mut rt).unwrap();
+        rt
+    }
+
+    #[test]
+    fn test_defaults() {
+        let args = NodoArgs::try_parse_from(["app"]).unwrap();
+        assert_eq!(args.inspector_address, DEFAULT_INSPECTOR_ADDRESS);
+        assert!(!args.no_inspector);
+        assert_eq!(args.stats_json, None);
+        assert_eq!(args.log_level, log::LevelFilter::Info);
+        assert_eq!(args.launch_mode(), LaunchMode::Run);
+    }
+
+    #[test]
+    fn test_apply() {
+        let rt = apply(&["app", "--no-inspector", "--stats-json", "/tmp/stats.json"]);
+        assert_eq!(rt.launch_mode(), LaunchMode::Run);
+        assert!(!rt.is_inspector_enabled());
+        assert_eq!(rt.stats_json_path(), Some(Path::new("/tmp/stats.json")));
+
+        // the inspector is not opened when the application does not run
+        let rt = apply(&[
+            "app",
+            "--dry-run",
+            "--inspector-address",
+            "tcp://localhost:1",
+        ]);
+        assert_eq!(rt.launch_mode(), LaunchMode::DryRun);
+        assert!(!rt.is_inspector_enabled());
+
+        let rt = apply(&["app", "--list-codelets", "--log-level", "debug"]);
+        assert_eq!(rt.launch_mode(), LaunchMode::ListCodelets);
+        assert_eq!(rt.log_level(), Some(log::LevelFilter::Debug));
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        assert!(NodoArgs::try_parse_from(["app", "--log-level", "loud"]).is_err());
+        assert!(NodoArgs::try_parse_from(["app", "--dry-run", "--list-codelets"]).is_err());
+    }
+
+    #[test]
+    fn test_flatten() {
+        let args = AppArgs::try_parse_from(["app", "--robots", "3", "--no-inspector"]).unwrap();
+        assert_eq!(args.robots, 3);
+        assert!(args.nodo.no_inspector);
+    }
+}
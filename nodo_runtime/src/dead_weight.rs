@@ -0,0 +1,225 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::{InspectorCodeletReport, InspectorReport};
+use core::{cmp::Reverse, time::Duration};
+use nodo::codelet::{Transition, RARELY_ACTIVE_LABEL};
+use nodo_core::fmt_duration;
+use std::time::Instant;
+
+/// Observation window after which codelets which never executed a step are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObservationWindow {
+    /// Codelets are checked once each of them was stepped this many times
+    Steps(u64),
+
+    /// Codelets are checked once the runtime was running for this long
+    Duration(Duration),
+}
+
+/// Configuration of the dead weight detection, see `Runtime::with_dead_weight_detection`
+///
+/// Codelets which skipped every step during the observation window are marked as suspected
+/// inactive. They still cost sync and flush overhead every cycle and might be leftovers.
+#[derive(Debug, Clone)]
+pub struct DeadWeightConfig {
+    pub window: ObservationWindow,
+}
+
+impl Default for DeadWeightConfig {
+    fn default() -> Self {
+        Self {
+            window: ObservationWindow::Steps(1000),
+        }
+    }
+}
+
+/// Marks suspected inactive codelets in reports and logs a summary once
+pub(crate) struct DeadWeightDetector {
+    config: DeadWeightConfig,
+    start: Instant,
+    is_reported: bool,
+}
+
+impl DeadWeightDetector {
+    pub fn new(config: DeadWeightConfig) -> Self {
+        Self {
+            config,
+            start: Instant::now(),
+            is_reported: false,
+        }
+    }
+
+    pub fn is_reported(&self) -> bool {
+        self.is_reported
+    }
+
+    /// Sets `suspected_inactive` for all codelets in the report
+    pub fn mark(&self, report: &mut InspectorReport) {
+        let elapsed = self.start.elapsed();
+        for entry in report.codelets.values_mut() {
+            entry.suspected_inactive = is_suspected_inactive(entry, self.config.window, elapsed);
+        }
+    }
+
+    /// Logs a summary of all suspected inactive codelets in a marked report once the observation
+    /// window is over
+    pub fn check(&mut self, report: &InspectorReport) {
+        if self.is_reported || !is_window_over(report, self.config.window, self.start.elapsed()) {
+            return;
+        }
+        self.is_reported = true;
+
+        if let Some(summary) = dead_weight_summary(report) {
+            log::warn!("{summary}");
+        }
+    }
+}
+
+fn is_suspected_inactive(
+    entry: &InspectorCodeletReport,
+    window: ObservationWindow,
+    elapsed: Duration,
+) -> bool {
+    if entry.labels.iter().any(|l| l == RARELY_ACTIVE_LABEL) {
+        return false;
+    }
+
+    let step = &entry.statistics.transitions[Transition::Step];
+    step.duration.count() == 0
+        && match window {
+            ObservationWindow::Steps(n) => step.skipped_count >= n,
+            ObservationWindow::Duration(d) => elapsed >= d && step.skipped_count > 0,
+        }
+}
+
+fn is_window_over(report: &InspectorReport, window: ObservationWindow, elapsed: Duration) -> bool {
+    match window {
+        ObservationWindow::Steps(n) => report.iter().all(|(_, entry)| {
+            let step = &entry.statistics.transitions[Transition::Step];
+            step.skipped_count + step.duration.count() >= n
+        }),
+        ObservationWindow::Duration(d) => elapsed >= d,
+    }
+}
+
+/// Lists all codelets marked as suspected inactive together with the time spent in their skipped
+/// steps. Returns None if there are none.
+pub fn dead_weight_summary(report: &InspectorReport) -> Option<String> {
+    let mut entries = report
+        .iter()
+        .map(|(_, entry)| entry)
+        .filter(|entry| entry.suspected_inactive)
+        .map(|entry| (entry, &entry.statistics.transitions[Transition::Step]))
+        .collect::<Vec<_>>();
+    if entries.is_empty() {
+        return None;
+    }
+    entries.sort_by_key(|(entry, step)| (Reverse(step.total_duration()), &entry.name));
+
+    let total = entries.iter().map(|(_, step)| step.total_duration()).sum();
+    let mut summary = format!(
+        "{} codelet(s) never executed a step during the observation window (total overhead {}):",
+        entries.len(),
+        fmt_duration(total)
+    );
+    for (entry, step) in entries {
+        summary += &format!(
+            "\n  {}/{} ({}): {} skipped steps, overhead {}",
+            entry.sequence,
+            entry.name,
+            entry.typename,
+            step.skipped_count,
+            fmt_duration(step.total_duration())
+        );
+    }
+    summary += &format!(
+        "\nAdd the label '{RARELY_ACTIVE_LABEL}' to codelets which are legitimately rarely active."
+    );
+    Some(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        dead_weight::is_window_over, dead_weight_summary, DeadWeightConfig, DeadWeightDetector,
+        ObservationWindow, ScheduleExecutor,
+    };
+    use core::time::Duration;
+    use nodo::{
+        codelet::{
+            Clocks, NodeletId, NodeletSetup, ScheduleBuilder, WorkerId, RARELY_ACTIVE_LABEL,
+        },
+        prelude::*,
+    };
+
+    struct Probe {
+        is_active: bool,
+    }
+
+    impl Codelet for Probe {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = ();
+        type Tx = ();
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            ((), ())
+        }
+
+        fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            if self.is_active {
+                SUCCESS
+            } else {
+                SKIPPED
+            }
+        }
+    }
+
+    #[test]
+    fn test_dead_weight() {
+        let mut schedule = ScheduleExecutor::from(
+            ScheduleBuilder::new()
+                .with_name("dw")
+                .with(Probe { is_active: true }.into_instance("active", ()))
+                .with(Probe { is_active: false }.into_instance("idle", ()))
+                .with(
+                    Probe { is_active: false }
+                        .into_instance("rare", ())
+                        .with_label(RARELY_ACTIVE_LABEL),
+                ),
+        );
+        schedule.setup(NodeletSetup {
+            clocks: Clocks::new(),
+            nodelet_id_issue: NodeletId(WorkerId(0), 0),
+        });
+
+        let window = ObservationWindow::Steps(5);
+        let detector = DeadWeightDetector::new(DeadWeightConfig { window });
+
+        // start and four steps
+        for _ in 0..5 {
+            schedule.spin();
+        }
+        assert!(!is_window_over(&schedule.report(), window, Duration::ZERO));
+
+        schedule.spin();
+        let mut report = schedule.report();
+        assert!(is_window_over(&report, window, Duration::ZERO));
+        detector.mark(&mut report);
+
+        let flags = report
+            .iter()
+            .map(|(_, entry)| (entry.name.as_str(), entry.suspected_inactive))
+            .collect::<std::collections::BTreeMap<_, _>>();
+        assert!(!flags["active"]);
+        assert!(flags["idle"]);
+        assert!(!flags["rare"]);
+
+        let summary = dead_weight_summary(&report).unwrap();
+        assert!(summary.starts_with("1 codelet(s) never executed a step"));
+        assert!(summary.contains("/idle ("));
+        assert!(summary.contains("Probe): 5 skipped steps, overhead "));
+        assert!(!summary.contains("/active"));
+        assert!(!summary.contains("/rare"));
+    }
+}
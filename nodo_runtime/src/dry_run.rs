@@ -0,0 +1,84 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use nodo::codelet::Transition;
+use nodo_core::DefaultStatus;
+use std::fmt;
+
+/// Outcome of a single codelet transition executed during a dry run
+#[derive(Debug, Clone)]
+pub struct DryRunTransition {
+    pub transition: Transition,
+    pub duration: Duration,
+
+    /// The status returned by the codelet or the error message if it failed
+    pub result: Result<DefaultStatus, String>,
+}
+
+/// Dry run outcomes of a single codelet
+#[derive(Debug, Clone)]
+pub struct DryRunCodeletReport {
+    pub sequence: String,
+    pub name: String,
+    pub type_name: String,
+
+    /// Executed transitions in order. After a failure only stop is executed, and only if the
+    /// codelet was started.
+    pub transitions: Vec<DryRunTransition>,
+}
+
+impl DryRunCodeletReport {
+    /// The failed transition and its error message, if any
+    pub fn error(&self) -> Option<(Transition, &str)> {
+        self.transitions
+            .iter()
+            .find_map(|t| t.result.as_ref().err().map(|e| (t.transition, e.as_str())))
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.error().is_none()
+    }
+}
+
+/// Result of a dry run of a schedule, see `ScheduleExecutor::dry_run`
+#[derive(Debug, Clone)]
+pub struct DryRunReport {
+    pub schedule: String,
+    pub codelets: Vec<DryRunCodeletReport>,
+}
+
+impl DryRunReport {
+    /// Codelets which failed during the dry run
+    pub fn failures(&self) -> impl Iterator<Item = &DryRunCodeletReport> {
+        self.codelets.iter().filter(|c| !c.is_ok())
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.failures().next().is_none()
+    }
+}
+
+/// Error returned by `Runtime::dry_run_all` if any codelet failed. Contains the reports of all
+/// schedules including codelets which succeeded.
+#[derive(Debug, thiserror::Error)]
+pub struct DryRunError {
+    pub reports: Vec<DryRunReport>,
+}
+
+impl fmt::Display for DryRunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "dry run failed:")?;
+        for report in self.reports.iter() {
+            for codelet in report.failures() {
+                if let Some((transition, error)) = codelet.error() {
+                    write!(
+                        f,
+                        "\n  codelet '{}' (schedule '{}', sequence '{}') failed during {transition:?}: {error}",
+                        codelet.name, report.schedule, codelet.sequence
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,39 +1,346 @@
 // Copyright 2023 by David Weikersdorfer. All rights reserved.
 
-use crate::{accurate_sleep_until, InspectorReport, ScheduleExecutor};
-use nodo::codelet::{Clocks, NodeletId, NodeletSetup, WorkerId};
+use crate::{
+    ChannelTaps, InFlightCodelet, InspectorReport, Manifold, Reaper, ScheduleExecutor, Snapshot,
+    StepMode, Teardown, TeardownConfig,
+};
+use core::time::Duration;
+use nodo::codelet::{
+    Clocks, FailurePlan, NodeletId, NodeletSetup, ResourceRegistry, Transition, WorkerId,
+};
+use nodo_core::TimeStandardConfig;
+use std::{
+    any::Any,
+    backtrace::Backtrace,
+    cell::RefCell,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex, Once},
+    time::Instant,
+};
 
 pub struct Executor {
     next_worker_id: WorkerId,
     clocks: Clocks,
     workers: Vec<Worker>,
+    manifold: Manifold,
+    taps: ChannelTaps,
+    snapshot: Option<(Arc<Mutex<Snapshot>>, Option<Duration>)>,
+    stop_wave_timeout: Duration,
+    start_barrier: StartBarrier,
+    resources: Arc<ResourceRegistry>,
+    teardown: Teardown,
 }
 
+/// State of the optional barrier between the start and the first step of all schedules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StartBarrier {
+    Disabled,
+    Pending,
+    Released,
+}
+
+/// Default time to wait for the schedules of one stop wave to finish, see
+/// [Executor::request_stop]
+pub const DEFAULT_STOP_WAVE_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub enum WorkerRequest {
     Stop,
     Report,
+
+    /// Allows a worker waiting at the start barrier to begin stepping
+    BeginSteps,
+
+    /// Changes how the schedule triggers steps
+    SetStepMode(StepMode),
+
+    /// Executes exactly one step of a schedule in manual step mode
+    StepOnce,
+
+    /// Arms or disarms the failure plan of a codelet, see [FailurePlan]
+    SetFailurePlan {
+        codelet: String,
+        plan: Option<FailurePlan>,
+    },
 }
 
 pub enum WorkerReply {
-    Report(InspectorReport),
+    Report(Box<InspectorReport>),
+
+    /// Sent by a worker at the start barrier once its schedule executed the start transition
+    Started(eyre::Result<()>),
 }
 
 pub struct WorkerState {
     schedule: ScheduleExecutor,
     rx_request: std::sync::mpsc::Receiver<WorkerRequest>,
     tx_reply: std::sync::mpsc::Sender<WorkerReply>,
+    snapshot: Option<WorkerSnapshot>,
+    start_barrier: bool,
+}
+
+/// Saves states of persisted codelets of a worker into the shared snapshot
+struct WorkerSnapshot {
+    snapshot: Arc<Mutex<Snapshot>>,
+    interval: Option<Duration>,
+    last_save: Instant,
+}
+
+impl WorkerSnapshot {
+    fn save(&mut self, schedule: &ScheduleExecutor) {
+        schedule.save_snapshot(&mut self.snapshot.lock().unwrap());
+        self.last_save = Instant::now();
+    }
+
+    fn save_periodic(&mut self, schedule: &ScheduleExecutor) {
+        if self
+            .interval
+            .is_some_and(|interval| self.last_save.elapsed() >= interval)
+        {
+            self.save(schedule);
+        }
+    }
+}
+
+/// Information about a panic which terminated a worker thread
+#[derive(Debug, Clone)]
+pub struct WorkerPanic {
+    /// Name of the worker (i.e. the schedule)
+    pub worker: String,
+
+    /// Panic message
+    pub message: String,
+
+    /// Backtrace captured at the location of the panic
+    pub backtrace: Option<String>,
+
+    /// The codelet which was executing when the panic happened
+    pub in_flight: Option<InFlightCodelet>,
+}
+
+impl fmt::Display for WorkerPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "worker '{}' panicked", self.worker)?;
+        if let Some(c) = self.in_flight.as_ref() {
+            write!(
+                f,
+                " in codelet '{}' (sequence '{}') during {:?}",
+                c.codelet, c.sequence, c.transition
+            )?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(backtrace) = self.backtrace.as_ref() {
+            write!(f, "\n{backtrace}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Error returned when joining workers of which some panicked
+#[derive(Debug, thiserror::Error)]
+#[error("{} worker(s) panicked:\n{}", panics.len(),
+    panics.iter().map(|p| p.to_string()).collect::<Vec<_>>().join("\n"))]
+pub struct WorkerJoinError {
+    pub panics: Vec<WorkerPanic>,
+}
+
+/// Error returned when releasing the start barrier if some schedules failed to start
+#[derive(Debug, thiserror::Error)]
+#[error("{} schedule(s) failed to start:\n{}", failures.len(),
+    failures.iter().map(|(name, err)| format!("schedule '{name}': {err:?}"))
+        .collect::<Vec<_>>().join("\n"))]
+pub struct StartBarrierError {
+    /// Name of the schedule and the error with which it failed to start
+    pub failures: Vec<(String, eyre::Report)>,
+}
+
+#[derive(Default)]
+struct PanicCapture {
+    is_enabled: bool,
+    backtrace: Option<String>,
+}
+
+thread_local! {
+    static PANIC_CAPTURE: RefCell<PanicCapture> = RefCell::new(PanicCapture::default());
+}
+
+/// Installs a panic hook which captures backtraces on worker threads. The previous hook is still
+/// called afterwards.
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            // Never panic inside the hook as that would abort the process
+            let _ = PANIC_CAPTURE.try_with(|capture| {
+                if let Ok(mut capture) = capture.try_borrow_mut() {
+                    if capture.is_enabled && capture.backtrace.is_none() {
+                        capture.backtrace = Some(Backtrace::force_capture().to_string());
+                    }
+                }
+            });
+            previous(info);
+        }));
+    });
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        String::from("<non-string panic payload>")
+    }
 }
 
 impl Executor {
     pub fn new() -> Self {
+        install_panic_hook();
+
         Self {
             next_worker_id: WorkerId(0),
             clocks: Clocks::new(),
             workers: Vec::new(),
+            manifold: Manifold::default(),
+            taps: ChannelTaps::default(),
+            snapshot: None,
+            stop_wave_timeout: DEFAULT_STOP_WAVE_TIMEOUT,
+            start_barrier: StartBarrier::Disabled,
+            resources: Arc::default(),
+            teardown: Teardown::default(),
         }
     }
 
+    /// If enabled all schedules execute their start transition and then wait until
+    /// [Executor::release_start_barrier] is called before they step. Must be called before
+    /// schedules are pushed.
+    pub fn set_start_barrier(&mut self, enable: bool) {
+        assert!(
+            self.workers.is_empty(),
+            "the start barrier must be configured before schedules are added"
+        );
+        self.start_barrier = if enable {
+            StartBarrier::Pending
+        } else {
+            StartBarrier::Disabled
+        };
+    }
+
+    /// Waits until all schedules executed their start transition and allows them to step
+    ///
+    /// Does nothing unless the start barrier is enabled. If any schedule failed to start no
+    /// schedule steps and an error with all failures is returned. Stopping the schedules is left to
+    /// the caller. Only the first call has an effect.
+    pub fn release_start_barrier(&mut self) -> Result<(), StartBarrierError> {
+        if self.start_barrier != StartBarrier::Pending {
+            return Ok(());
+        }
+        self.start_barrier = StartBarrier::Released;
+
+        let failures = self
+            .workers
+            .iter_mut()
+            .filter_map(|w| w.wait_started().err().map(|err| (w.name.clone(), err)))
+            .collect::<Vec<_>>();
+        if !failures.is_empty() {
+            return Err(StartBarrierError { failures });
+        }
+
+        log::debug!("all schedules started, releasing start barrier");
+        for worker in self.workers.iter() {
+            worker.tx_request.send(WorkerRequest::BeginSteps).ok();
+        }
+        Ok(())
+    }
+
+    /// Sets the step mode of the schedule with the given name, see [StepMode]
+    pub fn set_step_mode(&self, schedule: &str, mode: StepMode) -> eyre::Result<()> {
+        self.worker(schedule)?
+            .send_request(WorkerRequest::SetStepMode(mode))
+    }
+
+    /// Triggers a single step of the schedule with the given name if it is in manual step mode
+    pub fn step_once(&self, schedule: &str) -> eyre::Result<()> {
+        self.worker(schedule)?.send_request(WorkerRequest::StepOnce)
+    }
+
+    /// Arms or disarms the failure plan of a codelet in the schedule with the given name
+    pub fn set_failure_plan(
+        &self,
+        schedule: &str,
+        codelet: &str,
+        plan: Option<FailurePlan>,
+    ) -> eyre::Result<()> {
+        self.worker(schedule)?
+            .send_request(WorkerRequest::SetFailurePlan {
+                codelet: codelet.into(),
+                plan,
+            })
+    }
+
+    fn worker(&self, schedule: &str) -> eyre::Result<&Worker> {
+        self.workers
+            .iter()
+            .find(|w| w.name == schedule)
+            .ok_or_else(|| eyre::eyre!("unknown schedule '{schedule}'"))
+    }
+
+    /// Enables conversions to TAI and GPS time with the time anchor of schedules pushed afterwards
+    pub fn set_time_standards(&mut self, config: TimeStandardConfig) {
+        self.clocks.anchor = self.clocks.anchor.with_time_standards(config);
+    }
+
+    /// Sets how long [Executor::request_stop] waits for the schedules of one stop wave before it
+    /// stops all remaining schedules in parallel
+    pub fn set_stop_wave_timeout(&mut self, timeout: Duration) {
+        self.stop_wave_timeout = timeout;
+    }
+
+    /// Restores persisted codelets of schedules pushed afterwards from the snapshot and saves
+    /// their state into it when they stop and optionally every `interval`
+    pub fn set_snapshot(&mut self, snapshot: Arc<Mutex<Snapshot>>, interval: Option<Duration>) {
+        self.snapshot = Some((snapshot, interval));
+    }
+
+    /// Resources shared by codelets, see `Codelet::acquire_resources`
+    ///
+    /// Fails if schedules were already added as the registry is read-only afterwards.
+    pub fn resources_mut(&mut self) -> eyre::Result<&mut ResourceRegistry> {
+        Arc::get_mut(&mut self.resources)
+            .ok_or_else(|| eyre::eyre!("resources must be inserted before schedules are added"))
+    }
+
+    /// Sets how messages still queued in receivers are freed when schedules pushed afterwards
+    /// stop, see [TeardownConfig]
+    pub fn set_teardown(&mut self, config: TeardownConfig) {
+        if config.deferred_drop && self.teardown.reaper.is_none() {
+            self.teardown.reaper = Some(Reaper::new());
+        }
+        self.teardown.config = config;
+    }
+
     pub fn push(&mut self, mut schedule: ScheduleExecutor) {
+        self.setup(&mut schedule);
+
+        let snapshot = self.snapshot.as_ref().map(|(snapshot, interval)| {
+            schedule.restore_snapshot(&snapshot.lock().unwrap());
+            WorkerSnapshot {
+                snapshot: snapshot.clone(),
+                interval: *interval,
+                last_save: Instant::now(),
+            }
+        });
+
+        self.workers.push(Worker::new(
+            schedule,
+            snapshot,
+            self.start_barrier == StartBarrier::Pending,
+        ));
+    }
+
+    /// Assigns IDs and clocks to all codelets of the schedule and registers them in the manifold
+    pub(crate) fn setup(&mut self, schedule: &mut ScheduleExecutor) {
         let worker_id = self.next_worker_id;
         self.next_worker_id.0 += 1;
 
@@ -41,69 +348,218 @@ impl Executor {
             clocks: self.clocks.clone(),
             nodelet_id_issue: NodeletId(worker_id, 0),
         });
+        schedule.set_resources(self.resources.clone());
+        schedule.set_teardown(self.teardown.clone());
+        schedule.register(&mut self.manifold, &mut self.taps);
+    }
+
+    /// All codelet instances which were added to the executor
+    pub fn manifold(&self) -> &Manifold {
+        &self.manifold
+    }
 
-        self.workers.push(Worker::new(schedule));
+    /// RX channels of all codelet instances which can be snooped
+    pub fn channel_taps(&self) -> &ChannelTaps {
+        &self.taps
     }
 
     pub fn is_finished(&self) -> bool {
         self.workers.iter().all(|w| w.is_finished())
     }
 
-    pub fn join(&mut self) {
-        for w in self.workers.iter_mut() {
-            w.join()
-                .map_err(|err| {
-                    log::error!(
-                        "Could not join thread of worker '{}': {err:?}. Maybe it panicked previously.",
-                        w.name
-                    )
-                })
-                .ok();
+    /// Waits for all workers to finish and collects panics of worker threads
+    pub fn join(&mut self) -> Result<(), WorkerJoinError> {
+        let panics = self
+            .workers
+            .iter_mut()
+            .filter_map(|w| w.join())
+            .collect::<Vec<_>>();
+        for p in panics.iter() {
+            log::error!("{p}");
+        }
+        if panics.is_empty() {
+            Ok(())
+        } else {
+            Err(WorkerJoinError { panics })
         }
     }
 
+    /// Stops all workers in waves such that schedules stop before the schedules they depend on
+    ///
+    /// Without declared dependencies schedules are stopped in reverse insertion order. If the
+    /// schedules of a wave do not finish within the stop wave timeout all remaining schedules are
+    /// stopped in parallel.
     pub fn request_stop(&mut self) {
-        for w in self.workers.iter() {
-            w.tx_request
-                .send(WorkerRequest::Stop)
-                .map_err(|err| {
-                    log::error!(
-                        "Could not request worker '{}' to stop: {err:?}. Maybe it panicked previously.",
-                        w.name
-                    )
-                })
-                .ok();
+        let waves = stop_waves(
+            &self
+                .workers
+                .iter()
+                .map(|w| (w.name.as_str(), w.start_after.as_slice()))
+                .collect::<Vec<_>>(),
+        );
+        log::debug!(
+            "stop plan: {}",
+            waves
+                .iter()
+                .map(|wave| format!("[{}]", self.worker_names(wave).join(", ")))
+                .collect::<Vec<_>>()
+                .join(" -> ")
+        );
+
+        for (k, wave) in waves.iter().enumerate() {
+            for &i in wave.iter() {
+                self.workers[i].request_stop();
+            }
+
+            // no need to wait for the last wave
+            if k + 1 == waves.len() {
+                break;
+            }
+
+            let deadline = Instant::now() + self.stop_wave_timeout;
+            while !wave.iter().all(|&i| self.workers[i].is_finished()) {
+                if Instant::now() >= deadline {
+                    log::warn!(
+                        "schedule(s) {} did not stop within {:?}, stopping remaining schedules in \
+                         parallel",
+                        self.worker_names(wave).join(", "),
+                        self.stop_wave_timeout
+                    );
+                    for &i in waves[k + 1..].iter().flatten() {
+                        self.workers[i].request_stop();
+                    }
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(1));
+            }
         }
     }
 
+    fn worker_names(&self, indices: &[usize]) -> Vec<&str> {
+        indices
+            .iter()
+            .map(|&i| self.workers[i].name.as_str())
+            .collect()
+    }
+
     pub fn report(&self) -> InspectorReport {
         let mut result = InspectorReport::default();
-        for w in self.workers.iter() {
-            result.extend(w.report());
+        for (_, report) in self.schedule_reports() {
+            result.extend(report);
         }
         result
     }
+
+    /// Reports of all workers tagged with the name of their schedule
+    pub fn schedule_reports(&self) -> Vec<(String, InspectorReport)> {
+        self.workers
+            .iter()
+            .map(|w| (w.name.clone(), w.report()))
+            .collect()
+    }
+}
+
+/// Groups schedules given by name and dependencies into waves in which they are stopped
+///
+/// Schedules are stopped in the reverse order in which they would be started, i.e. a schedule
+/// stops before all schedules it depends on. Without any dependencies schedules are stopped one
+/// by one in reverse order. Schedules in a dependency cycle are stopped in the first wave.
+fn stop_waves(schedules: &[(&str, &[String])]) -> Vec<Vec<usize>> {
+    if schedules.iter().all(|(_, deps)| deps.is_empty()) {
+        return (0..schedules.len()).rev().map(|i| vec![i]).collect();
+    }
+
+    for (name, deps) in schedules.iter() {
+        for dep in deps.iter() {
+            if !schedules.iter().any(|(other, _)| other == dep) {
+                log::warn!("schedule '{name}' depends on unknown schedule '{dep}'");
+            }
+        }
+    }
+
+    // startup level: schedules start after all schedules they depend on
+    let mut levels: Vec<Option<usize>> = vec![None; schedules.len()];
+    loop {
+        let mut is_changed = false;
+        for (i, (_, deps)) in schedules.iter().enumerate() {
+            if levels[i].is_some() {
+                continue;
+            }
+            let level = schedules
+                .iter()
+                .enumerate()
+                .filter(|(_, (other, _))| deps.iter().any(|dep| dep == other))
+                .try_fold(0, |level, (j, _)| levels[j].map(|l| level.max(l + 1)));
+            if level.is_some() {
+                levels[i] = level;
+                is_changed = true;
+            }
+        }
+        if !is_changed {
+            break;
+        }
+    }
+
+    let max_level = levels.iter().flatten().copied().max().unwrap_or_default();
+    let cycle = (0..schedules.len())
+        .filter(|&i| levels[i].is_none())
+        .collect::<Vec<_>>();
+    if !cycle.is_empty() {
+        log::warn!(
+            "schedules {} have cyclic dependencies",
+            cycle
+                .iter()
+                .map(|&i| schedules[i].0)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    Some(cycle)
+        .into_iter()
+        .chain((0..=max_level).rev().map(|level| {
+            (0..schedules.len())
+                .filter(|&i| levels[i] == Some(level))
+                .collect()
+        }))
+        .filter(|wave: &Vec<usize>| !wave.is_empty())
+        .collect()
 }
 
 pub struct Worker {
     name: String,
-    thread: Option<std::thread::JoinHandle<()>>,
+    start_after: Vec<String>,
+    thread: Option<std::thread::JoinHandle<Option<WorkerPanic>>>,
     tx_request: std::sync::mpsc::Sender<WorkerRequest>,
     rx_reply: std::sync::mpsc::Receiver<WorkerReply>,
+
+    /// Last report sent by the worker thread before it terminated, kept after join
+    final_report: Option<InspectorReport>,
+
+    /// Start result sent by the worker at the start barrier which was not yet consumed
+    started: Mutex<Option<eyre::Result<()>>>,
 }
 
 impl Worker {
-    fn new(schedule: ScheduleExecutor) -> Self {
+    fn new(
+        schedule: ScheduleExecutor,
+        snapshot: Option<WorkerSnapshot>,
+        start_barrier: bool,
+    ) -> Self {
         let (tx_request, rx_request) = std::sync::mpsc::channel();
         let (tx_reply, rx_reply) = std::sync::mpsc::channel();
         let name = schedule.name().to_string();
+        let start_after = schedule.start_after().to_vec();
         let state = WorkerState {
             schedule,
             rx_request,
             tx_reply,
+            snapshot,
+            start_barrier,
         };
         Self {
             name: name.clone(),
+            start_after,
             thread: Some(
                 std::thread::Builder::new()
                     .name(name)
@@ -112,67 +568,446 @@ impl Worker {
             ),
             tx_request,
             rx_reply,
+            final_report: None,
+            started: Mutex::new(None),
         }
     }
 
+    /// Waits until the worker executed the start transition at the start barrier
+    fn wait_started(&mut self) -> eyre::Result<()> {
+        loop {
+            if let Some(result) = self.started.lock().unwrap().take() {
+                return result;
+            }
+            match self.rx_reply.recv() {
+                Ok(WorkerReply::Started(result)) => return result,
+                Ok(WorkerReply::Report(report)) => self.final_report = Some(*report),
+                Err(_) => eyre::bail!("worker terminated before it started"),
+            }
+        }
+    }
+
+    fn send_request(&self, request: WorkerRequest) -> eyre::Result<()> {
+        self.tx_request
+            .send(request)
+            .map_err(|_| eyre::eyre!("worker '{}' terminated", self.name))
+    }
+
+    fn request_stop(&self) {
+        self.tx_request
+            .send(WorkerRequest::Stop)
+            .map_err(|err| {
+                log::error!(
+                    "Could not request worker '{}' to stop: {err:?}. Maybe it panicked previously.",
+                    self.name
+                )
+            })
+            .ok();
+    }
+
     fn is_finished(&self) -> bool {
         self.thread.as_ref().map_or(true, |h| h.is_finished())
     }
 
-    fn join(&mut self) -> Result<(), ()> {
-        if let Some(thread) = self.thread.take() {
-            thread.join().map_err(|_| ())
-        } else {
-            Ok(())
+    /// Waits for the worker thread to finish and returns the panic which terminated it, if any
+    fn join(&mut self) -> Option<WorkerPanic> {
+        let result = match self.thread.take().map(|thread| thread.join()) {
+            None => return None,
+            Some(Ok(maybe_panic)) => maybe_panic,
+            // the thread panicked outside of the capture, e.g. while dropping codelets
+            Some(Err(payload)) => Some(WorkerPanic {
+                worker: self.name.clone(),
+                message: panic_message(&*payload),
+                backtrace: None,
+                in_flight: None,
+            }),
+        };
+
+        // keep the final report such that statistics are available after the run
+        while let Ok(reply) = self.rx_reply.try_recv() {
+            if let WorkerReply::Report(report) = reply {
+                self.final_report = Some(*report);
+            }
+        }
+
+        result
+    }
+
+    fn worker_thread(mut state: WorkerState) -> Option<WorkerPanic> {
+        PANIC_CAPTURE.with(|c| c.borrow_mut().is_enabled = true);
+        state.schedule.monitor_thread();
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| Self::worker_loop(&mut state)));
+
+        let outcome = match result {
+            Ok(()) => None,
+            Err(payload) => {
+                let panic = WorkerPanic {
+                    worker: state.schedule.name().to_string(),
+                    message: panic_message(&*payload),
+                    backtrace: PANIC_CAPTURE.with(|c| c.borrow_mut().backtrace.take()),
+                    in_flight: state.schedule.in_flight(),
+                };
+                // dropping the payload may panic again
+                let _ = panic::catch_unwind(AssertUnwindSafe(move || drop(payload)));
+                Some(panic)
+            }
+        };
+
+        // the schedule may be in an inconsistent state after a panic
+        if let Ok(report) = panic::catch_unwind(AssertUnwindSafe(|| state.schedule.report())) {
+            state
+                .tx_reply
+                .send(WorkerReply::Report(Box::new(report)))
+                .ok();
         }
+
+        outcome
     }
 
-    fn worker_thread(mut state: WorkerState) {
+    fn worker_loop(state: &mut WorkerState) {
+        if !state.start_barrier || Self::start_at_barrier(state) {
+            Self::spin_loop(state);
+        }
+
+        state.schedule.finalize();
+
+        // not reached if the worker panicked as the state might be inconsistent
+        if let Some(snapshot) = state.snapshot.as_mut() {
+            snapshot.save(&state.schedule);
+        }
+
+        state.schedule.teardown();
+    }
+
+    /// Executes the start transition and waits until the executor releases the start barrier.
+    /// Returns false if the worker was requested to stop instead.
+    fn start_at_barrier(state: &mut WorkerState) -> bool {
+        let result = state.schedule.start();
+        state.tx_reply.send(WorkerReply::Started(result)).ok();
+
         loop {
-            // Wait until next period. Be careful not to hold a lock on state while sleeping.
-            let maybe_next_instant = {
-                if let Some(period) = state.schedule.period() {
-                    state.schedule.last_instant().map(|t| t + period)
-                } else {
-                    None
+            match state.rx_request.recv() {
+                Ok(WorkerRequest::BeginSteps) => return true,
+                Ok(request) => {
+                    if !Self::handle_request(state, request) {
+                        return false;
+                    }
                 }
-            };
-            if let Some(next_instant) = maybe_next_instant {
-                accurate_sleep_until(next_instant);
+                Err(_) => return false,
             }
+        }
+    }
 
-            // handle requests
-            match state.rx_request.try_recv() {
-                Ok(WorkerRequest::Stop) => break,
-                Ok(WorkerRequest::Report) => state
-                    .tx_reply
-                    .send(WorkerReply::Report(state.schedule.report()))
-                    .unwrap(),
-                Err(_) => {
-                    // FIXME
+    /// Handles a request which does not trigger a step. Returns false if the worker shall stop.
+    fn handle_request(state: &mut WorkerState, request: WorkerRequest) -> bool {
+        match request {
+            WorkerRequest::Stop => return false,
+            WorkerRequest::Report => state
+                .tx_reply
+                .send(WorkerReply::Report(Box::new(state.schedule.report())))
+                .unwrap(),
+            WorkerRequest::SetStepMode(mode) => state.schedule.set_step_mode(mode),
+            WorkerRequest::StepOnce => log::debug!(
+                "schedule '{}' ignored step request as it is not in manual step mode",
+                state.schedule.name()
+            ),
+            WorkerRequest::SetFailurePlan { codelet, plan } => {
+                if !state.schedule.set_failure_plan(&codelet, plan) {
+                    log::warn!(
+                        "schedule '{}' has no codelet '{codelet}' for failure injection",
+                        state.schedule.name()
+                    );
+                }
+            }
+            WorkerRequest::BeginSteps => {}
+        }
+        true
+    }
+
+    fn spin_loop(state: &mut WorkerState) {
+        loop {
+            if state.schedule.step_mode() == StepMode::Manual
+                && state.schedule.next_transition() == Some(Transition::Step)
+            {
+                // block until the next step is triggered
+                match state.rx_request.recv() {
+                    Ok(WorkerRequest::StepOnce) => {}
+                    Ok(request) => {
+                        if Self::handle_request(state, request) {
+                            continue;
+                        } else {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            } else {
+                // Wait until next period. Be careful not to hold a lock on state while sleeping.
+                if let Some(next_instant) = state.schedule.next_instant(Instant::now()) {
+                    state.schedule.sleep_until(next_instant);
                 }
-            };
+
+                // handle requests
+                if let Ok(request) = state.rx_request.try_recv() {
+                    if !Self::handle_request(state, request) {
+                        break;
+                    }
+                }
+            }
 
             // execute
             state.schedule.spin();
             if state.schedule.is_terminated() {
                 break;
             }
-        }
 
-        state.schedule.finalize();
-
-        state
-            .tx_reply
-            .send(WorkerReply::Report(state.schedule.report()))
-            .ok();
+            if let Some(snapshot) = state.snapshot.as_mut() {
+                snapshot.save_periodic(&state.schedule);
+            }
+        }
     }
 
     fn report(&self) -> InspectorReport {
+        if let Some(report) = self.final_report.as_ref() {
+            return report.clone();
+        }
+
         self.tx_request.send(WorkerRequest::Report).ok();
-        match self.rx_reply.recv() {
-            Ok(WorkerReply::Report(stats)) => stats,
-            _ => panic!(),
+        loop {
+            match self.rx_reply.recv() {
+                Ok(WorkerReply::Report(stats)) => return *stats,
+                Ok(WorkerReply::Started(result)) => *self.started.lock().unwrap() = Some(result),
+                // the worker thread terminated and its final report was already received
+                Err(_) => return InspectorReport::default(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{executor::stop_waves, Executor, InFlightCodelet, StepMode};
+    use core::time::Duration;
+    use nodo::{
+        codelet::{ScheduleBuilder, SleepStrategy, Transition},
+        prelude::*,
+    };
+    use nodo_core::Name;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    struct Panicker {
+        num_steps: usize,
+    }
+
+    impl Codelet for Panicker {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = ();
+        type Tx = ();
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            ((), ())
+        }
+
+        fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            self.num_steps += 1;
+            if self.num_steps == 3 {
+                panic!("boom after {} steps", self.num_steps);
+            }
+            SUCCESS
+        }
+    }
+
+    #[test]
+    fn test_worker_panic_capture() {
+        let mut exec = Executor::new();
+        exec.push(
+            ScheduleBuilder::new()
+                .with_name("panicky")
+                .with(Panicker { num_steps: 0 }.into_instance("panicker", ()))
+                .into(),
+        );
+
+        while !exec.is_finished() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        // the final report is still available
+        assert_eq!(exec.report().into_vec().len(), 1);
+
+        let err = exec.join().unwrap_err();
+        assert_eq!(err.panics.len(), 1);
+
+        let panic = &err.panics[0];
+        assert_eq!(panic.worker, "panicky");
+        assert_eq!(panic.message, "boom after 3 steps");
+        assert!(panic.backtrace.is_some());
+        assert_eq!(
+            panic.in_flight,
+            Some(InFlightCodelet {
+                sequence: String::new(),
+                codelet: "panicker".into(),
+                transition: Transition::Step,
+            })
+        );
+        assert!(err.to_string().contains("in codelet 'panicker'"));
+    }
+
+    #[test]
+    fn test_stop_waves() {
+        let deps = |names: &[&str]| names.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        // reverse insertion order without dependencies
+        let none = deps(&[]);
+        assert_eq!(
+            stop_waves(&[("a", &none), ("b", &none), ("c", &none)]),
+            [vec![2], vec![1], vec![0]]
+        );
+
+        // producers stop before the recorder, the recorder before the logger
+        let logger = deps(&[]);
+        let recorder = deps(&["logger"]);
+        let producer = deps(&["recorder", "logger"]);
+        assert_eq!(
+            stop_waves(&[
+                ("producer_1", &producer),
+                ("recorder", &recorder),
+                ("producer_2", &producer),
+                ("logger", &logger),
+            ]),
+            [vec![0, 2], vec![1], vec![3]]
+        );
+
+        // cyclic dependencies are stopped first
+        let on_a = deps(&["a"]);
+        let on_b = deps(&["b"]);
+        assert_eq!(
+            stop_waves(&[("a", &on_b), ("b", &on_a), ("c", &on_a)]),
+            [vec![0, 1, 2]]
+        );
+    }
+
+    struct Counter {
+        steps: Arc<AtomicUsize>,
+    }
+
+    impl Codelet for Counter {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = ();
+        type Tx = ();
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            ((), ())
+        }
+
+        fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            self.steps.fetch_add(1, Ordering::SeqCst);
+            SUCCESS
+        }
+    }
+
+    #[test]
+    fn test_manual_step_mode() {
+        let steps = Arc::new(AtomicUsize::new(0));
+        let mut exec = Executor::new();
+        exec.push(
+            ScheduleBuilder::new()
+                .with_name("debug")
+                .with_period(Duration::from_millis(1))
+                .with(
+                    Counter {
+                        steps: steps.clone(),
+                    }
+                    .into_instance("counter", ()),
+                )
+                .into(),
+        );
+        assert!(exec.set_step_mode("unknown", StepMode::Manual).is_err());
+
+        while steps.load(Ordering::SeqCst) == 0 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        // requests are handled in order thus the report waits until the mode was applied
+        exec.set_step_mode("debug", StepMode::Manual).unwrap();
+        exec.report();
+        let frozen = steps.load(Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(steps.load(Ordering::SeqCst), frozen);
+
+        // exactly one step per trigger
+        for k in 1..=5 {
+            exec.step_once("debug").unwrap();
+            exec.report();
+            assert_eq!(steps.load(Ordering::SeqCst), frozen + k);
+        }
+
+        // periodic operation resumes
+        exec.set_step_mode("debug", StepMode::Auto).unwrap();
+        while steps.load(Ordering::SeqCst) < frozen + 20 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        // a trigger in auto mode does not cause an extra step and manual mode can be re-entered
+        exec.step_once("debug").unwrap();
+        exec.set_step_mode("debug", StepMode::Manual).unwrap();
+        exec.report();
+        let frozen = steps.load(Ordering::SeqCst);
+        exec.step_once("debug").unwrap();
+        exec.report();
+        assert_eq!(steps.load(Ordering::SeqCst), frozen + 1);
+
+        // a schedule in manual mode still stops
+        exec.request_stop();
+        exec.join().unwrap();
+    }
+
+    #[test]
+    fn test_sleep_overshoot_recorded() {
+        let steps = Arc::new(AtomicUsize::new(0));
+        let mut exec = Executor::new();
+        for (name, strategy) in [
+            ("default", SleepStrategy::default()),
+            (
+                "adaptive",
+                SleepStrategy::Hybrid {
+                    slack: Duration::from_micros(200),
+                    adaptive: true,
+                },
+            ),
+            ("spin", SleepStrategy::Spin),
+        ] {
+            exec.push(
+                ScheduleBuilder::new()
+                    .with_name(name)
+                    .with_period(Duration::from_millis(2))
+                    .with_sleep_strategy(strategy)
+                    .with(
+                        Counter {
+                            steps: steps.clone(),
+                        }
+                        .into_instance("counter", ()),
+                    )
+                    .into(),
+            );
+        }
+
+        while steps.load(Ordering::SeqCst) < 30 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        exec.request_stop();
+        exec.join().unwrap();
+
+        let report = exec.report();
+        for name in ["default", "adaptive", "spin"] {
+            let overshoot = &report.schedules()[&Name::new(name)].sleep_overshoot;
+            assert!(overshoot.count() > 0, "{name}");
+            assert!(overshoot.min() <= overshoot.avg() && overshoot.avg() <= overshoot.max());
         }
     }
 }
@@ -0,0 +1,549 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::{fmt, marker::PhantomData};
+use eyre::{eyre, Result};
+use nodo::prelude::*;
+use serde::Serialize;
+use serde_json::{Number, Value};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// Environment variable which makes [assert_matches_golden] overwrite goldens with the produced
+/// output when set to `1`
+pub const UPDATE_GOLDENS_ENV: &str = "UPDATE_GOLDENS";
+
+/// Configuration for [GoldenRecorder]
+#[derive(Debug, Clone)]
+pub struct GoldenRecorderConfig {
+    /// Floats are rounded to this number of decimal places before they are written. None writes
+    /// floats with full precision.
+    pub float_decimals: Option<u32>,
+}
+
+impl Default for GoldenRecorderConfig {
+    fn default() -> Self {
+        Self {
+            float_decimals: Some(6),
+        }
+    }
+}
+
+/// A sink which writes the value of every received message as one line of JSON
+///
+/// Objects are written with sorted keys such that the output only depends on the message
+/// values. Timestamps are not recorded as they depend on timing. The output is written next to
+/// the golden file given to [GoldenRecorder::new] with the extension `.actual` and compared with
+/// [assert_matches_golden] after the pipeline finished.
+pub struct GoldenRecorder<T> {
+    golden: PathBuf,
+    writer: Option<BufWriter<File>>,
+    marker: PhantomData<T>,
+}
+
+impl<T> GoldenRecorder<T> {
+    pub fn new(golden: impl Into<PathBuf>) -> Self {
+        Self {
+            golden: golden.into(),
+            writer: None,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Codelet for GoldenRecorder<T>
+where
+    T: Serialize + Send + Sync,
+{
+    type Status = DefaultStatus;
+    type Config = GoldenRecorderConfig;
+    type Rx = DoubleBufferRx<Message<T>>;
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (DoubleBufferRx::new_auto_size(), ())
+    }
+
+    fn start(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        let path = actual_path(&self.golden);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        self.writer = Some(BufWriter::new(File::create(path)?));
+        SUCCESS
+    }
+
+    fn step(&mut self, cx: &Context<Self>, rx: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        if rx.is_empty() {
+            return SKIPPED;
+        }
+
+        let writer = self
+            .writer
+            .as_mut()
+            .ok_or_else(|| eyre!("golden recorder was not started"))?;
+        while let Some(msg) = rx.try_pop() {
+            let mut value = serde_json::to_value(&msg.value)?;
+            if let Some(decimals) = cx.config.float_decimals {
+                round_floats(&mut value, decimals);
+            }
+            serde_json::to_writer(&mut *writer, &value)?;
+            writeln!(writer)?;
+        }
+        writer.flush()?;
+
+        SUCCESS
+    }
+
+    fn stop(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+        SUCCESS
+    }
+}
+
+/// Path to which a [GoldenRecorder] writes the produced output for the given golden file
+pub fn actual_path(golden: &Path) -> PathBuf {
+    let mut path = golden.as_os_str().to_owned();
+    path.push(".actual");
+    path.into()
+}
+
+/// Rounds all floats in a JSON value to the given number of decimal places
+pub fn round_floats(value: &mut Value, decimals: u32) {
+    match value {
+        Value::Number(number) => {
+            if let Some(x) = number.as_f64().filter(|_| number.is_f64()) {
+                let scale = 10f64.powi(decimals as i32);
+                let scaled = (x * scale).round();
+                if scaled.is_finite() {
+                    // normalize negative zero
+                    let rounded = scaled / scale + 0.0;
+                    if let Some(rounded) = Number::from_f64(rounded) {
+                        *number = rounded;
+                    }
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| round_floats(v, decimals)),
+        Value::Object(fields) => fields.values_mut().for_each(|v| round_floats(v, decimals)),
+        Value::Null | Value::Bool(_) | Value::String(_) => {}
+    }
+}
+
+/// Tolerance for numbers whose field path matches a pattern
+///
+/// Paths join field names with `.` and add array indices in brackets, e.g. `pose.position.x`
+/// or `points[3].x`. The path of a message which is a plain number is empty. Patterns may
+/// contain `*` which matches any sequence of characters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloatTolerance {
+    pub pattern: String,
+
+    /// Maximum absolute difference
+    pub abs: f64,
+
+    /// Maximum difference relative to the larger magnitude of the two numbers
+    pub rel: f64,
+}
+
+impl FloatTolerance {
+    pub fn matches(&self, path: &str) -> bool {
+        matches_pattern(&self.pattern, path)
+    }
+
+    /// True if the numbers are within the absolute or the relative tolerance
+    pub fn accepts(&self, expected: f64, actual: f64) -> bool {
+        let delta = (expected - actual).abs();
+        delta <= self.abs || delta <= self.rel * expected.abs().max(actual.abs())
+    }
+}
+
+/// Tolerances used by [assert_matches_golden_with]. Numbers without a matching tolerance must
+/// be identical. The first matching tolerance is used.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GoldenTolerances {
+    pub rules: Vec<FloatTolerance>,
+}
+
+impl GoldenTolerances {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a tolerance for all numbers whose path matches the pattern
+    #[must_use]
+    pub fn with(mut self, pattern: impl Into<String>, abs: f64, rel: f64) -> Self {
+        self.rules.push(FloatTolerance {
+            pattern: pattern.into(),
+            abs,
+            rel,
+        });
+        self
+    }
+
+    pub fn find(&self, path: &str) -> Option<&FloatTolerance> {
+        self.rules.iter().find(|rule| rule.matches(path))
+    }
+}
+
+/// First line at which the produced output diverged from the golden
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoldenDivergence {
+    /// Line number starting at 1
+    pub line: usize,
+
+    /// Line in the golden or None if the output has more lines
+    pub expected: Option<String>,
+
+    /// Line in the produced output or None if the output has fewer lines
+    pub actual: Option<String>,
+
+    /// Description of the first difference within the line
+    pub reason: String,
+}
+
+/// Differences between produced output and a golden
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoldenDiff {
+    pub expected_lines: usize,
+    pub actual_lines: usize,
+
+    /// Number of lines which differ including missing and additional lines
+    pub mismatched_lines: usize,
+
+    pub first_divergence: GoldenDivergence,
+}
+
+impl fmt::Display for GoldenDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let first = &self.first_divergence;
+        writeln!(
+            f,
+            "{} lines differ (expected {} lines, got {})",
+            self.mismatched_lines, self.expected_lines, self.actual_lines
+        )?;
+        writeln!(
+            f,
+            "first divergence at line {}: {}",
+            first.line, first.reason
+        )?;
+        writeln!(
+            f,
+            "  expected: {}",
+            first.expected.as_deref().unwrap_or("<none>")
+        )?;
+        write!(
+            f,
+            "  actual:   {}",
+            first.actual.as_deref().unwrap_or("<none>")
+        )
+    }
+}
+
+/// Compares produced output with a golden line by line. Returns None if they match.
+pub fn compare_golden(
+    expected: &str,
+    actual: &str,
+    tolerances: &GoldenTolerances,
+) -> Option<GoldenDiff> {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    let mut mismatched_lines = 0;
+    let mut first_divergence = None;
+    for i in 0..expected.len().max(actual.len()) {
+        let (e, a) = (expected.get(i).copied(), actual.get(i).copied());
+        let reason = match (e, a) {
+            (Some(e), Some(a)) => compare_lines(e, a, tolerances),
+            (Some(_), None) => Some("missing line".into()),
+            (None, Some(_)) => Some("unexpected line".into()),
+            (None, None) => None,
+        };
+        if let Some(reason) = reason {
+            mismatched_lines += 1;
+            first_divergence.get_or_insert_with(|| GoldenDivergence {
+                line: i + 1,
+                expected: e.map(String::from),
+                actual: a.map(String::from),
+                reason,
+            });
+        }
+    }
+
+    first_divergence.map(|first_divergence| GoldenDiff {
+        expected_lines: expected.len(),
+        actual_lines: actual.len(),
+        mismatched_lines,
+        first_divergence,
+    })
+}
+
+/// Compares the output of a [GoldenRecorder] with the golden and panics with a report of the
+/// differences if they do not match.
+///
+/// If the environment variable `UPDATE_GOLDENS=1` is set the golden is replaced by the output
+/// instead. The output file is removed if it matches or was accepted and kept otherwise.
+#[track_caller]
+pub fn assert_matches_golden(golden: impl AsRef<Path>) {
+    assert_matches_golden_with(golden, &GoldenTolerances::default())
+}
+
+/// Like [assert_matches_golden] but compares numbers with the given tolerances
+#[track_caller]
+pub fn assert_matches_golden_with(golden: impl AsRef<Path>, tolerances: &GoldenTolerances) {
+    if let Err(err) = check_golden(golden.as_ref(), tolerances) {
+        panic!("{err}");
+    }
+}
+
+fn check_golden(golden: &Path, tolerances: &GoldenTolerances) -> Result<()> {
+    let actual_path = actual_path(golden);
+    let actual = std::fs::read_to_string(&actual_path).map_err(|err| {
+        eyre!(
+            "no output for golden {golden:?}: could not read {actual_path:?}: {err}. Was the \
+             GoldenRecorder started?"
+        )
+    })?;
+
+    if std::env::var(UPDATE_GOLDENS_ENV).is_ok_and(|v| v == "1") {
+        if let Some(dir) = golden.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(golden, actual)?;
+        std::fs::remove_file(actual_path)?;
+        log::info!("updated golden {golden:?}");
+        return Ok(());
+    }
+
+    let expected = std::fs::read_to_string(golden).map_err(|err| {
+        eyre!("could not read golden {golden:?}: {err}. Run with {UPDATE_GOLDENS_ENV}=1 to create it.")
+    })?;
+
+    match compare_golden(&expected, &actual, tolerances) {
+        None => {
+            std::fs::remove_file(actual_path)?;
+            Ok(())
+        }
+        Some(diff) => Err(eyre!(
+            "output {actual_path:?} does not match golden {golden:?}: {diff}\nRun with \
+             {UPDATE_GOLDENS_ENV}=1 to accept the new output."
+        )),
+    }
+}
+
+fn compare_lines(expected: &str, actual: &str, tolerances: &GoldenTolerances) -> Option<String> {
+    match (
+        serde_json::from_str::<Value>(expected),
+        serde_json::from_str::<Value>(actual),
+    ) {
+        (Ok(e), Ok(a)) => compare_values("", &e, &a, tolerances),
+        _ => (expected != actual).then(|| "lines differ".into()),
+    }
+}
+
+fn compare_values(
+    path: &str,
+    expected: &Value,
+    actual: &Value,
+    tolerances: &GoldenTolerances,
+) -> Option<String> {
+    match (expected, actual) {
+        (Value::Number(e), Value::Number(a)) => {
+            let is_equal = match (tolerances.find(path), e.as_f64(), a.as_f64()) {
+                (Some(tolerance), Some(e), Some(a)) => tolerance.accepts(e, a),
+                _ => e == a,
+            };
+            (!is_equal).then(|| format!("{}: expected {e}, got {a}", display_path(path)))
+        }
+        (Value::Array(e), Value::Array(a)) => {
+            if e.len() != a.len() {
+                return Some(format!(
+                    "{}: expected {} elements, got {}",
+                    display_path(path),
+                    e.len(),
+                    a.len()
+                ));
+            }
+            e.iter()
+                .zip(a.iter())
+                .enumerate()
+                .find_map(|(i, (e, a))| compare_values(&format!("{path}[{i}]"), e, a, tolerances))
+        }
+        (Value::Object(e), Value::Object(a)) => {
+            if let Some(key) = e.keys().find(|key| !a.contains_key(*key)) {
+                return Some(format!("{}: missing field", join_path(path, key)));
+            }
+            if let Some(key) = a.keys().find(|key| !e.contains_key(*key)) {
+                return Some(format!("{}: unexpected field", join_path(path, key)));
+            }
+            e.iter()
+                .find_map(|(key, e)| compare_values(&join_path(path, key), e, &a[key], tolerances))
+        }
+        (e, a) => (e != a).then(|| format!("{}: expected {e}, got {a}", display_path(path))),
+    }
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.into()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() {
+        "<value>"
+    } else {
+        path
+    }
+}
+
+/// Matches a path against a pattern where `*` matches any sequence of characters
+fn matches_pattern(pattern: &str, path: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == path,
+        Some((prefix, rest)) => {
+            let Some(path) = path.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=path.len())
+                .filter(|&i| path.is_char_boundary(i))
+                .any(|i| matches_pattern(rest, &path[i..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compare_golden, golden::matches_pattern, round_floats, GoldenTolerances};
+    use serde_json::json;
+
+    #[test]
+    fn test_round_floats() {
+        let mut value = json!({"a": 0.1 + 0.2, "b": [1.23456789, -0.0000001], "c": 7});
+        round_floats(&mut value, 6);
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#"{"a":0.3,"b":[1.234568,0.0],"c":7}"#
+        );
+    }
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("pose.x", "pose.x"));
+        assert!(!matches_pattern("pose.x", "pose.y"));
+        assert!(matches_pattern("pose.*", "pose.x"));
+        assert!(matches_pattern("*.x", "points[3].x"));
+        assert!(matches_pattern("*", ""));
+        assert!(!matches_pattern("*.x", "x"));
+    }
+
+    #[test]
+    fn test_compare_exact() {
+        let golden = "{\"x\":1.0,\"n\":2}\n{\"x\":2.0,\"n\":3}\n";
+        let tolerances = GoldenTolerances::new();
+
+        assert_eq!(compare_golden(golden, golden, &tolerances), None);
+
+        let diff = compare_golden(
+            golden,
+            "{\"x\":1.0,\"n\":2}\n{\"x\":2.5,\"n\":4}\n",
+            &tolerances,
+        )
+        .unwrap();
+        assert_eq!(diff.mismatched_lines, 1);
+        assert_eq!(diff.first_divergence.line, 2);
+        assert_eq!(diff.first_divergence.reason, "n: expected 3, got 4");
+    }
+
+    #[test]
+    fn test_compare_line_counts() {
+        let tolerances = GoldenTolerances::new();
+
+        let diff = compare_golden("1\n2\n3\n", "1\n5\n", &tolerances).unwrap();
+        assert_eq!((diff.expected_lines, diff.actual_lines), (3, 2));
+        assert_eq!(diff.mismatched_lines, 2);
+        assert_eq!(diff.first_divergence.line, 2);
+        assert_eq!(diff.first_divergence.reason, "<value>: expected 2, got 5");
+
+        let diff = compare_golden("1\n", "1\n2\n", &tolerances).unwrap();
+        assert_eq!(diff.first_divergence.reason, "unexpected line");
+        assert_eq!(diff.first_divergence.expected, None);
+    }
+
+    #[test]
+    fn test_compare_structure() {
+        let tolerances = GoldenTolerances::new();
+
+        let reason = |e: &str, a: &str| {
+            compare_golden(e, a, &tolerances)
+                .unwrap()
+                .first_divergence
+                .reason
+        };
+        assert_eq!(reason(r#"{"a":1}"#, r#"{"b":1}"#), "a: missing field");
+        assert_eq!(
+            reason(r#"{"a":1}"#, r#"{"a":1,"b":1}"#),
+            "b: unexpected field"
+        );
+        assert_eq!(
+            reason(r#"{"a":[1,2]}"#, r#"{"a":[1]}"#),
+            "a: expected 2 elements, got 1"
+        );
+        assert_eq!(
+            reason(r#"{"a":"x"}"#, r#"{"a":"y"}"#),
+            r#"a: expected "x", got "y""#
+        );
+        assert_eq!(reason("not json", "not json!"), "lines differ");
+    }
+
+    #[test]
+    fn test_compare_tolerances() {
+        let golden = r#"{"pose":{"x":10.0,"y":100.0},"points":[{"x":1.0},{"x":2.0}],"id":1}"#;
+
+        // absolute tolerance
+        let tolerances = GoldenTolerances::new().with("pose.x", 0.01, 0.0);
+        let actual = r#"{"pose":{"x":10.005,"y":100.0},"points":[{"x":1.0},{"x":2.0}],"id":1}"#;
+        assert_eq!(compare_golden(golden, actual, &tolerances), None);
+        let actual = r#"{"pose":{"x":10.02,"y":100.0},"points":[{"x":1.0},{"x":2.0}],"id":1}"#;
+        assert!(compare_golden(golden, actual, &tolerances).is_some());
+
+        // relative tolerance
+        let tolerances = GoldenTolerances::new().with("pose.*", 0.0, 1e-3);
+        let actual = r#"{"pose":{"x":10.0,"y":100.05},"points":[{"x":1.0},{"x":2.0}],"id":1}"#;
+        assert_eq!(compare_golden(golden, actual, &tolerances), None);
+        let actual = r#"{"pose":{"x":10.05,"y":100.0},"points":[{"x":1.0},{"x":2.0}],"id":1}"#;
+        let diff = compare_golden(golden, actual, &tolerances).unwrap();
+        assert_eq!(
+            diff.first_divergence.reason,
+            "pose.x: expected 10.0, got 10.05"
+        );
+
+        // patterns apply to array elements and numbers without matching pattern are exact
+        let tolerances = GoldenTolerances::new().with("points[*].x", 0.1, 0.0);
+        let actual = r#"{"pose":{"x":10.0,"y":100.0},"points":[{"x":1.05},{"x":2.2}],"id":1}"#;
+        let diff = compare_golden(golden, actual, &tolerances).unwrap();
+        assert_eq!(
+            diff.first_divergence.reason,
+            "points[1].x: expected 2.0, got 2.2"
+        );
+        let actual = r#"{"pose":{"x":10.0,"y":100.0},"points":[{"x":1.0},{"x":2.0}],"id":2}"#;
+        assert!(compare_golden(golden, actual, &tolerances).is_some());
+
+        // the first matching tolerance is used
+        let tolerances = GoldenTolerances::new()
+            .with("pose.x", 0.0, 0.0)
+            .with("*", 1.0, 0.0);
+        let actual = r#"{"pose":{"x":10.5,"y":100.5},"points":[{"x":1.0},{"x":2.0}],"id":1}"#;
+        let diff = compare_golden(golden, actual, &tolerances).unwrap();
+        assert_eq!(
+            diff.first_divergence.reason,
+            "pose.x: expected 10.0, got 10.5"
+        );
+    }
+}
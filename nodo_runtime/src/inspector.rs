@@ -1,65 +1,254 @@
+use crate::{
+    decode_report_frame, encode_report_frame, inspector_snoop_topic, instance_path, AppInfo,
+    BacklogTeardown, ReportCodec, ReportCodecError, ReportCodecKind, SleepStatistics, StepMode,
+    WorkerThreadReport,
+};
 use eyre::Result;
-use lz4_flex::{compress_prepend_size, decompress_size_prepended};
 use nng::{
-    options::{protocol::pubsub::Subscribe, Options},
+    options::{
+        protocol::pubsub::{Subscribe, Unsubscribe},
+        Options, SendTimeout,
+    },
     Protocol, Socket,
 };
 use nodo::{
-    codelet::{NodeletId, Statistics},
-    prelude::DefaultStatus,
+    channels::SnoopSample,
+    codelet::{JitterStatistics, NodeletId, Statistics},
+    prelude::{DefaultStatus, Severity},
 };
+use nodo_core::{Name, UnknownFields};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, time::Instant};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    time::{Duration, Instant},
+};
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct RenderedStatus {
     pub label: String,
     pub status: DefaultStatus,
+    pub code: u32,
+    pub severity: Severity,
+
+    /// Why the codelet skipped, see `Context::skip_because`
+    #[serde(default)]
+    pub skip_reason: Option<String>,
 }
 
 #[derive(Default, Clone, Serialize, Deserialize)]
-pub struct InspectorReport(HashMap<NodeletId, InspectorCodeletReport>);
+pub struct InspectorReport {
+    pub(crate) codelets: HashMap<NodeletId, InspectorCodeletReport>,
+    pub(crate) app_info: Option<AppInfo>,
+
+    #[serde(default)]
+    pub(crate) schedules: BTreeMap<Name, InspectorScheduleReport>,
+
+    /// Top-level fields written by a newer version, see `REPORT_SCHEMA_VERSION`
+    #[serde(skip)]
+    pub(crate) unknown: UnknownFields,
+}
+
+/// Statistics of a schedule
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct InspectorScheduleReport {
+    /// Period of the schedule if it is periodic
+    pub period: Option<Duration>,
+
+    /// Deviation of schedule steps from the ideal periodic grid
+    pub jitter: JitterStatistics,
+
+    /// Names of sequences which are currently shed, see `ScheduleBuilder::with_load_shedding`
+    #[serde(default)]
+    pub shed_sequences: Vec<Name>,
+
+    /// Load shedding events by sequence name. Only contains sequences which were shed at least
+    /// once.
+    #[serde(default)]
+    pub shedding: BTreeMap<Name, SheddingStatistics>,
+
+    /// OS thread and CPU of the worker executing the schedule. None if the schedule is not
+    /// executed by a worker of the runtime.
+    #[serde(default)]
+    pub thread: Option<WorkerThreadReport>,
+
+    /// Large backlogs which were freed when the schedule stopped, see `TeardownConfig`
+    #[serde(default)]
+    pub teardown: Vec<BacklogTeardown>,
+
+    /// Time by which the worker woke up after the scheduled time of a step, see
+    /// `ScheduleBuilder::with_sleep_strategy`
+    #[serde(default)]
+    pub sleep_overshoot: SleepStatistics,
+
+    /// Fields written by a newer version which are kept when the report is forwarded
+    #[serde(flatten)]
+    pub unknown: UnknownFields,
+}
+
+/// Number of times a sequence was shed and restored
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SheddingStatistics {
+    pub shed_count: u64,
+    pub restore_count: u64,
+}
 
 impl InspectorReport {
     pub fn push(&mut self, id: NodeletId, entry: InspectorCodeletReport) {
-        if self.0.contains_key(&id) {
+        if self.codelets.contains_key(&id) {
             log::error!(
                 "Duplicated codelet id: {:?} (name='{}', other='{}'). This will be a hard error in the future.",
                 id,
                 entry.name,
-                self.0[&id].name
+                self.codelets[&id].name
             );
         }
-        self.0.insert(id, entry);
+        self.codelets.insert(id, entry);
     }
 
     pub fn extend(&mut self, other: InspectorReport) {
-        for (id, entry) in other.0 {
+        for (id, entry) in other.codelets {
             self.push(id, entry);
         }
+        self.schedules.extend(other.schedules);
+        if self.app_info.is_none() {
+            self.app_info = other.app_info;
+        }
+    }
+
+    pub fn push_schedule(&mut self, name: Name, entry: InspectorScheduleReport) {
+        self.schedules.insert(name, entry);
+    }
+
+    /// Statistics of all schedules by name
+    pub fn schedules(&self) -> &BTreeMap<Name, InspectorScheduleReport> {
+        &self.schedules
+    }
+
+    /// Build and version information of the runtime which sent the report
+    pub fn app_info(&self) -> Option<&AppInfo> {
+        self.app_info.as_ref()
+    }
+
+    pub fn set_app_info(&mut self, app_info: AppInfo) {
+        self.app_info = Some(app_info);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&NodeletId, &InspectorCodeletReport)> {
+        self.codelets.iter()
     }
 
     pub fn into_vec(self) -> Vec<(NodeletId, InspectorCodeletReport)> {
-        self.0.into_iter().collect()
+        self.codelets.into_iter().collect()
     }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct InspectorCodeletReport {
-    pub sequence: String,
-    pub name: String,
-    pub typename: String,
+    pub sequence: Name,
+    pub name: Name,
+    pub typename: Name,
     pub status: Option<RenderedStatus>,
     pub statistics: Statistics,
+
+    /// True while the schedule of the codelet is in its warm-up phase
+    pub is_warmup: bool,
+
+    /// Labels of the codelet instance, see `CodeletInstance::with_label`
+    #[serde(default)]
+    pub labels: Vec<String>,
+
+    /// True if the codelet never executed a step without skipping during the observation window
+    /// of the dead weight detection, see [DeadWeightConfig]
+    #[serde(default)]
+    pub suspected_inactive: bool,
+
+    /// Number of gaps in time detected by the schedule of the codelet, e.g. due to a system
+    /// suspend
+    #[serde(default)]
+    pub suspend_resume_count: u64,
+
+    /// Deadline of the codelet for a step, see `CodeletInstance::with_deadline`
+    #[serde(default)]
+    pub deadline: Option<Duration>,
+
+    /// Problems detected when the codelet started, see `CodeletInstance::start_diagnostics`
+    #[serde(default)]
+    pub start_diagnostics: Vec<String>,
+
+    /// Name of the schedule which executes the codelet
+    #[serde(default)]
+    pub schedule: Name,
+
+    /// Progress of long-running work as fraction in [0, 1] and detail, see
+    /// `Context::set_progress`
+    #[serde(default)]
+    pub progress: Option<(f32, String)>,
+
+    /// Fields written by a newer version which are kept when the report is forwarded
+    #[serde(flatten)]
+    pub unknown: UnknownFields,
+}
+
+impl InspectorCodeletReport {
+    /// Path which identifies the instance like `schedule/sequence/name`, see [instance_path]
+    pub fn path(&self) -> String {
+        instance_path(&self.schedule, &self.sequence, &self.name)
+    }
+}
+
+/// Common prefix of all topics under which schedule reports are published
+pub const INSPECTOR_SCHEDULE_TOPIC_PREFIX: &str = "schedule/";
+
+/// Topic under which the report of a single schedule is published
+pub fn inspector_schedule_topic(schedule: &str) -> String {
+    format!("{INSPECTOR_SCHEDULE_TOPIC_PREFIX}{schedule}")
+}
+
+/// Prepends the topic as a null-terminated string like nodo_nng does for messages. Subscribing
+/// to the topic including the null terminator only matches this exact topic.
+fn topic_prefix(topic: &str) -> Vec<u8> {
+    let mut prefix = Vec::with_capacity(topic.len() + 1);
+    prefix.extend_from_slice(topic.as_bytes());
+    prefix.push(0);
+    prefix
+}
+
+fn split_topic(buffer: &[u8]) -> Result<(&str, &[u8])> {
+    let end = buffer
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(ReportCodecError::Malformed("missing report topic"))?;
+    let topic = std::str::from_utf8(&buffer[..end])
+        .map_err(|_| ReportCodecError::Malformed("report topic is not UTF-8"))?;
+    Ok((topic, &buffer[end + 1..]))
+}
+
+/// Decodes a frame published by [InspectorServer::send_snoop_sample] into the topic and sample
+pub fn decode_snoop_frame(buffer: &[u8]) -> Result<(&str, SnoopSample)> {
+    let (topic, payload) = split_topic(buffer)?;
+    Ok((topic, rmp_serde::from_slice(payload)?))
 }
 
 /// The server is running in the nodo runtime and publishes reports
+///
+/// The report of every schedule is published once under its own topic, see
+/// [inspector_schedule_topic]. Clients subscribe to all schedules or a subset and merge the
+/// reports themselves. Every topic is encoded with its own codec instance as codecs may depend on
+/// earlier frames.
 pub struct InspectorServer {
     socket: Socket,
+    codec: ReportCodecKind,
+    topic_codecs: HashMap<String, Box<dyn ReportCodec>>,
+    last_report_size: usize,
 }
 
 impl InspectorServer {
     pub fn open(address: &str) -> Result<Self> {
+        Self::open_with_codec(address, ReportCodecKind::default())
+    }
+
+    /// Opens a server which encodes reports with the given codec. Clients must use the same codec.
+    pub fn open_with_codec(address: &str, codec: ReportCodecKind) -> Result<Self> {
         log::info!("Opening Inspector PUB socket at '{}'..", address);
 
         let socket = Socket::new(Protocol::Pub0)?;
@@ -70,26 +259,237 @@ impl InspectorServer {
 
         socket.listen(address)?;
 
+        Ok(Self {
+            socket,
+            codec,
+            topic_codecs: HashMap::new(),
+            last_report_size: 0,
+        })
+    }
+
+    /// Publishes a report which is not split by schedule under the topic of an unnamed schedule
+    pub fn send_report(&mut self, report: InspectorReport) -> Result<()> {
+        self.send_schedule_reports(vec![(String::new(), report)])
+    }
+
+    /// Publishes the report of every schedule once under its own topic
+    pub fn send_schedule_reports(
+        &mut self,
+        schedules: Vec<(String, InspectorReport)>,
+    ) -> Result<()> {
+        let mut size = 0;
+        for (schedule, report) in schedules {
+            size += self.send_topic(&inspector_schedule_topic(&schedule), &report)?;
+        }
+        self.last_report_size = size;
+        Ok(())
+    }
+
+    fn send_topic(&mut self, topic: &str, report: &InspectorReport) -> Result<usize> {
+        let codec = self
+            .topic_codecs
+            .entry(topic.to_string())
+            .or_insert_with(|| self.codec.build());
+        let mut frame = topic_prefix(topic);
+        frame.extend(encode_report_frame(codec.as_mut(), report)?);
+        let size = frame.len();
+        self.socket.send(&frame[..]).map_err(|(_, err)| err)?;
+        Ok(size)
+    }
+
+    /// Publishes a sample of a snooped channel under [inspector_snoop_topic] encoded with
+    /// MessagePack, see [decode_snoop_frame]
+    pub fn send_snoop_sample(&mut self, channel: &str, sample: &SnoopSample) -> Result<()> {
+        let mut frame = topic_prefix(&inspector_snoop_topic(channel));
+        frame.extend(rmp_serde::to_vec_named(sample)?);
+        self.socket.send(&frame[..]).map_err(|(_, err)| err)?;
+        Ok(())
+    }
+
+    /// Size in bytes of the last encoded reports of all schedules
+    pub fn last_report_size(&self) -> usize {
+        self.last_report_size
+    }
+}
+
+/// Commands sent from the inspector to the runtime
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InspectorCommand {
+    /// Changes how a schedule triggers steps, see [StepMode]
+    SetStepMode { schedule: String, mode: StepMode },
+
+    /// Executes a single step of a schedule in manual step mode
+    StepOnce { schedule: String },
+
+    /// Publishes samples of an RX channel given by its path, see `Runtime::snoop`
+    Snoop { channel: String },
+
+    /// Stops publishing samples of an RX channel
+    Unsnoop { channel: String },
+}
+
+/// Receives commands from an inspector over a PAIR socket
+///
+/// The command socket uses its own address as reports are published on a PUB socket which can
+/// not receive.
+pub struct InspectorCommandServer {
+    socket: Socket,
+}
+
+impl InspectorCommandServer {
+    pub fn open(address: &str) -> Result<Self> {
+        log::info!("Opening Inspector command PAIR socket at '{}'..", address);
+        let socket = Socket::new(Protocol::Pair0)?;
+        socket.listen(address)?;
+        Ok(Self { socket })
+    }
+
+    /// Receives all pending commands without blocking
+    pub fn try_recv_commands(&mut self) -> Result<Vec<InspectorCommand>> {
+        let mut commands = Vec::new();
+        loop {
+            match self.socket.try_recv() {
+                Ok(buff) => commands.push(bincode::deserialize(&buff)?),
+                Err(nng::Error::TryAgain) => return Ok(commands),
+                Err(err) => return Err(err)?,
+            }
+        }
+    }
+}
+
+/// Maximum time [InspectorCommandClient::send] waits for the runtime to accept a command
+pub const INSPECTOR_COMMAND_SEND_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Sends commands to the command socket of a runtime, see [InspectorCommandServer]
+pub struct InspectorCommandClient {
+    socket: Socket,
+}
+
+impl InspectorCommandClient {
+    pub fn dial(address: &str) -> Result<Self> {
+        log::info!("Dialing Inspector command PAIR socket at '{}'..", address);
+        let socket = Socket::new(Protocol::Pair0)?;
+        socket.set_opt::<SendTimeout>(Some(INSPECTOR_COMMAND_SEND_TIMEOUT))?;
+        socket.dial_async(address)?;
         Ok(Self { socket })
     }
 
-    pub fn send_report(&self, report: InspectorReport) -> Result<()> {
-        let buffer = bincode::serialize(&report)?;
-        let compressed = compress_prepend_size(&buffer);
-        self.socket.send(&compressed).map_err(|(_, err)| err)?;
+    /// Sends a command. Fails if the runtime is not connected or does not accept the command
+    /// within [INSPECTOR_COMMAND_SEND_TIMEOUT].
+    pub fn send(&self, command: &InspectorCommand) -> Result<()> {
+        let buff = bincode::serialize(command)?;
+        self.socket.send(&buff[..]).map_err(|(_, err)| err)?;
         Ok(())
     }
 }
 
 /// The client is running in the report viewer and receives reports
+///
+/// The client can be connected to multiple runtimes at once. Each runtime is identified by the
+/// address it was dialed with.
 pub struct InspectorClient {
+    sources: Vec<InspectorSource>,
+    pending: VecDeque<SourcedReport>,
+}
+
+/// Connection to a single inspector server
+pub struct InspectorSource {
+    address: String,
     socket: Socket,
+    codec: ReportCodecKind,
+    topic_codecs: HashMap<String, Box<dyn ReportCodec>>,
+    subscriptions: Vec<Vec<u8>>,
+    latest: BTreeMap<String, InspectorReport>,
     datarate: DatarateEstimation,
     last_report_time: Option<Instant>,
+    last_report_size: usize,
+    app_info: Option<AppInfo>,
+}
+
+/// A report tagged with the address of the runtime which sent it
+#[derive(Clone)]
+pub struct SourcedReport {
+    pub source: String,
+    pub report: InspectorReport,
 }
 
 impl InspectorClient {
     pub fn dial(address: &str) -> Result<Self> {
+        Self::dial_many(&[address])
+    }
+
+    /// Dials multiple inspector servers at once
+    pub fn dial_many<S: AsRef<str>>(addresses: &[S]) -> Result<Self> {
+        Self::dial_many_with_codec(addresses, ReportCodecKind::default())
+    }
+
+    /// Dials multiple inspector servers which all use the given codec
+    pub fn dial_many_with_codec<S: AsRef<str>>(
+        addresses: &[S],
+        codec: ReportCodecKind,
+    ) -> Result<Self> {
+        Ok(Self {
+            sources: addresses
+                .iter()
+                .map(|address| InspectorSource::dial(address.as_ref(), codec))
+                .collect::<Result<Vec<_>>>()?,
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Only receives the reports of the given schedules instead of the reports of all schedules
+    ///
+    /// The reports of all subscribed schedules are merged into a single report per source. Note
+    /// that NNG filters topics on the subscriber, thus frames of other topics are still
+    /// transmitted but discarded before decoding.
+    pub fn subscribe_schedules<S: AsRef<str>>(&mut self, schedules: &[S]) -> Result<()> {
+        for source in self.sources.iter_mut() {
+            source.subscribe_schedules(schedules)?;
+        }
+        Ok(())
+    }
+
+    /// Receives the latest report of every source which sent a new report
+    pub fn try_recv_reports(&mut self) -> Result<Vec<SourcedReport>> {
+        let mut result: Vec<_> = self.pending.drain(..).collect();
+        for source in self.sources.iter_mut() {
+            if let Some(report) = source.try_recv_report()? {
+                result.push(SourcedReport {
+                    source: source.address.clone(),
+                    report,
+                });
+            }
+        }
+        Ok(result)
+    }
+
+    /// Receives the next report of any source
+    ///
+    /// If multiple sources sent a report the others are kept and returned by subsequent calls.
+    pub fn try_recv_report(&mut self) -> Result<Option<SourcedReport>> {
+        if self.pending.is_empty() {
+            let reports = self.try_recv_reports()?;
+            self.pending.extend(reports);
+        }
+        Ok(self.pending.pop_front())
+    }
+
+    pub fn sources(&self) -> &[InspectorSource] {
+        &self.sources
+    }
+
+    /// Total datarate of all sources in bytes/s
+    pub fn datarate(&self) -> f64 {
+        self.sources.iter().map(|s| s.datarate()).sum()
+    }
+
+    pub fn last_report_time(&self) -> Option<Instant> {
+        self.sources.iter().filter_map(|s| s.last_report_time).max()
+    }
+}
+
+impl InspectorSource {
+    fn dial(address: &str, codec: ReportCodecKind) -> Result<Self> {
         log::info!("Opening Inspector SUB socket at '{}'..", address);
 
         let socket = Socket::new(Protocol::Sub0)?;
@@ -100,38 +500,82 @@ impl InspectorClient {
 
         socket.dial_async(address)?;
 
-        // subscribe to all topics
-        socket.set_opt::<Subscribe>(vec![])?;
+        // subscribes to all schedules as the prefix is not null-terminated
+        let subscription = INSPECTOR_SCHEDULE_TOPIC_PREFIX.as_bytes().to_vec();
+        socket.set_opt::<Subscribe>(subscription.clone())?;
 
         Ok(Self {
+            address: address.to_string(),
             socket,
+            codec,
+            topic_codecs: HashMap::new(),
+            subscriptions: vec![subscription],
+            latest: BTreeMap::new(),
             datarate: DatarateEstimation::default(),
             last_report_time: None,
+            last_report_size: 0,
+            app_info: None,
         })
     }
 
-    pub fn try_recv_report(&mut self) -> Result<Option<InspectorReport>> {
-        let mut maybe_buff = None;
+    fn subscribe_schedules<S: AsRef<str>>(&mut self, schedules: &[S]) -> Result<()> {
+        for subscription in self.subscriptions.drain(..) {
+            self.socket.set_opt::<Unsubscribe>(subscription)?;
+        }
+        for schedule in schedules {
+            let subscription = topic_prefix(&inspector_schedule_topic(schedule.as_ref()));
+            self.socket.set_opt::<Subscribe>(subscription.clone())?;
+            self.subscriptions.push(subscription);
+        }
+        self.topic_codecs.clear();
+        self.latest.clear();
+        Ok(())
+    }
+
+    fn try_recv_report(&mut self) -> Result<Option<InspectorReport>> {
+        // all frames are decoded in order as codecs may depend on earlier frames
+        let mut has_new_report = false;
         loop {
             match self.socket.try_recv() {
                 Ok(buff) => {
                     self.datarate.push(buff.len() as u64);
-                    maybe_buff = Some(buff);
+                    self.last_report_size = buff.len();
+                    self.last_report_time = Some(Instant::now());
+                    let (topic, frame) = split_topic(&buff)?;
+                    let codec = self
+                        .topic_codecs
+                        .entry(topic.to_string())
+                        .or_insert_with(|| self.codec.build());
+                    if let Some(report) = decode_report_frame(codec.as_mut(), frame)? {
+                        self.latest.insert(topic.to_string(), report);
+                        has_new_report = true;
+                    }
                 }
                 Err(nng::Error::TryAgain) => break,
                 Err(err) => return Err(err)?,
             }
         }
 
-        if let Some(buff) = maybe_buff {
-            self.last_report_time = Some(Instant::now());
-            let uncompressed = decompress_size_prepended(&buff)?;
-            Ok(Some(bincode::deserialize(&uncompressed)?))
-        } else {
-            Ok(None)
+        if !has_new_report {
+            return Ok(None);
+        }
+
+        let mut merged = InspectorReport::default();
+        for report in self.latest.values() {
+            merged.extend(report.clone());
+        }
+
+        if let Some(app_info) = merged.app_info() {
+            self.app_info = Some(app_info.clone());
         }
+        Ok(Some(merged))
     }
 
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Datarate of this source in bytes/s
     pub fn datarate(&self) -> f64 {
         self.datarate.datarate()
     }
@@ -139,6 +583,87 @@ impl InspectorClient {
     pub fn last_report_time(&self) -> Option<Instant> {
         self.last_report_time
     }
+
+    /// Size in bytes of the last received report
+    pub fn last_report_size(&self) -> usize {
+        self.last_report_size
+    }
+
+    /// Build and version information of the runtime as sent with the latest report
+    pub fn app_info(&self) -> Option<&AppInfo> {
+        self.app_info.as_ref()
+    }
+}
+
+/// Identifies a nodelet across multiple runtimes
+///
+/// Nodelet IDs are only unique within a single runtime, thus they are namespaced by the source.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SourcedNodeletId {
+    pub source: String,
+    pub id: NodeletId,
+}
+
+/// Merges the latest reports of multiple sources
+///
+/// Sources which did not send a report for longer than the stale timeout are kept but marked as
+/// stale.
+pub struct MultiSourceReport {
+    stale_timeout: Duration,
+    sources: BTreeMap<String, (InspectorReport, Instant)>,
+}
+
+/// A single entry of a merged multi-source report
+#[derive(Clone)]
+pub struct MultiSourceEntry {
+    pub key: SourcedNodeletId,
+    pub report: InspectorCodeletReport,
+    pub is_stale: bool,
+}
+
+impl MultiSourceReport {
+    pub fn new(stale_timeout: Duration) -> Self {
+        Self {
+            stale_timeout,
+            sources: BTreeMap::new(),
+        }
+    }
+
+    /// Replaces the report of a source
+    pub fn update(&mut self, sourced: SourcedReport, now: Instant) {
+        self.sources.insert(sourced.source, (sourced.report, now));
+    }
+
+    /// Names of all sources which sent at least one report
+    pub fn sources(&self) -> impl Iterator<Item = &str> {
+        self.sources.keys().map(|s| s.as_str())
+    }
+
+    /// Returns true if the source did not send a report within the stale timeout
+    pub fn is_stale(&self, source: &str, now: Instant) -> bool {
+        self.sources
+            .get(source)
+            .is_none_or(|(_, time)| now.saturating_duration_since(*time) > self.stale_timeout)
+    }
+
+    /// All entries of all sources
+    pub fn entries(&self, now: Instant) -> Vec<MultiSourceEntry> {
+        let mut result = Vec::new();
+        for (source, (report, _)) in self.sources.iter() {
+            let is_stale = self.is_stale(source, now);
+            for (id, entry) in report.codelets.iter() {
+                result.push(MultiSourceEntry {
+                    key: SourcedNodeletId {
+                        source: source.clone(),
+                        id: *id,
+                    },
+                    report: entry.clone(),
+                    is_stale,
+                });
+            }
+        }
+        result
+    }
 }
 
 #[derive(Default)]
@@ -173,3 +698,291 @@ impl DatarateEstimation {
         self.datarate
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        decode_report_frame, encode_report_frame, InspectorClient, InspectorCodeletReport,
+        InspectorCommand, InspectorCommandClient, InspectorCommandServer, InspectorReport,
+        InspectorServer, MultiSourceReport, RenderedStatus, ReportCodecKind, SourcedNodeletId,
+        SourcedReport, StepMode,
+    };
+    use core::time::Duration;
+    use nng::{
+        options::{protocol::pubsub::Subscribe, Options},
+        Protocol, Socket,
+    };
+    use nodo::{
+        codelet::{NodeletId, Statistics, WorkerId},
+        prelude::{DefaultStatus, Severity},
+    };
+    use std::time::Instant;
+
+    fn report(names: &[&str]) -> InspectorReport {
+        let mut report = InspectorReport::default();
+        for (i, name) in names.iter().enumerate() {
+            report.push(
+                NodeletId(WorkerId(0), i as u32),
+                InspectorCodeletReport {
+                    sequence: Default::default(),
+                    name: (*name).into(),
+                    typename: Default::default(),
+                    status: None,
+                    statistics: Statistics::new(),
+                    is_warmup: false,
+                    labels: Vec::new(),
+                    suspected_inactive: false,
+                    suspend_resume_count: 0,
+                    deadline: None,
+                    start_diagnostics: Vec::new(),
+                    schedule: Default::default(),
+                    progress: None,
+                    unknown: Default::default(),
+                },
+            );
+        }
+        report
+    }
+
+    fn sourced(source: &str, names: &[&str]) -> SourcedReport {
+        SourcedReport {
+            source: source.into(),
+            report: report(names),
+        }
+    }
+
+    #[test]
+    fn multi_source_merge() {
+        let t0 = Instant::now();
+        let mut msr = MultiSourceReport::new(Duration::from_secs(1));
+
+        // both sources use the same nodelet IDs
+        msr.update(sourced("tcp://a", &["a0", "a1"]), t0);
+        msr.update(sourced("tcp://b", &["b0"]), t0);
+
+        let mut entries = msr.entries(t0);
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries[0].key,
+            SourcedNodeletId {
+                source: "tcp://a".into(),
+                id: NodeletId(WorkerId(0), 0)
+            }
+        );
+        assert_eq!(entries[0].report.name, "a0");
+        assert_eq!(entries[1].report.name, "a1");
+        assert_eq!(entries[2].report.name, "b0");
+        assert_eq!(entries[2].key.id, entries[0].key.id);
+
+        // a new report replaces the previous report of the same source
+        msr.update(sourced("tcp://a", &["a2"]), t0);
+        let names = msr
+            .entries(t0)
+            .into_iter()
+            .map(|e| e.report.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a2".to_string(), "b0".to_string()]);
+        assert_eq!(
+            msr.sources().collect::<Vec<_>>(),
+            vec!["tcp://a", "tcp://b"]
+        );
+    }
+
+    #[test]
+    fn multi_source_staleness() {
+        let t0 = Instant::now();
+        let mut msr = MultiSourceReport::new(Duration::from_secs(1));
+
+        msr.update(sourced("tcp://a", &["a0"]), t0);
+        msr.update(sourced("tcp://b", &["b0"]), t0);
+        assert!(!msr.is_stale("tcp://a", t0));
+        assert!(msr.is_stale("tcp://unknown", t0));
+
+        // only source b keeps sending reports
+        let t1 = t0 + Duration::from_millis(1500);
+        msr.update(sourced("tcp://b", &["b0"]), t1);
+
+        assert!(msr.is_stale("tcp://a", t1));
+        assert!(!msr.is_stale("tcp://b", t1));
+
+        // stale rows are kept
+        let entries = msr.entries(t1);
+        assert_eq!(entries.len(), 2);
+        assert!(
+            entries
+                .iter()
+                .find(|e| e.report.name == "a0")
+                .unwrap()
+                .is_stale
+        );
+        assert!(
+            !entries
+                .iter()
+                .find(|e| e.report.name == "b0")
+                .unwrap()
+                .is_stale
+        );
+
+        // source a recovers
+        msr.update(sourced("tcp://a", &["a0"]), t1);
+        assert!(msr.entries(t1).iter().all(|e| !e.is_stale));
+    }
+
+    #[test]
+    fn status_code_and_severity_roundtrip() {
+        let mut report = report(&["a0"]);
+        report
+            .codelets
+            .get_mut(&NodeletId(WorkerId(0), 0))
+            .unwrap()
+            .status = Some(RenderedStatus {
+            label: "overheated".into(),
+            status: DefaultStatus::Skipped,
+            code: 42,
+            severity: Severity::Error,
+            skip_reason: Some("throttled".into()),
+        });
+
+        let mut server = ReportCodecKind::Lz4.build();
+        let mut client = ReportCodecKind::Lz4.build();
+        let frame = encode_report_frame(server.as_mut(), &report).unwrap();
+        let decoded = decode_report_frame(client.as_mut(), &frame)
+            .unwrap()
+            .unwrap();
+
+        let status = decoded.codelets[&NodeletId(WorkerId(0), 0)]
+            .status
+            .clone()
+            .unwrap();
+        assert_eq!(status.label, "overheated");
+        assert_eq!(status.status, DefaultStatus::Skipped);
+        assert_eq!(status.code, 42);
+        assert_eq!(status.severity, Severity::Error);
+        assert_eq!(status.skip_reason.as_deref(), Some("throttled"));
+    }
+
+    #[test]
+    fn progress_roundtrip() {
+        let mut report = report(&["a0", "a1"]);
+        report
+            .codelets
+            .get_mut(&NodeletId(WorkerId(0), 0))
+            .unwrap()
+            .progress = Some((0.42, "map 3/7".into()));
+
+        let mut server = ReportCodecKind::Lz4.build();
+        let mut client = ReportCodecKind::Lz4.build();
+        let frame = encode_report_frame(server.as_mut(), &report).unwrap();
+        let decoded = decode_report_frame(client.as_mut(), &frame)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            decoded.codelets[&NodeletId(WorkerId(0), 0)].progress,
+            Some((0.42, "map 3/7".into()))
+        );
+        assert_eq!(decoded.codelets[&NodeletId(WorkerId(0), 1)].progress, None);
+    }
+
+    fn worker_report(worker: u32, names: &[&str]) -> InspectorReport {
+        let mut result = InspectorReport::default();
+        for (id, entry) in report(names).into_vec() {
+            result.push(NodeletId(WorkerId(worker), id.1), entry);
+        }
+        result
+    }
+
+    #[test]
+    fn schedule_topics() {
+        const ADDRESS: &str = "inproc://nodo_runtime/inspector/schedule_topics";
+
+        let mut server =
+            InspectorServer::open_with_codec(ADDRESS, ReportCodecKind::Delta(3)).unwrap();
+        let mut control =
+            InspectorClient::dial_many_with_codec(&[ADDRESS], ReportCodecKind::Delta(3)).unwrap();
+        control.subscribe_schedules(&["control"]).unwrap();
+        let mut full =
+            InspectorClient::dial_many_with_codec(&[ADDRESS], ReportCodecKind::Delta(3)).unwrap();
+        let raw = Socket::new(Protocol::Sub0).unwrap();
+        raw.dial(ADDRESS).unwrap();
+        raw.set_opt::<Subscribe>(Vec::new()).unwrap();
+
+        let publish = |server: &mut InspectorServer| {
+            server
+                .send_schedule_reports(vec![
+                    ("control".into(), worker_report(0, &["pid", "motor"])),
+                    ("vision".into(), worker_report(1, &["camera"])),
+                ])
+                .unwrap()
+        };
+
+        let mut control_count = 0;
+        let mut full_count = 0;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while (control_count < 10 || full_count < 10) && Instant::now() < deadline {
+            publish(&mut server);
+
+            if let Some(SourcedReport { source, report }) = control.try_recv_report().unwrap() {
+                assert_eq!(source, ADDRESS);
+                let mut names: Vec<_> = report.iter().map(|(_, e)| e.name.clone()).collect();
+                names.sort();
+                assert_eq!(names, ["motor", "pid"]);
+                control_count += 1;
+            }
+            if let Some(SourcedReport { report, .. }) = full.try_recv_report().unwrap() {
+                assert_eq!(report.iter().count(), 3);
+                full_count += 1;
+            }
+
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(control_count >= 10);
+        assert!(full_count >= 10);
+
+        // every schedule report is published exactly once
+        while raw.try_recv().is_ok() {}
+        publish(&mut server);
+        std::thread::sleep(Duration::from_millis(50));
+        let mut frame_count = 0;
+        while raw.try_recv().is_ok() {
+            frame_count += 1;
+        }
+        assert_eq!(frame_count, 2);
+    }
+
+    #[test]
+    fn commands() {
+        const ADDRESS: &str = "inproc://nodo_runtime/inspector/commands";
+
+        let mut server = InspectorCommandServer::open(ADDRESS).unwrap();
+        let client = InspectorCommandClient::dial(ADDRESS).unwrap();
+
+        let sent = vec![
+            InspectorCommand::SetStepMode {
+                schedule: "control".into(),
+                mode: StepMode::Manual,
+            },
+            InspectorCommand::StepOnce {
+                schedule: "control".into(),
+            },
+            InspectorCommand::Snoop {
+                channel: "control/pid/rx.in".into(),
+            },
+        ];
+        // sending does not block while the connection is established or the runtime is busy
+        let mut received = Vec::new();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        for command in sent.iter() {
+            while client.send(command).is_err() {
+                assert!(Instant::now() < deadline, "command was not accepted");
+                received.extend(server.try_recv_commands().unwrap());
+            }
+        }
+        while received.len() < sent.len() && Instant::now() < deadline {
+            received.extend(server.try_recv_commands().unwrap());
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(received, sent);
+    }
+}
@@ -1,17 +1,51 @@
 // Copyright 2024 by David Weikersdorfer. All rights reserved.
 
+mod app_info;
+#[cfg(feature = "cli")]
+mod cli;
+mod dead_weight;
+mod dry_run;
 mod executor;
+#[cfg(feature = "test-util")]
+mod golden;
 mod inspector;
+mod manifold;
+mod queue_sizing;
+mod report_codec;
+mod report_delta;
+mod report_schema;
 mod runtime;
 mod schedule_executor;
 mod sleep;
+mod snapshot;
+mod snoop;
 mod state_machine;
 mod statistics;
+mod teardown;
+#[cfg(feature = "test-util")]
+mod test_util;
+mod thread_monitor;
 
+pub use app_info::*;
+#[cfg(feature = "cli")]
+pub use cli::*;
+pub use dead_weight::*;
+pub use dry_run::*;
 pub use executor::*;
+#[cfg(feature = "test-util")]
+pub use golden::*;
 pub use inspector::*;
+pub use manifold::*;
+pub use queue_sizing::*;
+pub use report_codec::*;
+pub use report_delta::*;
+pub use report_schema::*;
 pub use runtime::*;
 pub use schedule_executor::*;
 pub use sleep::*;
+pub use snapshot::*;
+pub use snoop::*;
 pub use state_machine::*;
 pub use statistics::*;
+pub use teardown::*;
+pub use thread_monitor::*;
@@ -0,0 +1,397 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::fmt;
+use nodo::{
+    channels::ChannelUid,
+    codelet::{EndpointInfo, NodeletId},
+};
+use serde::{Deserialize, Serialize};
+
+/// Registry of all codelet instances known to the runtime
+///
+/// Instances are registered when their schedule is added to the runtime. Connection status is
+/// captured at that time, i.e. after all channels were connected.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifold {
+    entries: Vec<ManifoldEntry>,
+}
+
+/// Metadata of a single codelet instance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifoldEntry {
+    pub id: NodeletId,
+    pub name: String,
+    pub type_name: String,
+    pub sequence: String,
+    pub schedule: String,
+    pub rx: Vec<EndpointInfo>,
+    pub tx: Vec<EndpointInfo>,
+}
+
+/// Path which identifies a codelet instance like `schedule/sequence/name`. Empty parts, e.g. of
+/// unnamed sequences, are omitted.
+pub fn instance_path(schedule: &str, sequence: &str, name: &str) -> String {
+    [schedule, sequence, name]
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl ManifoldEntry {
+    /// Path which identifies the instance, see [instance_path]
+    pub fn path(&self) -> String {
+        instance_path(&self.schedule, &self.sequence, &self.name)
+    }
+
+    /// Type name without module path and generic arguments, e.g. `Join` for
+    /// `nodo_std::join::Join<alloc::string::String>`
+    pub fn short_type_name(&self) -> &str {
+        let base = self
+            .type_name
+            .split_once('<')
+            .map_or(self.type_name.as_str(), |(base, _)| base);
+        base.rsplit("::").next().unwrap_or(base)
+    }
+
+    /// Returns true if all RX and TX channels are connected
+    pub fn is_fully_connected(&self) -> bool {
+        self.rx.iter().chain(self.tx.iter()).all(|e| e.is_connected)
+    }
+
+    /// Computes the [ChannelUid] of all endpoints from the schedule, sequence, instance and
+    /// endpoint names
+    pub(crate) fn assign_channel_uids(&mut self) {
+        for (direction, endpoints) in [("rx", &mut self.rx), ("tx", &mut self.tx)] {
+            for endpoint in endpoints.iter_mut() {
+                endpoint.uid = Some(ChannelUid::new(
+                    &self.schedule,
+                    &self.sequence,
+                    &self.name,
+                    &format!("{direction}.{}", endpoint.name),
+                ));
+            }
+        }
+    }
+}
+
+impl Manifold {
+    pub(crate) fn push(&mut self, entry: ManifoldEntry) {
+        for other in self.find_all_by_name(&entry.name) {
+            if other.type_name == entry.type_name {
+                log::warn!(
+                    "Codelet '{}' of type {} is scheduled more than once: '{}' and '{}'. \
+                     Use distinct names to tell them apart in logs and the inspector.",
+                    entry.name,
+                    entry.short_type_name(),
+                    other.path(),
+                    entry.path()
+                );
+            }
+        }
+        self.entries.push(entry);
+    }
+
+    /// All registered instances in the order they were scheduled
+    pub fn entries(&self) -> &[ManifoldEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, id: NodeletId) -> Option<&ManifoldEntry> {
+        self.entries.iter().find(|e| e.id == id)
+    }
+
+    /// Finds the first instance with the given name
+    pub fn find_by_name(&self, name: &str) -> Option<&ManifoldEntry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    /// Finds all instances with the given name
+    pub fn find_all_by_name<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a ManifoldEntry> {
+        self.entries.iter().filter(move |e| e.name == name)
+    }
+
+    /// Finds all instances with given type. Matches either the full type name or the short type
+    /// name without module path and generic arguments.
+    pub fn find_by_type<'a>(
+        &'a self,
+        type_name: &'a str,
+    ) -> impl Iterator<Item = &'a ManifoldEntry> {
+        self.entries
+            .iter()
+            .filter(move |e| e.type_name == type_name || e.short_type_name() == type_name)
+    }
+
+    /// All instances of the given schedule
+    pub fn schedule<'a>(&'a self, schedule: &'a str) -> impl Iterator<Item = &'a ManifoldEntry> {
+        self.entries.iter().filter(move |e| e.schedule == schedule)
+    }
+
+    /// Finds the instance and endpoint with the given channel identifier
+    pub fn find_channel(&self, uid: ChannelUid) -> Option<(&ManifoldEntry, &EndpointInfo)> {
+        self.entries.iter().find_map(|entry| {
+            entry
+                .rx
+                .iter()
+                .chain(entry.tx.iter())
+                .find(|e| e.uid == Some(uid))
+                .map(|endpoint| (entry, endpoint))
+        })
+    }
+}
+
+/// One line per instance like `schedule/sequence/name (Type) rx: [in] tx: [out]`. Unnamed
+/// sequences are omitted and unconnected endpoints are marked with a `?`.
+impl fmt::Display for Manifold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn endpoints(endpoints: &[EndpointInfo]) -> String {
+            endpoints
+                .iter()
+                .map(|e| {
+                    if e.is_connected {
+                        e.name.to_string()
+                    } else {
+                        format!("{}?", e.name)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+
+        for entry in self.entries.iter() {
+            writeln!(
+                f,
+                "{} ({}) rx: [{}] tx: [{}]",
+                entry.path(),
+                entry.short_type_name(),
+                endpoints(&entry.rx),
+                endpoints(&entry.tx)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Executor, Manifold};
+    use core::time::Duration;
+    use nodo::{
+        channels::{message_type_hash, ChannelContract, ChannelUid},
+        codelet::{CodeletInstance, EndpointInfo, ScheduleBuilder},
+        prelude::*,
+    };
+
+    struct Source;
+
+    impl Codelet for Source {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = ();
+        type Tx = DoubleBufferTx<u32>;
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            (
+                (),
+                DoubleBufferTx::new(1).with_contract(ChannelContract {
+                    lossless: true,
+                    ..Default::default()
+                }),
+            )
+        }
+
+        fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            SKIPPED
+        }
+    }
+
+    struct Relay<T>(core::marker::PhantomData<T>);
+
+    impl<T: Clone + Send + Sync> Codelet for Relay<T> {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = DoubleBufferRx<T>;
+        type Tx = DoubleBufferTx<T>;
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            (DoubleBufferRx::new_auto_size(), DoubleBufferTx::new(1))
+        }
+
+        fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            SKIPPED
+        }
+    }
+
+    fn relay(name: &str) -> CodeletInstance<Relay<u32>> {
+        Relay(core::marker::PhantomData).into_instance(name, ())
+    }
+
+    fn endpoint(
+        schedule: &str,
+        sequence: &str,
+        codelet: &str,
+        channel: &'static str,
+        is_connected: bool,
+    ) -> Vec<EndpointInfo> {
+        let type_hash = Some(message_type_hash::<u32>());
+        let name = channel.split_once('.').unwrap().1;
+        vec![EndpointInfo {
+            name: name.into(),
+            is_connected,
+            contract: None,
+            type_hash,
+            uid: Some(ChannelUid::new(schedule, sequence, codelet, channel)),
+        }]
+    }
+
+    /// Two schedules with a source and a relay connected and another unconnected relay
+    fn two_schedules() -> Executor {
+        let mut source = Source.into_instance("source", ());
+        let mut relay_1 = relay("relay_1");
+        let relay_2 = relay("relay_2");
+        source.tx.connect(&mut relay_1.rx).unwrap();
+
+        let mut exec = Executor::new();
+        exec.push(
+            ScheduleBuilder::new()
+                .with_name("input")
+                .with(source)
+                .with(Sequence::new().with_name("relays").with(relay_1))
+                .into(),
+        );
+        exec.push(
+            ScheduleBuilder::new()
+                .with_name("output")
+                .with(relay_2)
+                .into(),
+        );
+        exec
+    }
+
+    fn channel_uids(manifold: &Manifold) -> Vec<ChannelUid> {
+        manifold
+            .entries()
+            .iter()
+            .flat_map(|e| e.rx.iter().chain(e.tx.iter()))
+            .map(|e| e.uid.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_manifold_two_schedules() {
+        let mut exec = two_schedules();
+        let manifold = exec.manifold().clone();
+        exec.request_stop();
+        exec.join().unwrap();
+
+        assert_eq!(manifold.len(), 3);
+        assert_eq!(
+            manifold
+                .entries()
+                .iter()
+                .map(|e| (e.schedule.as_str(), e.sequence.as_str(), e.name.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                ("input", "", "source"),
+                ("input", "relays", "relay_1"),
+                ("output", "", "relay_2"),
+            ]
+        );
+
+        assert_eq!(
+            manifold.entries()[1].path(),
+            "input/relays/relay_1".to_string()
+        );
+
+        let source = manifold.find_by_name("source").unwrap();
+        assert_eq!(source.path(), "input/source");
+        assert_eq!(source.short_type_name(), "Source");
+        assert!(source.rx.is_empty());
+        assert_eq!(source.tx.len(), 1);
+        assert!(source.tx[0].is_connected);
+        assert!(source.tx[0].contract.as_ref().unwrap().lossless);
+        assert!(source.is_fully_connected());
+        assert_eq!(manifold.get(source.id), Some(source));
+
+        let relay_1 = manifold.find_by_name("relay_1").unwrap();
+        assert_eq!(
+            relay_1.rx,
+            endpoint("input", "relays", "relay_1", "rx.in", true)
+        );
+        assert_eq!(
+            relay_1.tx,
+            endpoint("input", "relays", "relay_1", "tx.out", false)
+        );
+        assert_ne!(relay_1.id, source.id);
+
+        let relay_2 = manifold.find_by_name("relay_2").unwrap();
+        assert_eq!(
+            relay_2.rx,
+            endpoint("output", "", "relay_2", "rx.in", false)
+        );
+        assert!(!relay_2.is_fully_connected());
+
+        assert_eq!(manifold.find_by_type("Relay").count(), 2);
+        assert_eq!(manifold.find_by_type(&relay_2.type_name).count(), 2);
+        assert_eq!(manifold.schedule("output").count(), 1);
+        assert!(manifold.find_by_name("missing").is_none());
+    }
+
+    #[test]
+    fn test_channel_uid() {
+        let mut exec = two_schedules();
+        let mut other = two_schedules();
+        let uids = channel_uids(exec.manifold());
+        other.request_stop();
+        other.join().unwrap();
+
+        // stable across constructions of the same graph
+        assert_eq!(uids, channel_uids(other.manifold()));
+
+        // unique per endpoint
+        let mut unique = uids.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), uids.len());
+
+        let relay_1 = exec.manifold().find_by_name("relay_1").unwrap();
+        let rx_uid = relay_1.rx[0].uid.unwrap();
+        let (entry, endpoint) = exec.manifold().find_channel(rx_uid).unwrap();
+        assert_eq!(
+            rx_uid,
+            relay("relay_1").rx_channel_uid("input", "relays", 0)
+        );
+        assert_eq!(
+            (entry.name.as_str(), endpoint.name.as_ref()),
+            ("relay_1", "in")
+        );
+
+        // the uid is reported with the channel statistics
+        let reported = loop {
+            let report = exec.report();
+            let stats = report
+                .iter()
+                .find(|(_, r)| r.name == "relay_1")
+                .and_then(|(_, r)| r.statistics.rx_channels.first().cloned());
+            if let Some(stats) = stats {
+                break stats;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        };
+        assert_eq!(reported.uid, Some(rx_uid));
+
+        exec.request_stop();
+        exec.join().unwrap();
+    }
+}
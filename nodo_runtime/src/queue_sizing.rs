@@ -0,0 +1,227 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::{InspectorReport, Manifold};
+use core::fmt;
+use nodo::codelet::RxChannelStatistics;
+
+/// Fraction of lost messages above which a channel is considered undersized
+pub const UNDERSIZED_LOSS_RATIO: f64 = 0.01;
+
+/// Kind of a queue size recommendation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueSizeAdvice {
+    /// The queue never came close to its capacity
+    Oversized,
+
+    /// The queue forgot or rejected a significant fraction of messages
+    Undersized,
+}
+
+/// Recommended capacity for a receiving channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueSizeRecommendation {
+    pub advice: QueueSizeAdvice,
+    pub suggested_capacity: usize,
+}
+
+/// Recommends a capacity for a channel based on its statistics or None if the capacity is fine
+///
+/// Channels which grow as needed, have a capacity of one (i.e. only keep the latest message) or
+/// never received a message are not considered.
+pub fn recommend_queue_size(stats: &RxChannelStatistics) -> Option<QueueSizeRecommendation> {
+    let capacity = stats.capacity?;
+    if capacity <= 1 || stats.received == 0 {
+        return None;
+    }
+
+    if loss_ratio(stats) > UNDERSIZED_LOSS_RATIO {
+        return Some(QueueSizeRecommendation {
+            advice: QueueSizeAdvice::Undersized,
+            suggested_capacity: 2 * capacity,
+        });
+    }
+
+    let lost = stats.forgotten + stats.rejected;
+    let suggested_capacity = stats.high_water_mark.max(1).next_power_of_two();
+    (lost == 0 && 2 * suggested_capacity <= capacity).then_some(QueueSizeRecommendation {
+        advice: QueueSizeAdvice::Oversized,
+        suggested_capacity,
+    })
+}
+
+/// Fraction of messages which were forgotten or rejected
+fn loss_ratio(stats: &RxChannelStatistics) -> f64 {
+    let lost = stats.forgotten + stats.rejected;
+    let total = stats.received + lost;
+    if total == 0 {
+        0.0
+    } else {
+        lost as f64 / total as f64
+    }
+}
+
+/// Recommendation for a single channel
+#[derive(Debug, Clone)]
+pub struct QueueSizingEntry {
+    /// Name of the codelet instance
+    pub codelet: String,
+
+    pub statistics: RxChannelStatistics,
+    pub recommendation: QueueSizeRecommendation,
+}
+
+impl fmt::Display for QueueSizingEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stats = &self.statistics;
+        write!(
+            f,
+            "channel {}/{}: capacity {}, ",
+            self.codelet,
+            stats.name,
+            stats.capacity.unwrap_or_default()
+        )?;
+        match self.recommendation.advice {
+            QueueSizeAdvice::Oversized => write!(
+                f,
+                "observed max {}, never forgot - consider {}",
+                stats.high_water_mark, self.recommendation.suggested_capacity
+            ),
+            QueueSizeAdvice::Undersized => write!(
+                f,
+                "forgot {:.0}% of messages - consider {} or a faster consumer",
+                100.0 * loss_ratio(stats),
+                self.recommendation.suggested_capacity
+            ),
+        }
+    }
+}
+
+/// Queue size recommendations for all channels of a run, see `Runtime::queue_sizing_report`
+#[derive(Debug, Clone, Default)]
+pub struct QueueSizingReport {
+    pub entries: Vec<QueueSizingEntry>,
+}
+
+impl QueueSizingReport {
+    /// Recommendations for all connected RX channels in the report
+    pub fn new(report: &InspectorReport, manifold: &Manifold) -> Self {
+        let mut entries = Vec::new();
+        for (id, codelet) in report.iter() {
+            let rx = manifold.get(*id).map(|entry| &entry.rx);
+            for stats in codelet.statistics.rx_channels.iter() {
+                let is_connected = rx.is_none_or(|rx| {
+                    rx.iter()
+                        .any(|endpoint| *endpoint.name == *stats.name && endpoint.is_connected)
+                });
+                if !is_connected {
+                    continue;
+                }
+                if let Some(recommendation) = recommend_queue_size(stats) {
+                    entries.push(QueueSizingEntry {
+                        codelet: codelet.name.to_string(),
+                        statistics: stats.clone(),
+                        recommendation,
+                    });
+                }
+            }
+        }
+        entries.sort_by(|a, b| {
+            (&a.codelet, &a.statistics.name).cmp(&(&b.codelet, &b.statistics.name))
+        });
+        Self { entries }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn pretty_print(&self) {
+        if self.is_empty() {
+            return;
+        }
+
+        let separator = format!(
+            "+{}+{}+{}+{}+{}+{}+",
+            "-".repeat(34),
+            "-".repeat(10),
+            "-".repeat(10),
+            "-".repeat(10),
+            "-".repeat(12),
+            "-".repeat(10),
+        );
+        println!();
+        println!("{separator}");
+        println!(
+            "| {:32} | {:>8} | {:>8} | {:>8} | {:10} | {:>8} |",
+            "CHANNEL", "Capacity", "Max", "Lost", "Advice", "Suggest"
+        );
+        println!("{separator}");
+        for entry in self.entries.iter() {
+            let stats = &entry.statistics;
+            println!(
+                "| {:32} | {:>8} | {:>8} | {:>7.1}% | {:10} | {:>8} |",
+                format!("{}/{}", entry.codelet, stats.name),
+                stats.capacity.unwrap_or_default(),
+                stats.high_water_mark,
+                100.0 * loss_ratio(stats),
+                format!("{:?}", entry.recommendation.advice),
+                entry.recommendation.suggested_capacity,
+            );
+        }
+        println!("{separator}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{recommend_queue_size, QueueSizeAdvice, QueueSizeRecommendation};
+    use nodo::codelet::RxChannelStatistics;
+
+    fn stats(
+        capacity: Option<usize>,
+        high_water_mark: usize,
+        forgotten: u64,
+    ) -> RxChannelStatistics {
+        RxChannelStatistics {
+            name: "in".into(),
+            capacity,
+            high_water_mark,
+            received: 100,
+            forgotten,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_recommend_queue_size() {
+        assert_eq!(
+            recommend_queue_size(&stats(Some(24), 3, 0)),
+            Some(QueueSizeRecommendation {
+                advice: QueueSizeAdvice::Oversized,
+                suggested_capacity: 4
+            })
+        );
+        assert_eq!(
+            recommend_queue_size(&stats(Some(8), 8, 12)),
+            Some(QueueSizeRecommendation {
+                advice: QueueSizeAdvice::Undersized,
+                suggested_capacity: 16
+            })
+        );
+
+        // well sized
+        assert_eq!(recommend_queue_size(&stats(Some(4), 3, 0)), None);
+        assert_eq!(recommend_queue_size(&stats(Some(8), 5, 1)), None);
+
+        // latest value channels, growing channels and unused channels
+        assert_eq!(recommend_queue_size(&stats(Some(1), 1, 50)), None);
+        assert_eq!(recommend_queue_size(&stats(None, 3, 0)), None);
+        assert_eq!(
+            recommend_queue_size(&RxChannelStatistics {
+                received: 0,
+                ..stats(Some(24), 0, 0)
+            }),
+            None
+        );
+    }
+}
@@ -0,0 +1,458 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::{decode_report, encode_report, DeltaCodec, InspectorReport};
+use eyre::Result;
+use lz4_flex::{compress_prepend_size, decompress_size_prepended};
+use nodo_core::{Name, NameTable};
+use std::{fmt, str::FromStr};
+
+/// Encodes inspector reports for sending them over the wire
+///
+/// Codecs may keep state between frames, thus the server and every client need their own codec
+/// instance. Frames start with the codec ID so that mismatching configurations are detected.
+pub trait ReportCodec: Send {
+    /// Unique identifier of the codec written as the first byte of every frame
+    fn id(&self) -> u8;
+
+    /// Encodes a report into a frame payload
+    fn encode(&mut self, report: &InspectorReport) -> Result<Vec<u8>>;
+
+    /// Decodes a frame payload. Returns None if the frame cannot be decoded yet, for example
+    /// because it depends on an earlier frame which was not received.
+    fn decode(&mut self, payload: &[u8]) -> Result<Option<InspectorReport>>;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReportCodecError {
+    #[error("received an empty report frame")]
+    EmptyFrame,
+
+    #[error(
+        "report codec mismatch: expected `{expected}` but received `{actual}`. \
+         Inspector server and client must be configured with the same codec."
+    )]
+    Mismatch { expected: String, actual: String },
+
+    #[error("malformed report frame: {0}")]
+    Malformed(&'static str),
+
+    #[error(
+        "report schema version {version} is not supported anymore. The oldest supported \
+         version is {min}."
+    )]
+    UnsupportedSchema { version: u16, min: u16 },
+}
+
+/// Built-in report codecs
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ReportCodecKind {
+    /// Versioned report encoding + lz4
+    #[default]
+    Lz4,
+
+    /// Versioned report encoding + zstd with given compression level
+    Zstd(i32),
+
+    /// Sends a full report every N frames and field-level deltas against the last full report in
+    /// between, see [DeltaCodec]
+    Delta(u32),
+
+    /// Sends every name once in a string table and indices into the table in the report + lz4
+    StringTable,
+}
+
+impl ReportCodecKind {
+    const LZ4_ID: u8 = 1;
+    const ZSTD_ID: u8 = 2;
+    pub(crate) const DELTA_ID: u8 = 3;
+    const STRING_TABLE_ID: u8 = 4;
+
+    /// Creates a new codec instance
+    pub fn build(&self) -> Box<dyn ReportCodec> {
+        match *self {
+            ReportCodecKind::Lz4 => Box::new(Lz4Codec),
+            ReportCodecKind::Zstd(level) => Box::new(ZstdCodec { level }),
+            ReportCodecKind::Delta(keyframe_interval) => {
+                Box::new(DeltaCodec::new(keyframe_interval))
+            }
+            ReportCodecKind::StringTable => Box::new(StringTableCodec),
+        }
+    }
+
+    fn id_name(id: u8) -> String {
+        match id {
+            Self::LZ4_ID => "lz4".into(),
+            Self::ZSTD_ID => "zstd".into(),
+            Self::DELTA_ID => "delta".into(),
+            Self::STRING_TABLE_ID => "strtab".into(),
+            other => format!("unknown codec {other}"),
+        }
+    }
+}
+
+impl fmt::Display for ReportCodecKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReportCodecKind::Lz4 => write!(f, "lz4"),
+            ReportCodecKind::Zstd(level) => write!(f, "zstd:{level}"),
+            ReportCodecKind::Delta(keyframe_interval) => write!(f, "delta:{keyframe_interval}"),
+            ReportCodecKind::StringTable => write!(f, "strtab"),
+        }
+    }
+}
+
+/// Parses `lz4`, `zstd`, `zstd:<level>`, `delta`, `delta:<keyframe interval>` or `strtab`
+impl FromStr for ReportCodecKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        match name {
+            "lz4" if arg.is_none() => Ok(ReportCodecKind::Lz4),
+            "zstd" => Ok(ReportCodecKind::Zstd(parse_codec_arg(arg, 3)?)),
+            "delta" => Ok(ReportCodecKind::Delta(parse_codec_arg(arg, 10)?)),
+            "strtab" if arg.is_none() => Ok(ReportCodecKind::StringTable),
+            _ => Err(format!("unknown report codec `{s}`")),
+        }
+    }
+}
+
+fn parse_codec_arg<T: FromStr>(arg: Option<&str>, default: T) -> Result<T, String> {
+    arg.map_or(Ok(default), |a| {
+        a.parse()
+            .map_err(|_| format!("invalid codec argument `{a}`"))
+    })
+}
+
+/// Encodes a report into a frame including the codec header
+pub fn encode_report_frame(
+    codec: &mut dyn ReportCodec,
+    report: &InspectorReport,
+) -> Result<Vec<u8>> {
+    let payload = codec.encode(report)?;
+    let mut frame = Vec::with_capacity(payload.len() + 1);
+    frame.push(codec.id());
+    frame.extend_from_slice(&payload);
+    Ok(frame)
+}
+
+/// Decodes a frame created with [encode_report_frame]
+pub fn decode_report_frame(
+    codec: &mut dyn ReportCodec,
+    frame: &[u8],
+) -> Result<Option<InspectorReport>> {
+    let (&id, payload) = frame.split_first().ok_or(ReportCodecError::EmptyFrame)?;
+    if id != codec.id() {
+        return Err(ReportCodecError::Mismatch {
+            expected: ReportCodecKind::id_name(codec.id()),
+            actual: ReportCodecKind::id_name(id),
+        }
+        .into());
+    }
+    codec.decode(payload)
+}
+
+/// Versioned report encoding + lz4, see [encode_report]
+pub struct Lz4Codec;
+
+impl ReportCodec for Lz4Codec {
+    fn id(&self) -> u8 {
+        ReportCodecKind::LZ4_ID
+    }
+
+    fn encode(&mut self, report: &InspectorReport) -> Result<Vec<u8>> {
+        Ok(compress_prepend_size(&encode_report(report)?))
+    }
+
+    fn decode(&mut self, payload: &[u8]) -> Result<Option<InspectorReport>> {
+        Ok(Some(decode_report(&decompress_size_prepended(payload)?)?))
+    }
+}
+
+/// Versioned report encoding + zstd
+pub struct ZstdCodec {
+    pub level: i32,
+}
+
+impl ReportCodec for ZstdCodec {
+    fn id(&self) -> u8 {
+        ReportCodecKind::ZSTD_ID
+    }
+
+    fn encode(&mut self, report: &InspectorReport) -> Result<Vec<u8>> {
+        Ok(zstd::encode_all(&encode_report(report)?[..], self.level)?)
+    }
+
+    fn decode(&mut self, payload: &[u8]) -> Result<Option<InspectorReport>> {
+        Ok(Some(decode_report(&zstd::decode_all(payload)?)?))
+    }
+}
+
+/// Replaces names in the report by indices into a string table sent with the frame, see
+/// [NameTable]
+///
+/// Reports repeat sequence, schedule, type and channel names for every codelet. With the table
+/// each name is encoded only once per frame.
+///
+/// Frame payload: lz4 of [table size: u32 LE][table][report encoding with name indices]
+///
+/// Unknown fields of reports from newer versions are not preserved by this codec, see
+/// [UnknownFields](nodo_core::UnknownFields).
+pub struct StringTableCodec;
+
+impl ReportCodec for StringTableCodec {
+    fn id(&self) -> u8 {
+        ReportCodecKind::STRING_TABLE_ID
+    }
+
+    fn encode(&mut self, report: &InspectorReport) -> Result<Vec<u8>> {
+        let (encoding, table) = NameTable::encode(|| encode_report(report));
+        let table = rmp_serde::to_vec(table.names())?;
+
+        let mut data = Vec::with_capacity(4 + table.len() + encoding.as_ref().map_or(0, Vec::len));
+        data.extend_from_slice(&(table.len() as u32).to_le_bytes());
+        data.extend_from_slice(&table);
+        data.extend_from_slice(&encoding?);
+        Ok(compress_prepend_size(&data))
+    }
+
+    fn decode(&mut self, payload: &[u8]) -> Result<Option<InspectorReport>> {
+        let data = decompress_size_prepended(payload)?;
+        let (size, rest) = data
+            .split_first_chunk::<4>()
+            .ok_or(ReportCodecError::Malformed("missing string table size"))?;
+        let size = u32::from_le_bytes(*size) as usize;
+        if rest.len() < size {
+            return Err(ReportCodecError::Malformed("string table too short").into());
+        }
+        let (table, encoding) = rest.split_at(size);
+
+        let table = NameTable::from_names(rmp_serde::from_slice::<Vec<Name>>(table)?);
+        Ok(Some(table.decode(|| decode_report(encoding))?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        decode_report_frame, encode_report, encode_report_frame, AppInfo, DeltaCodec,
+        InspectorCodeletReport, InspectorReport, ReportCodecError, ReportCodecKind,
+    };
+    use core::time::Duration;
+    use lz4_flex::decompress_size_prepended;
+    use nodo::codelet::{NodeletId, Statistics, Transition, WorkerId};
+    use nodo_core::{Name, UnknownValue};
+    use std::time::SystemTime;
+
+    fn report(step_count: u32) -> InspectorReport {
+        let mut report = InspectorReport::default();
+        for i in 0..20 {
+            let mut statistics = Statistics::new();
+            for k in 0..step_count {
+                statistics.transitions[Transition::Step]
+                    .duration
+                    .push(Duration::from_micros((i * 7 + k) as u64));
+            }
+            report.push(
+                NodeletId(WorkerId(0), i),
+                InspectorCodeletReport {
+                    sequence: "seq".into(),
+                    name: format!("codelet_{i}").into(),
+                    typename: "my_crate::MyCodelet".into(),
+                    status: None,
+                    statistics,
+                    is_warmup: false,
+                    labels: Vec::new(),
+                    suspected_inactive: false,
+                    suspend_resume_count: 0,
+                    deadline: None,
+                    start_diagnostics: Vec::new(),
+                    schedule: Default::default(),
+                    progress: None,
+                    unknown: Default::default(),
+                },
+            );
+        }
+        report.set_app_info(AppInfo {
+            start_time: SystemTime::UNIX_EPOCH,
+            ..AppInfo::new("test", "1.0.0")
+        });
+        report
+    }
+
+    fn assert_same(a: &InspectorReport, b: &InspectorReport) {
+        assert!(encode_report(a).unwrap() == encode_report(b).unwrap());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for kind in [
+            ReportCodecKind::Lz4,
+            ReportCodecKind::Zstd(3),
+            ReportCodecKind::Delta(3),
+            ReportCodecKind::StringTable,
+        ] {
+            let mut server = kind.build();
+            let mut client = kind.build();
+            for step_count in 0..10 {
+                let expected = report(step_count);
+                let frame = encode_report_frame(server.as_mut(), &expected).unwrap();
+                let actual = decode_report_frame(client.as_mut(), &frame)
+                    .unwrap()
+                    .unwrap();
+                assert_same(&actual, &expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_delta_smaller_than_keyframe() {
+        let mut codec = ReportCodecKind::Delta(10).build();
+        let key = encode_report_frame(codec.as_mut(), &report(5)).unwrap();
+        let delta = encode_report_frame(codec.as_mut(), &report(6)).unwrap();
+
+        // compare the data after the codec ID and the delta frame header
+        let header = 1 + DeltaCodec::HEADER_SIZE;
+        assert_eq!(key[0], ReportCodecKind::DELTA_ID);
+        assert_eq!(delta[0], ReportCodecKind::DELTA_ID);
+        let data = |frame: &[u8]| decompress_size_prepended(&frame[header..]).unwrap().len();
+        assert!(
+            data(&delta) < data(&key) / 2,
+            "{} vs {}",
+            data(&delta),
+            data(&key)
+        );
+        assert!(delta.len() < key.len(), "{} vs {}", delta.len(), key.len());
+    }
+
+    #[test]
+    fn test_delta_layout_change() {
+        let mut server = ReportCodecKind::Delta(10).build();
+        let mut client = ReportCodecKind::Delta(10).build();
+
+        let key = encode_report_frame(server.as_mut(), &report(3)).unwrap();
+        decode_report_frame(client.as_mut(), &key).unwrap().unwrap();
+
+        // fields are inserted in the middle of the report and new names appear
+        let mut expected = InspectorReport::default();
+        for (i, (id, mut entry)) in report(4).into_vec().into_iter().enumerate() {
+            if i < 3 {
+                entry.labels.push("new label".into());
+            }
+            expected.push(id, entry);
+        }
+        let delta = encode_report_frame(server.as_mut(), &expected).unwrap();
+        let actual = decode_report_frame(client.as_mut(), &delta)
+            .unwrap()
+            .unwrap();
+        assert_same(&actual, &expected);
+    }
+
+    #[test]
+    fn test_delta_dropped_frames() {
+        let mut server = ReportCodecKind::Delta(3).build();
+        let mut client = ReportCodecKind::Delta(3).build();
+
+        // frames 0 and 3 are keyframes
+        let frames = (0..6)
+            .map(|i| encode_report_frame(server.as_mut(), &report(i)).unwrap())
+            .collect::<Vec<_>>();
+
+        // the first keyframe is lost: deltas can not be decoded
+        assert!(decode_report_frame(client.as_mut(), &frames[1])
+            .unwrap()
+            .is_none());
+        assert!(decode_report_frame(client.as_mut(), &frames[2])
+            .unwrap()
+            .is_none());
+
+        // decoding resumes with the next keyframe
+        let actual = decode_report_frame(client.as_mut(), &frames[3])
+            .unwrap()
+            .unwrap();
+        assert_same(&actual, &report(3));
+
+        // a lost delta frame does not affect the next one
+        let actual = decode_report_frame(client.as_mut(), &frames[5])
+            .unwrap()
+            .unwrap();
+        assert_same(&actual, &report(5));
+    }
+
+    #[test]
+    fn test_string_table() {
+        let expected = report(3);
+        let mut server = ReportCodecKind::StringTable.build();
+        let mut client = ReportCodecKind::StringTable.build();
+        let frame = encode_report_frame(server.as_mut(), &expected).unwrap();
+
+        // names are interned when decoded
+        let actual = decode_report_frame(client.as_mut(), &frame)
+            .unwrap()
+            .unwrap();
+        assert_same(&actual, &expected);
+        let sequences = actual
+            .iter()
+            .map(|(_, entry)| &entry.sequence)
+            .collect::<Vec<_>>();
+        assert!(sequences.iter().all(|s| Name::ptr_eq(s, sequences[0])));
+
+        // repeated names are sent only once
+        let plain = encode_report_frame(ReportCodecKind::Lz4.build().as_mut(), &expected).unwrap();
+        let uncompressed = |frame: &[u8]| decompress_size_prepended(&frame[1..]).unwrap().len();
+        assert!(
+            uncompressed(&frame) < uncompressed(&plain),
+            "{} vs {}",
+            uncompressed(&frame),
+            uncompressed(&plain)
+        );
+    }
+
+    /// Names inside unknown fields would be stale indices into the string table of the sender
+    #[test]
+    fn test_string_table_drops_unknown_fields() {
+        let mut expected = report(3);
+        for entry in expected.codelets.values_mut() {
+            entry.unknown.insert("future_name", UnknownValue::U64(7));
+        }
+
+        let frame = encode_report_frame(ReportCodecKind::Lz4.build().as_mut(), &expected).unwrap();
+        let actual = decode_report_frame(ReportCodecKind::Lz4.build().as_mut(), &frame)
+            .unwrap()
+            .unwrap();
+        assert!(actual.iter().all(|(_, entry)| entry.unknown.len() == 1));
+
+        let frame =
+            encode_report_frame(ReportCodecKind::StringTable.build().as_mut(), &expected).unwrap();
+        let actual = decode_report_frame(ReportCodecKind::StringTable.build().as_mut(), &frame)
+            .unwrap()
+            .unwrap();
+        assert!(actual.iter().all(|(_, entry)| entry.unknown.is_empty()));
+    }
+
+    #[test]
+    fn test_codec_mismatch() {
+        let mut server = ReportCodecKind::Zstd(3).build();
+        let mut client = ReportCodecKind::Lz4.build();
+
+        let frame = encode_report_frame(server.as_mut(), &report(1)).unwrap();
+        let err = decode_report_frame(client.as_mut(), &frame).err().unwrap();
+        assert!(matches!(
+            err.downcast_ref::<ReportCodecError>(),
+            Some(ReportCodecError::Mismatch { expected, actual }) if expected == "lz4" && actual == "zstd"
+        ));
+    }
+
+    #[test]
+    fn test_parse_codec_kind() {
+        assert_eq!("lz4".parse(), Ok(ReportCodecKind::Lz4));
+        assert_eq!("zstd".parse(), Ok(ReportCodecKind::Zstd(3)));
+        assert_eq!("zstd:9".parse(), Ok(ReportCodecKind::Zstd(9)));
+        assert_eq!("delta:5".parse(), Ok(ReportCodecKind::Delta(5)));
+        assert_eq!("strtab".parse(), Ok(ReportCodecKind::StringTable));
+        assert!("brotli".parse::<ReportCodecKind>().is_err());
+    }
+}
@@ -0,0 +1,582 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::{decode_report, encode_report, InspectorReport, ReportCodec, ReportCodecError};
+use eyre::Result;
+use lz4_flex::{compress_prepend_size, decompress_size_prepended};
+use std::collections::HashMap;
+
+/// Sends a full report (keyframe) every N frames and field-level deltas against the last keyframe
+/// otherwise
+///
+/// The report encoding (see [encode_report]) is split into its MessagePack fields. Keyframes send
+/// a dictionary with every string in the report, like field names and codelet names, followed by
+/// all fields with strings replaced by their index in the dictionary. Delta frames only send the
+/// difference of every field to the same field in the keyframe: runs of unchanged fields are
+/// skipped, changed integers are sent as zigzag varint of their difference and other changed
+/// fields are sent in full. Statistics counters mostly increase slowly, thus deltas are small.
+///
+/// Lost delta frames do not affect later frames; after a lost keyframe decoding resumes with the
+/// next keyframe.
+///
+/// Frame payload: [kind: u8 (0 = keyframe, 1 = delta)][keyframe sequence: u32 LE][lz4 data]
+pub struct DeltaCodec {
+    keyframe_interval: u32,
+    frames_since_keyframe: u32,
+    keyframe_seq: u32,
+    keyframe: Option<Keyframe>,
+}
+
+/// Fields of the last keyframe and its string dictionary
+struct Keyframe {
+    schema_version: u16,
+    fields: Vec<Field>,
+    dictionary: Vec<String>,
+    dictionary_index: HashMap<String, u64>,
+}
+
+impl Keyframe {
+    fn new(schema_version: u16, fields: Vec<Field>, dictionary: Vec<String>) -> Self {
+        let dictionary_index = dictionary
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.clone(), i as u64))
+            .collect();
+        Self {
+            schema_version,
+            fields,
+            dictionary,
+            dictionary_index,
+        }
+    }
+}
+
+impl DeltaCodec {
+    const KEYFRAME: u8 = 0;
+    const DELTA: u8 = 1;
+
+    /// Size of the frame payload header before the compressed data
+    pub const HEADER_SIZE: usize = 5;
+
+    pub fn new(keyframe_interval: u32) -> Self {
+        Self {
+            keyframe_interval: keyframe_interval.max(1),
+            frames_since_keyframe: 0,
+            keyframe_seq: 0,
+            keyframe: None,
+        }
+    }
+
+    fn frame(kind: u8, seq: u32, data: &[u8]) -> Vec<u8> {
+        let mut payload = vec![kind];
+        payload.extend_from_slice(&seq.to_le_bytes());
+        payload.extend_from_slice(&compress_prepend_size(data));
+        payload
+    }
+}
+
+impl ReportCodec for DeltaCodec {
+    fn id(&self) -> u8 {
+        crate::ReportCodecKind::DELTA_ID
+    }
+
+    fn encode(&mut self, report: &InspectorReport) -> Result<Vec<u8>> {
+        let (schema_version, fields) = Field::split_report(&encode_report(report)?)?;
+
+        match self.keyframe.as_ref() {
+            Some(keyframe)
+                if self.frames_since_keyframe < self.keyframe_interval
+                    && keyframe.schema_version == schema_version =>
+            {
+                self.frames_since_keyframe += 1;
+                let data = encode_delta(keyframe, &fields);
+                Ok(Self::frame(Self::DELTA, self.keyframe_seq, &data))
+            }
+            _ => {
+                self.keyframe_seq = self.keyframe_seq.wrapping_add(1);
+                self.frames_since_keyframe = 1;
+                let (data, keyframe) = encode_keyframe(schema_version, fields);
+                self.keyframe = Some(keyframe);
+                Ok(Self::frame(Self::KEYFRAME, self.keyframe_seq, &data))
+            }
+        }
+    }
+
+    fn decode(&mut self, payload: &[u8]) -> Result<Option<InspectorReport>> {
+        if payload.len() < Self::HEADER_SIZE {
+            return Err(ReportCodecError::Malformed("delta frame header too short").into());
+        }
+        let kind = payload[0];
+        let seq = u32::from_le_bytes(payload[1..Self::HEADER_SIZE].try_into().unwrap());
+        let data = decompress_size_prepended(&payload[Self::HEADER_SIZE..])?;
+
+        match kind {
+            Self::KEYFRAME => {
+                let keyframe = decode_keyframe(&data)?;
+                let report = decode_report(&Field::join_report(
+                    keyframe.schema_version,
+                    &keyframe.fields,
+                ))?;
+                self.keyframe_seq = seq;
+                self.keyframe = Some(keyframe);
+                Ok(Some(report))
+            }
+            Self::DELTA => match self.keyframe.as_ref() {
+                Some(keyframe) if self.keyframe_seq == seq => {
+                    let fields = decode_delta(keyframe, &data)?;
+                    Ok(Some(decode_report(&Field::join_report(
+                        keyframe.schema_version,
+                        &fields,
+                    ))?))
+                }
+                // keyframe was lost: wait for the next one
+                _ => Ok(None),
+            },
+            _ => Err(ReportCodecError::Malformed("unknown delta frame kind").into()),
+        }
+    }
+}
+
+/// A MessagePack value or the header of a MessagePack array or map
+#[derive(Clone, PartialEq)]
+enum Field {
+    Nil,
+    Bool(bool),
+    UInt(u64),
+    Int(i64),
+    F32(u32),
+    F64(u64),
+    Str(String),
+    Bin(Vec<u8>),
+    Array(u32),
+    Map(u32),
+    Ext(i8, Vec<u8>),
+}
+
+impl Field {
+    /// Splits a report encoding into its schema version and MessagePack fields
+    fn split_report(buffer: &[u8]) -> Result<(u16, Vec<Field>)> {
+        let (version, mut rest) = buffer
+            .split_first_chunk::<2>()
+            .ok_or(ReportCodecError::Malformed("missing report schema version"))?;
+        let mut fields = Vec::new();
+        while !rest.is_empty() {
+            fields.push(Field::read(&mut rest)?);
+        }
+        Ok((u16::from_le_bytes(*version), fields))
+    }
+
+    /// Inverse of [Field::split_report]
+    fn join_report(schema_version: u16, fields: &[Field]) -> Vec<u8> {
+        let mut buffer = schema_version.to_le_bytes().to_vec();
+        for field in fields {
+            field.write(&mut buffer);
+        }
+        buffer
+    }
+
+    fn read(rd: &mut &[u8]) -> Result<Field> {
+        use rmp::Marker;
+
+        Ok(match Marker::from_u8(take_be(rd, 1)? as u8) {
+            Marker::Null => Field::Nil,
+            Marker::True => Field::Bool(true),
+            Marker::False => Field::Bool(false),
+            Marker::FixPos(v) => Field::UInt(v as u64),
+            Marker::U8 => Field::UInt(take_be(rd, 1)?),
+            Marker::U16 => Field::UInt(take_be(rd, 2)?),
+            Marker::U32 => Field::UInt(take_be(rd, 4)?),
+            Marker::U64 => Field::UInt(take_be(rd, 8)?),
+            Marker::FixNeg(v) => Field::Int(v as i64),
+            Marker::I8 => Field::Int(take_be(rd, 1)? as i8 as i64),
+            Marker::I16 => Field::Int(take_be(rd, 2)? as i16 as i64),
+            Marker::I32 => Field::Int(take_be(rd, 4)? as i32 as i64),
+            Marker::I64 => Field::Int(take_be(rd, 8)? as i64),
+            Marker::F32 => Field::F32(take_be(rd, 4)? as u32),
+            Marker::F64 => Field::F64(take_be(rd, 8)?),
+            Marker::FixStr(n) => Field::read_str(take(rd, n as usize)?)?,
+            Marker::Str8 => Field::read_str(take_sized(rd, 1)?)?,
+            Marker::Str16 => Field::read_str(take_sized(rd, 2)?)?,
+            Marker::Str32 => Field::read_str(take_sized(rd, 4)?)?,
+            Marker::Bin8 => Field::Bin(take_sized(rd, 1)?.to_vec()),
+            Marker::Bin16 => Field::Bin(take_sized(rd, 2)?.to_vec()),
+            Marker::Bin32 => Field::Bin(take_sized(rd, 4)?.to_vec()),
+            Marker::FixArray(n) => Field::Array(n as u32),
+            Marker::Array16 => Field::Array(take_be(rd, 2)? as u32),
+            Marker::Array32 => Field::Array(take_be(rd, 4)? as u32),
+            Marker::FixMap(n) => Field::Map(n as u32),
+            Marker::Map16 => Field::Map(take_be(rd, 2)? as u32),
+            Marker::Map32 => Field::Map(take_be(rd, 4)? as u32),
+            Marker::FixExt1 => Field::read_ext(rd, 1)?,
+            Marker::FixExt2 => Field::read_ext(rd, 2)?,
+            Marker::FixExt4 => Field::read_ext(rd, 4)?,
+            Marker::FixExt8 => Field::read_ext(rd, 8)?,
+            Marker::FixExt16 => Field::read_ext(rd, 16)?,
+            Marker::Ext8 => Field::read_ext_sized(rd, 1)?,
+            Marker::Ext16 => Field::read_ext_sized(rd, 2)?,
+            Marker::Ext32 => Field::read_ext_sized(rd, 4)?,
+            Marker::Reserved => {
+                return Err(ReportCodecError::Malformed("reserved MessagePack marker").into())
+            }
+        })
+    }
+
+    fn read_str(bytes: &[u8]) -> Result<Field> {
+        Ok(Field::Str(String::from_utf8(bytes.to_vec())?))
+    }
+
+    /// Reads an extension with a length prefix of `width` bytes
+    fn read_ext_sized(rd: &mut &[u8], width: usize) -> Result<Field> {
+        let len = take_be(rd, width)? as usize;
+        Field::read_ext(rd, len)
+    }
+
+    fn read_ext(rd: &mut &[u8], len: usize) -> Result<Field> {
+        let ty = take_be(rd, 1)? as i8;
+        Ok(Field::Ext(ty, take(rd, len)?.to_vec()))
+    }
+
+    fn write(&self, buffer: &mut Vec<u8>) {
+        // writing to a Vec does not fail
+        match self {
+            Field::Nil => rmp::encode::write_nil(buffer).unwrap(),
+            Field::Bool(v) => rmp::encode::write_bool(buffer, *v).unwrap(),
+            Field::UInt(v) => drop(rmp::encode::write_uint(buffer, *v).unwrap()),
+            Field::Int(v) => drop(rmp::encode::write_sint(buffer, *v).unwrap()),
+            Field::F32(v) => rmp::encode::write_f32(buffer, f32::from_bits(*v)).unwrap(),
+            Field::F64(v) => rmp::encode::write_f64(buffer, f64::from_bits(*v)).unwrap(),
+            Field::Str(v) => rmp::encode::write_str(buffer, v).unwrap(),
+            Field::Bin(v) => rmp::encode::write_bin(buffer, v).unwrap(),
+            Field::Array(n) => drop(rmp::encode::write_array_len(buffer, *n).unwrap()),
+            Field::Map(n) => drop(rmp::encode::write_map_len(buffer, *n).unwrap()),
+            Field::Ext(ty, data) => {
+                rmp::encode::write_ext_meta(buffer, data.len() as u32, *ty).unwrap();
+                buffer.extend_from_slice(data);
+            }
+        }
+    }
+}
+
+// Tags of fields and operations in keyframe and delta data
+const TAG_NIL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_UINT: u8 = 3;
+const TAG_INT: u8 = 4;
+const TAG_F32: u8 = 5;
+const TAG_F64: u8 = 6;
+const TAG_STR_INDEX: u8 = 7;
+const TAG_STR: u8 = 8;
+const TAG_BIN: u8 = 9;
+const TAG_ARRAY: u8 = 10;
+const TAG_MAP: u8 = 11;
+const TAG_EXT: u8 = 12;
+const TAG_UNCHANGED: u8 = 13;
+const TAG_UINT_DELTA: u8 = 14;
+
+/// Keyframe data: [schema version: u16 LE][dictionary size][strings][field count][fields]
+fn encode_keyframe(schema_version: u16, fields: Vec<Field>) -> (Vec<u8>, Keyframe) {
+    let mut dictionary = Vec::new();
+    let mut dictionary_index = HashMap::new();
+    for field in fields.iter() {
+        if let Field::Str(s) = field {
+            if !dictionary_index.contains_key(s) {
+                dictionary_index.insert(s.clone(), dictionary.len() as u64);
+                dictionary.push(s.clone());
+            }
+        }
+    }
+
+    let mut data = schema_version.to_le_bytes().to_vec();
+    write_varint(&mut data, dictionary.len() as u64);
+    for s in dictionary.iter() {
+        write_bytes(&mut data, s.as_bytes());
+    }
+    write_varint(&mut data, fields.len() as u64);
+    for field in fields.iter() {
+        write_field(&mut data, field, &dictionary_index);
+    }
+
+    (data, Keyframe::new(schema_version, fields, dictionary))
+}
+
+fn decode_keyframe(mut data: &[u8]) -> Result<Keyframe> {
+    let rd = &mut data;
+    let schema_version = take(rd, 2)?;
+    let schema_version = u16::from_le_bytes([schema_version[0], schema_version[1]]);
+
+    let dictionary_size = read_varint(rd)?;
+    let dictionary = (0..dictionary_size)
+        .map(|_| Ok(String::from_utf8(read_bytes(rd)?.to_vec())?))
+        .collect::<Result<Vec<_>>>()?;
+
+    let field_count = read_varint(rd)?;
+    let fields = (0..field_count)
+        .map(|_| {
+            let tag = take(rd, 1)?[0];
+            read_field(rd, tag, &dictionary)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Keyframe::new(schema_version, fields, dictionary))
+}
+
+/// Delta data: [field count][operations]
+fn encode_delta(keyframe: &Keyframe, fields: &[Field]) -> Vec<u8> {
+    let mut data = Vec::new();
+    write_varint(&mut data, fields.len() as u64);
+
+    let mut unchanged = 0;
+    for (i, field) in fields.iter().enumerate() {
+        let previous = keyframe.fields.get(i);
+        if previous == Some(field) {
+            unchanged += 1;
+            continue;
+        }
+
+        if unchanged > 0 {
+            data.push(TAG_UNCHANGED);
+            write_varint(&mut data, unchanged);
+            unchanged = 0;
+        }
+
+        match (previous, field) {
+            (Some(Field::UInt(previous)), Field::UInt(current)) => {
+                data.push(TAG_UINT_DELTA);
+                write_varint(&mut data, zigzag(current.wrapping_sub(*previous) as i64));
+            }
+            _ => write_field(&mut data, field, &keyframe.dictionary_index),
+        }
+    }
+    if unchanged > 0 {
+        data.push(TAG_UNCHANGED);
+        write_varint(&mut data, unchanged);
+    }
+
+    data
+}
+
+fn decode_delta(keyframe: &Keyframe, mut data: &[u8]) -> Result<Vec<Field>> {
+    let rd = &mut data;
+    let field_count = read_varint(rd)? as usize;
+
+    let mut fields = Vec::with_capacity(field_count);
+    while fields.len() < field_count {
+        let i = fields.len();
+        match take(rd, 1)?[0] {
+            TAG_UNCHANGED => {
+                let count = read_varint(rd)? as usize;
+                let previous = keyframe
+                    .fields
+                    .get(i..i + count)
+                    .ok_or(ReportCodecError::Malformed("delta exceeds keyframe"))?;
+                fields.extend_from_slice(previous);
+            }
+            TAG_UINT_DELTA => {
+                let Some(Field::UInt(previous)) = keyframe.fields.get(i) else {
+                    return Err(ReportCodecError::Malformed("delta of non-integer field").into());
+                };
+                let delta = unzigzag(read_varint(rd)?);
+                fields.push(Field::UInt(previous.wrapping_add(delta as u64)));
+            }
+            tag => fields.push(read_field(rd, tag, &keyframe.dictionary)?),
+        }
+    }
+    if fields.len() != field_count {
+        return Err(ReportCodecError::Malformed("delta exceeds field count").into());
+    }
+
+    Ok(fields)
+}
+
+fn write_field(data: &mut Vec<u8>, field: &Field, dictionary_index: &HashMap<String, u64>) {
+    match field {
+        Field::Nil => data.push(TAG_NIL),
+        Field::Bool(false) => data.push(TAG_FALSE),
+        Field::Bool(true) => data.push(TAG_TRUE),
+        Field::UInt(v) => {
+            data.push(TAG_UINT);
+            write_varint(data, *v);
+        }
+        Field::Int(v) => {
+            data.push(TAG_INT);
+            write_varint(data, zigzag(*v));
+        }
+        Field::F32(v) => {
+            data.push(TAG_F32);
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        Field::F64(v) => {
+            data.push(TAG_F64);
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        Field::Str(s) => match dictionary_index.get(s) {
+            Some(&index) => {
+                data.push(TAG_STR_INDEX);
+                write_varint(data, index);
+            }
+            None => {
+                data.push(TAG_STR);
+                write_bytes(data, s.as_bytes());
+            }
+        },
+        Field::Bin(v) => {
+            data.push(TAG_BIN);
+            write_bytes(data, v);
+        }
+        Field::Array(n) => {
+            data.push(TAG_ARRAY);
+            write_varint(data, *n as u64);
+        }
+        Field::Map(n) => {
+            data.push(TAG_MAP);
+            write_varint(data, *n as u64);
+        }
+        Field::Ext(ty, v) => {
+            data.push(TAG_EXT);
+            data.push(*ty as u8);
+            write_bytes(data, v);
+        }
+    }
+}
+
+fn read_field(rd: &mut &[u8], tag: u8, dictionary: &[String]) -> Result<Field> {
+    Ok(match tag {
+        TAG_NIL => Field::Nil,
+        TAG_FALSE => Field::Bool(false),
+        TAG_TRUE => Field::Bool(true),
+        TAG_UINT => Field::UInt(read_varint(rd)?),
+        TAG_INT => Field::Int(unzigzag(read_varint(rd)?)),
+        TAG_F32 => Field::F32(u32::from_le_bytes(take(rd, 4)?.try_into().unwrap())),
+        TAG_F64 => Field::F64(u64::from_le_bytes(take(rd, 8)?.try_into().unwrap())),
+        TAG_STR_INDEX => Field::Str(
+            dictionary
+                .get(read_varint(rd)? as usize)
+                .ok_or(ReportCodecError::Malformed("string index out of range"))?
+                .clone(),
+        ),
+        TAG_STR => Field::Str(String::from_utf8(read_bytes(rd)?.to_vec())?),
+        TAG_BIN => Field::Bin(read_bytes(rd)?.to_vec()),
+        TAG_ARRAY => Field::Array(read_varint_u32(rd)?),
+        TAG_MAP => Field::Map(read_varint_u32(rd)?),
+        TAG_EXT => {
+            let ty = take(rd, 1)?[0] as i8;
+            Field::Ext(ty, read_bytes(rd)?.to_vec())
+        }
+        _ => return Err(ReportCodecError::Malformed("unknown delta field tag").into()),
+    })
+}
+
+fn take<'a>(rd: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if rd.len() < len {
+        return Err(ReportCodecError::Malformed("unexpected end of data").into());
+    }
+    let (head, tail) = rd.split_at(len);
+    *rd = tail;
+    Ok(head)
+}
+
+/// Reads data with a big-endian length prefix of `width` bytes
+fn take_sized<'a>(rd: &mut &'a [u8], width: usize) -> Result<&'a [u8]> {
+    let len = take_be(rd, width)? as usize;
+    take(rd, len)
+}
+
+/// Reads a big-endian unsigned integer with `len` bytes as used by MessagePack
+fn take_be(rd: &mut &[u8], len: usize) -> Result<u64> {
+    Ok(take(rd, len)?
+        .iter()
+        .fold(0, |acc, &b| (acc << 8) | b as u64))
+}
+
+fn write_bytes(data: &mut Vec<u8>, bytes: &[u8]) {
+    write_varint(data, bytes.len() as u64);
+    data.extend_from_slice(bytes);
+}
+
+fn read_bytes<'a>(rd: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let len = read_varint(rd)? as usize;
+    take(rd, len)
+}
+
+/// LEB128 encoding of an unsigned integer
+fn write_varint(data: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        data.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    data.push(value as u8);
+}
+
+fn read_varint(rd: &mut &[u8]) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = take(rd, 1)?[0];
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(ReportCodecError::Malformed("varint too long").into())
+}
+
+fn read_varint_u32(rd: &mut &[u8]) -> Result<u32> {
+    u32::try_from(read_varint(rd)?)
+        .map_err(|_| ReportCodecError::Malformed("length out of range").into())
+}
+
+/// Maps signed integers to unsigned integers such that small magnitudes have small encodings
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::report_delta::{read_varint, unzigzag, write_varint, zigzag, Field};
+
+    #[test]
+    fn test_varint() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut data = Vec::new();
+            write_varint(&mut data, value);
+            assert_eq!(read_varint(&mut data.as_slice()).unwrap(), value);
+        }
+        for value in [0, 1, -1, 63, -64, i64::MIN, i64::MAX] {
+            assert_eq!(unzigzag(zigzag(value)), value);
+        }
+        assert_eq!(zigzag(-1), 1);
+        assert_eq!(zigzag(1), 2);
+    }
+
+    #[test]
+    fn test_split_join_report() {
+        let mut buffer = 7u16.to_le_bytes().to_vec();
+        rmp_serde::encode::write_named(
+            &mut buffer,
+            &(
+                "name",
+                -3i32,
+                1_000_000u64,
+                Some(0.5f32),
+                vec![1.5f64],
+                small_map(),
+            ),
+        )
+        .unwrap();
+
+        let (version, fields) = Field::split_report(&buffer).unwrap();
+        assert_eq!(version, 7);
+        assert!(matches!(fields[0], Field::Array(6)));
+        assert!(matches!(&fields[1], Field::Str(s) if s == "name"));
+        assert!(matches!(fields[2], Field::Int(-3)));
+        assert!(matches!(fields[3], Field::UInt(1_000_000)));
+        assert_eq!(Field::join_report(version, &fields), buffer);
+    }
+
+    fn small_map() -> std::collections::BTreeMap<String, u8> {
+        [("a".to_string(), 1), ("b".to_string(), 255)].into()
+    }
+}
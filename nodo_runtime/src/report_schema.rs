@@ -0,0 +1,458 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::{
+    AppInfo, InspectorCodeletReport, InspectorReport, InspectorScheduleReport, ReportCodecError,
+};
+use eyre::Result;
+use nodo::codelet::NodeletId;
+use nodo_core::{Name, UnknownFields};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Version of the encoding of inspector reports
+///
+/// Reports are encoded as MessagePack with named fields such that runtimes and clients of
+/// different versions can exchange reports:
+/// - New fields must be added with `#[serde(default)]` so that reports of older versions can
+///   still be decoded.
+/// - Fields of newer versions which are unknown to the decoder are kept in the `unknown` field of
+///   the report, codelet report, schedule report and statistics and are encoded again when the
+///   report is forwarded, e.g. by a recording.
+/// - Fields must never be removed, renamed or change their type.
+///
+/// Every change to a report type must bump this version and add the fixture of the new version
+/// by running the report schema tests with `UPDATE_GOLDENS=1`. Fixtures of earlier versions must
+/// never be modified as the tests decode all of them with the current types.
+pub const REPORT_SCHEMA_VERSION: u16 = 3;
+
+/// Oldest version of the report encoding which can still be decoded
+pub const MIN_REPORT_SCHEMA_VERSION: u16 = 1;
+
+/// Size of the version header in front of the encoded report
+const VERSION_SIZE: usize = 2;
+
+#[derive(Serialize)]
+struct SchemaReportRef<'a> {
+    codelets: Vec<(&'a NodeletId, &'a InspectorCodeletReport)>,
+    app_info: &'a Option<AppInfo>,
+    schedules: &'a BTreeMap<Name, InspectorScheduleReport>,
+
+    #[serde(flatten)]
+    unknown: &'a UnknownFields,
+}
+
+#[derive(Deserialize)]
+struct SchemaReport {
+    codelets: Vec<(NodeletId, InspectorCodeletReport)>,
+    app_info: Option<AppInfo>,
+
+    #[serde(default)]
+    schedules: BTreeMap<Name, InspectorScheduleReport>,
+
+    #[serde(flatten)]
+    unknown: UnknownFields,
+}
+
+/// Encodes a report with the current schema version
+///
+/// Codelets are sorted by ID such that consecutive reports have a similar binary layout.
+///
+/// Layout: [schema version: u16 LE][MessagePack with named fields]
+pub fn encode_report(report: &InspectorReport) -> Result<Vec<u8>> {
+    let mut codelets = report.codelets.iter().collect::<Vec<_>>();
+    codelets.sort_by_key(|(id, _)| **id);
+
+    let mut buffer = REPORT_SCHEMA_VERSION.to_le_bytes().to_vec();
+    rmp_serde::encode::write_named(
+        &mut buffer,
+        &SchemaReportRef {
+            codelets,
+            app_info: &report.app_info,
+            schedules: &report.schedules,
+            unknown: &report.unknown,
+        },
+    )?;
+    Ok(buffer)
+}
+
+/// Decodes a report created with [encode_report]
+///
+/// Reports of newer schema versions are decoded as well and their unknown fields are kept.
+pub fn decode_report(buffer: &[u8]) -> Result<InspectorReport> {
+    let version = report_schema_version(buffer)?;
+    if version < MIN_REPORT_SCHEMA_VERSION {
+        return Err(ReportCodecError::UnsupportedSchema {
+            version,
+            min: MIN_REPORT_SCHEMA_VERSION,
+        }
+        .into());
+    }
+
+    let report: SchemaReport = rmp_serde::from_slice(&buffer[VERSION_SIZE..])?;
+    Ok(InspectorReport {
+        codelets: report.codelets.into_iter().collect(),
+        app_info: report.app_info,
+        schedules: report.schedules,
+        unknown: report.unknown,
+    })
+}
+
+/// Schema version of a report created with [encode_report]
+pub fn report_schema_version(buffer: &[u8]) -> Result<u16> {
+    let header = buffer
+        .get(..VERSION_SIZE)
+        .ok_or(ReportCodecError::Malformed("missing report schema version"))?;
+    Ok(u16::from_le_bytes([header[0], header[1]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        decode_report, encode_report, report_schema::VERSION_SIZE, report_schema_version, AppInfo,
+        BacklogTeardown, InspectorCodeletReport, InspectorReport, InspectorScheduleReport,
+        RenderedStatus, ReportCodecError, SheddingStatistics, SleepStatistics, WorkerThreadReport,
+        MIN_REPORT_SCHEMA_VERSION, REPORT_SCHEMA_VERSION,
+    };
+    use core::time::Duration;
+    use nodo::{
+        channels::ChannelUid,
+        codelet::{
+            CountTotal, JitterStatistics, NodeletId, RxChannelStatistics, Statistics, Transition,
+            WorkerId,
+        },
+        prelude::{DefaultStatus, Severity},
+    };
+    use nodo_core::{Capability, Name, UnknownValue};
+    use std::{
+        path::PathBuf,
+        time::{Instant, SystemTime},
+    };
+
+    fn fixture_path(version: u16) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures/report_schema")
+            .join(format!("v{version}.msgpack"))
+    }
+
+    fn ms(x: u64) -> Duration {
+        Duration::from_millis(x)
+    }
+
+    /// A report in which every field has a non-default value
+    fn sample_report() -> InspectorReport {
+        let t0 = Instant::now();
+        let mut jitter = JitterStatistics::default();
+        for t in [0, 11, 20, 29] {
+            jitter.push(t0 + ms(t), ms(10));
+        }
+
+        let mut sleep_overshoot = SleepStatistics::default();
+        for t in [20, 50, 300] {
+            sleep_overshoot.push(Duration::from_micros(t));
+        }
+
+        let mut statistics = Statistics::new();
+        let step = &mut statistics.transitions[Transition::Step];
+        for k in 1..=3 {
+            step.duration.push(Duration::from_micros(100 * k));
+            step.period.push(ms(10));
+        }
+        step.skipped_count = 4;
+        step.consecutive_skipped_count = 1;
+        step.skipped_duration = Duration::from_micros(40);
+        step.disabled_count = 2;
+        step.skipped_by_reason.insert("no input".into(), 2);
+        step.pause_count = 1;
+        step.resume_count = 1;
+        step.resume_gap = Some(ms(500));
+        step.jitter = jitter.clone();
+        statistics.transitions[Transition::Start]
+            .duration
+            .push(ms(3));
+        let mut residency = CountTotal::default();
+        residency.push(Duration::from_micros(250));
+        statistics.rx_channels.push(RxChannelStatistics {
+            name: "in".into(),
+            uid: Some(ChannelUid(0x1234_5678_9abc_def0)),
+            capacity: Some(16),
+            high_water_mark: 7,
+            received: 100,
+            forgotten: 1,
+            dropped: 2,
+            rejected: 3,
+            residency,
+        });
+        statistics.deadline_miss_count = 5;
+        statistics.scratch_peak_bytes = 4096;
+
+        let codelet = InspectorCodeletReport {
+            sequence: "sensors".into(),
+            name: "camera".into(),
+            typename: "my_crate::Camera".into(),
+            status: Some(RenderedStatus {
+                label: "streaming".into(),
+                status: DefaultStatus::Running,
+                code: 3,
+                severity: Severity::Warn,
+                skip_reason: Some("no input".into()),
+            }),
+            statistics,
+            is_warmup: true,
+            labels: vec!["hardware".into()],
+            suspected_inactive: true,
+            suspend_resume_count: 1,
+            deadline: Some(ms(5)),
+            start_diagnostics: vec!["channel 'in' is not connected".into()],
+            schedule: "main".into(),
+            progress: Some((0.5, "calibrating".into())),
+            unknown: Default::default(),
+        };
+
+        let mut report = InspectorReport::default();
+        report.push(NodeletId(WorkerId(1), 0), codelet.clone());
+        report.push(
+            NodeletId(WorkerId(0), 2),
+            InspectorCodeletReport {
+                name: "logger".into(),
+                status: None,
+                statistics: Statistics::new(),
+                ..codelet
+            },
+        );
+        report.set_app_info(AppInfo {
+            git_hash: Some("a1b2c3d".into()),
+            build_profile: "release".into(),
+            start_time: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            capabilities: vec![Capability {
+                name: "nng".into(),
+                version: "0.1.0".into(),
+            }],
+            ..AppInfo::new("robot", "1.2.3")
+        });
+        report.push_schedule(
+            "main".into(),
+            InspectorScheduleReport {
+                period: Some(ms(10)),
+                jitter,
+                shed_sequences: vec!["logging".into()],
+                shedding: [(
+                    "logging".into(),
+                    SheddingStatistics {
+                        shed_count: 2,
+                        restore_count: 1,
+                    },
+                )]
+                .into(),
+                thread: Some(WorkerThreadReport {
+                    os_thread_id: Some(4242),
+                    initial_cpu: Some(1),
+                    last_cpu: Some(3),
+                    migration_count: 2,
+                    sample_count: 10,
+                    pinned_cpu: None,
+                }),
+                teardown: vec![BacklogTeardown {
+                    codelet: "camera".into(),
+                    channel: "in".into(),
+                    len: 20_000,
+                    bytes: 160_000,
+                    deferred: true,
+                }],
+                sleep_overshoot,
+                unknown: Default::default(),
+            },
+        );
+        report
+    }
+
+    /// The field with the given name of an encoded struct
+    fn field_mut<'a>(value: &'a mut UnknownValue, name: &str) -> &'a mut UnknownValue {
+        let UnknownValue::Map(entries) = value else {
+            panic!("expected a map but got {value:?}");
+        };
+        entries
+            .iter_mut()
+            .find(|(key, _)| *key == UnknownValue::String(name.into()))
+            .map(|(_, value)| value)
+            .unwrap_or_else(|| panic!("missing field `{name}`"))
+    }
+
+    fn element_mut(value: &mut UnknownValue, index: usize) -> &mut UnknownValue {
+        let UnknownValue::Seq(items) = value else {
+            panic!("expected a sequence but got {value:?}");
+        };
+        &mut items[index]
+    }
+
+    fn push_field(value: &mut UnknownValue, name: &str, field: UnknownValue) {
+        let UnknownValue::Map(entries) = value else {
+            panic!("expected a map but got {value:?}");
+        };
+        entries.push((UnknownValue::String(name.into()), field));
+    }
+
+    fn remove_field(value: &mut UnknownValue, name: &str) {
+        let UnknownValue::Map(entries) = value else {
+            panic!("expected a map but got {value:?}");
+        };
+        entries.retain(|(key, _)| *key != UnknownValue::String(name.into()));
+    }
+
+    /// Generic representation of an encoded report
+    fn to_value(buffer: &[u8]) -> UnknownValue {
+        rmp_serde::from_slice(&buffer[VERSION_SIZE..]).unwrap()
+    }
+
+    fn from_value(version: u16, value: &UnknownValue) -> Vec<u8> {
+        let mut buffer = version.to_le_bytes().to_vec();
+        rmp_serde::encode::write_named(&mut buffer, value).unwrap();
+        buffer
+    }
+
+    /// Fails if the encoding changed without bumping the schema version
+    #[test]
+    fn test_current_version_matches_fixture() {
+        let actual = encode_report(&sample_report()).unwrap();
+        let path = fixture_path(REPORT_SCHEMA_VERSION);
+
+        let Ok(expected) = std::fs::read(&path) else {
+            if std::env::var("UPDATE_GOLDENS").is_ok_and(|v| v == "1") {
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(&path, &actual).unwrap();
+                return;
+            }
+            panic!(
+                "missing report schema fixture {path:?} for the current version \
+                 {REPORT_SCHEMA_VERSION}. Run the test with UPDATE_GOLDENS=1 to create it."
+            );
+        };
+
+        assert!(
+            actual == expected,
+            "the encoding of inspector reports changed but REPORT_SCHEMA_VERSION is still \
+             {REPORT_SCHEMA_VERSION}. Bump the version, follow the rules documented at \
+             REPORT_SCHEMA_VERSION and create the fixture of the new version with \
+             UPDATE_GOLDENS=1. Never modify the fixture {path:?} of a released version."
+        );
+    }
+
+    /// Reports of all supported earlier versions can be decoded with the current types
+    #[test]
+    fn test_decode_fixtures() {
+        for version in MIN_REPORT_SCHEMA_VERSION..=REPORT_SCHEMA_VERSION {
+            let path = fixture_path(version);
+            let buffer = std::fs::read(&path)
+                .unwrap_or_else(|err| panic!("missing fixture {path:?}: {err}"));
+            assert_eq!(report_schema_version(&buffer).unwrap(), version);
+
+            let report = decode_report(&buffer)
+                .unwrap_or_else(|err| panic!("could not decode fixture {path:?}: {err:#}"));
+            let mut names = report
+                .iter()
+                .map(|(_, codelet)| codelet.name.as_str())
+                .collect::<Vec<_>>();
+            names.sort();
+            assert_eq!(names, ["camera", "logger"], "fixture {path:?}");
+            assert_eq!(report.app_info().unwrap().name, "robot");
+            assert!(report.schedules().contains_key(&Name::new("main")));
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let expected = encode_report(&sample_report()).unwrap();
+        let actual = encode_report(&decode_report(&expected).unwrap()).unwrap();
+        assert!(actual == expected);
+    }
+
+    /// Fields of a newer version are kept when the report is encoded again
+    #[test]
+    fn test_unknown_fields_preserved() {
+        let mut value = to_value(&encode_report(&sample_report()).unwrap());
+        let future = |name: &str| {
+            UnknownValue::Map(vec![
+                (
+                    UnknownValue::String("field".into()),
+                    UnknownValue::String(name.into()),
+                ),
+                (UnknownValue::String("count".into()), UnknownValue::U64(7)),
+            ])
+        };
+
+        push_field(&mut value, "future_report", future("report"));
+        let schedule = field_mut(field_mut(&mut value, "schedules"), "main");
+        push_field(schedule, "future_schedule", future("schedule"));
+        let codelet = element_mut(element_mut(field_mut(&mut value, "codelets"), 0), 1);
+        push_field(codelet, "future_codelet", UnknownValue::Bool(true));
+        let statistics = field_mut(codelet, "statistics");
+        push_field(statistics, "future_statistics", UnknownValue::I64(-3));
+        let step = element_mut(field_mut(statistics, "transitions"), 1);
+        push_field(step, "future_step", UnknownValue::Unit);
+
+        let buffer = from_value(REPORT_SCHEMA_VERSION + 1, &value);
+        let report = decode_report(&buffer).unwrap();
+        assert_eq!(report.unknown.get("future_report"), Some(&future("report")));
+        assert_eq!(
+            report.schedules()[&Name::new("main")]
+                .unknown
+                .get("future_schedule"),
+            Some(&future("schedule"))
+        );
+        let camera = &report.codelets[&NodeletId(WorkerId(0), 2)];
+        assert_eq!(
+            camera.unknown.get("future_codelet"),
+            Some(&UnknownValue::Bool(true))
+        );
+        assert_eq!(
+            camera.statistics.unknown.get("future_statistics"),
+            Some(&UnknownValue::I64(-3))
+        );
+        assert_eq!(
+            camera.statistics.transitions[Transition::Step]
+                .unknown
+                .get("future_step"),
+            Some(&UnknownValue::Unit)
+        );
+
+        // the client forwards the report with its own version but keeps all fields
+        let forwarded = encode_report(&report).unwrap();
+        assert_eq!(
+            report_schema_version(&forwarded).unwrap(),
+            REPORT_SCHEMA_VERSION
+        );
+        assert_eq!(to_value(&forwarded), value);
+    }
+
+    /// Fields added in later versions fall back to their default
+    #[test]
+    fn test_missing_fields_use_defaults() {
+        let mut value = to_value(&encode_report(&sample_report()).unwrap());
+        remove_field(&mut value, "schedules");
+        let codelet = element_mut(element_mut(field_mut(&mut value, "codelets"), 0), 1);
+        remove_field(codelet, "progress");
+        remove_field(field_mut(codelet, "statistics"), "deadline_miss_count");
+
+        let report = decode_report(&from_value(REPORT_SCHEMA_VERSION, &value)).unwrap();
+        assert!(report.schedules().is_empty());
+        let camera = &report.codelets[&NodeletId(WorkerId(0), 2)];
+        assert_eq!(camera.progress, None);
+        assert_eq!(camera.statistics.deadline_miss_count, 0);
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let mut buffer = encode_report(&sample_report()).unwrap();
+        buffer[..VERSION_SIZE].copy_from_slice(&(MIN_REPORT_SCHEMA_VERSION - 1).to_le_bytes());
+        let err = decode_report(&buffer).err().unwrap();
+        assert!(matches!(
+            err.downcast_ref::<ReportCodecError>(),
+            Some(ReportCodecError::UnsupportedSchema { version: 0, min: 1 })
+        ));
+
+        let err = decode_report(&[1]).err().unwrap();
+        assert!(matches!(
+            err.downcast_ref::<ReportCodecError>(),
+            Some(ReportCodecError::Malformed(_))
+        ));
+    }
+}
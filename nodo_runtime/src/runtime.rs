@@ -1,19 +1,77 @@
 // Copyright 2023 by David Weikersdorfer. All rights reserved.
 
 use crate::{
-    statistics_pretty_print, Executor as CodeletExecutor, InspectorServer,
-    ScheduleExecutor as CodeletSchedule,
+    statistics_pretty_print, AppInfo, ChannelTaps, DeadWeightConfig, DeadWeightDetector,
+    DryRunError, DryRunReport, Executor as CodeletExecutor, InspectorCommand,
+    InspectorCommandServer, InspectorReport, InspectorServer, Manifold, QueueSizingReport,
+    ReportCodecKind, ScheduleExecutor as CodeletSchedule, Snapshot, SnapshotConfig, SnoopError,
+    SnoopFormats, SnoopSink, SnoopTarget, Snoops, StepMode, TeardownConfig, WorkerJoinError,
 };
 use core::time::Duration;
 use eyre::Result;
-use nodo::prelude::RuntimeControl;
-use std::sync::mpsc::RecvTimeoutError;
+use nodo::{
+    channels::SnoopLimits,
+    codelet::{FailurePlan, ResourceRegistry},
+    prelude::{ControlHandle, RuntimeControl},
+};
+use nodo_core::{SeqAllocator, SeqDomains, TimeStandardConfig};
+use std::{
+    path::{Path, PathBuf},
+    sync::{mpsc::RecvTimeoutError, Arc, Mutex},
+    time::Instant,
+};
 
 pub struct Runtime {
     tx_control: std::sync::mpsc::SyncSender<RuntimeControl>,
+    control_handle: ControlHandle,
     rx_control: std::sync::mpsc::Receiver<RuntimeControl>,
     codelet_exec: CodeletExecutor,
     inspector_server: Option<InspectorServer>,
+    inspector_commands: Option<InspectorCommandServer>,
+    app_info: AppInfo,
+    snapshot: Option<SnapshotFile>,
+    dead_weight: Option<DeadWeightDetector>,
+    print_queue_sizing: bool,
+    stats_json_path: Option<PathBuf>,
+    launch_mode: LaunchMode,
+    dry_run_reports: Vec<DryRunReport>,
+    dry_run_error: Option<eyre::Report>,
+    log_level: Option<log::LevelFilter>,
+    seq_domains: SeqDomains,
+    snoop_formats: SnoopFormats,
+    snoops: Mutex<Snoops>,
+}
+
+/// How schedules added to the runtime are executed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LaunchMode {
+    /// Schedules run on worker threads until stopped
+    #[default]
+    Run,
+
+    /// Schedules are dry-run on the calling thread when they are added and `spin` reports the
+    /// result, see [Runtime::dry_run_all] and [Runtime::dry_run_result]
+    DryRun,
+
+    /// Schedules are only registered in the manifold and `spin` prints the manifold
+    ListCodelets,
+}
+
+/// Snapshot shared with the workers and the file it is written to
+struct SnapshotFile {
+    config: SnapshotConfig,
+    snapshot: Arc<Mutex<Snapshot>>,
+    last_write: Instant,
+}
+
+impl SnapshotFile {
+    fn write(&mut self) {
+        let result = self.snapshot.lock().unwrap().write(&self.config.path);
+        if let Err(err) = result {
+            log::error!("{err:#}");
+        }
+        self.last_write = Instant::now();
+    }
 }
 
 impl Runtime {
@@ -22,47 +80,458 @@ impl Runtime {
         let codelet_exec = CodeletExecutor::new();
 
         Self {
+            control_handle: ControlHandle::new(tx_control.clone()),
             tx_control,
             rx_control,
             codelet_exec,
             inspector_server: None,
+            inspector_commands: None,
+            app_info: AppInfo::from_process(),
+            snapshot: None,
+            dead_weight: Some(DeadWeightDetector::new(DeadWeightConfig::default())),
+            print_queue_sizing: false,
+            stats_json_path: None,
+            launch_mode: LaunchMode::Run,
+            dry_run_reports: Vec::new(),
+            dry_run_error: None,
+            log_level: None,
+            seq_domains: SeqDomains::new(),
+            snoop_formats: SnoopFormats::default(),
+            snoops: Mutex::new(Snoops::default()),
+        }
+    }
+
+    /// Enables persistence of codelets added with `CodeletInstance::with_persistence` (builder
+    /// style)
+    ///
+    /// The snapshot file is loaded immediately and persisted codelets are restored when their
+    /// schedule is added. States are written when all workers stopped and optionally
+    /// periodically. Missing or corrupt states result in a cold start of the affected codelets.
+    #[must_use]
+    pub fn with_snapshot(mut self, config: SnapshotConfig) -> Self {
+        let snapshot = Arc::new(Mutex::new(Snapshot::load(&config.path)));
+        self.codelet_exec
+            .set_snapshot(snapshot.clone(), config.interval);
+        self.snapshot = Some(SnapshotFile {
+            config,
+            snapshot,
+            last_write: Instant::now(),
+        });
+        self
+    }
+
+    /// Configures the detection of codelets which skip every step (builder style)
+    ///
+    /// Such codelets are marked as `suspected_inactive` in reports and are listed in a warning
+    /// once the observation window is over. Enabled with the default config unless disabled with
+    /// None.
+    #[must_use]
+    pub fn with_dead_weight_detection(mut self, config: Option<DeadWeightConfig>) -> Self {
+        self.dead_weight = config.map(DeadWeightDetector::new);
+        self
+    }
+
+    /// Sets how long to wait for the schedules of one stop wave during shutdown before all
+    /// remaining schedules are stopped in parallel (builder style)
+    ///
+    /// Schedules are stopped before the schedules they depend on, see
+    /// `ScheduleBuilder::with_start_after`.
+    #[must_use]
+    pub fn with_stop_wave_timeout(mut self, timeout: Duration) -> Self {
+        self.codelet_exec.set_stop_wave_timeout(timeout);
+        self
+    }
+
+    /// Enables conversions to TAI and GPS time with `cx.clocks.anchor` (builder style)
+    ///
+    /// Must be called before schedules are added.
+    #[must_use]
+    pub fn with_time_standards(mut self, config: TimeStandardConfig) -> Self {
+        self.codelet_exec.set_time_standards(config);
+        self
+    }
+
+    /// Prints queue size recommendations when all workers stopped (builder style), see
+    /// [Runtime::queue_sizing_report]
+    #[must_use]
+    pub fn with_queue_sizing_report(mut self, enable: bool) -> Self {
+        self.print_queue_sizing = enable;
+        self
+    }
+
+    /// Recommends capacities for RX channels which were much larger than needed or which lost
+    /// messages, based on the channel statistics collected so far
+    pub fn queue_sizing_report(&self) -> QueueSizingReport {
+        QueueSizingReport::new(&self.report(), self.manifold())
+    }
+
+    /// Sets build and version information shown in the inspector and in the statistics printed
+    /// at shutdown. Use `app_info!()` to fill it from the application crate.
+    #[must_use]
+    pub fn with_app_info(mut self, app_info: AppInfo) -> Self {
+        self.app_info = app_info;
+        self
+    }
+
+    pub fn app_info(&self) -> &AppInfo {
+        &self.app_info
+    }
+
+    pub(crate) fn report(&self) -> InspectorReport {
+        let mut report = self.codelet_exec.report();
+        report.set_app_info(self.current_app_info());
+        if let Some(dead_weight) = self.dead_weight.as_ref() {
+            dead_weight.mark(&mut report);
         }
+        report
+    }
+
+    /// Reports of every schedule which are published under separate inspector topics
+    pub(crate) fn schedule_reports(&self) -> Vec<(String, InspectorReport)> {
+        let app_info = self.current_app_info();
+        let mut reports = self.codelet_exec.schedule_reports();
+        for (_, report) in reports.iter_mut() {
+            report.set_app_info(app_info.clone());
+            if let Some(dead_weight) = self.dead_weight.as_ref() {
+                dead_weight.mark(report);
+            }
+        }
+        reports
+    }
+
+    fn current_app_info(&self) -> AppInfo {
+        self.app_info
+            .clone()
+            .with_capabilities(nodo_core::capabilities::list())
+    }
+
+    /// Writes the final statistics as JSON to the given file when all workers stopped
+    pub fn set_stats_json_path(&mut self, path: Option<PathBuf>) {
+        self.stats_json_path = path;
+    }
+
+    pub fn stats_json_path(&self) -> Option<&Path> {
+        self.stats_json_path.as_deref()
+    }
+
+    /// Sets how schedules are executed. Must be called before schedules are added.
+    pub fn set_launch_mode(&mut self, mode: LaunchMode) {
+        assert!(
+            self.manifold().is_empty(),
+            "the launch mode must be set before schedules are added"
+        );
+        self.launch_mode = mode;
+    }
+
+    pub fn launch_mode(&self) -> LaunchMode {
+        self.launch_mode
+    }
+
+    /// Sets the maximum log level. It is applied when `spin` is called.
+    pub fn set_log_level(&mut self, level: Option<log::LevelFilter>) {
+        self.log_level = level;
+    }
+
+    pub fn log_level(&self) -> Option<log::LevelFilter> {
+        self.log_level
+    }
+
+    pub fn is_inspector_enabled(&self) -> bool {
+        self.inspector_server.is_some()
     }
 
     pub fn enable_inspector(&mut self, address: &str) -> Result<()> {
-        self.inspector_server = Some(InspectorServer::open(address)?);
+        self.enable_inspector_with_codec(address, ReportCodecKind::default())
+    }
+
+    /// Enables the inspector with a specific report codec. Clients must use the same codec.
+    pub fn enable_inspector_with_codec(
+        &mut self,
+        address: &str,
+        codec: ReportCodecKind,
+    ) -> Result<()> {
+        self.inspector_server = Some(InspectorServer::open_with_codec(address, codec)?);
+        Ok(())
+    }
+
+    /// Accepts commands like single-stepping of schedules from an inspector on the given address,
+    /// see [InspectorCommand]. Commands are handled while the runtime spins.
+    pub fn enable_inspector_commands(&mut self, address: &str) -> Result<()> {
+        self.inspector_commands = Some(InspectorCommandServer::open(address)?);
         Ok(())
     }
 
-    pub fn add_codelet_schedule(&mut self, schedule: CodeletSchedule) {
-        self.codelet_exec.push(schedule)
+    /// Sets how the schedule with the given name triggers steps
+    ///
+    /// In [StepMode::Manual] the schedule is frozen until [Runtime::step_schedule_once] is called.
+    /// Switching back to [StepMode::Auto] resumes periodic operation.
+    pub fn set_schedule_step_mode(&mut self, schedule: &str, mode: StepMode) -> Result<()> {
+        self.codelet_exec.set_step_mode(schedule, mode)
+    }
+
+    /// Executes exactly one step of the schedule with the given name if it is in manual step
+    /// mode. Steps are executed asynchronously by the worker of the schedule.
+    pub fn step_schedule_once(&mut self, schedule: &str) -> Result<()> {
+        self.codelet_exec.step_once(schedule)
+    }
+
+    /// Arms a plan of injected failures for a codelet while the runtime is running, or disarms
+    /// it with None, see [FailurePlan]. The plan is applied asynchronously by the worker of the
+    /// schedule.
+    pub fn set_failure_injection(
+        &self,
+        schedule: &str,
+        codelet: &str,
+        plan: Option<FailurePlan>,
+    ) -> Result<()> {
+        self.codelet_exec.set_failure_plan(schedule, codelet, plan)
+    }
+
+    fn handle_inspector_commands(&mut self) {
+        let commands = match self
+            .inspector_commands
+            .as_mut()
+            .map(|s| s.try_recv_commands())
+        {
+            None => return,
+            Some(Ok(commands)) => commands,
+            Some(Err(err)) => {
+                log::error!("inspector could not receive commands: {err:?}");
+                return;
+            }
+        };
+
+        for command in commands {
+            log::debug!("inspector command: {command:?}");
+            let result = match &command {
+                InspectorCommand::SetStepMode { schedule, mode } => {
+                    self.set_schedule_step_mode(schedule, *mode)
+                }
+                InspectorCommand::StepOnce { schedule } => self.step_schedule_once(schedule),
+                InspectorCommand::Snoop { channel } => self
+                    .snoop(channel.as_str(), SnoopSink::Inspector)
+                    .map_err(Into::into),
+                InspectorCommand::Unsnoop { channel } => {
+                    self.unsnoop(channel.as_str()).map_err(Into::into)
+                }
+            };
+            if let Err(err) = result {
+                log::error!("inspector command {command:?} failed: {err:#}");
+            }
+        }
+    }
+
+    /// Enables a graph-wide start barrier: all codelets of all schedules complete their start
+    /// before any codelet steps
+    ///
+    /// Must be called before schedules are added. If any codelet fails to start no codelet steps
+    /// and all schedules are stopped.
+    pub fn set_start_barrier(&mut self, enable: bool) {
+        self.codelet_exec.set_start_barrier(enable);
+    }
+
+    /// Resources shared by codelets, see `Codelet::acquire_resources`
+    ///
+    /// Fails if schedules were already added as the registry is read-only afterwards.
+    pub fn resources(&mut self) -> Result<&mut ResourceRegistry> {
+        self.codelet_exec.resources_mut()
+    }
+
+    /// Sets how messages still queued in receivers are freed when schedules added afterwards
+    /// stop, see [TeardownConfig]
+    pub fn set_teardown(&mut self, config: TeardownConfig) {
+        self.codelet_exec.set_teardown(config);
+    }
+
+    /// Sequence number allocator shared by all producers which use the same domain name
+    ///
+    /// Clone the allocator into producers whose streams are merged and require unique sequence
+    /// numbers. Otherwise producers number their messages on their own.
+    pub fn seq_domain(&self, name: &str) -> SeqAllocator {
+        self.seq_domains.get(name)
+    }
+
+    pub fn add_codelet_schedule(&mut self, mut schedule: CodeletSchedule) {
+        match self.launch_mode {
+            LaunchMode::Run => self.codelet_exec.push(schedule),
+            LaunchMode::DryRun => {
+                self.codelet_exec.setup(&mut schedule);
+                match schedule.dry_run() {
+                    Ok(report) => self.dry_run_reports.push(report),
+                    Err(err) => {
+                        if self.dry_run_error.is_none() {
+                            self.dry_run_error = Some(err);
+                        }
+                    }
+                }
+            }
+            LaunchMode::ListCodelets => self.codelet_exec.setup(&mut schedule),
+        }
+    }
+
+    /// Dry-runs the given schedules one after another on the calling thread
+    ///
+    /// See `ScheduleExecutor::dry_run`. Schedules are registered in the manifold but no worker
+    /// threads are started. Fails with a `DryRunError` listing every codelet which failed.
+    pub fn dry_run_all<I>(&mut self, schedules: I) -> Result<Vec<DryRunReport>>
+    where
+        I: IntoIterator<Item = CodeletSchedule>,
+    {
+        let mut reports = Vec::new();
+        for mut schedule in schedules {
+            self.codelet_exec.setup(&mut schedule);
+            reports.push(schedule.dry_run()?);
+        }
+
+        if reports.iter().all(|r| r.is_ok()) {
+            Ok(reports)
+        } else {
+            Err(DryRunError { reports }.into())
+        }
+    }
+
+    /// Registry of all codelet instances which were added to the runtime
+    pub fn manifold(&self) -> &Manifold {
+        self.codelet_exec.manifold()
+    }
+
+    /// RX channels of all codelet instances which can be snooped
+    pub fn channel_taps(&self) -> &ChannelTaps {
+        self.codelet_exec.channel_taps()
+    }
+
+    /// Message types whose content is streamed by snoops, see [Runtime::snoop]
+    pub fn snoop_formats_mut(&mut self) -> &mut SnoopFormats {
+        &mut self.snoop_formats
     }
 
+    /// Attaches a snoop with default limits to an RX channel, see [Runtime::snoop_with_limits]
+    pub fn snoop(&self, target: impl Into<SnoopTarget>, sink: SnoopSink) -> Result<(), SnoopError> {
+        self.snoop_with_limits(target, sink, SnoopLimits::default())
+    }
+
+    /// Attaches a snoop to an RX channel for debugging while the runtime is running
+    ///
+    /// The snoop observes messages when they are flushed into the channel without consuming
+    /// them. Samples are delivered to the sink while spinning. Only messages with a type
+    /// registered in [Runtime::snoop_formats_mut] have content, other snoops report counts and
+    /// sizes. Samples beyond the limits are skipped.
+    pub fn snoop_with_limits(
+        &self,
+        target: impl Into<SnoopTarget>,
+        sink: SnoopSink,
+        limits: SnoopLimits,
+    ) -> Result<(), SnoopError> {
+        let target = target.into();
+        let channel = self
+            .channel_taps()
+            .find(&target)
+            .ok_or_else(|| SnoopError::UnknownChannel(target.to_string()))?;
+        self.snoops
+            .lock()
+            .unwrap()
+            .attach(channel, &self.snoop_formats, sink, limits)
+    }
+
+    /// Detaches a snoop attached with [Runtime::snoop] after delivering its remaining samples
+    pub fn unsnoop(&self, target: impl Into<SnoopTarget>) -> Result<(), SnoopError> {
+        let target = target.into();
+        let channel = self
+            .channel_taps()
+            .find(&target)
+            .ok_or_else(|| SnoopError::UnknownChannel(target.to_string()))?;
+        self.snoops.lock().unwrap().detach(channel)
+    }
+
+    /// Raw sender of the control channel
+    ///
+    /// Sending blocks if the channel is full and repeated stop requests are all forwarded. Prefer
+    /// [Runtime::control_handle] which deduplicates requests and never blocks.
     pub fn tx_control(&mut self) -> std::sync::mpsc::SyncSender<RuntimeControl> {
         self.tx_control.clone()
     }
 
+    /// Cloneable handle to request a stop from codelets or other threads
+    pub fn control_handle(&self) -> ControlHandle {
+        self.control_handle.clone()
+    }
+
     /// If called the program will stop when Ctrl+C is pressed
     pub fn enable_terminate_on_ctrl_c(&mut self) {
         log::info!("Press Ctrl+C to stop..");
 
-        let tx = self.tx_control();
+        let handle = self.control_handle();
         ctrlc::set_handler(move || {
-            tx.send(RuntimeControl::RequestStop)
+            handle
+                .request_stop()
                 .expect("Could not send signal on channel.")
         })
         .expect("Error setting Ctrl-C handler");
     }
 
+    /// Takes the result of schedules dry-run in [LaunchMode::DryRun]
+    ///
+    /// Fails with the first error of a schedule or with a `DryRunError` listing every codelet which
+    /// failed.
+    pub fn dry_run_result(&mut self) -> Result<Vec<DryRunReport>> {
+        let reports = core::mem::take(&mut self.dry_run_reports);
+        if let Some(err) = self.dry_run_error.take() {
+            return Err(err);
+        }
+        if reports.iter().all(|r| r.is_ok()) {
+            Ok(reports)
+        } else {
+            Err(DryRunError { reports }.into())
+        }
+    }
+
     pub fn spin(&mut self) {
-        let sleep_duration = Duration::from_millis(250);
+        if let Some(level) = self.log_level {
+            log::set_max_level(level);
+        }
+
+        match self.launch_mode {
+            LaunchMode::Run => {
+                self.spin_impl(Duration::from_millis(250), |_| false).ok();
+                statistics_pretty_print(self.report());
+            }
+            LaunchMode::DryRun => match self.dry_run_result() {
+                Ok(_) => log::info!("Dry run succeeded for {} codelets.", self.manifold().len()),
+                Err(err) => log::error!("{err:?}"),
+            },
+            LaunchMode::ListCodelets => print!("{}", self.manifold()),
+        }
+    }
+
+    /// Runs until all workers finished, a stop is requested via the control channel or
+    /// `interrupt` returns true. The callback is evaluated every `poll_interval`.
+    pub(crate) fn spin_impl(
+        &mut self,
+        poll_interval: Duration,
+        mut interrupt: impl FnMut(&Self) -> bool,
+    ) -> Result<()> {
+        if let Err(err) = self.codelet_exec.release_start_barrier() {
+            log::error!("{err}");
+            self.stop_and_join()?;
+            return Err(err.into());
+        }
 
         loop {
-            match self.rx_control.recv_timeout(sleep_duration) {
+            match self.rx_control.recv_timeout(poll_interval) {
                 Err(RecvTimeoutError::Timeout) => {
                     if self.codelet_exec.is_finished() {
                         log::info!("All workers finished.");
-                        break;
+                        let result = self.codelet_exec.join();
+                        self.on_workers_joined();
+                        return Ok(result?);
+                    }
+                    if self.control_handle.stop_requested() {
+                        log::info!("Stop requested..");
+                        return Ok(self.stop_and_join()?);
+                    }
+                    if interrupt(self) {
+                        log::info!("Stop requested by caller..");
+                        return Ok(self.stop_and_join()?);
                     }
                 }
                 Err(RecvTimeoutError::Disconnected) => {
@@ -70,23 +539,90 @@ impl Runtime {
                 }
                 Ok(RuntimeControl::RequestStop) => {
                     log::info!("Stop requested..");
-                    self.codelet_exec.request_stop();
-                    self.codelet_exec.join();
-                    log::info!("All workers stopped.");
-                    break;
+                    return Ok(self.stop_and_join()?);
                 }
             }
 
             // inspector
-            if let Some(inspector) = self.inspector_server.as_ref() {
-                match inspector.send_report(self.codelet_exec.report()) {
-                    Err(err) => log::error!("inspector could not send report: {err:?}"),
-                    Ok(()) => {}
+            let reports = self
+                .inspector_server
+                .is_some()
+                .then(|| self.schedule_reports());
+            if let (Some(inspector), Some(reports)) = (self.inspector_server.as_mut(), reports) {
+                if let Err(err) = inspector.send_schedule_reports(reports) {
+                    log::error!("inspector could not send report: {err:?}");
                 }
             }
+
+            self.handle_inspector_commands();
+
+            self.snoops
+                .get_mut()
+                .unwrap()
+                .deliver(self.inspector_server.as_mut());
+
+            // dead weight detection
+            if self.dead_weight.as_ref().is_some_and(|d| !d.is_reported()) {
+                let report = self.report();
+                if let Some(dead_weight) = self.dead_weight.as_mut() {
+                    dead_weight.check(&report);
+                }
+            }
+
+            // periodic snapshot
+            if let Some(file) = self.snapshot.as_mut() {
+                if file
+                    .config
+                    .interval
+                    .is_some_and(|interval| file.last_write.elapsed() >= interval)
+                {
+                    file.write();
+                }
+            }
+        }
+    }
+
+    #[cfg(any(test, feature = "test-util"))]
+    pub(crate) fn is_finished(&self) -> bool {
+        self.codelet_exec.is_finished()
+    }
+
+    pub(crate) fn stop_and_join(&mut self) -> Result<(), WorkerJoinError> {
+        self.codelet_exec.request_stop();
+        let result = self.codelet_exec.join();
+        log::info!("All workers stopped.");
+        self.on_workers_joined();
+        result
+    }
+
+    fn on_workers_joined(&mut self) {
+        self.snoops
+            .get_mut()
+            .unwrap()
+            .deliver(self.inspector_server.as_mut());
+        self.write_snapshot();
+        self.write_stats_json();
+        if self.print_queue_sizing {
+            self.queue_sizing_report().pretty_print();
+        }
+    }
+
+    fn write_stats_json(&self) {
+        let Some(path) = self.stats_json_path.as_ref() else {
+            return;
+        };
+        let result = serde_json::to_string_pretty(&self.report())
+            .map_err(eyre::Report::from)
+            .and_then(|json| Ok(std::fs::write(path, json)?));
+        if let Err(err) = result {
+            log::error!("could not write statistics to {path:?}: {err:#}");
         }
+    }
 
-        statistics_pretty_print(self.codelet_exec.report());
+    fn write_snapshot(&mut self) {
+        if let Some(file) = self.snapshot.as_mut() {
+            file.write();
+        }
     }
 
     #[deprecated(since = "0.2.0", note = "use `enable_terminate_on_ctrl_c` instead")]
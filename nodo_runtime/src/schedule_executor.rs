@@ -1,28 +1,77 @@
 // Copyright 2024 by David Weikersdorfer. All rights reserved.
 
-use crate::{InspectorCodeletReport, InspectorReport, RenderedStatus, StateMachine};
+use crate::{
+    BacklogTeardown, ChannelTaps, DryRunCodeletReport, DryRunReport, DryRunTransition,
+    InspectorCodeletReport, InspectorReport, InspectorScheduleReport, Manifold, ManifoldEntry,
+    RenderedStatus, SheddingStatistics, Sleeper, Snapshot, State, StateMachine, Teardown,
+    ThreadMonitor, TransitionError, CPU_SAMPLE_INTERVAL,
+};
 use core::time::Duration;
 use eyre::Result;
-use nodo::codelet::{DynamicVise, Lifecycle, NodeletSetup, ScheduleBuilder, Transition, ViseTrait};
+use nodo::codelet::{
+    CatchUpPolicy, DynamicVise, FailurePlan, JitterStatistics, Lifecycle, LoadShedding,
+    NodeletSetup, ResourceRegistry, ScheduleBuilder, ScheduleCycle, Transition, ViseTrait, Warmup,
+};
 use nodo_core::{Report, *};
-use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::Arc, time::Instant};
+
+/// How the worker of a schedule triggers steps
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StepMode {
+    /// Steps are executed periodically or as fast as possible
+    #[default]
+    Auto,
+
+    /// Steps are only executed when triggered, e.g. with `Runtime::step_schedule_once`. Codelets
+    /// see a time step of at most one period and a manual app clock is advanced by one period
+    /// per step. Start and stop are not held back.
+    Manual,
+}
 
 impl From<ScheduleBuilder> for ScheduleExecutor {
     fn from(builder: ScheduleBuilder) -> Self {
+        let is_warmup = match builder.warmup {
+            Some(Warmup::Steps(count)) => count > 0,
+            Some(Warmup::Duration(duration)) => !duration.is_zero(),
+            None => false,
+        };
+
         ScheduleExecutor {
-            name: builder.name,
+            name: builder.name.into(),
             thread_id: builder.thread_id,
-            sm: StateMachine::new(SequenceGroupExec::new(
-                builder
-                    .sequences
-                    .into_iter()
-                    .map(|seq| SequenceExec::new(seq.name, seq.period, seq.vises)),
-            )),
+            sm: StateMachine::new(SequenceGroupExec::new(builder.sequences.into_iter().map(
+                |seq| {
+                    SequenceExec::new(seq.name, seq.period, seq.vises)
+                        .with_auto_stop_on_closed(seq.auto_stop_on_closed)
+                        .with_priority(seq.priority)
+                },
+            ))),
             next_transition: Some(Transition::Start),
             max_step_count: builder.max_step_count,
             num_steps: 0,
             period: builder.period,
             last_instant: None,
+            scheduled_instant: None,
+            warmup: builder.warmup,
+            exclude_warmup_statistics: builder.exclude_warmup_statistics,
+            is_warmup,
+            first_step_instant: None,
+            catch_up: builder.catch_up,
+            max_dt: builder.max_dt,
+            suspend_resume_count: 0,
+            start_after: builder.start_after,
+            cycle: None,
+            start_error: None,
+            jitter: JitterStatistics::default(),
+            step_mode: StepMode::Auto,
+            load_shedding: builder.load_shedding,
+            sleeper: Sleeper::new(builder.sleep),
+            overrun_count: 0,
+            clean_count: 0,
+            thread_monitor: None,
+            teardown: Teardown::default(),
+            teardown_backlogs: Vec::new(),
         }
     }
 }
@@ -30,7 +79,7 @@ impl From<ScheduleBuilder> for ScheduleExecutor {
 /// A schedule of codelets to be executed
 #[derive(Debug)]
 pub struct ScheduleExecutor {
-    name: String,
+    name: Name,
     thread_id: usize,
     sm: StateMachine<SequenceGroupExec>,
     next_transition: Option<Transition>,
@@ -38,6 +87,40 @@ pub struct ScheduleExecutor {
     num_steps: usize,
     period: Option<Duration>,
     last_instant: Option<Instant>,
+
+    /// Instant at which the worker planned to execute the next step, see `next_instant`
+    scheduled_instant: Option<Instant>,
+
+    warmup: Option<Warmup>,
+    exclude_warmup_statistics: bool,
+    is_warmup: bool,
+    first_step_instant: Option<Instant>,
+    catch_up: CatchUpPolicy,
+    max_dt: Option<Duration>,
+    suspend_resume_count: u64,
+    start_after: Vec<String>,
+    cycle: Option<ScheduleCycle>,
+    start_error: Option<TransitionError>,
+    jitter: JitterStatistics,
+    step_mode: StepMode,
+    load_shedding: Option<LoadShedding>,
+
+    /// Waits for the next step of a periodic schedule
+    sleeper: Sleeper,
+
+    /// Number of consecutive steps which overran the period
+    overrun_count: usize,
+
+    /// Number of consecutive steps which finished within the period
+    clean_count: usize,
+
+    /// Monitors the thread of the worker which executes the schedule
+    thread_monitor: Option<ThreadMonitor>,
+
+    teardown: Teardown,
+
+    /// Large backlogs which were freed at teardown
+    teardown_backlogs: Vec<BacklogTeardown>,
 }
 
 impl ScheduleExecutor {
@@ -49,10 +132,57 @@ impl ScheduleExecutor {
         self.thread_id
     }
 
+    /// Starts to monitor the OS thread and CPU of the calling thread. Called by the worker
+    /// thread which executes the schedule.
+    pub(crate) fn monitor_thread(&mut self) {
+        self.thread_monitor = Some(ThreadMonitor::new(CPU_SAMPLE_INTERVAL));
+    }
+
+    /// Names of the schedules this schedule depends on, see `ScheduleBuilder::with_start_after`
+    pub fn start_after(&self) -> &[String] {
+        &self.start_after
+    }
+
+    /// Index of the current cycle, see [ScheduleCycle]
+    pub fn cycle_index(&self) -> u64 {
+        self.cycle.as_ref().map_or(0, |cycle| cycle.index())
+    }
+
     pub fn is_terminated(&self) -> bool {
         self.next_transition.is_none()
     }
 
+    /// Transition executed by the next spin or None if the schedule terminated
+    pub fn next_transition(&self) -> Option<Transition> {
+        self.next_transition
+    }
+
+    pub fn step_mode(&self) -> StepMode {
+        self.step_mode
+    }
+
+    /// Sets how steps are triggered, see [StepMode]
+    ///
+    /// Time spent in manual mode is neither detected as a gap nor counted as jitter.
+    pub fn set_step_mode(&mut self, mode: StepMode) {
+        if mode == self.step_mode {
+            return;
+        }
+        log::info!("Schedule {:?} switched to {mode:?} step mode", self.name);
+        self.step_mode = mode;
+
+        let max_dt = match (mode, self.max_dt, self.period) {
+            (StepMode::Manual, Some(max_dt), Some(period)) => Some(max_dt.min(period)),
+            (StepMode::Manual, max_dt, period) => max_dt.or(period),
+            (StepMode::Auto, max_dt, _) => max_dt,
+        };
+        self.sm.inner_mut().set_max_dt(max_dt);
+
+        self.last_instant = None;
+        self.scheduled_instant = None;
+        self.jitter.reset_anchor();
+    }
+
     pub fn period(&self) -> Option<Duration> {
         self.period
     }
@@ -61,14 +191,86 @@ impl ScheduleExecutor {
         self.last_instant
     }
 
-    pub fn setup(&mut self, setup: NodeletSetup) {
+    /// Number of gaps in time detected by `next_instant`, e.g. due to a system suspend
+    pub fn suspend_resume_count(&self) -> u64 {
+        self.suspend_resume_count
+    }
+
+    /// Deviation of steps from the ideal grid given by the period of the schedule
+    pub fn jitter(&self) -> &JitterStatistics {
+        &self.jitter
+    }
+
+    /// Instant at which the next step should be executed or None if the schedule is not periodic
+    ///
+    /// The worker is expected to wake up at the returned instant, or immediately if the previous
+    /// step overran the period. How late the next step actually begins is checked for gaps by
+    /// `spin`.
+    pub fn next_instant(&mut self, now: Instant) -> Option<Instant> {
+        let next_instant = self.last_instant? + self.period?;
+        self.scheduled_instant = Some(next_instant.max(now));
+        Some(next_instant)
+    }
+
+    /// Checks if a step beginning at the given instant is late by more than the number of periods
+    /// allowed by the catch-up policy. Such a gap, e.g. due to a system suspend, is logged and
+    /// excluded from period and jitter statistics. Time spent executing an overrunning step is not
+    /// part of the lag.
+    fn detect_gap(&mut self, time_begin: Instant) {
+        let (Some(period), Some(scheduled_instant)) = (self.period, self.scheduled_instant.take())
+        else {
+            return;
+        };
+
+        let lag = time_begin.saturating_duration_since(scheduled_instant);
+        match self.catch_up {
+            CatchUpPolicy::SkipAhead(max_periods) if lag > period * max_periods => {
+                log::warn!(
+                    "Schedule {:?} detected a gap of {} (system suspend?). Skipping ahead.",
+                    self.name,
+                    fmt_duration(lag)
+                );
+                self.suspend_resume_count += 1;
+                self.sm.inner_mut().on_clock_gap();
+                self.jitter.reset_anchor();
+            }
+            _ => {}
+        }
+    }
+
+    /// Sleeps until the given instant with the sleep strategy of the schedule
+    ///
+    /// The overshoot of the sleep is recorded in the report of the schedule.
+    pub fn sleep_until(&mut self, instant: Instant) {
+        self.sleeper.sleep_until(instant);
+    }
+
+    /// True while the schedule is in its warm-up phase
+    pub fn is_warmup(&self) -> bool {
+        self.is_warmup
+    }
+
+    pub fn setup(&mut self, mut setup: NodeletSetup) {
+        let cycle = ScheduleCycle::new(setup.clocks.app_mono.clone());
+        setup.clocks.schedule = cycle.clone();
+        self.cycle = Some(cycle);
+
         self.sm.inner_mut().setup(setup);
+        self.sm
+            .inner_mut()
+            .set_warmup(self.is_warmup, self.exclude_warmup_statistics);
+        self.sm.inner_mut().set_max_dt(self.max_dt);
+        self.sm.inner_mut().set_nominal_period(self.period);
     }
 
     pub fn spin(&mut self) {
         let time_begin = Instant::now();
         self.last_instant = Some(time_begin);
 
+        if let Some(monitor) = self.thread_monitor.as_mut() {
+            monitor.on_spin(&self.name);
+        }
+
         if self.next_transition.is_some() {
             if let Some(max_step_count) = self.max_step_count {
                 if self.num_steps >= max_step_count {
@@ -78,12 +280,48 @@ impl ScheduleExecutor {
         }
 
         if let Some(transition) = self.next_transition {
+            if let Some(cycle) = self.cycle.as_ref() {
+                match transition {
+                    Transition::Start => cycle.begin(),
+                    Transition::Step => cycle.advance(),
+                    _ => {}
+                }
+            }
+
+            if transition == Transition::Step && self.step_mode == StepMode::Auto {
+                self.detect_gap(time_begin);
+            }
+
+            match (transition, self.period) {
+                (Transition::Step, Some(period)) if self.step_mode == StepMode::Auto => {
+                    self.jitter.push(time_begin, period)
+                }
+                (Transition::Step, None) => {}
+                _ => self.jitter.reset_anchor(),
+            }
+
             if transition == Transition::Step {
+                self.advance_manual_clock();
                 self.num_steps += 1;
+                self.update_warmup(time_begin);
             }
 
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!(
+                "schedule",
+                schedule = %self.name,
+                transition = ?transition,
+                step = self.num_steps,
+                cycle = self.cycle_index(),
+            )
+            .entered();
+
             let result = self.sm.transition(transition);
 
+            if transition == Transition::Step && self.step_mode == StepMode::Auto {
+                self.update_load_shedding(time_begin.elapsed());
+            }
+
             match result {
                 Ok(OutcomeKind::Running) | Ok(OutcomeKind::Skipped) => {
                     self.next_transition = match transition {
@@ -101,11 +339,103 @@ impl ScheduleExecutor {
                         Transition::Stop => None,
                         _ => Some(Transition::Stop),
                     };
+                    if transition == Transition::Start {
+                        self.start_error = Some(err);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Executes the start transition of all codelets
+    ///
+    /// Fails if a codelet failed to start in which case the schedule stops on the next spin.
+    pub fn start(&mut self) -> Result<()> {
+        if self.next_transition != Some(Transition::Start) {
+            eyre::bail!("schedule {:?} was already started", self.name);
+        }
+        self.spin();
+        match self.start_error.take() {
+            Some(err) => Err(err.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Sheds or restores sequences based on the duration of the last step, see [LoadShedding]
+    fn update_load_shedding(&mut self, duration: Duration) {
+        let (Some(policy), Some(period)) = (self.load_shedding, self.period) else {
+            return;
+        };
+
+        if duration > period {
+            self.clean_count = 0;
+            self.overrun_count += 1;
+            if self.overrun_count >= policy.trigger_after_overruns {
+                self.overrun_count = 0;
+                if let Some((priority, names)) = self.sm.inner_mut().shed_lowest_priority() {
+                    log::warn!(
+                        "Schedule {:?} overran its period of {} for {} steps: shedding \
+                         sequences {names:?} with priority {priority}",
+                        self.name,
+                        fmt_duration(period),
+                        policy.trigger_after_overruns
+                    );
+                }
+            }
+        } else {
+            self.overrun_count = 0;
+            self.clean_count += 1;
+            if self.clean_count >= policy.recover_after_clean {
+                self.clean_count = 0;
+                if let Some((priority, names)) = self.sm.inner_mut().restore_highest_priority() {
+                    log::info!(
+                        "Schedule {:?} kept its period for {} steps: restoring sequences \
+                         {names:?} with priority {priority}",
+                        self.name,
+                        policy.recover_after_clean
+                    );
                 }
             }
         }
     }
 
+    /// Advances a manual app clock by one period per manually triggered step
+    fn advance_manual_clock(&self) {
+        if self.step_mode != StepMode::Manual {
+            return;
+        }
+        if let (Some(cycle), Some(period)) = (self.cycle.as_ref(), self.period) {
+            if cycle.app_mono().is_manual() {
+                cycle.app_mono().advance(period);
+            }
+        }
+    }
+
+    fn update_warmup(&mut self, now: Instant) {
+        if !self.is_warmup {
+            return;
+        }
+
+        let first_step_instant = *self.first_step_instant.get_or_insert(now);
+        let is_warmup = match self.warmup {
+            Some(Warmup::Steps(count)) => self.num_steps <= count,
+            Some(Warmup::Duration(duration)) => now - first_step_instant < duration,
+            None => false,
+        };
+
+        if !is_warmup {
+            log::info!(
+                "Schedule {:?} finished warm-up after {} steps",
+                self.name,
+                self.num_steps - 1
+            );
+            self.is_warmup = false;
+            self.sm
+                .inner_mut()
+                .set_warmup(false, self.exclude_warmup_statistics);
+        }
+    }
+
     pub fn finalize(&mut self) {
         if self.sm.is_valid_request(Transition::Stop) {
             self.sm.transition(Transition::Stop).unwrap();
@@ -113,11 +443,256 @@ impl ScheduleExecutor {
         }
     }
 
+    /// Sets how messages still queued in receivers are freed at teardown
+    pub(crate) fn set_teardown(&mut self, teardown: Teardown) {
+        self.teardown = teardown;
+    }
+
+    /// Frees messages which are still queued in receivers after the schedule stopped
+    ///
+    /// Large backlogs are reported and freed according to the teardown configuration. Messages
+    /// which are queued later are freed when the codelets are dropped.
+    pub(crate) fn teardown(&mut self) {
+        for csm in self
+            .sm
+            .inner_mut()
+            .items
+            .iter_mut()
+            .flat_map(|seq| seq.items.iter_mut())
+        {
+            let vise = csm.inner_mut();
+            for (channel, backlog) in vise.take_rx_backlogs() {
+                self.teardown_backlogs.extend(self.teardown.free(
+                    &self.name,
+                    vise.name(),
+                    &channel,
+                    backlog,
+                ));
+            }
+        }
+    }
+
     pub fn report(&self) -> InspectorReport {
-        self.sm.inner().report()
+        let mut report = self.sm.inner().report();
+        for entry in report.codelets.values_mut() {
+            entry.schedule = self.name.clone();
+            entry.suspend_resume_count = self.suspend_resume_count;
+        }
+        report.push_schedule(
+            self.name.clone(),
+            InspectorScheduleReport {
+                period: self.period,
+                jitter: self.jitter.clone(),
+                shed_sequences: self.sm.inner().shed_sequences(),
+                shedding: self.sm.inner().shedding_statistics(),
+                thread: self
+                    .thread_monitor
+                    .as_ref()
+                    .map(|monitor| monitor.report().clone()),
+                teardown: self.teardown_backlogs.clone(),
+                sleep_overshoot: self.sleeper.overshoot().clone(),
+                unknown: UnknownFields::default(),
+            },
+        );
+        report
+    }
+
+    /// Executes start, a single step and stop of all codelets back-to-back ignoring periods
+    ///
+    /// Codelets can check `Context::is_dry_run` to avoid accessing hardware or the network. Unlike
+    /// during normal execution a failing codelet does not stop other codelets. Instead outcomes
+    /// of all codelets are collected in the report. Every codelet which started successfully is
+    /// stopped, even if its step failed, such that it can release acquired resources. The
+    /// schedule is terminated afterwards.
+    pub fn dry_run(&mut self) -> Result<DryRunReport> {
+        if self.next_transition != Some(Transition::Start) {
+            eyre::bail!("schedule {:?} was already started", self.name);
+        }
+        self.next_transition = None;
+
+        let mut codelets = Vec::new();
+        for seq in self.sm.inner_mut().items.iter_mut() {
+            for csm in seq.items.iter_mut() {
+                csm.inner_mut().set_dry_run(true);
+                codelets.push(DryRunCodeletReport {
+                    sequence: seq.name.to_string(),
+                    name: csm.inner().name().to_string(),
+                    type_name: csm.inner().type_name().to_string(),
+                    transitions: Vec::new(),
+                });
+            }
+        }
+
+        for transition in [Transition::Start, Transition::Step, Transition::Stop] {
+            let csms = self
+                .sm
+                .inner_mut()
+                .items
+                .iter_mut()
+                .flat_map(|seq| seq.items.iter_mut());
+            for (csm, codelet) in csms.zip(codelets.iter_mut()) {
+                // codelets which failed are not stepped anymore but started codelets are always
+                // stopped
+                let is_started = codelet
+                    .transitions
+                    .first()
+                    .is_some_and(|t| t.result.is_ok());
+                let execute = match transition {
+                    Transition::Stop => is_started,
+                    _ => codelet.is_ok(),
+                };
+                if !execute {
+                    continue;
+                }
+
+                let time_begin = Instant::now();
+                let result = if csm.is_valid_request(transition) {
+                    csm.transition(transition)
+                } else {
+                    // The state machine is in the error state after a failed step.
+                    csm.inner_mut()
+                        .cycle(transition)
+                        .map_err(|err| TransitionError::ExecutionFailure(transition, err))
+                };
+                codelet.transitions.push(DryRunTransition {
+                    transition,
+                    duration: time_begin.elapsed(),
+                    result: result.map_err(|err| match err {
+                        TransitionError::ExecutionFailure(_, report) => format!("{report:#}"),
+                        err => err.to_string(),
+                    }),
+                });
+            }
+        }
+
+        Ok(DryRunReport {
+            schedule: self.name.to_string(),
+            codelets,
+        })
+    }
+
+    /// Sets the registry from which all codelets of the schedule acquire resources
+    pub fn set_resources(&mut self, resources: Arc<ResourceRegistry>) {
+        for seq in self.sm.inner_mut().items.iter_mut() {
+            for vise in seq.items.iter_mut() {
+                vise.inner_mut().set_resources(resources.clone());
+            }
+        }
+    }
+
+    /// Arms or disarms the failure plan of the codelet with the given name. Returns false if the
+    /// schedule has no such codelet.
+    pub fn set_failure_plan(&mut self, codelet: &str, plan: Option<FailurePlan>) -> bool {
+        let Some(vise) = self
+            .sm
+            .inner_mut()
+            .items
+            .iter_mut()
+            .flat_map(|seq| seq.items.iter_mut())
+            .map(|csm| csm.inner_mut())
+            .find(|vise| vise.name() == codelet)
+        else {
+            return false;
+        };
+        vise.set_failure_plan(plan);
+        true
+    }
+
+    /// Adds all codelet instances of this schedule to the manifold and their RX channels to the
+    /// channels which can be snooped
+    pub(crate) fn register(&mut self, manifold: &mut Manifold, taps: &mut ChannelTaps) {
+        for seq in self.sm.inner_mut().items.iter_mut() {
+            for vise in seq.items.iter_mut() {
+                let vise = vise.inner_mut();
+                let mut entry = ManifoldEntry {
+                    id: vise.id(),
+                    name: vise.name().to_string(),
+                    type_name: vise.type_name().to_string(),
+                    sequence: seq.name.to_string(),
+                    schedule: self.name.to_string(),
+                    rx: vise.rx_endpoints(),
+                    tx: vise.tx_endpoints(),
+                };
+                entry.assign_channel_uids();
+                vise.set_rx_channel_uids(entry.rx.iter().filter_map(|e| e.uid).collect());
+                for (endpoint, tap) in entry.rx.iter().zip(vise.rx_tap_points()) {
+                    if let (Some(uid), Some(tap)) = (endpoint.uid, tap) {
+                        taps.push(uid, format!("{}/rx.{}", entry.path(), endpoint.name), tap);
+                    }
+                }
+                manifold.push(entry);
+            }
+        }
+    }
+
+    /// Restores the state of persisted codelets. Codelets without a usable saved state are
+    /// started with their initial state.
+    pub fn restore_snapshot(&mut self, snapshot: &Snapshot) {
+        for vise in self
+            .sm
+            .inner_mut()
+            .items
+            .iter_mut()
+            .flat_map(|seq| seq.items.iter_mut())
+            .map(|csm| csm.inner_mut())
+        {
+            let Some(key) = vise.persistence_key().map(str::to_string) else {
+                continue;
+            };
+            match snapshot.get(&key) {
+                Some(state) => match vise.restore_state(state) {
+                    Ok(()) => log::info!("Restored state of codelet '{}'", vise.name()),
+                    Err(err) => log::warn!(
+                        "Could not restore state '{key}' of codelet '{}': {err:#}. Cold start.",
+                        vise.name()
+                    ),
+                },
+                None => log::info!(
+                    "No saved state '{key}' for codelet '{}': cold start",
+                    vise.name()
+                ),
+            }
+        }
+    }
+
+    /// Saves the state of persisted codelets into the snapshot
+    pub fn save_snapshot(&self, snapshot: &mut Snapshot) {
+        for vise in self
+            .sm
+            .inner()
+            .items
+            .iter()
+            .flat_map(|seq| seq.items.iter())
+            .map(|csm| csm.inner())
+        {
+            let Some(key) = vise.persistence_key() else {
+                continue;
+            };
+            match vise.save_state() {
+                Ok(Some(state)) => snapshot.insert(key.to_string(), state),
+                Ok(None) => {}
+                Err(err) => log::warn!(
+                    "Could not save state '{key}' of codelet '{}': {err:#}",
+                    vise.name()
+                ),
+            }
+        }
+    }
+
+    /// The codelet which is currently executing a transition, if any
+    pub fn in_flight(&self) -> Option<InFlightCodelet> {
+        self.sm.inner().items.iter().find_map(|seq| seq.in_flight())
     }
 }
 
+/// Identifies a codelet which is in the middle of executing a transition
+#[derive(Debug, Clone, PartialEq)]
+pub struct InFlightCodelet {
+    pub sequence: String,
+    pub codelet: String,
+    pub transition: Transition,
+}
+
 /// A group of codelet sequences which are executed one after another
 ///
 /// The group runs as long as any item in it is running.
@@ -138,6 +713,30 @@ impl SequenceGroupExec {
         }
     }
 
+    pub fn set_warmup(&mut self, is_warmup: bool, exclude_statistics: bool) {
+        for item in self.items.iter_mut() {
+            item.set_warmup(is_warmup, exclude_statistics);
+        }
+    }
+
+    pub fn set_max_dt(&mut self, max_dt: Option<Duration>) {
+        for item in self.items.iter_mut() {
+            item.set_max_dt(max_dt);
+        }
+    }
+
+    pub fn set_nominal_period(&mut self, period: Option<Duration>) {
+        for item in self.items.iter_mut() {
+            item.set_nominal_period(period);
+        }
+    }
+
+    pub fn on_clock_gap(&mut self) {
+        for item in self.items.iter_mut() {
+            item.on_clock_gap();
+        }
+    }
+
     pub fn report(&self) -> InspectorReport {
         let mut result = InspectorReport::default();
         for item in self.items.iter() {
@@ -145,6 +744,65 @@ impl SequenceGroupExec {
         }
         result
     }
+
+    /// Sheds all sequences with the lowest priority which are not shed yet. Returns the priority
+    /// and the names of the shed sequences.
+    pub fn shed_lowest_priority(&mut self) -> Option<(u8, Vec<Name>)> {
+        let priority = self
+            .items
+            .iter()
+            .filter(|item| item.priority > 0 && !item.is_shed)
+            .map(|item| item.priority)
+            .max()?;
+        let mut names = Vec::new();
+        for item in self
+            .items
+            .iter_mut()
+            .filter(|item| item.priority == priority)
+        {
+            item.shed();
+            names.push(item.name.clone());
+        }
+        Some((priority, names))
+    }
+
+    /// Restores all shed sequences with the highest priority. Returns the priority and the
+    /// names of the restored sequences.
+    pub fn restore_highest_priority(&mut self) -> Option<(u8, Vec<Name>)> {
+        let priority = self
+            .items
+            .iter()
+            .filter(|item| item.is_shed)
+            .map(|item| item.priority)
+            .min()?;
+        let mut names = Vec::new();
+        for item in self
+            .items
+            .iter_mut()
+            .filter(|item| item.is_shed && item.priority == priority)
+        {
+            item.restore();
+            names.push(item.name.clone());
+        }
+        Some((priority, names))
+    }
+
+    /// Names of sequences which are currently shed
+    pub fn shed_sequences(&self) -> Vec<Name> {
+        self.items
+            .iter()
+            .filter(|item| item.is_shed)
+            .map(|item| item.name.clone())
+            .collect()
+    }
+
+    pub fn shedding_statistics(&self) -> BTreeMap<Name, SheddingStatistics> {
+        self.items
+            .iter()
+            .filter(|item| item.shedding.shed_count > 0)
+            .map(|item| (item.name.clone(), item.shedding))
+            .collect()
+    }
 }
 
 impl Lifecycle for SequenceGroupExec {
@@ -166,9 +824,16 @@ impl Lifecycle for SequenceGroupExec {
 
 /// Executes a Sequence of nodos.
 pub(crate) struct SequenceExec {
-    name: String,
+    name: Name,
     period: Option<Duration>,
     items: Vec<StateMachine<DynamicVise>>,
+    auto_stop_on_closed: bool,
+    in_flight: Option<(usize, Transition)>,
+    priority: u8,
+
+    /// If set the codelets are paused by load shedding and not stepped
+    is_shed: bool,
+    shedding: SheddingStatistics,
 }
 
 impl SequenceExec {
@@ -178,12 +843,61 @@ impl SequenceExec {
         vises: I,
     ) -> Self {
         Self {
-            name,
+            name: name.into(),
             period,
             items: vises
                 .into_iter()
                 .map(|vise| StateMachine::new(vise))
                 .collect(),
+            auto_stop_on_closed: false,
+            in_flight: None,
+            priority: 0,
+            is_shed: false,
+            shedding: SheddingStatistics::default(),
+        }
+    }
+
+    fn in_flight(&self) -> Option<InFlightCodelet> {
+        self.in_flight.map(|(index, transition)| InFlightCodelet {
+            sequence: self.name.to_string(),
+            codelet: self.items[index].inner().name().to_string(),
+            transition,
+        })
+    }
+
+    pub fn with_auto_stop_on_closed(mut self, enabled: bool) -> Self {
+        self.auto_stop_on_closed = enabled;
+        self
+    }
+
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Pauses all started codelets. The sequence is not stepped until it is restored.
+    fn shed(&mut self) {
+        self.is_shed = true;
+        self.shedding.shed_count += 1;
+        self.transition_all(State::Started, Transition::Pause);
+    }
+
+    /// Resumes all codelets paused by [SequenceExec::shed]
+    fn restore(&mut self) {
+        self.is_shed = false;
+        self.shedding.restore_count += 1;
+        self.transition_all(State::Paused, Transition::Resume);
+    }
+
+    /// Executes a transition for all codelets in the given state. Failures are logged.
+    fn transition_all(&mut self, state: State, transition: Transition) {
+        for csm in self.items.iter_mut().filter(|csm| csm.state() == state) {
+            if let Err(err) = csm.transition(transition) {
+                log::error!(
+                    "Codelet {:?} failed {transition:?} for load shedding: {err:?}",
+                    csm.inner().name()
+                );
+            }
         }
     }
 
@@ -193,6 +907,30 @@ impl SequenceExec {
         }
     }
 
+    pub fn set_warmup(&mut self, is_warmup: bool, exclude_statistics: bool) {
+        for csm in self.items.iter_mut() {
+            csm.inner_mut().set_warmup(is_warmup, exclude_statistics);
+        }
+    }
+
+    pub fn set_max_dt(&mut self, max_dt: Option<Duration>) {
+        for csm in self.items.iter_mut() {
+            csm.inner_mut().set_max_dt(max_dt);
+        }
+    }
+
+    pub fn set_nominal_period(&mut self, period: Option<Duration>) {
+        for csm in self.items.iter_mut() {
+            csm.inner_mut().set_nominal_period(period);
+        }
+    }
+
+    pub fn on_clock_gap(&mut self) {
+        for csm in self.items.iter_mut() {
+            csm.inner_mut().on_clock_gap();
+        }
+    }
+
     pub fn report(&self) -> InspectorReport {
         let mut report = InspectorReport::default();
         for vice in self.items.iter() {
@@ -200,13 +938,28 @@ impl SequenceExec {
                 vice.inner().id(),
                 InspectorCodeletReport {
                     sequence: self.name.clone(),
-                    name: vice.inner().name().to_string(),
-                    typename: vice.inner().type_name().to_string(),
-                    status: vice
-                        .inner()
-                        .status()
-                        .map(|(label, status)| RenderedStatus { label, status }),
+                    name: vice.inner().name().clone(),
+                    typename: vice.inner().type_name().clone(),
+                    status: vice.inner().status().map(|s| RenderedStatus {
+                        label: s.label,
+                        status: s.status,
+                        code: s.code,
+                        severity: s.severity,
+                        skip_reason: s.skip_reason.map(|r| r.label().to_string()),
+                    }),
                     statistics: vice.inner().statistics().clone(),
+                    is_warmup: vice.inner().is_warmup(),
+                    labels: vice.inner().labels().to_vec(),
+                    suspected_inactive: false,
+                    suspend_resume_count: 0,
+                    deadline: vice.inner().deadline(),
+                    start_diagnostics: vice.inner().start_diagnostics().to_vec(),
+                    schedule: Name::default(),
+                    progress: vice
+                        .inner()
+                        .progress()
+                        .map(|(fraction, detail)| (fraction, detail.to_string())),
+                    unknown: UnknownFields::default(),
                 },
             );
         }
@@ -216,14 +969,43 @@ impl SequenceExec {
 
 impl Lifecycle for SequenceExec {
     fn cycle(&mut self, transition: Transition) -> Outcome {
+        // shed sequences stay paused until they are restored
+        if self.is_shed
+            && matches!(
+                transition,
+                Transition::Step | Transition::Pause | Transition::Resume
+            )
+        {
+            return RUNNING;
+        }
+
         let mut result = SequenceExecCycleResult::new();
 
-        for csm in self.items.iter_mut() {
-            match csm.transition(transition) {
-                Err(err) => {
-                    result.mark(csm.inner(), err.into());
+        for (index, csm) in self.items.iter_mut().enumerate() {
+            let transition = if self.auto_stop_on_closed {
+                match (csm.state(), transition) {
+                    // codelets which were stopped automatically are not executed anymore
+                    (State::Inactive, t) if t != Transition::Start => continue,
+                    (State::Started, Transition::Step) if csm.inner().is_rx_closed() => {
+                        log::info!(
+                            "Stopping codelet {:?} as all its RX channels are closed.",
+                            csm.inner().name()
+                        );
+                        Transition::Stop
+                    }
+                    _ => transition,
                 }
-                Ok(_) => {}
+            } else {
+                transition
+            };
+
+            // remembered to identify the codelet in case it panics
+            self.in_flight = Some((index, transition));
+            let outcome = csm.transition(transition);
+            self.in_flight = None;
+
+            if let Err(err) = outcome {
+                result.mark(csm.inner(), err.into());
             }
         }
 
@@ -279,3 +1061,342 @@ impl From<SequenceExecCycleResult> for Option<eyre::Report> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{decode_report, encode_report, ScheduleExecutor, StepMode};
+    use core::time::Duration;
+    use nodo::{
+        codelet::{
+            CatchUpPolicy, Clocks, NodeletId, NodeletSetup, ScheduleBuilder, Transition, WorkerId,
+        },
+        prelude::*,
+    };
+    use nodo_core::AppMonotonicClock;
+    use std::{
+        sync::{Arc, Mutex},
+        time::Instant,
+    };
+
+    struct WarmupProbe {
+        log: Arc<Mutex<Vec<bool>>>,
+    }
+
+    impl Codelet for WarmupProbe {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = ();
+        type Tx = ();
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            ((), ())
+        }
+
+        fn step(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            self.log.lock().unwrap().push(cx.is_warmup());
+            SUCCESS
+        }
+    }
+
+    fn probe_schedule(exclude_statistics: bool) -> (ScheduleExecutor, Arc<Mutex<Vec<bool>>>) {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let probe = WarmupProbe { log: log.clone() }.into_instance("probe", ());
+        let mut schedule = ScheduleExecutor::from(
+            ScheduleBuilder::new()
+                .with_warmup_steps(3)
+                .with_exclude_warmup_statistics(exclude_statistics)
+                .with(probe),
+        );
+        schedule.setup(NodeletSetup {
+            clocks: Clocks::new(),
+            nodelet_id_issue: NodeletId(WorkerId(0), 0),
+        });
+        (schedule, log)
+    }
+
+    fn step_count(schedule: &ScheduleExecutor) -> u64 {
+        let report = schedule.report().into_vec();
+        report[0].1.statistics.transitions[Transition::Step]
+            .duration
+            .count()
+    }
+
+    #[test]
+    fn test_warmup_steps() {
+        let (mut schedule, log) = probe_schedule(false);
+        assert!(schedule.is_warmup());
+
+        // start and five steps
+        for _ in 0..6 {
+            schedule.spin();
+        }
+
+        assert_eq!(*log.lock().unwrap(), [true, true, true, false, false]);
+        assert!(!schedule.is_warmup());
+        assert!(!schedule.report().into_vec()[0].1.is_warmup);
+        assert_eq!(step_count(&schedule), 5);
+    }
+
+    #[test]
+    fn test_warmup_excluded_from_statistics() {
+        let (mut schedule, log) = probe_schedule(true);
+
+        for _ in 0..4 {
+            schedule.spin();
+        }
+        assert!(schedule.report().into_vec()[0].1.is_warmup);
+        assert_eq!(step_count(&schedule), 0);
+
+        for _ in 0..2 {
+            schedule.spin();
+        }
+        assert_eq!(log.lock().unwrap().len(), 5);
+        assert_eq!(step_count(&schedule), 2);
+    }
+
+    fn periodic_schedule(policy: CatchUpPolicy) -> ScheduleExecutor {
+        let probe = WarmupProbe {
+            log: Arc::new(Mutex::new(Vec::new())),
+        }
+        .into_instance("probe", ());
+        let mut schedule = ScheduleExecutor::from(
+            ScheduleBuilder::new()
+                .with_period(Duration::from_millis(10))
+                .with_catch_up_policy(policy)
+                .with(probe),
+        );
+        schedule.setup(NodeletSetup {
+            clocks: Clocks::new(),
+            nodelet_id_issue: NodeletId(WorkerId(0), 0),
+        });
+        schedule.spin();
+        schedule
+    }
+
+    #[test]
+    fn test_skip_ahead_after_gap() {
+        let period = Duration::from_millis(10);
+        let mut schedule = periodic_schedule(CatchUpPolicy::SkipAhead(3));
+
+        // a small delay is caught up
+        schedule.scheduled_instant = Some(Instant::now() - 2 * period);
+        schedule.spin();
+        assert_eq!(schedule.suspend_resume_count(), 0);
+
+        // a gap skips ahead
+        schedule.scheduled_instant = Some(Instant::now() - Duration::from_secs(5));
+        schedule.spin();
+        assert_eq!(schedule.suspend_resume_count(), 1);
+        assert_eq!(schedule.report().into_vec()[0].1.suspend_resume_count, 1);
+
+        // the next step is scheduled one period after the late step
+        let last_instant = schedule.last_instant().unwrap();
+        assert_eq!(
+            schedule.next_instant(last_instant),
+            Some(last_instant + period)
+        );
+    }
+
+    #[test]
+    fn test_catch_up_after_gap() {
+        let mut schedule = periodic_schedule(CatchUpPolicy::CatchUp);
+
+        schedule.scheduled_instant = Some(Instant::now() - Duration::from_secs(5));
+        schedule.spin();
+        assert_eq!(schedule.suspend_resume_count(), 0);
+    }
+
+    struct Overrun {
+        duration: Duration,
+    }
+
+    impl Codelet for Overrun {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = ();
+        type Tx = ();
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            ((), ())
+        }
+
+        fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            std::thread::sleep(self.duration);
+            SUCCESS
+        }
+    }
+
+    #[test]
+    fn test_overrun_is_not_a_gap() {
+        let period = Duration::from_millis(10);
+        let mut schedule = ScheduleExecutor::from(
+            ScheduleBuilder::new().with_period(period).with(
+                Overrun {
+                    duration: 15 * period,
+                }
+                .into_instance("overrun", ()),
+            ),
+        );
+        schedule.setup(NodeletSetup {
+            clocks: Clocks::new(),
+            nodelet_id_issue: NodeletId(WorkerId(0), 0),
+        });
+
+        // start and a few steps which take longer than the default limit of 10 periods
+        schedule.spin();
+        for _ in 0..3 {
+            let next_instant = schedule.next_instant(Instant::now()).unwrap();
+            assert_eq!(next_instant, schedule.last_instant().unwrap() + period);
+            schedule.sleep_until(next_instant);
+            schedule.spin();
+        }
+
+        assert_eq!(schedule.suspend_resume_count(), 0);
+    }
+
+    #[test]
+    fn test_gap_excluded_from_period() {
+        let mut schedule = periodic_schedule(CatchUpPolicy::SkipAhead(3));
+        schedule.spin();
+
+        schedule.scheduled_instant = Some(Instant::now() - Duration::from_secs(5));
+        schedule.spin();
+
+        let report = schedule.report().into_vec();
+        let step = &report[0].1.statistics.transitions[Transition::Step];
+        assert_eq!(step.duration.count(), 2);
+        assert_eq!(step.period.count(), 0);
+    }
+
+    /// App time and time step seen by the codelet in every step
+    type TimeLog = Arc<Mutex<Vec<(Duration, f32)>>>;
+
+    struct TimeProbe {
+        log: TimeLog,
+    }
+
+    impl Codelet for TimeProbe {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = ();
+        type Tx = ();
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            ((), ())
+        }
+
+        fn step(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            self.log.lock().unwrap().push((
+                cx.clocks.app_mono.now().into(),
+                cx.clocks.codelet.dt_secs_f32(),
+            ));
+            SUCCESS
+        }
+    }
+
+    fn time_probe_schedule(clocks: Clocks) -> (ScheduleExecutor, TimeLog) {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut schedule = ScheduleExecutor::from(
+            ScheduleBuilder::new()
+                .with_period(Duration::from_millis(10))
+                .with(TimeProbe { log: log.clone() }.into_instance("probe", ())),
+        );
+        schedule.setup(NodeletSetup {
+            clocks,
+            nodelet_id_issue: NodeletId(WorkerId(0), 0),
+        });
+        (schedule, log)
+    }
+
+    #[test]
+    fn test_manual_step_mode_advances_manual_clock() {
+        let clocks = Clocks::new_manual();
+        let (mut schedule, log) = time_probe_schedule(clocks.clone());
+        schedule.start().unwrap();
+
+        schedule.set_step_mode(StepMode::Manual);
+        for _ in 0..3 {
+            schedule.spin();
+        }
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                (Duration::from_millis(10), 0.01),
+                (Duration::from_millis(20), 0.01),
+                (Duration::from_millis(30), 0.01)
+            ]
+        );
+
+        // the manual clock is not advanced in auto mode
+        schedule.set_step_mode(StepMode::Auto);
+        schedule.spin();
+        assert_eq!(log.lock().unwrap()[3], (Duration::from_millis(30), 0.0));
+    }
+
+    #[test]
+    fn test_manual_step_mode_dt() {
+        let (mut schedule, log) = time_probe_schedule(Clocks::new());
+        schedule.start().unwrap();
+
+        // time between manual steps is not seen by codelets
+        schedule.set_step_mode(StepMode::Manual);
+        std::thread::sleep(Duration::from_millis(30));
+        schedule.spin();
+        assert_eq!(log.lock().unwrap()[0].1, 0.01);
+
+        schedule.set_step_mode(StepMode::Auto);
+        std::thread::sleep(Duration::from_millis(30));
+        schedule.spin();
+        assert!(log.lock().unwrap()[1].1 >= 0.03);
+
+        // the time spent in manual mode is not detected as a gap
+        assert_eq!(schedule.suspend_resume_count(), 0);
+    }
+
+    struct Sink;
+
+    impl Codelet for Sink {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = DoubleBufferRx<u32>;
+        type Tx = ();
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            (DoubleBufferRx::new_auto_size(), ())
+        }
+
+        fn step(&mut self, _: &Context<Self>, rx: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            while rx.try_pop().is_some() {}
+            SUCCESS
+        }
+    }
+
+    #[test]
+    fn test_rx_residency_in_report() {
+        let mut sink = Sink.into_instance("sink", ());
+        sink.rx.enable_latency_tracking(AppMonotonicClock::new());
+        let mut tx = DoubleBufferTx::new_auto_size();
+        tx.connect(&mut sink.rx).unwrap();
+
+        let mut schedule = ScheduleExecutor::from(ScheduleBuilder::new().with(sink));
+        schedule.setup(NodeletSetup {
+            clocks: Clocks::new(),
+            nodelet_id_issue: NodeletId(WorkerId(0), 0),
+        });
+        schedule.spin();
+
+        for i in 0..3 {
+            tx.push(i).unwrap();
+        }
+        tx.flush();
+        std::thread::sleep(Duration::from_millis(2));
+        schedule.spin();
+
+        // the residency is part of the encoded inspector report
+        let report = decode_report(&encode_report(&schedule.report()).unwrap()).unwrap();
+        let channel = &report.into_vec()[0].1.statistics.rx_channels[0];
+        assert_eq!(channel.received, 3);
+        assert_eq!(channel.residency.count(), 3);
+        assert!(channel.residency.min().unwrap() >= Duration::from_millis(2));
+    }
+}
@@ -1,42 +1,293 @@
 // Copyright 2023 by David Weikersdorfer. All rights reserved.
 
+use nodo::codelet::SleepStrategy;
+use serde::{Deserialize, Serialize};
 use std::time::{Duration, Instant};
 
+/// Time before the target at which `accurate_sleep` switches from OS sleep to spinning
+const NATIVE_ACCURACY: Duration = Duration::from_millis(15); // TODO
+
+/// Smallest slack chosen by [AdaptiveSlack]
+pub const MIN_ADAPTIVE_SLACK: Duration = Duration::from_micros(50);
+
+/// Largest slack chosen by [AdaptiveSlack]
+pub const MAX_ADAPTIVE_SLACK: Duration = NATIVE_ACCURACY;
+
 /// Sleeps for a certain duration with high accuracy potentially using a spin loop
 pub fn accurate_sleep(duration: Duration) {
-    accurate_sleep_impl(Instant::now() + duration, duration);
+    hybrid_sleep_until(Instant::now() + duration, NATIVE_ACCURACY);
 }
 
 /// Sleeps up to a time instant with high accuracy potentially using a spin loop
 pub fn accurate_sleep_until(target: Instant) {
-    accurate_sleep_impl(target, target - Instant::now()); // Duration will wrap to 0
+    hybrid_sleep_until(target, NATIVE_ACCURACY);
+}
+
+/// Timing of a sleep executed by [hybrid_sleep_until]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SleepTiming {
+    /// Time by which the sleep returned after the deadline
+    pub overshoot: Duration,
+
+    /// Time by which the OS sleep woke up after its target, i.e. `slack` before the deadline.
+    /// None if the deadline was too close for an OS sleep.
+    pub os_overshoot: Option<Duration>,
 }
 
-fn accurate_sleep_impl(target: Instant, duration: Duration) {
-    const NATIVE_ACCURACY: Duration = Duration::from_millis(15); // TODO
+/// Sleeps with the OS until `slack` before the deadline and spins for the rest
+///
+/// Spinning keeps the CPU core busy, thus a large slack increases power consumption. With a slack
+/// of zero the function only uses the OS sleep.
+pub fn hybrid_sleep_until(deadline: Instant, slack: Duration) -> SleepTiming {
+    let now = Instant::now();
+    let duration = deadline.saturating_duration_since(now);
 
-    // native sleep for majority up to accuracy
-    if duration > NATIVE_ACCURACY {
-        let native_sleep_duration = duration - NATIVE_ACCURACY;
-        std::thread::sleep(native_sleep_duration);
+    let os_overshoot = (duration > slack).then(|| {
+        let os_target = deadline - slack;
+        std::thread::sleep(os_target - now);
+        Instant::now().saturating_duration_since(os_target)
+    });
+
+    SleepTiming {
+        overshoot: spin_until(deadline),
+        os_overshoot,
     }
+}
 
-    // spin the rest
-    while Instant::now() <= target {
+/// Spins until the deadline and returns the time by which it returned after the deadline
+pub fn spin_until(deadline: Instant) -> Duration {
+    loop {
+        let now = Instant::now();
+        if now > deadline {
+            return now - deadline;
+        }
         std::hint::spin_loop();
     }
 }
 
+/// Spin slack of a hybrid sleep tuned from the measured overshoot of OS sleeps
+///
+/// The slack is twice the peak overshoot. The peak follows larger overshoots immediately and
+/// decays slowly towards smaller ones such that a single outlier is not forgotten right away.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdaptiveSlack {
+    peak: Duration,
+    slack: Duration,
+}
+
+impl AdaptiveSlack {
+    /// Factor between peak overshoot and slack
+    const HEADROOM: u32 = 2;
+
+    /// The peak moves by 1/DECAY of the difference towards a smaller overshoot
+    const DECAY: u32 = 8;
+
+    pub fn new(initial: Duration) -> Self {
+        let slack = initial.clamp(MIN_ADAPTIVE_SLACK, MAX_ADAPTIVE_SLACK);
+        Self {
+            peak: slack / Self::HEADROOM,
+            slack,
+        }
+    }
+
+    pub fn slack(&self) -> Duration {
+        self.slack
+    }
+
+    /// Updates the slack with the overshoot of an OS sleep
+    pub fn update(&mut self, os_overshoot: Duration) {
+        self.peak = if os_overshoot >= self.peak {
+            os_overshoot
+        } else {
+            self.peak - (self.peak - os_overshoot) / Self::DECAY
+        };
+        self.slack = (self.peak * Self::HEADROOM).clamp(MIN_ADAPTIVE_SLACK, MAX_ADAPTIVE_SLACK);
+    }
+}
+
+/// Time by which the worker of a schedule woke up after the deadline of the next step
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SleepStatistics {
+    count: u64,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+impl SleepStatistics {
+    pub fn push(&mut self, overshoot: Duration) {
+        if self.count == 0 {
+            self.min = overshoot;
+            self.max = overshoot;
+        } else {
+            self.min = self.min.min(overshoot);
+            self.max = self.max.max(overshoot);
+        }
+        self.count += 1;
+        self.total += overshoot;
+    }
+
+    /// Number of sleeps
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    pub fn avg(&self) -> Option<Duration> {
+        (self.count > 0).then(|| self.total.div_f64(self.count as f64))
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        (self.count > 0).then_some(self.max)
+    }
+}
+
+/// Waits for the steps of a schedule with its sleep strategy and measures the overshoot
+#[derive(Debug, Clone)]
+pub(crate) struct Sleeper {
+    strategy: SleepStrategy,
+    adaptive: Option<AdaptiveSlack>,
+    overshoot: SleepStatistics,
+}
+
+impl Sleeper {
+    pub fn new(strategy: SleepStrategy) -> Self {
+        let adaptive = match strategy {
+            SleepStrategy::Hybrid {
+                slack,
+                adaptive: true,
+            } => Some(AdaptiveSlack::new(slack)),
+            _ => None,
+        };
+        Self {
+            strategy,
+            adaptive,
+            overshoot: SleepStatistics::default(),
+        }
+    }
+
+    pub fn overshoot(&self) -> &SleepStatistics {
+        &self.overshoot
+    }
+
+    /// Sleeps until the deadline. Overshoot is only recorded if the deadline is in the future.
+    pub fn sleep_until(&mut self, deadline: Instant) {
+        if deadline <= Instant::now() {
+            return;
+        }
+
+        let overshoot = match (self.strategy, self.adaptive.as_mut()) {
+            (SleepStrategy::Spin, _) => spin_until(deadline),
+            (_, Some(adaptive)) => {
+                let timing = hybrid_sleep_until(deadline, adaptive.slack());
+                if let Some(os_overshoot) = timing.os_overshoot {
+                    adaptive.update(os_overshoot);
+                }
+                timing.overshoot
+            }
+            (SleepStrategy::Hybrid { slack, .. }, None) => {
+                hybrid_sleep_until(deadline, slack).overshoot
+            }
+        };
+        self.overshoot.push(overshoot);
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::sleep::{accurate_sleep, accurate_sleep_until};
+    use crate::sleep::{
+        accurate_sleep, accurate_sleep_until, hybrid_sleep_until, AdaptiveSlack, SleepStatistics,
+        Sleeper, MAX_ADAPTIVE_SLACK, MIN_ADAPTIVE_SLACK,
+    };
     use core::time::Duration;
+    use nodo::codelet::SleepStrategy;
     use std::time::Instant;
 
+    fn us(x: u64) -> Duration {
+        Duration::from_micros(x)
+    }
+
     #[test]
     fn test_accurate_sleep() {
         accurate_sleep(Duration::from_millis(100));
         accurate_sleep_until(Instant::now() + Duration::from_millis(100));
         accurate_sleep_until(Instant::now() - Duration::from_millis(100));
     }
+
+    #[test]
+    fn test_hybrid_sleep_until() {
+        let deadline = Instant::now() + Duration::from_millis(5);
+        let timing = hybrid_sleep_until(deadline, us(500));
+        assert!(Instant::now() > deadline);
+        assert!(timing.os_overshoot.is_some());
+
+        // too close for an OS sleep
+        let timing = hybrid_sleep_until(Instant::now() + us(100), us(500));
+        assert_eq!(timing.os_overshoot, None);
+    }
+
+    #[test]
+    fn test_adaptive_slack() {
+        let mut slack = AdaptiveSlack::new(us(200));
+        assert_eq!(slack.slack(), us(200));
+
+        // larger overshoots are followed immediately
+        slack.update(us(300));
+        assert_eq!(slack.slack(), us(600));
+
+        // smaller overshoots decay slowly
+        slack.update(us(140));
+        assert_eq!(slack.slack(), us(560));
+        for _ in 0..100 {
+            slack.update(Duration::ZERO);
+        }
+        assert_eq!(slack.slack(), MIN_ADAPTIVE_SLACK);
+
+        slack.update(Duration::from_secs(1));
+        assert_eq!(slack.slack(), MAX_ADAPTIVE_SLACK);
+
+        assert_eq!(
+            AdaptiveSlack::new(Duration::ZERO).slack(),
+            MIN_ADAPTIVE_SLACK
+        );
+    }
+
+    #[test]
+    fn test_sleep_statistics() {
+        let mut stats = SleepStatistics::default();
+        assert_eq!(stats.avg(), None);
+
+        for x in [30, 10, 20] {
+            stats.push(us(x));
+        }
+        assert_eq!(stats.count(), 3);
+        assert_eq!(stats.min(), Some(us(10)));
+        assert_eq!(stats.avg(), Some(us(20)));
+        assert_eq!(stats.max(), Some(us(30)));
+    }
+
+    #[test]
+    fn test_sleeper() {
+        for strategy in [
+            SleepStrategy::default(),
+            SleepStrategy::Hybrid {
+                slack: us(200),
+                adaptive: true,
+            },
+            SleepStrategy::Spin,
+        ] {
+            let mut sleeper = Sleeper::new(strategy);
+            for _ in 0..3 {
+                sleeper.sleep_until(Instant::now() + Duration::from_millis(2));
+            }
+
+            // deadlines in the past are not recorded
+            sleeper.sleep_until(Instant::now() - Duration::from_millis(2));
+
+            assert_eq!(sleeper.overshoot().count(), 3, "{strategy:?}");
+        }
+    }
 }
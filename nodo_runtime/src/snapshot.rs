@@ -0,0 +1,241 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use eyre::{Result, WrapErr};
+use nodo::codelet::PersistedState;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+const MAGIC: &[u8; 8] = b"NODOSNAP";
+const FORMAT_VERSION: u8 = 1;
+const CRC: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISCSI);
+
+/// Where and how often states of persisted codelets are saved, see `Runtime::with_snapshot`
+#[derive(Debug, Clone)]
+pub struct SnapshotConfig {
+    /// File which stores the states of all persisted codelets
+    pub path: PathBuf,
+
+    /// If set states are additionally saved periodically while the runtime is running.
+    /// Otherwise they are only saved at clean shutdown.
+    pub interval: Option<Duration>,
+}
+
+impl SnapshotConfig {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            interval: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+}
+
+/// Saved states of persisted codelets indexed by their persistence key
+///
+/// The file starts with a header and an index listing key, version, offset, length and checksum
+/// of every entry followed by the data of all entries. Entries which are corrupt are skipped.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    entries: BTreeMap<String, PersistedState>,
+}
+
+impl Snapshot {
+    pub fn get(&self, key: &str) -> Option<&PersistedState> {
+        self.entries.get(key)
+    }
+
+    pub fn insert(&mut self, key: String, state: PersistedState) {
+        self.entries.insert(key, state);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|k| k.as_str())
+    }
+
+    /// Loads a snapshot from a file. A missing or corrupt file results in an empty snapshot.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        match std::fs::read(path) {
+            Ok(bytes) => Self::decode(&bytes),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                log::info!("No snapshot at {path:?}: cold start");
+                Self::default()
+            }
+            Err(err) => {
+                log::warn!("Could not read snapshot {path:?}: {err}. Cold start.");
+                Self::default()
+            }
+        }
+    }
+
+    /// Writes the snapshot to a temporary file which then replaces the given file such that a
+    /// crash while writing does not corrupt an existing snapshot
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, self.encode())
+            .wrap_err_with(|| format!("could not write snapshot {tmp:?}"))?;
+        std::fs::rename(&tmp, path)
+            .wrap_err_with(|| format!("could not replace snapshot {path:?}"))?;
+        Ok(())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut index = Vec::new();
+        let mut data = Vec::new();
+        for (key, state) in self.entries.iter() {
+            index.extend_from_slice(&(key.len() as u16).to_le_bytes());
+            index.extend_from_slice(key.as_bytes());
+            index.push(state.version);
+            index.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            index.extend_from_slice(&(state.data.len() as u64).to_le_bytes());
+            index.extend_from_slice(&CRC.checksum(&state.data).to_le_bytes());
+            data.extend_from_slice(&state.data);
+        }
+
+        let mut buffer = Vec::with_capacity(MAGIC.len() + 5 + index.len() + data.len());
+        buffer.extend_from_slice(MAGIC);
+        buffer.push(FORMAT_VERSION);
+        buffer.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        buffer.extend_from_slice(&index);
+        buffer.extend_from_slice(&data);
+        buffer
+    }
+
+    /// Decodes a snapshot. Corrupt entries are skipped with a warning.
+    pub fn decode(bytes: &[u8]) -> Self {
+        let mut snapshot = Self::default();
+
+        let mut reader = Reader(bytes);
+        if reader.take(MAGIC.len()) != Some(MAGIC) || reader.u8() != Some(FORMAT_VERSION) {
+            log::warn!("Snapshot has an unknown format. Cold start.");
+            return snapshot;
+        }
+        let Some(count) = reader.u32() else {
+            log::warn!("Snapshot is truncated. Cold start.");
+            return snapshot;
+        };
+
+        let mut index = Vec::new();
+        for _ in 0..count {
+            let Some(entry) = reader.index_entry() else {
+                log::warn!("Snapshot index is truncated. Cold start for missing entries.");
+                break;
+            };
+            index.push(entry);
+        }
+
+        let data = reader.0;
+        for (key, version, offset, len, checksum) in index {
+            let state = usize::try_from(offset)
+                .ok()
+                .zip(usize::try_from(len).ok())
+                .and_then(|(offset, len)| data.get(offset..offset.checked_add(len)?))
+                .filter(|state| CRC.checksum(state) == checksum);
+            match state {
+                Some(state) => snapshot.insert(
+                    key,
+                    PersistedState {
+                        version,
+                        data: state.to_vec(),
+                    },
+                ),
+                None => log::warn!("Snapshot entry '{key}' is corrupt. Cold start."),
+            }
+        }
+
+        snapshot
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2)
+            .map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    fn index_entry(&mut self) -> Option<(String, u8, u64, u64, u32)> {
+        let key_len = self.u16()? as usize;
+        let key = String::from_utf8(self.take(key_len)?.to_vec()).ok()?;
+        Some((key, self.u8()?, self.u64()?, self.u64()?, self.u32()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Snapshot;
+    use nodo::codelet::PersistedState;
+
+    fn snapshot() -> Snapshot {
+        let mut snapshot = Snapshot::default();
+        for (key, version, data) in [("alpha", 1, vec![1, 2, 3]), ("beta", 0, vec![])] {
+            snapshot.insert(key.into(), PersistedState { version, data });
+        }
+        snapshot
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let snapshot = snapshot();
+        assert_eq!(Snapshot::decode(&snapshot.encode()), snapshot);
+    }
+
+    #[test]
+    fn test_snapshot_corrupt() {
+        let mut bytes = snapshot().encode();
+
+        // flipping a data byte only invalidates the affected entry
+        let n = bytes.len();
+        bytes[n - 1] ^= 0xff;
+        let decoded = Snapshot::decode(&bytes);
+        assert!(decoded.get("alpha").is_none());
+        assert_eq!(decoded.get("beta").unwrap().version, 0);
+
+        // truncated or garbage files are empty
+        assert!(Snapshot::decode(&bytes[..20]).get("beta").is_none());
+        assert!(Snapshot::decode(b"garbage").is_empty());
+        assert!(Snapshot::decode(&[]).is_empty());
+    }
+}
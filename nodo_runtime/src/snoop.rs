@@ -0,0 +1,418 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::InspectorServer;
+use core::{
+    any::{Any, TypeId},
+    fmt,
+};
+use nodo::channels::{ChannelUid, SnoopContent, SnoopFormat, SnoopLimits, SnoopSample, TapPoint};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::{
+        mpsc::{sync_channel, Receiver},
+        Arc, Weak,
+    },
+};
+
+/// An RX channel which can be snooped
+pub struct ChannelTap {
+    /// Identifier of the channel as in the manifold
+    pub uid: ChannelUid,
+
+    /// Path of the channel, e.g. `schedule/sequence/codelet/rx.in`
+    pub path: String,
+
+    message_type_name: &'static str,
+
+    /// Weak such that the transmitter can prune the back stage of a dropped receiver
+    tap: Weak<dyn TapPoint>,
+}
+
+impl ChannelTap {
+    pub fn message_type_name(&self) -> &'static str {
+        self.message_type_name
+    }
+
+    /// True if a snoop is attached
+    pub fn is_snooped(&self) -> bool {
+        self.tap.upgrade().is_some_and(|tap| tap.is_attached())
+    }
+}
+
+/// All RX channels of the runtime which can be snooped
+#[derive(Default)]
+pub struct ChannelTaps {
+    taps: Vec<ChannelTap>,
+}
+
+impl ChannelTaps {
+    pub(crate) fn push(&mut self, uid: ChannelUid, path: String, tap: Arc<dyn TapPoint>) {
+        self.taps.push(ChannelTap {
+            uid,
+            path,
+            message_type_name: tap.message_type_name(),
+            tap: Arc::downgrade(&tap),
+        });
+    }
+
+    pub fn find(&self, target: &SnoopTarget) -> Option<&ChannelTap> {
+        self.taps.iter().find(|tap| match target {
+            SnoopTarget::Uid(uid) => tap.uid == *uid,
+            SnoopTarget::Path(path) => tap.path == *path,
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ChannelTap> {
+        self.taps.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.taps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.taps.is_empty()
+    }
+}
+
+/// Message types whose content can be streamed by snoops
+///
+/// Snoops on channels with other message types only report message counts and sizes.
+#[derive(Default, Clone)]
+pub struct SnoopFormats {
+    formats: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl SnoopFormats {
+    /// Streams messages of type `T` serialized as JSON
+    pub fn register_json<T: Serialize + 'static>(&mut self) {
+        self.insert::<T>(Arc::new(|value: &T| {
+            serde_json::to_string(value)
+                .map(SnoopContent::Json)
+                .unwrap_or_else(|err| SnoopContent::Debug(format!("serialization failed: {err}")))
+        }));
+    }
+
+    /// Streams messages of type `T` formatted with `Debug`
+    pub fn register_debug<T: fmt::Debug + 'static>(&mut self) {
+        self.insert::<T>(Arc::new(|value: &T| {
+            SnoopContent::Debug(format!("{value:?}"))
+        }));
+    }
+
+    /// Streams messages of type `T` with a custom format. Replaces a previous registration.
+    pub fn insert<T: 'static>(&mut self, format: SnoopFormat<T>) {
+        self.formats.insert(TypeId::of::<T>(), Arc::new(format));
+    }
+
+    pub fn contains(&self, type_id: TypeId) -> bool {
+        self.formats.contains_key(&type_id)
+    }
+
+    fn get(&self, type_id: TypeId) -> Option<&(dyn Any + Send + Sync)> {
+        self.formats.get(&type_id).map(|format| &**format)
+    }
+}
+
+/// Identifies the channel to snoop by its UID or by its path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnoopTarget {
+    Uid(ChannelUid),
+    Path(String),
+}
+
+impl From<ChannelUid> for SnoopTarget {
+    fn from(uid: ChannelUid) -> Self {
+        SnoopTarget::Uid(uid)
+    }
+}
+
+impl From<&str> for SnoopTarget {
+    fn from(path: &str) -> Self {
+        SnoopTarget::Path(path.into())
+    }
+}
+
+impl From<String> for SnoopTarget {
+    fn from(path: String) -> Self {
+        SnoopTarget::Path(path)
+    }
+}
+
+impl fmt::Display for SnoopTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnoopTarget::Uid(uid) => write!(f, "{uid}"),
+            SnoopTarget::Path(path) => write!(f, "{path}"),
+        }
+    }
+}
+
+/// Where samples of a snoop are delivered
+pub enum SnoopSink {
+    /// Calls the function for every sample
+    Callback(Box<dyn FnMut(&SnoopSample) + Send>),
+
+    /// Writes samples as JSON lines to a file. An existing file is truncated.
+    File(PathBuf),
+
+    /// Publishes samples on the inspector socket under [inspector_snoop_topic]. Samples are
+    /// dropped if the inspector is not enabled.
+    Inspector,
+}
+
+impl SnoopSink {
+    pub fn callback<F: FnMut(&SnoopSample) + Send + 'static>(f: F) -> Self {
+        SnoopSink::Callback(Box::new(f))
+    }
+}
+
+impl fmt::Debug for SnoopSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnoopSink::Callback(_) => write!(f, "Callback"),
+            SnoopSink::File(path) => write!(f, "File({path:?})"),
+            SnoopSink::Inspector => write!(f, "Inspector"),
+        }
+    }
+}
+
+/// Topic under which the inspector publishes samples of a snooped channel
+pub fn inspector_snoop_topic(channel: &str) -> String {
+    format!("snoop/{channel}")
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SnoopError {
+    #[error("no RX channel '{0}' which can be snooped")]
+    UnknownChannel(String),
+
+    #[error("channel '{0}' is already snooped")]
+    AlreadySnooped(String),
+
+    #[error("channel '{0}' is not snooped")]
+    NotSnooped(String),
+
+    #[error("the receiver of channel '{0}' was dropped")]
+    ChannelDropped(String),
+
+    #[error("could not open snoop file: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+enum ActiveSink {
+    Callback(Box<dyn FnMut(&SnoopSample) + Send>),
+    File(BufWriter<File>),
+    Inspector,
+}
+
+/// A snoop attached to a channel. The tap is detached when dropped.
+struct ActiveSnoop {
+    uid: ChannelUid,
+    path: String,
+    tap: Weak<dyn TapPoint>,
+    samples: Receiver<SnoopSample>,
+    sink: ActiveSink,
+}
+
+impl ActiveSnoop {
+    fn detach_tap(&self) {
+        if let Some(tap) = self.tap.upgrade() {
+            tap.detach();
+        }
+    }
+
+    fn deliver(&mut self, mut inspector: Option<&mut InspectorServer>) {
+        for sample in self.samples.try_iter() {
+            match &mut self.sink {
+                ActiveSink::Callback(callback) => callback(&sample),
+                ActiveSink::File(file) => {
+                    let result = serde_json::to_writer(&mut *file, &sample)
+                        .map_err(std::io::Error::from)
+                        .and_then(|()| file.write_all(b"\n"));
+                    if let Err(err) = result {
+                        log::error!("snoop '{}' could not write sample: {err}", self.path);
+                    }
+                }
+                ActiveSink::Inspector => {
+                    if let Some(inspector) = inspector.as_deref_mut() {
+                        if let Err(err) = inspector.send_snoop_sample(&self.path, &sample) {
+                            log::error!("inspector could not send snoop sample: {err:?}");
+                        }
+                    }
+                }
+            }
+        }
+
+        if let ActiveSink::File(file) = &mut self.sink {
+            if let Err(err) = file.flush() {
+                log::error!("snoop '{}' could not write samples: {err}", self.path);
+            }
+        }
+    }
+}
+
+impl Drop for ActiveSnoop {
+    fn drop(&mut self) {
+        self.detach_tap();
+    }
+}
+
+/// Snoops attached with `Runtime::snoop`
+#[derive(Default)]
+pub(crate) struct Snoops {
+    active: Vec<ActiveSnoop>,
+}
+
+impl Snoops {
+    pub fn attach(
+        &mut self,
+        channel: &ChannelTap,
+        formats: &SnoopFormats,
+        sink: SnoopSink,
+        limits: SnoopLimits,
+    ) -> Result<(), SnoopError> {
+        if self.active.iter().any(|snoop| snoop.uid == channel.uid) {
+            return Err(SnoopError::AlreadySnooped(channel.path.clone()));
+        }
+
+        let tap = channel
+            .tap
+            .upgrade()
+            .ok_or_else(|| SnoopError::ChannelDropped(channel.path.clone()))?;
+
+        let sink = match sink {
+            SnoopSink::Callback(callback) => ActiveSink::Callback(callback),
+            SnoopSink::File(path) => ActiveSink::File(BufWriter::new(File::create(path)?)),
+            SnoopSink::Inspector => ActiveSink::Inspector,
+        };
+
+        // samples which are not delivered within a second are skipped by the tap
+        let (tx, samples) = sync_channel(limits.max_samples_per_sec.max(1) as usize);
+        tap.attach(formats.get(tap.message_type_id()), limits, tx);
+
+        log::info!(
+            "Snooping channel '{}' of type {}",
+            channel.path,
+            tap.message_type_name()
+        );
+        self.active.push(ActiveSnoop {
+            uid: channel.uid,
+            path: channel.path.clone(),
+            tap: Arc::downgrade(&tap),
+            samples,
+            sink,
+        });
+        Ok(())
+    }
+
+    /// Detaches the snoop after delivering the remaining samples. Remaining samples for the
+    /// inspector are dropped.
+    pub fn detach(&mut self, channel: &ChannelTap) -> Result<(), SnoopError> {
+        let index = self
+            .active
+            .iter()
+            .position(|snoop| snoop.uid == channel.uid)
+            .ok_or_else(|| SnoopError::NotSnooped(channel.path.clone()))?;
+        let mut snoop = self.active.remove(index);
+        snoop.detach_tap();
+        snoop.deliver(None);
+        log::info!("Stopped snooping channel '{}'", channel.path);
+        Ok(())
+    }
+
+    pub fn deliver(&mut self, mut inspector: Option<&mut InspectorServer>) {
+        for snoop in self.active.iter_mut() {
+            snoop.deliver(inspector.as_deref_mut());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ChannelTaps, SnoopError, SnoopFormats, SnoopSink, SnoopTarget, Snoops};
+    use core::any::TypeId;
+    use nodo::channels::{
+        ChannelUid, DoubleBufferRx, DoubleBufferTx, Rx, SnoopContent, SnoopFormat, SnoopLimits,
+    };
+
+    #[test]
+    fn test_snoop_formats() {
+        let mut formats = SnoopFormats::default();
+        formats.register_json::<Vec<u32>>();
+        formats.register_debug::<(u8, char)>();
+        assert!(formats.contains(TypeId::of::<Vec<u32>>()));
+        assert!(!formats.contains(TypeId::of::<String>()));
+
+        let json = formats
+            .get(TypeId::of::<Vec<u32>>())
+            .and_then(|f| f.downcast_ref::<SnoopFormat<Vec<u32>>>())
+            .unwrap();
+        assert_eq!(json(&vec![1, 2]), SnoopContent::Json("[1,2]".into()));
+
+        let debug = formats
+            .get(TypeId::of::<(u8, char)>())
+            .and_then(|f| f.downcast_ref::<SnoopFormat<(u8, char)>>())
+            .unwrap();
+        assert_eq!(debug(&(1, 'a')), SnoopContent::Debug("(1, 'a')".into()));
+    }
+
+    #[test]
+    fn test_snoop_target() {
+        assert_eq!(
+            SnoopTarget::from("s/a/rx.in"),
+            SnoopTarget::Path("s/a/rx.in".into())
+        );
+        assert_eq!(
+            SnoopTarget::from(ChannelUid(7)).to_string(),
+            "0000000000000007"
+        );
+    }
+
+    #[test]
+    fn test_snoop_dropped_channel() {
+        let mut tx = DoubleBufferTx::<u32>::new_auto_size();
+        let mut rx = DoubleBufferRx::new_auto_size();
+        tx.connect(&mut rx).unwrap();
+
+        let mut taps = ChannelTaps::default();
+        taps.push(
+            ChannelUid(1),
+            "s/a/rx.in".into(),
+            Rx::tap_point(&rx).unwrap(),
+        );
+        let channel = taps.find(&"s/a/rx.in".into()).unwrap();
+        assert_eq!(channel.message_type_name(), "u32");
+
+        let mut snoops = Snoops::default();
+        snoops
+            .attach(
+                channel,
+                &SnoopFormats::default(),
+                SnoopSink::Callback(Box::new(|_| {})),
+                SnoopLimits::default(),
+            )
+            .unwrap();
+        assert!(channel.is_snooped());
+
+        // the taps do not keep the back stage of a dropped receiver alive
+        drop(rx);
+        tx.disconnect_dropped();
+        assert!(!channel.is_snooped());
+
+        snoops.detach(channel).unwrap();
+        assert!(matches!(
+            snoops.attach(
+                channel,
+                &SnoopFormats::default(),
+                SnoopSink::Callback(Box::new(|_| {})),
+                SnoopLimits::default(),
+            ),
+            Err(SnoopError::ChannelDropped(_))
+        ));
+    }
+}
@@ -1,91 +1,277 @@
 // Copyright 2023 by David Weikersdorfer. All rights reserved.
 
-use crate::{InspectorCodeletReport, InspectorReport};
-use nodo::codelet::Transition;
+use crate::{InspectorCodeletReport, InspectorReport, SleepStatistics, WorkerThreadReport};
+use core::{cmp::Reverse, fmt::Write, time::Duration};
+use nodo::codelet::{CountTotal, JitterStatistics, Transition};
+use nodo_core::fmt_duration;
 
 pub fn statistics_pretty_print(report: InspectorReport) {
+    print!("{}", format_statistics(report));
+}
+
+/// Formats the statistics table printed by [statistics_pretty_print]
+///
+/// Codelets are listed by their path `schedule/sequence/name` such that instances with the same
+/// name in different schedules can be told apart.
+pub fn format_statistics(report: InspectorReport) -> String {
+    let mut out = String::new();
+    let app_info = report.app_info().map(|info| {
+        if info.capabilities.is_empty() {
+            info.summary()
+        } else {
+            format!("{} [{}]", info.summary(), info.capabilities_summary())
+        }
+    });
+    let schedules = report.schedules().clone();
     let mut vec = report.into_vec();
-    vec.sort_by_key(|(_, u)| {
-        u.statistics.transitions[Transition::Step]
-            .duration
-            .total()
-            .as_nanos()
+    vec.sort_by_cached_key(|(_, u)| {
+        (
+            u.statistics.transitions[Transition::Step].duration.total(),
+            Reverse(u.path()),
+        )
     });
 
-    println!("");
-    println!("+--------------------------+----------------------------------+--------+--------+----------------------+-------+----------------------+--------+---------+");
-    println!("| NAME                     | TYPE                             | STEP              Duration                       Period               | START            |");
-    println!("|                          |                                  | Skipped| Count  | (min-avg-max) [ms]   | Total | (min-avg-max) [ms]   | Count  |  D [ms] |");
-    println!("+--------------------------+----------------------------------+--------+--------+----------------------+-------+----------------------+--------+---------+");
-    for (
-        _,
-        InspectorCodeletReport {
-            name: tag,
+    writeln!(out).unwrap();
+    if let Some(app_info) = app_info {
+        writeln!(out, "{app_info}").unwrap();
+    }
+    let separator = format!(
+        "+{}+{}+{}+{}+{}+{}+{}+{}+{}+",
+        "-".repeat(34),
+        "-".repeat(34),
+        "-".repeat(8),
+        "-".repeat(8),
+        "-".repeat(28),
+        "-".repeat(10),
+        "-".repeat(28),
+        "-".repeat(19),
+        "-".repeat(19),
+    );
+    writeln!(out, "{separator}").unwrap();
+    writeln!(
+        out,
+        "| {:32} | {:32} | {:6} | {:6} | {:26} | {:8} | {:26} | {:17} | {:17} |",
+        "NAME", "TYPE", "STEP", "", "Duration", "", "Period", "Jitter", "START"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "| {:32} | {:32} | {:>6} | {:>6} | {:^26} | {:>8} | {:^26} | {:^17} | {:>7} | {:>7} |",
+        "",
+        "",
+        "Skip",
+        "Count",
+        "min / avg / max",
+        "Total",
+        "min / avg / max",
+        "rms / max",
+        "Skip/N",
+        "avg"
+    )
+    .unwrap();
+    writeln!(out, "{separator}").unwrap();
+    let mut diagnostics = Vec::new();
+    for (_, codelet) in vec.into_iter().rev() {
+        let path = codelet.path();
+        let InspectorCodeletReport {
             typename,
             statistics: stats,
+            start_diagnostics,
             ..
-        },
-    ) in vec.into_iter().rev()
-    {
-        println!(
-            "| {:024} | {:032} | {:6} | {:6} | {} {} {} |{} | {} {} {} | {:2} /{:2} | {} |",
-            cut_middle(&tag, 24),
+        } = codelet;
+
+        if !start_diagnostics.is_empty() {
+            diagnostics.push((path.clone(), start_diagnostics));
+        }
+
+        let step = &stats.transitions[Transition::Step];
+        let start = &stats.transitions[Transition::Start];
+        writeln!(
+            out,
+            "| {:032} | {:032} | {:6} | {:6} | {} | {:>8} | {} | {} | {:>7} | {:>7} |",
+            cut_middle(&path, 32),
             cut_middle(&typename, 32),
-            stats.transitions[Transition::Step].skipped_count,
-            stats.transitions[Transition::Step].duration.count(),
-            stats.transitions[Transition::Step]
-                .duration
-                .min_ms()
-                .map(|dt| format!("{:>6.2}", dt))
-                .unwrap_or("------".to_string()),
-            stats.transitions[Transition::Step]
-                .duration
-                .average_ms()
-                .map(|dt| format!("{:>6.2}", dt))
-                .unwrap_or("------".to_string()),
-            stats.transitions[Transition::Step]
+            step.skipped_count,
+            step.duration.count(),
+            format_limits(&step.duration),
+            fmt_duration(step.duration.total()),
+            format_limits(&step.period),
+            format_jitter(&step.jitter),
+            format!("{}/{}", start.skipped_count, start.duration.count()),
+            start
                 .duration
-                .max_ms()
-                .map(|dt| format!("{:>6.2}", dt))
-                .unwrap_or("------".to_string()),
-            format!(
-                "{:>6.2}",
-                stats.transitions[Transition::Step]
-                    .duration
-                    .total()
-                    .as_secs_f32()
-            ),
-            stats.transitions[Transition::Step]
-                .period
-                .min_ms()
-                .map(|dt| format!("{:>6.2}", dt))
-                .unwrap_or("------".to_string()),
-            stats.transitions[Transition::Step]
-                .period
-                .average_ms()
-                .map(|dt| format!("{:>6.2}", dt))
-                .unwrap_or("------".to_string()),
-            stats.transitions[Transition::Step]
-                .period
-                .max_ms()
-                .map(|dt| format!("{:>6.2}", dt))
-                .unwrap_or("------".to_string()),
-            stats.transitions[Transition::Start].skipped_count,
-            stats.transitions[Transition::Start].duration.count(),
-            stats.transitions[Transition::Start]
-                .duration
-                .average_ms()
-                .map(|dt| format!("{:>7.2}", dt))
+                .average()
+                .map(fmt_duration)
                 .unwrap_or("-------".to_string()),
-        );
+        )
+        .unwrap();
     }
-    println!("+--------------------------+----------------------------------+--------+--------+----------------------+-------+----------------------+--------+---------+");
+    writeln!(out, "{separator}").unwrap();
+
+    let periodic = schedules
+        .iter()
+        .filter_map(|(name, schedule)| schedule.period.map(|period| (name, period, schedule)))
+        .collect::<Vec<_>>();
+    if !periodic.is_empty() {
+        writeln!(out, "Schedule jitter (rms / max):").unwrap();
+        for (name, period, schedule) in periodic {
+            writeln!(
+                out,
+                "  {name} [{}]: {}",
+                fmt_duration(period),
+                format_jitter(&schedule.jitter)
+            )
+            .unwrap();
+        }
+    }
+
+    let sleeping = schedules
+        .iter()
+        .filter(|(_, schedule)| schedule.sleep_overshoot.count() > 0)
+        .collect::<Vec<_>>();
+    if !sleeping.is_empty() {
+        writeln!(out, "Sleep overshoot (min / avg / max):").unwrap();
+        for (name, schedule) in sleeping {
+            writeln!(out, "  {name}: {}", format_sleep(&schedule.sleep_overshoot)).unwrap();
+        }
+    }
+
+    let threads = schedules
+        .iter()
+        .filter_map(|(name, schedule)| schedule.thread.as_ref().map(|thread| (name, thread)))
+        .filter(|(_, thread)| thread.os_thread_id.is_some())
+        .collect::<Vec<_>>();
+    if !threads.is_empty() {
+        writeln!(out, "Worker threads:").unwrap();
+        for (name, thread) in threads {
+            writeln!(out, "  {name}: {}", format_thread(thread)).unwrap();
+        }
+    }
+
+    if !diagnostics.is_empty() {
+        writeln!(out, "Start diagnostics:").unwrap();
+        for (name, messages) in diagnostics {
+            for msg in messages {
+                writeln!(out, "  {name}: {msg}").unwrap();
+            }
+        }
+    }
+
+    out
+}
+
+/// Formats min, average and max like `1.00 ms / 1.50 ms / 3.00 ms`
+fn format_limits(x: &CountTotal) -> String {
+    let f =
+        |d: Option<Duration>| format!("{:>8}", d.map(fmt_duration).unwrap_or("------".to_string()));
+    format!("{} {} {}", f(x.min()), f(x.average()), f(x.max()))
 }
 
-fn cut_middle(text: &String, len: usize) -> String {
+/// Formats RMS and max deviation like `0.10 ms  0.50 ms`
+fn format_jitter(x: &JitterStatistics) -> String {
+    let f =
+        |d: Option<Duration>| format!("{:>8}", d.map(fmt_duration).unwrap_or("------".to_string()));
+    format!("{} {}", f(x.rms()), f(x.max()))
+}
+
+/// Formats min, average and max overshoot like `0.01 ms  0.02 ms  0.30 ms`
+fn format_sleep(x: &SleepStatistics) -> String {
+    let f =
+        |d: Option<Duration>| format!("{:>8}", d.map(fmt_duration).unwrap_or("------".to_string()));
+    format!("{} {} {}", f(x.min()), f(x.avg()), f(x.max()))
+}
+
+/// Formats thread id, CPU and migrations like `tid 1234, cpu 3 (pinned), 2 migrations / 50`
+fn format_thread(x: &WorkerThreadReport) -> String {
+    let f = |v: Option<String>| v.unwrap_or("?".to_string());
+    format!(
+        "tid {}, cpu {}{}, {} migrations / {}",
+        f(x.os_thread_id.map(|tid| tid.to_string())),
+        f(x.last_cpu.map(|cpu| cpu.to_string())),
+        if x.pinned_cpu.is_some() {
+            " (pinned)"
+        } else {
+            ""
+        },
+        x.migration_count,
+        x.sample_count
+    )
+}
+
+fn cut_middle(text: &str, len: usize) -> String {
     if text.len() <= len || len <= 6 {
         text.to_string()
     } else {
         text[0..2].to_string() + ".." + &text[(text.len() - (len - 4))..]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{format_statistics, ScheduleExecutor};
+    use nodo::{
+        codelet::{Clocks, NodeletId, NodeletSetup, ScheduleBuilder, WorkerId},
+        prelude::*,
+    };
+
+    struct Serializer;
+
+    impl Codelet for Serializer {
+        type Status = DefaultStatus;
+        type Config = ();
+        type Rx = ();
+        type Tx = ();
+
+        fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+            ((), ())
+        }
+
+        fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+            SUCCESS
+        }
+    }
+
+    fn schedule(name: &str, worker: u32, step_count: usize) -> ScheduleExecutor {
+        let mut exec: ScheduleExecutor = ScheduleBuilder::new()
+            .with_name(name)
+            .with(Serializer.into_instance("serializer", ()))
+            .into();
+        exec.setup(NodeletSetup {
+            clocks: Clocks::new(),
+            nodelet_id_issue: NodeletId(WorkerId(worker), 0),
+        });
+        for _ in 0..=step_count {
+            exec.spin();
+        }
+        exec
+    }
+
+    #[test]
+    fn test_same_name_in_two_schedules() {
+        let left = schedule("left", 0, 3);
+        let right = schedule("right", 1, 5);
+
+        let mut report = left.report();
+        report.extend(right.report());
+        assert_eq!(report.iter().count(), 2);
+
+        // every instance has its own row with its own step count
+        let table = format_statistics(report);
+        let step_count = |path: &str| {
+            let rows = table
+                .lines()
+                .filter(|line| line.split('|').nth(1).unwrap_or("").trim() == path)
+                .collect::<Vec<_>>();
+            assert_eq!(rows.len(), 1, "{table}");
+            rows[0]
+                .split('|')
+                .nth(4)
+                .unwrap()
+                .trim()
+                .parse::<u64>()
+                .unwrap()
+        };
+        assert_eq!(step_count("left/serializer"), 3);
+        assert_eq!(step_count("right/serializer"), 5);
+    }
+}
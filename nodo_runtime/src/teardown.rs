@@ -0,0 +1,151 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use nodo::channels::Backlog;
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::Sender;
+
+/// Accounting of messages which are still queued in receivers when a schedule stops
+///
+/// Freeing a large backlog can take a long time, for example if an auto-sized channel grew out of
+/// control. Backlogs which exceed one of the thresholds are reported with a warning.
+#[derive(Debug, Clone)]
+pub struct TeardownConfig {
+    /// Backlogs with at least this many messages are reported
+    pub warn_len: usize,
+
+    /// Backlogs with at least this many estimated bytes are reported, see [Backlog::bytes]
+    pub warn_bytes: usize,
+
+    /// If enabled reported backlogs are freed on a low-priority reaper thread such that joining
+    /// the worker is not blocked by deallocation
+    pub deferred_drop: bool,
+}
+
+impl Default for TeardownConfig {
+    fn default() -> Self {
+        Self {
+            warn_len: 10_000,
+            warn_bytes: 64 * 1024 * 1024,
+            deferred_drop: false,
+        }
+    }
+}
+
+impl TeardownConfig {
+    fn is_large(&self, backlog: &Backlog) -> bool {
+        backlog.len() >= self.warn_len || backlog.bytes() >= self.warn_bytes
+    }
+}
+
+/// A large backlog which was freed when a schedule stopped
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BacklogTeardown {
+    pub codelet: String,
+    pub channel: String,
+
+    /// Number of messages
+    pub len: usize,
+
+    /// Estimated size of the messages in bytes
+    pub bytes: usize,
+
+    /// True if the messages were freed on the reaper thread
+    pub deferred: bool,
+}
+
+/// Frees backlogs on a dedicated low-priority thread
+///
+/// The thread terminates when all handles are dropped and the remaining backlogs are freed.
+#[derive(Debug, Clone)]
+pub(crate) struct Reaper {
+    tx: Sender<Backlog>,
+}
+
+impl Reaper {
+    pub fn new() -> Self {
+        let (tx, rx) = std::sync::mpsc::channel::<Backlog>();
+        std::thread::Builder::new()
+            .name("nodo-reaper".into())
+            .spawn(move || {
+                sys::lower_priority();
+                for backlog in rx {
+                    drop(backlog);
+                }
+            })
+            .unwrap();
+        Self { tx }
+    }
+
+    /// Frees the backlog on the reaper thread, or on the calling thread if the reaper is gone
+    fn reap(&self, backlog: Backlog) {
+        if let Err(err) = self.tx.send(backlog) {
+            drop(err.0);
+        }
+    }
+}
+
+/// Frees the backlogs of the codelets of a schedule
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Teardown {
+    pub config: TeardownConfig,
+    pub reaper: Option<Reaper>,
+}
+
+impl Teardown {
+    /// Frees a backlog and returns a record if it is large
+    pub fn free(
+        &self,
+        schedule: &str,
+        codelet: &str,
+        channel: &str,
+        backlog: Backlog,
+    ) -> Option<BacklogTeardown> {
+        if !self.config.is_large(&backlog) {
+            return None;
+        }
+
+        let deferred = self.config.deferred_drop && self.reaper.is_some();
+        log::warn!(
+            "schedule '{schedule}': channel '{codelet}.rx.{channel}' holds {} messages (~{} bytes) \
+             at teardown{}",
+            backlog.len(),
+            backlog.bytes(),
+            if deferred {
+                ", freeing them on the reaper thread"
+            } else {
+                ""
+            }
+        );
+
+        let record = BacklogTeardown {
+            codelet: codelet.to_string(),
+            channel: channel.to_string(),
+            len: backlog.len(),
+            bytes: backlog.bytes(),
+            deferred,
+        };
+        match self.reaper.as_ref() {
+            Some(reaper) if deferred => reaper.reap(backlog),
+            _ => drop(backlog),
+        }
+        Some(record)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    pub fn lower_priority() {
+        // SAFETY: gettid and setpriority have no preconditions
+        unsafe {
+            let tid = libc::syscall(libc::SYS_gettid);
+            if let Ok(tid) = libc::id_t::try_from(tid) {
+                libc::setpriority(libc::PRIO_PROCESS, tid, 19);
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    pub fn lower_priority() {}
+}
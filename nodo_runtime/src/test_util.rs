@@ -0,0 +1,88 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::{statistics_pretty_print, InspectorReport, Runtime};
+use core::time::Duration;
+use eyre::{bail, Result};
+use std::time::Instant;
+
+/// How often the predicate of [Runtime::spin_until] is evaluated
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+impl Runtime {
+    /// Spins until `predicate` returns true for the current report and then stops all workers.
+    ///
+    /// Fails if the predicate is not satisfied within `timeout`, if the workers finished before
+    /// it was satisfied, or if a worker panicked.
+    pub fn spin_until(
+        &mut self,
+        mut predicate: impl FnMut(&InspectorReport) -> bool,
+        timeout: Duration,
+    ) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        let mut is_satisfied = false;
+
+        self.spin_impl(POLL_INTERVAL, |rt| {
+            is_satisfied = predicate(&rt.report());
+            is_satisfied || Instant::now() >= deadline
+        })?;
+
+        let report = self.report();
+        if !is_satisfied {
+            // workers may have finished before the predicate was evaluated the last time
+            is_satisfied = predicate(&report);
+        }
+        statistics_pretty_print(report);
+
+        if is_satisfied {
+            Ok(())
+        } else if Instant::now() >= deadline {
+            bail!("spin_until: predicate not satisfied within timeout of {timeout:?}")
+        } else {
+            bail!("spin_until: workers finished before predicate was satisfied")
+        }
+    }
+
+    /// Spins until `callback` returns true and then stops all workers. The callback is called
+    /// with the running runtime, e.g. to attach snoops.
+    ///
+    /// Fails if the callback does not return true within `timeout`, or if a worker panicked.
+    pub fn spin_with(
+        &mut self,
+        mut callback: impl FnMut(&Runtime) -> bool,
+        timeout: Duration,
+    ) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        let mut is_done = false;
+        self.spin_impl(POLL_INTERVAL, |rt| {
+            is_done = callback(rt);
+            is_done || Instant::now() >= deadline
+        })?;
+        statistics_pretty_print(self.report());
+
+        if is_done {
+            Ok(())
+        } else {
+            bail!("spin_with: stopped before the callback was done, timeout {timeout:?}")
+        }
+    }
+
+    /// Spins for the given duration and then stops all workers. Fails if a worker panicked.
+    pub fn spin_for(&mut self, duration: Duration) -> Result<()> {
+        let deadline = Instant::now() + duration;
+        self.spin_impl(POLL_INTERVAL, |_| Instant::now() >= deadline)?;
+        statistics_pretty_print(self.report());
+        Ok(())
+    }
+}
+
+/// Stops and joins all workers if the runtime is dropped while the thread is panicking, e.g. due
+/// to a failed assert in a test. Otherwise worker threads would keep running and block the test
+/// harness.
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        if std::thread::panicking() && !self.is_finished() {
+            log::warn!("Runtime dropped during panic: stopping workers..");
+            self.stop_and_join().ok();
+        }
+    }
+}
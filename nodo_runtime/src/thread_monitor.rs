@@ -0,0 +1,171 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use serde::{Deserialize, Serialize};
+
+/// Number of spins between samples of the CPU on which a worker is running
+pub const CPU_SAMPLE_INTERVAL: usize = 64;
+
+/// OS thread and CPU of the worker thread which executes a schedule
+///
+/// Only available on Linux. On other platforms all fields stay empty.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkerThreadReport {
+    /// Thread id as returned by `gettid`
+    pub os_thread_id: Option<u32>,
+
+    /// CPU on which the worker started
+    pub initial_cpu: Option<usize>,
+
+    /// CPU on which the worker was running at the last sample
+    pub last_cpu: Option<usize>,
+
+    /// Number of samples at which the worker ran on a different CPU than at the previous sample.
+    /// Migrations between two samples are not counted.
+    pub migration_count: u64,
+
+    /// Number of CPU samples taken
+    pub sample_count: u64,
+
+    /// CPU to which the thread is pinned if its affinity mask contains a single CPU
+    pub pinned_cpu: Option<usize>,
+}
+
+/// Samples the CPU of the current thread every few spins and counts migrations
+#[derive(Debug)]
+pub(crate) struct ThreadMonitor {
+    interval: usize,
+    spin_count: usize,
+    report: WorkerThreadReport,
+}
+
+impl ThreadMonitor {
+    /// Creates a monitor for the calling thread
+    pub fn new(interval: usize) -> Self {
+        let cpu = sys::current_cpu();
+        Self {
+            interval: interval.max(1),
+            spin_count: 0,
+            report: WorkerThreadReport {
+                os_thread_id: sys::thread_id(),
+                initial_cpu: cpu,
+                last_cpu: cpu,
+                migration_count: 0,
+                sample_count: cpu.is_some() as u64,
+                pinned_cpu: sys::pinned_cpu(),
+            },
+        }
+    }
+
+    /// Samples the current CPU every `interval` calls
+    pub fn on_spin(&mut self, worker: &str) {
+        self.spin_count += 1;
+        if self.spin_count % self.interval == 0 {
+            self.sample(worker);
+        }
+    }
+
+    pub fn sample(&mut self, worker: &str) {
+        let Some(cpu) = sys::current_cpu() else {
+            return;
+        };
+        self.report.sample_count += 1;
+
+        if self.report.last_cpu.is_some_and(|last| last != cpu) {
+            self.report.migration_count += 1;
+        }
+        self.report.last_cpu = Some(cpu);
+
+        if let Some(pinned) = self.report.pinned_cpu {
+            if pinned != cpu {
+                log::warn!("worker '{worker}' is pinned to CPU {pinned} but runs on CPU {cpu}");
+            }
+        }
+    }
+
+    pub fn report(&self) -> &WorkerThreadReport {
+        &self.report
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    pub fn thread_id() -> Option<u32> {
+        // SAFETY: gettid has no preconditions
+        let tid = unsafe { libc::syscall(libc::SYS_gettid) };
+        u32::try_from(tid).ok()
+    }
+
+    pub fn current_cpu() -> Option<usize> {
+        // SAFETY: sched_getcpu has no preconditions
+        let cpu = unsafe { libc::sched_getcpu() };
+        usize::try_from(cpu).ok()
+    }
+
+    pub fn pinned_cpu() -> Option<usize> {
+        // SAFETY: the set is zero-initialized and its size is passed to sched_getaffinity
+        unsafe {
+            let mut set: libc::cpu_set_t = core::mem::zeroed();
+            if libc::sched_getaffinity(0, core::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+                return None;
+            }
+            if libc::CPU_COUNT(&set) != 1 {
+                return None;
+            }
+            (0..libc::CPU_SETSIZE as usize).find(|&cpu| libc::CPU_ISSET(cpu, &set))
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    pub fn thread_id() -> Option<u32> {
+        None
+    }
+
+    pub fn current_cpu() -> Option<usize> {
+        None
+    }
+
+    pub fn pinned_cpu() -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::thread_monitor::ThreadMonitor;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_thread_monitor() {
+        let mut monitor = ThreadMonitor::new(4);
+        let report = monitor.report().clone();
+        assert!(report.os_thread_id.is_some());
+        assert!(report.initial_cpu.is_some());
+        assert_eq!(report.last_cpu, report.initial_cpu);
+        assert_eq!(report.sample_count, 1);
+
+        // every thread has its own id
+        let other = std::thread::spawn(|| ThreadMonitor::new(1).report().os_thread_id)
+            .join()
+            .unwrap();
+        assert_ne!(other, report.os_thread_id);
+
+        let mut migrations = 0;
+        for _ in 0..40 {
+            monitor.on_spin("test");
+            assert!(monitor.report().migration_count >= migrations);
+            migrations = monitor.report().migration_count;
+        }
+        assert_eq!(monitor.report().sample_count, 11);
+        assert!(monitor.report().migration_count < 11);
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    #[test]
+    fn test_thread_monitor() {
+        let mut monitor = ThreadMonitor::new(1);
+        monitor.on_spin("test");
+        assert_eq!(monitor.report(), &Default::default());
+    }
+}
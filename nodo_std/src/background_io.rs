@@ -0,0 +1,397 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use log::{error, warn};
+use nodo_core::{eyre, EyreResult};
+use std::{
+    collections::VecDeque,
+    sync::{mpsc, Arc, Condvar, Mutex},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+/// What happens when an item is handed to the I/O thread while the queue is full
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum QueueFullPolicy {
+    /// The oldest item in the queue is dropped
+    #[default]
+    DropOldest,
+
+    /// The caller blocks until the I/O thread made room in the queue
+    Block,
+}
+
+/// Configuration for performing I/O on a dedicated thread, see [BackgroundWriter]
+#[derive(Debug, Clone)]
+pub struct BackgroundIoConfig {
+    /// Maximum number of items waiting to be written
+    pub queue_size: usize,
+
+    pub full_policy: QueueFullPolicy,
+
+    /// Maximum time to wait for the I/O thread to write remaining items when finishing
+    pub stop_timeout: Duration,
+}
+
+impl Default for BackgroundIoConfig {
+    fn default() -> Self {
+        Self {
+            queue_size: 1024,
+            full_policy: QueueFullPolicy::DropOldest,
+            stop_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Statistics of the queue between the producer and the I/O thread
+///
+/// Every pushed item is counted exactly once: either it was handed to the I/O thread
+/// (`enqueued`), dropped because the queue was full (`dropped`) or it is still waiting in the
+/// queue.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BackgroundStatistics {
+    /// Number of items handed to the I/O thread
+    pub enqueued: u64,
+
+    /// Number of items dropped because the queue was full
+    pub dropped: u64,
+
+    /// Number of items written by the I/O thread
+    pub written: u64,
+
+    /// Maximum number of items which were waiting in the queue at the same time
+    pub high_water_mark: usize,
+}
+
+struct HandoffState<T> {
+    items: VecDeque<T>,
+    is_closed: bool,
+    statistics: BackgroundStatistics,
+}
+
+/// Bounded queue to hand items from a single producer to a single consumer thread
+pub struct HandoffQueue<T> {
+    state: Mutex<HandoffState<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    full_policy: QueueFullPolicy,
+}
+
+impl<T> HandoffQueue<T> {
+    pub fn new(capacity: usize, full_policy: QueueFullPolicy) -> Self {
+        assert!(capacity > 0, "queue_size must be at least 1");
+        Self {
+            state: Mutex::new(HandoffState {
+                items: VecDeque::with_capacity(capacity),
+                is_closed: false,
+                statistics: BackgroundStatistics::default(),
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity,
+            full_policy,
+        }
+    }
+
+    /// Adds an item to the queue. Returns false if the queue was closed.
+    pub fn push(&self, item: T) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        if state.items.len() >= self.capacity {
+            match self.full_policy {
+                QueueFullPolicy::DropOldest => {
+                    state.items.pop_front();
+                    state.statistics.dropped += 1;
+                }
+                QueueFullPolicy::Block => {
+                    state = self
+                        .not_full
+                        .wait_while(state, |s| s.items.len() >= self.capacity && !s.is_closed)
+                        .unwrap();
+                }
+            }
+        }
+
+        if state.is_closed {
+            return false;
+        }
+
+        state.items.push_back(item);
+        state.statistics.high_water_mark = state.statistics.high_water_mark.max(state.items.len());
+
+        self.not_empty.notify_one();
+        true
+    }
+
+    /// Waits until items are available and moves all of them into `out`. Returns false once the
+    /// queue is closed and empty.
+    pub fn pop_all(&self, out: &mut Vec<T>) -> bool {
+        let mut state = self
+            .not_empty
+            .wait_while(self.state.lock().unwrap(), |s| {
+                s.items.is_empty() && !s.is_closed
+            })
+            .unwrap();
+
+        if state.items.is_empty() {
+            return false;
+        }
+
+        state.statistics.enqueued += state.items.len() as u64;
+        out.extend(state.items.drain(..));
+        self.not_full.notify_one();
+        true
+    }
+
+    /// Closes the queue. Remaining items can still be popped.
+    pub fn close(&self) {
+        self.state.lock().unwrap().is_closed = true;
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+    }
+
+    /// Number of items waiting in the queue
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn add_written(&self, count: usize) {
+        self.state.lock().unwrap().statistics.written += count as u64;
+    }
+
+    pub fn statistics(&self) -> BackgroundStatistics {
+        self.state.lock().unwrap().statistics
+    }
+}
+
+/// Output which is owned by the I/O thread of a [BackgroundWriter]
+pub trait BackgroundSink<T>: Send + 'static {
+    /// Writes a single item. Errors are logged and the item is skipped.
+    fn write(&mut self, item: T) -> EyreResult<()>;
+
+    /// Called after all items which were available at once were written
+    fn flush(&mut self) -> EyreResult<()> {
+        Ok(())
+    }
+
+    /// Called once after the last item was written
+    fn finish(&mut self) -> EyreResult<()> {
+        Ok(())
+    }
+}
+
+/// Owns a sink on a dedicated I/O thread which is fed through a bounded [HandoffQueue]
+///
+/// Pushing only hands the item over to the I/O thread, thus slow I/O does not stall the caller
+/// unless the queue is full and configured to block.
+///
+/// The `background_io` mode of `McapWriter` is not built on top of this yet as nodo_record is
+/// currently not part of the workspace.
+pub struct BackgroundWriter<T> {
+    queue: Arc<HandoffQueue<T>>,
+    thread: JoinHandle<()>,
+    done: mpsc::Receiver<EyreResult<()>>,
+}
+
+impl<T: Send + 'static> BackgroundWriter<T> {
+    pub fn spawn<S: BackgroundSink<T>>(
+        name: &str,
+        mut sink: S,
+        config: &BackgroundIoConfig,
+    ) -> EyreResult<Self> {
+        let queue = Arc::new(HandoffQueue::new(config.queue_size, config.full_policy));
+        let (done_tx, done) = mpsc::channel();
+
+        let thread = std::thread::Builder::new().name(name.into()).spawn({
+            let queue = queue.clone();
+            move || {
+                let result = run(&mut sink, &queue);
+                queue.close();
+                done_tx.send(result).ok();
+            }
+        })?;
+
+        Ok(Self {
+            queue,
+            thread,
+            done,
+        })
+    }
+
+    /// Hands an item to the I/O thread
+    pub fn push(&self, item: T) -> EyreResult<()> {
+        if self.queue.push(item) {
+            Ok(())
+        } else {
+            Err(eyre!("background I/O thread terminated"))
+        }
+    }
+
+    pub fn statistics(&self) -> BackgroundStatistics {
+        self.queue.statistics()
+    }
+
+    /// Writes all remaining items, finishes the sink and joins the I/O thread
+    pub fn finish(self, timeout: Duration) -> EyreResult<BackgroundStatistics> {
+        self.queue.close();
+
+        match self.done.recv_timeout(timeout) {
+            Ok(result) => {
+                self.thread.join().ok();
+                result?;
+            }
+            Err(_) => {
+                return Err(eyre!(
+                    "background I/O thread did not finish within {timeout:?}: {} items were \
+                     not written",
+                    self.queue.len()
+                ));
+            }
+        }
+
+        let statistics = self.queue.statistics();
+        if statistics.dropped > 0 {
+            warn!(
+                "background I/O dropped {} of {} items as the queue was full (high-water mark: {})",
+                statistics.dropped,
+                statistics.enqueued + statistics.dropped,
+                statistics.high_water_mark
+            );
+        }
+
+        Ok(statistics)
+    }
+}
+
+fn run<T, S: BackgroundSink<T>>(sink: &mut S, queue: &HandoffQueue<T>) -> EyreResult<()> {
+    let mut batch = Vec::new();
+
+    while queue.pop_all(&mut batch) {
+        let count = batch.len();
+        for item in batch.drain(..) {
+            if let Err(err) = sink.write(item) {
+                error!("error writing item on background I/O thread: {err:?}");
+            }
+        }
+        queue.add_written(count);
+        sink.flush()?;
+    }
+
+    sink.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        BackgroundIoConfig, BackgroundSink, BackgroundWriter, HandoffQueue, QueueFullPolicy,
+    };
+    use nodo_core::EyreResult;
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn test_drop_oldest() {
+        let queue = HandoffQueue::new(3, QueueFullPolicy::DropOldest);
+        for i in 0..5 {
+            assert!(queue.push(i));
+        }
+
+        let mut out = Vec::new();
+        assert!(queue.pop_all(&mut out));
+        assert_eq!(out, [2, 3, 4]);
+
+        // dropped items are not counted as enqueued
+        let stats = queue.statistics();
+        assert_eq!(stats.enqueued, 3);
+        assert_eq!(stats.dropped, 2);
+        assert_eq!(stats.high_water_mark, 3);
+
+        queue.close();
+        assert!(!queue.pop_all(&mut out));
+        assert!(!queue.push(5));
+    }
+
+    #[test]
+    fn test_block() {
+        let queue = Arc::new(HandoffQueue::new(2, QueueFullPolicy::Block));
+
+        let consumer = std::thread::spawn({
+            let queue = queue.clone();
+            move || {
+                let mut out = Vec::new();
+                while queue.pop_all(&mut out) {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+                out
+            }
+        });
+
+        for i in 0..20 {
+            assert!(queue.push(i));
+        }
+        queue.close();
+
+        assert_eq!(consumer.join().unwrap(), (0..20).collect::<Vec<_>>());
+        let stats = queue.statistics();
+        assert_eq!(stats.enqueued, 20);
+        assert_eq!(stats.dropped, 0);
+        assert!(stats.high_water_mark <= 2);
+    }
+
+    /// Simulates slow I/O
+    struct SlowSink {
+        written: Arc<Mutex<Vec<u32>>>,
+        is_finished: Arc<Mutex<bool>>,
+    }
+
+    impl BackgroundSink<u32> for SlowSink {
+        fn write(&mut self, item: u32) -> EyreResult<()> {
+            std::thread::sleep(Duration::from_millis(2));
+            self.written.lock().unwrap().push(item);
+            Ok(())
+        }
+
+        fn finish(&mut self) -> EyreResult<()> {
+            *self.is_finished.lock().unwrap() = true;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_slow_sink() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let is_finished = Arc::new(Mutex::new(false));
+        let writer = BackgroundWriter::spawn(
+            "test-io",
+            SlowSink {
+                written: written.clone(),
+                is_finished: is_finished.clone(),
+            },
+            &BackgroundIoConfig {
+                queue_size: 100,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        // pushing does not wait for the slow sink
+        let t0 = Instant::now();
+        for i in 0..50 {
+            writer.push(i).unwrap();
+        }
+        assert!(t0.elapsed() < Duration::from_millis(50));
+
+        let stats = writer.finish(Duration::from_secs(5)).unwrap();
+        assert_eq!(*written.lock().unwrap(), (0..50).collect::<Vec<_>>());
+        assert!(*is_finished.lock().unwrap());
+        assert_eq!(stats.enqueued, 50);
+        assert_eq!(stats.written, 50);
+        assert_eq!(stats.dropped, 0);
+    }
+}
@@ -0,0 +1,357 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use nodo::prelude::*;
+use nodo_core::{eyre, EyreResult};
+
+/// Number of low bits of the sequence number of messages published by [Unbatch] which hold the
+/// index of the item in its batch
+pub const UNBATCH_INDEX_BITS: u32 = 20;
+
+/// Maximum number of items in a batch which can be split by [Unbatch]
+pub const UNBATCH_MAX_BATCH_LEN: usize = 1 << UNBATCH_INDEX_BITS;
+
+/// Maximum sequence number of a batch which can be split by [Unbatch]
+pub const UNBATCH_MAX_BATCH_SEQ: u64 = u64::MAX >> UNBATCH_INDEX_BITS;
+
+/// Sequence number of the `index`-th item of the batch with sequence number `seq`
+///
+/// Returns None if the batch sequence number exceeds [UNBATCH_MAX_BATCH_SEQ] or the index does not
+/// fit into [UNBATCH_INDEX_BITS] bits.
+pub fn unbatch_seq(seq: u64, index: usize) -> Option<u64> {
+    if index >= UNBATCH_MAX_BATCH_LEN {
+        return None;
+    }
+    seq.checked_mul(UNBATCH_MAX_BATCH_LEN as u64)
+        .map(|high| high | index as u64)
+}
+
+/// Splits a sequence number created by [Unbatch] into batch sequence number and item index
+pub fn split_unbatch_seq(seq: u64) -> (u64, usize) {
+    (
+        seq >> UNBATCH_INDEX_BITS,
+        (seq & (UNBATCH_MAX_BATCH_LEN as u64 - 1)) as usize,
+    )
+}
+
+/// Splits batches into individual messages
+///
+/// Every item is published with the stamp of its batch. The sequence number of an item combines
+/// the sequence number of the batch and the index of the item in the batch, see [unbatch_seq].
+/// Thus batches can have at most [UNBATCH_MAX_BATCH_LEN] items and a sequence number of at most
+/// [UNBATCH_MAX_BATCH_SEQ]. Empty batches produce nothing.
+pub struct Unbatch<T>(core::marker::PhantomData<T>);
+
+impl<T> Default for Unbatch<T> {
+    fn default() -> Self {
+        Self(core::marker::PhantomData)
+    }
+}
+
+impl<T> Unbatch<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn unbatch<T>(batch: Message<Vec<T>>) -> EyreResult<impl Iterator<Item = Message<T>>> {
+    if batch.value.len() > UNBATCH_MAX_BATCH_LEN {
+        return Err(eyre!(
+            "batch with {} items exceeds the maximum of {UNBATCH_MAX_BATCH_LEN}",
+            batch.value.len()
+        ));
+    }
+    if batch.seq > UNBATCH_MAX_BATCH_SEQ {
+        return Err(eyre!(
+            "batch sequence number {} exceeds the maximum of {UNBATCH_MAX_BATCH_SEQ}",
+            batch.seq
+        ));
+    }
+
+    let Message { seq, stamp, value } = batch;
+    Ok(value
+        .into_iter()
+        .enumerate()
+        .map(move |(index, value)| Message {
+            // cannot fail as sequence number and length were checked above
+            seq: unbatch_seq(seq, index).unwrap(),
+            stamp: stamp.clone(),
+            value,
+        }))
+}
+
+impl<T: Clone + Send + Sync> Codelet for Unbatch<T> {
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = DoubleBufferRx<Message<Vec<T>>>;
+    type Tx = DoubleBufferTx<Message<T>>;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (
+            DoubleBufferRx::new_auto_size(),
+            DoubleBufferTx::new_auto_size(),
+        )
+    }
+
+    fn step(&mut self, _: &Context<Self>, rx: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        let mut count = 0;
+        while let Some(batch) = rx.try_pop() {
+            for msg in unbatch(batch)? {
+                tx.push(msg)?;
+                count += 1;
+            }
+        }
+
+        if count > 0 {
+            SUCCESS
+        } else {
+            SKIPPED
+        }
+    }
+}
+
+/// Configuration for [Batch]
+#[derive(Debug, Clone)]
+pub struct BatchConfig {
+    /// A batch is published as soon as it has this many items
+    pub max_count: usize,
+
+    /// A batch is published once the acquisition time of its oldest item is older than this
+    pub max_age: Option<Duration>,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_count: 16,
+            max_age: None,
+        }
+    }
+}
+
+/// Collects individual messages into batches
+///
+/// A batch is published when it reached the maximum count or when its oldest item is older than
+/// the maximum age. The batch has the acquisition time of its oldest item and its own sequence
+/// number. A partial batch is published when the codelet is stopped.
+pub struct Batch<T> {
+    items: Vec<T>,
+    oldest: Option<Acqtime>,
+    seq: u64,
+}
+
+impl<T> Default for Batch<T> {
+    fn default() -> Self {
+        Self {
+            items: Vec::new(),
+            oldest: None,
+            seq: 0,
+        }
+    }
+}
+
+impl<T> Batch<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a message and returns the batch if it reached the maximum count
+    fn push(&mut self, msg: Message<T>, max_count: usize) -> Option<(Acqtime, Vec<T>)> {
+        self.oldest.get_or_insert(msg.stamp.acqtime);
+        self.items.push(msg.value);
+        (self.items.len() >= max_count)
+            .then(|| self.take())
+            .flatten()
+    }
+
+    /// Returns the batch if its oldest item is older than the maximum age
+    fn take_expired(&mut self, now: Acqtime, max_age: Duration) -> Option<(Acqtime, Vec<T>)> {
+        let oldest = self.oldest?;
+        (*now >= *oldest + max_age).then(|| self.take()).flatten()
+    }
+
+    /// Returns the current batch unless it is empty
+    fn take(&mut self) -> Option<(Acqtime, Vec<T>)> {
+        let oldest = self.oldest.take()?;
+        Some((oldest, core::mem::take(&mut self.items)))
+    }
+
+    fn publish(
+        &mut self,
+        cx: &Context<Self>,
+        tx: &mut DoubleBufferTx<Message<Vec<T>>>,
+        (acqtime, value): (Acqtime, Vec<T>),
+    ) -> EyreResult<()>
+    where
+        T: Clone + Send + Sync,
+    {
+        tx.push(Message {
+            seq: self.seq,
+            stamp: Stamp {
+                acqtime,
+                pubtime: cx.clocks.app_mono.now(),
+            },
+            value,
+        })?;
+        self.seq += 1;
+        Ok(())
+    }
+}
+
+impl<T: Clone + Send + Sync> Codelet for Batch<T> {
+    type Status = DefaultStatus;
+    type Config = BatchConfig;
+    type Rx = DoubleBufferRx<Message<T>>;
+    type Tx = DoubleBufferTx<Message<Vec<T>>>;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (
+            DoubleBufferRx::new_auto_size(),
+            DoubleBufferTx::new_auto_size(),
+        )
+    }
+
+    fn stop(&mut self, cx: &Context<Self>, _: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        if let Some(batch) = self.take() {
+            self.publish(cx, tx, batch)?;
+        }
+        SUCCESS
+    }
+
+    fn step(&mut self, cx: &Context<Self>, rx: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        let mut count = 0;
+
+        while let Some(msg) = rx.try_pop() {
+            if let Some(batch) = self.push(msg, cx.config.max_count) {
+                self.publish(cx, tx, batch)?;
+                count += 1;
+            }
+        }
+
+        if let Some(max_age) = cx.config.max_age {
+            if let Some(batch) = self.take_expired(cx.clocks.sys_mono.now(), max_age) {
+                self.publish(cx, tx, batch)?;
+                count += 1;
+            }
+        }
+
+        if count > 0 {
+            SUCCESS
+        } else {
+            SKIPPED
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        batch::unbatch, split_unbatch_seq, unbatch_seq, Batch, UNBATCH_MAX_BATCH_LEN,
+        UNBATCH_MAX_BATCH_SEQ,
+    };
+    use core::time::Duration;
+    use nodo::prelude::*;
+
+    fn message<T>(seq: u64, acqtime_ms: u64, value: T) -> Message<T> {
+        Message {
+            seq,
+            stamp: Stamp {
+                acqtime: Duration::from_millis(acqtime_ms).into(),
+                pubtime: Duration::from_millis(acqtime_ms).into(),
+            },
+            value,
+        }
+    }
+
+    #[test]
+    fn test_batch_count_trigger() {
+        let mut batch = Batch::new();
+        assert_eq!(batch.push(message(0, 10, 'a'), 3), None);
+        assert_eq!(batch.push(message(1, 20, 'b'), 3), None);
+        let (acqtime, items) = batch.push(message(2, 30, 'c'), 3).unwrap();
+        assert_eq!(*acqtime, Duration::from_millis(10));
+        assert_eq!(items, ['a', 'b', 'c']);
+        assert_eq!(batch.take(), None);
+    }
+
+    #[test]
+    fn test_batch_age_trigger() {
+        let ms = |t| Duration::from_millis(t).into();
+        let max_age = Duration::from_millis(50);
+
+        let mut batch = Batch::new();
+        assert_eq!(batch.take_expired(ms(0), max_age), None);
+
+        batch.push(message(0, 100, 1), 10);
+        batch.push(message(1, 120, 2), 10);
+        assert_eq!(batch.take_expired(ms(149), max_age), None);
+        let (acqtime, items) = batch.take_expired(ms(150), max_age).unwrap();
+        assert_eq!(acqtime, ms(100));
+        assert_eq!(items, [1, 2]);
+
+        // the age starts with the oldest item of the next batch
+        batch.push(message(2, 160, 3), 10);
+        assert_eq!(batch.take_expired(ms(200), max_age), None);
+        assert!(batch.take_expired(ms(210), max_age).is_some());
+    }
+
+    #[test]
+    fn test_batch_stop_flush() {
+        let mut batch = Batch::new();
+        assert_eq!(batch.take(), None);
+        batch.push(message(0, 0, 'x'), 10);
+        assert_eq!(batch.take().unwrap().1, ['x']);
+        assert_eq!(batch.take(), None);
+    }
+
+    #[test]
+    fn test_unbatch() {
+        let items = unbatch(message(7, 5, vec!['a', 'b', 'c']))
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            items
+                .iter()
+                .map(|m| split_unbatch_seq(m.seq))
+                .collect::<Vec<_>>(),
+            [(7, 0), (7, 1), (7, 2)]
+        );
+        assert!(items
+            .iter()
+            .all(|m| *m.stamp.acqtime == Duration::from_millis(5)));
+
+        assert_eq!(
+            unbatch(message(8, 5, Vec::<char>::new())).unwrap().count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_unbatch_seq_limits() {
+        let max_index = UNBATCH_MAX_BATCH_LEN - 1;
+        let seq = unbatch_seq(UNBATCH_MAX_BATCH_SEQ, max_index).unwrap();
+        assert_eq!(seq, u64::MAX);
+        assert_eq!(split_unbatch_seq(seq), (UNBATCH_MAX_BATCH_SEQ, max_index));
+
+        assert_eq!(unbatch_seq(UNBATCH_MAX_BATCH_SEQ + 1, 0), None);
+        assert_eq!(unbatch_seq(0, UNBATCH_MAX_BATCH_LEN), None);
+
+        assert!(unbatch(message(UNBATCH_MAX_BATCH_SEQ + 1, 0, vec![1])).is_err());
+    }
+
+    #[test]
+    fn test_unbatch_batch_roundtrip() {
+        let batches = [vec![1, 2, 3], vec![], vec![4], vec![5, 6, 7, 8, 9]];
+
+        let mut batch = Batch::new();
+        let mut output = Vec::new();
+        for (seq, items) in batches.iter().enumerate() {
+            for msg in unbatch(message(seq as u64, 0, items.clone())).unwrap() {
+                output.extend(batch.push(msg, 4).map(|(_, v)| v));
+            }
+        }
+        output.extend(batch.take().map(|(_, v)| v));
+
+        assert_eq!(output, [vec![1, 2, 3, 4], vec![5, 6, 7, 8], vec![9]]);
+    }
+}
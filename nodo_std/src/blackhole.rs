@@ -0,0 +1,323 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use nodo::prelude::*;
+use nodo_core::Result;
+
+/// Size of a message payload in bytes
+pub trait ByteSize {
+    fn byte_size(&self) -> usize;
+}
+
+impl ByteSize for Vec<u8> {
+    fn byte_size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl ByteSize for String {
+    fn byte_size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T: ByteSize> ByteSize for Message<T> {
+    fn byte_size(&self) -> usize {
+        self.value.byte_size()
+    }
+}
+
+/// Configuration for [Blackhole]
+#[derive(Debug, Clone)]
+pub struct BlackholeConfig {
+    /// If set the totals are published on the stats channel with this interval
+    pub stats_interval: Option<Duration>,
+
+    /// Logs a warning when more messages per second than this are discarded
+    pub warn_above_rate: Option<f64>,
+
+    /// Time window over which the discard rate is computed
+    pub rate_window: Duration,
+}
+
+impl Default for BlackholeConfig {
+    fn default() -> Self {
+        Self {
+            stats_interval: None,
+            warn_above_rate: None,
+            rate_window: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Totals of messages discarded by a [Blackhole]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BlackholeStats {
+    pub messages: u64,
+
+    /// Total payload size. Only counted if the blackhole was created with `with_byte_size`.
+    pub bytes: u64,
+
+    /// Discarded messages per second over the last completed rate window
+    pub rate: f64,
+}
+
+/// Status of [Blackhole] which shows the discarded totals in its label
+pub struct BlackholeStatus {
+    label: String,
+    status: DefaultStatus,
+    severity: Severity,
+}
+
+impl CodeletStatus for BlackholeStatus {
+    fn default_implementation_status() -> Self {
+        Self {
+            label: "idle".into(),
+            status: DefaultStatus::Skipped,
+            severity: Severity::Info,
+        }
+    }
+
+    fn as_default_status(&self) -> DefaultStatus {
+        self.status
+    }
+
+    fn label(&self) -> &str {
+        &self.label
+    }
+
+    fn severity(&self) -> Severity {
+        self.severity
+    }
+}
+
+/// A codelet which discards all messages it receives and accounts for them
+///
+/// Connects like a [Sink](crate::Sink). Use it instead of [NullRx](crate::NullRx) to see how much
+/// data is thrown away, for example to catch an expensive stream which is accidentally wired to
+/// nowhere useful.
+pub struct Blackhole<T> {
+    byte_size: Option<fn(&T) -> usize>,
+    stats: BlackholeStats,
+    window_start: Option<Duration>,
+    window_count: u64,
+    is_above_rate: bool,
+    warning_count: usize,
+    last_publish: Option<Duration>,
+}
+
+impl<T> Default for Blackhole<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Blackhole<T> {
+    /// Creates a blackhole which counts discarded messages
+    pub fn new() -> Self {
+        Self {
+            byte_size: None,
+            stats: BlackholeStats::default(),
+            window_start: None,
+            window_count: 0,
+            is_above_rate: false,
+            warning_count: 0,
+            last_publish: None,
+        }
+    }
+
+    /// Creates a blackhole which counts discarded messages and bytes
+    pub fn with_byte_size() -> Self
+    where
+        T: ByteSize,
+    {
+        Self {
+            byte_size: Some(T::byte_size),
+            ..Self::new()
+        }
+    }
+
+    pub fn stats(&self) -> &BlackholeStats {
+        &self.stats
+    }
+
+    /// Number of times the discard rate rose above `warn_above_rate`
+    pub fn warning_count(&self) -> usize {
+        self.warning_count
+    }
+
+    fn discard(&mut self, value: T) {
+        self.stats.messages += 1;
+        self.window_count += 1;
+        if let Some(byte_size) = self.byte_size {
+            self.stats.bytes += byte_size(&value) as u64;
+        }
+    }
+
+    /// Updates the discard rate once the rate window passed. Warns when the rate rises above the
+    /// threshold and returns true in that case.
+    fn update_rate(&mut self, now: Duration, config: &BlackholeConfig) -> bool {
+        let start = *self.window_start.get_or_insert(now);
+        let elapsed = now.saturating_sub(start);
+        if elapsed < config.rate_window || elapsed.is_zero() {
+            return false;
+        }
+
+        self.stats.rate = self.window_count as f64 / elapsed.as_secs_f64();
+        self.window_start = Some(now);
+        self.window_count = 0;
+
+        let was_above_rate = self.is_above_rate;
+        self.is_above_rate = config
+            .warn_above_rate
+            .is_some_and(|threshold| self.stats.rate > threshold);
+
+        if self.is_above_rate && !was_above_rate {
+            log::warn!(
+                "blackhole discards {:.1} messages/s which is above the threshold of {:.1}",
+                self.stats.rate,
+                config.warn_above_rate.unwrap_or_default()
+            );
+            self.warning_count += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn status(&self, received: bool) -> BlackholeStatus {
+        let label = if self.byte_size.is_some() {
+            format!(
+                "discarded {} msgs ({} bytes)",
+                self.stats.messages, self.stats.bytes
+            )
+        } else {
+            format!("discarded {} msgs", self.stats.messages)
+        };
+
+        BlackholeStatus {
+            label,
+            status: if received {
+                DefaultStatus::Running
+            } else {
+                DefaultStatus::Skipped
+            },
+            severity: if self.is_above_rate {
+                Severity::Warn
+            } else {
+                Severity::Info
+            },
+        }
+    }
+}
+
+impl<T: Send + Sync> Codelet for Blackhole<T> {
+    type Status = BlackholeStatus;
+    type Config = BlackholeConfig;
+    type Rx = DoubleBufferRx<T>;
+    type Tx = Option<DoubleBufferTx<BlackholeStats>>;
+
+    fn build_bundles(config: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (
+            DoubleBufferRx::new_auto_size(),
+            config.stats_interval.map(|_| DoubleBufferTx::new(1)),
+        )
+    }
+
+    fn step(
+        &mut self,
+        cx: &Context<Self>,
+        rx: &mut Self::Rx,
+        tx: &mut Self::Tx,
+    ) -> Result<BlackholeStatus> {
+        let received = !rx.is_empty();
+        while let Some(value) = rx.try_pop() {
+            self.discard(value);
+        }
+
+        let now = *cx.clocks.sys_mono.now();
+        self.update_rate(now, cx.config);
+
+        if let (Some(tx), Some(interval)) = (tx.as_mut(), cx.config.stats_interval) {
+            if self.last_publish.is_none_or(|last| now >= last + interval) {
+                self.last_publish = Some(now);
+                tx.push(self.stats.clone())?;
+            }
+        }
+
+        Ok(self.status(received))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Blackhole, BlackholeConfig};
+    use core::time::Duration;
+    use nodo::prelude::*;
+
+    fn ms(x: u64) -> Duration {
+        Duration::from_millis(x)
+    }
+
+    #[test]
+    fn test_counts() {
+        let mut bh = Blackhole::<Vec<u8>>::with_byte_size();
+        bh.discard(vec![0; 10]);
+        bh.discard(vec![0; 32]);
+        assert_eq!(bh.stats().messages, 2);
+        assert_eq!(bh.stats().bytes, 42);
+        assert_eq!(bh.status(true).label(), "discarded 2 msgs (42 bytes)");
+
+        let mut bh = Blackhole::<u32>::new();
+        bh.discard(7);
+        assert_eq!(bh.stats().bytes, 0);
+        assert_eq!(bh.status(false).label(), "discarded 1 msgs");
+        assert_eq!(bh.status(false).as_default_status(), DefaultStatus::Skipped);
+    }
+
+    #[test]
+    fn test_warn_above_rate() {
+        let config = BlackholeConfig {
+            warn_above_rate: Some(100.0),
+            rate_window: ms(1000),
+            ..Default::default()
+        };
+        let mut bh = Blackhole::<u32>::new();
+
+        assert!(!bh.update_rate(ms(0), &config));
+
+        // 50 messages per second
+        for t in 1..=10 {
+            for _ in 0..5 {
+                bh.discard(0);
+            }
+            assert!(!bh.update_rate(ms(t * 100), &config));
+        }
+        assert_eq!(bh.stats().rate, 50.0);
+        assert_eq!(bh.status(true).severity(), Severity::Info);
+
+        // 200 messages per second triggers a single warning
+        for t in 11..=30 {
+            for _ in 0..20 {
+                bh.discard(0);
+            }
+            let warned = bh.update_rate(ms(t * 100), &config);
+            assert_eq!(warned, t == 20);
+        }
+        assert_eq!(bh.stats().rate, 200.0);
+        assert_eq!(bh.warning_count(), 1);
+        assert_eq!(bh.status(true).severity(), Severity::Warn);
+
+        // falling below the threshold re-arms the warning
+        for _ in 0..10 {
+            bh.discard(0);
+        }
+        assert!(!bh.update_rate(ms(4000), &config));
+        assert_eq!(bh.stats().rate, 10.0);
+        for _ in 0..500 {
+            bh.discard(0);
+        }
+        assert!(bh.update_rate(ms(5000), &config));
+        assert_eq!(bh.warning_count(), 2);
+    }
+}
@@ -0,0 +1,429 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::{
+    any::{type_name, Any, TypeId},
+    fmt,
+};
+use nodo::{
+    channels::{TxConnectError, TxSendError},
+    prelude::*,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// Errors of the in-process bus
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum BusError {
+    #[error(
+        "bus topic '{topic}' carries messages of type '{expected}' but '{actual}' was requested"
+    )]
+    TypeMismatch {
+        topic: String,
+        expected: &'static str,
+        actual: &'static str,
+    },
+}
+
+/// What a subscriber receives which joins after messages were already published
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LateJoin {
+    /// Only messages published after the subscriber was created are received
+    #[default]
+    Miss,
+
+    /// The latest message published before the subscriber was created is received first
+    ReplayLatest,
+}
+
+/// Registry of named topics for lightweight publish/subscribe within a process
+///
+/// Topics are created on first use and are keyed by name and message type. Requesting a topic
+/// with a different message type than it was created with fails. Clones share the same topics.
+#[derive(Clone, Default)]
+pub struct Bus {
+    topics: Arc<Mutex<HashMap<String, BusEntry>>>,
+}
+
+struct BusEntry {
+    type_id: TypeId,
+    type_name: &'static str,
+    topic: Arc<dyn Any + Send + Sync>,
+}
+
+impl Bus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the topic with given name or creates it
+    pub fn topic<T>(&self, name: &str) -> Result<BusTopic<T>, BusError>
+    where
+        T: Send + Sync + Clone + 'static,
+    {
+        let mut topics = self.topics.lock().unwrap();
+        let entry = topics.entry(name.to_string()).or_insert_with(|| BusEntry {
+            type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
+            topic: Arc::new(BusTopicShared::<T> {
+                name: name.to_string(),
+                tx: Mutex::new(DoubleBufferTx::new_auto_size().with_replay(1)),
+            }),
+        });
+
+        if entry.type_id != TypeId::of::<T>() {
+            return Err(BusError::TypeMismatch {
+                topic: name.to_string(),
+                expected: entry.type_name,
+                actual: type_name::<T>(),
+            });
+        }
+
+        // cannot fail as the type was checked above
+        let shared = entry.topic.clone().downcast::<BusTopicShared<T>>().unwrap();
+        Ok(BusTopic { shared })
+    }
+
+    /// Gets a subscriber configuration for the topic with given name
+    pub fn subscribe<T>(&self, name: &str, late_join: LateJoin) -> Result<BusSubConfig<T>, BusError>
+    where
+        T: Send + Sync + Clone + 'static,
+    {
+        Ok(BusSubConfig {
+            topic: self.topic(name)?,
+            late_join,
+        })
+    }
+
+    /// Names of all topics sorted alphabetically
+    pub fn topic_names(&self) -> Vec<String> {
+        let mut names = self
+            .topics
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+}
+
+impl fmt::Debug for Bus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let topics = self.topics.lock().unwrap();
+        f.debug_map()
+            .entries(topics.iter().map(|(name, entry)| (name, entry.type_name)))
+            .finish()
+    }
+}
+
+/// Handle to a topic of a [Bus] used to create publishers and subscribers
+pub struct BusTopic<T> {
+    shared: Arc<BusTopicShared<T>>,
+}
+
+struct BusTopicShared<T> {
+    name: String,
+
+    /// All subscribers are connected to this transmitter. It keeps the latest message for
+    /// subscribers which join late.
+    tx: Mutex<DoubleBufferTx<Message<T>>>,
+}
+
+impl<T> Clone for BusTopic<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for BusTopic<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BusTopic")
+            .field("name", &self.shared.name)
+            .finish()
+    }
+}
+
+impl<T: Send + Sync + Clone> BusTopic<T> {
+    pub fn name(&self) -> &str {
+        &self.shared.name
+    }
+
+    /// Number of connected subscribers
+    ///
+    /// Subscribers which were dropped are only removed the next time a message is published.
+    pub fn subscriber_count(&self) -> usize {
+        self.shared.tx.lock().unwrap().connection_count()
+    }
+
+    /// Publishes messages to all current subscribers
+    fn publish(&self, messages: impl IntoIterator<Item = Message<T>>) -> Result<(), TxSendError> {
+        let mut tx = self.shared.tx.lock().unwrap();
+        tx.disconnect_dropped();
+        tx.push_many(messages)?;
+        tx.flush();
+        Ok(())
+    }
+
+    fn connect(
+        &self,
+        rx: &mut DoubleBufferRx<Message<T>>,
+        late_join: LateJoin,
+    ) -> Result<(), TxConnectError> {
+        let mut tx = self.shared.tx.lock().unwrap();
+        match late_join {
+            LateJoin::Miss => tx.connect_live(rx),
+            LateJoin::ReplayLatest => tx.connect(rx),
+        }
+    }
+}
+
+/// Publishes all received messages to a topic of a [Bus]
+///
+/// Messages are delivered to the subscribers in the same step in which they are received.
+pub struct BusPub<T>(core::marker::PhantomData<T>);
+
+impl<T> Default for BusPub<T> {
+    fn default() -> Self {
+        Self(Default::default())
+    }
+}
+
+impl<T> Codelet for BusPub<T>
+where
+    T: Send + Sync + Clone,
+{
+    type Status = DefaultStatus;
+    type Config = BusTopic<T>;
+    type Rx = DoubleBufferRx<Message<T>>;
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (DoubleBufferRx::new_auto_size(), ())
+    }
+
+    fn step(&mut self, cx: &Context<Self>, rx: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        if rx.is_empty() {
+            return SKIPPED;
+        }
+        cx.config.publish(rx.pop_all())?;
+        SUCCESS
+    }
+}
+
+/// Configuration of a [BusSub]
+#[derive(Debug, Clone)]
+pub struct BusSubConfig<T> {
+    pub topic: BusTopic<T>,
+    pub late_join: LateJoin,
+}
+
+/// Receives all messages published to a topic of a [Bus] and forwards them
+///
+/// The subscriber is connected to the topic when it is instantiated and can be created before or
+/// after publishers. Dropping the subscriber disconnects it from the topic.
+pub struct BusSub<T>(core::marker::PhantomData<T>);
+
+impl<T> Default for BusSub<T> {
+    fn default() -> Self {
+        Self(Default::default())
+    }
+}
+
+impl<T> Codelet for BusSub<T>
+where
+    T: Send + Sync + Clone,
+{
+    type Status = DefaultStatus;
+    type Config = BusSubConfig<T>;
+    type Rx = DoubleBufferRx<Message<T>>;
+    type Tx = DoubleBufferTx<Message<T>>;
+
+    fn build_bundles(config: &Self::Config) -> (Self::Rx, Self::Tx) {
+        // keep replayed messages which are synced when the codelet starts
+        let mut rx = DoubleBufferRx::new(OverflowPolicy::Resize, RetentionPolicy::Keep);
+        // cannot fail as the receiver is new and the topic and receiver both resize
+        config.topic.connect(&mut rx, config.late_join).unwrap();
+        (rx, DoubleBufferTx::new_auto_size())
+    }
+
+    fn step(&mut self, _: &Context<Self>, rx: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        if rx.is_empty() {
+            return SKIPPED;
+        }
+        tx.push_many(rx.pop_all())?;
+        SUCCESS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Bus, BusError, BusPub, BusSub, BusSubConfig, LateJoin};
+    use core::time::Duration;
+    use nodo::{
+        codelet::{
+            Clocks, CodeletInstance, Lifecycle, NodeletId, NodeletSetup, Transition, Vise,
+            ViseTrait, WorkerId,
+        },
+        prelude::*,
+    };
+
+    fn message(seq: u64, value: u32) -> Message<u32> {
+        Message {
+            seq,
+            stamp: Stamp {
+                acqtime: Duration::ZERO.into(),
+                pubtime: Duration::ZERO.into(),
+            },
+            value,
+        }
+    }
+
+    fn vise<C: Codelet>(instance: CodeletInstance<C>) -> Vise<C> {
+        let mut vise = Vise::new(instance);
+        vise.setup(&mut NodeletSetup {
+            clocks: Clocks::new(),
+            nodelet_id_issue: NodeletId(WorkerId(0), 0),
+        });
+        vise.cycle(Transition::Start).unwrap();
+        vise
+    }
+
+    /// Publisher with an input channel to feed it
+    struct Publisher {
+        source: DoubleBufferTx<Message<u32>>,
+        vise: Vise<BusPub<u32>>,
+    }
+
+    impl Publisher {
+        fn new(bus: &Bus, topic: &str) -> Self {
+            let mut instance = BusPub::instantiate("pub", bus.topic(topic).unwrap());
+            let mut source = DoubleBufferTx::new_auto_size();
+            source.connect(&mut instance.rx).unwrap();
+            Self {
+                source,
+                vise: vise(instance),
+            }
+        }
+
+        fn publish(&mut self, values: &[u32]) {
+            for (i, value) in values.iter().enumerate() {
+                self.source.push(message(i as u64, *value)).unwrap();
+            }
+            self.source.flush();
+            self.vise.cycle(Transition::Step).unwrap();
+        }
+    }
+
+    /// Subscriber with an output channel to observe it
+    struct Subscriber {
+        vise: Vise<BusSub<u32>>,
+        sink: DoubleBufferRx<Message<u32>>,
+    }
+
+    impl Subscriber {
+        fn new(config: BusSubConfig<u32>) -> Self {
+            let mut instance = BusSub::instantiate("sub", config);
+            let mut sink = DoubleBufferRx::new_auto_size();
+            instance.tx.connect(&mut sink).unwrap();
+            Self {
+                vise: vise(instance),
+                sink,
+            }
+        }
+
+        fn receive(&mut self) -> Vec<u32> {
+            self.vise.cycle(Transition::Step).unwrap();
+            self.sink.sync();
+            self.sink.pop_all().map(|msg| msg.value).collect()
+        }
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        let bus = Bus::new();
+        bus.topic::<u32>("numbers").unwrap();
+
+        assert_eq!(
+            bus.subscribe::<String>("numbers", LateJoin::Miss)
+                .unwrap_err(),
+            BusError::TypeMismatch {
+                topic: "numbers".into(),
+                expected: "u32",
+                actual: "alloc::string::String",
+            }
+        );
+
+        // the same type is accepted
+        assert!(bus.subscribe::<u32>("numbers", LateJoin::Miss).is_ok());
+        assert_eq!(bus.topic_names(), ["numbers"]);
+    }
+
+    #[test]
+    fn test_multiple_subscribers() {
+        let bus = Bus::new();
+        let mut sub_1 = Subscriber::new(bus.subscribe("numbers", LateJoin::Miss).unwrap());
+        let mut publisher = Publisher::new(&bus, "numbers");
+        let mut sub_2 = Subscriber::new(bus.subscribe("numbers", LateJoin::Miss).unwrap());
+        let mut other = Subscriber::new(bus.subscribe("other", LateJoin::Miss).unwrap());
+        assert_eq!(bus.topic::<u32>("numbers").unwrap().subscriber_count(), 2);
+
+        publisher.publish(&[1, 2, 3]);
+        assert_eq!(sub_1.receive(), [1, 2, 3]);
+        assert_eq!(sub_2.receive(), [1, 2, 3]);
+        assert_eq!(other.receive(), []);
+
+        publisher.publish(&[4]);
+        assert_eq!(sub_1.receive(), [4]);
+        assert_eq!(sub_2.receive(), [4]);
+    }
+
+    #[test]
+    fn test_late_join() {
+        let bus = Bus::new();
+        let mut publisher = Publisher::new(&bus, "numbers");
+        publisher.publish(&[1, 2, 3]);
+
+        let mut miss = Subscriber::new(bus.subscribe("numbers", LateJoin::Miss).unwrap());
+        let mut replay = Subscriber::new(bus.subscribe("numbers", LateJoin::ReplayLatest).unwrap());
+        assert_eq!(miss.receive(), []);
+        assert_eq!(replay.receive(), [3]);
+
+        publisher.publish(&[4, 5]);
+        assert_eq!(miss.receive(), [4, 5]);
+        assert_eq!(replay.receive(), [4, 5]);
+
+        // without any published message there is nothing to replay
+        let mut replay = Subscriber::new(bus.subscribe("empty", LateJoin::ReplayLatest).unwrap());
+        assert_eq!(replay.receive(), []);
+    }
+
+    #[test]
+    fn test_teardown() {
+        let bus = Bus::new();
+        let topic = bus.topic::<u32>("numbers").unwrap();
+        let mut publisher = Publisher::new(&bus, "numbers");
+        let mut sub_1 = Subscriber::new(bus.subscribe("numbers", LateJoin::Miss).unwrap());
+        let sub_2 = Subscriber::new(bus.subscribe("numbers", LateJoin::Miss).unwrap());
+        assert_eq!(topic.subscriber_count(), 2);
+
+        // dropped subscribers are removed on the next publish
+        drop(sub_2);
+        publisher.publish(&[1]);
+        assert_eq!(topic.subscriber_count(), 1);
+        assert_eq!(sub_1.receive(), [1]);
+
+        drop(sub_1);
+        publisher.publish(&[2]);
+        assert_eq!(topic.subscriber_count(), 0);
+
+        // subscribers can join again
+        let mut sub_3 = Subscriber::new(bus.subscribe("numbers", LateJoin::Miss).unwrap());
+        publisher.publish(&[3]);
+        assert_eq!(sub_3.receive(), [3]);
+    }
+}
@@ -2,32 +2,315 @@
 
 use core::marker::PhantomData;
 use nodo::prelude::*;
-use nodo_core::BinaryFormat;
+use nodo_core::{eyre, BinaryFormat, EyreResult, Report, Result};
+use std::sync::{Arc, Mutex};
 
-/// A codelet which serializes a message
+/// What a [Deserializer] does when the publisher changed the schema of the messages
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaChangePolicy {
+    /// The step fails on the first message which can not be deserialized or which was published
+    /// with a different schema than expected
+    #[default]
+    Error,
+
+    /// Messages are dropped after a schema change until the codelet is restarted
+    DropUntilRestart,
+
+    /// Sends [SchemaChanged] and drops messages until a new decoder is installed with
+    /// [DecoderHandle::replace]. Processing resumes afterwards.
+    Renegotiate,
+}
+
+/// Sent by a [Deserializer] with [SchemaChangePolicy::Renegotiate] when it detected a schema
+/// change
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaChanged {
+    /// Topic of the [SchemaWatch] or empty if the deserializer has no watch
+    pub topic: String,
+
+    /// Hash of the schema the current decoder was written for if known
+    pub old_hash: Option<String>,
+
+    /// Hash of the schema announced by the publisher if known
+    pub new_hash: Option<String>,
+}
+
+/// Latest schema hash announced by the publisher of a topic
+///
+/// Shared between the [Deserializer] of the topic and the codelet which receives the
+/// announcements, e.g. the `SchemaWatcher` of `nodo_nng`.
+#[derive(Debug, Clone)]
+pub struct SchemaWatch {
+    topic: String,
+    hash: Arc<Mutex<Option<String>>>,
+}
+
+impl SchemaWatch {
+    pub fn new(topic: &str) -> Self {
+        Self {
+            topic: topic.to_string(),
+            hash: Arc::default(),
+        }
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// Updates the announced schema hash
+    pub fn announce(&self, hash: &str) {
+        *self.hash.lock().unwrap() = Some(hash.to_string());
+    }
+
+    /// The latest announced schema hash
+    pub fn hash(&self) -> Option<String> {
+        self.hash.lock().unwrap().clone()
+    }
+}
+
+/// Decodes the payload of a message
+pub type DecodeFn<T> = Box<dyn FnMut(&[u8]) -> EyreResult<T> + Send>;
+
+/// A new decoder and the schema hash it was written for
+type Replacement<T> = (Option<String>, DecodeFn<T>);
+
+/// Replaces the decoder of a running [Deserializer], see [SchemaChangePolicy::Renegotiate]
+pub struct DecoderHandle<T> {
+    replacement: Arc<Mutex<Option<Replacement<T>>>>,
+}
+
+impl<T> Clone for DecoderHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            replacement: self.replacement.clone(),
+        }
+    }
+}
+
+impl<T> DecoderHandle<T> {
+    /// Installs a decoder for messages with the given schema hash. The latest announced hash is
+    /// used if no hash is given. The decoder is used from the next step on.
+    pub fn replace<F>(&self, schema_hash: Option<String>, decoder: F)
+    where
+        F: FnMut(&[u8]) -> EyreResult<T> + Send + 'static,
+    {
+        *self.replacement.lock().unwrap() = Some((schema_hash, Box::new(decoder)));
+    }
+
+    fn take(&self) -> Option<Replacement<T>> {
+        self.replacement.lock().unwrap().take()
+    }
+}
+
+/// Distinguishes schema changes from transient corruption
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SchemaState {
+    /// Messages are delivered. Counts consecutive messages which could not be decoded.
+    Matching { mismatches: usize },
+
+    /// The schema changed and messages are dropped
+    Changed,
+}
+
+/// Outcome of deserializing a single message
+#[derive(Debug, PartialEq)]
+enum Decoded<T> {
+    Value(T),
+    Dropped,
+    Changed(SchemaChanged),
+}
+
+/// A codelet which deserializes a message
+///
+/// The schema is considered changed as soon as the publisher announced a different schema hash on
+/// the [SchemaWatch]. Messages which can not be deserialized are treated as transient corruption
+/// and only after [DeserializerConfig::schema_change_threshold] consecutive failures the schema
+/// is considered changed, see [SchemaChangePolicy].
 pub struct Deserializer<T, BF> {
     format: BF,
+    decoder: Option<DecodeFn<T>>,
+    handle: DecoderHandle<T>,
+    watch: Option<SchemaWatch>,
+    current_hash: Option<String>,
+    state: SchemaState,
     marker: PhantomData<T>,
 }
 
 pub struct DeserializerConfig {
     /// Maximum number of messages which can be queued before messages are dropped.
     pub queue_size: usize,
+
+    pub on_schema_change: SchemaChangePolicy,
+
+    /// Number of consecutive messages which can not be deserialized after which the schema is
+    /// considered changed. A different announced schema hash is a change right away. Not used by
+    /// [SchemaChangePolicy::Error].
+    pub schema_change_threshold: usize,
 }
 
 impl Default for DeserializerConfig {
     fn default() -> Self {
-        Self { queue_size: 10 }
+        Self {
+            queue_size: 10,
+            on_schema_change: SchemaChangePolicy::Error,
+            schema_change_threshold: 3,
+        }
     }
 }
 
+#[derive(TxBundleDerive)]
+pub struct DeserializerTx<T: Clone + Send + Sync> {
+    /// Deserialized messages
+    pub output: DoubleBufferTx<Message<T>>,
+
+    /// See [SchemaChangePolicy::Renegotiate]
+    pub schema_changed: DoubleBufferTx<SchemaChanged>,
+}
+
 impl<T, BF> Deserializer<T, BF> {
     pub fn new(format: BF) -> Self {
         Self {
             format,
+            decoder: None,
+            handle: DecoderHandle {
+                replacement: Arc::default(),
+            },
+            watch: None,
+            current_hash: None,
+            state: SchemaState::Matching { mismatches: 0 },
             marker: PhantomData::default(),
         }
     }
+
+    /// Compares the schema announced by the publisher with the expected schema
+    #[must_use]
+    pub fn with_schema_watch(mut self, watch: SchemaWatch) -> Self {
+        self.watch = Some(watch);
+        self
+    }
+
+    /// Hash of the schema the format was written for. Without it the first announced hash is
+    /// expected.
+    #[must_use]
+    pub fn with_expected_schema_hash(mut self, hash: &str) -> Self {
+        self.current_hash = Some(hash.to_string());
+        self
+    }
+
+    /// Handle to install a new decoder at runtime
+    pub fn decoder_handle(&self) -> DecoderHandle<T> {
+        self.handle.clone()
+    }
+
+    /// Installs a decoder passed to the [DecoderHandle] and resumes processing
+    fn install_replacement(&mut self, announced: Option<&str>) {
+        if let Some((hash, decoder)) = self.handle.take() {
+            self.decoder = Some(decoder);
+            self.current_hash = hash.or(announced.map(String::from));
+            self.state = SchemaState::Matching { mismatches: 0 };
+            log::info!(
+                "installed new decoder for topic '{}' with schema {:?}",
+                self.topic(),
+                self.current_hash
+            );
+        }
+    }
+
+    fn topic(&self) -> &str {
+        self.watch.as_ref().map_or("", SchemaWatch::topic)
+    }
+}
+
+impl<T, BF: BinaryFormat<T>> Deserializer<T, BF> {
+    fn decode(
+        &mut self,
+        buffer: &[u8],
+        announced: Option<&str>,
+        cfg: &DeserializerConfig,
+    ) -> Result<Decoded<T>> {
+        if self.state == SchemaState::Changed {
+            return Ok(Decoded::Dropped);
+        }
+
+        if self.current_hash.is_none() {
+            self.current_hash = announced.map(String::from);
+        }
+
+        if let Some(hash) = announced.filter(|&hash| self.current_hash.as_deref() != Some(hash)) {
+            let err = eyre!(
+                "message was published with schema {hash} but schema {:?} was expected",
+                self.current_hash
+            );
+            if cfg.on_schema_change == SchemaChangePolicy::Error {
+                return Err(err);
+            }
+            return Ok(self.change_schema(announced, cfg, err));
+        }
+
+        let result = match self.decoder.as_mut() {
+            Some(decoder) => decoder(buffer),
+            None => self.format.deserialize(buffer),
+        };
+
+        let err = match result {
+            Ok(value) => {
+                self.state = SchemaState::Matching { mismatches: 0 };
+                return Ok(Decoded::Value(value));
+            }
+            Err(err) => err,
+        };
+
+        if cfg.on_schema_change == SchemaChangePolicy::Error {
+            return Err(err);
+        }
+
+        let SchemaState::Matching { mismatches } = self.state else {
+            unreachable!()
+        };
+        let mismatches = mismatches + 1;
+        if mismatches < cfg.schema_change_threshold {
+            self.state = SchemaState::Matching { mismatches };
+            log::warn!(
+                "dropped message on topic '{}' which did not match the schema ({}/{} before \
+                 assuming a schema change): {err:?}",
+                self.topic(),
+                mismatches,
+                cfg.schema_change_threshold
+            );
+            return Ok(Decoded::Dropped);
+        }
+
+        Ok(self.change_schema(announced, cfg, err))
+    }
+
+    /// Stops delivering messages after a schema change
+    fn change_schema(
+        &mut self,
+        announced: Option<&str>,
+        cfg: &DeserializerConfig,
+        err: Report,
+    ) -> Decoded<T> {
+        self.state = SchemaState::Changed;
+        let change = SchemaChanged {
+            topic: self.topic().to_string(),
+            old_hash: self.current_hash.clone(),
+            new_hash: announced.map(String::from),
+        };
+        log::warn!(
+            "schema of topic '{}' changed from {:?} to {:?}. dropping messages {}: {err:?}",
+            change.topic,
+            change.old_hash,
+            change.new_hash,
+            match cfg.on_schema_change {
+                SchemaChangePolicy::Renegotiate => "until a new decoder is installed",
+                _ => "until restart",
+            }
+        );
+        match cfg.on_schema_change {
+            SchemaChangePolicy::Renegotiate => Decoded::Changed(change),
+            _ => Decoded::Dropped,
+        }
+    }
 }
 
 impl<T, BF> Codelet for Deserializer<T, BF>
@@ -38,7 +321,7 @@ where
     type Status = DefaultStatus;
     type Config = DeserializerConfig;
     type Rx = DoubleBufferRx<Message<Vec<u8>>>;
-    type Tx = DoubleBufferTx<Message<T>>;
+    type Tx = DeserializerTx<T>;
 
     fn build_bundles(cfg: &Self::Config) -> (Self::Rx, Self::Tx) {
         (
@@ -46,25 +329,279 @@ where
                 OverflowPolicy::Forget(cfg.queue_size),
                 RetentionPolicy::Keep,
             ),
-            DoubleBufferTx::new(cfg.queue_size),
+            DeserializerTx {
+                output: DoubleBufferTx::new(cfg.queue_size),
+                schema_changed: DoubleBufferTx::new_auto_size(),
+            },
         )
     }
 
+    fn start(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.state = SchemaState::Matching { mismatches: 0 };
+        SUCCESS
+    }
+
     fn step(&mut self, cx: &Context<Self>, rx: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        let announced = self.watch.as_ref().and_then(SchemaWatch::hash);
+        self.install_replacement(announced.as_deref());
+
         if rx.is_empty() {
             SKIPPED
         } else {
             while let Some(message) = rx.try_pop() {
-                tx.push(Message {
-                    seq: message.seq,
-                    stamp: Stamp {
-                        acqtime: message.stamp.acqtime,
-                        pubtime: cx.clocks.app_mono.now(),
-                    },
-                    value: self.format.deserialize(&message.value)?,
-                })?;
+                match self.decode(&message.value, announced.as_deref(), cx.config)? {
+                    Decoded::Value(value) => tx.output.push(Message {
+                        seq: message.seq,
+                        stamp: Stamp {
+                            acqtime: message.stamp.acqtime,
+                            pubtime: cx.clocks.app_mono.now(),
+                        },
+                        value,
+                    })?,
+                    Decoded::Dropped => {}
+                    Decoded::Changed(change) => tx.schema_changed.push(change)?,
+                }
             }
             SUCCESS
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        deserializer::Decoded, Deserializer, DeserializerConfig, SchemaChangePolicy, SchemaChanged,
+        SchemaWatch,
+    };
+    use core::time::Duration;
+    use nodo::{
+        codelet::{
+            Clocks, Lifecycle, NodeletId, NodeletSetup, Transition, Vise, ViseTrait, WorkerId,
+        },
+        prelude::*,
+    };
+    use nodo_core::{BinaryFormat, EyreResult, Schema};
+
+    /// Numbers as text. Version 2 of the publisher prefixes numbers with `v2:`.
+    struct Text;
+
+    impl BinaryFormat<u32> for Text {
+        fn schema(&self) -> Schema {
+            Schema {
+                name: "u32".into(),
+                encoding: "text".into(),
+            }
+        }
+
+        fn serialize(&mut self, data: &u32) -> EyreResult<Vec<u8>> {
+            Ok(data.to_string().into_bytes())
+        }
+
+        fn deserialize(&mut self, buffer: &[u8]) -> EyreResult<u32> {
+            Ok(std::str::from_utf8(buffer)?.parse()?)
+        }
+    }
+
+    fn config(policy: SchemaChangePolicy) -> DeserializerConfig {
+        DeserializerConfig {
+            on_schema_change: policy,
+            schema_change_threshold: 3,
+            ..Default::default()
+        }
+    }
+
+    /// Decodes messages as text with the given announced schema hash and summarizes the outcomes
+    fn run(
+        de: &mut Deserializer<u32, Text>,
+        cfg: &DeserializerConfig,
+        announced: Option<&str>,
+        messages: &[&str],
+    ) -> Vec<String> {
+        de.install_replacement(announced);
+        messages
+            .iter()
+            .map(|msg| match de.decode(msg.as_bytes(), announced, cfg) {
+                Ok(Decoded::Value(value)) => value.to_string(),
+                Ok(Decoded::Dropped) => "dropped".into(),
+                Ok(Decoded::Changed(_)) => "changed".into(),
+                Err(_) => "error".into(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_transient_corruption() {
+        for policy in [
+            SchemaChangePolicy::DropUntilRestart,
+            SchemaChangePolicy::Renegotiate,
+        ] {
+            let cfg = config(policy);
+            let mut de = Deserializer::new(Text);
+            assert_eq!(
+                run(&mut de, &cfg, None, &["1", "x", "y", "2", "z", "3"]),
+                ["1", "dropped", "dropped", "2", "dropped", "3"]
+            );
+        }
+
+        let cfg = config(SchemaChangePolicy::Error);
+        let mut de = Deserializer::new(Text);
+        assert_eq!(
+            run(&mut de, &cfg, None, &["1", "x", "2"]),
+            ["1", "error", "2"]
+        );
+    }
+
+    #[test]
+    fn test_error_on_hash_change() {
+        let cfg = config(SchemaChangePolicy::Error);
+        let mut de = Deserializer::new(Text).with_expected_schema_hash("v1");
+        assert_eq!(run(&mut de, &cfg, Some("v1"), &["1"]), ["1"]);
+        assert_eq!(run(&mut de, &cfg, Some("v2"), &["2"]), ["error"]);
+    }
+
+    #[test]
+    fn test_drop_until_restart() {
+        let cfg = config(SchemaChangePolicy::DropUntilRestart);
+        let mut de = Deserializer::new(Text);
+
+        // the first announced hash is expected
+        assert_eq!(run(&mut de, &cfg, Some("v1"), &["1", "2"]), ["1", "2"]);
+        assert_eq!(de.current_hash.as_deref(), Some("v1"));
+
+        // the publisher restarted with a new schema mid-stream
+        assert_eq!(
+            run(&mut de, &cfg, Some("v2"), &["v2:3", "v2:4", "v2:5", "v2:6"]),
+            ["dropped"; 4]
+        );
+
+        // messages are dropped even if the hash changes back
+        assert_eq!(run(&mut de, &cfg, Some("v1"), &["7"]), ["dropped"]);
+    }
+
+    #[test]
+    fn test_renegotiate() {
+        let cfg = config(SchemaChangePolicy::Renegotiate);
+        let mut de = Deserializer::new(Text).with_expected_schema_hash("v1");
+        let handle = de.decoder_handle();
+
+        assert_eq!(run(&mut de, &cfg, Some("v1"), &["1"]), ["1"]);
+
+        // a different announced hash is a schema change right away
+        assert_eq!(
+            de.decode(b"v2:2", Some("v2"), &cfg).unwrap(),
+            Decoded::Changed(SchemaChanged {
+                topic: String::new(),
+                old_hash: Some("v1".into()),
+                new_hash: Some("v2".into()),
+            })
+        );
+        assert_eq!(
+            run(&mut de, &cfg, Some("v2"), &["v2:3", "v2:4"]),
+            ["dropped"; 2]
+        );
+
+        // the application installs a decoder for the new schema and processing resumes
+        handle.replace(None, |buffer| {
+            let text = std::str::from_utf8(buffer)?;
+            Ok(text.trim_start_matches("v2:").parse()?)
+        });
+        assert_eq!(
+            run(&mut de, &cfg, Some("v2"), &["v2:6", "v2:7"]),
+            ["6", "7"]
+        );
+        assert_eq!(de.current_hash.as_deref(), Some("v2"));
+    }
+
+    #[test]
+    fn test_renegotiate_without_announcement() {
+        let cfg = config(SchemaChangePolicy::Renegotiate);
+        let mut de = Deserializer::new(Text);
+        let handle = de.decoder_handle();
+
+        assert_eq!(
+            run(&mut de, &cfg, None, &["1", "v2:2", "v2:3", "v2:4", "v2:5"]),
+            ["1", "dropped", "dropped", "changed", "dropped"]
+        );
+
+        handle.replace(Some("v2".into()), |buffer| {
+            Ok(std::str::from_utf8(buffer)?[3..].parse()?)
+        });
+        assert_eq!(run(&mut de, &cfg, None, &["v2:6"]), ["6"]);
+        assert_eq!(de.current_hash.as_deref(), Some("v2"));
+    }
+
+    /// The running codelet reports the change announced on the watch and uses the decoder
+    /// installed by the application on the next step
+    #[test]
+    fn test_renegotiate_instance() {
+        let watch = SchemaWatch::new("numbers");
+        watch.announce("v1");
+        let de = Deserializer::new(Text).with_schema_watch(watch.clone());
+        let handle = de.decoder_handle();
+        let mut instance = de.into_instance("de", config(SchemaChangePolicy::Renegotiate));
+
+        let mut input = DoubleBufferTx::new_auto_size();
+        let mut output = DoubleBufferRx::new_auto_size();
+        let mut schema_changed = DoubleBufferRx::new_auto_size();
+        input.connect(&mut instance.rx).unwrap();
+        instance.tx.output.connect(&mut output).unwrap();
+        instance
+            .tx
+            .schema_changed
+            .connect(&mut schema_changed)
+            .unwrap();
+
+        let mut vise = Vise::new(instance);
+        vise.setup(&mut NodeletSetup {
+            clocks: Clocks::new(),
+            nodelet_id_issue: NodeletId(WorkerId(0), 0),
+        });
+        vise.cycle(Transition::Start).unwrap();
+
+        let mut step = |vise: &mut Vise<Deserializer<u32, Text>>, text: &str| {
+            input
+                .push(Message {
+                    seq: 0,
+                    stamp: Stamp {
+                        acqtime: Duration::ZERO.into(),
+                        pubtime: Duration::ZERO.into(),
+                    },
+                    value: text.as_bytes().to_vec(),
+                })
+                .unwrap();
+            input.flush();
+            vise.cycle(Transition::Step).unwrap();
+            output.sync();
+            schema_changed.sync();
+            (
+                output.pop_all().map(|m| m.value).collect::<Vec<_>>(),
+                schema_changed.pop_all().collect::<Vec<_>>(),
+            )
+        };
+
+        assert_eq!(step(&mut vise, "1"), (vec![1], vec![]));
+
+        // the publisher restarted with a new schema
+        watch.announce("v2");
+        assert_eq!(
+            step(&mut vise, "v2:2"),
+            (
+                vec![],
+                vec![SchemaChanged {
+                    topic: "numbers".into(),
+                    old_hash: Some("v1".into()),
+                    new_hash: Some("v2".into()),
+                }]
+            )
+        );
+        assert_eq!(step(&mut vise, "v2:3"), (vec![], vec![]));
+
+        handle.replace(
+            None,
+            |buffer| Ok(std::str::from_utf8(buffer)?[3..].parse()?),
+        );
+        assert_eq!(step(&mut vise, "v2:4"), (vec![4], vec![]));
+
+        vise.cycle(Transition::Stop).unwrap();
+    }
+}
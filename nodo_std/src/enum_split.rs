@@ -0,0 +1,228 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::marker::PhantomData;
+use nodo::{
+    channels::{EnumSplitMessage, TxSendError},
+    prelude::*,
+};
+use nodo_core::Result;
+
+/// Status of [EnumSplit] which shows the number of received messages per variant in its label
+pub struct EnumSplitStatus {
+    label: String,
+    status: DefaultStatus,
+}
+
+impl CodeletStatus for EnumSplitStatus {
+    fn default_implementation_status() -> Self {
+        Self {
+            label: "idle".into(),
+            status: DefaultStatus::Skipped,
+        }
+    }
+
+    fn as_default_status(&self) -> DefaultStatus {
+        self.status
+    }
+
+    fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+/// Routes each variant of an enum message to its own channel
+///
+/// The enum must implement [EnumSplitMessage], usually with `#[derive(EnumSplitDerive)]`. The TX
+/// bundle has one channel per variant named after the variant in snake case.
+pub struct EnumSplit<E> {
+    counts: Vec<u64>,
+    marker: PhantomData<E>,
+}
+
+impl<E: EnumSplitMessage> Default for EnumSplit<E> {
+    fn default() -> Self {
+        Self {
+            counts: vec![0; E::VARIANTS.len()],
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<E: EnumSplitMessage> EnumSplit<E> {
+    /// Number of received messages per variant in declaration order
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Number of received messages of the variant with the given name
+    pub fn count(&self, variant: &str) -> Option<u64> {
+        E::VARIANTS
+            .iter()
+            .position(|name| *name == variant)
+            .map(|i| self.counts[i])
+    }
+
+    /// Counts the message and pushes it to the channel of its variant
+    fn route(&mut self, value: E, tx: &mut E::Tx) -> Result<(), TxSendError> {
+        self.counts[value.variant_index()] += 1;
+        value.route(tx)
+    }
+
+    fn status(&self, received: bool) -> EnumSplitStatus {
+        let label = E::VARIANTS
+            .iter()
+            .zip(self.counts.iter())
+            .map(|(name, count)| format!("{name}: {count}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        EnumSplitStatus {
+            label,
+            status: if received {
+                DefaultStatus::Running
+            } else {
+                DefaultStatus::Skipped
+            },
+        }
+    }
+}
+
+impl<E: EnumSplitMessage + Send + Sync> Codelet for EnumSplit<E> {
+    type Status = EnumSplitStatus;
+    type Config = ();
+    type Rx = DoubleBufferRx<E>;
+    type Tx = E::Tx;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (DoubleBufferRx::new_auto_size(), E::Tx::default())
+    }
+
+    fn step(
+        &mut self,
+        _: &Context<Self>,
+        rx: &mut Self::Rx,
+        tx: &mut Self::Tx,
+    ) -> Result<EnumSplitStatus> {
+        let received = !rx.is_empty();
+        while let Some(value) = rx.try_pop() {
+            self.route(value, tx)?;
+        }
+        Ok(self.status(received))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::EnumSplit;
+    use nodo::{
+        channels::{EnumSplitMessage, TxBundle},
+        prelude::*,
+    };
+
+    #[derive(Clone, Debug, PartialEq, EnumSplitDerive)]
+    enum DriverEvent {
+        Frame(Vec<u8>),
+        Dropped(u32, String),
+        Error(String),
+        Shutdown,
+    }
+
+    #[derive(Clone, Debug, PartialEq, EnumSplitDerive)]
+    enum Either<A, B> {
+        Left(A),
+        Right(B),
+    }
+
+    fn receive<T: Send + Sync>(rx: &mut DoubleBufferRx<T>) -> Vec<T> {
+        rx.sync();
+        rx.drain(..).collect()
+    }
+
+    #[test]
+    fn test_enum_split() {
+        let mut split = EnumSplit::<DriverEvent>::default();
+        let mut tx = DriverEventSplitTx::default();
+        assert_eq!(tx.len(), 4);
+        assert_eq!(tx.name(2), "error");
+        assert_eq!(
+            DriverEvent::VARIANTS,
+            ["Frame", "Dropped", "Error", "Shutdown"]
+        );
+        assert_eq!(
+            split.status(false).label(),
+            "Frame: 0, Dropped: 0, Error: 0, Shutdown: 0"
+        );
+
+        let mut frames = DoubleBufferRx::new_auto_size();
+        let mut dropped = DoubleBufferRx::new_auto_size();
+        let mut errors = DoubleBufferRx::new_auto_size();
+        let mut shutdown = DoubleBufferRx::new_auto_size();
+        tx.frame.connect(&mut frames).unwrap();
+        tx.dropped.connect(&mut dropped).unwrap();
+        tx.error.connect(&mut errors).unwrap();
+        tx.shutdown.connect(&mut shutdown).unwrap();
+
+        for value in [
+            DriverEvent::Frame(vec![1]),
+            DriverEvent::Dropped(3, "overrun".into()),
+            DriverEvent::Frame(vec![2, 3]),
+            DriverEvent::Error("timeout".into()),
+            DriverEvent::Frame(vec![4]),
+            DriverEvent::Dropped(1, "checksum".into()),
+            DriverEvent::Shutdown,
+        ] {
+            split.route(value, &mut tx).unwrap();
+        }
+        tx.flush_all(&mut vec![Default::default(); tx.len()]);
+
+        assert_eq!(receive(&mut frames), [vec![1], vec![2, 3], vec![4]]);
+        assert_eq!(
+            receive(&mut dropped),
+            [(3, "overrun".to_string()), (1, "checksum".to_string())]
+        );
+        assert_eq!(receive(&mut errors), ["timeout"]);
+        assert_eq!(receive(&mut shutdown), [()]);
+
+        assert_eq!(split.counts(), [3, 2, 1, 1]);
+        assert_eq!(split.count("Dropped"), Some(2));
+        assert_eq!(split.count("Unknown"), None);
+        assert_eq!(
+            split.status(true).label(),
+            "Frame: 3, Dropped: 2, Error: 1, Shutdown: 1"
+        );
+        assert_eq!(
+            split.status(true).as_default_status(),
+            DefaultStatus::Running
+        );
+        assert_eq!(
+            split.status(false).as_default_status(),
+            DefaultStatus::Skipped
+        );
+    }
+
+    #[test]
+    fn test_enum_split_generic() {
+        let mut split = EnumSplit::<Either<u32, String>>::default();
+        let mut tx = EitherSplitTx::default();
+
+        let mut left = DoubleBufferRx::new_auto_size();
+        let mut right = DoubleBufferRx::new_auto_size();
+        tx.left.connect(&mut left).unwrap();
+        tx.right.connect(&mut right).unwrap();
+
+        for value in [
+            Either::Left(1),
+            Either::Right("a".to_string()),
+            Either::Left(2),
+            Either::Left(3),
+        ] {
+            split.route(value, &mut tx).unwrap();
+        }
+        tx.flush_all(&mut vec![Default::default(); tx.len()]);
+
+        assert_eq!(receive(&mut left), [1, 2, 3]);
+        assert_eq!(receive(&mut right), ["a"]);
+        assert_eq!(split.counts(), [3, 1]);
+        assert_eq!(split.status(true).label(), "Left: 3, Right: 1");
+    }
+}
@@ -0,0 +1,488 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use nodo::prelude::*;
+use nodo_core::{eyre, Result};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read},
+    os::{
+        fd::{AsRawFd, OwnedFd},
+        unix::fs::OpenOptionsExt,
+    },
+    path::PathBuf,
+    time::Instant,
+};
+
+/// Where an [FdSource] reads from
+pub enum FdTarget {
+    /// An already opened file descriptor like a pipe or a socket. It is not reopened after EOF.
+    Fd(OwnedFd),
+
+    /// A path which is opened on the first step and reopened after EOF or read errors, for
+    /// example a serial device or a named pipe
+    Path(PathBuf),
+}
+
+/// Configuration for [FdSource]
+#[derive(Debug, Clone)]
+pub struct FdSourceConfig {
+    /// Maximal time a step waits for and reads data. Waiting has millisecond resolution, thus
+    /// shorter durations never wait but still read all data which is immediately available.
+    pub max_step_duration: Duration,
+
+    /// Maximal number of bytes read in a single step
+    pub max_step_bytes: usize,
+
+    /// Time between attempts to reopen a path-based source
+    pub retry_interval: Duration,
+
+    /// Maximal number of consecutive reopen attempts. The count is reset whenever the source was
+    /// opened successfully. None retries forever.
+    pub max_retries: Option<usize>,
+}
+
+impl Default for FdSourceConfig {
+    fn default() -> Self {
+        Self {
+            max_step_duration: Duration::from_millis(1),
+            max_step_bytes: 64 * 1024,
+            retry_interval: Duration::from_secs(1),
+            max_retries: None,
+        }
+    }
+}
+
+#[derive(Status)]
+pub enum FdSourceStatus {
+    #[default]
+    #[skipped]
+    #[label = "idle"]
+    Idle,
+
+    #[label = "received"]
+    Received(usize),
+
+    /// The file descriptor reached EOF or the peer closed the connection
+    #[skipped]
+    #[label = "eof"]
+    Eof,
+
+    /// Waiting to reopen a path-based source
+    #[skipped]
+    #[label = "reconnecting"]
+    Reconnecting,
+}
+
+/// Extracts a single item from the front of the buffer and removes the consumed bytes. Returns
+/// None if the buffer does not contain a complete item yet.
+pub type FdParser<T> = Box<dyn FnMut(&mut Vec<u8>) -> Option<T> + Send>;
+
+/// Reads from a file descriptor without blocking and publishes decoded items
+///
+/// Each step waits at most `max_step_duration` for data and reads whatever is available.
+/// Incomplete items are buffered until the next step. Partial data left after EOF is discarded
+/// when a path-based source is reopened.
+pub struct FdSource<T> {
+    path: Option<PathBuf>,
+    file: Option<File>,
+    parser: FdParser<T>,
+    buffer: Vec<u8>,
+    seq: u64,
+    retry_count: usize,
+    next_retry: Option<Instant>,
+}
+
+/// Result of reading from the file descriptor in one step
+#[derive(Debug, PartialEq)]
+enum ReadResult {
+    Data(usize),
+    Eof(usize),
+}
+
+impl FdSource<String> {
+    /// Publishes newline-delimited lines without the line terminator
+    pub fn lines(target: FdTarget) -> Result<Self> {
+        Self::with_parser(target, |buffer: &mut Vec<u8>| {
+            let pos = buffer.iter().position(|&b| b == b'\n')?;
+            let mut line = buffer.drain(..=pos).collect::<Vec<_>>();
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            Some(String::from_utf8_lossy(&line).into_owned())
+        })
+    }
+}
+
+impl FdSource<Vec<u8>> {
+    /// Publishes frames with a fixed number of bytes
+    pub fn fixed_size(target: FdTarget, frame_size: usize) -> Result<Self> {
+        assert!(frame_size > 0, "frame size must not be zero");
+        Self::with_parser(target, move |buffer: &mut Vec<u8>| {
+            (buffer.len() >= frame_size).then(|| buffer.drain(..frame_size).collect())
+        })
+    }
+}
+
+impl<T> FdSource<T> {
+    /// Publishes items decoded with a custom parser, see [FdParser]
+    pub fn with_parser<F>(target: FdTarget, parser: F) -> Result<Self>
+    where
+        F: FnMut(&mut Vec<u8>) -> Option<T> + Send + 'static,
+    {
+        let (path, file) = match target {
+            FdTarget::Fd(fd) => {
+                set_nonblocking(&fd)?;
+                (None, Some(File::from(fd)))
+            }
+            FdTarget::Path(path) => (Some(path), None),
+        };
+
+        Ok(Self {
+            path,
+            file,
+            parser: Box::new(parser),
+            buffer: Vec::new(),
+            seq: 0,
+            retry_count: 0,
+            next_retry: None,
+        })
+    }
+
+    /// Opens the path if it is time for the next attempt. Returns true if the source is open.
+    fn try_open(&mut self, config: &FdSourceConfig) -> bool {
+        let Some(path) = self.path.as_ref() else {
+            return false;
+        };
+
+        let now = Instant::now();
+        if self.next_retry.is_some_and(|t| now < t) {
+            return false;
+        }
+
+        match OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)
+        {
+            Ok(file) => {
+                self.file = Some(file);
+                self.buffer.clear();
+                self.next_retry = None;
+                self.retry_count = 0;
+                true
+            }
+            Err(err) => {
+                log::debug!("could not open '{}': {err}", path.display());
+                self.schedule_retry(config);
+                false
+            }
+        }
+    }
+
+    fn schedule_retry(&mut self, config: &FdSourceConfig) {
+        self.next_retry = Some(Instant::now() + config.retry_interval);
+        self.retry_count += 1;
+    }
+
+    fn is_retry_exhausted(&self, config: &FdSourceConfig) -> bool {
+        self.path.is_none() || config.max_retries.is_some_and(|max| self.retry_count > max)
+    }
+
+    /// Reads available data into the buffer within the limits of the step budget
+    fn read_available(&mut self, config: &FdSourceConfig) -> io::Result<ReadResult> {
+        let Some(mut file) = self.file.as_ref() else {
+            return Ok(ReadResult::Eof(0));
+        };
+
+        let deadline = Instant::now() + config.max_step_duration;
+        let mut chunk = [0u8; 4096];
+        let mut total = 0;
+
+        while total < config.max_step_bytes {
+            // only wait for data if nothing was received yet
+            let timeout = if total == 0 {
+                deadline.saturating_duration_since(Instant::now())
+            } else {
+                Duration::ZERO
+            };
+            if !poll_readable(file, timeout)? {
+                break;
+            }
+
+            let len = chunk.len().min(config.max_step_bytes - total);
+            match file.read(&mut chunk[..len]) {
+                Ok(0) => return Ok(ReadResult::Eof(total)),
+                Ok(n) => {
+                    self.buffer.extend_from_slice(&chunk[..n]);
+                    total += n;
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(ReadResult::Data(total))
+    }
+}
+
+impl<T: Send + Sync + Clone> Codelet for FdSource<T> {
+    type Status = FdSourceStatus;
+    type Config = FdSourceConfig;
+    type Rx = ();
+    type Tx = DoubleBufferTx<Message<T>>;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), DoubleBufferTx::new_auto_size())
+    }
+
+    fn step(
+        &mut self,
+        cx: &Context<Self>,
+        _: &mut Self::Rx,
+        tx: &mut Self::Tx,
+    ) -> Result<FdSourceStatus> {
+        if self.file.is_none() {
+            if self.is_retry_exhausted(cx.config) {
+                return Ok(FdSourceStatus::Eof);
+            }
+            if !self.try_open(cx.config) {
+                return Ok(FdSourceStatus::Reconnecting);
+            }
+        }
+
+        let is_eof = match self.read_available(cx.config) {
+            Ok(ReadResult::Data(_)) => false,
+            Ok(ReadResult::Eof(_)) => true,
+            Err(err) if self.path.is_some() => {
+                log::warn!("read error, reopening source: {err}");
+                true
+            }
+            Err(err) => return Err(eyre!("read error: {err}")),
+        };
+
+        let mut count = 0;
+        while let Some(value) = (self.parser)(&mut self.buffer) {
+            tx.push(Message {
+                seq: self.seq,
+                stamp: Stamp {
+                    acqtime: cx.clocks.sys_mono.now(),
+                    pubtime: cx.clocks.app_mono.now(),
+                },
+                value,
+            })?;
+            self.seq += 1;
+            count += 1;
+        }
+
+        if is_eof {
+            self.file = None;
+            if self.path.is_some() {
+                self.schedule_retry(cx.config);
+            }
+        }
+
+        Ok(if count > 0 {
+            FdSourceStatus::Received(count)
+        } else if is_eof {
+            if self.is_retry_exhausted(cx.config) {
+                FdSourceStatus::Eof
+            } else {
+                FdSourceStatus::Reconnecting
+            }
+        } else {
+            FdSourceStatus::Idle
+        })
+    }
+}
+
+fn set_nonblocking<F: AsRawFd>(fd: &F) -> io::Result<()> {
+    let fd = fd.as_raw_fd();
+    // SAFETY: fcntl is called with a valid file descriptor owned by the caller
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: see above
+    if unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Waits until the file descriptor is readable or closed, or until the timeout expired
+fn poll_readable<F: AsRawFd>(fd: &F, timeout: Duration) -> io::Result<bool> {
+    let mut pfd = libc::pollfd {
+        fd: fd.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout_ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+    loop {
+        // SAFETY: pfd is a valid pollfd and the count is 1
+        let ret = unsafe { libc::poll(&mut pfd, 1, timeout_ms) };
+        if ret >= 0 {
+            return Ok(ret > 0);
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        fd_source::{FdSource, ReadResult},
+        FdSourceConfig, FdTarget,
+    };
+    use core::time::Duration;
+    use nodo::{
+        codelet::{
+            Clocks, Lifecycle, NodeletId, NodeletSetup, Transition, Vise, ViseTrait, WorkerId,
+        },
+        prelude::*,
+    };
+    use std::{
+        fs::File,
+        io::Write,
+        os::fd::{FromRawFd, OwnedFd},
+        time::Instant,
+    };
+
+    fn pipe() -> (OwnedFd, File) {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        // SAFETY: pipe returned two new file descriptors owned by nobody else
+        unsafe { (OwnedFd::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) }
+    }
+
+    /// Reads available data and decodes all complete items like a step does
+    fn step<T>(source: &mut FdSource<T>, config: &FdSourceConfig) -> (ReadResult, Vec<T>) {
+        let result = source.read_available(config).unwrap();
+        let mut items = Vec::new();
+        while let Some(item) = (source.parser)(&mut source.buffer) {
+            items.push(item);
+        }
+        (result, items)
+    }
+
+    #[test]
+    fn test_lines_across_steps() {
+        let config = FdSourceConfig::default();
+        let (rx, mut writer) = pipe();
+        let mut source = FdSource::lines(FdTarget::Fd(rx)).unwrap();
+
+        assert_eq!(step(&mut source, &config), (ReadResult::Data(0), vec![]));
+
+        writer.write_all(b"hel").unwrap();
+        assert_eq!(step(&mut source, &config).1, Vec::<String>::new());
+
+        writer.write_all(b"lo\r\nwor").unwrap();
+        assert_eq!(step(&mut source, &config).1, vec!["hello"]);
+
+        writer.write_all(b"ld\n\nlast").unwrap();
+        assert_eq!(step(&mut source, &config).1, vec!["world", ""]);
+
+        drop(writer);
+        assert_eq!(step(&mut source, &config), (ReadResult::Eof(0), vec![]));
+        assert_eq!(source.buffer, b"last");
+    }
+
+    #[test]
+    fn test_fixed_size_frames() {
+        let config = FdSourceConfig::default();
+        let (rx, mut writer) = pipe();
+        let mut source = FdSource::fixed_size(FdTarget::Fd(rx), 3).unwrap();
+
+        writer.write_all(&[1, 2, 3, 4, 5]).unwrap();
+        assert_eq!(step(&mut source, &config).1, vec![vec![1, 2, 3]]);
+
+        writer.write_all(&[6, 7, 8, 9]).unwrap();
+        assert_eq!(
+            step(&mut source, &config).1,
+            vec![vec![4, 5, 6], vec![7, 8, 9]]
+        );
+    }
+
+    #[test]
+    fn test_custom_parser_with_byte_budget() {
+        let config = FdSourceConfig {
+            max_step_bytes: 4,
+            ..Default::default()
+        };
+        let (rx, mut writer) = pipe();
+
+        // length-prefixed frames
+        let mut source = FdSource::with_parser(FdTarget::Fd(rx), |buffer: &mut Vec<u8>| {
+            let len = *buffer.first()? as usize;
+            (buffer.len() > len).then(|| buffer.drain(..=len).skip(1).collect::<Vec<u8>>())
+        })
+        .unwrap();
+
+        writer.write_all(&[2, 10, 11, 3, 20, 21, 22]).unwrap();
+        assert_eq!(
+            step(&mut source, &config),
+            (ReadResult::Data(4), vec![vec![10, 11]])
+        );
+        assert_eq!(
+            step(&mut source, &config),
+            (ReadResult::Data(3), vec![vec![20, 21, 22]])
+        );
+    }
+
+    #[test]
+    fn test_wait_is_bounded() {
+        let config = FdSourceConfig {
+            max_step_duration: Duration::from_millis(20),
+            ..Default::default()
+        };
+        let (rx, _writer) = pipe();
+        let mut source = FdSource::lines(FdTarget::Fd(rx)).unwrap();
+
+        let t0 = Instant::now();
+        assert_eq!(step(&mut source, &config), (ReadResult::Data(0), vec![]));
+        let dt = t0.elapsed();
+        assert!(dt >= Duration::from_millis(15) && dt < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_reconnect_resets_retries() {
+        let path = std::env::temp_dir().join(format!("nodo_fd_source_{}", std::process::id()));
+        std::fs::write(&path, b"line\n").unwrap();
+
+        let config = FdSourceConfig {
+            retry_interval: Duration::ZERO,
+            max_retries: Some(1),
+            ..Default::default()
+        };
+        let instance = FdSource::lines(FdTarget::Path(path.clone()))
+            .unwrap()
+            .into_instance("fd_source", config);
+        let mut vise = Vise::new(instance);
+        vise.setup(&mut NodeletSetup {
+            clocks: Clocks::new(),
+            nodelet_id_issue: NodeletId(WorkerId(0), 0),
+        });
+        vise.cycle(Transition::Start).unwrap();
+
+        // every step reaches EOF of the file and reopens it in the next step
+        for _ in 0..5 {
+            vise.cycle(Transition::Step).unwrap();
+            assert_eq!(vise.status().unwrap().label, "received");
+        }
+
+        // once the file is gone the retry limit applies
+        std::fs::remove_file(&path).unwrap();
+        vise.cycle(Transition::Step).unwrap();
+        assert_eq!(vise.status().unwrap().label, "reconnecting");
+        vise.cycle(Transition::Step).unwrap();
+        vise.cycle(Transition::Step).unwrap();
+        assert_eq!(vise.status().unwrap().label, "eof");
+
+        vise.cycle(Transition::Stop).unwrap();
+    }
+}
@@ -0,0 +1,437 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use nodo::prelude::*;
+use nodo_core::{EyreResult, Result, Topic, WithTopic};
+use std::collections::VecDeque;
+
+/// A serialized message tagged with its topic as received by the [FlightRecorder]
+pub type TopicMessage = Message<WithTopic<Vec<u8>>>;
+
+/// Configuration for [FlightRecorder]
+#[derive(Debug, Clone)]
+pub struct FlightRecorderConfig {
+    /// Messages with an acquisition time older than this are evicted. None keeps messages until
+    /// the byte limit is reached.
+    pub window: Option<Duration>,
+
+    /// Maximum number of payload bytes buffered per topic. None only evicts by time.
+    pub max_bytes_per_topic: Option<usize>,
+
+    /// Time to keep recording after a trigger before the dump is written
+    pub post_trigger: Duration,
+}
+
+impl Default for FlightRecorderConfig {
+    fn default() -> Self {
+        Self {
+            window: Some(Duration::from_secs(30)),
+            max_bytes_per_topic: Some(16 * 1024 * 1024),
+            post_trigger: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Control messages for [FlightRecorder]
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlightRecorderCommand {
+    /// Write the buffered window plus the post-trigger duration to the sink
+    Dump { reason: String },
+}
+
+/// Messages captured by a [FlightRecorder] around a trigger
+#[derive(Clone)]
+pub struct FlightDump {
+    /// Reason given by the trigger
+    pub reason: String,
+
+    /// Time at which the trigger was received
+    pub trigger_time: Duration,
+
+    /// Start of the recorded time window (inclusive)
+    pub start: Duration,
+
+    /// End of the recorded time window (inclusive)
+    pub end: Duration,
+
+    /// All messages in the window sorted by acquisition time
+    pub messages: Vec<TopicMessage>,
+}
+
+/// Destination for dumps written by a [FlightRecorder]
+///
+/// There is no MCAP sink yet as nodo_record is currently not part of the workspace. Until then
+/// dumps are persisted by a user provided sink, for example a closure.
+pub trait FlightRecordSink: Send {
+    fn write_dump(&mut self, dump: &FlightDump) -> EyreResult<()>;
+}
+
+impl<F> FlightRecordSink for F
+where
+    F: FnMut(&FlightDump) -> EyreResult<()> + Send,
+{
+    fn write_dump(&mut self, dump: &FlightDump) -> EyreResult<()> {
+        self(dump)
+    }
+}
+
+#[derive(Status)]
+pub enum FlightRecorderStatus {
+    #[default]
+    #[skipped]
+    #[label = "idle"]
+    Idle,
+
+    #[label = "recording"]
+    Recording,
+
+    /// A dump was triggered and the post-trigger duration is being recorded
+    #[label = "triggered"]
+    Triggered,
+
+    /// A dump with the given number of messages was written
+    #[label = "dumped"]
+    Dumped(usize),
+}
+
+#[derive(RxBundleDerive)]
+pub struct FlightRecorderRx {
+    /// Serialized messages to record
+    pub data: DoubleBufferRx<TopicMessage>,
+
+    /// Triggers for dumps
+    pub control: DoubleBufferRx<FlightRecorderCommand>,
+}
+
+/// Keeps the most recent messages of all topics in memory and writes them to a sink on demand
+///
+/// Every topic has its own ring buffer. Messages are evicted oldest first when they fall out of
+/// the configured time window or when the payload of the topic exceeds `max_bytes_per_topic`.
+/// Limits are applied per topic so that a burst on one topic can not push out the history of
+/// other topics. The buffered payload is thus bounded by `topic count * max_bytes_per_topic`.
+///
+/// While a dump is pending, messages inside the dump window are not evicted by time, but the
+/// byte limit still applies.
+///
+/// Time is measured with the acquisition time of messages and compared against the system
+/// monotonic clock.
+pub struct FlightRecorder {
+    sink: Box<dyn FlightRecordSink>,
+    topics: Vec<(Topic, TopicRing)>,
+    pending: Option<PendingDump>,
+}
+
+#[derive(Default)]
+struct TopicRing {
+    messages: VecDeque<TopicMessage>,
+    bytes: usize,
+}
+
+struct PendingDump {
+    reason: String,
+    trigger_time: Duration,
+    start: Duration,
+    end: Duration,
+}
+
+impl FlightRecorder {
+    pub fn new<S: FlightRecordSink + 'static>(sink: S) -> Self {
+        Self {
+            sink: Box::new(sink),
+            topics: Vec::new(),
+            pending: None,
+        }
+    }
+
+    /// Total number of payload bytes currently buffered
+    pub fn buffered_bytes(&self) -> usize {
+        self.topics.iter().map(|(_, ring)| ring.bytes).sum()
+    }
+
+    /// Total number of messages currently buffered
+    pub fn buffered_count(&self) -> usize {
+        self.topics
+            .iter()
+            .map(|(_, ring)| ring.messages.len())
+            .sum()
+    }
+
+    fn push(&mut self, message: TopicMessage, config: &FlightRecorderConfig) {
+        let ring = match self
+            .topics
+            .iter()
+            .position(|(topic, _)| *topic == message.value.topic)
+        {
+            Some(index) => &mut self.topics[index].1,
+            None => {
+                self.topics
+                    .push((message.value.topic.clone(), TopicRing::default()));
+                &mut self.topics.last_mut().unwrap().1
+            }
+        };
+
+        ring.bytes += message.value.value.len();
+        ring.messages.push_back(message);
+
+        if let Some(max_bytes) = config.max_bytes_per_topic {
+            while ring.bytes > max_bytes {
+                ring.pop_front();
+            }
+        }
+    }
+
+    fn evict(&mut self, now: Duration, config: &FlightRecorderConfig) {
+        let Some(window) = config.window else {
+            return;
+        };
+
+        let mut cutoff = now.saturating_sub(window);
+        if let Some(pending) = self.pending.as_ref() {
+            cutoff = cutoff.min(pending.start);
+        }
+
+        for (_, ring) in self.topics.iter_mut() {
+            while ring
+                .messages
+                .front()
+                .is_some_and(|msg| *msg.stamp.acqtime < cutoff)
+            {
+                ring.pop_front();
+            }
+        }
+    }
+
+    fn trigger(&mut self, reason: String, now: Duration, config: &FlightRecorderConfig) {
+        if let Some(pending) = self.pending.as_ref() {
+            log::info!(
+                "ignoring flight recorder trigger '{reason}' while dump '{}' is pending",
+                pending.reason
+            );
+            return;
+        }
+
+        self.pending = Some(PendingDump {
+            reason,
+            trigger_time: now,
+            start: config
+                .window
+                .map_or(Duration::ZERO, |window| now.saturating_sub(window)),
+            end: now + config.post_trigger,
+        });
+    }
+
+    /// Takes the pending dump if its post-trigger duration has passed
+    fn take_due_dump(&mut self, now: Duration) -> Option<FlightDump> {
+        if self.pending.as_ref()?.end > now {
+            return None;
+        }
+        let pending = self.pending.take()?;
+
+        let mut messages: Vec<TopicMessage> = self
+            .topics
+            .iter()
+            .flat_map(|(_, ring)| ring.messages.iter())
+            .filter(|msg| (pending.start..=pending.end).contains(&*msg.stamp.acqtime))
+            .cloned()
+            .collect();
+        messages.sort_by_key(|msg| msg.stamp.acqtime);
+
+        Some(FlightDump {
+            reason: pending.reason,
+            trigger_time: pending.trigger_time,
+            start: pending.start,
+            end: pending.end,
+            messages,
+        })
+    }
+}
+
+impl TopicRing {
+    fn pop_front(&mut self) {
+        if let Some(msg) = self.messages.pop_front() {
+            self.bytes -= msg.value.value.len();
+        }
+    }
+}
+
+impl Codelet for FlightRecorder {
+    type Status = FlightRecorderStatus;
+    type Config = FlightRecorderConfig;
+    type Rx = FlightRecorderRx;
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (
+            FlightRecorderRx {
+                data: DoubleBufferRx::new_auto_size(),
+                control: DoubleBufferRx::new_auto_size(),
+            },
+            (),
+        )
+    }
+
+    fn step(
+        &mut self,
+        cx: &Context<Self>,
+        rx: &mut Self::Rx,
+        _tx: &mut Self::Tx,
+    ) -> Result<FlightRecorderStatus> {
+        let now = *cx.clocks.sys_mono.now();
+
+        while let Some(FlightRecorderCommand::Dump { reason }) = rx.control.try_pop() {
+            self.trigger(reason, now, cx.config);
+        }
+
+        let received = !rx.data.is_empty();
+        while let Some(msg) = rx.data.try_pop() {
+            self.push(msg, cx.config);
+        }
+
+        self.evict(now, cx.config);
+
+        if let Some(dump) = self.take_due_dump(now) {
+            let count = dump.messages.len();
+            log::info!(
+                "writing flight recorder dump '{}' with {count} messages",
+                dump.reason
+            );
+            if let Err(err) = self.sink.write_dump(&dump) {
+                log::error!("error writing flight recorder dump: {err:?}");
+            }
+            return Ok(FlightRecorderStatus::Dumped(count));
+        }
+
+        Ok(if self.pending.is_some() {
+            FlightRecorderStatus::Triggered
+        } else if received {
+            FlightRecorderStatus::Recording
+        } else {
+            FlightRecorderStatus::Idle
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FlightDump, FlightRecorder, FlightRecorderConfig, TopicMessage};
+    use core::time::Duration;
+    use nodo::prelude::*;
+    use nodo_core::WithTopic;
+    use std::sync::{Arc, Mutex};
+
+    fn ms(x: u64) -> Duration {
+        Duration::from_millis(x)
+    }
+
+    fn message(topic: &str, time: Duration, len: usize) -> TopicMessage {
+        Message {
+            seq: 0,
+            stamp: Stamp {
+                acqtime: time.into(),
+                pubtime: time.into(),
+            },
+            value: WithTopic {
+                topic: topic.into(),
+                value: vec![0; len],
+            },
+        }
+    }
+
+    fn recorder() -> (FlightRecorder, Arc<Mutex<Vec<FlightDump>>>) {
+        let dumps = Arc::new(Mutex::new(Vec::new()));
+        let sink = {
+            let dumps = dumps.clone();
+            move |dump: &FlightDump| {
+                dumps.lock().unwrap().push(dump.clone());
+                Ok(())
+            }
+        };
+        (FlightRecorder::new(sink), dumps)
+    }
+
+    fn times(dump: &FlightDump) -> Vec<Duration> {
+        dump.messages.iter().map(|m| *m.stamp.acqtime).collect()
+    }
+
+    #[test]
+    fn test_dump_contains_window_and_post_trigger() {
+        let config = FlightRecorderConfig {
+            window: Some(ms(300)),
+            max_bytes_per_topic: None,
+            post_trigger: ms(100),
+        };
+        let (mut rec, _) = recorder();
+
+        for t in (0..=1000).step_by(100) {
+            rec.push(message("a", ms(t), 10), &config);
+            rec.evict(ms(t), &config);
+        }
+        assert_eq!(rec.buffered_count(), 4);
+
+        rec.trigger("fault".into(), ms(1000), &config);
+        assert!(rec.take_due_dump(ms(1050)).is_none());
+
+        for t in [1100, 1200] {
+            rec.push(message("a", ms(t), 10), &config);
+            rec.push(message("b", ms(t - 50), 10), &config);
+            rec.evict(ms(t), &config);
+        }
+
+        let dump = rec.take_due_dump(ms(1200)).unwrap();
+        assert_eq!(dump.reason, "fault");
+        assert_eq!(dump.trigger_time, ms(1000));
+        assert_eq!((dump.start, dump.end), (ms(700), ms(1100)));
+        assert_eq!(
+            times(&dump),
+            vec![ms(700), ms(800), ms(900), ms(1000), ms(1050), ms(1100)]
+        );
+        assert!(rec.take_due_dump(ms(2000)).is_none());
+    }
+
+    #[test]
+    fn test_pending_dump_protects_window_from_time_eviction() {
+        let config = FlightRecorderConfig {
+            window: Some(ms(100)),
+            max_bytes_per_topic: None,
+            post_trigger: ms(500),
+        };
+        let (mut rec, _) = recorder();
+
+        rec.push(message("a", ms(950), 1), &config);
+        rec.trigger("manual".into(), ms(1000), &config);
+        rec.trigger("ignored".into(), ms(1200), &config);
+
+        rec.push(message("a", ms(1400), 1), &config);
+        rec.evict(ms(1500), &config);
+
+        let dump = rec.take_due_dump(ms(1500)).unwrap();
+        assert_eq!(dump.reason, "manual");
+        assert_eq!(times(&dump), vec![ms(950), ms(1400)]);
+
+        // without a pending dump the old message is evicted again
+        rec.evict(ms(1500), &config);
+        assert_eq!(rec.buffered_count(), 1);
+    }
+
+    #[test]
+    fn test_byte_limit_is_per_topic() {
+        let config = FlightRecorderConfig {
+            window: None,
+            max_bytes_per_topic: Some(100),
+            post_trigger: Duration::ZERO,
+        };
+        let (mut rec, _) = recorder();
+
+        rec.push(message("quiet", ms(0), 40), &config);
+
+        // burst on a single topic
+        for t in 1..=50 {
+            rec.push(message("burst", ms(t), 30), &config);
+        }
+        assert_eq!(rec.buffered_bytes(), 40 + 90);
+
+        rec.trigger("burst".into(), ms(50), &config);
+        let dump = rec.take_due_dump(ms(50)).unwrap();
+        assert_eq!(times(&dump), vec![ms(0), ms(48), ms(49), ms(50)]);
+    }
+}
@@ -3,6 +3,7 @@
 use core::marker::PhantomData;
 use nodo::{channels::SyncResult, prelude::*};
 use nodo_core::{Outcome, SUCCESS};
+use std::borrow::Cow;
 
 #[derive(Default)]
 pub struct JoinConfig {
@@ -71,9 +72,9 @@ impl<T: Send + Sync> nodo::channels::RxBundle for JoinRx<T> {
         self.inputs.len()
     }
 
-    fn name(&self, index: usize) -> String {
+    fn name(&self, index: usize) -> Cow<'static, str> {
         if index < self.inputs.len() {
-            format!("input_{index}")
+            Cow::Owned(format!("input_{index}"))
         } else {
             panic!(
                 "invalid index '{index}': number of inputs is {}",
@@ -95,4 +96,26 @@ impl<T: Send + Sync> nodo::channels::RxBundle for JoinRx<T> {
         }
         cc
     }
+
+    fn message_type_hash(&self, index: usize) -> Option<u64> {
+        Rx::message_type_hash(&self.inputs[index])
+    }
+
+    fn queue_len(&self, index: usize) -> Option<usize> {
+        Rx::queue_len(&self.inputs[index])
+    }
+
+    fn take_backlog(&mut self, index: usize) -> Option<nodo::channels::Backlog>
+    where
+        Self: 'static,
+    {
+        Rx::take_backlog(&mut self.inputs[index])
+    }
+
+    fn tap_point(&self, index: usize) -> Option<std::sync::Arc<dyn nodo::channels::TapPoint>>
+    where
+        Self: 'static,
+    {
+        Rx::tap_point(&self.inputs[index])
+    }
 }
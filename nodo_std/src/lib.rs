@@ -1,35 +1,69 @@
 // Copyright 2023 by David Weikersdorfer. All rights reserved.
 
+mod background_io;
+mod batch;
+mod blackhole;
+mod bus;
 mod cloner;
 mod convert;
 mod deserializer;
+mod enum_split;
+#[cfg(unix)]
+mod fd_source;
+mod flight_recorder;
 mod identity;
 mod join;
+mod link_emulator;
+mod lockstep;
 mod log;
+mod merge;
 mod multiplexer;
 mod null_rx;
 mod null_tx;
+mod pid_controller;
 mod pipe;
+mod reorder;
 mod serializer;
 mod sink;
 mod source;
+mod stages;
+mod stream_digest;
 mod terminator;
+mod timer;
 mod topic_join;
 mod topic_split;
+mod windowed_stats;
 
+pub use background_io::*;
+pub use batch::*;
+pub use blackhole::*;
+pub use bus::*;
 pub use cloner::*;
 pub use convert::*;
 pub use deserializer::*;
+pub use enum_split::*;
+#[cfg(unix)]
+pub use fd_source::*;
+pub use flight_recorder::*;
 pub use identity::*;
 pub use join::*;
+pub use link_emulator::*;
+pub use lockstep::*;
 pub use log::*;
+pub use merge::*;
 pub use multiplexer::*;
 pub use null_rx::*;
 pub use null_tx::*;
+pub use pid_controller::*;
 pub use pipe::*;
+pub use reorder::*;
 pub use serializer::*;
 pub use sink::*;
 pub use source::*;
+pub use stages::*;
+pub use stream_digest::*;
 pub use terminator::*;
+pub use timer::*;
 pub use topic_join::*;
 pub use topic_split::*;
+pub use windowed_stats::*;
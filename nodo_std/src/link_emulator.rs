@@ -0,0 +1,450 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::ByteSize;
+use core::time::Duration;
+use nodo::prelude::*;
+use std::collections::VecDeque;
+
+/// Configuration for [LinkEmulator]
+#[derive(Debug, Clone)]
+pub struct LinkEmulatorConfig {
+    /// Base delay added to every message
+    pub latency: Duration,
+
+    /// The delay of every message is shifted by a uniformly random value in [-jitter, +jitter]
+    pub jitter: Duration,
+
+    /// Bandwidth of the link. None for an unlimited bandwidth.
+    pub bandwidth_bytes_per_sec: Option<u64>,
+
+    /// Maximum number of bytes which can be sent back-to-back after the link was idle. Messages
+    /// larger than the burst are sent once the bucket is full.
+    pub burst_bytes: u64,
+
+    /// Probability in [0, 1] that a message is lost
+    pub loss_prob: f32,
+
+    /// Seed for the random loss and jitter. The same seed produces the same link behavior.
+    pub seed: u64,
+
+    /// Maximum number of messages waiting for bandwidth. Messages arriving at a full queue are
+    /// lost.
+    pub queue_capacity: usize,
+}
+
+impl Default for LinkEmulatorConfig {
+    fn default() -> Self {
+        Self {
+            latency: Duration::ZERO,
+            jitter: Duration::ZERO,
+            bandwidth_bytes_per_sec: None,
+            burst_bytes: 1500,
+            loss_prob: 0.0,
+            seed: 0,
+            queue_capacity: 64,
+        }
+    }
+}
+
+/// Statistics of a [LinkEmulator]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LinkEmulatorStats {
+    /// Number of messages received from the input
+    pub received: u64,
+
+    /// Number of messages which were delivered to the output
+    pub delivered: u64,
+
+    /// Number of messages lost randomly
+    pub lost_random: u64,
+
+    /// Number of messages lost because the bandwidth queue was full
+    pub lost_overflow: u64,
+
+    /// Total payload size of delivered messages
+    pub bytes_delivered: u64,
+
+    /// Smallest and largest delay induced on a delivered message
+    pub min_delay: Option<Duration>,
+    pub max_delay: Option<Duration>,
+
+    /// Sum of delays induced on delivered messages
+    pub total_delay: Duration,
+}
+
+impl LinkEmulatorStats {
+    /// Total number of lost messages
+    pub fn lost(&self) -> u64 {
+        self.lost_random + self.lost_overflow
+    }
+
+    /// Average delay induced on delivered messages
+    pub fn mean_delay(&self) -> Option<Duration> {
+        (self.delivered > 0).then(|| self.total_delay / self.delivered as u32)
+    }
+
+    fn record_delay(&mut self, delay: Duration) {
+        self.min_delay = Some(self.min_delay.map_or(delay, |d| d.min(delay)));
+        self.max_delay = Some(self.max_delay.map_or(delay, |d| d.max(delay)));
+        self.total_delay += delay;
+    }
+}
+
+#[derive(TxBundleDerive)]
+pub struct LinkEmulatorTx<T: Clone + Send + Sync> {
+    /// Messages which made it through the link
+    pub output: DoubleBufferTx<Message<T>>,
+
+    /// Statistics published after every step in which messages were received or delivered
+    pub stats: DoubleBufferTx<LinkEmulatorStats>,
+}
+
+/// Bytes which may be sent at a given time. Refills with the bandwidth up to the burst size.
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    time: Duration,
+}
+
+impl TokenBucket {
+    fn new(bytes_per_sec: u64, capacity: u64) -> Self {
+        Self {
+            rate: bytes_per_sec as f64,
+            capacity: capacity as f64,
+            tokens: capacity as f64,
+            time: Duration::ZERO,
+        }
+    }
+
+    /// Tokens available at the given time which must not be before the last take
+    fn tokens_at(&self, time: Duration) -> f64 {
+        let elapsed = time.saturating_sub(self.time).as_secs_f64();
+        (self.tokens + elapsed * self.rate).min(self.capacity)
+    }
+
+    /// Earliest time at or after `time` at which a message of the given size can be sent
+    fn available_at(&self, time: Duration, size: usize) -> Duration {
+        let time = time.max(self.time);
+        let required = (size as f64).min(self.capacity);
+        let missing = required - self.tokens_at(time);
+        if missing <= 0.0 {
+            time
+        } else {
+            time + Duration::from_secs_f64(missing / self.rate)
+        }
+    }
+
+    /// Sends a message at the given time. Large messages leave the bucket in debt.
+    fn take(&mut self, time: Duration, size: usize) {
+        self.tokens = self.tokens_at(time) - size as f64;
+        self.time = self.time.max(time);
+    }
+}
+
+struct Queued<T> {
+    arrival: Duration,
+    msg: Message<T>,
+}
+
+/// Emulates a constrained link, e.g. a radio, on a channel without changing the codelets on
+/// either side
+///
+/// Messages are randomly lost, wait in a bounded queue for bandwidth, and are delivered after the
+/// latency plus random jitter. Messages are always delivered in the order in which they were
+/// received. All randomness is derived from the configured seed. Times are measured with the app
+/// clock and messages are released in the first step after their delivery time.
+pub struct LinkEmulator<T> {
+    rng: u64,
+    bucket: Option<TokenBucket>,
+    queue: VecDeque<Queued<T>>,
+    in_flight: VecDeque<(Duration, Queued<T>)>,
+    last_delivery: Duration,
+    stats: LinkEmulatorStats,
+}
+
+impl<T> Default for LinkEmulator<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> LinkEmulator<T> {
+    pub fn new() -> Self {
+        Self {
+            rng: 0,
+            bucket: None,
+            queue: VecDeque::new(),
+            in_flight: VecDeque::new(),
+            last_delivery: Duration::ZERO,
+            stats: LinkEmulatorStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> &LinkEmulatorStats {
+        &self.stats
+    }
+
+    fn reset(&mut self, config: &LinkEmulatorConfig) {
+        assert!(
+            (0.0..=1.0).contains(&config.loss_prob),
+            "loss_prob must be in [0, 1]"
+        );
+
+        // xorshift must not be seeded with zero
+        self.rng = config.seed ^ 0x9E37_79B9_7F4A_7C15;
+        self.bucket = config
+            .bandwidth_bytes_per_sec
+            .map(|rate| TokenBucket::new(rate.max(1), config.burst_bytes.max(1)));
+        self.queue.clear();
+        self.in_flight.clear();
+        self.last_delivery = Duration::ZERO;
+        self.stats = LinkEmulatorStats::default();
+    }
+
+    /// Uniform random number in [0, 1)
+    fn random(&mut self) -> f64 {
+        // xorshift64
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Latency with random jitter
+    fn sample_delay(&mut self, config: &LinkEmulatorConfig) -> Duration {
+        if config.jitter.is_zero() {
+            return config.latency;
+        }
+        let offset = config.jitter.mul_f64(2.0 * self.random());
+        (config.latency + offset).saturating_sub(config.jitter)
+    }
+
+    /// Receives a message at the given time. Returns false if it was lost.
+    fn push(&mut self, now: Duration, msg: Message<T>, config: &LinkEmulatorConfig) -> bool {
+        self.stats.received += 1;
+
+        if config.loss_prob > 0.0 && self.random() < config.loss_prob as f64 {
+            self.stats.lost_random += 1;
+            return false;
+        }
+
+        if self.queue.len() >= config.queue_capacity {
+            self.stats.lost_overflow += 1;
+            return false;
+        }
+
+        self.queue.push_back(Queued { arrival: now, msg });
+        true
+    }
+
+    /// Removes all messages which are delivered at or before the given time
+    fn deliver(&mut self, now: Duration, config: &LinkEmulatorConfig) -> Vec<Message<T>>
+    where
+        T: ByteSize,
+    {
+        // send queued messages for which bandwidth is available
+        while let Some(head) = self.queue.front() {
+            let size = head.msg.byte_size();
+            let departure = match self.bucket.as_ref() {
+                Some(bucket) => bucket.available_at(head.arrival, size),
+                None => head.arrival,
+            };
+            if departure > now {
+                break;
+            }
+            if let Some(bucket) = self.bucket.as_mut() {
+                bucket.take(departure, size);
+            }
+
+            // a message never overtakes a message sent before it
+            let delivery = (departure + self.sample_delay(config)).max(self.last_delivery);
+            self.last_delivery = delivery;
+
+            let queued = self.queue.pop_front().unwrap();
+            self.in_flight.push_back((delivery, queued));
+        }
+
+        let mut delivered = Vec::new();
+        while self
+            .in_flight
+            .front()
+            .is_some_and(|(delivery, _)| *delivery <= now)
+        {
+            let (delivery, queued) = self.in_flight.pop_front().unwrap();
+            self.stats.delivered += 1;
+            self.stats.bytes_delivered += queued.msg.byte_size() as u64;
+            self.stats.record_delay(delivery - queued.arrival);
+            delivered.push(queued.msg);
+        }
+        delivered
+    }
+}
+
+impl<T: ByteSize + Send + Sync + Clone> Codelet for LinkEmulator<T> {
+    type Status = DefaultStatus;
+    type Config = LinkEmulatorConfig;
+    type Rx = DoubleBufferRx<Message<T>>;
+    type Tx = LinkEmulatorTx<T>;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (
+            DoubleBufferRx::new_auto_size(),
+            LinkEmulatorTx {
+                output: DoubleBufferTx::new_auto_size(),
+                stats: DoubleBufferTx::new(1),
+            },
+        )
+    }
+
+    fn start(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.reset(cx.config);
+        SUCCESS
+    }
+
+    fn step(&mut self, cx: &Context<Self>, rx: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        let now = *cx.clocks.app_mono.now();
+
+        let received = !rx.is_empty();
+        while let Some(msg) = rx.try_pop() {
+            self.push(now, msg, cx.config);
+        }
+
+        let delivered = self.deliver(now, cx.config);
+        if !received && delivered.is_empty() {
+            return SKIPPED;
+        }
+
+        tx.output.push_many(delivered)?;
+        tx.stats.push(self.stats.clone())?;
+        SUCCESS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TokenBucket;
+    use crate::{LinkEmulator, LinkEmulatorConfig};
+    use core::time::Duration;
+    use nodo::prelude::*;
+
+    fn ms(x: u64) -> Duration {
+        Duration::from_millis(x)
+    }
+
+    fn msg(seq: u64, size: usize) -> Message<Vec<u8>> {
+        Message {
+            seq,
+            stamp: Stamp {
+                acqtime: ms(seq).into(),
+                pubtime: ms(seq).into(),
+            },
+            value: vec![0; size],
+        }
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        // 1000 bytes/s with a burst of 100 bytes
+        let mut bucket = TokenBucket::new(1000, 100);
+
+        // a full bucket sends right away
+        assert_eq!(bucket.available_at(ms(0), 100), ms(0));
+        bucket.take(ms(0), 100);
+
+        // 50 bytes take 50ms to refill
+        assert_eq!(bucket.available_at(ms(0), 50), ms(50));
+        assert_eq!(bucket.available_at(ms(20), 50), ms(50));
+        bucket.take(ms(50), 50);
+        assert_eq!(bucket.tokens_at(ms(50)), 0.0);
+
+        // the bucket does not fill beyond its capacity
+        assert_eq!(bucket.tokens_at(ms(1000)), 100.0);
+
+        // a message larger than the burst waits for a full bucket and leaves it in debt
+        assert_eq!(bucket.available_at(ms(50), 300), ms(150));
+        bucket.take(ms(150), 300);
+        assert_eq!(bucket.available_at(ms(150), 10), ms(360));
+    }
+
+    #[test]
+    fn test_bandwidth() {
+        let config = LinkEmulatorConfig {
+            bandwidth_bytes_per_sec: Some(10_000),
+            burst_bytes: 100,
+            queue_capacity: 5,
+            ..Default::default()
+        };
+        let mut link = LinkEmulator::new();
+        link.reset(&config);
+
+        // 8 messages of 100 bytes at once: the first is sent right away, the others every 10ms,
+        // and 3 overflow the queue
+        for seq in 0..8 {
+            link.push(ms(0), msg(seq, 100), &config);
+        }
+        assert_eq!(link.stats().lost_overflow, 3);
+
+        let mut delivered = Vec::new();
+        for t in 0..100 {
+            for m in link.deliver(ms(t), &config) {
+                delivered.push((m.seq, t));
+            }
+        }
+        assert_eq!(delivered, [(0, 0), (1, 10), (2, 20), (3, 30), (4, 40)]);
+        assert_eq!(link.stats().bytes_delivered, 500);
+        assert_eq!(link.stats().max_delay, Some(ms(40)));
+    }
+
+    #[test]
+    fn test_latency_and_jitter() {
+        let config = LinkEmulatorConfig {
+            latency: ms(50),
+            jitter: ms(10),
+            loss_prob: 0.2,
+            seed: 3,
+            queue_capacity: 1000,
+            ..Default::default()
+        };
+
+        let run = || {
+            let mut link = LinkEmulator::new();
+            link.reset(&config);
+            let mut delivered = Vec::new();
+            // messages are sent further apart than the jitter such that ordering does not add
+            // to the delay
+            for t in 0..26_000 {
+                if t < 25_000 && t % 25 == 0 {
+                    link.push(ms(t), msg(t, 10), &config);
+                }
+                for m in link.deliver(ms(t), &config) {
+                    delivered.push((m.seq, t));
+                }
+            }
+            (link.stats().clone(), delivered)
+        };
+
+        let (stats, delivered) = run();
+        assert_eq!(stats.received, 1000);
+        assert_eq!(stats.delivered + stats.lost(), 1000);
+        assert!((150..250).contains(&stats.lost_random), "{stats:?}");
+
+        // delivery is ordered and delayed by latency +- jitter
+        assert!(delivered.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(stats.min_delay.unwrap() >= ms(40), "{stats:?}");
+        assert!(stats.max_delay.unwrap() <= ms(60), "{stats:?}");
+        for (seq, t) in delivered.iter() {
+            let delay = ms(t - seq);
+            assert!(ms(40) <= delay && delay <= ms(61), "{delay:?}");
+        }
+
+        // the jitter spreads delays over the whole range
+        let mean = stats.mean_delay().unwrap();
+        assert!(ms(48) <= mean && mean <= ms(52), "{mean:?}");
+        assert!(stats.max_delay.unwrap() - stats.min_delay.unwrap() > ms(15));
+
+        // the same seed results in the same behavior
+        assert_eq!(run(), (stats, delivered));
+    }
+}
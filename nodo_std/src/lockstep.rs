@@ -0,0 +1,397 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use nodo::prelude::*;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+/// Configuration for lockstep coupling with an external simulator
+#[derive(Debug, Clone)]
+pub struct LockstepConfig {
+    /// Simulated time per tick. A manual app clock is advanced by this amount every tick.
+    pub dt: Duration,
+
+    /// Maximum time the [LockstepGate] waits for the inputs of the next tick
+    pub timeout: Duration,
+}
+
+/// Errors of the lockstep protocol
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum LockstepError {
+    #[error("simulator stalled: no inputs for tick {tick} within {timeout:?}")]
+    SimulatorStalled { tick: u64, timeout: Duration },
+
+    #[error("nodo stalled: no outputs for tick {tick} within {timeout:?}")]
+    GraphStalled { tick: u64, timeout: Duration },
+
+    #[error("unexpected tick {actual}: expected tick {expected}")]
+    UnexpectedTick { expected: u64, actual: u64 },
+
+    #[error("outputs of tick {tick} were not consumed")]
+    OutputsPending { tick: u64 },
+
+    #[error("lockstep codelets stopped")]
+    Stopped,
+}
+
+/// API for the external side of a lockstep coupling, e.g. a physics simulator
+///
+/// Every tick `k` the simulator submits its inputs with [LockstepHandle::submit_inputs], the
+/// [LockstepGate] publishes them into the graph, the [LockstepSink] collects the outputs produced
+/// in the same schedule cycle, and the simulator receives them with
+/// [LockstepHandle::wait_outputs] before it advances. Ticks start at 0. The gate must be the first
+/// and the sink the last codelet in the sequence.
+pub struct LockstepHandle<I, O> {
+    shared: Arc<LockstepShared<I, O>>,
+}
+
+struct LockstepShared<I, O> {
+    config: LockstepConfig,
+    state: Mutex<LockstepState<I, O>>,
+    condvar: Condvar,
+}
+
+struct LockstepState<I, O> {
+    /// Inputs submitted for the next tick
+    inputs: Option<(u64, Vec<I>)>,
+
+    /// Outputs of the last released tick
+    outputs: Option<(u64, Vec<O>)>,
+
+    /// Number of ticks for which inputs were submitted
+    submitted: u64,
+
+    /// Number of ticks released into the graph by the gate
+    released: u64,
+
+    /// Number of ticks for which outputs were consumed
+    acknowledged: u64,
+
+    is_stopped: bool,
+}
+
+impl<I, O> LockstepShared<I, O> {
+    fn lock(&self) -> MutexGuard<'_, LockstepState<I, O>> {
+        self.state.lock().unwrap()
+    }
+
+    fn stop(&self) {
+        self.lock().is_stopped = true;
+        self.condvar.notify_all();
+    }
+}
+
+impl<I, O> LockstepHandle<I, O> {
+    pub fn new(config: LockstepConfig) -> Self {
+        Self {
+            shared: Arc::new(LockstepShared {
+                config,
+                state: Mutex::new(LockstepState {
+                    inputs: None,
+                    outputs: None,
+                    submitted: 0,
+                    released: 0,
+                    acknowledged: 0,
+                    is_stopped: false,
+                }),
+                condvar: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Creates the codelet which publishes the inputs into the graph
+    pub fn gate(&self) -> LockstepGate<I, O> {
+        LockstepGate {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Creates the codelet which collects the outputs of the graph
+    pub fn sink(&self) -> LockstepSink<I, O> {
+        LockstepSink {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Ends the coupling. The gate stops waiting for inputs and skips all further steps. Called
+    /// when the handle is dropped.
+    pub fn stop(&self) {
+        self.shared.stop();
+    }
+
+    /// Number of ticks for which outputs were consumed
+    pub fn tick_count(&self) -> u64 {
+        self.shared.lock().acknowledged
+    }
+
+    /// Submits the inputs for tick `tick`. The outputs of the previous tick must have been consumed.
+    pub fn submit_inputs(&self, tick: u64, inputs: Vec<I>) -> Result<(), LockstepError> {
+        let mut state = self.shared.lock();
+        if state.is_stopped {
+            return Err(LockstepError::Stopped);
+        }
+        if tick != state.submitted {
+            return Err(LockstepError::UnexpectedTick {
+                expected: state.submitted,
+                actual: tick,
+            });
+        }
+        if state.acknowledged != tick {
+            return Err(LockstepError::OutputsPending {
+                tick: state.acknowledged,
+            });
+        }
+
+        state.inputs = Some((tick, inputs));
+        state.submitted += 1;
+        self.shared.condvar.notify_all();
+        Ok(())
+    }
+
+    /// Waits until the graph produced the outputs for tick `tick` and consumes them
+    pub fn wait_outputs(&self, tick: u64, timeout: Duration) -> Result<Vec<O>, LockstepError> {
+        let state = self.shared.lock();
+        if tick != state.acknowledged || tick >= state.submitted {
+            return Err(LockstepError::UnexpectedTick {
+                expected: state.acknowledged,
+                actual: tick,
+            });
+        }
+
+        let (mut state, result) = self
+            .shared
+            .condvar
+            .wait_timeout_while(state, timeout, |state| {
+                !state.is_stopped && !matches!(state.outputs, Some((k, _)) if k == tick)
+            })
+            .unwrap();
+
+        match state.outputs.take() {
+            Some((k, outputs)) if k == tick => {
+                state.acknowledged += 1;
+                self.shared.condvar.notify_all();
+                Ok(outputs)
+            }
+            outputs => {
+                state.outputs = outputs;
+                if result.timed_out() {
+                    Err(LockstepError::GraphStalled { tick, timeout })
+                } else {
+                    Err(LockstepError::Stopped)
+                }
+            }
+        }
+    }
+}
+
+impl<I, O> Drop for LockstepHandle<I, O> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Publishes the inputs submitted with [LockstepHandle::submit_inputs]
+///
+/// Every step blocks until the inputs of the next tick were submitted. If the app clock is manual
+/// it is advanced by `dt` before the inputs are published such that app time is `(k + 1) * dt`
+/// during tick `k`. Messages are stamped with the current app time and the tick index as sequence
+/// number. Fails if no inputs arrive within the configured timeout. After the coupling was
+/// stopped steps are skipped.
+pub struct LockstepGate<I, O> {
+    shared: Arc<LockstepShared<I, O>>,
+}
+
+impl<I, O> Codelet for LockstepGate<I, O>
+where
+    I: Clone + Send + Sync,
+    O: Send,
+{
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = ();
+    type Tx = DoubleBufferTx<Message<I>>;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), DoubleBufferTx::new_auto_size())
+    }
+
+    fn step(&mut self, cx: &Context<Self>, _: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        let timeout = self.shared.config.timeout;
+
+        let (tick, inputs) = {
+            let state = self.shared.lock();
+            let tick = state.released;
+            let (mut state, _) = self
+                .shared
+                .condvar
+                .wait_timeout_while(state, timeout, |state| {
+                    !state.is_stopped && !matches!(state.inputs, Some((k, _)) if k == tick)
+                })
+                .unwrap();
+
+            let Some((_, inputs)) = state.inputs.take() else {
+                if state.is_stopped {
+                    return cx.skip_because(SkipReason::Custom("lockstep stopped"));
+                }
+                return Err(LockstepError::SimulatorStalled { tick, timeout }.into());
+            };
+            state.released += 1;
+            (tick, inputs)
+        };
+
+        if cx.clocks.app_mono.is_manual() {
+            cx.clocks.app_mono.advance(self.shared.config.dt);
+        }
+
+        let now = cx.clocks.app_mono.now();
+        for value in inputs {
+            tx.push(Message {
+                seq: tick,
+                stamp: Stamp {
+                    acqtime: Duration::from(now).into(),
+                    pubtime: now,
+                },
+                value,
+            })?;
+        }
+
+        SUCCESS
+    }
+
+    fn stop(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.shared.stop();
+        SUCCESS
+    }
+}
+
+/// Collects the outputs of the graph for [LockstepHandle::wait_outputs]
+///
+/// Every step hands all received messages to the external side as the outputs of the last tick
+/// released by the [LockstepGate], even if no message was received.
+pub struct LockstepSink<I, O> {
+    shared: Arc<LockstepShared<I, O>>,
+}
+
+impl<I, O> Codelet for LockstepSink<I, O>
+where
+    I: Send,
+    O: Send + Sync,
+{
+    type Status = DefaultStatus;
+    type Config = ();
+    type Rx = DoubleBufferRx<O>;
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (DoubleBufferRx::new_auto_size(), ())
+    }
+
+    fn step(&mut self, _: &Context<Self>, rx: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        let messages = rx.pop_all().collect::<Vec<_>>();
+
+        let mut state = self.shared.lock();
+        let Some(tick) = state.released.checked_sub(1) else {
+            return SKIPPED;
+        };
+        match &mut state.outputs {
+            Some((k, outputs)) if *k == tick => outputs.extend(messages),
+            outputs => *outputs = Some((tick, messages)),
+        }
+        self.shared.condvar.notify_all();
+
+        SUCCESS
+    }
+
+    fn stop(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.shared.stop();
+        SUCCESS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LockstepConfig, LockstepError, LockstepHandle};
+    use core::time::Duration;
+    use nodo::{
+        codelet::{
+            Clocks, Lifecycle, NodeletId, NodeletSetup, Transition, Vise, ViseTrait, WorkerId,
+        },
+        prelude::*,
+    };
+
+    fn handle(timeout: Duration) -> LockstepHandle<u32, u32> {
+        LockstepHandle::new(LockstepConfig {
+            dt: Duration::from_millis(10),
+            timeout,
+        })
+    }
+
+    #[test]
+    fn test_protocol_errors() {
+        let handle = handle(Duration::from_secs(1));
+
+        assert_eq!(
+            handle.submit_inputs(1, vec![]),
+            Err(LockstepError::UnexpectedTick {
+                expected: 0,
+                actual: 1
+            })
+        );
+        handle.submit_inputs(0, vec![1]).unwrap();
+
+        // outputs of tick 0 must be consumed before tick 1 is submitted
+        assert_eq!(
+            handle.submit_inputs(1, vec![]),
+            Err(LockstepError::OutputsPending { tick: 0 })
+        );
+
+        // no graph is running
+        assert_eq!(
+            handle.wait_outputs(0, Duration::from_millis(10)),
+            Err(LockstepError::GraphStalled {
+                tick: 0,
+                timeout: Duration::from_millis(10)
+            })
+        );
+    }
+
+    #[test]
+    fn test_gate_timeout() {
+        let handle = handle(Duration::from_millis(10));
+
+        let mut vise = Vise::new(handle.gate().into_instance("gate", ()));
+        vise.setup(&mut NodeletSetup {
+            clocks: Clocks::new_manual(),
+            nodelet_id_issue: NodeletId(WorkerId(0), 0),
+        });
+        vise.cycle(Transition::Start).unwrap();
+
+        let err = vise.cycle(Transition::Step).unwrap_err();
+        assert!(format!("{err:?}").contains("simulator stalled: no inputs for tick 0"));
+
+        // the simulator learns that the graph stopped
+        vise.cycle(Transition::Stop).unwrap();
+        assert_eq!(handle.submit_inputs(0, vec![]), Err(LockstepError::Stopped));
+    }
+
+    #[test]
+    fn test_simulator_stop() {
+        let handle = handle(Duration::from_secs(10));
+
+        let mut vise = Vise::new(handle.gate().into_instance("gate", ()));
+        vise.setup(&mut NodeletSetup {
+            clocks: Clocks::new_manual(),
+            nodelet_id_issue: NodeletId(WorkerId(0), 0),
+        });
+        vise.cycle(Transition::Start).unwrap();
+
+        // the gate does not wait for the timeout and does not report a stall
+        handle.submit_inputs(0, vec![1]).unwrap();
+        vise.cycle(Transition::Step).unwrap();
+        let time = std::time::Instant::now();
+        drop(handle);
+        vise.cycle(Transition::Step).unwrap();
+        assert!(time.elapsed() < Duration::from_secs(1));
+
+        vise.cycle(Transition::Stop).unwrap();
+    }
+}
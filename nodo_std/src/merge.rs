@@ -0,0 +1,224 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::marker::PhantomData;
+use nodo::{
+    channels::{SyncResult, TxSendError},
+    prelude::*,
+};
+use nodo_core::{Outcome, SKIPPED, SUCCESS};
+use std::{
+    borrow::Cow,
+    sync::{atomic::AtomicU64, Arc},
+};
+
+#[derive(Default)]
+pub struct MergeConfig {
+    /// Number of inputs created initially. More inputs can be added with
+    /// [MergeRx::new_channel_mut].
+    pub initial_input_count: usize,
+}
+
+/// Merge fans in multiple producers into one consumer
+///
+/// A receiver can only be connected to a single transmitter. To feed multiple producers into one
+/// consumer connect each producer to its own input of the merge and the merge output to the
+/// consumer. Every step all messages received on any input are forwarded in the order in which
+/// they arrived. Unconnected inputs are skipped. The step is skipped if no message was received.
+pub struct Merge<T>(PhantomData<T>);
+
+impl<T: Send + Sync + Clone> Default for Merge<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: Send + Sync + Clone> Codelet for Merge<T> {
+    type Status = DefaultStatus;
+    type Config = MergeConfig;
+    type Rx = MergeRx<T>;
+    type Tx = DoubleBufferTx<T>;
+
+    fn build_bundles(cfg: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (
+            MergeRx::new(cfg.initial_input_count),
+            DoubleBufferTx::new_auto_size(),
+        )
+    }
+
+    fn step(&mut self, _: &Context<Self>, rx: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        if rx.forward(tx)? > 0 {
+            SUCCESS
+        } else {
+            SKIPPED
+        }
+    }
+}
+
+pub struct MergeRx<T> {
+    inputs: Vec<DoubleBufferRx<T>>,
+
+    /// Shared by all inputs to stamp messages with their arrival
+    arrival: Arc<AtomicU64>,
+
+    /// Arrival stamp and input index of received messages
+    order: Vec<(u64, usize)>,
+}
+
+impl<T> MergeRx<T> {
+    pub fn new(count: usize) -> Self {
+        let arrival = Arc::new(AtomicU64::new(0));
+        Self {
+            inputs: (0..count).map(|_| Self::new_input(&arrival)).collect(),
+            arrival,
+            order: Vec::new(),
+        }
+    }
+
+    fn new_input(arrival: &Arc<AtomicU64>) -> DoubleBufferRx<T> {
+        let mut rx = DoubleBufferRx::new_auto_size();
+        rx.enable_arrival_stamps(arrival.clone());
+        rx
+    }
+
+    /// Get the i-th input channel
+    pub fn channel_mut(&mut self, index: usize) -> &mut DoubleBufferRx<T> {
+        &mut self.inputs[index]
+    }
+
+    /// Add a new input channel and return it
+    pub fn new_channel_mut(&mut self) -> &mut DoubleBufferRx<T> {
+        self.inputs.push(Self::new_input(&self.arrival));
+        self.inputs.last_mut().unwrap()
+    }
+}
+
+impl<T: Send + Sync + Clone> MergeRx<T> {
+    /// Number of inputs which are connected to a transmitter
+    pub fn connected_count(&self) -> usize {
+        self.inputs.iter().filter(|rx| rx.is_connected()).count()
+    }
+
+    /// Pushes all received messages to the output in the order in which they arrived and
+    /// returns their count
+    fn forward(&mut self, output: &mut DoubleBufferTx<T>) -> Result<usize, TxSendError> {
+        self.order.clear();
+        for (index, channel) in self.inputs.iter().enumerate() {
+            self.order
+                .extend(channel.arrival_stamps().iter().map(|&stamp| (stamp, index)));
+        }
+        self.order.sort_unstable();
+
+        let mut count = 0;
+        for &(_, index) in self.order.iter() {
+            if let Some(message) = self.inputs[index].try_pop() {
+                output.push(message)?;
+                count += 1;
+            }
+        }
+
+        // messages without stamp, e.g. left over from before the input was added
+        for channel in self.inputs.iter_mut() {
+            for message in channel.pop_all() {
+                output.push(message)?;
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+}
+
+impl<T: Send + Sync> nodo::channels::RxBundle for MergeRx<T> {
+    fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    fn name(&self, index: usize) -> Cow<'static, str> {
+        if index < self.inputs.len() {
+            Cow::Owned(format!("input_{index}"))
+        } else {
+            panic!(
+                "invalid index '{index}': number of inputs is {}",
+                self.inputs.len()
+            )
+        }
+    }
+
+    fn sync_all(&mut self, results: &mut [SyncResult]) {
+        for (i, channel) in self.inputs.iter_mut().enumerate() {
+            results[i] = channel.sync()
+        }
+    }
+
+    fn check_connection(&self) -> nodo::channels::ConnectionCheck {
+        let mut cc = nodo::channels::ConnectionCheck::new(self.inputs.len());
+        for (i, channel) in self.inputs.iter().enumerate() {
+            cc.mark(i, channel.is_connected());
+        }
+        cc
+    }
+
+    fn message_type_hash(&self, index: usize) -> Option<u64> {
+        Rx::message_type_hash(&self.inputs[index])
+    }
+
+    fn queue_len(&self, index: usize) -> Option<usize> {
+        Rx::queue_len(&self.inputs[index])
+    }
+
+    fn take_backlog(&mut self, index: usize) -> Option<nodo::channels::Backlog>
+    where
+        Self: 'static,
+    {
+        Rx::take_backlog(&mut self.inputs[index])
+    }
+
+    fn tap_point(&self, index: usize) -> Option<std::sync::Arc<dyn nodo::channels::TapPoint>>
+    where
+        Self: 'static,
+    {
+        Rx::tap_point(&self.inputs[index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::MergeRx;
+    use nodo::{channels::RxBundle, prelude::*};
+
+    #[test]
+    fn test_forward_in_arrival_order() {
+        let mut rx = MergeRx::<u32>::new(1);
+        let mut a = DoubleBufferTx::new_auto_size();
+        let mut b = DoubleBufferTx::new_auto_size();
+        a.connect(rx.new_channel_mut()).unwrap();
+        b.connect(rx.new_channel_mut()).unwrap();
+        assert_eq!(rx.len(), 3);
+        assert_eq!(rx.connected_count(), 2);
+        assert_eq!(rx.check_connection().list_unconnected(), [0]);
+
+        let mut output = DoubleBufferTx::new_auto_size();
+        let mut consumer = DoubleBufferRx::new_auto_size();
+        output.connect(&mut consumer).unwrap();
+
+        let mut results = vec![Default::default(); rx.len()];
+
+        // nothing is forwarded before messages arrive
+        rx.sync_all(&mut results);
+        assert_eq!(rx.forward(&mut output).unwrap(), 0);
+
+        b.push_many([10, 11]).unwrap();
+        b.flush();
+        a.push_many([1, 2]).unwrap();
+        a.flush();
+        b.push(12).unwrap();
+        b.flush();
+
+        rx.sync_all(&mut results);
+        assert_eq!(rx.forward(&mut output).unwrap(), 5);
+        output.flush();
+
+        consumer.sync();
+        assert_eq!(consumer.pop_all().collect::<Vec<_>>(), [10, 11, 1, 2, 12]);
+    }
+}
@@ -6,6 +6,7 @@ use nodo::{
     prelude::*,
 };
 use nodo_core::{ensure, Outcome, SUCCESS};
+use std::borrow::Cow;
 
 /// A multiplexer has multiple input inputs and a single output channel. Messages received on
 /// the selected input channel are send on the output channel and messages on other inputs are
@@ -69,11 +70,11 @@ impl<T: Send + Sync> nodo::channels::RxBundle for MultiplexerRx<T> {
         self.inputs.len() + 1
     }
 
-    fn name(&self, index: usize) -> String {
+    fn name(&self, index: usize) -> Cow<'static, str> {
         if index < self.inputs.len() {
-            format!("{index}")
+            Cow::Owned(format!("{index}"))
         } else if index == self.inputs.len() {
-            "selection".to_string()
+            Cow::Borrowed("selection")
         } else {
             panic!(
                 "invalid index '{index}': number of inputs is {}",
@@ -97,6 +98,14 @@ impl<T: Send + Sync> nodo::channels::RxBundle for MultiplexerRx<T> {
         cc.mark(self.inputs.len(), self.selection.is_connected());
         cc
     }
+
+    fn message_type_hash(&self, index: usize) -> Option<u64> {
+        if index < self.inputs.len() {
+            Rx::message_type_hash(&self.inputs[index])
+        } else {
+            Rx::message_type_hash(&self.selection)
+        }
+    }
 }
 
 pub struct MultiplexerTx<T> {
@@ -108,9 +117,9 @@ impl<T: Send + Sync + Clone> nodo::channels::TxBundle for MultiplexerTx<T> {
         1
     }
 
-    fn name(&self, index: usize) -> String {
+    fn name(&self, index: usize) -> Cow<'static, str> {
         assert_eq!(index, 0);
-        "output".to_string()
+        Cow::Borrowed("output")
     }
 
     fn flush_all(&mut self, results: &mut [FlushResult]) {
@@ -122,6 +131,11 @@ impl<T: Send + Sync + Clone> nodo::channels::TxBundle for MultiplexerTx<T> {
         cc.mark(0, self.output.is_connected());
         cc
     }
+
+    fn message_type_hash(&self, index: usize) -> Option<u64> {
+        assert_eq!(index, 0);
+        Tx::message_type_hash(&self.output)
+    }
 }
 
 impl<T: Send + Sync + Clone> Codelet for Multiplexer<T> {
@@ -4,6 +4,8 @@ use core::marker::PhantomData;
 use nodo::prelude::*;
 
 /// A codelet which drops all messages it receives.
+///
+/// Use [Blackhole](crate::Blackhole) instead to keep track of how many messages are dropped.
 pub struct NullRx<T>(PhantomData<T>);
 
 impl<T> Default for NullRx<T> {
@@ -4,6 +4,9 @@ use core::marker::PhantomData;
 use nodo::prelude::*;
 
 /// A codelet with a single transmitter which nevers publishes anything.
+///
+/// The counterpart for receivers is [NullRx](crate::NullRx) or [Blackhole](crate::Blackhole) if
+/// discarded messages should be accounted for.
 pub struct NullTx<T>(PhantomData<T>);
 
 impl<T> Default for NullTx<T> {
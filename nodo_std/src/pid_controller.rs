@@ -0,0 +1,416 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use nodo::prelude::*;
+use nodo_core::{ensure, Result};
+
+/// Configuration for [PidController]
+#[derive(Debug, Clone)]
+pub struct PidConfig {
+    /// Proportional gain
+    pub kp: f64,
+
+    /// Integral gain
+    pub ki: f64,
+
+    /// Derivative gain
+    pub kd: f64,
+
+    /// Commands are clamped to [output_min, output_max]
+    pub output_min: f64,
+    pub output_max: f64,
+
+    /// The integrated error is clamped to [-integral_limit, +integral_limit] to avoid windup
+    pub integral_limit: f64,
+
+    /// No command is published while the latest measurement is older than this
+    pub stale_timeout: Duration,
+}
+
+impl Default for PidConfig {
+    fn default() -> Self {
+        Self {
+            kp: 1.0,
+            ki: 0.0,
+            kd: 0.0,
+            output_min: f64::NEG_INFINITY,
+            output_max: f64::INFINITY,
+            integral_limit: f64::INFINITY,
+            stale_timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+impl PidConfig {
+    pub fn validate(&self) -> Result<()> {
+        for (name, gain) in [("kp", self.kp), ("ki", self.ki), ("kd", self.kd)] {
+            ensure!(
+                gain.is_finite() && gain >= 0.0,
+                "{name} must be finite and non-negative: {gain}"
+            );
+        }
+        ensure!(
+            self.output_min < self.output_max,
+            "output_min must be smaller than output_max: [{}, {}]",
+            self.output_min,
+            self.output_max
+        );
+        ensure!(
+            self.integral_limit >= 0.0,
+            "integral_limit must be non-negative: {}",
+            self.integral_limit
+        );
+        ensure!(
+            !self.stale_timeout.is_zero(),
+            "stale_timeout must not be zero"
+        );
+        Ok(())
+    }
+}
+
+#[derive(Status)]
+pub enum PidStatus {
+    /// No setpoint or no measurement received yet
+    #[default]
+    #[skipped]
+    #[label = "waiting"]
+    Waiting,
+
+    #[label = "active"]
+    Active,
+
+    /// The latest measurement is older than the stale timeout. No command is published.
+    #[skipped]
+    #[label = "stale measurement"]
+    StaleMeasurement,
+
+    /// The command was clamped to the output limits
+    #[severity = "warn"]
+    #[label = "saturated"]
+    Saturated,
+}
+
+#[derive(RxBundleDerive)]
+pub struct PidRx {
+    pub setpoint: DoubleBufferRx<Message<f64>>,
+    pub measurement: DoubleBufferRx<Message<f64>>,
+}
+
+/// A PID controller with clamped outputs and anti-windup
+///
+/// Every step the latest setpoint and the latest measurement are used to compute a command. Both
+/// are kept until a newer message arrives. The derivative term is computed on the measurement
+/// instead of the error such that setpoint changes do not cause a kick. The integral is clamped
+/// to `integral_limit` to bound windup while the output is saturated.
+///
+/// Measurements are considered stale by their publish time compared against the app-monotonic
+/// clock. While the measurement is stale no command is published and the derivative is reset.
+#[derive(Default)]
+pub struct PidController {
+    state: PidState,
+    seq: u64,
+}
+
+/// The internal state of a PID controller
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PidState {
+    integral: f64,
+    previous_measurement: Option<f64>,
+}
+
+/// Result of a single controller update
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PidOutput {
+    /// Clamped command
+    pub command: f64,
+
+    /// True if the command was clamped to the output limits
+    pub is_saturated: bool,
+}
+
+impl PidState {
+    /// Integrated error
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    /// Forgets the previous measurement such that the next update has no derivative term
+    pub fn reset_derivative(&mut self) {
+        self.previous_measurement = None;
+    }
+
+    /// Computes the command for a time step of `dt` seconds
+    pub fn update(
+        &mut self,
+        config: &PidConfig,
+        setpoint: f64,
+        measurement: f64,
+        dt: f64,
+    ) -> PidOutput {
+        let error = setpoint - measurement;
+
+        self.integral =
+            (self.integral + error * dt).clamp(-config.integral_limit, config.integral_limit);
+
+        let derivative = match self.previous_measurement {
+            Some(previous) if dt > 0.0 => -(measurement - previous) / dt,
+            _ => 0.0,
+        };
+        self.previous_measurement = Some(measurement);
+
+        let raw = config.kp * error + config.ki * self.integral + config.kd * derivative;
+        let command = raw.clamp(config.output_min, config.output_max);
+
+        PidOutput {
+            command,
+            is_saturated: command != raw,
+        }
+    }
+}
+
+impl Codelet for PidController {
+    type Status = PidStatus;
+    type Config = PidConfig;
+    type Rx = PidRx;
+    type Tx = DoubleBufferTx<Message<f64>>;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (
+            PidRx {
+                setpoint: DoubleBufferRx::new_latest(),
+                measurement: DoubleBufferRx::new_latest(),
+            },
+            DoubleBufferTx::new(1),
+        )
+    }
+
+    fn start(
+        &mut self,
+        cx: &Context<Self>,
+        _: &mut Self::Rx,
+        _: &mut Self::Tx,
+    ) -> Result<PidStatus> {
+        cx.config.validate()?;
+        self.state = PidState::default();
+        Ok(PidStatus::Waiting)
+    }
+
+    fn step(
+        &mut self,
+        cx: &Context<Self>,
+        rx: &mut Self::Rx,
+        tx: &mut Self::Tx,
+    ) -> Result<PidStatus> {
+        let (Some(setpoint), Some(measurement)) = (rx.setpoint.latest(), rx.measurement.latest())
+        else {
+            return Ok(PidStatus::Waiting);
+        };
+
+        let now = cx.clocks.app_mono.now();
+        if rx
+            .measurement
+            .oldest_age(now)
+            .is_some_and(|age| age > cx.config.stale_timeout)
+        {
+            self.state.reset_derivative();
+            return Ok(PidStatus::StaleMeasurement);
+        }
+
+        let output = self.state.update(
+            cx.config,
+            setpoint.value,
+            measurement.value,
+            cx.clocks.codelet.dt_secs_f32() as f64,
+        );
+
+        self.seq += 1;
+        tx.push(Message {
+            seq: self.seq,
+            stamp: Stamp {
+                acqtime: measurement.stamp.acqtime,
+                pubtime: now,
+            },
+            value: output.command,
+        })?;
+
+        Ok(if output.is_saturated {
+            PidStatus::Saturated
+        } else {
+            PidStatus::Active
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{PidConfig, PidController, PidState};
+    use core::time::Duration;
+    use nodo::{
+        codelet::{
+            Clocks, Lifecycle, NodeletId, NodeletSetup, Transition, Vise, ViseTrait, WorkerId,
+        },
+        prelude::*,
+    };
+
+    fn config() -> PidConfig {
+        PidConfig {
+            kp: 2.0,
+            ki: 0.5,
+            kd: 0.1,
+            output_min: -10.0,
+            output_max: 10.0,
+            integral_limit: 4.0,
+            stale_timeout: Duration::from_millis(50),
+        }
+    }
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn test_step_response() {
+        let config = config();
+        let mut pid = PidState::default();
+
+        // setpoint jumps to 1 with the measurement at 0: no derivative on the first update
+        // P = 2 * 1, I = 0.5 * (1 * 0.1), D = 0
+        let out = pid.update(&config, 1.0, 0.0, 0.1);
+        assert_near(out.command, 2.05);
+        assert!(!out.is_saturated);
+
+        // measurement rises to 0.2
+        // P = 2 * 0.8, I = 0.5 * (0.1 + 0.08), D = 0.1 * -(0.2 / 0.1)
+        let out = pid.update(&config, 1.0, 0.2, 0.1);
+        assert_near(out.command, 1.6 + 0.09 - 0.2);
+
+        // a setpoint change does not cause a derivative kick
+        // P = 2 * 4.8, I = 0.5 * (0.18 + 0.48), D = 0
+        let out = pid.update(&config, 5.0, 0.2, 0.1);
+        assert_near(out.command, 9.6 + 0.33);
+        assert_near(pid.integral(), 0.66);
+    }
+
+    #[test]
+    fn test_anti_windup() {
+        let config = config();
+        let mut pid = PidState::default();
+
+        // a large persistent error saturates the output and the integral stops at its limit
+        for _ in 0..100 {
+            let out = pid.update(&config, 100.0, 0.0, 0.1);
+            assert_eq!(out.command, 10.0);
+            assert!(out.is_saturated);
+        }
+        assert_eq!(pid.integral(), 4.0);
+
+        // once the error reverses the integral unwinds immediately from the limit
+        // P = 2 * -1, I = 0.5 * (4 - 0.1), D = 0.1 * -(1 / 0.1)
+        let out = pid.update(&config, 0.0, 1.0, 0.1);
+        assert_near(out.command, -2.0 + 1.95 - 1.0);
+        assert!(!out.is_saturated);
+    }
+
+    #[test]
+    fn test_config_validation() {
+        assert!(config().validate().is_ok());
+        assert!(PidConfig::default().validate().is_ok());
+        assert!(PidConfig {
+            kp: -1.0,
+            ..config()
+        }
+        .validate()
+        .is_err());
+        assert!(PidConfig {
+            ki: f64::NAN,
+            ..config()
+        }
+        .validate()
+        .is_err());
+        assert!(PidConfig {
+            output_min: 1.0,
+            output_max: 1.0,
+            ..config()
+        }
+        .validate()
+        .is_err());
+        assert!(PidConfig {
+            stale_timeout: Duration::ZERO,
+            ..config()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_status_transitions() {
+        let clocks = Clocks::new();
+        let app_mono = clocks.app_mono.clone();
+
+        // steps run back-to-back with a tiny dt which would amplify the derivative term
+        let config = PidConfig {
+            kd: 0.0,
+            ..config()
+        };
+        let mut instance = PidController::default().into_instance("pid", config);
+        let mut setpoint = DoubleBufferTx::new(1);
+        let mut measurement = DoubleBufferTx::new(1);
+        let mut command = DoubleBufferRx::new_auto_size();
+        setpoint.connect(&mut instance.rx.setpoint).unwrap();
+        measurement.connect(&mut instance.rx.measurement).unwrap();
+        instance.tx.connect(&mut command).unwrap();
+
+        let mut vise = Vise::new(instance);
+        vise.setup(&mut NodeletSetup {
+            clocks,
+            nodelet_id_issue: NodeletId(WorkerId(0), 0),
+        });
+        vise.cycle(Transition::Start).unwrap();
+
+        let publish = |tx: &mut DoubleBufferTx<Message<f64>>, value: f64| {
+            let now = app_mono.now();
+            tx.push(Message {
+                seq: 0,
+                stamp: Stamp {
+                    acqtime: Duration::ZERO.into(),
+                    pubtime: now,
+                },
+                value,
+            })
+            .unwrap();
+            tx.flush();
+        };
+        let mut step = |vise: &mut Vise<PidController>| {
+            vise.cycle(Transition::Step).unwrap();
+            command.sync();
+            (
+                vise.status().unwrap().label,
+                command.pop_all().map(|m| m.value).collect::<Vec<_>>(),
+            )
+        };
+
+        // nothing happens until setpoint and measurement were received
+        assert_eq!(step(&mut vise), ("waiting".into(), vec![]));
+        publish(&mut setpoint, 1.0);
+        assert_eq!(step(&mut vise), ("waiting".into(), vec![]));
+
+        publish(&mut measurement, 0.5);
+        let (label, commands) = step(&mut vise);
+        assert_eq!(label, "active");
+        assert_eq!(commands.len(), 1);
+
+        // the setpoint is kept while the measurement is fresh
+        publish(&mut measurement, 0.6);
+        assert_eq!(step(&mut vise).0, "active");
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(step(&mut vise), ("stale measurement".into(), vec![]));
+
+        // a large error saturates the output
+        publish(&mut setpoint, 100.0);
+        publish(&mut measurement, 0.0);
+        assert_eq!(step(&mut vise), ("saturated".into(), vec![10.0]));
+
+        vise.cycle(Transition::Stop).unwrap();
+    }
+}
@@ -0,0 +1,242 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use nodo::prelude::*;
+use std::collections::BTreeMap;
+
+/// Configuration for [Reorder]
+#[derive(Debug, Clone)]
+pub struct ReorderConfig {
+    /// Maximum time a message is held back waiting for older messages
+    pub max_delay: Duration,
+
+    /// Messages are accepted if their acqtime is at most this much older than the acqtime of the
+    /// newest message which was already released. Accepted late messages are released
+    /// immediately and thus slightly out of order.
+    pub tolerance: Duration,
+}
+
+impl Default for ReorderConfig {
+    fn default() -> Self {
+        Self {
+            max_delay: Duration::from_millis(50),
+            tolerance: Duration::ZERO,
+        }
+    }
+}
+
+/// Releases messages in acqtime order even if they arrive out of order, e.g. from multiple relays
+///
+/// Received messages are buffered for at most `max_delay` (measured with the app clock). All
+/// buffered messages with an acqtime older than the newest received acqtime minus `max_delay`
+/// are released, as are messages which were held back for `max_delay`. Thus a stalled input
+/// still releases buffered messages in time. Messages which arrive older than the newest
+/// released acqtime minus `tolerance` are dropped.
+pub struct Reorder<T> {
+    buffer: BTreeMap<(Duration, u64), (Duration, Message<T>)>,
+    next_index: u64,
+    newest_acqtime: Option<Duration>,
+    newest_released: Option<Duration>,
+    dropped_count: u64,
+}
+
+impl<T> Default for Reorder<T> {
+    fn default() -> Self {
+        Self {
+            buffer: BTreeMap::new(),
+            next_index: 0,
+            newest_acqtime: None,
+            newest_released: None,
+            dropped_count: 0,
+        }
+    }
+}
+
+impl<T> Reorder<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of messages which were dropped because they arrived too late
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped_count
+    }
+
+    /// Number of messages which are currently held back
+    pub fn buffered_count(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Buffers a message which arrived at the given app time. Returns false if it was dropped.
+    fn push(&mut self, arrival: Duration, msg: Message<T>, tolerance: Duration) -> bool {
+        let acqtime = *msg.stamp.acqtime;
+        if self
+            .newest_released
+            .is_some_and(|newest| acqtime + tolerance < newest)
+        {
+            self.dropped_count += 1;
+            return false;
+        }
+
+        self.newest_acqtime = Some(self.newest_acqtime.map_or(acqtime, |t| t.max(acqtime)));
+        self.buffer
+            .insert((acqtime, self.next_index), (arrival, msg));
+        self.next_index += 1;
+        true
+    }
+
+    /// Removes all messages which are due at the given app time in acqtime order
+    fn release(&mut self, now: Duration, max_delay: Duration) -> Vec<Message<T>> {
+        let watermark = self
+            .newest_acqtime
+            .and_then(|newest| newest.checked_sub(max_delay));
+
+        // Messages which waited long enough are released together with all older messages
+        let expired = self
+            .buffer
+            .iter()
+            .filter(|(_, (arrival, _))| *arrival + max_delay <= now)
+            .map(|((acqtime, _), _)| *acqtime)
+            .max();
+
+        // Accepted late messages are released right away
+        let Some(cutoff) = watermark.max(expired).max(self.newest_released) else {
+            return Vec::new();
+        };
+
+        let mut released = Vec::new();
+        while let Some(entry) = self.buffer.first_entry() {
+            if entry.key().0 > cutoff {
+                break;
+            }
+            let (_, msg) = entry.remove();
+            let acqtime = *msg.stamp.acqtime;
+            self.newest_released = Some(self.newest_released.map_or(acqtime, |t| t.max(acqtime)));
+            released.push(msg);
+        }
+        released
+    }
+}
+
+impl<T: Send + Sync + Clone> Codelet for Reorder<T> {
+    type Status = DefaultStatus;
+    type Config = ReorderConfig;
+    type Rx = DoubleBufferRx<Message<T>>;
+    type Tx = DoubleBufferTx<Message<T>>;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (
+            DoubleBufferRx::new_auto_size(),
+            DoubleBufferTx::new_auto_size(),
+        )
+    }
+
+    fn step(&mut self, cx: &Context<Self>, rx: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        let now = *cx.clocks.app_mono.now();
+
+        while let Some(msg) = rx.try_pop() {
+            let acqtime = msg.stamp.acqtime;
+            if !self.push(now, msg, cx.config.tolerance) {
+                log::warn!(
+                    "dropped message with acqtime {acqtime:?} which arrived too late (total: {})",
+                    self.dropped_count
+                );
+            }
+        }
+
+        let released = self.release(now, cx.config.max_delay);
+        if released.is_empty() {
+            return SKIPPED;
+        }
+
+        tx.push_many(released)?;
+        SUCCESS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Reorder;
+    use core::time::Duration;
+    use nodo::prelude::*;
+
+    fn msg(acqtime: u64) -> Message<u64> {
+        Message {
+            seq: acqtime,
+            stamp: Stamp {
+                acqtime: Duration::from_millis(acqtime).into(),
+                pubtime: Duration::ZERO.into(),
+            },
+            value: acqtime,
+        }
+    }
+
+    #[test]
+    fn test_reorder() {
+        let ms = Duration::from_millis;
+        let max_delay = ms(30);
+        let step = ms(10);
+
+        // acqtime and app time of arrival of every message
+        let arrivals = [
+            (10, 10),
+            (30, 12),
+            (20, 14),
+            (50, 30),
+            (40, 31),
+            (5, 40), // older than the released 20 by more than the tolerance
+            (70, 60),
+            (45, 65), // within tolerance of the released 50
+            (60, 75),
+            (80, 82),
+            (90, 95),
+        ];
+
+        let mut reorder = Reorder::new();
+        let mut released = Vec::new();
+        let mut now = ms(0);
+        let mut pending = arrivals.iter().peekable();
+        while now < ms(200) {
+            while let Some(&(acqtime, _)) = pending.next_if(|(_, t)| ms(*t) <= now) {
+                reorder.push(now, msg(acqtime), ms(6));
+            }
+            for m in reorder.release(now, max_delay) {
+                let arrival = arrivals.iter().find(|(a, _)| *a == m.value).unwrap().1;
+                released.push((m.value, now - ms(arrival)));
+            }
+            now += step;
+        }
+
+        let order = released.iter().map(|(v, _)| *v).collect::<Vec<_>>();
+        assert_eq!(order, [10, 20, 30, 40, 50, 45, 60, 70, 80, 90]);
+        assert_eq!(reorder.dropped_count(), 1);
+        assert_eq!(reorder.buffered_count(), 0);
+
+        // the stalled input at the end still releases the buffered messages
+        for (_, latency) in released {
+            assert!(latency <= max_delay + step, "{latency:?}");
+        }
+    }
+
+    #[test]
+    fn test_reorder_drops_late_messages() {
+        let ms = Duration::from_millis;
+
+        let mut reorder = Reorder::new();
+        for acqtime in [40, 10, 30, 20] {
+            assert!(reorder.push(ms(0), msg(acqtime), ms(0)));
+        }
+        let released = reorder.release(ms(100), ms(50));
+        assert_eq!(
+            released.iter().map(|m| m.value).collect::<Vec<_>>(),
+            [10, 20, 30, 40]
+        );
+
+        assert!(!reorder.push(ms(100), msg(39), ms(0)));
+        assert!(!reorder.push(ms(100), msg(25), ms(0)));
+        assert!(reorder.push(ms(100), msg(40), ms(0)));
+        assert!(reorder.push(ms(100), msg(41), ms(0)));
+        assert_eq!(reorder.dropped_count(), 2);
+        assert_eq!(reorder.buffered_count(), 2);
+    }
+}
@@ -0,0 +1,258 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use nodo::{codelet::CountTotal, prelude::*};
+use nodo_core::{EyreResult, Result, WrapErr};
+use std::time::Instant;
+
+/// What [Stages] does when a stage fails
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StageErrorPolicy {
+    /// The message is dropped and processing continues with the next message
+    #[default]
+    DropMessage,
+
+    /// The step fails with the error of the stage
+    FailStep,
+}
+
+/// Configuration for [Stages]
+#[derive(Debug, Default, Clone)]
+pub struct StagesConfig {
+    pub error_policy: StageErrorPolicy,
+}
+
+/// Statistics of a single stage of [Stages]
+#[derive(Debug, Clone)]
+pub struct StageStatistics {
+    pub name: String,
+
+    /// Time spent in the stage per message
+    pub duration: CountTotal,
+
+    /// Number of messages for which the stage failed
+    pub error_count: u64,
+}
+
+#[derive(TxBundleDerive)]
+pub struct StagesTx<T: Clone + Send + Sync> {
+    /// Messages which passed all stages
+    pub output: DoubleBufferTx<Message<T>>,
+
+    /// Statistics of all stages in order. Published after every step which processed messages.
+    pub stats: DoubleBufferTx<Vec<StageStatistics>>,
+}
+
+struct Stage<T> {
+    callback: Box<dyn FnMut(T) -> EyreResult<T> + Send>,
+    stats: StageStatistics,
+}
+
+/// A codelet which applies a chain of named functions to every received message
+///
+/// Every stage is timed individually such that it is visible which part of the chain is slow.
+///
+/// ```
+/// use nodo_std::Stages;
+///
+/// let stages = Stages::<Vec<u8>>::new()
+///     .then("crop", |mut v| {
+///         v.truncate(16);
+///         Ok(v)
+///     })
+///     .then("flip", |mut v| {
+///         v.reverse();
+///         Ok(v)
+///     });
+/// ```
+pub struct Stages<T> {
+    stages: Vec<Stage<T>>,
+}
+
+impl<T> Default for Stages<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Stages<T> {
+    pub fn new() -> Self {
+        Self { stages: Vec::new() }
+    }
+
+    /// Appends a stage which is applied after all previous stages
+    #[must_use]
+    pub fn then<S, F>(mut self, name: S, callback: F) -> Self
+    where
+        S: Into<String>,
+        F: FnMut(T) -> EyreResult<T> + Send + 'static,
+    {
+        self.stages.push(Stage {
+            callback: Box::new(callback),
+            stats: StageStatistics {
+                name: name.into(),
+                duration: CountTotal::default(),
+                error_count: 0,
+            },
+        });
+        self
+    }
+
+    /// Statistics of all stages in order
+    pub fn statistics(&self) -> Vec<StageStatistics> {
+        self.stages.iter().map(|s| s.stats.clone()).collect()
+    }
+
+    /// Applies all stages in order. Returns None if a stage failed and the message was dropped.
+    fn process(&mut self, mut value: T, policy: StageErrorPolicy) -> Result<Option<T>> {
+        for stage in self.stages.iter_mut() {
+            let start = Instant::now();
+            let result = (stage.callback)(value);
+            stage.stats.duration.push(start.elapsed());
+
+            match result {
+                Ok(next) => value = next,
+                Err(err) => {
+                    stage.stats.error_count += 1;
+                    return match policy {
+                        StageErrorPolicy::DropMessage => {
+                            log::warn!("stage '{}' failed: {err:?}", stage.stats.name);
+                            Ok(None)
+                        }
+                        StageErrorPolicy::FailStep => {
+                            Err(err).wrap_err(format!("stage '{}' failed", stage.stats.name))
+                        }
+                    };
+                }
+            }
+        }
+        Ok(Some(value))
+    }
+}
+
+impl<T: Clone + Send + Sync> Codelet for Stages<T> {
+    type Status = DefaultStatus;
+    type Config = StagesConfig;
+    type Rx = DoubleBufferRx<Message<T>>;
+    type Tx = StagesTx<T>;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (
+            DoubleBufferRx::new_auto_size(),
+            StagesTx {
+                output: DoubleBufferTx::new_auto_size(),
+                stats: DoubleBufferTx::new(1),
+            },
+        )
+    }
+
+    fn step(&mut self, cx: &Context<Self>, rx: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        if rx.is_empty() {
+            return SKIPPED;
+        }
+
+        while let Some(msg) = rx.try_pop() {
+            let Message { seq, stamp, value } = msg;
+            if let Some(value) = self.process(value, cx.config.error_policy)? {
+                tx.output.push(Message { seq, stamp, value })?;
+            }
+        }
+
+        tx.stats.push(self.statistics())?;
+
+        SUCCESS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{StageErrorPolicy, Stages};
+    use core::time::Duration;
+    use nodo_core::eyre;
+
+    fn trace(
+        name: &'static str,
+    ) -> impl FnMut(Vec<&'static str>) -> nodo_core::EyreResult<Vec<&'static str>> {
+        move |mut v| {
+            v.push(name);
+            Ok(v)
+        }
+    }
+
+    #[test]
+    fn test_stage_order() {
+        let mut stages = Stages::new()
+            .then("a", trace("a"))
+            .then("b", trace("b"))
+            .then("c", trace("c"));
+
+        let result = stages
+            .process(Vec::new(), StageErrorPolicy::FailStep)
+            .unwrap();
+        assert_eq!(result, Some(vec!["a", "b", "c"]));
+
+        let stats = stages.statistics();
+        assert_eq!(
+            stats.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(),
+            ["a", "b", "c"]
+        );
+        assert!(stats.iter().all(|s| s.duration.count() == 1));
+    }
+
+    #[test]
+    fn test_stage_timing() {
+        let sleep = |ms| {
+            move |x: u32| {
+                std::thread::sleep(Duration::from_millis(ms));
+                Ok(x)
+            }
+        };
+        let mut stages = Stages::new().then("fast", sleep(1)).then("slow", sleep(20));
+
+        for i in 0..3 {
+            stages.process(i, StageErrorPolicy::FailStep).unwrap();
+        }
+
+        let stats = stages.statistics();
+        assert_eq!(stats[0].duration.count(), 3);
+        assert_eq!(stats[1].duration.count(), 3);
+        assert!(stats[0].duration.min_ms().unwrap() >= 1.0);
+        assert!(stats[1].duration.min_ms().unwrap() >= 20.0);
+        assert!(stats[1].duration.average_ms().unwrap() > stats[0].duration.max_ms().unwrap());
+    }
+
+    #[test]
+    fn test_error_policies() {
+        let build = || {
+            Stages::new()
+                .then(
+                    "check",
+                    |x: u32| {
+                        if x % 2 == 0 {
+                            Ok(x)
+                        } else {
+                            Err(eyre!("odd"))
+                        }
+                    },
+                )
+                .then("double", |x| Ok(2 * x))
+        };
+
+        let mut stages = build();
+        assert_eq!(
+            stages.process(2, StageErrorPolicy::DropMessage).unwrap(),
+            Some(4)
+        );
+        assert_eq!(
+            stages.process(3, StageErrorPolicy::DropMessage).unwrap(),
+            None
+        );
+        let stats = stages.statistics();
+        assert_eq!(stats[0].error_count, 1);
+        assert_eq!(stats[1].duration.count(), 1);
+
+        let mut stages = build();
+        let err = stages.process(3, StageErrorPolicy::FailStep).unwrap_err();
+        assert_eq!(err.to_string(), "stage 'check' failed");
+        assert_eq!(stages.statistics()[0].error_count, 1);
+    }
+}
@@ -0,0 +1,321 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use crate::TopicMessage;
+use core::hash::Hasher;
+use nodo::prelude::*;
+use nodo_core::{EyreResult, Topic};
+use twox_hash::XxHash64;
+
+/// Configuration for [StreamDigest]
+#[derive(Debug, Clone)]
+pub struct StreamDigestConfig {
+    /// A checkpoint hash is kept every this many messages to localize divergences
+    pub checkpoint_interval: usize,
+
+    /// Include the sequence number in the hash
+    pub hash_seq: bool,
+
+    /// Include the acquisition time in the hash
+    pub hash_acqtime: bool,
+
+    /// Include the publish time in the hash. Off by default as it depends on scheduling.
+    pub hash_pubtime: bool,
+}
+
+impl Default for StreamDigestConfig {
+    fn default() -> Self {
+        Self {
+            checkpoint_interval: 1024,
+            hash_seq: true,
+            hash_acqtime: true,
+            hash_pubtime: false,
+        }
+    }
+}
+
+/// Order-sensitive digest of all messages received on a topic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicDigest {
+    pub topic: String,
+    pub message_count: usize,
+
+    /// Number of messages between checkpoints
+    pub checkpoint_interval: usize,
+
+    /// Rolling hash after every `checkpoint_interval` messages
+    pub checkpoints: Vec<u64>,
+
+    /// Rolling hash after the last message
+    pub digest: u64,
+}
+
+/// A topic for which two streams diverged, see [compare_digests]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicMismatch {
+    pub topic: String,
+
+    /// Index of the first message of the checkpoint block in which the streams diverged. The
+    /// first differing message is in `first_divergence..first_divergence + checkpoint_interval`.
+    pub first_divergence: usize,
+
+    /// Number of messages in the first stream or None if the topic was not present
+    pub count_a: Option<usize>,
+
+    /// Number of messages in the second stream or None if the topic was not present
+    pub count_b: Option<usize>,
+}
+
+/// Compares the digests of two streams and reports all topics which diverged sorted by topic
+pub fn compare_digests(a: &[TopicDigest], b: &[TopicDigest]) -> Vec<TopicMismatch> {
+    let mut topics: Vec<&str> = a.iter().chain(b.iter()).map(|d| d.topic.as_str()).collect();
+    topics.sort();
+    topics.dedup();
+
+    topics
+        .into_iter()
+        .filter_map(|topic| {
+            let da = a.iter().find(|d| d.topic == topic);
+            let db = b.iter().find(|d| d.topic == topic);
+            let first_divergence = match (da, db) {
+                (Some(da), Some(db)) => first_divergence(da, db)?,
+                _ => 0,
+            };
+            Some(TopicMismatch {
+                topic: topic.into(),
+                first_divergence,
+                count_a: da.map(|d| d.message_count),
+                count_b: db.map(|d| d.message_count),
+            })
+        })
+        .collect()
+}
+
+fn first_divergence(a: &TopicDigest, b: &TopicDigest) -> Option<usize> {
+    if a.checkpoint_interval != b.checkpoint_interval {
+        return Some(0);
+    }
+
+    if let Some(block) = a
+        .checkpoints
+        .iter()
+        .zip(b.checkpoints.iter())
+        .position(|(ca, cb)| ca != cb)
+    {
+        return Some(block * a.checkpoint_interval);
+    }
+
+    if a.message_count != b.message_count || a.digest != b.digest {
+        Some(a.checkpoints.len().min(b.checkpoints.len()) * a.checkpoint_interval)
+    } else {
+        None
+    }
+}
+
+/// Computes a [TopicDigest] for every topic in a stream of messages
+pub struct StreamDigester {
+    config: StreamDigestConfig,
+    topics: Vec<(Topic, XxHash64, TopicDigest)>,
+}
+
+impl StreamDigester {
+    pub fn new(config: StreamDigestConfig) -> Self {
+        Self {
+            config,
+            topics: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, message: &TopicMessage) {
+        let index = match self
+            .topics
+            .iter()
+            .position(|(topic, _, _)| *topic == message.value.topic)
+        {
+            Some(index) => index,
+            None => {
+                self.topics.push((
+                    message.value.topic.clone(),
+                    XxHash64::with_seed(0),
+                    TopicDigest {
+                        topic: String::from(&message.value.topic),
+                        message_count: 0,
+                        checkpoint_interval: self.config.checkpoint_interval,
+                        checkpoints: Vec::new(),
+                        digest: 0,
+                    },
+                ));
+                self.topics.len() - 1
+            }
+        };
+        let (_, hasher, digest) = &mut self.topics[index];
+
+        if self.config.hash_seq {
+            hasher.write_u64(message.seq);
+        }
+        if self.config.hash_acqtime {
+            hasher.write_u128(message.stamp.acqtime.as_nanos());
+        }
+        if self.config.hash_pubtime {
+            hasher.write_u128(message.stamp.pubtime.as_nanos());
+        }
+        hasher.write_usize(message.value.value.len());
+        hasher.write(&message.value.value);
+
+        digest.message_count += 1;
+        digest.digest = hasher.finish();
+        if self.config.checkpoint_interval > 0
+            && digest.message_count % self.config.checkpoint_interval == 0
+        {
+            digest.checkpoints.push(digest.digest);
+        }
+    }
+
+    /// Current digests of all topics sorted by topic
+    pub fn digests(&self) -> Vec<TopicDigest> {
+        let mut digests: Vec<_> = self.topics.iter().map(|(_, _, d)| d.clone()).collect();
+        digests.sort_by(|a, b| a.topic.cmp(&b.topic));
+        digests
+    }
+}
+
+/// Destination for the final digests of a [StreamDigest]
+pub trait DigestSink: Send {
+    fn write_digests(&mut self, digests: Vec<TopicDigest>) -> EyreResult<()>;
+}
+
+impl<F> DigestSink for F
+where
+    F: FnMut(Vec<TopicDigest>) -> EyreResult<()> + Send,
+{
+    fn write_digests(&mut self, digests: Vec<TopicDigest>) -> EyreResult<()> {
+        self(digests)
+    }
+}
+
+/// Computes a rolling hash per topic and writes the final digests to a sink when stopped
+///
+/// Running a deterministic pipeline twice, for example once live and once replayed from a
+/// recording, must produce the same digests. Use [compare_digests] to find which topics diverged
+/// and where.
+pub struct StreamDigest {
+    sink: Box<dyn DigestSink>,
+    digester: Option<StreamDigester>,
+}
+
+impl StreamDigest {
+    pub fn new<S: DigestSink + 'static>(sink: S) -> Self {
+        Self {
+            sink: Box::new(sink),
+            digester: None,
+        }
+    }
+}
+
+impl Codelet for StreamDigest {
+    type Status = DefaultStatus;
+    type Config = StreamDigestConfig;
+    type Rx = DoubleBufferRx<TopicMessage>;
+    type Tx = ();
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (DoubleBufferRx::new_auto_size(), ())
+    }
+
+    fn start(&mut self, cx: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        self.digester = Some(StreamDigester::new(cx.config.clone()));
+        SUCCESS
+    }
+
+    fn step(&mut self, _: &Context<Self>, rx: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        // SAFETY: created in start
+        let digester = self.digester.as_mut().unwrap();
+        if rx.is_empty() {
+            SKIPPED
+        } else {
+            while let Some(msg) = rx.try_pop() {
+                digester.push(&msg);
+            }
+            SUCCESS
+        }
+    }
+
+    fn stop(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
+        if let Some(digester) = self.digester.take() {
+            self.sink.write_digests(digester.digests())?;
+        }
+        SUCCESS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compare_digests, StreamDigestConfig, StreamDigester, TopicMessage};
+    use core::time::Duration;
+    use nodo::prelude::*;
+    use nodo_core::WithTopic;
+
+    fn message(topic: &str, seq: u64, payload: Vec<u8>) -> TopicMessage {
+        Message {
+            seq,
+            stamp: Stamp {
+                acqtime: Duration::from_millis(seq).into(),
+                pubtime: Duration::from_millis(seq).into(),
+            },
+            value: WithTopic {
+                topic: topic.into(),
+                value: payload,
+            },
+        }
+    }
+
+    #[test]
+    fn test_compare_digests() {
+        let config = StreamDigestConfig {
+            checkpoint_interval: 10,
+            ..Default::default()
+        };
+        let mut a = StreamDigester::new(config.clone());
+        let mut b = StreamDigester::new(config);
+        for i in 0..35 {
+            a.push(&message("x", i, vec![i as u8]));
+            b.push(&message("x", i, vec![if i == 23 { 0 } else { i as u8 }]));
+            a.push(&message("y", i, vec![1, 2]));
+            b.push(&message("y", i, vec![1, 2]));
+        }
+        a.push(&message("z", 0, vec![]));
+
+        let a = a.digests();
+        let b = b.digests();
+        assert_eq!(a[0].checkpoints.len(), 3);
+        assert_eq!(a[1], b[1]);
+        assert!(compare_digests(&a, &a).is_empty());
+
+        let mismatches = compare_digests(&a, &b);
+        assert_eq!(mismatches.len(), 2);
+        assert_eq!(mismatches[0].topic, "x");
+        assert_eq!(mismatches[0].first_divergence, 20);
+        assert_eq!(mismatches[1].topic, "z");
+        assert_eq!(mismatches[1].count_a, Some(1));
+        assert_eq!(mismatches[1].count_b, None);
+    }
+
+    #[test]
+    fn test_divergence_after_last_checkpoint() {
+        let config = StreamDigestConfig {
+            checkpoint_interval: 10,
+            ..Default::default()
+        };
+        let mut a = StreamDigester::new(config.clone());
+        let mut b = StreamDigester::new(config);
+        for i in 0..25 {
+            a.push(&message("x", i, vec![]));
+            b.push(&message("x", i, vec![]));
+        }
+        b.push(&message("x", 25, vec![]));
+
+        let mismatches = compare_digests(&a.digests(), &b.digests());
+        assert_eq!(mismatches[0].first_divergence, 20);
+        assert_eq!(mismatches[0].count_a, Some(25));
+        assert_eq!(mismatches[0].count_b, Some(26));
+    }
+}
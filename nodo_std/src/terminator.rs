@@ -5,15 +5,14 @@ use nodo::prelude::*;
 /// Terminates after certain number of steps.
 pub struct Terminator {
     countdown: usize,
-    tx_control: std::sync::mpsc::SyncSender<RuntimeControl>,
+    control: ControlHandle,
 }
 
 impl Terminator {
-    pub fn new(countdown: usize, tx_control: std::sync::mpsc::SyncSender<RuntimeControl>) -> Self {
-        Self {
-            countdown,
-            tx_control,
-        }
+    /// The handle must be obtained via `Runtime::control_handle` such that the runtime observes
+    /// the stop request even if its control channel is full.
+    pub fn new(countdown: usize, control: ControlHandle) -> Self {
+        Self { countdown, control }
     }
 }
 
@@ -29,7 +28,7 @@ impl Codelet for Terminator {
 
     fn step(&mut self, _: &Context<Self>, _: &mut Self::Rx, _: &mut Self::Tx) -> Outcome {
         if self.countdown == 0 {
-            self.tx_control.send(RuntimeControl::RequestStop)?;
+            self.control.request_stop()?;
             SUCCESS
         } else {
             self.countdown -= 1;
@@ -0,0 +1,293 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use nodo::prelude::*;
+use nodo_core::{ensure, Result};
+
+/// When a timer of the [Timer] codelet fires. All times are relative to the start of the codelet.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimerSpec {
+    /// Fires once after the given duration
+    OneShot { after: Duration },
+
+    /// Fires at `offset + k * interval` for k = 0, 1, 2, ...
+    Repeating {
+        interval: Duration,
+        offset: Duration,
+    },
+}
+
+/// How to handle triggers which were missed because the codelet was not stepped in time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissedPolicy {
+    /// Fire a single event for the most recent missed trigger
+    #[default]
+    FireOnce,
+
+    /// Fire one event for every missed trigger
+    FireAll,
+
+    /// Drop missed triggers and wait for the next one
+    Skip,
+}
+
+/// Configuration for [Timer]
+#[derive(Debug, Clone, Default)]
+pub struct TimerConfig {
+    pub specs: Vec<TimerSpec>,
+    pub missed_policy: MissedPolicy,
+}
+
+/// Event published when a timer fires
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimerEvent {
+    /// Index of the timer in `TimerConfig::specs`
+    pub id: usize,
+
+    /// App-monotonic time at which the timer was supposed to fire
+    pub scheduled_for: Duration,
+
+    /// App-monotonic time at which the event was published
+    pub fired_at: Duration,
+}
+
+#[derive(Status)]
+pub enum TimerStatus {
+    #[default]
+    #[skipped]
+    #[label = "idle"]
+    Idle,
+
+    /// The given number of events was published
+    #[label = "fired"]
+    Fired(usize),
+
+    /// All timers are one-shots which already fired
+    #[skipped]
+    #[label = "done"]
+    Done,
+}
+
+/// Publishes events at specific times after the codelet was started
+///
+/// Timers are checked against the app-monotonic clock every step. Events are thus published with
+/// a delay of up to one schedule period after `scheduled_for`. If more than one trigger of a
+/// repeating timer passed since the last step, the triggers which were passed over are handled
+/// according to the `missed_policy`.
+#[derive(Default)]
+pub struct Timer {
+    next: Vec<Option<Duration>>,
+}
+
+impl Timer {
+    fn reset(&mut self, start: Duration, config: &TimerConfig) -> Result<()> {
+        self.next.clear();
+        for (id, spec) in config.specs.iter().enumerate() {
+            self.next.push(Some(match spec {
+                TimerSpec::OneShot { after } => start + *after,
+                TimerSpec::Repeating { interval, offset } => {
+                    ensure!(!interval.is_zero(), "timer {id} has zero interval");
+                    start + *offset
+                }
+            }));
+        }
+        Ok(())
+    }
+
+    fn poll(&mut self, now: Duration, config: &TimerConfig) -> Vec<TimerEvent> {
+        let mut events = Vec::new();
+
+        for (id, (spec, next)) in config.specs.iter().zip(self.next.iter_mut()).enumerate() {
+            let Some(scheduled_for) = *next else {
+                continue;
+            };
+            if scheduled_for > now {
+                continue;
+            }
+
+            let interval = match spec {
+                TimerSpec::OneShot { .. } => {
+                    events.push(TimerEvent {
+                        id,
+                        scheduled_for,
+                        fired_at: now,
+                    });
+                    *next = None;
+                    continue;
+                }
+                TimerSpec::Repeating { interval, .. } => *interval,
+            };
+
+            // number of triggers which passed since the last step
+            let count = 1 + ((now - scheduled_for).as_nanos() / interval.as_nanos()) as u32;
+            let latest = scheduled_for + interval * (count - 1);
+            *next = Some(latest + interval);
+
+            let fired = match config.missed_policy {
+                MissedPolicy::FireAll => 0..count,
+                MissedPolicy::FireOnce => (count - 1)..count,
+                MissedPolicy::Skip if count == 1 => 0..1,
+                MissedPolicy::Skip => 0..0,
+            };
+            events.extend(fired.map(|k| TimerEvent {
+                id,
+                scheduled_for: scheduled_for + interval * k,
+                fired_at: now,
+            }));
+        }
+
+        events
+    }
+}
+
+impl Codelet for Timer {
+    type Status = TimerStatus;
+    type Config = TimerConfig;
+    type Rx = ();
+    type Tx = DoubleBufferTx<Message<TimerEvent>>;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        ((), DoubleBufferTx::new_auto_size())
+    }
+
+    fn start(
+        &mut self,
+        cx: &Context<Self>,
+        _: &mut Self::Rx,
+        _: &mut Self::Tx,
+    ) -> Result<TimerStatus> {
+        self.reset(*cx.clocks.app_mono.now(), cx.config)?;
+        Ok(TimerStatus::Idle)
+    }
+
+    fn step(
+        &mut self,
+        cx: &Context<Self>,
+        _: &mut Self::Rx,
+        tx: &mut Self::Tx,
+    ) -> Result<TimerStatus> {
+        let pubtime = cx.clocks.app_mono.now();
+        let events = self.poll(*pubtime, cx.config);
+        let count = events.len();
+
+        for event in events {
+            tx.push(Message {
+                seq: 0,
+                stamp: Stamp {
+                    acqtime: cx.clocks.sys_mono.now(),
+                    pubtime,
+                },
+                value: event,
+            })?;
+        }
+
+        Ok(if count > 0 {
+            TimerStatus::Fired(count)
+        } else if self.next.iter().all(Option::is_none) {
+            TimerStatus::Done
+        } else {
+            TimerStatus::Idle
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MissedPolicy, Timer, TimerConfig, TimerEvent, TimerSpec};
+    use core::time::Duration;
+
+    fn ms(x: u64) -> Duration {
+        Duration::from_millis(x)
+    }
+
+    fn timer(specs: Vec<TimerSpec>, missed_policy: MissedPolicy) -> (Timer, TimerConfig) {
+        let config = TimerConfig {
+            specs,
+            missed_policy,
+        };
+        let mut timer = Timer::default();
+        timer.reset(ms(1000), &config).unwrap();
+        (timer, config)
+    }
+
+    fn scheduled(events: Vec<TimerEvent>) -> Vec<(usize, Duration)> {
+        events
+            .into_iter()
+            .map(|e| (e.id, e.scheduled_for))
+            .collect()
+    }
+
+    fn repeating() -> Vec<TimerSpec> {
+        vec![TimerSpec::Repeating {
+            interval: ms(100),
+            offset: ms(50),
+        }]
+    }
+
+    #[test]
+    fn test_one_shot() {
+        let (mut timer, config) = timer(
+            vec![TimerSpec::OneShot { after: ms(500) }],
+            MissedPolicy::FireOnce,
+        );
+
+        assert!(timer.poll(ms(1499), &config).is_empty());
+        assert_eq!(
+            timer.poll(ms(1520), &config),
+            vec![TimerEvent {
+                id: 0,
+                scheduled_for: ms(1500),
+                fired_at: ms(1520),
+            }]
+        );
+        assert!(timer.poll(ms(5000), &config).is_empty());
+    }
+
+    #[test]
+    fn test_repeating_with_offset() {
+        let (mut timer, config) = timer(repeating(), MissedPolicy::FireOnce);
+
+        assert!(timer.poll(ms(1040), &config).is_empty());
+        assert_eq!(scheduled(timer.poll(ms(1050), &config)), [(0, ms(1050))]);
+        assert!(timer.poll(ms(1100), &config).is_empty());
+        assert_eq!(scheduled(timer.poll(ms(1160), &config)), [(0, ms(1150))]);
+        assert_eq!(scheduled(timer.poll(ms(1250), &config)), [(0, ms(1250))]);
+    }
+
+    #[test]
+    fn test_missed_fire_once() {
+        let (mut timer, config) = timer(repeating(), MissedPolicy::FireOnce);
+        assert_eq!(scheduled(timer.poll(ms(1370), &config)), [(0, ms(1350))]);
+        assert_eq!(scheduled(timer.poll(ms(1450), &config)), [(0, ms(1450))]);
+    }
+
+    #[test]
+    fn test_missed_fire_all() {
+        let (mut timer, config) = timer(repeating(), MissedPolicy::FireAll);
+        assert_eq!(
+            scheduled(timer.poll(ms(1370), &config)),
+            [(0, ms(1050)), (0, ms(1150)), (0, ms(1250)), (0, ms(1350))]
+        );
+        assert_eq!(scheduled(timer.poll(ms(1450), &config)), [(0, ms(1450))]);
+    }
+
+    #[test]
+    fn test_missed_skip() {
+        let (mut timer, config) = timer(repeating(), MissedPolicy::Skip);
+        assert!(timer.poll(ms(1370), &config).is_empty());
+        assert!(timer.poll(ms(1440), &config).is_empty());
+        assert_eq!(scheduled(timer.poll(ms(1460), &config)), [(0, ms(1450))]);
+    }
+
+    #[test]
+    fn test_zero_interval_is_rejected() {
+        let config = TimerConfig {
+            specs: vec![TimerSpec::Repeating {
+                interval: Duration::ZERO,
+                offset: Duration::ZERO,
+            }],
+            ..Default::default()
+        };
+        assert!(Timer::default().reset(ms(0), &config).is_err());
+    }
+}
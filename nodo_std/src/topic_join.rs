@@ -7,6 +7,7 @@ use nodo::{
     prelude::*,
 };
 use nodo_core::{Topic, WithTopic};
+use std::borrow::Cow;
 
 /// Join has multiple input channels and a single output channel. All messages received on any
 /// input channel are sent to the output channel. There is no particular guarantee on the order
@@ -53,6 +54,11 @@ where
     }
 }
 
+/// Error returned when a topic is added twice to a [TopicJoinRx]
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("topic '{0}' was already added to the topic join")]
+pub struct DuplicateTopicError(pub String);
+
 pub struct TopicJoinRx<T> {
     channels: Vec<(Topic, DoubleBufferRx<T>)>,
 }
@@ -74,11 +80,27 @@ impl<T> TopicJoinRx<T> {
             .map(|(_, value)| value)
     }
 
-    /// Add a new input channel and return it
-    pub fn add(&mut self, topic: Topic) -> &mut DoubleBufferRx<T> {
+    /// Add a new input channel and return it. Fails if the topic was already added.
+    pub fn add(&mut self, topic: Topic) -> Result<&mut DoubleBufferRx<T>, DuplicateTopicError> {
+        if self.channels.iter().any(|(key, _)| *key == topic) {
+            return Err(DuplicateTopicError((&topic).into()));
+        }
+        Ok(self.add_source(topic))
+    }
+
+    /// Add another input channel for a topic which may already have input channels and return it
+    ///
+    /// Messages from all input channels of a topic are interleaved on the output channel in no
+    /// particular order.
+    pub fn add_source(&mut self, topic: Topic) -> &mut DoubleBufferRx<T> {
         self.channels.push((topic, DoubleBufferRx::new_auto_size()));
         &mut self.channels.last_mut().unwrap().1
     }
+
+    /// Number of input channels for the given topic
+    pub fn source_count(&self, topic: &Topic) -> usize {
+        self.channels.iter().filter(|(key, _)| key == topic).count()
+    }
 }
 
 impl<T: Send + Sync> RxBundle for TopicJoinRx<T> {
@@ -86,9 +108,9 @@ impl<T: Send + Sync> RxBundle for TopicJoinRx<T> {
         self.channels.len()
     }
 
-    fn name(&self, index: usize) -> String {
+    fn name(&self, index: usize) -> Cow<'static, str> {
         if index < self.channels.len() {
-            format!("input_{index}")
+            Cow::Owned(format!("input_{index}"))
         } else {
             panic!(
                 "invalid index '{index}': number of channels is {}",
@@ -110,4 +132,31 @@ impl<T: Send + Sync> RxBundle for TopicJoinRx<T> {
         }
         cc
     }
+
+    fn message_type_hash(&self, index: usize) -> Option<u64> {
+        Rx::message_type_hash(&self.channels[index].1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DuplicateTopicError, TopicJoinRx};
+
+    #[test]
+    fn test_duplicate_topic() {
+        let mut rx = TopicJoinRx::<u32>::default();
+        rx.add("pose".into()).unwrap();
+        rx.add("image".into()).unwrap();
+
+        assert_eq!(
+            rx.add("pose".into()).err(),
+            Some(DuplicateTopicError("pose".into()))
+        );
+        assert_eq!(rx.source_count(&"pose".into()), 1);
+
+        // additional sources are explicit
+        rx.add_source("pose".into());
+        assert_eq!(rx.source_count(&"pose".into()), 2);
+        assert_eq!(rx.source_count(&"image".into()), 1);
+    }
 }
@@ -3,6 +3,7 @@
 use core::marker::PhantomData;
 use nodo::{channels::FlushResult, codelet::Context, prelude::*};
 use nodo_core::{Topic, WithTopic};
+use std::borrow::Cow;
 
 /// Reroutes 'WithTopic' messages based on their topic to the right receiver.
 pub struct TopicSplit<T> {
@@ -75,8 +76,8 @@ impl<T: Send + Sync + Clone> nodo::channels::TxBundle for TopicSplitTx<T> {
         self.channels.len()
     }
 
-    fn name(&self, index: usize) -> String {
-        (&self.channels[index].0).into()
+    fn name(&self, index: usize) -> Cow<'static, str> {
+        Cow::Owned((&self.channels[index].0).into())
     }
 
     fn flush_all(&mut self, result: &mut [FlushResult]) {
@@ -93,4 +94,8 @@ impl<T: Send + Sync + Clone> nodo::channels::TxBundle for TopicSplitTx<T> {
         }
         cc
     }
+
+    fn message_type_hash(&self, index: usize) -> Option<u64> {
+        Tx::message_type_hash(&self.channels[index].1)
+    }
 }
@@ -0,0 +1,168 @@
+// Copyright 2024 by David Weikersdorfer. All rights reserved.
+
+use core::time::Duration;
+use nodo::{channels::Welford, prelude::*};
+use std::collections::VecDeque;
+
+/// Configuration for [WindowedStats]
+#[derive(Debug, Clone)]
+pub struct WindowedStatsConfig {
+    /// Duration of the sliding window
+    pub window: Duration,
+
+    /// Time between two published samples
+    pub interval: Duration,
+}
+
+impl Default for WindowedStatsConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(1),
+            interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Statistics over the values received in a time window
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatsSample {
+    pub mean: f64,
+    pub var: f64,
+    pub min: f64,
+    pub max: f64,
+    pub count: usize,
+    pub window: Duration,
+}
+
+/// Computes sliding-window statistics over received messages and publishes them periodically
+///
+/// The window is based on the acquisition time of messages. No sample is published if there are
+/// no messages in the window.
+pub struct WindowedStats<T> {
+    projection: Box<dyn Fn(&T) -> f64 + Send>,
+    history: VecDeque<(Duration, f64)>,
+    last_publish: Option<Duration>,
+}
+
+impl WindowedStats<f64> {
+    pub fn new() -> Self {
+        Self::with_projection(|x: &f64| *x)
+    }
+}
+
+impl Default for WindowedStats<f64> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> WindowedStats<T> {
+    /// Creates a codelet which computes statistics over a value derived from each message
+    pub fn with_projection<F>(projection: F) -> Self
+    where
+        F: Fn(&T) -> f64 + Send + 'static,
+    {
+        Self {
+            projection: Box::new(projection),
+            history: VecDeque::new(),
+            last_publish: None,
+        }
+    }
+
+    fn push(&mut self, time: Duration, value: &T) {
+        self.history.push_back((time, (self.projection)(value)));
+    }
+
+    /// Removes samples which are older than the window and computes stats over the rest
+    fn compute(&mut self, now: Duration, window: Duration) -> Option<StatsSample> {
+        let start = now.saturating_sub(window);
+        while self.history.front().is_some_and(|(t, _)| *t < start) {
+            self.history.pop_front();
+        }
+
+        let mut acc = Welford::new();
+        for (_, x) in self.history.iter() {
+            acc.push(*x);
+        }
+
+        acc.stats().map(|s| StatsSample {
+            mean: s.mean,
+            var: s.var,
+            min: s.min,
+            max: s.max,
+            count: s.count,
+            window,
+        })
+    }
+}
+
+impl<T: Send + Sync> Codelet for WindowedStats<T> {
+    type Status = DefaultStatus;
+    type Config = WindowedStatsConfig;
+    type Rx = DoubleBufferRx<Message<T>>;
+    type Tx = DoubleBufferTx<Message<StatsSample>>;
+
+    fn build_bundles(_: &Self::Config) -> (Self::Rx, Self::Tx) {
+        (DoubleBufferRx::new_auto_size(), DoubleBufferTx::new(1))
+    }
+
+    fn step(&mut self, cx: &Context<Self>, rx: &mut Self::Rx, tx: &mut Self::Tx) -> Outcome {
+        while let Some(msg) = rx.try_pop() {
+            self.push(*msg.stamp.acqtime, &msg.value);
+        }
+
+        let now = cx.clocks.sys_mono.now();
+        if let Some(last) = self.last_publish {
+            if *now < last + cx.config.interval {
+                return SKIPPED;
+            }
+        }
+
+        match self.compute(*now, cx.config.window) {
+            Some(sample) => {
+                self.last_publish = Some(*now);
+                tx.push(Message {
+                    seq: 0,
+                    stamp: Stamp {
+                        acqtime: now,
+                        pubtime: cx.clocks.app_mono.now(),
+                    },
+                    value: sample,
+                })?;
+                SUCCESS
+            }
+            None => SKIPPED,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::WindowedStats;
+    use core::time::Duration;
+
+    #[test]
+    fn test_windowed_stats() {
+        let ms = Duration::from_millis;
+
+        let mut ws = WindowedStats::with_projection(|x: &i32| *x as f64);
+        assert_eq!(ws.compute(ms(100), ms(50)), None);
+
+        ws.push(ms(10), &1);
+        ws.push(ms(20), &3);
+        ws.push(ms(30), &8);
+        ws.push(ms(40), &4);
+
+        let sample = ws.compute(ms(50), ms(30)).unwrap();
+        assert_eq!(sample.count, 3);
+        assert_eq!(sample.mean, 5.0);
+        assert_eq!(sample.var, 14.0 / 3.0);
+        assert_eq!(sample.min, 3.0);
+        assert_eq!(sample.max, 8.0);
+        assert_eq!(sample.window, ms(30));
+
+        // all samples are older than the window
+        assert_eq!(ws.compute(ms(100), ms(30)), None);
+        assert!(ws.history.is_empty());
+    }
+}